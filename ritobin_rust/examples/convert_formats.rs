@@ -1,10 +1,15 @@
 //! Example: Convert between different formats
 //!
-//! This example demonstrates converting between bin, text, and JSON formats.
+//! This example demonstrates converting between bin, text, and JSON formats,
+//! with writer options set on the registered formats.
 
-use ritobin_rust::{binary, json, text};
+use ritobin_rust::format::{FormatRegistry, JsonFormat, TextFormat};
+use ritobin_rust::json::JsonWriteOptions;
+use ritobin_rust::text::TextWriteOptions;
 use std::error::Error;
 use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = std::env::args().collect();
@@ -26,35 +31,35 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     println!("Converting: {} -> {}", input_path, output_path);
 
-    // Read input
-    let bin = if input_path.ends_with(".bin") {
-        let data = fs::read(input_path)?;
-        binary::read_bin(&data)?
-    } else if input_path.ends_with(".py") {
-        let text = fs::read_to_string(input_path)?;
-        text::read_text(&text)?
-    } else if input_path.ends_with(".json") {
-        let json_str = fs::read_to_string(input_path)?;
-        json::read_json(&json_str)?
-    } else {
-        return Err("Unknown input format. Use .bin, .py, or .json".into());
+    // Registering a format under an existing name replaces it
+    let mut formats = FormatRegistry::with_defaults();
+    formats.register(TextFormat {
+        write_options: TextWriteOptions { indent_width: 4, ..Default::default() },
+        ..Default::default()
+    });
+    formats.register(JsonFormat {
+        write_options: JsonWriteOptions { hex_hashes: true },
+        ..Default::default()
+    });
+    let format_of = |path: &str| {
+        Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| formats.by_extension(e))
     };
 
+    // Read input
+    let input_format = format_of(input_path).ok_or("Unknown input format. Use .bin, .py, or .json")?;
+    let bin = input_format.read(&fs::read(input_path)?)?;
+
     println!("✓ Read input file ({} sections)", bin.sections.len());
 
     // Write output
-    if output_path.ends_with(".bin") {
-        let bytes = binary::write_bin(&bin)?;
-        fs::write(output_path, bytes)?;
-    } else if output_path.ends_with(".py") {
-        let text = text::write_text(&bin)?;
-        fs::write(output_path, text)?;
-    } else if output_path.ends_with(".json") {
-        let json_str = json::write_json(&bin)?;
-        fs::write(output_path, json_str)?;
-    } else {
-        return Err("Unknown output format. Use .bin, .py, or .json".into());
-    }
+    let output_format = format_of(output_path).ok_or("Unknown output format. Use .bin, .py, or .json")?;
+    // Streamed, so the output isn't built in memory first
+    let mut out = BufWriter::new(fs::File::create(output_path)?);
+    output_format.write_to(&bin, &mut out)?;
+    out.flush()?;
 
     println!("✓ Wrote output file: {}", output_path);
     Ok(())
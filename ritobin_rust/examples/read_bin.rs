@@ -25,6 +25,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Print file statistics
     println!("\n=== Bin File Statistics ===");
     println!("Sections: {}", bin.sections.len());
+    println!("Entries: {}", bin.entry_count());
     
     // Show all section names
     println!("\n=== Sections ===");
@@ -40,7 +40,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut loaded_count = 0;
     for file in hash_files {
-        let path = format!("{}/{}", hash_dir, file);
+        let path = std::path::Path::new(hash_dir).join(file);
         // load_auto tries .bin first, then .txt
         if let Ok(()) = unhasher.load_auto(&path) {
             println!("  ✓ Loaded: {}", file);
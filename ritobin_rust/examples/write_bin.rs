@@ -3,7 +3,8 @@
 //! This example shows how to create a bin file from scratch and write it.
 
 use ritobin_rust::binary::write_bin;
-use ritobin_rust::model::{Bin, BinValue};
+use ritobin_rust::hash::fnv1a;
+use ritobin_rust::model::{Bin, BinType, BinValue, Field};
 use std::error::Error;
 use std::fs;
 
@@ -13,34 +14,44 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Create a new bin file
     let mut bin = Bin::new();
 
-    // Add sections
+    // Add the header sections
     bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
     bin.sections.insert("version".to_string(), BinValue::U32(3));
-    bin.sections.insert("name".to_string(), BinValue::String("ExampleChampion".to_string()));
-    bin.sections.insert("hp".to_string(), BinValue::F32(580.0));
-    bin.sections.insert("mana".to_string(), BinValue::F32(350.0));
 
-    // Add a vector
+    // Values live in the fields of entries; names are stored as FNV1a hashes
+    let field = |name: &str, value| Field { key: fnv1a(name), key_str: Some(name.to_string()), value };
+    let champion = BinValue::Embed {
+        name: fnv1a("ExampleChampionData"),
+        name_str: Some("ExampleChampionData".to_string()),
+        items: vec![
+            field("name", BinValue::String("ExampleChampion".to_string())),
+            field("hp", BinValue::F32(580.0)),
+            field("mana", BinValue::F32(350.0)),
+            // A vector
+            field("position", BinValue::Vec3([100.0, 200.0, 300.0])),
+            // A list
+            field("abilities", BinValue::List {
+                value_type: BinType::String,
+                items: vec![
+                    BinValue::String("Q - Ability1".to_string()),
+                    BinValue::String("W - Ability2".to_string()),
+                    BinValue::String("E - Ability3".to_string()),
+                    BinValue::String("R - Ultimate".to_string()),
+                ],
+            }),
+        ],
+    };
+    let path = "Characters/ExampleChampion/CharacterRecords/Root";
     bin.sections.insert(
-        "position".to_string(),
-        BinValue::Vec3([100.0, 200.0, 300.0]),
-    );
-
-    // Add a list
-    bin.sections.insert(
-        "abilities".to_string(),
-        BinValue::List {
-            value_type: ritobin_rust::model::BinType::String,
-            items: vec![
-                BinValue::String("Q - Ability1".to_string()),
-                BinValue::String("W - Ability2".to_string()),
-                BinValue::String("E - Ability3".to_string()),
-                BinValue::String("R - Ultimate".to_string()),
-            ],
+        "entries".to_string(),
+        BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: fnv1a(path), name: Some(path.to_string()) }, champion)],
         },
     );
 
-    println!("Created bin with {} sections", bin.sections.len());
+    println!("Created bin with {} entries", bin.entry_count());
 
     // Write to file
     let output_path = "example_output.bin";
@@ -0,0 +1,205 @@
+//! User annotations kept in a `.meta.json` sidecar next to a bin.
+//!
+//! Notes, TODOs and tags are keyed by [`ValuePath`] strings such as
+//! `entries[Characters/Ahri/CharacterRecords/Root].baseHP`, so they never
+//! touch the game data itself. Paths are resolved against a bin with
+//! [`Annotations::resolve`]; names and `0x` hashes both work, as in
+//! [`edit`](crate::edit). The text writer can show them as comments through
+//! [`write_text_annotated`](crate::text::write_text_annotated).
+//!
+//! The sidecar is a JSON object from path to annotation:
+//!
+//! ```json
+//! {
+//!   "entries[Characters/Ahri/CharacterRecords/Root].baseHP": {
+//!     "note": "Matches the 14.3 patch notes",
+//!     "todos": ["Check against live"],
+//!     "tags": ["balance"]
+//!   }
+//! }
+//! ```
+
+use crate::edit::ValuePath;
+use crate::model::{Bin, BinValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Notes attached to one value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub todos: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_none() && self.todos.is_empty() && self.tags.is_empty()
+    }
+
+    /// The annotation as comment lines, without the leading `#`
+    pub fn comment_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.note.iter().flat_map(|n| n.lines()).map(String::from).collect();
+        for todo in &self.todos {
+            labelled_lines("TODO: ", todo, &mut lines);
+        }
+        if !self.tags.is_empty() {
+            labelled_lines("tags: ", &self.tags.join(", "), &mut lines);
+        }
+        lines
+    }
+}
+
+/// Push `text` line by line, the first after `label` and the rest lined up under it
+fn labelled_lines(label: &str, text: &str, lines: &mut Vec<String>) {
+    let mut text_lines = text.lines();
+    lines.push(format!("{}{}", label, text_lines.next().unwrap_or("")));
+    lines.extend(text_lines.map(|line| format!("{:width$}{}", "", line, width = label.len())));
+}
+
+/// All annotations of one bin, in path order.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::annotations::Annotations;
+///
+/// let mut notes = Annotations::new();
+/// notes.entry("entries[Characters/Ahri/CharacterRecords/Root].baseHP".parse()?).tags.push("balance".to_string());
+/// let json = notes.to_json()?;
+/// assert_eq!(Annotations::from_json(&json)?, notes);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Annotations {
+    items: BTreeMap<String, Annotation>,
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sidecar location for a bin: `champion.bin` -> `champion.meta.json`
+    pub fn sidecar_path(bin_path: &Path) -> PathBuf {
+        bin_path.with_extension("meta.json")
+    }
+
+    /// Load the sidecar of `bin_path`, if it has one
+    pub fn load_sidecar(bin_path: &Path) -> Result<Option<Self>, String> {
+        let path = Self::sidecar_path(bin_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(&path).map(Some)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_json(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write to `path`, or delete it when there are no annotations left
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if self.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("{}: {}", path.display(), e)),
+                _ => Ok(()),
+            };
+        }
+        std::fs::write(path, self.to_json()?).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Parse a sidecar, checking that every key is a valid path
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        let notes: Self = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        for path in notes.items.keys() {
+            path.parse::<ValuePath>()?;
+        }
+        Ok(notes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The annotation stored under exactly `path`
+    pub fn get(&self, path: &ValuePath) -> Option<&Annotation> {
+        self.items.get(&path.to_string())
+    }
+
+    /// The annotation for `path`, added empty if missing
+    pub fn entry(&mut self, path: ValuePath) -> &mut Annotation {
+        self.items.entry(path.to_string()).or_default()
+    }
+
+    pub fn remove(&mut self, path: &ValuePath) -> Option<Annotation> {
+        self.items.remove(&path.to_string())
+    }
+
+    /// All annotations with their paths
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Annotation)> {
+        self.items.iter().map(|(path, a)| (path.as_str(), a))
+    }
+
+    /// Each annotation with the value its path points to in `bin`, if any
+    pub fn resolve<'a>(&'a self, bin: &'a Bin) -> impl Iterator<Item = (&'a str, &'a Annotation, Option<&'a BinValue>)> {
+        self.iter().map(move |(path, a)| {
+            let value = path.parse::<ValuePath>().ok().and_then(|p| p.get(bin));
+            (path, a, value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BinType, Field};
+
+    #[test]
+    fn test_annotations_in_text_output() {
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: crate::hash::fnv1a("Root"), name: Some("Root".to_string()) },
+                BinValue::Embed { name: 1, name_str: None, items: vec![
+                    Field { key: crate::hash::fnv1a("baseHP"), key_str: None, value: BinValue::F32(650.0) },
+                ] },
+            )],
+        });
+
+        let mut notes = Annotations::new();
+        let hp = notes.entry("entries[Root].baseHP".parse().unwrap());
+        hp.note = Some("From the patch notes".to_string());
+        hp.todos.push("Verify".to_string());
+        hp.todos.push("Compare with\nlive".to_string());
+        hp.tags.push("balance\nhp".to_string());
+        notes.entry("entries[Missing]".parse().unwrap()).tags.push("stale".to_string());
+
+        let resolved: Vec<_> = notes.resolve(&bin).map(|(_, _, v)| v.is_some()).collect();
+        assert_eq!(resolved, vec![false, true]);
+
+        let text = crate::text::write_text_annotated(&bin, &Default::default(), &notes).unwrap();
+        assert!(text.contains(concat!(
+            "    # From the patch notes\n    # TODO: Verify\n    # TODO: Compare with\n    #       live\n",
+            "    # tags: balance\n    #       hp\n    0x",
+        )));
+        assert!(text.contains("# entries[Missing] (not found): tags: stale\n"));
+        assert_eq!(crate::text::read_text(&text).unwrap(), crate::text::read_text(&crate::text::write_text(&bin).unwrap()).unwrap());
+    }
+}
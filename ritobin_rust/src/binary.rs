@@ -2,8 +2,13 @@ use crate::model::{Bin, BinType, BinValue, Field};
 use byteorder::{ReadBytesExt, LE};
 use std::convert::TryFrom;
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+pub mod patch;
+pub mod events;
+pub mod borrowed;
+
 #[derive(Error, Debug)]
 pub enum BinError {
     #[error("IO error: {0}")]
@@ -12,88 +17,544 @@ pub enum BinError {
     InvalidMagic,
     #[error("Unknown type: {0}")]
     UnknownType(u8),
-    #[error("Unexpected end of file")]
-    UnexpectedEof,
+    /// The data ended in the middle of a value; [`read_bin_checked`] reports
+    /// every truncation this way
+    #[error("Unexpected end of file at offset {at_offset} while reading {while_reading} ({entries_recovered} complete entries before it)")]
+    UnexpectedEof { at_offset: u64, while_reading: String, entries_recovered: usize },
     #[error("Invalid value for type {0:?}")]
     InvalidValue(BinType),
+    #[error("Written data does not read back as the source{}", match .0.first() {
+        Some(first) => format!(" ({} differences, first at {}: {})", .0.len(), first.path, first.message),
+        None => String::new(),
+    })]
+    RoundTripMismatch(Vec<crate::compare::Divergence>),
+    #[error("Size mismatch at offset {offset}: declared {declared} bytes, read {read}")]
+    SizeMismatch { offset: u64, declared: u32, read: u64 },
+    #[error("Trailing data: {0} unread bytes after the last section")]
+    TrailingData(u64),
+    #[error("Nesting deeper than {0} levels")]
+    TooDeep(usize),
+    /// Reading took longer than [`ReadOptions::time_limit`]
+    #[error("Reading took longer than {0:?}")]
+    TimedOut(Duration),
+    /// A map key repeated within one map, with [`DuplicateKeys::Error`]
+    #[error("Duplicate map key {0}")]
+    DuplicateKey(String),
+    /// A count or length in the data above a [`ReadOptions`] limit
+    #[error("{what} of {value} is over the limit of {limit}")]
+    LimitExceeded { what: &'static str, value: u64, limit: u64 },
+    /// An event given to [`events::BinEventWriter`] out of order
+    #[error("Unexpected event: {0}")]
+    UnexpectedEvent(String),
+    /// Written data larger than the budget given to [`write_bin_within`]
+    #[error("Written size of {} bytes is over the budget of {} bytes by {}", .0.size, .0.budget, .0.excess())]
+    OverBudget(Box<crate::profile::BudgetReport>),
+    /// A string, field count, item count or size the format cannot store
+    #[error("Value{} is too large for the bin format (limit {limit})", if path.is_empty() { String::new() } else { format!(" at {}", path) })]
+    TooLarge { path: String, limit: u64 },
+    /// A list, map or option item not of the container's type, with [`WriteOptions::check_item_types`]
+    #[error("Item{} is {found:?}, but the container holds {expected:?}", if path.is_empty() { String::new() } else { format!(" at {}", path) })]
+    WrongItemType { path: String, expected: BinType, found: BinType },
+    /// An error while reading a value, with where it happened
+    #[error("{source} (at offset {offset}{})", if path.is_empty() { String::new() } else { format!(", in {}", path) })]
+    Context { offset: u64, path: String, source: Box<BinError> },
+}
+
+impl BinError {
+    /// Byte offset in the input where reading failed, if known
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            BinError::Context { offset, .. } | BinError::SizeMismatch { offset, .. } => Some(*offset),
+            BinError::UnexpectedEof { at_offset, .. } => Some(*at_offset),
+            _ => None,
+        }
+    }
+
+    /// Path of the value being read when the error happened,
+    /// e.g. `entries[0x1234abcd].0x2c3a9e81[3]`
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            BinError::Context { path, .. } | BinError::TooLarge { path, .. } | BinError::WrongItemType { path, .. }
+                if !path.is_empty() => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without offset and path context
+    pub fn root(&self) -> &BinError {
+        match self {
+            BinError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Prepend `segment` to the path of a value the writer could not store
+    fn in_path(self, segment: impl std::fmt::Display) -> Self {
+        match self {
+            BinError::TooLarge { path, limit } => BinError::TooLarge { path: format!("{}{}", segment, path), limit },
+            BinError::WrongItemType { path, expected, found } => {
+                BinError::WrongItemType { path: format!("{}{}", segment, path), expected, found }
+            }
+            other => other,
+        }
+    }
+
+    /// Attach `offset` if no location is known yet and prepend `segment` to the path
+    fn context(self, offset: u64, segment: impl std::fmt::Display) -> Self {
+        match self {
+            BinError::Context { offset, path, source } => {
+                BinError::Context { offset, path: format!("{}{}", segment, path), source }
+            }
+            other => BinError::Context { offset, path: segment.to_string(), source: Box::new(other) },
+        }
+    }
+
+    /// Turn running out of data into [`BinError::UnexpectedEof`], naming what
+    /// was being read; `stage` and `stage_offset` stand in when the error has
+    /// no path or offset of its own
+    fn truncated(self, stage: &str, stage_offset: u64, entries_recovered: usize) -> Self {
+        let path = self.path().map(str::to_string);
+        let (at_offset, while_reading) = match self.root() {
+            BinError::UnexpectedEof { at_offset, while_reading, .. } => match path {
+                Some(path) => (*at_offset, format!("{} in {}", while_reading, path)),
+                None => (*at_offset, while_reading.clone()),
+            },
+            BinError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                (self.offset().unwrap_or(stage_offset), path.unwrap_or_else(|| stage.to_string()))
+            }
+            _ => return self,
+        };
+        BinError::UnexpectedEof { at_offset, while_reading, entries_recovered }
+    }
+}
+
+/// How strictly [`read_bin_with_options`] treats malformed input.
+///
+/// The defaults match [`read_bin`]: declared sizes are trusted for skipping
+/// and anything after the last section is ignored.
+///
+/// Counts and lengths come straight from the file, so the limits keep a
+/// crafted file from asking for huge allocations: anything over a limit is a
+/// [`BinError::LimitExceeded`]. Memory reserved up front is also capped by
+/// what the rest of the data could actually hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Error when an entry, patch or container does not consume exactly its declared size
+    pub strict_sizes: bool,
+    /// Accept bytes after the last section
+    pub allow_trailing_data: bool,
+    /// Maximum nesting of containers, pointers and embeds inside an entry
+    pub max_depth: usize,
+    /// Keep fields with unknown type bytes as [`BinValue::Raw`] instead of failing
+    pub preserve_unknown_types: bool,
+    /// Maximum number of items in one list, map, entry table or patch table
+    pub max_items: u32,
+    /// Maximum length of one string in bytes
+    pub max_string_len: usize,
+    /// What to do with keys repeated within one map
+    pub duplicate_keys: DuplicateKeys,
+    /// Give up with [`BinError::TimedOut`] once reading has taken this long;
+    /// the clock is checked every few thousand values
+    pub time_limit: Option<Duration>,
+}
+
+/// How maps holding the same key more than once are read, see [`ReadOptions::duplicate_keys`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keep every item, as stored (default)
+    #[default]
+    KeepAll,
+    /// Keep the first item with each key
+    KeepFirst,
+    /// Keep the last value for each key, at the position of the first
+    KeepLast,
+    /// Fail with [`BinError::DuplicateKey`]
+    Error,
+}
+
+/// A key repeated within one map, reported by [`read_bin_with_duplicates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKey {
+    /// Offset of the repeated item; [`events::path_at`] gives its path
+    pub offset: u64,
+    /// The key, as in value paths
+    pub key: String,
+}
+
+/// Something [`read_bin_with_warnings`] read past that may have lost data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadWarning {
+    /// A string that was not valid UTF-8, read with replacement characters
+    LossyString { offset: u64 },
+    /// A block whose declared size was not what its contents took; reading
+    /// skipped to the declared end
+    SizeMismatch { offset: u64, declared: u32, read: u64 },
+}
+
+impl std::fmt::Display for ReadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadWarning::LossyString { offset } => write!(f, "string at offset {} is not valid UTF-8", offset),
+            ReadWarning::SizeMismatch { offset, declared, read } => {
+                write!(f, "block at offset {} declares {} bytes but holds {}", offset, declared, read)
+            }
+        }
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            strict_sizes: false,
+            allow_trailing_data: true,
+            max_depth: 256,
+            preserve_unknown_types: false,
+            max_items: 1 << 24,
+            max_string_len: u16::MAX as usize,
+            duplicate_keys: DuplicateKeys::KeepAll,
+            time_limit: None,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// The limits [`read_bin_untrusted`] reads with.
+    ///
+    /// Sizes must be exact, nothing may follow the last section, nesting and
+    /// item counts are capped well below the defaults but above anything the
+    /// game ships, and reading stops after a second.
+    pub fn untrusted() -> Self {
+        Self {
+            strict_sizes: true,
+            allow_trailing_data: false,
+            max_depth: 64,
+            preserve_unknown_types: false,
+            max_items: 1 << 20,
+            max_string_len: u16::MAX as usize,
+            duplicate_keys: DuplicateKeys::KeepAll,
+            time_limit: Some(Duration::from_secs(1)),
+        }
+    }
+
+    /// When a read starting now has to stop, if ever
+    fn deadline(&self) -> Option<Instant> {
+        self.time_limit.and_then(|limit| Instant::now().checked_add(limit))
+    }
+}
+
+/// Buffers recycled between reads by [`ReadScratch::read_into`].
+///
+/// Holds the field, item and string buffers of bins that were read over, so
+/// the next read can fill them instead of allocating new ones. Keep one per
+/// worker thread in batch jobs.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{ReadOptions, ReadScratch};
+/// use ritobin_rust::model::Bin;
+///
+/// let mut scratch = ReadScratch::new();
+/// let mut bin = Bin::new();
+/// for path in ["a.bin", "b.bin"] {
+///     scratch.read_into(&mut bin, &std::fs::read(path)?, &ReadOptions::default())?;
+///     println!("{}: {} sections", path, bin.sections.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct ReadScratch {
+    classes: Vec<u32>,
+    fields: Vec<Vec<Field>>,
+    values: Vec<Vec<BinValue>>,
+    pairs: Vec<Vec<(BinValue, BinValue)>>,
+    strings: Vec<String>,
+}
+
+impl ReadScratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `data` into `bin`, reusing the allocations of what `bin` held
+    /// before and of earlier reads.
+    ///
+    /// On error `bin` is left empty.
+    pub fn read_into(&mut self, bin: &mut Bin, data: &[u8], opts: &ReadOptions) -> Result<(), BinError> {
+        self.recycle_bin(bin);
+        let mut reader = BinaryReader::with_options(data, *opts);
+        reader.scratch = std::mem::take(self);
+        let result = read_sections(&mut reader, bin, None);
+        *self = std::mem::take(&mut reader.scratch);
+        if result.is_err() {
+            self.recycle_bin(bin);
+        }
+        result.map(|_| ())
+    }
+
+    /// Empty `bin`, keeping its buffers for later reads
+    pub fn recycle_bin(&mut self, bin: &mut Bin) {
+        for (_, value) in bin.sections.drain(..) {
+            self.recycle(value);
+        }
+    }
+
+    /// Keep the buffers of `value` and everything inside it for later reads
+    pub fn recycle(&mut self, value: BinValue) {
+        match value {
+            BinValue::String(s) => self.recycle_string(s),
+            BinValue::Hash { name, .. } | BinValue::File { name, .. } | BinValue::Link { name, .. } => {
+                name.into_iter().for_each(|s| self.recycle_string(s));
+            }
+            BinValue::List { mut items, .. } | BinValue::List2 { mut items, .. } => {
+                for item in items.drain(..) {
+                    self.recycle(item);
+                }
+                self.values.push(items);
+            }
+            BinValue::Pointer { mut items, name_str, .. } | BinValue::Embed { mut items, name_str, .. } => {
+                name_str.into_iter().for_each(|s| self.recycle_string(s));
+                for field in items.drain(..) {
+                    field.key_str.into_iter().for_each(|s| self.recycle_string(s));
+                    self.recycle(field.value);
+                }
+                self.fields.push(items);
+            }
+            BinValue::Map { mut items, .. } => {
+                for (key, value) in items.drain(..) {
+                    self.recycle(key);
+                    self.recycle(value);
+                }
+                self.pairs.push(items);
+            }
+            BinValue::Option { item: Some(item), .. } => self.recycle(*item),
+            _ => {}
+        }
+    }
+
+    fn recycle_string(&mut self, mut s: String) {
+        s.clear();
+        self.strings.push(s);
+    }
+}
+
+/// Pop an emptied buffer from `pool` with room for `capacity` items
+fn reuse<T>(pool: &mut Vec<Vec<T>>, capacity: usize) -> Vec<T> {
+    match pool.pop() {
+        Some(mut buf) => {
+            buf.reserve(capacity);
+            buf
+        }
+        None => Vec::with_capacity(capacity),
+    }
 }
 
-struct BinaryReader<'a> {
+/// Reads the primitives of the bin format from a byte slice.
+///
+/// Useful for parsing structures that sit next to bins or embed bin values;
+/// all values are little-endian and reads past the end fail with
+/// `BinError::Io`. Containers read with [`read_value`](Self::read_value)
+/// follow the [`ReadOptions`] limits.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::{BinaryReader, BinaryWriter};
+/// use ritobin_rust::model::{BinType, BinValue};
+///
+/// let mut writer = BinaryWriter::new();
+/// writer.write_bytes(b"CHNK")?;
+/// writer.write_string("Characters/Ahri")?;
+/// writer.write_value(&BinValue::Vec3([1.0, 2.0, 3.0]))?;
+/// let data = writer.into_inner();
+///
+/// let mut reader = BinaryReader::new(&data);
+/// assert_eq!(reader.read_bytes(4)?, b"CHNK");
+/// assert_eq!(reader.read_string()?, "Characters/Ahri");
+/// assert_eq!(reader.read_value(&BinType::Vec3)?, BinValue::Vec3([1.0, 2.0, 3.0]));
+/// assert_eq!(reader.position(), data.len() as u64);
+/// # Ok::<(), ritobin_rust::binary::BinError>(())
+/// ```
+#[derive(Debug)]
+pub struct BinaryReader<'a> {
     cursor: Cursor<&'a [u8]>,
+    opts: ReadOptions,
+    depth: usize,
+    /// Buffers to fill before allocating new ones; empty unless reading through [`ReadScratch`]
+    scratch: ReadScratch,
+    /// Repeated map keys, if they are being reported
+    duplicates: Option<Vec<DuplicateKey>>,
+    /// Possible data loss, if it is being reported
+    warnings: Option<Vec<ReadWarning>>,
+    /// When `time_limit` runs out, if there is one
+    deadline: Option<Instant>,
+    /// Values read, to check the clock only every so often
+    values_read: u32,
+    /// Report running out of data as [`BinError::UnexpectedEof`] with the type being read
+    checked: bool,
+    /// What [`read_sections`] is reading and where it started, for truncation errors
+    stage: (&'static str, u64),
+    /// Entries [`read_sections`] has finished so far
+    entries_read: usize,
+    /// Items of no size, such as `none` list elements, that may still be read
+    free_items: u64,
 }
 
 impl<'a> BinaryReader<'a> {
-    fn new(data: &'a [u8]) -> Self {
+    /// Read `data` with the default [`ReadOptions`]
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_options(data, ReadOptions::default())
+    }
+
+    pub fn with_options(data: &'a [u8], opts: ReadOptions) -> Self {
         Self {
             cursor: Cursor::new(data),
+            opts,
+            depth: 0,
+            scratch: ReadScratch::default(),
+            duplicates: None,
+            warnings: None,
+            deadline: opts.deadline(),
+            values_read: 0,
+            checked: false,
+            stage: ("header", 0),
+            entries_read: 0,
+            free_items: data.len() as u64,
+        }
+    }
+
+    /// Move to the end of a sized block, checking the size if `strict_sizes` is set
+    fn end_block(&mut self, start: u64, size: u32) -> Result<(), BinError> {
+        let end = start + size as u64;
+        let pos = self.position();
+        if pos != end {
+            if self.opts.strict_sizes {
+                return Err(BinError::SizeMismatch { offset: start, declared: size, read: pos - start });
+            }
+            if let Some(warnings) = &mut self.warnings {
+                warnings.push(ReadWarning::SizeMismatch { offset: start, declared: size, read: pos - start });
+            }
+            self.cursor.seek(SeekFrom::Start(end))?;
         }
+        Ok(())
     }
 
-    fn position(&self) -> u64 {
+    /// Offset of the next byte to read
+    pub fn position(&self) -> u64 {
         self.cursor.position()
     }
 
-    fn read_u8(&mut self) -> Result<u8, BinError> {
+    /// Move to `offset` from the start of the data
+    pub fn set_position(&mut self, offset: u64) {
+        self.cursor.set_position(offset);
+    }
+
+    /// The next `len` bytes, borrowed from the data
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BinError> {
+        let data: &'a [u8] = self.cursor.get_ref();
+        let start = self.position() as usize;
+        let bytes = data.get(start..start.saturating_add(len))
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+        self.cursor.set_position((start + len) as u64);
+        Ok(bytes)
+    }
+
+    /// Check a count read from the data against `max_items`, returning how
+    /// many items of at least `min_size` bytes the remaining data can hold
+    ///
+    /// Items of no size take nothing from the data, so each is charged a byte
+    /// of a budget the size of the whole input instead. Otherwise a few bytes
+    /// could declare millions of them.
+    fn item_count(&mut self, count: u32, min_size: u64) -> Result<usize, BinError> {
+        if count > self.opts.max_items {
+            return Err(BinError::LimitExceeded { what: "item count", value: count as u64, limit: self.opts.max_items as u64 });
+        }
+        if min_size == 0 {
+            if count as u64 > self.free_items {
+                return Err(BinError::LimitExceeded { what: "count of items of no size", value: count as u64, limit: self.free_items });
+            }
+            self.free_items -= count as u64;
+        }
+        let remaining = (self.cursor.get_ref().len() as u64).saturating_sub(self.position());
+        Ok((count as u64).min(remaining / min_size.max(1)) as usize)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, BinError> {
         Ok(self.cursor.read_u8()?)
     }
 
-    fn read_u16(&mut self) -> Result<u16, BinError> {
+    pub fn read_u16(&mut self) -> Result<u16, BinError> {
         Ok(self.cursor.read_u16::<LE>()?)
     }
 
-    fn read_u32(&mut self) -> Result<u32, BinError> {
+    pub fn read_u32(&mut self) -> Result<u32, BinError> {
         Ok(self.cursor.read_u32::<LE>()?)
     }
 
-    fn read_u64(&mut self) -> Result<u64, BinError> {
+    pub fn read_u64(&mut self) -> Result<u64, BinError> {
         Ok(self.cursor.read_u64::<LE>()?)
     }
 
-    fn read_i8(&mut self) -> Result<i8, BinError> {
+    pub fn read_i8(&mut self) -> Result<i8, BinError> {
         Ok(self.cursor.read_i8()?)
     }
 
-    fn read_i16(&mut self) -> Result<i16, BinError> {
+    pub fn read_i16(&mut self) -> Result<i16, BinError> {
         Ok(self.cursor.read_i16::<LE>()?)
     }
 
-    fn read_i32(&mut self) -> Result<i32, BinError> {
+    pub fn read_i32(&mut self) -> Result<i32, BinError> {
         Ok(self.cursor.read_i32::<LE>()?)
     }
 
-    fn read_i64(&mut self) -> Result<i64, BinError> {
+    pub fn read_i64(&mut self) -> Result<i64, BinError> {
         Ok(self.cursor.read_i64::<LE>()?)
     }
 
-    fn read_f32(&mut self) -> Result<f32, BinError> {
+    pub fn read_f32(&mut self) -> Result<f32, BinError> {
         Ok(self.cursor.read_f32::<LE>()?)
     }
 
-    fn read_bool(&mut self) -> Result<bool, BinError> {
+    /// A byte, anything but 0 being true
+    pub fn read_bool(&mut self) -> Result<bool, BinError> {
         Ok(self.read_u8()? != 0)
     }
 
-    fn read_string(&mut self) -> Result<String, BinError> {
+    /// A string with a u16 length prefix, checked against `max_string_len`
+    pub fn read_string(&mut self) -> Result<String, BinError> {
         let len = self.read_u16()? as usize;
-        let mut buf = vec![0u8; len];
-        self.cursor.read_exact(&mut buf)?;
-        Ok(String::from_utf8_lossy(&buf).into_owned())
+        if len > self.opts.max_string_len {
+            return Err(BinError::LimitExceeded { what: "string length", value: len as u64, limit: self.opts.max_string_len as u64 });
+        }
+        let start = self.position() as usize;
+        let bytes = self.cursor.get_ref().get(start..start + len)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+        self.cursor.set_position((start + len) as u64);
+        if let Some(warnings) = &mut self.warnings {
+            if std::str::from_utf8(bytes).is_err() {
+                warnings.push(ReadWarning::LossyString { offset: start as u64 - 2 });
+            }
+        }
+        let mut s = self.scratch.strings.pop().unwrap_or_default();
+        s.push_str(&String::from_utf8_lossy(bytes));
+        Ok(s)
     }
 
-    fn read_type(&mut self) -> Result<BinType, BinError> {
+    /// A type byte
+    pub fn read_type(&mut self) -> Result<BinType, BinError> {
         let raw = self.read_u8()?;
         BinType::try_from(raw).map_err(|_| BinError::UnknownType(raw))
     }
 
-    fn read_vec2(&mut self) -> Result<[f32; 2], BinError> {
+    pub fn read_vec2(&mut self) -> Result<[f32; 2], BinError> {
         Ok([self.read_f32()?, self.read_f32()?])
     }
 
-    fn read_vec3(&mut self) -> Result<[f32; 3], BinError> {
+    pub fn read_vec3(&mut self) -> Result<[f32; 3], BinError> {
         Ok([self.read_f32()?, self.read_f32()?, self.read_f32()?])
     }
 
-    fn read_vec4(&mut self) -> Result<[f32; 4], BinError> {
+    pub fn read_vec4(&mut self) -> Result<[f32; 4], BinError> {
         Ok([
             self.read_f32()?,
             self.read_f32()?,
@@ -102,22 +563,48 @@ impl<'a> BinaryReader<'a> {
         ])
     }
 
-    fn read_mtx44(&mut self) -> Result<[f32; 16], BinError> {
+    /// 16 floats, row by row
+    pub fn read_mtx44(&mut self) -> Result<[f32; 16], BinError> {
         let mut m = [0.0; 16];
-        for i in 0..16 {
-            m[i] = self.read_f32()?;
+        for x in m.iter_mut() {
+            *x = self.read_f32()?;
         }
         Ok(m)
     }
 
-    fn read_rgba(&mut self) -> Result<[u8; 4], BinError> {
+    pub fn read_rgba(&mut self) -> Result<[u8; 4], BinError> {
         let mut buf = [0u8; 4];
         self.cursor.read_exact(&mut buf)?;
         Ok(buf)
     }
 
-    fn read_value(&mut self, type_: &BinType) -> Result<BinValue, BinError> {
-        match type_ {
+    /// A value of `type_`, as stored in fields, lists and maps (no type byte)
+    pub fn read_value(&mut self, type_: &BinType) -> Result<BinValue, BinError> {
+        let start = self.position();
+        let checked = self.checked;
+        self.read_value_at(type_).map_err(|e| match e {
+            BinError::Io(io) if checked && io.kind() == std::io::ErrorKind::UnexpectedEof => {
+                BinError::UnexpectedEof { at_offset: start, while_reading: format!("{:?}", type_), entries_recovered: 0 }
+            }
+            e => e.context(start, ""),
+        })
+    }
+
+    fn read_value_at(&mut self, type_: &BinType) -> Result<BinValue, BinError> {
+        if let Some(deadline) = self.deadline {
+            self.values_read = self.values_read.wrapping_add(1);
+            if self.values_read.is_multiple_of(4096) && Instant::now() >= deadline {
+                return Err(BinError::TimedOut(self.opts.time_limit.unwrap_or_default()));
+            }
+        }
+        let nested = type_.is_container() || matches!(type_, BinType::Pointer | BinType::Embed);
+        if nested {
+            if self.depth >= self.opts.max_depth {
+                return Err(BinError::TooDeep(self.opts.max_depth));
+            }
+            self.depth += 1;
+        }
+        let value = match type_ {
             BinType::None => Ok(BinValue::None),
             BinType::Bool => Ok(BinValue::Bool(self.read_bool()?)),
             BinType::I8 => Ok(BinValue::I8(self.read_i8()?)),
@@ -145,7 +632,11 @@ impl<'a> BinaryReader<'a> {
             BinType::Option => self.read_option(),
             BinType::Map => self.read_map(),
             BinType::Flag => Ok(BinValue::Flag(self.read_bool()?)),
+        };
+        if nested {
+            self.depth -= 1;
         }
+        value
     }
 
     fn read_list(&mut self) -> Result<BinValue, BinError> {
@@ -156,19 +647,13 @@ impl<'a> BinaryReader<'a> {
         let size = self.read_u32()?;
         let start_pos = self.position();
         let count = self.read_u32()?;
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            items.push(self.read_value(&value_type)?);
-        }
-        if self.position() != start_pos + size as u64 {
-             // In strict mode we might error, but ritobin just asserts.
-             // We'll trust the size for skipping if needed, but here we read exactly count items.
-             // If the size doesn't match, it might be an issue, but let's proceed.
-             // Actually ritobin asserts: bin_assert(reader.position() == position + size);
-             // We should probably seek to ensure we are at the right place if we want to be robust,
-             // or error if mismatch.
-             self.cursor.seek(SeekFrom::Start(start_pos + size as u64))?;
+        let capacity = self.item_count(count, value_type.min_size())?;
+        let mut items = reuse(&mut self.scratch.values, capacity);
+        for i in 0..count {
+            let start = self.position();
+            items.push(self.read_value(&value_type).map_err(|e| e.context(start, format_args!("[{}]", i)))?);
         }
+        self.end_block(start_pos, size)?;
         Ok(BinValue::List { value_type, items })
     }
 
@@ -181,11 +666,13 @@ impl<'a> BinaryReader<'a> {
         let size = self.read_u32()?;
         let start_pos = self.position();
         let count = self.read_u32()?;
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            items.push(self.read_value(&value_type)?);
+        let capacity = self.item_count(count, value_type.min_size())?;
+        let mut items = reuse(&mut self.scratch.values, capacity);
+        for i in 0..count {
+            let start = self.position();
+            items.push(self.read_value(&value_type).map_err(|e| e.context(start, format_args!("[{}]", i)))?);
         }
-        self.cursor.seek(SeekFrom::Start(start_pos + size as u64))?;
+        self.end_block(start_pos, size)?;
         Ok(BinValue::List2 { value_type, items })
     }
 
@@ -197,14 +684,8 @@ impl<'a> BinaryReader<'a> {
         let size = self.read_u32()?;
         let start_pos = self.position();
         let count = self.read_u16()?;
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            let key = self.read_u32()?;
-            let type_ = self.read_type()?;
-            let value = self.read_value(&type_)?;
-            items.push(Field { key, key_str: None, value });
-        }
-        self.cursor.seek(SeekFrom::Start(start_pos + size as u64))?;
+        let items = self.read_fields(count, start_pos + size as u64)?;
+        self.end_block(start_pos, size)?;
         Ok(BinValue::Pointer { name, name_str: None, items })
     }
 
@@ -213,15 +694,77 @@ impl<'a> BinaryReader<'a> {
         let size = self.read_u32()?;
         let start_pos = self.position();
         let count = self.read_u16()?;
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            let key = self.read_u32()?;
-            let type_ = self.read_type()?;
-            let value = self.read_value(&type_)?;
+        let items = self.read_fields(count, start_pos + size as u64)?;
+        self.end_block(start_pos, size)?;
+        Ok(BinValue::Embed { name, name_str: None, items })
+    }
+
+    /// Read `count` fields of a structure that ends at `end`
+    fn read_fields(&mut self, count: u16, end: u64) -> Result<Vec<Field>, BinError> {
+        // Key hash and type byte
+        let capacity = self.item_count(count as u32, 5)?;
+        let mut items = reuse(&mut self.scratch.fields, capacity);
+        let mut read = 0;
+        while read < count {
+            let start = self.position();
+            let key = self.read_u32().map_err(|e| e.context(start, ""))?;
+            let value = self.read_field_value(count - read - 1, end)
+                .map_err(|e| e.context(start, format_args!(".{:#010x}", key)))?;
+            if let BinValue::Raw { absorbed_fields, .. } = value {
+                read += absorbed_fields;
+            }
             items.push(Field { key, key_str: None, value });
+            read += 1;
         }
-        self.cursor.seek(SeekFrom::Start(start_pos + size as u64))?;
-        Ok(BinValue::Embed { name, name_str: None, items })
+        Ok(items)
+    }
+
+    /// Read a field's type byte and value
+    ///
+    /// With `preserve_unknown_types`, a value that can't be decoded because of
+    /// an unknown type byte is kept as [`BinValue::Raw`]: lists and maps by
+    /// their declared size, anything else together with the `remaining`
+    /// fields up to `end`.
+    fn read_field_value(&mut self, remaining: u16, end: u64) -> Result<BinValue, BinError> {
+        let type_byte = self.read_u8()?;
+        let value_start = self.position();
+        let type_ = match BinType::try_from(type_byte) {
+            Ok(type_) => type_,
+            Err(_) if self.opts.preserve_unknown_types => return self.read_raw(type_byte, end, remaining),
+            Err(_) => return Err(BinError::UnknownType(type_byte)),
+        };
+        match self.read_value(&type_) {
+            Err(e) if self.opts.preserve_unknown_types && matches!(e.root(), BinError::UnknownType(_)) => {
+                self.cursor.seek(SeekFrom::Start(value_start))?;
+                // Lists and maps start with their element type bytes, then a size
+                let types_len = match type_ {
+                    BinType::List | BinType::List2 => Some(1),
+                    BinType::Map => Some(2),
+                    _ => None,
+                };
+                match types_len {
+                    Some(types_len) => {
+                        self.cursor.seek(SeekFrom::Start(value_start + types_len))?;
+                        let size = self.read_u32()?;
+                        self.cursor.seek(SeekFrom::Start(value_start))?;
+                        self.read_raw(type_byte, value_start + types_len + 4 + size as u64, 0)
+                    }
+                    None => self.read_raw(type_byte, end, remaining),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Capture everything from the current position up to `end` as a raw value
+    fn read_raw(&mut self, type_byte: u8, end: u64, absorbed_fields: u16) -> Result<BinValue, BinError> {
+        let data = *self.cursor.get_ref();
+        let start = self.position();
+        if end < start || end > data.len() as u64 {
+            return Err(BinError::UnexpectedEof { at_offset: start, while_reading: "raw value".to_string(), entries_recovered: 0 });
+        }
+        self.cursor.seek(SeekFrom::Start(end))?;
+        Ok(BinValue::Raw { type_byte, bytes: data[start as usize..end as usize].to_vec(), absorbed_fields })
     }
 
     fn read_option(&mut self) -> Result<BinValue, BinError> {
@@ -250,80 +793,397 @@ impl<'a> BinaryReader<'a> {
         let size = self.read_u32()?;
         let start_pos = self.position();
         let count = self.read_u32()?;
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            let key = self.read_value(&key_type)?;
-            let value = self.read_value(&value_type)?;
+        let capacity = self.item_count(count, key_type.min_size() + value_type.min_size())?;
+        let mut items = reuse(&mut self.scratch.pairs, capacity);
+        // Encoded keys seen so far and where their item is
+        let check_keys = self.opts.duplicate_keys != DuplicateKeys::KeepAll || self.duplicates.is_some();
+        let mut seen = std::collections::HashMap::new();
+        let data: &[u8] = self.cursor.get_ref();
+        for i in 0..count {
+            let start = self.position();
+            let key = self.read_value(&key_type).map_err(|e| e.context(start, format_args!("[#{}]", i)))?;
+            let key_end = self.position();
+            let value = self.read_value(&value_type)
+                .map_err(|e| e.context(start, format_args!("[{}]", crate::compare::key_label(&key))))?;
+            if check_keys {
+                if let Some(&index) = seen.get(&data[start as usize..key_end as usize]) {
+                    let label = crate::compare::key_label(&key);
+                    if let Some(duplicates) = &mut self.duplicates {
+                        duplicates.push(DuplicateKey { offset: start, key: label.clone() });
+                    }
+                    match self.opts.duplicate_keys {
+                        DuplicateKeys::KeepAll => {}
+                        DuplicateKeys::KeepFirst => {
+                            self.scratch.recycle(key);
+                            self.scratch.recycle(value);
+                            continue;
+                        }
+                        DuplicateKeys::KeepLast => {
+                            let old = std::mem::replace(&mut items[index], (key, value));
+                            self.scratch.recycle(old.0);
+                            self.scratch.recycle(old.1);
+                            continue;
+                        }
+                        DuplicateKeys::Error => {
+                            return Err(BinError::DuplicateKey(label.clone()).context(start, format_args!("[{}]", label)));
+                        }
+                    }
+                } else {
+                    seen.insert(&data[start as usize..key_end as usize], items.len());
+                }
+            }
             items.push((key, value));
         }
-        self.cursor.seek(SeekFrom::Start(start_pos + size as u64))?;
+        self.end_block(start_pos, size)?;
         Ok(BinValue::Map { key_type, value_type, items })
     }
 }
 
 pub fn read_bin(data: &[u8]) -> Result<Bin, BinError> {
-    let mut reader = BinaryReader::new(data);
-    let mut bin = Bin::new();
+    read_bin_with_options(data, &ReadOptions::default())
+}
 
-    let mut magic = [0u8; 4];
-    reader.cursor.read_exact(&mut magic)?;
-    
-    let is_patch = if magic == *b"PTCH" {
-        let _unk = reader.read_u64()?; // skip unk
-        reader.cursor.read_exact(&mut magic)?; // read next magic
-        bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
-        true
-    } else {
-        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
-        false
-    };
+/// [`read_bin`] with control over how malformed input is handled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_with_options, ReadOptions};
+///
+/// let data = std::fs::read("champion.bin")?;
+/// let opts = ReadOptions { strict_sizes: true, allow_trailing_data: false, ..Default::default() };
+/// let bin = read_bin_with_options(&data, &opts)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_with_options(data: &[u8], opts: &ReadOptions) -> Result<Bin, BinError> {
+    read_bin_impl(data, opts, None).map(|(bin, _)| bin)
+}
+
+/// Read a bin from an untrusted source, such as a user upload, with
+/// [`ReadOptions::untrusted`].
+///
+/// This never panics: any input, however malformed, is either a bin or a
+/// [`BinError`], and the tests feed it thousands of corrupted files to keep
+/// it that way. Nesting, counts, string lengths and reading time are capped,
+/// and memory reserved up front never exceeds what the rest of the data could
+/// hold. Values that take no bytes, like `none` list items, are counted
+/// against `data.len()` across the whole file, so every value read accounts
+/// for at least one byte of input and the memory used grows at most linearly
+/// with `data.len()`. Cap the upload size to bound it.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::{read_bin_untrusted, BinError};
+///
+/// assert!(matches!(read_bin_untrusted(b"PROP\x03\x00\x00\x00\xff\xff"), Err(BinError::Io(_))));
+/// ```
+pub fn read_bin_untrusted(data: &[u8]) -> Result<Bin, BinError> {
+    read_bin_with_options(data, &ReadOptions::untrusted())
+}
+
+/// Read a bin that starts `offset` bytes into `data`, ignoring anything after it.
+///
+/// Returns the bin and the number of bytes it took up, so containers holding
+/// several bins back to back can be walked without copying them out; see
+/// [`find_prop_blobs`] to locate bins in unknown data. Offsets in errors are
+/// relative to `offset`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::read_bin_at;
+///
+/// // Two bins stored back to back
+/// let chunk = std::fs::read("chunk.dat")?;
+/// let (first, len) = read_bin_at(&chunk, 0)?;
+/// let (second, _) = read_bin_at(&chunk, len)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_at(data: &[u8], offset: usize) -> Result<(Bin, usize), BinError> {
+    let data = data.get(offset..).ok_or_else(|| {
+        BinError::UnexpectedEof { at_offset: offset as u64, while_reading: "header".to_string(), entries_recovered: 0 }
+    })?;
+    let opts = ReadOptions { allow_trailing_data: true, ..Default::default() };
+    read_bin_impl(data, &opts, None).map(|(bin, len)| (bin, len as usize))
+}
 
-    if magic != *b"PROP" {
-        return Err(BinError::InvalidMagic);
+/// Read a bin, reporting a file cut off part way with where and in what.
+///
+/// Running out of data anywhere is a [`BinError::UnexpectedEof`] giving the
+/// offset of the value that was cut off, its type and path, e.g.
+/// `Mtx44 in entries[0x1234abcd].0x2c3a9e81`, and how many entries were read
+/// completely before it. Other errors are as from [`read_bin_with_options`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_checked, BinError, ReadOptions};
+///
+/// let data = std::fs::read("truncated.bin")?;
+/// if let Err(BinError::UnexpectedEof { at_offset, while_reading, entries_recovered }) =
+///     read_bin_checked(&data, &ReadOptions::default())
+/// {
+///     eprintln!("cut off at {} reading {}, {} entries intact", at_offset, while_reading, entries_recovered);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_checked(data: &[u8], opts: &ReadOptions) -> Result<Bin, BinError> {
+    let mut reader = BinaryReader::with_options(data, *opts);
+    reader.checked = true;
+    let mut bin = Bin::new();
+    match read_sections(&mut reader, &mut bin, None) {
+        Ok(_) => Ok(bin),
+        Err(e) => Err(e.truncated(reader.stage.0, reader.stage.1, reader.entries_read)),
     }
+}
 
-    let version = reader.read_u32()?;
-    bin.sections.insert("version".to_string(), BinValue::U32(version));
+/// Read `data` into `bin`, reusing the buffers of what `bin` held before.
+///
+/// Batch jobs that convert one file after another can keep a single `Bin`
+/// instead of allocating every list, field and string anew; a
+/// [`ReadScratch`] also keeps spare buffers between calls.
+pub fn read_bin_into(bin: &mut Bin, data: &[u8]) -> Result<(), BinError> {
+    ReadScratch::new().read_into(bin, data, &ReadOptions::default())
+}
 
-    if version >= 2 {
-        let linked_files_count = reader.read_u32()?;
-        let mut linked_items = Vec::with_capacity(linked_files_count as usize);
-        for _ in 0..linked_files_count {
-            linked_items.push(BinValue::String(reader.read_string()?));
-        }
-        bin.sections.insert("linked".to_string(), BinValue::List { 
-            value_type: BinType::String, 
-            items: linked_items 
-        });
+/// Find complete PROP and PTCH bins inside `data`, such as bins embedded in
+/// an unknown container or a memory dump.
+///
+/// Every `PROP`/`PTCH` magic is tried as the start of a bin; the ranges of
+/// the ones that read without errors are returned in order, and nothing
+/// inside a found bin is searched again. Versions above 3 are not tried, to
+/// keep stray magic bytes in other data from being read as bins.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{find_prop_blobs, read_bin_at};
+///
+/// let dump = std::fs::read("memory.dmp")?;
+/// for range in find_prop_blobs(&dump) {
+///     let (bin, _) = read_bin_at(&dump, range.start)?;
+///     println!("{} bytes at {:#x}: {} sections", range.len(), range.start, bin.sections.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn find_prop_blobs(data: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let opts = ReadOptions { allow_trailing_data: true, ..Default::default() };
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(i) = data[start..].windows(4).position(|w| w == b"PROP" || w == b"PTCH") {
+        let at = start + i;
+        let blob = &data[at..];
+        let version_at = if blob.starts_with(b"PTCH") { 16 } else { 4 };
+        let plausible = blob.get(version_at..version_at + 4)
+            .is_some_and(|v| u32::from_le_bytes(v.try_into().unwrap()) <= 3);
+        match read_bin_impl(blob, &opts, None) {
+            Ok((_, len)) if plausible => {
+                found.push(at..at + len as usize);
+                start = at + len as usize;
+            }
+            _ => start = at + 1,
+        }
     }
+    found
+}
+
+/// An entry left out by [`read_bin_recover`].
+#[derive(Debug)]
+pub struct SkippedEntry {
+    /// Entry key hash, or 0 if the record header itself was cut off
+    pub hash: u32,
+    /// Class hash from the entry table
+    pub class: u32,
+    /// Offset of the entry record from the start of the file
+    pub offset: u64,
+    pub error: BinError,
+}
+
+/// Result of [`read_bin_recover`].
+#[derive(Debug)]
+pub struct RecoveredBin {
+    /// Everything that could be read
+    pub bin: Bin,
+    /// Entries that failed to parse, in file order
+    pub skipped: Vec<SkippedEntry>,
+    /// Keys repeated within a map, as [`read_bin_with_duplicates`] lists them
+    pub duplicates: Vec<DuplicateKey>,
+    /// Possible data loss, as [`read_bin_with_warnings`] lists it
+    pub warnings: Vec<ReadWarning>,
+}
+
+/// Read a bin, skipping entries that fail to parse instead of failing entirely.
+///
+/// After a damaged entry, reading resumes at the end of its record as given
+/// by its length field. If that length runs past the end of the data, the
+/// remaining entries can't be located and reading stops there. Errors in the
+/// header or entry table are still fatal.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_recover, ReadOptions};
+///
+/// let data = std::fs::read("damaged.bin")?;
+/// let recovered = read_bin_recover(&data, &ReadOptions::default())?;
+/// for s in &recovered.skipped {
+///     eprintln!("skipped entry {:08x} at {}: {}", s.hash, s.offset, s.error);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_recover(data: &[u8], opts: &ReadOptions) -> Result<RecoveredBin, BinError> {
+    let mut skipped = Vec::new();
+    let ReadReport { bin, duplicates, warnings } = read_bin_reporting(data, opts, Some(&mut skipped))?;
+    Ok(RecoveredBin { bin, skipped, duplicates, warnings })
+}
+
+/// Result of [`read_bin_with_report`].
+#[derive(Debug)]
+pub struct ReadReport {
+    pub bin: Bin,
+    /// Keys repeated within a map, as [`read_bin_with_duplicates`] lists them
+    pub duplicates: Vec<DuplicateKey>,
+    /// Possible data loss, as [`read_bin_with_warnings`] lists it
+    pub warnings: Vec<ReadWarning>,
+}
+
+/// Read a bin, listing both repeated map keys and possible data loss.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::{read_bin_with_report, write_bin, ReadOptions};
+/// use ritobin_rust::model::{Bin, BinValue};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+/// bin.sections.insert("version".to_string(), BinValue::U32(3));
+/// let report = read_bin_with_report(&write_bin(&bin)?, &ReadOptions::default())?;
+/// assert_eq!(report.bin.sections["version"], BinValue::U32(3));
+/// assert!(report.duplicates.is_empty() && report.warnings.is_empty());
+/// # Ok::<(), ritobin_rust::binary::BinError>(())
+/// ```
+pub fn read_bin_with_report(data: &[u8], opts: &ReadOptions) -> Result<ReadReport, BinError> {
+    read_bin_reporting(data, opts, None)
+}
+
+fn read_bin_reporting(data: &[u8], opts: &ReadOptions, skipped: Option<&mut Vec<SkippedEntry>>) -> Result<ReadReport, BinError> {
+    let mut reader = BinaryReader::with_options(data, *opts);
+    reader.duplicates = Some(Vec::new());
+    reader.warnings = Some(Vec::new());
+    let mut bin = Bin::new();
+    read_sections(&mut reader, &mut bin, skipped)?;
+    Ok(ReadReport {
+        bin,
+        duplicates: reader.duplicates.unwrap_or_default(),
+        warnings: reader.warnings.unwrap_or_default(),
+    })
+}
+
+/// Read a bin, also listing every key repeated within a map.
+///
+/// What happens to the repeated items is up to `opts.duplicate_keys`; with
+/// the default they are kept and only reported.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_with_duplicates, DuplicateKeys, ReadOptions};
+///
+/// let data = std::fs::read("champion.bin")?;
+/// let opts = ReadOptions { duplicate_keys: DuplicateKeys::KeepLast, ..Default::default() };
+/// let (bin, duplicates) = read_bin_with_duplicates(&data, &opts)?;
+/// for d in &duplicates {
+///     eprintln!("duplicate key {} at offset {}", d.key, d.offset);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_with_duplicates(data: &[u8], opts: &ReadOptions) -> Result<(Bin, Vec<DuplicateKey>), BinError> {
+    let mut reader = BinaryReader::with_options(data, *opts);
+    reader.duplicates = Some(Vec::new());
+    let mut bin = Bin::new();
+    read_sections(&mut reader, &mut bin, None)?;
+    Ok((bin, reader.duplicates.unwrap_or_default()))
+}
+
+/// Read a bin, also listing anything read past that may have lost data:
+/// strings that were not valid UTF-8 and, unless `opts.strict_sizes` makes
+/// them errors, blocks whose size did not match their contents.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_with_warnings, ReadOptions};
+///
+/// let data = std::fs::read("champion.bin")?;
+/// let (bin, warnings) = read_bin_with_warnings(&data, &ReadOptions::default())?;
+/// for w in &warnings {
+///     eprintln!("warning: {}", w);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_with_warnings(data: &[u8], opts: &ReadOptions) -> Result<(Bin, Vec<ReadWarning>), BinError> {
+    let mut reader = BinaryReader::with_options(data, *opts);
+    reader.warnings = Some(Vec::new());
+    let mut bin = Bin::new();
+    read_sections(&mut reader, &mut bin, None)?;
+    Ok((bin, reader.warnings.unwrap_or_default()))
+}
+
+/// Read a bin from the start of `data`; also returns how many bytes it took
+fn read_bin_impl(data: &[u8], opts: &ReadOptions, skipped: Option<&mut Vec<SkippedEntry>>) -> Result<(Bin, u64), BinError> {
+    let mut reader = BinaryReader::with_options(data, *opts);
+    let mut bin = Bin::new();
+    let len = read_sections(&mut reader, &mut bin, skipped)?;
+    Ok((bin, len))
+}
+
+/// Read every section into `bin`, returning the number of bytes read
+fn read_sections(reader: &mut BinaryReader, bin: &mut Bin, mut skipped: Option<&mut Vec<SkippedEntry>>) -> Result<u64, BinError> {
+    let data = *reader.cursor.get_ref();
+    reader.stage = ("header", reader.position());
+    let is_patch = reader.read_header(bin)?;
 
+    reader.stage = ("entry table", reader.position());
     let entry_count = reader.read_u32()?;
-    let mut entry_name_hashes = Vec::with_capacity(entry_count as usize);
+    let mut entry_name_hashes = std::mem::take(&mut reader.scratch.classes);
+    entry_name_hashes.clear();
+    entry_name_hashes.reserve(reader.item_count(entry_count, 4)?);
     for _ in 0..entry_count {
         entry_name_hashes.push(reader.read_u32()?);
     }
 
-    let mut entries_items = Vec::with_capacity(entry_count as usize);
-    for entry_name_hash in entry_name_hashes {
-        let entry_length = reader.read_u32()?;
-        let start_pos = reader.position();
-        let entry_key_hash = reader.read_u32()?;
-        let field_count = reader.read_u16()?;
-        
-        let mut fields = Vec::with_capacity(field_count as usize);
-        for _ in 0..field_count {
-            let name = reader.read_u32()?;
-            let type_ = reader.read_type()?;
-            let value = reader.read_value(&type_)?;
-            fields.push(Field { key: name, key_str: None, value });
+    let mut entries_items = reuse(&mut reader.scratch.pairs, entry_name_hashes.len());
+    let mut complete = true;
+    for &entry_name_hash in &entry_name_hashes {
+        let offset = reader.position();
+        reader.stage = ("entry header", offset);
+        let error = match reader.read_entry(entry_name_hash) {
+            Ok(item) => {
+                entries_items.push(item);
+                reader.entries_read += 1;
+                continue;
+            }
+            Err(error) => error,
+        };
+        let Some(skipped) = skipped.as_deref_mut() else { return Err(error) };
+
+        // Resynchronize using the record's length field
+        let header = |at: u64| data.get(at as usize..at as usize + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+        let hash = header(offset + 4).unwrap_or(0);
+        skipped.push(SkippedEntry { hash, class: entry_name_hash, offset, error });
+        let end = header(offset).map(|length| offset + 4 + length as u64);
+        match end {
+            Some(end) if end <= data.len() as u64 => {
+                reader.depth = 0;
+                reader.cursor.seek(SeekFrom::Start(end))?;
+            }
+            _ => {
+                complete = false;
+                break;
+            }
         }
-        
-        reader.cursor.seek(SeekFrom::Start(start_pos + entry_length as u64))?;
-        
-        entries_items.push((
-            BinValue::Hash { value: entry_key_hash, name: None },
-            BinValue::Embed { name: entry_name_hash, name_str: None, items: fields }
-        ));
     }
     
     bin.sections.insert("entries".to_string(), BinValue::Map { 
@@ -332,19 +1192,100 @@ pub fn read_bin(data: &[u8]) -> Result<Bin, BinError> {
         items: entries_items 
     });
 
-    if is_patch {
-        let patch_count = reader.read_u32()?;
-        let mut patch_items = Vec::with_capacity(patch_count as usize);
+    if complete {
+        if is_patch {
+            bin.sections.insert("patches".to_string(), reader.read_patches()?);
+        }
+        reader.check_trailing()?;
+    }
+    reader.scratch.classes = entry_name_hashes;
+    Ok(reader.position())
+}
+
+impl BinaryReader<'_> {
+    fn check_trailing(&self) -> Result<(), BinError> {
+        let rest = (self.cursor.get_ref().len() as u64).saturating_sub(self.position());
+        if rest > 0 && !self.opts.allow_trailing_data {
+            return Err(BinError::TrailingData(rest));
+        }
+        Ok(())
+    }
+
+    /// Read magic, version and linked files into `bin`; returns whether this is a PTCH file
+    fn read_header(&mut self, bin: &mut Bin) -> Result<bool, BinError> {
+        let mut magic = [0u8; 4];
+        self.cursor.read_exact(&mut magic)?;
+        
+        let is_patch = if magic == *b"PTCH" {
+            let header = self.read_u64()?;
+            self.cursor.read_exact(&mut magic)?; // read next magic
+            bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+            bin.sections.insert("ptch_header".to_string(), BinValue::U64(header));
+            true
+        } else {
+            bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+            false
+        };
+
+        if magic != *b"PROP" {
+            return Err(BinError::InvalidMagic);
+        }
+
+        let version = self.read_u32()?;
+        bin.sections.insert("version".to_string(), BinValue::U32(version));
+
+        if version >= 2 {
+            let linked_files_count = self.read_u32()?;
+            let mut linked_items = Vec::with_capacity(self.item_count(linked_files_count, 2)?);
+            for _ in 0..linked_files_count {
+                linked_items.push(BinValue::String(self.read_string()?));
+            }
+            bin.sections.insert("linked".to_string(), BinValue::List { 
+                value_type: BinType::String, 
+                items: linked_items 
+            });
+        }
+        Ok(is_patch)
+    }
+
+    /// Read one entry record as a (key hash, embed) pair
+    fn read_entry(&mut self, entry_name_hash: u32) -> Result<(BinValue, BinValue), BinError> {
+        let entry_length = self.read_u32()?;
+        let start_pos = self.position();
+        let entry_key_hash = self.read_u32()?;
+        let fields = self.read_u16()
+            .and_then(|field_count| self.read_fields(field_count, start_pos + entry_length as u64))
+            .and_then(|fields| self.end_block(start_pos, entry_length).map(|()| fields))
+            .map_err(|e| e.context(start_pos - 4, format_args!("entries[{:#010x}]", entry_key_hash)))?;
+        
+        Ok((
+            BinValue::Hash { value: entry_key_hash, name: None },
+            BinValue::Embed { name: entry_name_hash, name_str: None, items: fields }
+        ))
+    }
+
+    /// Read one patch record after its key hash as (path, value)
+    fn read_patch(&mut self) -> Result<(String, BinValue), BinError> {
+        let patch_length = self.read_u32()?;
+        let start_pos = self.position();
+        let type_ = self.read_type()?;
+        let name = self.read_string()?;
+        let value = self.read_value(&type_)?;
+        self.end_block(start_pos, patch_length)?;
+        Ok((name, value))
+    }
+
+    /// Read the patch section of a PTCH file as a map of embeds
+    fn read_patches(&mut self) -> Result<BinValue, BinError> {
+        self.stage = ("patch table", self.position());
+        let patch_count = self.read_u32()?;
+        let mut patch_items = Vec::with_capacity(self.item_count(patch_count, 4)?);
         for _ in 0..patch_count {
-            let patch_key_hash = reader.read_u32()?;
-            let patch_length = reader.read_u32()?;
-            let start_pos = reader.position();
-            
-            let type_ = reader.read_type()?;
-            let name = reader.read_string()?;
-            let value = reader.read_value(&type_)?;
-            
-            reader.cursor.seek(SeekFrom::Start(start_pos + patch_length as u64))?;
+            self.stage = ("patch header", self.position());
+            let patch_key_hash = self.read_u32()?;
+            let start_pos = self.position();
+            let (name, value) = self.read_patch()
+                .map_err(|e| e.context(start_pos, format_args!("patches[{:#010x}]", patch_key_hash)))?;
             
             // Patch is stored as an Embed with "path" and "value" fields in ritobin
             let fields = vec![
@@ -357,121 +1298,505 @@ pub fn read_bin(data: &[u8]) -> Result<Bin, BinError> {
                 BinValue::Embed { name: crate::hash::Fnv1a::new("patch").0, name_str: None, items: fields }
             ));
         }
-        bin.sections.insert("patches".to_string(), BinValue::Map {
+        Ok(BinValue::Map {
             key_type: BinType::Hash,
             value_type: BinType::Embed,
             items: patch_items
-        });
+        })
     }
+}
 
-    Ok(bin)
+/// Location of one entry inside a bin file, as indexed by [`LazyBin`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryHeader {
+    /// Entry key hash
+    pub hash: u32,
+    /// Class hash of the entry's embed
+    pub class: u32,
+    /// Offset of the entry record (its length field) from the start of the file
+    pub offset: u64,
+    /// Length of the record after the length field
+    pub length: u32,
 }
 
-use byteorder::WriteBytesExt;
+/// A bin file whose entries are decoded on demand.
+///
+/// [`LazyBin::parse`] reads only the header and the entry table, skipping over
+/// entry bodies using their length fields. Individual entries are then decoded
+/// with [`LazyBin::get_entry`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::LazyBin;
+///
+/// let data = std::fs::read("champions.bin")?;
+/// let lazy = LazyBin::parse(&data)?;
+/// if let Some(entry) = lazy.get_entry(ritobin_rust::hash::fnv1a("Characters/Ahri/CharacterRecords/Root"))? {
+///     println!("{:?}", entry);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct LazyBin<'a> {
+    data: &'a [u8],
+    opts: ReadOptions,
+    header: Bin,
+    index: EntryIndex,
+    patches_pos: Option<u64>,
+}
 
-struct BinaryWriter {
-    cursor: Cursor<Vec<u8>>,
+/// Offsets and lengths of every entry in a bin file, found without decoding values.
+///
+/// Offsets are absolute, so an index can be stored alongside a file and used
+/// later with [`decode_at`] to pull out single entries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{decode_at, EntryIndex};
+///
+/// let data = std::fs::read("champions.bin")?;
+/// let index = EntryIndex::build(&data)?;
+/// if let Some(entry) = index.get(ritobin_rust::hash::fnv1a("Characters/Ahri/CharacterRecords/Root")) {
+///     let (_hash, fields) = decode_at(&data, entry.offset)?;
+///     println!("{} fields", fields.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EntryIndex {
+    entries: Vec<EntryHeader>,
+    by_hash: std::collections::HashMap<u32, usize>,
 }
 
-impl BinaryWriter {
-    fn new() -> Self {
-        Self {
-            cursor: Cursor::new(Vec::new()),
+impl EntryIndex {
+    /// Index the entries of `data`
+    pub fn build(data: &[u8]) -> Result<Self, BinError> {
+        let mut reader = BinaryReader::with_options(data, ReadOptions::default());
+        reader.read_header(&mut Bin::new())?;
+        Self::scan(&mut reader)
+    }
+
+    /// Read the entry table and skip over every record, leaving the reader after the last one
+    fn scan(reader: &mut BinaryReader) -> Result<Self, BinError> {
+        let data_len = reader.cursor.get_ref().len() as u64;
+        let entry_count = reader.read_u32()?;
+        let mut classes = Vec::with_capacity(reader.item_count(entry_count, 4)?);
+        for _ in 0..entry_count {
+            classes.push(reader.read_u32()?);
+        }
+
+        let mut index = Self {
+            entries: Vec::with_capacity(classes.len()),
+            by_hash: std::collections::HashMap::with_capacity(classes.len()),
+        };
+        for class in classes {
+            let offset = reader.position();
+            let length = reader.read_u32()?;
+            let hash = reader.read_u32()?;
+            let end = offset + 4 + length as u64;
+            if end > data_len {
+                return Err(BinError::UnexpectedEof {
+                    at_offset: offset,
+                    while_reading: format!("entries[{:#010x}]", hash),
+                    entries_recovered: index.entries.len(),
+                });
+            }
+            reader.cursor.seek(SeekFrom::Start(end))?;
+            index.by_hash.entry(hash).or_insert(index.entries.len());
+            index.entries.push(EntryHeader { hash, class, offset, length });
         }
+        Ok(index)
     }
 
-    fn position(&self) -> u64 {
-        self.cursor.position()
+    /// Location of the entry with key `hash`; the first one if the key repeats
+    pub fn get(&self, hash: u32) -> Option<&EntryHeader> {
+        self.by_hash.get(&hash).map(|&i| &self.entries[i])
     }
 
-    fn into_inner(self) -> Vec<u8> {
-        self.cursor.into_inner()
+    /// All entries in file order
+    pub fn entries(&self) -> &[EntryHeader] {
+        &self.entries
     }
 
-    fn write_u8(&mut self, v: u8) -> Result<(), BinError> {
-        self.cursor.write_u8(v)?;
-        Ok(())
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
 
-    fn write_u16(&mut self, v: u16) -> Result<(), BinError> {
-        self.cursor.write_u16::<LE>(v)?;
-        Ok(())
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
-    fn write_u32(&mut self, v: u32) -> Result<(), BinError> {
-        self.cursor.write_u32::<LE>(v)?;
-        Ok(())
+    pub fn contains(&self, hash: u32) -> bool {
+        self.by_hash.contains_key(&hash)
     }
+}
 
-    fn write_u64(&mut self, v: u64) -> Result<(), BinError> {
-        self.cursor.write_u64::<LE>(v)?;
-        Ok(())
+/// Decode the entry record at `offset` as its key hash and fields.
+///
+/// `offset` points at the record's length field, as in [`EntryHeader::offset`].
+/// The class hash lives in the entry table rather than the record, so it is
+/// not returned here; use [`EntryHeader::class`] for it.
+pub fn decode_at(data: &[u8], offset: u64) -> Result<(u32, Vec<Field>), BinError> {
+    let mut reader = BinaryReader::with_options(data, ReadOptions::default());
+    reader.cursor.seek(SeekFrom::Start(offset))?;
+    match reader.read_entry(0)? {
+        (BinValue::Hash { value, .. }, BinValue::Embed { items, .. }) => Ok((value, items)),
+        _ => unreachable!("read_entry returns a hash and an embed"),
     }
+}
 
-    fn write_i8(&mut self, v: i8) -> Result<(), BinError> {
-        self.cursor.write_i8(v)?;
-        Ok(())
+impl<'a> LazyBin<'a> {
+    /// Index the entries of `data` without decoding them
+    pub fn parse(data: &'a [u8]) -> Result<Self, BinError> {
+        Self::parse_with_options(data, &ReadOptions::default())
     }
 
-    fn write_i16(&mut self, v: i16) -> Result<(), BinError> {
-        self.cursor.write_i16::<LE>(v)?;
-        Ok(())
+    /// [`LazyBin::parse`] with options applied to every decoded entry
+    pub fn parse_with_options(data: &'a [u8], opts: &ReadOptions) -> Result<Self, BinError> {
+        let mut reader = BinaryReader::with_options(data, *opts);
+        let mut header = Bin::new();
+        let is_patch = reader.read_header(&mut header)?;
+        let index = EntryIndex::scan(&mut reader)?;
+        if !is_patch {
+            reader.check_trailing()?;
+        }
+        let patches_pos = is_patch.then(|| reader.position());
+        Ok(Self { data, opts: *opts, header, index, patches_pos })
     }
 
-    fn write_i32(&mut self, v: i32) -> Result<(), BinError> {
-        self.cursor.write_i32::<LE>(v)?;
-        Ok(())
+    /// Sections other than `entries` and `patches` (type, version, linked)
+    pub fn header(&self) -> &Bin {
+        &self.header
     }
 
-    fn write_i64(&mut self, v: i64) -> Result<(), BinError> {
-        self.cursor.write_i64::<LE>(v)?;
-        Ok(())
+    /// All entries in file order
+    pub fn entries(&self) -> &[EntryHeader] {
+        self.index.entries()
     }
 
-    fn write_f32(&mut self, v: f32) -> Result<(), BinError> {
-        self.cursor.write_f32::<LE>(v)?;
-        Ok(())
+    /// The underlying entry index
+    pub fn index(&self) -> &EntryIndex {
+        &self.index
     }
 
-    fn write_bool(&mut self, v: bool) -> Result<(), BinError> {
-        self.write_u8(if v { 1 } else { 0 })
+    pub fn len(&self) -> usize {
+        self.index.len()
     }
 
-    fn write_string(&mut self, v: &str) -> Result<(), BinError> {
-        self.write_u16(v.len() as u16)?;
-        self.cursor.write_all(v.as_bytes())?;
-        Ok(())
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
     }
 
-    fn write_type(&mut self, v: BinType) -> Result<(), BinError> {
-        self.write_u8(v as u8)
+    pub fn contains(&self, hash: u32) -> bool {
+        self.index.contains(hash)
     }
 
-    fn write_vec2(&mut self, v: [f32; 2]) -> Result<(), BinError> {
-        for x in v { self.write_f32(x)?; }
-        Ok(())
+    /// Decode the entry with key `hash` as an `Embed`, if present
+    pub fn get_entry(&self, hash: u32) -> Result<Option<BinValue>, BinError> {
+        match self.index.get(hash) {
+            Some(entry) => Ok(Some(self.decode(entry)?.1)),
+            None => Ok(None),
+        }
     }
 
-    fn write_vec3(&mut self, v: [f32; 3]) -> Result<(), BinError> {
-        for x in v { self.write_f32(x)?; }
-        Ok(())
+    /// Decode one indexed entry as a (key hash, embed) pair
+    pub fn decode(&self, entry: &EntryHeader) -> Result<(BinValue, BinValue), BinError> {
+        self.decode_all([entry], self.opts.deadline(), |_| {}).map(|mut items| items.remove(0))
+    }
+
+    /// Decode `entries` with one reader, so they all count towards `deadline`,
+    /// calling `decoded` after each one that succeeds
+    fn decode_all<'e>(
+        &self,
+        entries: impl IntoIterator<Item = &'e EntryHeader>,
+        deadline: Option<Instant>,
+        mut decoded: impl FnMut(&EntryHeader),
+    ) -> Result<Vec<(BinValue, BinValue)>, BinError> {
+        let mut reader = self.reader(deadline);
+        entries.into_iter().map(|entry| {
+            reader.cursor.seek(SeekFrom::Start(entry.offset))?;
+            let item = reader.read_entry(entry.class)?;
+            decoded(entry);
+            Ok(item)
+        }).collect()
+    }
+
+    fn reader(&self, deadline: Option<Instant>) -> BinaryReader<'a> {
+        let mut reader = BinaryReader::with_options(self.data, self.opts);
+        reader.deadline = deadline;
+        reader
+    }
+
+    /// Decode everything into a regular [`Bin`], as `read_bin` would
+    pub fn to_bin(&self) -> Result<Bin, BinError> {
+        self.to_bin_where(|_| true)
+    }
+
+    /// [`LazyBin::to_bin`], decoding the entries on `threads` threads.
+    ///
+    /// Entries are split into one contiguous run per thread and put back in
+    /// file order, so the result (or the first error) is the same as with
+    /// `to_bin`. `threads` of 0 uses one thread per CPU. A
+    /// [`ReadOptions::time_limit`] applies to all threads together.
+    pub fn to_bin_parallel(&self, threads: usize) -> Result<Bin, BinError> {
+        let threads = resolve_threads(threads);
+        let entries = self.index.entries();
+        if threads < 2 || entries.len() < 2 {
+            return self.to_bin();
+        }
+        let deadline = self.opts.deadline();
+        let run = entries.len().div_ceil(threads);
+        let runs: Vec<Result<Vec<_>, BinError>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = entries.chunks(run)
+                .map(|run| scope.spawn(move || self.decode_all(run, deadline, |_| {})))
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("entry decoding thread panicked")).collect()
+        });
+        let mut items = Vec::with_capacity(entries.len());
+        for run in runs {
+            items.extend(run?);
+        }
+        self.with_entries(items, deadline)
     }
 
-    fn write_vec4(&mut self, v: [f32; 4]) -> Result<(), BinError> {
-        for x in v { self.write_f32(x)?; }
-        Ok(())
+    /// Decode into a [`Bin`] keeping only the entries accepted by `keep`
+    fn to_bin_where(&self, mut keep: impl FnMut(&EntryHeader) -> bool) -> Result<Bin, BinError> {
+        let deadline = self.opts.deadline();
+        let items = self.decode_all(self.index.entries.iter().filter(|e| keep(e)), deadline, |_| {})?;
+        self.with_entries(items, deadline)
     }
 
-    fn write_mtx44(&mut self, v: [f32; 16]) -> Result<(), BinError> {
+    /// The header sections, `items` as the entries and the decoded patches
+    fn with_entries(&self, items: Vec<(BinValue, BinValue)>, deadline: Option<Instant>) -> Result<Bin, BinError> {
+        let mut bin = self.header.clone();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items,
+        });
+        if let Some(pos) = self.patches_pos {
+            let mut reader = self.reader(deadline);
+            reader.cursor.seek(SeekFrom::Start(pos))?;
+            bin.sections.insert("patches".to_string(), reader.read_patches()?);
+            reader.check_trailing()?;
+        }
+        Ok(bin)
+    }
+}
+
+/// Read a bin, decoding only the entries whose key hash is in `hashes`.
+///
+/// Other entries are skipped using their length fields, so they are never
+/// decoded and errors inside them are not reported. Header sections and
+/// patches are read as usual.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::collections::HashSet;
+/// use ritobin_rust::binary::read_bin_filtered;
+///
+/// let data = std::fs::read("skin0.bin")?;
+/// let wanted = HashSet::from([ritobin_rust::hash::fnv1a("Characters/Ahri/Skins/Skin0")]);
+/// let bin = read_bin_filtered(&data, &wanted)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_filtered(data: &[u8], hashes: &std::collections::HashSet<u32>) -> Result<Bin, BinError> {
+    LazyBin::parse(data)?.to_bin_where(|e| hashes.contains(&e.hash))
+}
+
+/// Read a bin, decoding its entries on `threads` threads (0 for one per CPU).
+///
+/// Entries are length-prefixed and independent, so after the entry offsets
+/// are scanned each thread decodes its own run of entries. Worth it for
+/// large map and world bins; small files are read on the calling thread.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::{read_bin_parallel, ReadOptions};
+///
+/// let data = std::fs::read("Map11.bin")?;
+/// let bin = read_bin_parallel(&data, &ReadOptions::default(), 0)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_bin_parallel(data: &[u8], opts: &ReadOptions, threads: usize) -> Result<Bin, BinError> {
+    LazyBin::parse_with_options(data, opts)?.to_bin_parallel(threads)
+}
+
+/// [`read_bin_with_options`], reporting an [`Event::EntryParsed`](crate::progress::Event::EntryParsed) per entry.
+///
+/// Entry offsets are scanned first so the total is known up front. Each event
+/// follows the entry's successful decoding, so an entry that fails to decode
+/// is never reported.
+pub fn read_bin_with_progress(data: &[u8], opts: &ReadOptions, progress: &mut dyn crate::progress::Progress) -> Result<Bin, BinError> {
+    let lazy = LazyBin::parse_with_options(data, opts)?;
+    let total = lazy.len();
+    let deadline = opts.deadline();
+    let mut index = 0;
+    let items = lazy.decode_all(lazy.entries(), deadline, |e| {
+        progress.event(crate::progress::Event::EntryParsed { hash: e.hash, index, total });
+        index += 1;
+    })?;
+    lazy.with_entries(items, deadline)
+}
+
+use byteorder::WriteBytesExt;
+
+/// What [`write_bin_with_options`] checks and reorders while writing.
+///
+/// The defaults match [`write_bin`], which trusts the model and writes it in
+/// its own order. With both sort options set, bins that only differ in entry
+/// and field order (as [`compare_bins`](crate::compare::compare_bins) sees
+/// them) are written to identical bytes, ready for content hashing and diffs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Fail with [`BinError::WrongItemType`] when a list, map or option item
+    /// is not of the type the container declares, instead of writing it as is
+    pub check_item_types: bool,
+    /// Write entries in order of their key hash
+    pub sort_entries_by_hash: bool,
+    /// Write the fields of every entry, pointer and embed in order of their key hash
+    ///
+    /// Structures holding a [`BinValue::Raw`] that absorbed the fields after it
+    /// are written in their original order, since those fields can't be moved.
+    pub sort_fields_by_key: bool,
+}
+
+/// Writes the primitives of the bin format into a growing buffer.
+///
+/// The counterpart of [`BinaryReader`]. Sized blocks are written by writing a
+/// `u32` placeholder, the contents, then [`write_size_at`](Self::write_size_at).
+#[derive(Debug, Default)]
+pub struct BinaryWriter {
+    cursor: Cursor<Vec<u8>>,
+    opts: WriteOptions,
+}
+
+impl BinaryWriter {
+    pub fn new() -> Self {
+        Self::with_options(WriteOptions::default())
+    }
+
+    pub fn with_options(opts: WriteOptions) -> Self {
+        Self {
+            cursor: Cursor::new(Vec::new()),
+            opts,
+        }
+    }
+
+    /// Offset of the next byte to write
+    pub fn position(&self) -> u64 {
+        self.cursor.position()
+    }
+
+    /// The bytes written so far
+    pub fn into_inner(self) -> Vec<u8> {
+        self.cursor.into_inner()
+    }
+
+    pub fn write_bytes(&mut self, v: &[u8]) -> Result<(), BinError> {
+        self.cursor.write_all(v)?;
+        Ok(())
+    }
+
+    pub fn write_u8(&mut self, v: u8) -> Result<(), BinError> {
+        self.cursor.write_u8(v)?;
+        Ok(())
+    }
+
+    pub fn write_u16(&mut self, v: u16) -> Result<(), BinError> {
+        self.cursor.write_u16::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_u32(&mut self, v: u32) -> Result<(), BinError> {
+        self.cursor.write_u32::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_u64(&mut self, v: u64) -> Result<(), BinError> {
+        self.cursor.write_u64::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_i8(&mut self, v: i8) -> Result<(), BinError> {
+        self.cursor.write_i8(v)?;
+        Ok(())
+    }
+
+    pub fn write_i16(&mut self, v: i16) -> Result<(), BinError> {
+        self.cursor.write_i16::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_i32(&mut self, v: i32) -> Result<(), BinError> {
+        self.cursor.write_i32::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_i64(&mut self, v: i64) -> Result<(), BinError> {
+        self.cursor.write_i64::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_f32(&mut self, v: f32) -> Result<(), BinError> {
+        self.cursor.write_f32::<LE>(v)?;
+        Ok(())
+    }
+
+    pub fn write_bool(&mut self, v: bool) -> Result<(), BinError> {
+        self.write_u8(if v { 1 } else { 0 })
+    }
+
+    /// A string with a u16 length prefix.
+    ///
+    /// The format has no longer encoding (the game reads the prefix as a
+    /// plain u16), so strings over 65535 bytes are a [`BinError::TooLarge`].
+    pub fn write_string(&mut self, v: &str) -> Result<(), BinError> {
+        self.write_u16(checked_len(v.len(), u16::MAX)?)?;
+        self.cursor.write_all(v.as_bytes())?;
+        Ok(())
+    }
+
+    /// A type byte
+    pub fn write_type(&mut self, v: BinType) -> Result<(), BinError> {
+        self.write_u8(v as u8)
+    }
+
+    pub fn write_vec2(&mut self, v: [f32; 2]) -> Result<(), BinError> {
+        for x in v { self.write_f32(x)?; }
+        Ok(())
+    }
+
+    pub fn write_vec3(&mut self, v: [f32; 3]) -> Result<(), BinError> {
+        for x in v { self.write_f32(x)?; }
+        Ok(())
+    }
+
+    pub fn write_vec4(&mut self, v: [f32; 4]) -> Result<(), BinError> {
+        for x in v { self.write_f32(x)?; }
+        Ok(())
+    }
+
+    pub fn write_mtx44(&mut self, v: [f32; 16]) -> Result<(), BinError> {
         for x in v { self.write_f32(x)?; }
         Ok(())
     }
 
-    fn write_rgba(&mut self, v: [u8; 4]) -> Result<(), BinError> {
+    pub fn write_rgba(&mut self, v: [u8; 4]) -> Result<(), BinError> {
         self.cursor.write_all(&v)?;
         Ok(())
     }
 
+    /// Fill the size placeholder at `size_pos` with the length of what follows `start_pos`
+    pub fn write_size_at(&mut self, size_pos: u64, start_pos: u64) -> Result<(), BinError> {
+        let size = checked_len((self.position() - start_pos) as usize, u32::MAX)?;
+        self.write_at(size_pos, size)
+    }
+
     fn write_at(&mut self, pos: u64, v: u32) -> Result<(), BinError> {
         let current = self.position();
         self.cursor.seek(SeekFrom::Start(pos))?;
@@ -501,7 +1826,8 @@ impl BinaryWriter {
         Ok(())
     }
 
-    fn write_value(&mut self, v: &BinValue) -> Result<(), BinError> {
+    /// A value without its type byte, as [`BinaryReader::read_value`] reads it
+    pub fn write_value(&mut self, v: &BinValue) -> Result<(), BinError> {
         match v {
             BinValue::None => {},
             BinValue::Bool(b) => self.write_bool(*b)?,
@@ -530,21 +1856,32 @@ impl BinaryWriter {
             BinValue::Option { value_type, item } => self.write_option(*value_type, item.as_ref().map(|b| b.as_ref()))?,
             BinValue::Map { key_type, value_type, items } => self.write_map(*key_type, *value_type, items)?,
             BinValue::Flag(b) => self.write_bool(*b)?,
+            BinValue::Raw { bytes, .. } => self.cursor.write_all(bytes)?,
         }
         Ok(())
     }
 
+    /// Write a container item, checking its type if `check_item_types` is set
+    fn write_item(&mut self, expected: BinType, item: &BinValue) -> Result<(), BinError> {
+        if self.opts.check_item_types {
+            let found = get_value_type(item);
+            if found != expected {
+                return Err(BinError::WrongItemType { path: String::new(), expected, found });
+            }
+        }
+        self.write_value(item)
+    }
+
     fn write_list(&mut self, value_type: BinType, items: &[BinValue]) -> Result<(), BinError> {
         self.write_type(value_type)?;
         let size_pos = self.position();
         self.write_u32(0)?; // size placeholder
-        self.write_u32(items.len() as u32)?;
         let start_pos = self.position();
-        for item in items {
-            self.write_value(item)?;
+        self.write_u32(checked_len(items.len(), u32::MAX)?)?;
+        for (i, item) in items.iter().enumerate() {
+            self.write_item(value_type, item).map_err(|e| e.in_path(format_args!("[{}]", i)))?;
         }
-        let end_pos = self.position();
-        self.write_at(size_pos, (end_pos - start_pos) as u32)?;
+        self.write_size_at(size_pos, start_pos)?;
         Ok(())
     }
 
@@ -552,13 +1889,12 @@ impl BinaryWriter {
         self.write_type(value_type)?;
         let size_pos = self.position();
         self.write_u32(0)?; // size placeholder
-        self.write_u32(items.len() as u32)?;
         let start_pos = self.position();
-        for item in items {
-            self.write_value(item)?;
+        self.write_u32(checked_len(items.len(), u32::MAX)?)?;
+        for (i, item) in items.iter().enumerate() {
+            self.write_item(value_type, item).map_err(|e| e.in_path(format_args!("[{}]", i)))?;
         }
-        let end_pos = self.position();
-        self.write_at(size_pos, (end_pos - start_pos) as u32)?;
+        self.write_size_at(size_pos, start_pos)?;
         Ok(())
     }
 
@@ -569,16 +1905,9 @@ impl BinaryWriter {
         }
         let size_pos = self.position();
         self.write_u32(0)?; // size placeholder
-        self.write_u16(items.len() as u16)?;
         let start_pos = self.position();
-        for field in items {
-            self.write_u32(field.key)?;
-            let type_ = get_value_type(&field.value);
-            self.write_type(type_)?;
-            self.write_value(&field.value)?;
-        }
-        let end_pos = self.position();
-        self.write_at(size_pos, (end_pos - start_pos) as u32)?;
+        self.write_fields(items)?;
+        self.write_size_at(size_pos, start_pos)?;
         Ok(())
     }
 
@@ -586,25 +1915,48 @@ impl BinaryWriter {
         self.write_u32(name)?;
         let size_pos = self.position();
         self.write_u32(0)?; // size placeholder
-        self.write_u16(items.len() as u16)?;
         let start_pos = self.position();
-        for field in items {
-            self.write_u32(field.key)?;
-            let type_ = get_value_type(&field.value);
-            self.write_type(type_)?;
-            self.write_value(&field.value)?;
-        }
-        let end_pos = self.position();
-        self.write_at(size_pos, (end_pos - start_pos) as u32)?;
+        self.write_fields(items)?;
+        self.write_size_at(size_pos, start_pos)?;
         Ok(())
     }
 
+    /// Write the field count and fields of a pointer, embed or entry
+    fn write_fields(&mut self, items: &[Field]) -> Result<(), BinError> {
+        // Raw values also carry the fields they absorbed
+        let count: usize = items.iter()
+            .map(|field| match &field.value {
+                BinValue::Raw { absorbed_fields, .. } => 1 + *absorbed_fields as usize,
+                _ => 1,
+            })
+            .sum();
+        self.write_u16(checked_len(count, u16::MAX)?)?;
+        let absorbs = count != items.len();
+        if self.opts.sort_fields_by_key && !absorbs && !items.is_sorted_by_key(|field| field.key) {
+            let mut sorted: Vec<&Field> = items.iter().collect();
+            sorted.sort_by_key(|field| field.key);
+            sorted.into_iter().try_for_each(|field| self.write_field(field))
+        } else {
+            items.iter().try_for_each(|field| self.write_field(field))
+        }
+    }
+
+    fn write_field(&mut self, field: &Field) -> Result<(), BinError> {
+        self.write_u32(field.key)?;
+        match &field.value {
+            BinValue::Raw { type_byte, .. } => self.write_u8(*type_byte)?,
+            value => self.write_type(get_value_type(value))?,
+        }
+        self.write_value(&field.value)
+            .map_err(|e| e.in_path(format_args!(".{}", crate::compare::field_label(field))))
+    }
+
     fn write_option(&mut self, value_type: BinType, item: Option<&BinValue>) -> Result<(), BinError> {
         self.write_type(value_type)?;
         match item {
             Some(v) => {
                 self.write_u8(1)?;
-                self.write_value(v)?;
+                self.write_item(value_type, v)?;
             },
             None => {
                 self.write_u8(0)?;
@@ -613,24 +1965,47 @@ impl BinaryWriter {
         Ok(())
     }
 
+    /// Write an entry record: length, key hash, field count and fields
+    pub fn write_entry(&mut self, key: u32, fields: &[Field]) -> Result<(), BinError> {
+        let entry_pos = self.position();
+        self.write_u32(0)?; // size placeholder
+        let start_pos = self.position();
+        self.write_u32(key)?;
+        self.write_fields(fields)?;
+        self.write_size_at(entry_pos, start_pos)?;
+        Ok(())
+    }
+
+    /// Write entries (hash, fields) one after another
+    fn write_entries(&mut self, entries: &[(u32, &[Field])]) -> Result<(), BinError> {
+        for (h, fields) in entries {
+            self.write_entry(*h, fields).map_err(|e| e.in_path(format_args!("entries[{:#010x}]", h)))?;
+        }
+        Ok(())
+    }
+
     fn write_map(&mut self, key_type: BinType, value_type: BinType, items: &[(BinValue, BinValue)]) -> Result<(), BinError> {
         self.write_type(key_type)?;
         self.write_type(value_type)?;
         let size_pos = self.position();
         self.write_u32(0)?; // size placeholder
-        self.write_u32(items.len() as u32)?;
         let start_pos = self.position();
-        for (key, value) in items {
-            self.write_value(key)?;
-            self.write_value(value)?;
+        self.write_u32(checked_len(items.len(), u32::MAX)?)?;
+        for (i, (key, value)) in items.iter().enumerate() {
+            self.write_item(key_type, key).map_err(|e| e.in_path(format_args!("[#{}]", i)))?;
+            self.write_item(value_type, value).map_err(|e| e.in_path(format_args!("[{}]", crate::compare::key_label(key))))?;
         }
-        let end_pos = self.position();
-        self.write_at(size_pos, (end_pos - start_pos) as u32)?;
+        self.write_size_at(size_pos, start_pos)?;
         Ok(())
     }
 }
 
-fn get_value_type(v: &BinValue) -> BinType {
+/// `len` as a count or size field, if it fits in one (`limit` is the field's maximum)
+fn checked_len<T: TryFrom<usize> + Into<u64>>(len: usize, limit: T) -> Result<T, BinError> {
+    T::try_from(len).map_err(|_| BinError::TooLarge { path: String::new(), limit: limit.into() })
+}
+
+pub(crate) fn get_value_type(v: &BinValue) -> BinType {
     match v {
         BinValue::None => BinType::None,
         BinValue::Bool(_) => BinType::Bool,
@@ -659,11 +2034,97 @@ fn get_value_type(v: &BinValue) -> BinType {
         BinValue::Option { .. } => BinType::Option,
         BinValue::Map { .. } => BinType::Map,
         BinValue::Flag(_) => BinType::Flag,
+        // Unknown type bytes have no BinType; callers writing fields use `type_byte`
+        BinValue::Raw { type_byte, .. } => BinType::try_from(*type_byte).unwrap_or(BinType::None),
     }
 }
 
-pub fn write_bin(bin: &Bin) -> Result<Vec<u8>, BinError> {
+/// Encode a single entry record exactly as `write_bin` lays it out in the entry data.
+///
+/// The entry's class hash is not part of the record; it lives in the file's
+/// class hash table.
+pub fn encode_entry(key: u32, fields: &[Field]) -> Result<Vec<u8>, BinError> {
     let mut writer = BinaryWriter::new();
+    writer.write_entry(key, fields)?;
+    Ok(writer.into_inner())
+}
+
+pub fn write_bin(bin: &Bin) -> Result<Vec<u8>, BinError> {
+    write_bin_impl(bin, 1, WriteOptions::default(), None)
+}
+
+/// [`write_bin_with_options`], reporting an [`Event::EntryWritten`](crate::progress::Event::EntryWritten) per entry.
+pub fn write_bin_with_progress(bin: &Bin, opts: &WriteOptions, progress: &mut dyn crate::progress::Progress) -> Result<Vec<u8>, BinError> {
+    write_bin_impl(bin, 1, *opts, Some(progress))
+}
+
+/// [`write_bin`] with checks on the model, see [`WriteOptions`].
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::{write_bin_with_options, BinError, WriteOptions};
+/// use ritobin_rust::model::{Bin, BinType, BinValue, Field};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+/// bin.sections.insert("version".to_string(), BinValue::U32(3));
+/// bin.sections.insert("entries".to_string(), BinValue::Map {
+///     key_type: BinType::Hash,
+///     value_type: BinType::Embed,
+///     items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed {
+///         name: 20,
+///         name_str: None,
+///         items: vec![Field { key: 1, key_str: None, value: BinValue::List {
+///             value_type: BinType::U32,
+///             items: vec![BinValue::U32(1), BinValue::F32(2.0)],
+///         }}],
+///     })],
+/// });
+///
+/// let opts = WriteOptions { check_item_types: true, ..Default::default() };
+/// let err = write_bin_with_options(&bin, &opts).unwrap_err();
+/// assert!(matches!(err, BinError::WrongItemType { expected: BinType::U32, found: BinType::F32, .. }));
+/// assert_eq!(err.path(), Some("entries[0x0000000a].0x00000001[1]"));
+/// ```
+pub fn write_bin_with_options(bin: &Bin, opts: &WriteOptions) -> Result<Vec<u8>, BinError> {
+    write_bin_impl(bin, 1, *opts, None)
+}
+
+/// [`write_bin`], encoding the entries on `threads` threads (0 for one per CPU).
+///
+/// Each thread encodes a contiguous run of entries into its own buffer and
+/// the buffers are joined in file order, so the output (or the first error)
+/// is the same as with `write_bin`. Worth it for large merged bins.
+///
+/// # Examples
+///
+/// ```no_run
+/// let bin = ritobin_rust::binary::read_bin(&std::fs::read("Map11.bin")?)?;
+/// let data = ritobin_rust::binary::write_bin_parallel(&bin, 0)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn write_bin_parallel(bin: &Bin, threads: usize) -> Result<Vec<u8>, BinError> {
+    write_bin_impl(bin, resolve_threads(threads), WriteOptions::default(), None)
+}
+
+/// The number of threads to use for `threads`, where 0 means one per CPU
+pub(crate) fn resolve_threads(threads: usize) -> usize {
+    match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// [`write_bin_with_options`] on `threads` threads, as in [`write_bin_parallel`],
+/// reporting written entries to `progress`
+pub(crate) fn write_bin_impl(
+    bin: &Bin,
+    threads: usize,
+    opts: WriteOptions,
+    progress: Option<&mut dyn crate::progress::Progress>,
+) -> Result<Vec<u8>, BinError> {
+    let mut writer = BinaryWriter::with_options(opts);
 
     let type_section = bin.sections.get("type").ok_or(BinError::InvalidValue(BinType::None))?;
     let type_str = match type_section {
@@ -672,40 +2133,14 @@ pub fn write_bin(bin: &Bin) -> Result<Vec<u8>, BinError> {
     };
 
     if type_str == "PTCH" {
+        let header = match bin.sections.get("ptch_header") {
+            Some(BinValue::U64(v)) => *v,
+            Some(_) => return Err(BinError::InvalidValue(BinType::U64)),
+            None => DEFAULT_PTCH_HEADER,
+        };
         writer.cursor.write_all(b"PTCH")?;
-        writer.write_u64(0)?; // unk? ritobin writes u32 1 then u32 0. Wait.
-        // ritobin: writer.write(uint32_t{ 1 }); writer.write(uint32_t{ 0 });
-        // My read_bin skipped u64. So it's 8 bytes.
-        // Let's match ritobin exactly: 1u32, 0u32.
-        // But wait, read_bin: let _unk = reader.read_u64()?;
-        // If ritobin writes 1 then 0 (both u32), that's 0x00000001 followed by 0x00000000 (LE).
-        // So as u64 LE it is 0x0000000000000001.
-        // I'll write it as u64 1.
-        // Actually ritobin writes:
-        // writer.write(uint32_t{ 1 });
-        // writer.write(uint32_t{ 0 });
-        // This is 1, 0.
-        // read_bin reads u64.
-        // I'll write two u32s to be safe and explicit.
-        // But I don't have write_u32 exposed in write_bin scope easily unless I use writer.
-        // I'll fix read_bin to match if needed, but u64 read is fine.
-        // I'll write u64(1) which is 1 followed by 0s.
-        // Wait, 1u32 is 01 00 00 00. 0u32 is 00 00 00 00.
-        // So 01 00 00 00 00 00 00 00.
-        // u64(1) is 01 00 00 00 00 00 00 00.
-        // So yes, write_u64(1) is correct.
-        // But ritobin writes 1 then 0.
-        // I'll use write_u64(1).
+        writer.write_u64(header)?;
     }
-    
-    // Actually, ritobin writes 1 then 0.
-    // If I write u64(1), it's 1.
-    // So:
-    if type_str == "PTCH" {
-         writer.cursor.write_all(b"PTCH")?;
-         writer.write_u64(1)?; 
-    }
-
     writer.cursor.write_all(b"PROP")?;
 
     let version_section = bin.sections.get("version").ok_or(BinError::InvalidValue(BinType::None))?;
@@ -716,98 +2151,370 @@ pub fn write_bin(bin: &Bin) -> Result<Vec<u8>, BinError> {
     writer.write_u32(version)?;
 
     if version >= 2 {
-        if let Some(linked_section) = bin.sections.get("linked") {
-            if let BinValue::List { items, .. } = linked_section {
-                writer.write_u32(items.len() as u32)?;
-                for item in items {
-                    if let BinValue::String(s) = item {
-                        writer.write_string(s)?;
-                    }
+        if let Some(BinValue::List { items, .. }) = bin.sections.get("linked") {
+            writer.write_u32(checked_len(items.len(), u32::MAX).map_err(|e| e.in_path("linked"))?)?;
+            for (i, item) in items.iter().enumerate() {
+                if let BinValue::String(s) = item {
+                    writer.write_string(s).map_err(|e| e.in_path(format_args!("linked[{}]", i)))?;
                 }
-            } else {
-                writer.write_u32(0)?;
             }
         } else {
             writer.write_u32(0)?;
         }
     }
 
-    if let Some(entries_section) = bin.sections.get("entries") {
-        if let BinValue::Map { items, .. } = entries_section {
-            writer.write_u32(items.len() as u32)?;
-            let hashes_pos = writer.position();
-            writer.skip((items.len() * 4) as u64)?;
+    if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+        writer.write_u32(checked_len(items.len(), u32::MAX).map_err(|e| e.in_path("entries"))?)?;
+        let hashes_pos = writer.position();
+        writer.skip((items.len() * 4) as u64)?;
             
-            let mut hashes = Vec::with_capacity(items.len());
-            for (key, value) in items {
-                if let BinValue::Embed { name, items: fields, .. } = value {
-                    hashes.push(*name);
-                    if let BinValue::Hash { value: h, .. } = key {
-                        let entry_pos = writer.position();
-                        writer.write_u32(0)?; // size placeholder
-                        writer.write_u32(*h)?;
-                        writer.write_u16(fields.len() as u16)?;
-                        let start_pos = writer.position();
-                        for field in fields {
-                            writer.write_u32(field.key)?;
-                            let type_ = get_value_type(&field.value);
-                            writer.write_type(type_)?;
-                            writer.write_value(&field.value)?;
-                        }
-                        let end_pos = writer.position();
-                        writer.write_at(entry_pos, (end_pos - start_pos) as u32)?;
+        let mut items: Vec<_> = items.iter().collect();
+        if opts.sort_entries_by_hash {
+            items.sort_by_key(|(key, _)| match key {
+                BinValue::Hash { value, .. } => *value,
+                _ => 0,
+            });
+        }
+        let mut hashes = Vec::with_capacity(items.len());
+        let mut entries = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            if let BinValue::Embed { name, items: fields, .. } = value {
+                hashes.push(*name);
+                if let BinValue::Hash { value: h, .. } = key {
+                    entries.push((*h, fields.as_slice()));
+                }
+            }
+        }
+        let total = entries.len();
+        if threads < 2 || entries.len() < 2 {
+            match progress {
+                Some(progress) => {
+                    for (index, entry) in entries.iter().enumerate() {
+                        writer.write_entries(std::slice::from_ref(entry))?;
+                        progress.event(crate::progress::Event::EntryWritten { hash: entry.0, index, total });
                     }
                 }
+                None => writer.write_entries(&entries)?,
             }
-            writer.write_u32_slice_at(hashes_pos, &hashes)?;
         } else {
-            writer.write_u32(0)?;
+            let run = entries.len().div_ceil(threads);
+            let buffers: Vec<Result<Vec<u8>, BinError>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = entries.chunks(run)
+                    .map(|run| scope.spawn(move || {
+                        let mut writer = BinaryWriter::with_options(opts);
+                        writer.write_entries(run)?;
+                        Ok(writer.into_inner())
+                    }))
+                    .collect();
+                handles.into_iter().map(|h| h.join().expect("entry encoding thread panicked")).collect()
+            });
+            // Runs finish out of order, so report each one as it is joined in
+            let mut progress = progress;
+            let mut index = 0;
+            for (buffer, run) in buffers.into_iter().zip(entries.chunks(run)) {
+                writer.cursor.write_all(&buffer?)?;
+                for entry in run {
+                    if let Some(progress) = progress.as_deref_mut() {
+                        progress.event(crate::progress::Event::EntryWritten { hash: entry.0, index, total });
+                    }
+                    index += 1;
+                }
+            }
         }
+        writer.write_u32_slice_at(hashes_pos, &hashes)?;
     } else {
         writer.write_u32(0)?;
     }
 
     if type_str == "PTCH" && version >= 3 {
-         // Patches
-         if let Some(patches_section) = bin.sections.get("patches") {
-            if let BinValue::Map { items, .. } = patches_section {
-                writer.write_u32(items.len() as u32)?;
-                for (key, value) in items {
-                    if let BinValue::Hash { value: h, .. } = key {
-                        writer.write_u32(*h)?;
-                        let entry_pos = writer.position();
-                        writer.write_u32(0)?; // size placeholder
+        // Patches
+        if let Some(BinValue::Map { items, .. }) = bin.sections.get("patches") {
+            writer.write_u32(checked_len(items.len(), u32::MAX).map_err(|e| e.in_path("patches"))?)?;
+            for (key, value) in items {
+                if let BinValue::Hash { value: h, .. } = key {
+                    let in_patch = |e: BinError| e.in_path(format_args!("patches[{:#010x}]", h));
+                    writer.write_u32(*h)?;
+                    let entry_pos = writer.position();
+                    writer.write_u32(0)?; // size placeholder
                         
-                        if let BinValue::Embed { items: fields, .. } = value {
-                            // Expect "path" and "value" fields
-                            let path_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("path").0);
-                            let value_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("value").0);
+                    if let BinValue::Embed { items: fields, .. } = value {
+                        // Expect "path" and "value" fields
+                        let path_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("path").0);
+                        let value_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("value").0);
                             
-                            if let (Some(path), Some(val)) = (path_field, value_field) {
-                                let val_type = get_value_type(&val.value);
-                                writer.write_type(val_type)?;
-                                if let BinValue::String(s) = &path.value {
-                                    writer.write_string(s)?;
-                                }
-                                writer.write_value(&val.value)?;
+                        if let (Some(path), Some(val)) = (path_field, value_field) {
+                            let val_type = get_value_type(&val.value);
+                            writer.write_type(val_type)?;
+                            if let BinValue::String(s) = &path.value {
+                                writer.write_string(s).map_err(|e| in_patch(e.in_path(".path")))?;
                             }
+                            writer.write_value(&val.value).map_err(|e| in_patch(e.in_path(".value")))?;
                         }
-                        
-                        let end_pos = writer.position();
-                        writer.write_at(entry_pos, (end_pos - entry_pos - 4) as u32)?;
                     }
+                        
+                    writer.write_size_at(entry_pos, entry_pos + 4).map_err(in_patch)?;
                 }
-            } else {
-                writer.write_u32(0)?;
             }
-         } else {
-             writer.write_u32(0)?;
-         }
+        } else {
+            writer.write_u32(0)?;
+        }
     }
 
     Ok(writer.into_inner())
 }
 
+/// The number of bytes [`write_bin`] produces for `bin`, computed without writing it.
+///
+/// Exact for every bin `write_bin` accepts, so space can be reserved (e.g.
+/// in a WAD) before serializing. Use [`SizeProfile`](crate::profile::SizeProfile)
+/// for a breakdown of where the bytes go.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::{estimate_size, write_bin};
+///
+/// for case in ritobin_rust::corpus::cases() {
+///     assert_eq!(estimate_size(&case.bin), write_bin(&case.bin).unwrap().len());
+/// }
+/// ```
+pub fn estimate_size(bin: &Bin) -> usize {
+    let is_patch = matches!(bin.sections.get("type"), Some(BinValue::String(s)) if s == "PTCH");
+    let version = match bin.sections.get("version") {
+        Some(BinValue::U32(v)) => *v,
+        _ => 0,
+    };
+    // Magic(s) and version
+    let mut size = if is_patch { 4 + 8 + 4 + 4 } else { 4 + 4 };
+
+    if version >= 2 {
+        size += 4;
+        if let Some(BinValue::List { items, .. }) = bin.sections.get("linked") {
+            size += items.iter().map(|item| match item {
+                BinValue::String(s) => 2 + s.len(),
+                _ => 0,
+            }).sum::<usize>();
+        }
+    }
+
+    size += 4;
+    if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+        // Class hash table, then the entries
+        size += items.len() * 4;
+        size += items.iter().map(|(key, value)| match (key, value) {
+            (BinValue::Hash { .. }, BinValue::Embed { items: fields, .. }) => estimate_entry_size(fields),
+            _ => 0,
+        }).sum::<usize>();
+    }
+
+    if is_patch && version >= 3 {
+        size += 4;
+        if let Some(BinValue::Map { items, .. }) = bin.sections.get("patches") {
+            for (key, value) in items {
+                if !matches!(key, BinValue::Hash { .. }) {
+                    continue;
+                }
+                // Key hash and length
+                size += 4 + 4;
+                if let BinValue::Embed { items: fields, .. } = value {
+                    let path_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("path").0);
+                    let value_field = fields.iter().find(|f| f.key == crate::hash::Fnv1a::new("value").0);
+                    if let (Some(path), Some(val)) = (path_field, value_field) {
+                        // Value type, path and value
+                        size += 1 + value_size(&val.value);
+                        if let BinValue::String(s) = &path.value {
+                            size += 2 + s.len();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    size
+}
+
+/// The number of bytes [`encode_entry`] produces for an entry with `fields`.
+pub fn estimate_entry_size(fields: &[Field]) -> usize {
+    // Length and key hash
+    4 + 4 + fields_size(fields)
+}
+
+/// Encoded size of a field count and fields
+fn fields_size(fields: &[Field]) -> usize {
+    // Key hash and type byte per field
+    2 + fields.iter().map(|field| 4 + 1 + value_size(&field.value)).sum::<usize>()
+}
+
+/// Encoded size of a value without its type byte
+fn value_size(value: &BinValue) -> usize {
+    match value {
+        BinValue::None => 0,
+        BinValue::Bool(_) | BinValue::Flag(_) | BinValue::I8(_) | BinValue::U8(_) => 1,
+        BinValue::I16(_) | BinValue::U16(_) => 2,
+        BinValue::I32(_) | BinValue::U32(_) | BinValue::F32(_) | BinValue::Hash { .. } | BinValue::Link { .. } | BinValue::Rgba(_) => 4,
+        BinValue::I64(_) | BinValue::U64(_) | BinValue::File { .. } | BinValue::Vec2(_) => 8,
+        BinValue::Vec3(_) => 12,
+        BinValue::Vec4(_) => 16,
+        BinValue::Mtx44(_) => 64,
+        BinValue::String(s) => 2 + s.len(),
+        // Type, size and count
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => 1 + 4 + 4 + items.iter().map(value_size).sum::<usize>(),
+        BinValue::Pointer { name: 0, .. } => 4,
+        // Class hash and size
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => 4 + 4 + fields_size(items),
+        // Type and presence flag
+        BinValue::Option { item, .. } => 1 + 1 + item.as_deref().map_or(0, value_size),
+        // Key and value types, size and count
+        BinValue::Map { items, .. } => 1 + 1 + 4 + 4 + items.iter().map(|(k, v)| value_size(k) + value_size(v)).sum::<usize>(),
+        BinValue::Raw { bytes, .. } => bytes.len(),
+    }
+}
+
+/// Read a bin file by mapping it into memory instead of reading it into a buffer.
+///
+/// Avoids holding a full copy of the file alongside the parsed model, which
+/// matters for map geometry bins of hundreds of megabytes.
+///
+/// # Safety
+///
+/// As for [`Mmap::open`](crate::mmap::Mmap::open): the file must not be
+/// truncated or written to, by this or any other process, until this returns.
+#[cfg(feature = "mmap")]
+pub unsafe fn read_bin_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<Bin, BinError> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the map is dropped before returning, and the caller guarantees
+    // the file stays unchanged until then
+    let map = unsafe { crate::mmap::Mmap::open(&file)? };
+    read_bin(&map)
+}
+
+/// The word after the `PTCH` magic when a bin has no `ptch_header` section
+///
+/// Matches what the game's own patch files carry (`1u32` followed by `0u32`).
+pub const DEFAULT_PTCH_HEADER: u64 = 1;
+
+/// Write `bin`, failing with `BinError::OverBudget` if it takes more than
+/// `budget` bytes.
+///
+/// The error carries a [`BudgetReport`](crate::profile::BudgetReport) with
+/// the size of every entry and the longest strings, to guide trimming.
+pub fn write_bin_within(bin: &Bin, budget: u64) -> Result<Vec<u8>, BinError> {
+    let data = write_bin(bin)?;
+    check_budget(bin, &data, budget)?;
+    Ok(data)
+}
+
+/// Fail with `BinError::OverBudget` if `data`, the encoding of `bin`, is over `budget` bytes.
+pub fn check_budget(bin: &Bin, data: &[u8], budget: u64) -> Result<(), BinError> {
+    let size = data.len() as u64;
+    if size > budget {
+        return Err(BinError::OverBudget(Box::new(crate::profile::BudgetReport::new(bin, size, budget))));
+    }
+    Ok(())
+}
+
+/// Write `bin`, then read the bytes back and check they describe the same bin.
+///
+/// Returns `BinError::RoundTripMismatch` listing every difference instead of
+/// the bytes when the writer could not represent the model, e.g. a null
+/// pointer with fields or sections the format has no room for. Mistyped
+/// container items fail early with `BinError::WrongItemType`.
+pub fn write_bin_checked(bin: &Bin) -> Result<Vec<u8>, BinError> {
+    write_checked(bin, WriteOptions::default())
+}
+
+/// [`write_bin_checked`], sorting as `opts` says; item types are always checked
+pub(crate) fn write_checked(bin: &Bin, opts: WriteOptions) -> Result<Vec<u8>, BinError> {
+    use crate::compare::{compare_bins, CompareOptions};
+
+    let data = write_bin_with_options(bin, &WriteOptions { check_item_types: true, ..opts })?;
+    let mut written = read_bin(&data)?;
+    // The reader always produces `linked` (and `patches` and `ptch_header` for
+    // PTCH); empty or default ones carry nothing
+    written.sections.retain(|key, value| {
+        let empty = match value {
+            BinValue::List { items, .. } => items.is_empty(),
+            BinValue::Map { items, .. } => items.is_empty(),
+            BinValue::U64(v) => key == "ptch_header" && *v == DEFAULT_PTCH_HEADER,
+            _ => false,
+        };
+        bin.sections.contains_key(key) || !empty
+    });
+    let diffs = compare_bins(bin, &written, CompareOptions { float_tolerance: 0.0 });
+    if !diffs.is_empty() {
+        return Err(BinError::RoundTripMismatch(diffs));
+    }
+    Ok(data)
+}
+
+/// Result of [`verify_roundtrip`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundTripReport {
+    pub original_len: u64,
+    pub written_len: u64,
+    /// First byte offset where the rewritten data differs from the original
+    pub first_difference: Option<u64>,
+    /// Path of the value at `first_difference` in the original, e.g.
+    /// `entries[0x1234abcd].0x2c3a9e81[3]`; empty for the header and entry table
+    pub path: Option<String>,
+}
+
+impl RoundTripReport {
+    /// Whether the rewritten data is byte-for-byte the original
+    pub fn is_identical(&self) -> bool {
+        self.first_difference.is_none()
+    }
+}
+
+impl std::fmt::Display for RoundTripReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.first_difference {
+            None => write!(f, "identical ({} bytes)", self.original_len),
+            Some(offset) => {
+                write!(f, "differs at offset {}", offset)?;
+                if let Some(path) = self.path.as_deref().filter(|p| !p.is_empty()) {
+                    write!(f, " ({})", path)?;
+                }
+                write!(f, ": {} bytes read, {} written", self.original_len, self.written_len)
+            }
+        }
+    }
+}
+
+/// Read `data`, write it back and compare the bytes.
+///
+/// A report that is not [identical](RoundTripReport::is_identical) means
+/// converting the file loses or changes something, even if it still reads
+/// back to the same values. Read and write errors are returned as errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// let data = std::fs::read("champion.bin")?;
+/// let report = ritobin_rust::binary::verify_roundtrip(&data)?;
+/// if !report.is_identical() {
+///     eprintln!("champion.bin {}", report);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn verify_roundtrip(data: &[u8]) -> Result<RoundTripReport, BinError> {
+    verify_roundtrip_with_options(data, &ReadOptions::default())
+}
+
+/// [`verify_roundtrip`], reading with `opts`
+pub fn verify_roundtrip_with_options(data: &[u8], opts: &ReadOptions) -> Result<RoundTripReport, BinError> {
+    let written = write_bin(&read_bin_with_options(data, opts)?)?;
+    let first_difference = data.iter().zip(&written).position(|(a, b)| a != b)
+        .or_else(|| (data.len() != written.len()).then(|| data.len().min(written.len())))
+        .map(|offset| offset as u64);
+    Ok(RoundTripReport {
+        original_len: data.len() as u64,
+        written_len: written.len() as u64,
+        first_difference,
+        path: first_difference.and_then(|offset| events::path_at(data, offset)),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -849,4 +2556,806 @@ mod tests {
         assert_eq!(bin.sections.get("type"), bin2.sections.get("type"));
         assert_eq!(bin.sections.get("version"), bin2.sections.get("version"));
     }
+
+    #[test]
+    fn test_round_trip_entries() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String("DATA/Shared.bin".to_string())],
+        });
+        let fields = vec![
+            Field { key: 1, key_str: None, value: BinValue::F32(1.5) },
+            Field { key: 2, key_str: None, value: BinValue::List {
+                value_type: BinType::U32,
+                items: vec![BinValue::U32(1), BinValue::U32(2)],
+            }},
+            Field { key: 3, key_str: None, value: BinValue::Map {
+                key_type: BinType::Hash,
+                value_type: BinType::String,
+                items: vec![(BinValue::Hash { value: 7, name: None }, BinValue::String("x".to_string()))],
+            }},
+            Field { key: 4, key_str: None, value: BinValue::Pointer {
+                name: 5,
+                name_str: None,
+                items: vec![Field { key: 6, key_str: None, value: BinValue::Bool(true) }],
+            }},
+        ];
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![
+                (BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: fields.clone() }),
+                (BinValue::Hash { value: 11, name: None }, BinValue::Embed { name: 20, name_str: None, items: fields }),
+            ],
+        });
+
+        let data = write_bin(&bin).unwrap();
+        let bin2 = read_bin(&data).unwrap();
+        assert_eq!(bin, bin2);
+        assert_eq!(write_bin(&bin2).unwrap(), data);
+        assert_eq!(write_bin_checked(&bin).unwrap(), data);
+
+        let lazy = LazyBin::parse(&data).unwrap();
+        assert_eq!(lazy.len(), 2);
+        assert_eq!(lazy.entries()[1].class, 20);
+        assert!(lazy.get_entry(12).unwrap().is_none());
+        let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") else { panic!() };
+        assert_eq!(lazy.get_entry(11).unwrap().as_ref(), Some(&items[1].1));
+        assert_eq!(lazy.to_bin().unwrap(), bin);
+        for threads in [0, 1, 2, 5] {
+            assert_eq!(read_bin_parallel(&data, &ReadOptions::default(), threads).unwrap(), bin);
+            assert_eq!(write_bin_parallel(&bin, threads).unwrap(), data);
+        }
+        assert_eq!(estimate_size(&bin), data.len());
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!() };
+        assert_eq!(estimate_entry_size(fields), encode_entry(10, fields).unwrap().len());
+        let mut damaged = data.clone();
+        damaged.truncate(damaged.len() - 1);
+        assert!(read_bin_parallel(&damaged, &ReadOptions::default(), 2).is_err());
+
+        let filtered = read_bin_filtered(&data, &std::collections::HashSet::from([11, 12])).unwrap();
+        let Some(BinValue::Map { items: kept, .. }) = filtered.sections.get("entries") else { panic!() };
+        assert_eq!(kept.as_slice(), &items[1..]);
+        assert_eq!(filtered.sections.get("linked"), bin.sections.get("linked"));
+
+        let index = EntryIndex::build(&data).unwrap();
+        let at = index.get(11).unwrap();
+        assert_eq!(*at, lazy.entries()[1]);
+        let BinValue::Embed { items: fields, .. } = &items[1].1 else { panic!() };
+        assert_eq!(decode_at(&data, at.offset).unwrap(), (11, fields.clone()));
+    }
+
+    #[test]
+    fn test_read_bin_parallel() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: (0..20_000).map(|i| (
+                BinValue::Hash { value: i, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![Field { key: 1, key_str: None, value: BinValue::U32(i) }] },
+            )).collect(),
+        });
+        let data = write_bin(&bin).unwrap();
+        let expected = read_bin(&data).unwrap();
+        for threads in [1, 2, 4] {
+            assert_eq!(read_bin_parallel(&data, &ReadOptions::default(), threads).unwrap(), expected);
+        }
+
+        // One deadline for all threads, though each reads too few values to reach it alone
+        let opts = ReadOptions { time_limit: Some(Duration::from_nanos(1)), ..Default::default() };
+        assert!(matches!(read_bin_with_options(&data, &opts).unwrap_err().root(), BinError::TimedOut(_)));
+        assert!(matches!(read_bin_parallel(&data, &opts, 4).unwrap_err().root(), BinError::TimedOut(_)));
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(read_bin_parallel(&trailing, &ReadOptions::default(), 4).unwrap(), expected);
+        let strict = ReadOptions { allow_trailing_data: false, ..Default::default() };
+        assert!(matches!(read_bin_parallel(&trailing, &strict, 4), Err(BinError::TrailingData(1))));
+    }
+
+    #[test]
+    fn test_write_bin_parallel() {
+        let entry = |i: u32, value: BinValue| (
+            BinValue::Hash { value: i, name: None },
+            BinValue::Embed { name: 20, name_str: None, items: vec![Field { key: 1, key_str: None, value }] },
+        );
+        let bin = |items| {
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+            bin.sections.insert("version".to_string(), BinValue::U32(3));
+            bin.sections.insert("entries".to_string(), BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items });
+            bin
+        };
+        let text = |i: u32| BinValue::String("x".repeat(i as usize % 50));
+        let good = bin((0..1000).map(|i| entry(i, text(i))).collect());
+        let data = write_bin(&good).unwrap();
+        for threads in [1, 2, resolve_threads(0), 7] {
+            assert_eq!(write_bin_parallel(&good, threads).unwrap(), data, "{} threads", threads);
+        }
+
+        // Strings over 64 KiB in entries of different runs: the first one is reported
+        let long = || BinValue::String("x".repeat(70_000));
+        let bad = bin((0..1000).map(|i| entry(i, if i == 400 || i == 900 { long() } else { text(i) })).collect());
+        let expected = write_bin(&bad).unwrap_err();
+        assert_eq!(expected.path(), Some("entries[0x00000190].0x00000001"));
+        for threads in [1, 2, resolve_threads(0), 7] {
+            let err = write_bin_parallel(&bad, threads).unwrap_err();
+            assert!(matches!(err, BinError::TooLarge { limit: 0xffff, .. }), "{} threads: {:?}", threads, err);
+            assert_eq!(err.path(), expected.path(), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn test_deterministic_write() {
+        let field = |key: u32| Field { key, key_str: None, value: BinValue::U32(key) };
+        let entry = |hash: u32, fields: Vec<Field>| (
+            BinValue::Hash { value: hash, name: None },
+            BinValue::Embed { name: 20, name_str: None, items: fields },
+        );
+        let bin = |items| {
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+            bin.sections.insert("version".to_string(), BinValue::U32(3));
+            bin.sections.insert("entries".to_string(), BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items });
+            bin
+        };
+        let shuffled = bin(vec![
+            entry(11, vec![field(2), Field { key: 1, key_str: None, value: BinValue::Pointer { name: 5, name_str: None, items: vec![field(4), field(3)] } }]),
+            entry(10, vec![]),
+        ]);
+        let sorted = bin(vec![
+            entry(10, vec![]),
+            entry(11, vec![Field { key: 1, key_str: None, value: BinValue::Pointer { name: 5, name_str: None, items: vec![field(3), field(4)] } }, field(2)]),
+        ]);
+        assert_ne!(write_bin(&shuffled).unwrap(), write_bin(&sorted).unwrap());
+
+        let opts = WriteOptions { sort_entries_by_hash: true, sort_fields_by_key: true, ..Default::default() };
+        let data = write_bin_with_options(&shuffled, &opts).unwrap();
+        assert_eq!(data, write_bin(&sorted).unwrap());
+        assert_eq!(write_bin_impl(&shuffled, 2, opts, None).unwrap(), data);
+        assert_eq!(write_checked(&shuffled, opts).unwrap(), data);
+    }
+
+    #[test]
+    fn test_deterministic_write_keeps_absorbed_fields() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![
+                Field { key: 3, key_str: None, value: BinValue::U32(7) },
+                Field { key: 1, key_str: None, value: BinValue::U32(8) },
+                Field { key: 2, key_str: None, value: BinValue::U32(9) },
+            ] })],
+        });
+        let mut data = write_bin(&bin).unwrap();
+        // Unknown type on the second field: it and the third are kept raw
+        let entry = LazyBin::parse(&data).unwrap().entries()[0].offset as usize;
+        data[entry + 14 + 9] = 0x7f;
+        let read = read_bin_with_options(&data, &ReadOptions { preserve_unknown_types: true, ..Default::default() }).unwrap();
+
+        let opts = WriteOptions { sort_fields_by_key: true, ..Default::default() };
+        assert_eq!(write_bin_with_options(&read, &opts).unwrap(), data);
+        assert_eq!(write_bin_impl(&read, 2, opts, None).unwrap(), data);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_read_bin_mmap() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        let path = std::env::temp_dir().join(format!("ritobin_mmap_{}.bin", std::process::id()));
+        std::fs::write(&path, write_bin(&bin).unwrap()).unwrap();
+
+        // SAFETY: the file is only removed once the read is done
+        let read = unsafe { read_bin_mmap(&path) };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap().sections["version"], BinValue::U32(3));
+    }
+
+    #[test]
+    fn test_write_checked_rejects_lossy_model() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: 10, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![
+                    // A null pointer can't carry fields
+                    Field { key: 1, key_str: None, value: BinValue::Pointer {
+                        name: 0,
+                        name_str: None,
+                        items: vec![Field { key: 2, key_str: None, value: BinValue::U8(1) }],
+                    }},
+                ] },
+            )],
+        });
+
+        match write_bin_checked(&bin) {
+            Err(e @ BinError::RoundTripMismatch(_)) => {
+                assert!(e.to_string().contains("(1 differences, first at entries[0x0000000a].0x00000001.0x00000002: "));
+                let BinError::RoundTripMismatch(diffs) = e else { unreachable!() };
+                assert_eq!(diffs.len(), 1);
+                assert_eq!(diffs[0].path, "entries[0x0000000a].0x00000001.0x00000002");
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        assert_eq!(BinError::RoundTripMismatch(Vec::new()).to_string(), "Written data does not read back as the source");
+
+        // A map key of the wrong type is caught before writing
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: 10, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![
+                    Field { key: 1, key_str: None, value: BinValue::Map {
+                        key_type: BinType::Hash,
+                        value_type: BinType::U8,
+                        items: vec![(BinValue::U32(2), BinValue::U8(3))],
+                    }},
+                ] },
+            )],
+        });
+        assert!(write_bin(&bin).is_ok());
+        match write_bin_checked(&bin) {
+            Err(e @ BinError::WrongItemType { expected: BinType::Hash, found: BinType::U32, .. }) => {
+                assert_eq!(e.path(), Some("entries[0x0000000a].0x00000001[#0]"));
+            }
+            other => panic!("expected a wrong item type, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_write_rejects_values_over_format_limits() {
+        let entry = |fields: Vec<Field>| {
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+            bin.sections.insert("version".to_string(), BinValue::U32(3));
+            bin.sections.insert("entries".to_string(), BinValue::Map {
+                key_type: BinType::Hash,
+                value_type: BinType::Embed,
+                items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: fields })],
+            });
+            bin
+        };
+
+        let long = entry(vec![Field { key: 1, key_str: Some("mNames".to_string()), value: BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String(String::new()), BinValue::String("x".repeat(u16::MAX as usize + 1))],
+        } }]);
+        match write_bin(&long) {
+            Err(BinError::TooLarge { path, limit }) => {
+                assert_eq!(path, "entries[0x0000000a].mNames[1]");
+                assert_eq!(limit, u16::MAX as u64);
+            }
+            other => panic!("expected a too-large error, got {:?}", other),
+        }
+
+        let mut linked = entry(vec![]);
+        linked.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String("x".repeat(70_000))],
+        });
+        assert!(matches!(write_bin(&linked), Err(BinError::TooLarge { path, .. }) if path == "linked[0]"));
+        let mut patch = entry(vec![]);
+        patch.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+        patch.sections.insert("patches".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 6, name: None }, BinValue::Embed { name: 0, name_str: None, items: vec![
+                Field { key: crate::hash::Fnv1a::new("path").0, key_str: None, value: BinValue::String("x".repeat(70_000)) },
+                Field { key: crate::hash::Fnv1a::new("value").0, key_str: None, value: BinValue::U8(1) },
+            ] })],
+        });
+        assert!(matches!(write_bin(&patch), Err(BinError::TooLarge { path, .. }) if path == "patches[0x00000006].path"));
+
+        let many = entry(vec![Field { key: 1, key_str: None, value: BinValue::Flag(false) }; u16::MAX as usize + 1]);
+        let err = write_bin(&many).unwrap_err();
+        assert_eq!(err.to_string(), "Value at entries[0x0000000a] is too large for the bin format (limit 65535)");
+        assert!(write_bin(&entry(vec![Field { key: 1, key_str: None, value: BinValue::Flag(false) }; u16::MAX as usize])).is_ok());
+    }
+
+    #[test]
+    fn test_verify_roundtrip_reports_first_difference() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![
+                Field { key: 4, key_str: None, value: BinValue::Map {
+                    key_type: BinType::Hash,
+                    value_type: BinType::Embed,
+                    items: vec![(BinValue::Hash { value: 7, name: None }, BinValue::Embed { name: 30, name_str: None, items: vec![
+                        Field { key: 5, key_str: None, value: BinValue::List { value_type: BinType::Bool, items: vec![BinValue::Bool(false), BinValue::Bool(true)] } },
+                    ] })],
+                } },
+            ] })],
+        });
+        let mut data = write_bin(&bin).unwrap();
+        let report = verify_roundtrip(&data).unwrap();
+        assert!(report.is_identical());
+        assert_eq!(report.written_len, data.len() as u64);
+
+        // Any non-zero byte reads as true, but true is written as 1
+        *data.last_mut().unwrap() = 2;
+        let report = verify_roundtrip(&data).unwrap();
+        assert_eq!(report.first_difference, Some(data.len() as u64 - 1));
+        assert_eq!(report.path.as_deref(), Some("entries[0x0000000a].0x00000004[0x00000007].0x00000005[1]"));
+        assert_eq!(events::path_at(&data, 16), Some(String::new()));
+        assert_eq!(events::path_at(&data, data.len() as u64), None);
+    }
+
+    #[test]
+    fn test_find_embedded_bins() {
+        let cases = crate::corpus::cases();
+        let case = |name: &str| &cases.iter().find(|c| c.name == name).unwrap().bin;
+        let (prop, ptch) = (write_bin(case("all_types")).unwrap(), write_bin(case("ptch")).unwrap());
+
+        let mut data = b"junk PROP\x07\x00\x00\x00 more junk".to_vec();
+        let prop_at = data.len();
+        data.extend_from_slice(&prop);
+        data.extend_from_slice(b"PROP");
+        let ptch_at = data.len();
+        data.extend_from_slice(&ptch);
+        data.extend_from_slice(b"PTC");
+
+        assert_eq!(find_prop_blobs(&data), vec![prop_at..prop_at + prop.len(), ptch_at..ptch_at + ptch.len()]);
+        assert_eq!(read_bin_at(&data, prop_at).unwrap(), (case("all_types").clone(), prop.len()));
+        assert_eq!(read_bin_at(&data, ptch_at).unwrap(), (read_bin(&ptch).unwrap(), ptch.len()));
+        assert!(read_bin_at(&data, data.len() + 1).is_err());
+    }
+
+    #[test]
+    fn test_read_into_reuses_buffers() {
+        let cases = crate::corpus::cases();
+        let mut scratch = ReadScratch::new();
+        let mut bin = Bin::new();
+        for case in &cases {
+            let data = write_bin(&case.bin).unwrap();
+            scratch.read_into(&mut bin, &data, &ReadOptions::default()).unwrap();
+            assert_eq!(bin, read_bin(&data).unwrap(), "{}", case.name);
+        }
+
+        let data = write_bin(&cases[0].bin).unwrap();
+        read_bin_into(&mut bin, &data).unwrap();
+        let entries_ptr = |bin: &Bin| match &bin.sections["entries"] {
+            BinValue::Map { items, .. } => items.as_ptr(),
+            _ => unreachable!(),
+        };
+        let before = entries_ptr(&bin);
+        read_bin_into(&mut bin, &data).unwrap();
+        assert_eq!(entries_ptr(&bin), before);
+
+        assert!(scratch.read_into(&mut bin, &data[..data.len() - 1], &ReadOptions::default()).is_err());
+        assert!(bin.sections.is_empty());
+    }
+
+    #[test]
+    fn test_read_options_strictness() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: 10, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![
+                    Field { key: 1, key_str: None, value: BinValue::Embed { name: 30, name_str: None, items: vec![] } },
+                ] },
+            )],
+        });
+        let data = write_bin(&bin).unwrap();
+        let strict = ReadOptions { strict_sizes: true, allow_trailing_data: false, ..Default::default() };
+        assert!(read_bin_with_options(&data, &strict).is_ok());
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert!(read_bin(&trailing).is_ok());
+        assert!(matches!(read_bin_with_options(&trailing, &strict), Err(BinError::TrailingData(1))));
+
+        // Declare one byte more than the entry consumes
+        let entry = LazyBin::parse(&data).unwrap().entries()[0];
+        let at = entry.offset as usize;
+        let mut padded = data.clone();
+        padded[at..at + 4].copy_from_slice(&(entry.length + 1).to_le_bytes());
+        padded.insert(at + 4 + entry.length as usize, 0);
+        assert_eq!(read_bin(&padded).unwrap(), read_bin(&data).unwrap());
+        let err = read_bin_with_options(&padded, &strict).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            BinError::SizeMismatch { declared, read, .. } if *declared == entry.length + 1 && *read == entry.length as u64
+        ));
+        assert_eq!(err.path(), Some("entries[0x0000000a]"));
+
+        let shallow = ReadOptions { max_depth: 0, ..Default::default() };
+        let err = read_bin_with_options(&data, &shallow).unwrap_err();
+        assert!(matches!(err.root(), BinError::TooDeep(0)));
+        assert_eq!(err.path(), Some("entries[0x0000000a].0x00000001"));
+        // An entry table claiming 2^31 entries is refused before anything is reserved
+        let table = data.len() - 4 - 4 - entry.length as usize - 4;
+        let mut huge = data.clone();
+        huge[table..table + 4].copy_from_slice(&(1u32 << 31).to_le_bytes());
+        assert!(matches!(read_bin(&huge).unwrap_err().root(), BinError::LimitExceeded { value, .. } if *value == 1 << 31));
+        let unlimited = ReadOptions { max_items: u32::MAX, ..Default::default() };
+        assert!(matches!(read_bin_with_options(&huge, &unlimited).unwrap_err().root(), BinError::Io(_)));
+        let short = ReadOptions { max_string_len: 3, ..Default::default() };
+        let mut typed = bin.clone();
+        typed.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String("DATA/Shared.bin".to_string())],
+        });
+        let err = read_bin_with_options(&write_bin(&typed).unwrap(), &short).unwrap_err();
+        assert!(matches!(err.root(), BinError::LimitExceeded { what: "string length", .. }));
+    }
+
+    #[test]
+    fn test_zero_size_items_are_limited() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 2, name_str: None, items: vec![
+                Field { key: 3, key_str: None, value: BinValue::List { value_type: BinType::None, items: vec![BinValue::None; 3] } },
+            ] })],
+        });
+        let data = write_bin(&bin).unwrap();
+        assert_eq!(read_bin_untrusted(&data).unwrap().sections["entries"], bin.sections["entries"]);
+
+        // Element type, size and count of the list
+        let at = data.windows(9).position(|w| w == [0, 4, 0, 0, 0, 3, 0, 0, 0]).unwrap() + 5;
+        let mut huge = data.clone();
+        huge[at..at + 4].copy_from_slice(&ReadOptions::untrusted().max_items.to_le_bytes());
+        for result in [read_bin_untrusted(&huge), read_bin(&huge), borrowed::read_bin_ref(&huge).map(|b| b.to_bin())] {
+            assert!(matches!(result.unwrap_err().root(), BinError::LimitExceeded { value: 0x100000, .. }));
+        }
+    }
+
+    #[test]
+    fn test_read_untrusted_zero_size_counts() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 2, name_str: None, items: (0..40).map(|key| Field {
+                key,
+                key_str: None,
+                value: BinValue::List { value_type: BinType::None, items: vec![BinValue::None; 3] },
+            }).collect() })],
+        });
+        let data = write_bin(&bin).unwrap();
+        let counts: Vec<usize> = data.windows(9)
+            .enumerate()
+            .filter(|(_, w)| *w == [0, 4, 0, 0, 0, 3, 0, 0, 0])
+            .map(|(i, _)| i + 5)
+            .collect();
+        assert_eq!(counts.len(), 40);
+        let with_counts = |count: u32| {
+            let mut data = data.clone();
+            for &at in &counts {
+                data[at..at + 4].copy_from_slice(&count.to_le_bytes());
+            }
+            data
+        };
+
+        // The largest count a file can declare is over every limit
+        assert!(matches!(read_bin_untrusted(&with_counts(u32::MAX)).unwrap_err().root(), BinError::LimitExceeded { what: "item count", .. }));
+        // Each list fits the input, but all of them together would hold 10 values per input byte
+        let crafted = with_counts(data.len() as u32 / 4);
+        for result in [read_bin_untrusted(&crafted), read_bin(&crafted)] {
+            assert!(matches!(
+                result.unwrap_err().root(),
+                BinError::LimitExceeded { what: "count of items of no size", limit, .. } if *limit < data.len() as u64 / 4
+            ));
+        }
+        assert!(matches!(
+            events::BinEventReader::new(&crafted).find_map(Result::err).unwrap().root(),
+            BinError::LimitExceeded { what: "count of items of no size", .. }
+        ));
+        // Up to one per input byte is fine
+        let fits = with_counts(data.len() as u32 / 40);
+        let read = read_bin_untrusted(&fits).unwrap();
+        let BinValue::Map { items, .. } = &read.sections["entries"] else { panic!() };
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!() };
+        assert!(fields.iter().all(|f| matches!(&f.value, BinValue::List { items, .. } if items.len() == data.len() / 40)));
+    }
+
+    #[test]
+    fn test_read_untrusted_never_panics() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+        bin.sections.insert("ptch_header".to_string(), BinValue::U64(1));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String("DATA/Shared.bin".to_string())],
+        });
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![
+                Field { key: 1, key_str: None, value: BinValue::List2 { value_type: BinType::Vec3, items: vec![BinValue::Vec3([1.0; 3])] } },
+                Field { key: 2, key_str: None, value: BinValue::Map {
+                    key_type: BinType::String,
+                    value_type: BinType::Pointer,
+                    items: vec![(BinValue::String("a".to_string()), BinValue::Pointer {
+                        name: 5,
+                        name_str: None,
+                        items: vec![Field { key: 3, key_str: None, value: BinValue::Option {
+                            value_type: BinType::Link,
+                            item: Some(Box::new(BinValue::Link { value: 4, name: None })),
+                        }}],
+                    })],
+                }},
+            ] })],
+        });
+        bin.sections.insert("patches".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 6, name: None }, BinValue::Embed { name: 0, name_str: None, items: vec![
+                Field { key: crate::hash::Fnv1a::new("path").0, key_str: None, value: BinValue::String("a.b".to_string()) },
+                Field { key: crate::hash::Fnv1a::new("value").0, key_str: None, value: BinValue::U16(7) },
+            ] })],
+        });
+        let data = write_bin(&bin).unwrap();
+        assert_eq!(write_bin(&read_bin_untrusted(&data).unwrap()).unwrap(), data);
+
+        for len in 0..data.len() {
+            assert!(read_bin_untrusted(&data[..len]).is_err());
+        }
+        // Corrupt a few bytes at a time, favouring values that make counts and sizes huge
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let mut damaged = data.clone();
+            for _ in 0..1 + next() % 4 {
+                let at = (next() % data.len() as u64) as usize;
+                damaged[at] = [0x00, 0x7f, 0x80, 0xff, next() as u8][(next() % 5) as usize];
+            }
+            let _ = read_bin_untrusted(&damaged);
+        }
+
+        let mut long = bin.clone();
+        let Some(BinValue::Map { items, .. }) = long.sections.get_mut("entries") else { panic!() };
+        let BinValue::Embed { items: fields, .. } = &mut items[0].1 else { panic!() };
+        fields.push(Field { key: 9, key_str: None, value: BinValue::List { value_type: BinType::U8, items: vec![BinValue::U8(0); 10_000] } });
+        let opts = ReadOptions { time_limit: Some(Duration::ZERO), ..ReadOptions::untrusted() };
+        let err = read_bin_with_options(&write_bin(&long).unwrap(), &opts).unwrap_err();
+        assert!(matches!(err.root(), BinError::TimedOut(_)));
+    }
+
+    #[test]
+    fn test_duplicate_map_keys() {
+        let map = |items: &[(u32, u32)]| BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::U32,
+            items: items.iter().map(|&(k, v)| (BinValue::Hash { value: k, name: None }, BinValue::U32(v))).collect(),
+        };
+        let with_map = |value: BinValue| {
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+            bin.sections.insert("version".to_string(), BinValue::U32(3));
+            bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+            bin.sections.insert("entries".to_string(), BinValue::Map {
+                key_type: BinType::Hash,
+                value_type: BinType::Embed,
+                items: vec![(
+                    BinValue::Hash { value: 10, name: None },
+                    BinValue::Embed { name: 20, name_str: None, items: vec![Field { key: 1, key_str: None, value }] },
+                )],
+            });
+            bin
+        };
+        let bin = with_map(map(&[(1, 1), (2, 2), (1, 3)]));
+        let data = write_bin(&bin).unwrap();
+        let read = |policy| read_bin_with_duplicates(&data, &ReadOptions { duplicate_keys: policy, ..Default::default() });
+
+        let (kept, duplicates) = read(DuplicateKeys::KeepAll).unwrap();
+        assert_eq!(kept, bin);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].key, "0x00000001");
+        assert_eq!(events::path_at(&data, duplicates[0].offset).as_deref(), Some("entries[0x0000000a].0x00000001[#2]"));
+        assert_eq!(read(DuplicateKeys::KeepFirst).unwrap().0, with_map(map(&[(1, 1), (2, 2)])));
+        assert_eq!(read(DuplicateKeys::KeepLast).unwrap().0, with_map(map(&[(1, 3), (2, 2)])));
+        let err = read(DuplicateKeys::Error).unwrap_err();
+        assert!(matches!(err.root(), BinError::DuplicateKey(key) if key == "0x00000001"));
+        assert_eq!(err.path(), Some("entries[0x0000000a].0x00000001[0x00000001]"));
+    }
+
+    #[test]
+    fn test_recover_skips_damaged_entry() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: (1..=3).map(|h| (
+                BinValue::Hash { value: h, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![
+                    Field { key: 1, key_str: None, value: BinValue::U32(h) },
+                ] },
+            )).collect(),
+        });
+        let mut data = write_bin(&bin).unwrap();
+        let middle = LazyBin::parse(&data).unwrap().entries()[1];
+        // Length, key hash, field count and field key come before the first type byte
+        data[middle.offset as usize + 14] = 0x7f;
+
+        let err = read_bin(&data).unwrap_err();
+        assert!(matches!(err.root(), BinError::UnknownType(0x7f)));
+        assert_eq!(err.path(), Some("entries[0x00000002].0x00000001"));
+        assert_eq!(err.offset(), Some(middle.offset + 10));
+        let recovered = read_bin_recover(&data, &ReadOptions::default()).unwrap();
+        assert_eq!(recovered.skipped.len(), 1);
+        assert_eq!((recovered.skipped[0].hash, recovered.skipped[0].offset), (2, middle.offset));
+        let Some(BinValue::Map { items, .. }) = recovered.bin.sections.get("entries") else { panic!() };
+        let keys: Vec<_> = items.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!(keys, vec![BinValue::Hash { value: 1, name: None }, BinValue::Hash { value: 3, name: None }]);
+    }
+
+    #[test]
+    fn test_checked_read_reports_truncation() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: (1..=3).map(|h| (
+                BinValue::Hash { value: h, name: None },
+                BinValue::Embed { name: 20, name_str: None, items: vec![
+                    Field { key: 7, key_str: None, value: BinValue::Mtx44([h as f32; 16]) },
+                ] },
+            )).collect(),
+        });
+        let data = write_bin(&bin).unwrap();
+        let read = read_bin_checked(&data, &ReadOptions::default()).unwrap();
+        assert_eq!(read.sections.get("entries"), bin.sections.get("entries"));
+
+        let third = LazyBin::parse(&data).unwrap().entries()[2];
+        // Length, key hash, field count, field key and type byte come before the matrix
+        let matrix = third.offset + 15;
+        let err = read_bin_checked(&data[..matrix as usize + 20], &ReadOptions::default()).unwrap_err();
+        let BinError::UnexpectedEof { at_offset, while_reading, entries_recovered } = err else { panic!("{:?}", err) };
+        assert_eq!(at_offset, matrix);
+        assert_eq!(while_reading, "Mtx44 in entries[0x00000003].0x00000007");
+        assert_eq!(entries_recovered, 2);
+
+        let err = read_bin_checked(&data[..third.offset as usize + 2], &ReadOptions::default()).unwrap_err();
+        assert!(matches!(err, BinError::UnexpectedEof { at_offset, ref while_reading, entries_recovered: 2 }
+            if at_offset == third.offset && while_reading == "entry header"));
+        let err = read_bin_checked(&data[..10], &ReadOptions::default()).unwrap_err();
+        assert!(matches!(err, BinError::UnexpectedEof { at_offset: 0, ref while_reading, entries_recovered: 0 }
+            if while_reading == "header"));
+        assert!(matches!(read_bin(&data[..matrix as usize + 20]).unwrap_err().root(), BinError::Io(_)));
+    }
+
+    #[test]
+    fn test_ptch_header_round_trip() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        let data = write_bin(&bin).unwrap();
+        assert_eq!(&data[..16], b"PTCH\x01\0\0\0\0\0\0\0PROP");
+        assert_eq!(write_bin_checked(&bin).unwrap(), data);
+
+        let mut data = data;
+        data[4..12].copy_from_slice(&0x0002_0000_0001u64.to_le_bytes());
+        let read = read_bin(&data).unwrap();
+        assert_eq!(read.sections["ptch_header"], BinValue::U64(0x0002_0000_0001));
+        assert_eq!(write_bin(&read).unwrap(), data);
+        let text = crate::text::read_text(&crate::text::write_text(&read).unwrap()).unwrap();
+        assert_eq!(write_bin(&text).unwrap(), data);
+    }
+
+    #[test]
+    fn test_preserve_unknown_types() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![
+                Field { key: 1, key_str: None, value: BinValue::U32(7) },
+                Field { key: 2, key_str: None, value: BinValue::List { value_type: BinType::U32, items: vec![BinValue::U32(1)] } },
+                Field { key: 3, key_str: None, value: BinValue::U32(9) },
+            ] })],
+        });
+        let mut data = write_bin(&bin).unwrap();
+        let entry = LazyBin::parse(&data).unwrap().entries()[0].offset as usize;
+        let opts = ReadOptions { preserve_unknown_types: true, ..Default::default() };
+        let fields = |bin: &Bin| match bin.sections.get("entries") {
+            Some(BinValue::Map { items, .. }) => match &items[0].1 {
+                BinValue::Embed { items, .. } => items.clone(),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        };
+
+        // Unknown list element type: only the list is kept raw
+        data[entry + 24] = 0x7e;
+        assert!(read_bin(&data).is_err());
+        let read = read_bin_with_options(&data, &opts).unwrap();
+        let items = fields(&read);
+        assert_eq!(items[0].value, BinValue::U32(7));
+        assert!(matches!(items[1].value, BinValue::Raw { type_byte: 0x80, absorbed_fields: 0, .. }));
+        assert_eq!(items[2].value, BinValue::U32(9));
+        assert_eq!(write_bin(&read).unwrap(), data);
+
+        // Unknown field type: the rest of the entry is kept raw
+        data[entry + 14] = 0x7f;
+        let read = read_bin_with_options(&data, &opts).unwrap();
+        let items = fields(&read);
+        assert_eq!(items.len(), 1);
+        assert!(matches!(&items[0].value, BinValue::Raw { type_byte: 0x7f, absorbed_fields: 2, bytes } if bytes.len() == 4 + 18 + 9));
+        assert_eq!(write_bin(&read).unwrap(), data);
+        let text = crate::text::read_text(&crate::text::write_text(&read).unwrap()).unwrap();
+        assert_eq!(write_bin(&text).unwrap(), data);
+        let json = crate::json::read_json(&crate::json::write_json(&read).unwrap()).unwrap();
+        assert_eq!(write_bin(&json).unwrap(), data);
+    }
+
+    #[test]
+    fn test_read_warnings() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![BinValue::String("a~b".to_string())] });
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![] })],
+        });
+        let data = write_bin(&bin).unwrap();
+        let (read, warnings) = read_bin_with_warnings(&data, &ReadOptions::default()).unwrap();
+        assert_eq!(read.sections["entries"], bin.sections["entries"]);
+        assert!(warnings.is_empty());
+
+        // Make the linked string invalid UTF-8 and pad the entry by a byte
+        let mut damaged = data.clone();
+        let at = damaged.windows(3).position(|w| w == b"a~b").unwrap();
+        damaged[at + 1] = 0xff;
+        let entry = LazyBin::parse(&damaged).unwrap().entries()[0];
+        let offset = entry.offset as usize;
+        damaged[offset..offset + 4].copy_from_slice(&(entry.length + 1).to_le_bytes());
+        damaged.insert(offset + 4 + entry.length as usize, 0);
+
+        let (read, warnings) = read_bin_with_warnings(&damaged, &ReadOptions::default()).unwrap();
+        assert_eq!(read_bin(&damaged).unwrap(), read);
+        assert_eq!(warnings, [
+            ReadWarning::LossyString { offset: at as u64 - 2 },
+            ReadWarning::SizeMismatch { offset: offset as u64 + 4, declared: entry.length + 1, read: entry.length as u64 },
+        ]);
+        let strict = ReadOptions { strict_sizes: true, ..Default::default() };
+        assert!(read_bin_with_warnings(&damaged, &strict).is_err());
+    }
 }
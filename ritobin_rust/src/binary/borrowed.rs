@@ -0,0 +1,437 @@
+//! A borrowed model of bin files for read-only scanning.
+//!
+//! [`read_bin_ref`] decodes a bin like [`read_bin`](super::read_bin), but into
+//! [`BinValueRef`]s whose strings point into the input instead of being
+//! copied into new `String`s, and without the name slots of the owned model.
+//! Scanning a corpus for hashes or strings this way allocates only the lists
+//! holding values.
+//!
+//! Strings that are not valid UTF-8 are the one exception: like the owned
+//! reader, they are decoded lossily into an owned copy.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::binary::borrowed::{read_bin_ref, BinValueRef};
+//!
+//! let data = std::fs::read("champion.bin")?;
+//! let bin = read_bin_ref(&data)?;
+//! for entry in &bin.entries {
+//!     for field in &entry.fields {
+//!         if let BinValueRef::String(s) = &field.value {
+//!             println!("{:08x}.{:08x} = {}", entry.hash, field.key, s);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::borrow::Cow;
+
+use super::{BinError, BinaryReader, ReadOptions};
+use crate::model::{Bin, BinType, BinValue, Field};
+
+/// A value borrowing its strings from the data it was read from.
+///
+/// Mirrors [`BinValue`]; hashes, links and classes carry no names.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinValueRef<'a> {
+    None,
+    Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mtx44([f32; 16]),
+    Rgba([u8; 4]),
+    String(Cow<'a, str>),
+    Hash(u32),
+    File(u64),
+    List { value_type: BinType, items: Vec<BinValueRef<'a>> },
+    List2 { value_type: BinType, items: Vec<BinValueRef<'a>> },
+    /// A null pointer has class 0 and no fields
+    Pointer { name: u32, items: Vec<FieldRef<'a>> },
+    Embed { name: u32, items: Vec<FieldRef<'a>> },
+    Link(u32),
+    Option { value_type: BinType, item: Option<Box<BinValueRef<'a>>> },
+    Map { key_type: BinType, value_type: BinType, items: Vec<(BinValueRef<'a>, BinValueRef<'a>)> },
+    Flag(bool),
+}
+
+/// A field of an entry, pointer or embed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRef<'a> {
+    pub key: u32,
+    pub value: BinValueRef<'a>,
+}
+
+/// An entry of the `entries` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryRef<'a> {
+    pub hash: u32,
+    pub class: u32,
+    pub fields: Vec<FieldRef<'a>>,
+}
+
+/// A patch of a PTCH file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchRef<'a> {
+    pub hash: u32,
+    pub path: Cow<'a, str>,
+    pub value: BinValueRef<'a>,
+}
+
+/// A whole bin file, borrowing from its data; see [`read_bin_ref`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinRef<'a> {
+    /// The word after the `PTCH` magic, for patch files
+    pub ptch_header: Option<u64>,
+    pub version: u32,
+    /// Linked files; always empty before version 2
+    pub linked: Vec<Cow<'a, str>>,
+    pub entries: Vec<EntryRef<'a>>,
+    /// Always empty for PROP files
+    pub patches: Vec<PatchRef<'a>>,
+}
+
+impl BinValueRef<'_> {
+    /// An owned copy, as [`read_bin`](super::read_bin) would have produced it
+    pub fn to_value(&self) -> BinValue {
+        let fields = |items: &[FieldRef]| items.iter().map(FieldRef::to_field).collect();
+        match self {
+            BinValueRef::None => BinValue::None,
+            BinValueRef::Bool(v) => BinValue::Bool(*v),
+            BinValueRef::I8(v) => BinValue::I8(*v),
+            BinValueRef::U8(v) => BinValue::U8(*v),
+            BinValueRef::I16(v) => BinValue::I16(*v),
+            BinValueRef::U16(v) => BinValue::U16(*v),
+            BinValueRef::I32(v) => BinValue::I32(*v),
+            BinValueRef::U32(v) => BinValue::U32(*v),
+            BinValueRef::I64(v) => BinValue::I64(*v),
+            BinValueRef::U64(v) => BinValue::U64(*v),
+            BinValueRef::F32(v) => BinValue::F32(*v),
+            BinValueRef::Vec2(v) => BinValue::Vec2(*v),
+            BinValueRef::Vec3(v) => BinValue::Vec3(*v),
+            BinValueRef::Vec4(v) => BinValue::Vec4(*v),
+            BinValueRef::Mtx44(v) => BinValue::Mtx44(*v),
+            BinValueRef::Rgba(v) => BinValue::Rgba(*v),
+            BinValueRef::String(s) => BinValue::String(s.to_string()),
+            BinValueRef::Hash(v) => BinValue::Hash { value: *v, name: None },
+            BinValueRef::File(v) => BinValue::File { value: *v, name: None },
+            BinValueRef::List { value_type, items } => BinValue::List {
+                value_type: *value_type,
+                items: items.iter().map(BinValueRef::to_value).collect(),
+            },
+            BinValueRef::List2 { value_type, items } => BinValue::List2 {
+                value_type: *value_type,
+                items: items.iter().map(BinValueRef::to_value).collect(),
+            },
+            BinValueRef::Pointer { name, items } => BinValue::Pointer { name: *name, name_str: None, items: fields(items) },
+            BinValueRef::Embed { name, items } => BinValue::Embed { name: *name, name_str: None, items: fields(items) },
+            BinValueRef::Link(v) => BinValue::Link { value: *v, name: None },
+            BinValueRef::Option { value_type, item } => BinValue::Option {
+                value_type: *value_type,
+                item: item.as_ref().map(|v| Box::new(v.to_value())),
+            },
+            BinValueRef::Map { key_type, value_type, items } => BinValue::Map {
+                key_type: *key_type,
+                value_type: *value_type,
+                items: items.iter().map(|(k, v)| (k.to_value(), v.to_value())).collect(),
+            },
+            BinValueRef::Flag(v) => BinValue::Flag(*v),
+        }
+    }
+}
+
+impl FieldRef<'_> {
+    pub fn to_field(&self) -> Field {
+        Field { key: self.key, key_str: None, value: self.value.to_value() }
+    }
+}
+
+impl BinRef<'_> {
+    /// An owned [`Bin`], as [`read_bin`](super::read_bin) would have produced it
+    pub fn to_bin(&self) -> Bin {
+        let mut bin = Bin::new();
+        let type_ = if self.ptch_header.is_some() { "PTCH" } else { "PROP" };
+        bin.sections.insert("type".to_string(), BinValue::String(type_.to_string()));
+        if let Some(header) = self.ptch_header {
+            bin.sections.insert("ptch_header".to_string(), BinValue::U64(header));
+        }
+        bin.sections.insert("version".to_string(), BinValue::U32(self.version));
+        if self.version >= 2 {
+            bin.sections.insert("linked".to_string(), BinValue::List {
+                value_type: BinType::String,
+                items: self.linked.iter().map(|s| BinValue::String(s.to_string())).collect(),
+            });
+        }
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: self.entries.iter().map(|e| (
+                BinValue::Hash { value: e.hash, name: None },
+                BinValue::Embed { name: e.class, name_str: None, items: e.fields.iter().map(FieldRef::to_field).collect() },
+            )).collect(),
+        });
+        if self.ptch_header.is_some() {
+            let path_key = crate::hash::Fnv1a::new("path").0;
+            let value_key = crate::hash::Fnv1a::new("value").0;
+            bin.sections.insert("patches".to_string(), BinValue::Map {
+                key_type: BinType::Hash,
+                value_type: BinType::Embed,
+                items: self.patches.iter().map(|p| (
+                    BinValue::Hash { value: p.hash, name: None },
+                    BinValue::Embed { name: crate::hash::Fnv1a::new("patch").0, name_str: None, items: vec![
+                        Field { key: path_key, key_str: Some("path".to_string()), value: BinValue::String(p.path.to_string()) },
+                        Field { key: value_key, key_str: Some("value".to_string()), value: p.value.to_value() },
+                    ] },
+                )).collect(),
+            });
+        }
+        bin
+    }
+}
+
+/// Read a bin, borrowing its strings from `data`.
+pub fn read_bin_ref(data: &[u8]) -> Result<BinRef<'_>, BinError> {
+    read_bin_ref_with_options(data, &ReadOptions::default())
+}
+
+/// [`read_bin_ref`] with control over how malformed input is handled.
+///
+/// Fields with unknown type bytes are always errors, whatever
+/// [`ReadOptions::preserve_unknown_types`] says, and repeated map keys are
+/// kept as stored.
+pub fn read_bin_ref_with_options<'a>(data: &'a [u8], opts: &ReadOptions) -> Result<BinRef<'a>, BinError> {
+    let mut r = BinaryReader::with_options(data, *opts);
+    let mut magic = r.read_bytes(4)?;
+    let ptch_header = if magic == b"PTCH" {
+        let header = r.read_u64()?;
+        magic = r.read_bytes(4)?;
+        Some(header)
+    } else {
+        None
+    };
+    if magic != b"PROP" {
+        return Err(BinError::InvalidMagic);
+    }
+    let version = r.read_u32()?;
+    let mut linked = Vec::new();
+    if version >= 2 {
+        let count = r.read_u32()?;
+        linked.reserve(r.item_count(count, 2)?);
+        for _ in 0..count {
+            linked.push(read_str(&mut r)?);
+        }
+    }
+
+    let count = r.read_u32()?;
+    let mut classes = Vec::with_capacity(r.item_count(count, 4)?);
+    for _ in 0..count {
+        classes.push(r.read_u32()?);
+    }
+    let mut entries = Vec::with_capacity(classes.len());
+    for class in classes {
+        let length = r.read_u32()?;
+        let start = r.position();
+        let hash = r.read_u32()?;
+        let fields = r.read_u16()
+            .and_then(|count| read_fields(&mut r, count))
+            .and_then(|fields| r.end_block(start, length).map(|()| fields))
+            .map_err(|e| e.context(start - 4, format_args!("entries[{:#010x}]", hash)))?;
+        entries.push(EntryRef { hash, class, fields });
+    }
+
+    let mut patches = Vec::new();
+    if ptch_header.is_some() {
+        let count = r.read_u32()?;
+        patches.reserve(r.item_count(count, 4)?);
+        for _ in 0..count {
+            let hash = r.read_u32()?;
+            let start = r.position();
+            let patch = (|| {
+                let length = r.read_u32()?;
+                let block = r.position();
+                let type_ = r.read_type()?;
+                let path = read_str(&mut r)?;
+                let value = read_value(&mut r, type_)?;
+                r.end_block(block, length)?;
+                Ok(PatchRef { hash, path, value })
+            })();
+            patches.push(patch.map_err(|e: BinError| e.context(start, format_args!("patches[{:#010x}]", hash)))?);
+        }
+    }
+    r.check_trailing()?;
+    Ok(BinRef { ptch_header, version, linked, entries, patches })
+}
+
+fn read_str<'a>(r: &mut BinaryReader<'a>) -> Result<Cow<'a, str>, BinError> {
+    let len = r.read_u16()? as usize;
+    if len > r.opts.max_string_len {
+        return Err(BinError::LimitExceeded { what: "string length", value: len as u64, limit: r.opts.max_string_len as u64 });
+    }
+    Ok(String::from_utf8_lossy(r.read_bytes(len)?))
+}
+
+fn read_fields<'a>(r: &mut BinaryReader<'a>, count: u16) -> Result<Vec<FieldRef<'a>>, BinError> {
+    let mut fields = Vec::with_capacity(r.item_count(count as u32, 5)?);
+    for _ in 0..count {
+        let start = r.position();
+        let key = r.read_u32().map_err(|e| e.context(start, ""))?;
+        let value = r.read_type()
+            .and_then(|type_| read_value(r, type_))
+            .map_err(|e| e.context(start, format_args!(".{:#010x}", key)))?;
+        fields.push(FieldRef { key, value });
+    }
+    Ok(fields)
+}
+
+/// A value of `type_`, checking nesting against `max_depth`
+fn read_value<'a>(r: &mut BinaryReader<'a>, type_: BinType) -> Result<BinValueRef<'a>, BinError> {
+    let nested = type_.is_container() || matches!(type_, BinType::Pointer | BinType::Embed);
+    if nested {
+        if r.depth >= r.opts.max_depth {
+            return Err(BinError::TooDeep(r.opts.max_depth));
+        }
+        r.depth += 1;
+    }
+    let value = read_value_at(r, type_);
+    if nested {
+        r.depth -= 1;
+    }
+    value
+}
+
+fn read_value_at<'a>(r: &mut BinaryReader<'a>, type_: BinType) -> Result<BinValueRef<'a>, BinError> {
+    Ok(match type_ {
+        BinType::None => BinValueRef::None,
+        BinType::Bool => BinValueRef::Bool(r.read_bool()?),
+        BinType::I8 => BinValueRef::I8(r.read_i8()?),
+        BinType::U8 => BinValueRef::U8(r.read_u8()?),
+        BinType::I16 => BinValueRef::I16(r.read_i16()?),
+        BinType::U16 => BinValueRef::U16(r.read_u16()?),
+        BinType::I32 => BinValueRef::I32(r.read_i32()?),
+        BinType::U32 => BinValueRef::U32(r.read_u32()?),
+        BinType::I64 => BinValueRef::I64(r.read_i64()?),
+        BinType::U64 => BinValueRef::U64(r.read_u64()?),
+        BinType::F32 => BinValueRef::F32(r.read_f32()?),
+        BinType::Vec2 => BinValueRef::Vec2(r.read_vec2()?),
+        BinType::Vec3 => BinValueRef::Vec3(r.read_vec3()?),
+        BinType::Vec4 => BinValueRef::Vec4(r.read_vec4()?),
+        BinType::Mtx44 => BinValueRef::Mtx44(r.read_mtx44()?),
+        BinType::Rgba => BinValueRef::Rgba(r.read_rgba()?),
+        BinType::String => BinValueRef::String(read_str(r)?),
+        BinType::Hash => BinValueRef::Hash(r.read_u32()?),
+        BinType::File => BinValueRef::File(r.read_u64()?),
+        BinType::Link => BinValueRef::Link(r.read_u32()?),
+        BinType::Flag => BinValueRef::Flag(r.read_bool()?),
+        BinType::List | BinType::List2 => {
+            let value_type = r.read_type()?;
+            if value_type.is_container() {
+                return Err(BinError::InvalidValue(value_type));
+            }
+            let size = r.read_u32()?;
+            let start = r.position();
+            let count = r.read_u32()?;
+            let mut items = Vec::with_capacity(r.item_count(count, value_type.min_size())?);
+            for i in 0..count {
+                let at = r.position();
+                items.push(read_value(r, value_type).map_err(|e| e.context(at, format_args!("[{}]", i)))?);
+            }
+            r.end_block(start, size)?;
+            match type_ {
+                BinType::List => BinValueRef::List { value_type, items },
+                _ => BinValueRef::List2 { value_type, items },
+            }
+        }
+        BinType::Pointer | BinType::Embed => {
+            let name = r.read_u32()?;
+            if name == 0 && type_ == BinType::Pointer {
+                return Ok(BinValueRef::Pointer { name, items: Vec::new() });
+            }
+            let size = r.read_u32()?;
+            let start = r.position();
+            let count = r.read_u16()?;
+            let items = read_fields(r, count)?;
+            r.end_block(start, size)?;
+            match type_ {
+                BinType::Pointer => BinValueRef::Pointer { name, items },
+                _ => BinValueRef::Embed { name, items },
+            }
+        }
+        BinType::Option => {
+            let value_type = r.read_type()?;
+            if value_type.is_container() {
+                return Err(BinError::InvalidValue(value_type));
+            }
+            let item = match r.read_u8()? {
+                0 => None,
+                _ => Some(Box::new(read_value(r, value_type)?)),
+            };
+            BinValueRef::Option { value_type, item }
+        }
+        BinType::Map => {
+            let key_type = r.read_type()?;
+            if !key_type.is_primitive() {
+                return Err(BinError::InvalidValue(key_type));
+            }
+            let value_type = r.read_type()?;
+            if value_type.is_container() {
+                return Err(BinError::InvalidValue(value_type));
+            }
+            let size = r.read_u32()?;
+            let start = r.position();
+            let count = r.read_u32()?;
+            let mut items = Vec::with_capacity(r.item_count(count, key_type.min_size() + value_type.min_size())?);
+            for i in 0..count {
+                let at = r.position();
+                let key = read_value(r, key_type).map_err(|e| e.context(at, format_args!("[#{}]", i)))?;
+                let value = read_value(r, value_type)
+                    .map_err(|e| e.context(at, format_args!("[{}]", crate::compare::key_label(&key.to_value()))))?;
+                items.push((key, value));
+            }
+            r.end_block(start, size)?;
+            BinValueRef::Map { key_type, value_type, items }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{read_bin, write_bin};
+
+    #[test]
+    fn test_read_bin_ref_matches_read_bin() {
+        // Compared by encoding, as the corpus holds NaNs
+        for case in crate::corpus::cases() {
+            let data = write_bin(&case.bin).unwrap();
+            let borrowed = read_bin_ref(&data).unwrap();
+            assert_eq!(write_bin(&borrowed.to_bin()).unwrap(), data, "{}", case.name);
+            assert_eq!(write_bin(&borrowed.to_bin()).unwrap(), write_bin(&read_bin(&data).unwrap()).unwrap(), "{}", case.name);
+        }
+
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 20, name_str: None, items: vec![
+                Field { key: 1, key_str: None, value: BinValue::String("Ahri".to_string()) },
+            ] })],
+        });
+        let data = write_bin(&bin).unwrap();
+        let borrowed = read_bin_ref(&data).unwrap();
+        let BinValueRef::String(Cow::Borrowed(s)) = &borrowed.entries[0].fields[0].value else { panic!() };
+        assert_eq!(s.as_ptr(), data[data.len() - 4..].as_ptr());
+    }
+}
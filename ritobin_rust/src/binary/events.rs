@@ -0,0 +1,1037 @@
+//! Reading bin files as a stream of events.
+//!
+//! [`BinEventReader`] walks a bin the way a SAX parser walks XML: instead of
+//! building a [`Bin`](crate::model::Bin), it yields one [`BinEvent`] per
+//! entry, field, container boundary and plain value, decoding only as far as
+//! the consumer pulls. Counting hashes or collecting strings this way never
+//! holds more than one value in memory.
+//!
+//! Every value is either a single [`BinEvent::Value`] or a start event, the
+//! events of its contents and the matching end event. A field is a
+//! [`BinEvent::Field`] followed by its value; a map item is its key value
+//! followed by its value.
+//!
+//! [`BinEventWriter`] takes the same events and writes binary, so a file can
+//! be transformed (read events, filter or rewrite them, write them) without
+//! ever being held in memory.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{WriteBytesExt, LE};
+
+use super::{checked_len, get_value_type, BinError, BinaryReader, BinaryWriter, ReadOptions};
+use crate::model::{BinType, BinValue};
+
+/// One step of a bin file, as produced by [`BinEventReader`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinEvent {
+    /// Always the first event
+    Header {
+        /// The word after the `PTCH` magic, for patch files
+        ptch_header: Option<u64>,
+        version: u32,
+        /// Linked files; always empty before version 2
+        linked: Vec<String>,
+    },
+    /// Start of an entry; its fields follow, then [`BinEvent::EntryEnd`]
+    EntryStart { hash: u32, class: u32 },
+    EntryEnd,
+    /// Start of a patch of a PTCH file; its value follows, then [`BinEvent::PatchEnd`]
+    PatchStart { hash: u32, path: String },
+    PatchEnd,
+    /// A field key; the field's value follows
+    Field { key: u32 },
+    /// A value without children
+    Value(BinValue),
+    /// Start of a `list` or `list2` of `len` items
+    ListStart { list2: bool, value_type: BinType, len: u32 },
+    ListEnd,
+    /// Start of a map of `len` key/value pairs
+    MapStart { key_type: BinType, value_type: BinType, len: u32 },
+    MapEnd,
+    /// Start of an option, holding one value if `is_some`
+    OptionStart { value_type: BinType, is_some: bool },
+    OptionEnd,
+    /// Start of a pointer or embed of class `class`; its fields follow.
+    /// A null pointer has class 0 and no fields.
+    StructStart { pointer: bool, class: u32 },
+    StructEnd,
+}
+
+/// What is being read at one level of nesting
+#[derive(Debug)]
+enum Frame {
+    Entries { classes: std::vec::IntoIter<u32>, is_patch: bool },
+    Patches { remaining: u32 },
+    /// The value of the current patch, then its end
+    Patch { start: u64, size: u32 },
+    Entry { remaining: u16, start: u64, size: u32 },
+    Struct { remaining: u16, block: Option<(u64, u32)> },
+    List { value_type: BinType, remaining: u32, start: u64, size: u32 },
+    Map { key_type: BinType, value_type: BinType, remaining: u32, key_next: bool, start: u64, size: u32 },
+    Option { value_type: BinType, pending: bool },
+}
+
+/// Pull parser over the bytes of a bin file.
+///
+/// Implements [`Iterator`] over `Result<BinEvent, BinError>`; iteration stops
+/// after the first error. Fields with unknown type bytes are always errors,
+/// whatever [`ReadOptions::preserve_unknown_types`] says.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::binary::events::{BinEvent, BinEventReader};
+/// use ritobin_rust::model::BinValue;
+///
+/// let data = std::fs::read("champion.bin")?;
+/// let mut strings = 0;
+/// for event in BinEventReader::new(&data) {
+///     if let BinEvent::Value(BinValue::String(_)) = event? {
+///         strings += 1;
+///     }
+/// }
+/// println!("{} strings", strings);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct BinEventReader<'a> {
+    reader: BinaryReader<'a>,
+    stack: Vec<Frame>,
+    /// Type of a field or patch value announced but not yet read
+    pending: Option<BinType>,
+    started: bool,
+    finished: bool,
+}
+
+impl<'a> BinEventReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_options(data, &ReadOptions::default())
+    }
+
+    /// Read with the size, trailing data and depth checks of `opts`
+    pub fn with_options(data: &'a [u8], opts: &ReadOptions) -> Self {
+        Self {
+            reader: BinaryReader::with_options(data, *opts),
+            stack: Vec::new(),
+            pending: None,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Current offset into the data
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+
+    /// The next event, or `None` after the last one
+    pub fn next_event(&mut self) -> Result<Option<BinEvent>, BinError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let start = self.reader.position();
+        let event = self.step().map_err(|e| e.context(start, ""));
+        if !matches!(event, Ok(Some(_))) {
+            self.finished = true;
+        }
+        event
+    }
+
+    fn step(&mut self) -> Result<Option<BinEvent>, BinError> {
+        if !self.started {
+            self.started = true;
+            return self.read_header().map(Some);
+        }
+        if let Some(type_) = self.pending.take() {
+            return self.begin_value(type_).map(Some);
+        }
+        let r = &mut self.reader;
+        let Some(frame) = self.stack.last_mut() else {
+            r.check_trailing()?;
+            return Ok(None);
+        };
+        let event = match frame {
+            Frame::Entries { classes, is_patch } => match classes.next() {
+                Some(class) => {
+                    let size = r.read_u32()?;
+                    let start = r.position();
+                    let hash = r.read_u32()?;
+                    let remaining = r.read_u16()?;
+                    self.stack.push(Frame::Entry { remaining, start, size });
+                    BinEvent::EntryStart { hash, class }
+                }
+                None => {
+                    let is_patch = *is_patch;
+                    self.stack.pop();
+                    if is_patch {
+                        let remaining = r.read_u32()?;
+                        r.item_count(remaining, 4)?;
+                        self.stack.push(Frame::Patches { remaining });
+                    }
+                    return self.step();
+                }
+            },
+            Frame::Patches { remaining: 0 } => {
+                self.stack.pop();
+                return self.step();
+            }
+            Frame::Patches { remaining } => {
+                *remaining -= 1;
+                let hash = r.read_u32()?;
+                let size = r.read_u32()?;
+                let start = r.position();
+                self.pending = Some(r.read_type()?);
+                let path = r.read_string()?;
+                self.stack.push(Frame::Patch { start, size });
+                BinEvent::PatchStart { hash, path }
+            }
+            Frame::Patch { start, size } => {
+                r.end_block(*start, *size)?;
+                self.stack.pop();
+                BinEvent::PatchEnd
+            }
+            Frame::Entry { remaining: 0, start, size } => {
+                r.end_block(*start, *size)?;
+                self.stack.pop();
+                BinEvent::EntryEnd
+            }
+            Frame::Struct { remaining: 0, block } => {
+                if let Some((start, size)) = *block {
+                    r.end_block(start, size)?;
+                }
+                self.stack.pop();
+                BinEvent::StructEnd
+            }
+            Frame::Entry { remaining, .. } | Frame::Struct { remaining, .. } => {
+                *remaining -= 1;
+                let key = r.read_u32()?;
+                self.pending = Some(r.read_type()?);
+                BinEvent::Field { key }
+            }
+            Frame::List { remaining: 0, start, size, .. } => {
+                r.end_block(*start, *size)?;
+                self.stack.pop();
+                BinEvent::ListEnd
+            }
+            Frame::Map { remaining: 0, key_next: true, start, size, .. } => {
+                r.end_block(*start, *size)?;
+                self.stack.pop();
+                BinEvent::MapEnd
+            }
+            Frame::List { value_type, remaining, .. } => {
+                *remaining -= 1;
+                let type_ = *value_type;
+                return self.begin_value(type_).map(Some);
+            }
+            Frame::Map { key_type, value_type, remaining, key_next, .. } => {
+                let type_ = if *key_next { *key_type } else { *remaining -= 1; *value_type };
+                *key_next = !*key_next;
+                return self.begin_value(type_).map(Some);
+            }
+            Frame::Option { value_type, pending: true } => {
+                let type_ = *value_type;
+                *frame = Frame::Option { value_type: type_, pending: false };
+                return self.begin_value(type_).map(Some);
+            }
+            Frame::Option { pending: false, .. } => {
+                self.stack.pop();
+                BinEvent::OptionEnd
+            }
+        };
+        Ok(Some(event))
+    }
+
+    fn read_header(&mut self) -> Result<BinEvent, BinError> {
+        let mut header = crate::model::Bin::new();
+        let is_patch = self.reader.read_header(&mut header)?;
+        let ptch_header = match header.sections.get("ptch_header") {
+            Some(BinValue::U64(v)) => Some(*v),
+            _ => None,
+        };
+        let version = match header.sections.get("version") {
+            Some(BinValue::U32(v)) => *v,
+            _ => unreachable!("read_header always sets the version"),
+        };
+        let linked = match header.sections.shift_remove("linked") {
+            Some(BinValue::List { items, .. }) => items.into_iter()
+                .filter_map(|item| match item {
+                    BinValue::String(s) => Some(s),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let entry_count = self.reader.read_u32()?;
+        let mut classes = Vec::with_capacity(self.reader.item_count(entry_count, 4)?);
+        for _ in 0..entry_count {
+            classes.push(self.reader.read_u32()?);
+        }
+        self.stack.push(Frame::Entries { classes: classes.into_iter(), is_patch });
+        Ok(BinEvent::Header { ptch_header, version, linked })
+    }
+
+    /// Read a plain value, or the header of a nested one and push its frame
+    fn begin_value(&mut self, type_: BinType) -> Result<BinEvent, BinError> {
+        let nested = type_.is_container() || matches!(type_, BinType::Pointer | BinType::Embed);
+        if !nested {
+            return Ok(BinEvent::Value(self.reader.read_value(&type_)?));
+        }
+        let max_depth = self.reader.opts.max_depth;
+        // The outermost frames are the entry table and the entry itself
+        if self.stack.len().saturating_sub(2) >= max_depth {
+            return Err(BinError::TooDeep(max_depth));
+        }
+        let r = &mut self.reader;
+        let (frame, event) = match type_ {
+            BinType::List | BinType::List2 => {
+                let value_type = r.read_type()?;
+                if value_type.is_container() {
+                    return Err(BinError::InvalidValue(value_type));
+                }
+                let size = r.read_u32()?;
+                let start = r.position();
+                let len = r.read_u32()?;
+                r.item_count(len, value_type.min_size())?;
+                (
+                    Frame::List { value_type, remaining: len, start, size },
+                    BinEvent::ListStart { list2: type_ == BinType::List2, value_type, len },
+                )
+            }
+            BinType::Map => {
+                let key_type = r.read_type()?;
+                if !key_type.is_primitive() {
+                    return Err(BinError::InvalidValue(key_type));
+                }
+                let value_type = r.read_type()?;
+                if value_type.is_container() {
+                    return Err(BinError::InvalidValue(value_type));
+                }
+                let size = r.read_u32()?;
+                let start = r.position();
+                let len = r.read_u32()?;
+                r.item_count(len, key_type.min_size() + value_type.min_size())?;
+                (
+                    Frame::Map { key_type, value_type, remaining: len, key_next: true, start, size },
+                    BinEvent::MapStart { key_type, value_type, len },
+                )
+            }
+            BinType::Option => {
+                let value_type = r.read_type()?;
+                if value_type.is_container() {
+                    return Err(BinError::InvalidValue(value_type));
+                }
+                let is_some = r.read_u8()? != 0;
+                (Frame::Option { value_type, pending: is_some }, BinEvent::OptionStart { value_type, is_some })
+            }
+            BinType::Pointer | BinType::Embed => {
+                let pointer = type_ == BinType::Pointer;
+                let class = r.read_u32()?;
+                let frame = if pointer && class == 0 {
+                    Frame::Struct { remaining: 0, block: None }
+                } else {
+                    let size = r.read_u32()?;
+                    let start = r.position();
+                    let remaining = r.read_u16()?;
+                    Frame::Struct { remaining, block: Some((start, size)) }
+                };
+                (frame, BinEvent::StructStart { pointer, class })
+            }
+            _ => unreachable!("only nested types get here"),
+        };
+        self.stack.push(frame);
+        Ok(event)
+    }
+
+    /// Skip the rest of the current entry, patch or container
+    ///
+    /// Uses the size recorded in the data where there is one, so the skipped
+    /// part is not decoded. Returns `false` if there is nothing to skip.
+    pub fn skip_current(&mut self) -> Result<bool, BinError> {
+        self.pending = None;
+        let block = match self.stack.last() {
+            Some(Frame::Entry { start, size, .. })
+            | Some(Frame::Patch { start, size })
+            | Some(Frame::List { start, size, .. })
+            | Some(Frame::Map { start, size, .. }) => Some((*start, *size)),
+            Some(Frame::Struct { block, .. }) => *block,
+            Some(Frame::Option { .. }) => {
+                // No size; decode and drop the item
+                while !matches!(self.next_event()?, Some(BinEvent::OptionEnd) | None) {}
+                return Ok(true);
+            }
+            Some(Frame::Entries { .. } | Frame::Patches { .. }) | None => return Ok(false),
+        };
+        if let Some((start, size)) = block {
+            self.reader.cursor.seek(SeekFrom::Start(start + size as u64))?;
+        }
+        self.stack.pop();
+        Ok(true)
+    }
+}
+
+impl Iterator for BinEventReader<'_> {
+    type Item = Result<BinEvent, BinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+/// Path of the value holding byte `offset` of `data`, in the form used by
+/// [`BinError::path`], e.g. `entries[0x1234abcd].0x2c3a9e81[3]`.
+///
+/// The header and entry table have an empty path. Returns `None` when
+/// `offset` is past the last section or the data can't be read that far.
+pub fn path_at(data: &[u8], offset: u64) -> Option<String> {
+    let mut events = BinEventReader::new(data);
+    let mut tracker = PathTracker::default();
+    let mut current = None;
+    while events.position() <= offset {
+        let event = events.next_event().ok()??;
+        current = Some(tracker.step(&event));
+    }
+    current
+}
+
+/// Where a value sits in its parent, for [`path_at`]
+#[derive(Debug)]
+enum PathFrame {
+    Fields { field: Option<u32> },
+    List { index: u32 },
+    /// `key` is the label of a key whose value comes next
+    Map { index: u32, key: Option<String> },
+    /// An option or patch, whose value adds nothing to the path
+    Single,
+}
+
+#[derive(Debug, Default)]
+struct PathTracker {
+    segments: Vec<String>,
+    frames: Vec<PathFrame>,
+}
+
+impl PathTracker {
+    /// Follow `event` and return the path it belongs to
+    fn step(&mut self, event: &BinEvent) -> String {
+        match event {
+            BinEvent::Header { .. } => String::new(),
+            BinEvent::EntryStart { hash, .. } => {
+                self.segments.push(format!("entries[{:#010x}]", hash));
+                self.frames.push(PathFrame::Fields { field: None });
+                self.segments.concat()
+            }
+            BinEvent::PatchStart { hash, .. } => {
+                self.segments.push(format!("patches[{:#010x}]", hash));
+                self.frames.push(PathFrame::Single);
+                self.segments.concat()
+            }
+            BinEvent::EntryEnd | BinEvent::PatchEnd => {
+                let path = self.segments.concat();
+                self.frames.pop();
+                self.segments.pop();
+                path
+            }
+            BinEvent::Field { key } => {
+                if let Some(PathFrame::Fields { field }) = self.frames.last_mut() {
+                    *field = Some(*key);
+                }
+                format!("{}.{:#010x}", self.segments.concat(), key)
+            }
+            BinEvent::Value(value) => {
+                let path = format!("{}{}", self.segments.concat(), self.element());
+                self.after_value(Some(value));
+                path
+            }
+            BinEvent::ListStart { .. } | BinEvent::MapStart { .. } | BinEvent::OptionStart { .. } | BinEvent::StructStart { .. } => {
+                let segment = self.element();
+                self.segments.push(segment);
+                self.frames.push(match event {
+                    BinEvent::ListStart { .. } => PathFrame::List { index: 0 },
+                    BinEvent::MapStart { .. } => PathFrame::Map { index: 0, key: None },
+                    BinEvent::OptionStart { .. } => PathFrame::Single,
+                    _ => PathFrame::Fields { field: None },
+                });
+                self.segments.concat()
+            }
+            BinEvent::ListEnd | BinEvent::MapEnd | BinEvent::OptionEnd | BinEvent::StructEnd => {
+                let path = self.segments.concat();
+                self.frames.pop();
+                self.segments.pop();
+                self.after_value(None);
+                path
+            }
+        }
+    }
+
+    /// Path segment of the next value in the innermost container
+    fn element(&self) -> String {
+        match self.frames.last() {
+            Some(PathFrame::Fields { field: Some(key) }) => format!(".{:#010x}", key),
+            Some(PathFrame::List { index }) => format!("[{}]", index),
+            Some(PathFrame::Map { key: Some(key), .. }) => format!("[{}]", key),
+            Some(PathFrame::Map { index, key: None }) => format!("[#{}]", index),
+            _ => String::new(),
+        }
+    }
+
+    /// Move past a finished value; `value` is only given for plain values
+    fn after_value(&mut self, value: Option<&BinValue>) {
+        match self.frames.last_mut() {
+            Some(PathFrame::Fields { field }) => *field = None,
+            Some(PathFrame::List { index }) => *index += 1,
+            Some(PathFrame::Map { index, key }) => match key.take() {
+                Some(_) => *index += 1,
+                None => *key = Some(value.map_or_else(|| format!("#{}", index), crate::compare::key_label)),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// What is being written at one level of nesting
+#[derive(Debug)]
+enum Open {
+    Entry { size_pos: u64, count_pos: u64, count: u16 },
+    /// A pointer or embed; a null pointer has no size or count
+    Struct { block: Option<(u64, u64)>, count: u16 },
+    List { size_pos: u64, count: u32 },
+    Map { size_pos: u64, count: u32, key_next: bool },
+    Option { remaining: u8 },
+    Patch { size_pos: u64 },
+}
+
+impl Open {
+    fn name(&self) -> &'static str {
+        match self {
+            Open::Entry { .. } => "entry",
+            Open::Struct { .. } => "struct",
+            Open::List { .. } => "list",
+            Open::Map { .. } => "map",
+            Open::Option { .. } => "option",
+            Open::Patch { .. } => "patch",
+        }
+    }
+}
+
+/// What the next value must be preceded by
+#[derive(Debug)]
+enum Prefix {
+    None,
+    /// The type byte of a field
+    FieldType,
+    /// The type byte and path of a patch
+    Patch(String),
+}
+
+/// Section of the file being written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Start,
+    Entries,
+    Patches { count_pos: u64, count: u32 },
+}
+
+/// Writes binary from a stream of [`BinEvent`]s.
+///
+/// Size and count fields are written as placeholders and filled in when the
+/// matching end event arrives. The entry table that precedes the entries is
+/// only known once every entry has been seen, so [`finish`](Self::finish)
+/// inserts it by moving the written data forward; apart from the 4 bytes per
+/// entry kept for that table, memory use doesn't grow with the input.
+///
+/// [`BinEvent::Value`] may also carry a whole list, map or structure, which
+/// is written in one go. `len` in start events is ignored; counts are taken
+/// from the events actually written.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use ritobin_rust::binary::events::{BinEvent, BinEventReader, BinEventWriter};
+/// use ritobin_rust::model::BinValue;
+///
+/// let mut bin = ritobin_rust::model::Bin::new();
+/// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+/// bin.sections.insert("version".to_string(), BinValue::U32(3));
+/// let data = ritobin_rust::binary::write_bin(&bin)?;
+///
+/// // Copy the file event by event, upper-casing every string
+/// let mut writer = BinEventWriter::new(Cursor::new(Vec::new()));
+/// for event in BinEventReader::new(&data) {
+///     match event? {
+///         BinEvent::Value(BinValue::String(s)) => writer.write(BinEvent::Value(BinValue::String(s.to_uppercase())))?,
+///         event => writer.write(event)?,
+///     }
+/// }
+/// let copy = writer.finish()?.into_inner();
+/// assert_eq!(copy, data);
+/// # Ok::<(), ritobin_rust::binary::BinError>(())
+/// ```
+#[derive(Debug)]
+pub struct BinEventWriter<W> {
+    out: W,
+    stack: Vec<Open>,
+    prefix: Prefix,
+    stage: Stage,
+    is_patch: bool,
+    version: u32,
+    /// Where the entry table goes
+    table_pos: u64,
+    classes: Vec<u32>,
+}
+
+impl<W: Read + Write + Seek> BinEventWriter<W> {
+    /// Write into `out`, starting at its current position
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            stack: Vec::new(),
+            prefix: Prefix::None,
+            stage: Stage::Start,
+            is_patch: false,
+            version: 0,
+            table_pos: 0,
+            classes: Vec::new(),
+        }
+    }
+
+    /// Write one event
+    pub fn write(&mut self, event: BinEvent) -> Result<(), BinError> {
+        if self.stage == Stage::Start && !matches!(event, BinEvent::Header { .. }) {
+            return Err(unexpected("the header must come first"));
+        }
+        match event {
+            BinEvent::Header { ptch_header, version, linked } => {
+                if self.stage != Stage::Start {
+                    return Err(unexpected("a second header"));
+                }
+                if let Some(header) = ptch_header {
+                    self.out.write_all(b"PTCH")?;
+                    self.out.write_u64::<LE>(header)?;
+                }
+                self.out.write_all(b"PROP")?;
+                self.out.write_u32::<LE>(version)?;
+                if version >= 2 {
+                    self.out.write_u32::<LE>(checked_len(linked.len(), u32::MAX)?)?;
+                    for path in &linked {
+                        self.write_string(path)?;
+                    }
+                }
+                self.is_patch = ptch_header.is_some();
+                self.version = version;
+                self.table_pos = self.out.stream_position()?;
+                self.stage = Stage::Entries;
+            }
+            BinEvent::EntryStart { hash, class } => {
+                if self.stage != Stage::Entries || !self.stack.is_empty() {
+                    return Err(unexpected("an entry inside another value or after the patches"));
+                }
+                self.classes.push(class);
+                let size_pos = self.placeholder_u32()?;
+                self.out.write_u32::<LE>(hash)?;
+                let count_pos = self.out.stream_position()?;
+                self.out.write_u16::<LE>(0)?;
+                self.stack.push(Open::Entry { size_pos, count_pos, count: 0 });
+            }
+            BinEvent::PatchStart { hash, path } => {
+                if !self.stack.is_empty() || !self.is_patch || self.version < 3 {
+                    return Err(unexpected("a patch outside a version 3 PTCH file, or inside another value"));
+                }
+                let (count_pos, count) = match self.stage {
+                    Stage::Patches { count_pos, count } => (count_pos, count),
+                    _ => (self.placeholder_u32()?, 0),
+                };
+                self.stage = Stage::Patches { count_pos, count: count + 1 };
+                self.out.write_u32::<LE>(hash)?;
+                let size_pos = self.placeholder_u32()?;
+                self.prefix = Prefix::Patch(path);
+                self.stack.push(Open::Patch { size_pos });
+            }
+            BinEvent::Field { key } => {
+                if !matches!(self.prefix, Prefix::None) {
+                    return Err(unexpected("a field where a value was expected"));
+                }
+                match self.stack.last_mut() {
+                    Some(Open::Entry { count, .. } | Open::Struct { block: Some(_), count }) => {
+                        *count = count.checked_add(1).ok_or(too_large(u16::MAX))?;
+                    }
+                    _ => return Err(unexpected("a field outside an entry or struct")),
+                }
+                self.out.write_u32::<LE>(key)?;
+                self.prefix = Prefix::FieldType;
+            }
+            BinEvent::Value(value) => {
+                let type_byte = match &value {
+                    BinValue::Raw { type_byte, .. } => *type_byte,
+                    value => get_value_type(value) as u8,
+                };
+                self.begin_value(type_byte)?;
+                if let BinValue::Raw { absorbed_fields, .. } = &value {
+                    if let Some(Open::Entry { count, .. } | Open::Struct { count, .. }) = self.stack.last_mut() {
+                        *count = count.checked_add(*absorbed_fields).ok_or(too_large(u16::MAX))?;
+                    }
+                }
+                let mut writer = BinaryWriter::new();
+                writer.write_value(&value)?;
+                self.out.write_all(&writer.into_inner())?;
+            }
+            BinEvent::ListStart { list2, value_type, .. } => {
+                self.begin_value(if list2 { BinType::List2 } else { BinType::List } as u8)?;
+                self.out.write_u8(value_type as u8)?;
+                let size_pos = self.placeholder_u32()?;
+                self.out.write_u32::<LE>(0)?;
+                self.stack.push(Open::List { size_pos, count: 0 });
+            }
+            BinEvent::MapStart { key_type, value_type, .. } => {
+                self.begin_value(BinType::Map as u8)?;
+                self.out.write_u8(key_type as u8)?;
+                self.out.write_u8(value_type as u8)?;
+                let size_pos = self.placeholder_u32()?;
+                self.out.write_u32::<LE>(0)?;
+                self.stack.push(Open::Map { size_pos, count: 0, key_next: true });
+            }
+            BinEvent::OptionStart { value_type, is_some } => {
+                self.begin_value(BinType::Option as u8)?;
+                self.out.write_u8(value_type as u8)?;
+                self.out.write_u8(is_some as u8)?;
+                self.stack.push(Open::Option { remaining: is_some as u8 });
+            }
+            BinEvent::StructStart { pointer, class } => {
+                self.begin_value(if pointer { BinType::Pointer } else { BinType::Embed } as u8)?;
+                self.out.write_u32::<LE>(class)?;
+                let block = if pointer && class == 0 {
+                    None
+                } else {
+                    let size_pos = self.placeholder_u32()?;
+                    let count_pos = self.out.stream_position()?;
+                    self.out.write_u16::<LE>(0)?;
+                    Some((size_pos, count_pos))
+                };
+                self.stack.push(Open::Struct { block, count: 0 });
+            }
+            BinEvent::EntryEnd => match self.close("entry")? {
+                Open::Entry { size_pos, count_pos, count } => {
+                    self.fill_size(size_pos)?;
+                    self.fill(count_pos, |out| out.write_u16::<LE>(count))?;
+                }
+                _ => unreachable!(),
+            },
+            BinEvent::StructEnd => {
+                // Null pointers have nothing to fill in
+                if let Open::Struct { block: Some((size_pos, count_pos)), count } = self.close("struct")? {
+                    self.fill_size(size_pos)?;
+                    self.fill(count_pos, |out| out.write_u16::<LE>(count))?;
+                }
+            }
+            BinEvent::ListEnd | BinEvent::MapEnd => {
+                let name = if event == BinEvent::ListEnd { "list" } else { "map" };
+                match self.close(name)? {
+                    Open::List { size_pos, count } | Open::Map { size_pos, count, key_next: true } => {
+                        self.fill_size(size_pos)?;
+                        self.fill(size_pos + 4, |out| out.write_u32::<LE>(count))?;
+                    }
+                    _ => return Err(unexpected("the end of a map after a key without its value")),
+                }
+            }
+            BinEvent::OptionEnd => match self.close("option")? {
+                Open::Option { remaining: 0 } => {}
+                _ => return Err(unexpected("the end of an option that is missing its value")),
+            },
+            BinEvent::PatchEnd => match self.close("patch")? {
+                Open::Patch { size_pos } => self.fill_size(size_pos)?,
+                _ => unreachable!(),
+            },
+        }
+        Ok(())
+    }
+
+    /// Check that every value is complete, write the entry table and return the output
+    pub fn finish(mut self) -> Result<W, BinError> {
+        if let Some(open) = self.stack.last() {
+            return Err(unexpected(&format!("the end of the file inside an unfinished {}", open.name())));
+        }
+        match self.stage {
+            Stage::Start => return Err(unexpected("the end of the file before the header")),
+            Stage::Patches { count_pos, count } => self.fill(count_pos, |out| out.write_u32::<LE>(count))?,
+            Stage::Entries if self.is_patch && self.version >= 3 => self.out.write_u32::<LE>(0)?,
+            Stage::Entries => {}
+        }
+
+        // Move everything after the header forward to make room for the table
+        let mut table = Vec::with_capacity(4 + self.classes.len() * 4);
+        table.write_u32::<LE>(checked_len(self.classes.len(), u32::MAX)?)?;
+        for class in &self.classes {
+            table.write_u32::<LE>(*class)?;
+        }
+        let end = self.out.stream_position()?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut pos = end;
+        while pos > self.table_pos {
+            let n = buf.len().min((pos - self.table_pos) as usize);
+            pos -= n as u64;
+            self.out.seek(SeekFrom::Start(pos))?;
+            self.out.read_exact(&mut buf[..n])?;
+            self.out.seek(SeekFrom::Start(pos + table.len() as u64))?;
+            self.out.write_all(&buf[..n])?;
+        }
+        self.out.seek(SeekFrom::Start(self.table_pos))?;
+        self.out.write_all(&table)?;
+        self.out.seek(SeekFrom::Start(end + table.len() as u64))?;
+        Ok(self.out)
+    }
+
+    /// Write what precedes a value of type `type_byte` in its container
+    fn begin_value(&mut self, type_byte: u8) -> Result<(), BinError> {
+        match std::mem::replace(&mut self.prefix, Prefix::None) {
+            Prefix::FieldType => self.out.write_u8(type_byte)?,
+            Prefix::Patch(path) => {
+                self.out.write_u8(type_byte)?;
+                self.write_string(&path)?;
+            }
+            Prefix::None => match self.stack.last_mut() {
+                Some(Open::List { count, .. }) => *count += 1,
+                Some(Open::Map { count, key_next, .. }) => {
+                    if *key_next {
+                        *count += 1;
+                    }
+                    *key_next = !*key_next;
+                }
+                Some(Open::Option { remaining }) if *remaining > 0 => *remaining -= 1,
+                Some(open) => return Err(unexpected(&format!("a value directly inside a {}", open.name()))),
+                None => return Err(unexpected("a value outside any entry or patch")),
+            },
+        }
+        Ok(())
+    }
+
+    /// Pop the innermost open value, which must be a `name`
+    fn close(&mut self, name: &str) -> Result<Open, BinError> {
+        if !matches!(self.prefix, Prefix::None) {
+            return Err(unexpected(&format!("the end of a {} where a value was expected", name)));
+        }
+        match self.stack.pop() {
+            Some(open) if open.name() == name => Ok(open),
+            Some(open) => Err(unexpected(&format!("the end of a {} inside a {}", name, open.name()))),
+            None => Err(unexpected(&format!("the end of a {} that was never started", name))),
+        }
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), BinError> {
+        self.out.write_u16::<LE>(checked_len(s.len(), u16::MAX)?)?;
+        self.out.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    fn placeholder_u32(&mut self) -> Result<u64, BinError> {
+        let pos = self.out.stream_position()?;
+        self.out.write_u32::<LE>(0)?;
+        Ok(pos)
+    }
+
+    /// Overwrite the data at `pos`, then return to the current position
+    fn fill(&mut self, pos: u64, write: impl FnOnce(&mut W) -> std::io::Result<()>) -> Result<(), BinError> {
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(pos))?;
+        write(&mut self.out)?;
+        self.out.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+
+    /// Fill the size placeholder at `size_pos` with the length of what follows it
+    fn fill_size(&mut self, size_pos: u64) -> Result<(), BinError> {
+        let size = checked_len((self.out.stream_position()? - size_pos - 4) as usize, u32::MAX)?;
+        self.fill(size_pos, |out| out.write_u32::<LE>(size))
+    }
+}
+
+fn too_large(limit: u16) -> BinError {
+    BinError::TooLarge { path: String::new(), limit: limit.into() }
+}
+
+fn unexpected(what: &str) -> BinError {
+    BinError::UnexpectedEvent(what.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write_bin;
+
+    #[test]
+    fn test_events_cover_every_value() {
+        for case in crate::corpus::cases() {
+            let data = write_bin(&case.bin).unwrap();
+            let events: Vec<BinEvent> = BinEventReader::new(&data).collect::<Result<_, _>>().unwrap();
+            assert!(matches!(events[0], BinEvent::Header { .. }), "{}", case.name);
+
+            // Every start has its end, and every value is reached
+            let mut depth = 0i32;
+            let mut values = 0;
+            for event in &events {
+                match event {
+                    BinEvent::EntryStart { .. } | BinEvent::PatchStart { .. } | BinEvent::ListStart { .. }
+                    | BinEvent::MapStart { .. } | BinEvent::OptionStart { .. } | BinEvent::StructStart { .. } => depth += 1,
+                    BinEvent::EntryEnd | BinEvent::PatchEnd | BinEvent::ListEnd
+                    | BinEvent::MapEnd | BinEvent::OptionEnd | BinEvent::StructEnd => depth -= 1,
+                    BinEvent::Value(_) => values += 1,
+                    _ => {}
+                }
+                assert!(depth >= 0, "{}", case.name);
+            }
+            assert_eq!(depth, 0, "{}", case.name);
+            assert_eq!(values, count_values(&case.bin), "{}", case.name);
+
+            let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+            for event in events {
+                writer.write(event).unwrap();
+            }
+            assert_eq!(writer.finish().unwrap().into_inner(), data, "{}", case.name);
+        }
+
+        // Drop one entry while copying
+        let version3 = crate::corpus::cases().into_iter().find(|c| c.name == "version3").unwrap();
+        let data = write_bin(&version3.bin).unwrap();
+        let dropped = crate::hash::fnv1a("Corpus/V3/B");
+        let mut reader = BinEventReader::new(&data);
+        let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+        while let Some(event) = reader.next_event().unwrap() {
+            if event == (BinEvent::EntryStart { hash: dropped, class: crate::hash::fnv1a("Simple") }) {
+                reader.skip_current().unwrap();
+            } else {
+                writer.write(event).unwrap();
+            }
+        }
+        let filtered = crate::binary::read_bin(&writer.finish().unwrap().into_inner()).unwrap();
+        let kept = [crate::hash::fnv1a("Corpus/V3/A"), crate::hash::fnv1a("Corpus/V3/C")].into_iter().collect();
+        assert_eq!(filtered, crate::binary::read_bin_filtered(&data, &kept).unwrap());
+
+        let all_types = write_bin(&crate::corpus::cases()[0].bin).unwrap();
+        let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+        assert!(matches!(writer.write(BinEvent::EntryEnd), Err(BinError::UnexpectedEvent(_))));
+        let mut reader = BinEventReader::new(&all_types);
+        reader.next_event().unwrap();
+        assert!(matches!(reader.next_event().unwrap(), Some(BinEvent::EntryStart { .. })));
+        assert!(reader.skip_current().unwrap());
+        assert_eq!(reader.next_event().unwrap(), None);
+    }
+
+    #[test]
+    fn test_event_writer() {
+        // Whole structures as single values give the same bytes as write_bin
+        let path_key = crate::hash::fnv1a("path");
+        let value_key = crate::hash::fnv1a("value");
+        for case in crate::corpus::cases() {
+            let data = write_bin(&case.bin).unwrap();
+            let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+            writer.write(BinEventReader::new(&data).next_event().unwrap().unwrap()).unwrap();
+            if let Some(BinValue::Map { items, .. }) = case.bin.sections.get("entries") {
+                for (key, value) in items {
+                    let (BinValue::Hash { value: hash, .. }, BinValue::Embed { name, items: fields, .. }) = (key, value) else { panic!() };
+                    writer.write(BinEvent::EntryStart { hash: *hash, class: *name }).unwrap();
+                    for field in fields {
+                        writer.write(BinEvent::Field { key: field.key }).unwrap();
+                        writer.write(BinEvent::Value(field.value.clone())).unwrap();
+                    }
+                    writer.write(BinEvent::EntryEnd).unwrap();
+                }
+            }
+            if let Some(BinValue::Map { items, .. }) = case.bin.sections.get("patches") {
+                for (key, value) in items {
+                    let (BinValue::Hash { value: hash, .. }, BinValue::Embed { items: fields, .. }) = (key, value) else { panic!() };
+                    let field = |key| &fields.iter().find(|f| f.key == key).unwrap().value;
+                    let BinValue::String(path) = field(path_key) else { panic!() };
+                    writer.write(BinEvent::PatchStart { hash: *hash, path: path.clone() }).unwrap();
+                    writer.write(BinEvent::Value(field(value_key).clone())).unwrap();
+                    writer.write(BinEvent::PatchEnd).unwrap();
+                }
+            }
+            assert_eq!(writer.finish().unwrap().into_inner(), data, "{}", case.name);
+        }
+
+        // Each sequence is accepted up to its last event, which is rejected
+        let header = || BinEvent::Header { ptch_header: None, version: 3, linked: vec![] };
+        let entry = || BinEvent::EntryStart { hash: 1, class: 2 };
+        let field = || BinEvent::Field { key: 3 };
+        let value = || BinEvent::Value(BinValue::U8(4));
+        let cases: Vec<(Vec<BinEvent>, &str)> = vec![
+            (vec![entry()], "the header must come first"),
+            (vec![header(), header()], "a second header"),
+            (vec![header(), entry(), entry()], "an entry inside another value"),
+            (vec![header(), BinEvent::PatchStart { hash: 1, path: "a".to_string() }], "a patch outside a version 3 PTCH file"),
+            (vec![header(), field()], "a field outside an entry or struct"),
+            (vec![header(), entry(), field(), field()], "a field where a value was expected"),
+            (vec![header(), value()], "a value outside any entry or patch"),
+            (vec![header(), entry(), value()], "a value directly inside a entry"),
+            (vec![header(), entry(), field(), BinEvent::EntryEnd], "the end of a entry where a value was expected"),
+            (vec![header(), BinEvent::StructEnd], "the end of a struct that was never started"),
+            (
+                vec![header(), entry(), field(), BinEvent::ListStart { list2: false, value_type: BinType::U8, len: 0 }, BinEvent::MapEnd],
+                "the end of a map inside a list",
+            ),
+            (
+                vec![header(), entry(), field(), BinEvent::MapStart { key_type: BinType::U8, value_type: BinType::U8, len: 1 }, value(), BinEvent::MapEnd],
+                "the end of a map after a key without its value",
+            ),
+            (
+                vec![header(), entry(), field(), BinEvent::OptionStart { value_type: BinType::U8, is_some: true }, BinEvent::OptionEnd],
+                "the end of an option that is missing its value",
+            ),
+            (
+                vec![header(), entry(), field(), BinEvent::OptionStart { value_type: BinType::U8, is_some: false }, value()],
+                "a value directly inside a option",
+            ),
+        ];
+        for (mut events, expected) in cases {
+            let last = events.pop().unwrap();
+            let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+            for event in events {
+                writer.write(event).unwrap();
+            }
+            match writer.write(last) {
+                Err(BinError::UnexpectedEvent(message)) => assert!(message.starts_with(expected), "{}", message),
+                result => panic!("{}: {:?}", expected, result),
+            }
+        }
+
+        let finish = |events: Vec<BinEvent>| {
+            let mut writer = BinEventWriter::new(std::io::Cursor::new(Vec::new()));
+            events.into_iter().for_each(|event| writer.write(event).unwrap());
+            match writer.finish() {
+                Err(BinError::UnexpectedEvent(message)) => message,
+                result => panic!("{:?}", result),
+            }
+        };
+        assert_eq!(finish(vec![]), "the end of the file before the header");
+        assert_eq!(finish(vec![header(), entry()]), "the end of the file inside an unfinished entry");
+    }
+
+    /// Number of plain values under the entries and patches of `bin`
+    fn count_values(bin: &crate::model::Bin) -> usize {
+        fn count(value: &BinValue) -> usize {
+            match value {
+                BinValue::List { items, .. } | BinValue::List2 { items, .. } => items.iter().map(count).sum(),
+                BinValue::Map { items, .. } => items.iter().map(|(k, v)| count(k) + count(v)).sum(),
+                BinValue::Option { item, .. } => item.as_deref().map_or(0, count),
+                BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => items.iter().map(|f| count(&f.value)).sum(),
+                _ => 1,
+            }
+        }
+        let entries = match bin.sections.get("entries") {
+            Some(BinValue::Map { items, .. }) => items.iter().map(|(_, v)| count(v)).sum(),
+            _ => 0,
+        };
+        let patches = match bin.sections.get("patches") {
+            Some(BinValue::Map { items, .. }) => items.iter()
+                .filter_map(|(_, v)| match v {
+                    BinValue::Embed { items, .. } => items.iter().find(|f| f.key_str.as_deref() == Some("value")),
+                    _ => None,
+                })
+                .map(|f| count(&f.value))
+                .sum(),
+            _ => 0,
+        };
+        entries + patches
+    }
+}
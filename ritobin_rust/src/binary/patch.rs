@@ -0,0 +1,413 @@
+//! Building PTCH files from code.
+//!
+//! A PTCH file carries, besides ordinary entries, a list of patches: each one
+//! targets an entry by its hash and replaces the value at a field path inside
+//! it, e.g. `mCharacterName` or `mAbilities.mSpell.mCooldownTime`.
+//!
+//! [`PatchBuilder`] writes such files, [`diff`] derives one from two versions
+//! of a bin, and [`apply`] applies one to the PROP bin it patches, to preview
+//! what the game will see.
+
+use super::{write_bin, BinError, DEFAULT_PTCH_HEADER};
+use crate::hash::fnv1a;
+use crate::compare::{field_label, same_value};
+use crate::edit::ValuePath;
+use crate::model::{Bin, BinType, BinValue, Field};
+
+/// Assembles a PTCH [`Bin`] patch by patch.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::patch::PatchBuilder;
+/// use ritobin_rust::model::BinValue;
+///
+/// let mut builder = PatchBuilder::new();
+/// builder.add_patch("Characters/Ahri/CharacterRecords/Root.baseHP", BinValue::F32(650.0))?;
+/// let data = builder.write()?;
+/// assert!(data.starts_with(b"PTCH"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PatchBuilder {
+    version: u32,
+    header: u64,
+    linked: Vec<String>,
+    entries: Vec<(BinValue, BinValue)>,
+    patches: Vec<(BinValue, BinValue)>,
+}
+
+impl Default for PatchBuilder {
+    fn default() -> Self {
+        Self { version: 3, header: DEFAULT_PTCH_HEADER, linked: Vec::new(), entries: Vec::new(), patches: Vec::new() }
+    }
+}
+
+impl PatchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the PROP version; patches are only written from version 3 on
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the word written after the `PTCH` magic
+    pub fn header(&mut self, header: u64) -> &mut Self {
+        self.header = header;
+        self
+    }
+
+    /// Add a linked file
+    pub fn link(&mut self, path: &str) -> &mut Self {
+        self.linked.push(path.to_string());
+        self
+    }
+
+    /// Add an ordinary entry of class `class`
+    pub fn add_entry(&mut self, name: &str, class: &str, fields: Vec<Field>) -> &mut Self {
+        self.entries.push((
+            BinValue::Hash { value: fnv1a(name), name: Some(name.to_string()) },
+            BinValue::Embed { name: fnv1a(class), name_str: Some(class.to_string()), items: fields },
+        ));
+        self
+    }
+
+    /// Patch `target`, an entry name followed by a field path, to `value`
+    ///
+    /// The field path starts at the first `.` after the entry name's last `/`,
+    /// so `Characters/Ahri/CharacterRecords/Root.baseHP` patches `baseHP` of
+    /// `Characters/Ahri/CharacterRecords/Root`. Use [`add_patch_to`](Self::add_patch_to)
+    /// for entry names with a `.` in their last component.
+    pub fn add_patch(&mut self, target: &str, value: BinValue) -> Result<&mut Self, String> {
+        let last = target.rfind('/').map_or(0, |i| i + 1);
+        let dot = target[last..].find('.').map(|i| last + i)
+            .ok_or_else(|| format!("Patch target '{}' has no field path", target))?;
+        let (entry, path) = (&target[..dot], &target[dot + 1..]);
+        if entry.is_empty() || path.is_empty() {
+            return Err(format!("Patch target '{}' needs both an entry and a field path", target));
+        }
+        Ok(self.add_patch_to(entry, path, value))
+    }
+
+    /// Patch the field at `path` of the entry named `entry` to `value`
+    pub fn add_patch_to(&mut self, entry: &str, path: &str, value: BinValue) -> &mut Self {
+        self.push_patch(BinValue::Hash { value: fnv1a(entry), name: Some(entry.to_string()) }, path, value)
+    }
+
+    /// Patch the field at `path` of the entry with hash `entry` to `value`
+    pub fn add_patch_hashed(&mut self, entry: u32, path: &str, value: BinValue) -> &mut Self {
+        self.push_patch(BinValue::Hash { value: entry, name: None }, path, value)
+    }
+
+    fn push_patch(&mut self, key: BinValue, path: &str, value: BinValue) -> &mut Self {
+        // Same shape the reader produces for the `patches` section
+        let fields = vec![
+            Field { key: fnv1a("path"), key_str: Some("path".to_string()), value: BinValue::String(path.to_string()) },
+            Field { key: fnv1a("value"), key_str: Some("value".to_string()), value },
+        ];
+        self.patches.push((key, BinValue::Embed { name: fnv1a("patch"), name_str: None, items: fields }));
+        self
+    }
+
+    /// The PTCH bin built so far
+    pub fn build(&self) -> Bin {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+        bin.sections.insert("ptch_header".to_string(), BinValue::U64(self.header));
+        bin.sections.insert("version".to_string(), BinValue::U32(self.version));
+        bin.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: self.linked.iter().cloned().map(BinValue::String).collect(),
+        });
+        let map = |items: &Vec<(BinValue, BinValue)>| BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: items.clone(),
+        };
+        bin.sections.insert("entries".to_string(), map(&self.entries));
+        bin.sections.insert("patches".to_string(), map(&self.patches));
+        bin
+    }
+
+    /// Encode the PTCH file
+    pub fn write(&self) -> Result<Vec<u8>, BinError> {
+        write_bin(&self.build())
+    }
+}
+
+/// Build a PTCH bin that turns `old` into `new`, holding only what changed.
+///
+/// Each changed field becomes a patch with its full path inside the entry,
+/// descending into embeds and pointers whose class didn't change; any other
+/// changed value (a list, map, ...) is patched as a whole. Entries that are
+/// new in `new`, changed class or lost top-level fields can't be expressed as
+/// patches and are carried whole in the `entries` section. Entries removed in
+/// `new` are not represented, as PTCH files have no way to delete.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::binary::patch::{apply, diff, PatchBuilder};
+/// use ritobin_rust::model::{BinValue, Field};
+///
+/// let hp = |v| vec![Field { key: ritobin_rust::hash::fnv1a("baseHP"), key_str: None, value: BinValue::F32(v) }];
+/// let old = PatchBuilder::new().add_entry("Root", "CharacterRecord", hp(500.0)).build();
+/// let new = PatchBuilder::new().add_entry("Root", "CharacterRecord", hp(650.0)).build();
+///
+/// let patch = diff(&old, &new);
+/// let mut patched = old.clone();
+/// assert_eq!(apply(&mut patched, &patch)?.applied, 1);
+/// assert_eq!(patched.entry("Root"), new.entry("Root"));
+/// # Ok::<(), String>(())
+/// ```
+pub fn diff(old: &Bin, new: &Bin) -> Bin {
+    let mut builder = PatchBuilder::new();
+    let Some(BinValue::Map { items, .. }) = new.sections.get("entries") else {
+        return builder.build();
+    };
+    let old_entries = old.sections.get("entries");
+    for (key, entry) in items {
+        let BinValue::Hash { value: hash, .. } = key else { continue };
+        let old_entry = old_entries.and_then(|e| e.get_by_hash(*hash));
+        let mut patches = Vec::new();
+        let patchable = match (old_entry, entry) {
+            (Some(BinValue::Embed { name: a, items: old_fields, .. }), BinValue::Embed { name: b, items: new_fields, .. }) => {
+                a == b && diff_fields(old_fields, new_fields, "", &mut patches)
+            }
+            _ => false,
+        };
+        if !patchable {
+            builder.entries.push((key.clone(), entry.clone()));
+            continue;
+        }
+        for (path, value) in patches {
+            builder.push_patch(key.clone(), &path, value);
+        }
+    }
+    builder.build()
+}
+
+/// Collect the patches turning the fields `old` into `new`
+///
+/// Returns false when that isn't possible because a field was removed.
+fn diff_fields(old: &[Field], new: &[Field], prefix: &str, out: &mut Vec<(String, BinValue)>) -> bool {
+    if old.iter().any(|o| !new.iter().any(|n| n.key == o.key)) {
+        return false;
+    }
+    for field in new {
+        let path = match prefix {
+            "" => field_label(field),
+            _ => format!("{}.{}", prefix, field_label(field)),
+        };
+        let Some(old_field) = old.iter().find(|o| o.key == field.key) else {
+            out.push((path, field.value.clone()));
+            continue;
+        };
+        if same_value(&old_field.value, &field.value) {
+            continue;
+        }
+        let nested = match (&old_field.value, &field.value) {
+            (BinValue::Embed { name: a, items: oi, .. }, BinValue::Embed { name: b, items: ni, .. })
+            | (BinValue::Pointer { name: a, items: oi, .. }, BinValue::Pointer { name: b, items: ni, .. }) => {
+                // Patch inside the struct when possible, otherwise replace it whole
+                let mut inner = Vec::new();
+                let ok = a == b && *a != 0 && diff_fields(oi, ni, &path, &mut inner);
+                ok.then_some(inner)
+            }
+            _ => None,
+        };
+        match nested {
+            Some(inner) => out.extend(inner),
+            None => out.push((path, field.value.clone())),
+        }
+    }
+    true
+}
+
+/// What [`apply`] changed in the base bin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// Entries of the patch file added to, or replacing entries in, the base
+    pub entries: usize,
+    /// Patches written into the base
+    pub applied: usize,
+    /// Patches whose target doesn't exist in the base, as (target, reason)
+    pub failed: Vec<(String, String)>,
+}
+
+/// Apply the PTCH bin `patch` to `base`.
+///
+/// Entries in the patch's `entries` section replace the base entries with the
+/// same hash (or are appended), then each patch sets the value at its path
+/// inside its target entry. Fields missing from the target entry are added;
+/// patches whose entry or container doesn't exist are listed in
+/// [`ApplyReport::failed`] and otherwise ignored.
+///
+/// Fails without changing `base` if the patch has malformed records.
+pub fn apply(base: &mut Bin, patch: &Bin) -> Result<ApplyReport, String> {
+    let mut report = ApplyReport::default();
+    let patches = match patch.sections.get("patches") {
+        Some(BinValue::Map { items, .. }) => items.iter().map(patch_target).collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("The patches section must be a map".to_string()),
+        None => Vec::new(),
+    };
+
+    if let Some(BinValue::Map { items, .. }) = patch.sections.get("entries") {
+        if !items.is_empty() && !matches!(base.sections.get("entries"), Some(BinValue::Map { .. })) {
+            base.sections.insert("entries".to_string(), BinValue::Map {
+                key_type: BinType::Hash,
+                value_type: BinType::Embed,
+                items: Vec::new(),
+            });
+        }
+        if let Some(BinValue::Map { items: base_items, .. }) = base.sections.get_mut("entries") {
+            for (key, entry) in items {
+                match base_items.iter_mut().find(|(k, _)| crate::compare::same_key(k, key)) {
+                    Some((_, slot)) => *slot = entry.clone(),
+                    None => base_items.push((key.clone(), entry.clone())),
+                }
+                report.entries += 1;
+            }
+        }
+    }
+
+    for (label, hash, path, value) in patches {
+        let target = if path.starts_with('[') {
+            format!("entries[{:#010x}]{}", hash, path)
+        } else {
+            format!("entries[{:#010x}].{}", hash, path)
+        };
+        let exists = base.sections.get("entries").and_then(|e| e.get_by_hash(hash)).is_some();
+        let result = match target.parse::<ValuePath>() {
+            Ok(target) if exists => crate::edit::set(base, &target, value),
+            Ok(_) => Err("entry not found".to_string()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => report.applied += 1,
+            Err(e) => report.failed.push((format!("{}.{}", label, path), e)),
+        }
+    }
+    Ok(report)
+}
+
+/// (entry label, entry hash, field path, value) of one `patches` item
+fn patch_target((key, record): &(BinValue, BinValue)) -> Result<(String, u32, String, BinValue), String> {
+    let BinValue::Hash { value: hash, .. } = key else {
+        return Err("Patch keys must be hashes".to_string());
+    };
+    let label = match key {
+        BinValue::Hash { name: Some(name), .. } => name.clone(),
+        _ => crate::compare::key_label(key),
+    };
+    let BinValue::Embed { items, .. } = record else {
+        return Err(format!("Patch {} is not an embed", label));
+    };
+    let field = |name: &str| items.iter().find(|f| f.key == fnv1a(name)).map(|f| &f.value);
+    let (Some(BinValue::String(path)), Some(value)) = (field("path"), field("value")) else {
+        return Err(format!("Patch {} needs a string path and a value", label));
+    };
+    Ok((label, *hash, path.clone(), value.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read_bin;
+
+    #[test]
+    fn test_patch_builder_round_trip() {
+        let mut builder = PatchBuilder::new();
+        builder.link("DATA/Characters/Ahri/Ahri.bin");
+        builder.add_patch("Characters/Ahri/CharacterRecords/Root.mAbilities.baseHP", BinValue::F32(650.0)).unwrap();
+        builder.add_patch_to("Maps/Shipping/Map11.v2", "enabled", BinValue::Bool(false));
+        assert!(builder.add_patch("Characters/Ahri/Root", BinValue::None).is_err());
+
+        let read = read_bin(&builder.write().unwrap()).unwrap();
+        let patches = &read.sections["patches"];
+        let Some(BinValue::Embed { items, .. }) = patches.get_by_name("Characters/Ahri/CharacterRecords/Root") else { panic!() };
+        assert_eq!(items[0].value, BinValue::String("mAbilities.baseHP".to_string()));
+        assert_eq!(items[1].value, BinValue::F32(650.0));
+        let Some(BinValue::Embed { items, .. }) = patches.get_by_name("Maps/Shipping/Map11.v2") else { panic!() };
+        assert_eq!(items[0].value, BinValue::String("enabled".to_string()));
+        assert!(crate::binary::write_bin_checked(&builder.build()).is_ok());
+    }
+
+    #[test]
+    fn test_diff_then_apply() {
+        let fields = |hp: f32, scale: f32, extra: bool| {
+            let mut fields = vec![
+                Field { key: fnv1a("baseHP"), key_str: Some("baseHP".to_string()), value: BinValue::F32(hp) },
+                Field { key: fnv1a("skin"), key_str: Some("skin".to_string()), value: BinValue::Embed {
+                    name: fnv1a("SkinData"),
+                    name_str: None,
+                    items: vec![Field { key: fnv1a("scale"), key_str: Some("scale".to_string()), value: BinValue::F32(scale) }],
+                } },
+            ];
+            if extra {
+                fields.push(Field { key: fnv1a("mana"), key_str: None, value: BinValue::F32(1.0) });
+            }
+            fields
+        };
+        let mut old = PatchBuilder::new();
+        old.add_entry("A", "Record", fields(1.0, 1.0, false))
+            .add_entry("B", "Record", fields(1.0, 1.0, true))
+            .add_entry("C", "Record", fields(1.0, 1.0, false));
+        let mut new = PatchBuilder::new();
+        new.add_entry("A", "Record", fields(2.0, 1.5, false))
+            .add_entry("B", "Record", fields(1.0, 1.0, false))
+            .add_entry("C", "Record", fields(1.0, 1.0, false))
+            .add_entry("D", "Record", vec![]);
+        let (old, new) = (old.build(), new.build());
+
+        let patch = diff(&old, &new);
+        let paths: Vec<_> = patch.sections["patches"].hash_items()
+            .map(|(_, name, record)| match record {
+                BinValue::Embed { items, .. } => format!("{}.{:?}", name.unwrap(), items[0].value),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(paths, ["A.String(\"baseHP\")", "A.String(\"skin.scale\")"]);
+        let carried: Vec<_> = patch.sections["entries"].hash_keys().map(|(_, name)| name.unwrap()).collect();
+        assert_eq!(carried, ["B", "D"]);
+
+        let mut patched = old.clone();
+        apply(&mut patched, &patch).unwrap();
+        assert!(crate::compare::compare_bins(&patched, &new, Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_apply_patches() {
+        let mut base = PatchBuilder::new();
+        base.add_entry("Root", "CharacterRecord", vec![
+            Field { key: fnv1a("baseHP"), key_str: None, value: BinValue::F32(500.0) },
+            Field { key: fnv1a("spells"), key_str: None, value: BinValue::List {
+                value_type: BinType::String,
+                items: vec![BinValue::String("Q".to_string()), BinValue::String("W".to_string())],
+            } },
+        ]);
+        let mut base = base.build();
+        base.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+
+        let mut patch = PatchBuilder::new();
+        patch.add_patch("Root.baseHP", BinValue::F32(650.0)).unwrap()
+            .add_patch("Root.spells[1]", BinValue::String("E".to_string())).unwrap()
+            .add_patch("Root.mana", BinValue::F32(300.0)).unwrap()
+            .add_patch("Missing.baseHP", BinValue::F32(1.0)).unwrap()
+            .add_entry("Extra", "CharacterRecord", vec![]);
+        let report = apply(&mut base, &patch.build()).unwrap();
+        assert_eq!((report.entries, report.applied), (1, 3));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "Missing.baseHP");
+
+        let root = base.entry("Root").unwrap();
+        let BinValue::Embed { items, .. } = root else { panic!() };
+        assert_eq!(items[0].value, BinValue::F32(650.0));
+        assert!(matches!(&items[1].value, BinValue::List { items, .. } if items[1] == BinValue::String("E".to_string())));
+        assert_eq!(items[2].value, BinValue::F32(300.0));
+        assert!(base.entry("Extra").is_some());
+    }
+}
@@ -0,0 +1,152 @@
+//! Everything about one champion, gathered from many bins into one.
+//!
+//! A champion's data is spread over the bins under `data/characters/<name>/`
+//! (character record, spells, one bin per skin) and over entries in other
+//! files that those link to, such as shared VFX. [`champion_bundle`] starts
+//! from the champion's own files and follows links through the entry tables
+//! of every given file, decoding only the entries it reaches.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::binary::LazyBin;
+//! use ritobin_rust::bundle::champion_bundle;
+//!
+//! let mut data = Vec::new();
+//! for entry in walkdir::WalkDir::new("game/data") {
+//!     let path = entry?.into_path();
+//!     if path.extension().is_some_and(|e| e == "bin") {
+//!         data.push((path.clone(), std::fs::read(&path)?));
+//!     }
+//! }
+//! let files = data.iter()
+//!     .map(|(path, data)| Ok((path.clone(), LazyBin::parse(data)?)))
+//!     .collect::<Result<Vec<_>, ritobin_rust::binary::BinError>>()?;
+//! let bundle = champion_bundle(&files, "Ahri")?;
+//! std::fs::write("Ahri.py", ritobin_rust::text::write_text(&bundle.bin)?)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::binary::{BinError, EntryHeader, LazyBin};
+use crate::model::{visit_links, Bin, BinType, BinValue};
+
+/// The entries related to one champion, see [`champion_bundle`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    /// A version 3 PROP bin holding the entries, the champion's own first
+    pub bin: Bin,
+    /// The files entries were taken from, in the order first used
+    pub sources: Vec<PathBuf>,
+}
+
+/// Whether `path` lies under a `characters/<champion>` directory, ignoring case
+pub fn is_champion_file(path: &Path, champion: &str) -> bool {
+    let parts: Vec<String> = path.iter().map(|p| p.to_string_lossy().to_lowercase()).collect();
+    parts.windows(2).any(|w| w[0] == "characters" && w[1] == champion.to_lowercase())
+}
+
+/// Gather every entry of `files` related to `champion`.
+///
+/// The seeds are all entries of files under `characters/<champion>` (see
+/// [`is_champion_file`]) and the `Characters/<champion>/CharacterRecords/Root`
+/// entry wherever it is; from there every link to an entry of `files` is
+/// followed. When several files hold the same entry, the first one wins.
+pub fn champion_bundle(files: &[(PathBuf, LazyBin)], champion: &str) -> Result<Bundle, BinError> {
+    let mut index: HashMap<u32, (usize, &EntryHeader)> = HashMap::new();
+    let mut seeds = Vec::new();
+    for (i, (path, bin)) in files.iter().enumerate() {
+        let own = is_champion_file(path, champion);
+        for entry in bin.entries() {
+            index.entry(entry.hash).or_insert((i, entry));
+            if own {
+                seeds.push(entry.hash);
+            }
+        }
+    }
+    seeds.push(crate::hash::fnv1a(&format!("Characters/{}/CharacterRecords/Root", champion)));
+
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<u32> = seeds.into_iter().collect();
+    let mut items = Vec::new();
+    let mut used = Vec::new();
+    while let Some(hash) = queue.pop_front() {
+        if !seen.insert(hash) {
+            continue;
+        }
+        let Some(&(file, entry)) = index.get(&hash) else { continue };
+        if !used.contains(&file) {
+            used.push(file);
+        }
+        let (key, value) = files[file].1.decode(entry)?;
+        visit_links(&value, &mut |target| {
+            if index.contains_key(&target) && !seen.contains(&target) {
+                queue.push_back(target);
+            }
+        });
+        items.push((key, value));
+    }
+
+    let mut bin = Bin::new();
+    bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+    bin.sections.insert("version".to_string(), BinValue::U32(3));
+    bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+    bin.sections.insert("entries".to_string(), BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items });
+    Ok(Bundle { bin, sources: used.into_iter().map(|i| files[i].0.clone()).collect() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write_bin;
+    use crate::hash::fnv1a;
+    use crate::model::{key_hash, Field};
+
+    fn bin_with(entries: Vec<(&str, Vec<&str>)>) -> Vec<u8> {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: entries.into_iter().map(|(name, links)| (
+                BinValue::Hash { value: fnv1a(name), name: None },
+                BinValue::Embed { name: 1, name_str: None, items: links.into_iter().enumerate().map(|(i, link)| Field {
+                    key: i as u32,
+                    key_str: None,
+                    value: BinValue::Link { value: fnv1a(link), name: None },
+                }).collect() },
+            )).collect(),
+        });
+        write_bin(&bin).unwrap()
+    }
+
+    #[test]
+    fn test_champion_bundle() {
+        let data = [
+            (PathBuf::from("game/data/shared/vfx.bin"), bin_with(vec![
+                ("Shared/Orb", vec!["Shared/Glow"]),
+                ("Shared/Glow", vec!["Characters/Ahri/CharacterRecords/Root"]),
+                ("Shared/Unrelated", vec![]),
+            ])),
+            (PathBuf::from("game/DATA/Characters/Ahri/Skins/Skin0.bin"), bin_with(vec![
+                ("Characters/Ahri/Skins/Skin0", vec!["Shared/Orb", "Missing/Entry"]),
+            ])),
+            (PathBuf::from("game/data/characters/annie/annie.bin"), bin_with(vec![
+                ("Characters/Ahri/CharacterRecords/Root", vec![]),
+                ("Characters/Annie/CharacterRecords/Root", vec![]),
+            ])),
+        ];
+        let files: Vec<_> = data.iter().map(|(path, data)| (path.clone(), LazyBin::parse(data).unwrap())).collect();
+        assert!(is_champion_file(&files[1].0, "ahri"));
+        assert!(!is_champion_file(&files[2].0, "Ahri"));
+
+        let bundle = champion_bundle(&files, "Ahri").unwrap();
+        let Some(BinValue::Map { items, .. }) = bundle.bin.sections.get("entries") else { panic!() };
+        let hashes: Vec<u32> = items.iter().filter_map(|(k, _)| key_hash(k)).collect();
+        assert_eq!(hashes, ["Characters/Ahri/Skins/Skin0", "Characters/Ahri/CharacterRecords/Root", "Shared/Orb", "Shared/Glow"].map(fnv1a));
+        assert_eq!(bundle.sources, [&files[1].0, &files[2].0, &files[0].0].map(PathBuf::clone));
+    }
+}
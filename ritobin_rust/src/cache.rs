@@ -0,0 +1,161 @@
+//! Parse results cached by file content.
+//!
+//! Front-ends that re-convert files as they change (watching a mod folder,
+//! serving conversions to an editor) see the same bytes again and again, as
+//! editors autosave files that did not change. A [`ParseCache`] keys parsed
+//! bins by a hash of the content, so unchanged files are not parsed twice.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::cache::ParseCache;
+//!
+//! let mut cache = ParseCache::new(64);
+//! let data = std::fs::read("champion.bin")?;
+//! let bin = cache.get_or_read(&data, ritobin_rust::binary::read_bin)?;
+//! // Served from memory until the file's bytes change
+//! let again = cache.get_or_read(&data, ritobin_rust::binary::read_bin)?;
+//! assert!(std::sync::Arc::ptr_eq(&bin, &again));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+
+use crate::model::Bin;
+
+/// The most recently used parse results, keyed by content.
+///
+/// Content is looked up by its length and 64-bit XXH64 hash, and a copy of
+/// the bytes is kept with each bin to compare on a hit, so input crafted to
+/// collide is parsed rather than answered with another file's bin. Failed
+/// parses are not cached. Use one cache per way of reading (format and options), as
+/// the key is the content alone.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    capacity: usize,
+    /// Least recently used first
+    entries: IndexMap<(u64, usize), Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    content: Box<[u8]>,
+    bin: Arc<Bin>,
+}
+
+impl ParseCache {
+    /// A cache holding up to `capacity` bins; 0 disables caching
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: IndexMap::new() }
+    }
+
+    /// The bin `read` parses from `data`, calling it only if these bytes are not cached
+    pub fn get_or_read<E>(
+        &mut self,
+        data: &[u8],
+        read: impl FnOnce(&[u8]) -> Result<Bin, E>,
+    ) -> Result<Arc<Bin>, E> {
+        let key = (crate::hash::xxh64_exact(data, 0), data.len());
+        if let Some(entry) = self.entries.shift_remove(&key) {
+            if *entry.content == *data {
+                let bin = Arc::clone(&entry.bin);
+                self.entries.insert(key, entry);
+                return Ok(bin);
+            }
+        }
+        let bin = Arc::new(read(data)?);
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.entries.shift_remove_index(0);
+            }
+            self.entries.insert(key, Entry { content: data.into(), bin: Arc::clone(&bin) });
+        }
+        Ok(bin)
+    }
+
+    /// Change how many bins are kept, dropping the least recently used ones
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of bins held
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BinValue;
+
+    #[test]
+    fn test_parse_cache() {
+        let reads = std::cell::Cell::new(0);
+        let read = |data: &[u8]| -> Result<Bin, String> {
+            reads.set(reads.get() + 1);
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String(String::from_utf8_lossy(data).into_owned()));
+            Ok(bin)
+        };
+        let mut cache = ParseCache::new(2);
+        let a = cache.get_or_read(b"a", read).unwrap();
+        assert!(Arc::ptr_eq(&a, &cache.get_or_read(b"a", read).unwrap()));
+        cache.get_or_read(b"b", read).unwrap();
+        cache.get_or_read(b"a", read).unwrap();
+        // "b" is now the least recently used and makes room for "c"
+        cache.get_or_read(b"c", read).unwrap();
+        cache.get_or_read(b"a", read).unwrap();
+        assert_eq!(reads.get(), 3);
+        cache.get_or_read(b"b", read).unwrap();
+        assert_eq!(reads.get(), 4);
+
+        assert!(cache.get_or_read(b"x", |_| Err("bad")).is_err());
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        cache.get_or_read(b"b", read).unwrap();
+        assert_eq!(reads.get(), 4);
+        cache.set_capacity(0);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_parse_cache_collision() {
+        let read = |data: &[u8]| -> Result<Bin, String> {
+            let mut bin = Bin::new();
+            bin.sections.insert("type".to_string(), BinValue::String(String::from_utf8_lossy(data).into_owned()));
+            Ok(bin)
+        };
+        let mut cache = ParseCache::new(2);
+        let other = cache.get_or_read(b"b", read).unwrap();
+        // Pretend "a" hashes like "b": the stored bytes differ, so it is parsed
+        let (_, entry) = cache.entries.shift_remove_index(0).unwrap();
+        cache.entries.insert((crate::hash::xxh64_exact(b"a", 0), 1), entry);
+        let a = cache.get_or_read(b"a", read).unwrap();
+        assert!(!Arc::ptr_eq(&a, &other));
+        assert_eq!(a.sections["type"], BinValue::String("a".to_string()));
+        assert!(Arc::ptr_eq(&a, &cache.get_or_read(b"a", read).unwrap()));
+        assert_eq!(cache.len(), 1);
+
+        // Content differing only in case is not folded together like names
+        cache.get_or_read(b"A", read).unwrap();
+        assert!(Arc::ptr_eq(&a, &cache.get_or_read(b"a", read).unwrap()));
+        assert_eq!(cache.len(), 2);
+    }
+}
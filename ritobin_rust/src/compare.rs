@@ -0,0 +1,307 @@
+//! Semantic comparison of two bins.
+//!
+//! Values are compared by what gets written to a `.bin`: hashes are compared
+//! by value (resolved names are ignored), fields and map items are matched by
+//! key rather than position, and floats may differ within a tolerance. This is
+//! what is needed to check this crate's output against the reference C++
+//! ritobin, whose text output differs in formatting but not in meaning.
+
+use crate::binary::get_value_type;
+use crate::model::{Bin, BinValue, Field};
+use std::collections::HashMap;
+
+/// One difference found by [`compare_bins`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Location of the difference, e.g. `entries[0x1234abcd].0x2c3a9e81[3]`
+    pub path: String,
+    pub message: String,
+}
+
+/// Options for [`compare_bins`].
+#[derive(Debug, Clone, Copy)]
+pub struct CompareOptions {
+    /// Maximum relative difference for floats to still be considered equal
+    pub float_tolerance: f32,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self { float_tolerance: 1e-6 }
+    }
+}
+
+/// List every semantic difference between `left` and `right`.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::compare::{compare_bins, CompareOptions};
+/// use ritobin_rust::model::{Bin, BinValue};
+///
+/// let mut a = Bin::new();
+/// a.sections.insert("version".to_string(), BinValue::U32(3));
+/// let mut b = a.clone();
+/// assert!(compare_bins(&a, &b, CompareOptions::default()).is_empty());
+///
+/// b.sections.insert("version".to_string(), BinValue::U32(2));
+/// let diffs = compare_bins(&a, &b, CompareOptions::default());
+/// assert_eq!(diffs[0].path, "version");
+/// ```
+pub fn compare_bins(left: &Bin, right: &Bin, opts: CompareOptions) -> Vec<Divergence> {
+    let mut cmp = Comparer { opts, out: Vec::new() };
+    for (key, l) in &left.sections {
+        match right.sections.get(key) {
+            Some(r) => cmp.value(key, l, r),
+            None => cmp.report(key, "section missing on the right"),
+        }
+    }
+    for key in right.sections.keys() {
+        if !left.sections.contains_key(key) {
+            cmp.report(key, "section missing on the left");
+        }
+    }
+    cmp.out
+}
+
+struct Comparer {
+    opts: CompareOptions,
+    out: Vec<Divergence>,
+}
+
+impl Comparer {
+    fn report(&mut self, path: &str, message: impl Into<String>) {
+        self.out.push(Divergence { path: path.to_string(), message: message.into() });
+    }
+
+    fn floats(&mut self, path: &str, l: &[f32], r: &[f32]) {
+        let tol = self.opts.float_tolerance;
+        let equal = l.iter().zip(r).all(|(a, b)| {
+            a.to_bits() == b.to_bits() || (a - b).abs() <= tol * a.abs().max(b.abs()).max(1.0)
+        });
+        if !equal {
+            match (l, r) {
+                ([a], [b]) => self.report(path, format!("{:?} != {:?}", a, b)),
+                _ => self.report(path, format!("{:?} != {:?}", l, r)),
+            }
+        }
+    }
+
+    fn value(&mut self, path: &str, l: &BinValue, r: &BinValue) {
+        let (lt, rt) = (get_value_type(l), get_value_type(r));
+        if lt != rt {
+            self.report(path, format!("type {} != {}", lt.name(), rt.name()));
+            return;
+        }
+        match (l, r) {
+            (BinValue::F32(a), BinValue::F32(b)) => self.floats(path, &[*a], &[*b]),
+            (BinValue::Vec2(a), BinValue::Vec2(b)) => self.floats(path, a, b),
+            (BinValue::Vec3(a), BinValue::Vec3(b)) => self.floats(path, a, b),
+            (BinValue::Vec4(a), BinValue::Vec4(b)) => self.floats(path, a, b),
+            (BinValue::Mtx44(a), BinValue::Mtx44(b)) => self.floats(path, a, b),
+            (BinValue::Hash { value: a, .. }, BinValue::Hash { value: b, .. })
+            | (BinValue::Link { value: a, .. }, BinValue::Link { value: b, .. }) => {
+                if a != b {
+                    self.report(path, format!("{:#010x} != {:#010x}", a, b));
+                }
+            }
+            (BinValue::File { value: a, .. }, BinValue::File { value: b, .. }) => {
+                if a != b {
+                    self.report(path, format!("{:#018x} != {:#018x}", a, b));
+                }
+            }
+            (BinValue::List { value_type: lv, items: li }, BinValue::List { value_type: rv, items: ri })
+            | (BinValue::List2 { value_type: lv, items: li }, BinValue::List2 { value_type: rv, items: ri }) => {
+                if lv != rv {
+                    self.report(path, format!("element type {} != {}", lv.name(), rv.name()));
+                    return;
+                }
+                if li.len() != ri.len() {
+                    self.report(path, format!("{} items != {} items", li.len(), ri.len()));
+                }
+                for (i, (a, b)) in li.iter().zip(ri).enumerate() {
+                    self.value(&format!("{}[{}]", path, i), a, b);
+                }
+            }
+            (BinValue::Pointer { name: ln, items: li, .. }, BinValue::Pointer { name: rn, items: ri, .. })
+            | (BinValue::Embed { name: ln, items: li, .. }, BinValue::Embed { name: rn, items: ri, .. }) => {
+                if ln != rn {
+                    self.report(path, format!("class {:#010x} != {:#010x}", ln, rn));
+                    return;
+                }
+                self.fields(path, li, ri);
+            }
+            (BinValue::Option { value_type: lv, item: li }, BinValue::Option { value_type: rv, item: ri }) => {
+                if lv != rv {
+                    self.report(path, format!("element type {} != {}", lv.name(), rv.name()));
+                    return;
+                }
+                match (li, ri) {
+                    (Some(a), Some(b)) => self.value(path, a, b),
+                    (None, None) => {}
+                    (Some(_), None) => self.report(path, "value missing on the right"),
+                    (None, Some(_)) => self.report(path, "value missing on the left"),
+                }
+            }
+            (
+                BinValue::Map { key_type: lk, value_type: lv, items: li },
+                BinValue::Map { key_type: rk, value_type: rv, items: ri },
+            ) => {
+                if lk != rk || lv != rv {
+                    self.report(path, format!(
+                        "map[{},{}] != map[{},{}]", lk.name(), lv.name(), rk.name(), rv.name()
+                    ));
+                    return;
+                }
+                let right = index_by_key(ri);
+                for (k, a) in li {
+                    let label = key_label(k);
+                    let item_path = format!("{}[{}]", path, label);
+                    match find_key(&right, &label, k) {
+                        Some(b) => self.value(&item_path, a, b),
+                        None => self.report(&item_path, "item missing on the right"),
+                    }
+                }
+                let left = index_by_key(li);
+                for (k, _) in ri {
+                    let label = key_label(k);
+                    if find_key(&left, &label, k).is_none() {
+                        self.report(&format!("{}[{}]", path, label), "item missing on the left");
+                    }
+                }
+            }
+            _ => {
+                if l != r {
+                    self.report(path, format!("{:?} != {:?}", l, r));
+                }
+            }
+        }
+    }
+
+    fn fields(&mut self, path: &str, left: &[Field], right: &[Field]) {
+        for l in left {
+            let field_path = format!("{}.{}", path, field_label(l));
+            match right.iter().find(|r| r.key == l.key) {
+                Some(r) => self.value(&field_path, &l.value, &r.value),
+                None => self.report(&field_path, "field missing on the right"),
+            }
+        }
+        for r in right {
+            if !left.iter().any(|l| l.key == r.key) {
+                self.report(&format!("{}.{}", path, field_label(r)), "field missing on the left");
+            }
+        }
+    }
+}
+
+/// Whether [`compare_bins`] finds no difference between two values, with no float tolerance
+pub(crate) fn same_value(a: &BinValue, b: &BinValue) -> bool {
+    let mut cmp = Comparer { opts: CompareOptions { float_tolerance: 0.0 }, out: Vec::new() };
+    cmp.value("", a, b);
+    cmp.out.is_empty()
+}
+
+/// Whether two map keys are the same key, ignoring resolved names
+pub(crate) fn same_key(a: &BinValue, b: &BinValue) -> bool {
+    match (a, b) {
+        (BinValue::Hash { value: x, .. }, BinValue::Hash { value: y, .. })
+        | (BinValue::Link { value: x, .. }, BinValue::Link { value: y, .. }) => x == y,
+        (BinValue::File { value: x, .. }, BinValue::File { value: y, .. }) => x == y,
+        _ => a == b,
+    }
+}
+
+/// Map items grouped by [`key_label`], to find keys without comparing every pair
+type KeyIndex<'a> = HashMap<String, Vec<(&'a BinValue, &'a BinValue)>>;
+
+fn index_by_key(items: &[(BinValue, BinValue)]) -> KeyIndex<'_> {
+    let mut index = KeyIndex::with_capacity(items.len());
+    for (k, v) in items {
+        index.entry(key_label(k)).or_default().push((k, v));
+    }
+    index
+}
+
+/// The value of the first item of `index` under `label` with the same key as `key`
+fn find_key<'a>(index: &KeyIndex<'a>, label: &str, key: &BinValue) -> Option<&'a BinValue> {
+    index.get(label)?.iter().find(|(other, _)| same_key(key, other)).map(|&(_, v)| v)
+}
+
+/// How a map key appears in paths, e.g. `0x1234abcd` or `"name"`
+pub(crate) fn key_label(key: &BinValue) -> String {
+    match key {
+        BinValue::Hash { value, .. } | BinValue::Link { value, .. } => format!("{:#010x}", value),
+        BinValue::File { value, .. } => format!("{:#018x}", value),
+        BinValue::String(s) => format!("{:?}", s),
+        other => format!("{:?}", other),
+    }
+}
+
+pub(crate) fn field_label(field: &Field) -> String {
+    match &field.key_str {
+        Some(name) => name.clone(),
+        None => format!("{:#010x}", field.key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BinType;
+
+    fn entries(items: Vec<(BinValue, BinValue)>) -> Bin {
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items,
+        });
+        bin
+    }
+
+    #[test]
+    fn test_compare_ignores_names_and_order() {
+        let field = |key, key_str: Option<&str>, value| Field { key, key_str: key_str.map(String::from), value };
+        let left = entries(vec![
+            (BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 9, name_str: None, items: vec![
+                field(1, None, BinValue::F32(0.1)),
+                field(2, None, BinValue::Link { value: 5, name: None }),
+            ] }),
+            (BinValue::Hash { value: 2, name: None }, BinValue::Embed { name: 9, name_str: None, items: vec![] }),
+        ]);
+        let right = entries(vec![
+            (BinValue::Hash { value: 2, name: Some("b".to_string()) }, BinValue::Embed { name: 9, name_str: None, items: vec![] }),
+            (BinValue::Hash { value: 1, name: Some("a".to_string()) }, BinValue::Embed { name: 9, name_str: None, items: vec![
+                field(2, Some("link"), BinValue::Link { value: 5, name: Some("x".to_string()) }),
+                field(1, Some("f"), BinValue::F32(0.100_000_01)),
+            ] }),
+        ]);
+        assert_eq!(compare_bins(&left, &right, CompareOptions::default()), vec![]);
+
+        let right = entries(vec![
+            (BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 9, name_str: None, items: vec![
+                field(1, Some("f"), BinValue::F32(0.2)),
+            ] }),
+        ]);
+        let diffs = compare_bins(&left, &right, CompareOptions::default());
+        let paths: Vec<_> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec![
+            "entries[0x00000001].0x00000001",
+            "entries[0x00000001].0x00000002",
+            "entries[0x00000002]",
+        ]);
+    }
+
+    #[test]
+    fn test_compare_large_maps() {
+        let item = |i: u32| (BinValue::Hash { value: i, name: None }, BinValue::Embed { name: 9, name_str: None, items: vec![] });
+        let left = entries((0..50_000).map(item).collect());
+        let right = entries((1..=50_000).rev().map(item).collect());
+        let diffs = compare_bins(&left, &right, CompareOptions::default());
+        let found: Vec<_> = diffs.iter().map(|d| (d.path.as_str(), d.message.as_str())).collect();
+        assert_eq!(found, vec![
+            ("entries[0x00000000]", "item missing on the right"),
+            ("entries[0x0000c350]", "item missing on the left"),
+        ]);
+    }
+}
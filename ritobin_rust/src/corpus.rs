@@ -0,0 +1,307 @@
+//! Generated edge-case bins for conformance testing.
+//!
+//! [`cases`] builds a fixed suite of small bins covering every value type,
+//! deep nesting, empty containers, PTCH files and each header version. The
+//! bins are constructed exactly as [`read_bin`](crate::binary::read_bin)
+//! would return them, so a reader passes a case when reading its bytes gives
+//! back the same [`Bin`]. [`write_corpus`] writes the suite to disk to share
+//! with other tools.
+
+use std::path::{Path, PathBuf};
+
+use crate::binary::{write_bin, BinError, DEFAULT_PTCH_HEADER};
+use crate::hash::fnv1a;
+use crate::model::{Bin, BinType, BinValue, Field};
+
+/// One generated bin
+#[derive(Debug, Clone)]
+pub struct Case {
+    /// File stem used by [`write_corpus`]
+    pub name: &'static str,
+    /// What the case exercises
+    pub description: &'static str,
+    pub bin: Bin,
+}
+
+/// Nesting depth of the `deep_nesting` case
+///
+/// Each level of list nesting is several levels deep in JSON, and JSON readers
+/// commonly stop at 128, so this stays well below the binary reader's limit.
+pub const DEEP_NESTING_DEPTH: usize = 20;
+
+/// Build every case of the corpus
+pub fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "all_types",
+            description: "One field of every value type",
+            bin: prop(3, &[], vec![entry("Corpus/AllTypes", "AllTypes", all_types())]),
+        },
+        Case {
+            name: "extremes",
+            description: "Minimum and maximum integers, special floats and non-ASCII strings",
+            bin: prop(3, &[], vec![entry("Corpus/Extremes", "Extremes", extremes())]),
+        },
+        Case {
+            name: "empty_containers",
+            description: "Empty lists, maps, structures and strings, null pointer, empty option",
+            bin: prop(3, &[], vec![entry("Corpus/Empty", "Empty", empty_containers())]),
+        },
+        Case {
+            name: "deep_nesting",
+            description: "Pointers and lists of embeds nested 20 levels deep",
+            bin: prop(3, &[], vec![entry("Corpus/Deep", "Deep", vec![
+                field("pointers", nested_pointers(DEEP_NESTING_DEPTH)),
+                field("lists", nested_lists(DEEP_NESTING_DEPTH)),
+            ])]),
+        },
+        Case {
+            name: "no_entries",
+            description: "Version 3 header with no entries",
+            bin: prop(3, &[], vec![]),
+        },
+        Case {
+            name: "version1",
+            description: "Version 1 header, which has no linked files",
+            bin: prop(1, &[], vec![entry("Corpus/V1", "Simple", simple())]),
+        },
+        Case {
+            name: "version2",
+            description: "Version 2 header with linked files",
+            bin: prop(2, &["DATA/Shared.bin", "DATA/Other.bin"], vec![entry("Corpus/V2", "Simple", simple())]),
+        },
+        Case {
+            name: "version3",
+            description: "Version 3 header with linked files and several entries",
+            bin: prop(3, &["DATA/Shared.bin"], vec![
+                entry("Corpus/V3/A", "Simple", simple()),
+                entry("Corpus/V3/B", "Simple", simple()),
+                entry("Corpus/V3/C", "Other", vec![]),
+            ]),
+        },
+        Case {
+            name: "ptch",
+            description: "PTCH file with an entry and patches of several types",
+            bin: ptch(),
+        },
+        Case {
+            name: "ptch_empty",
+            description: "PTCH file with no entries and no patches",
+            bin: ptch_with(vec![], vec![]),
+        },
+    ]
+}
+
+/// Write every case as `<name>.bin` into `dir`, creating it if needed
+///
+/// Returns the written paths in [`cases`] order.
+pub fn write_corpus(dir: &Path) -> Result<Vec<PathBuf>, BinError> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for case in cases() {
+        let path = dir.join(format!("{}.bin", case.name));
+        std::fs::write(&path, write_bin(&case.bin)?)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn hash(name: &str) -> BinValue {
+    BinValue::Hash { value: fnv1a(name), name: None }
+}
+
+fn field(name: &str, value: BinValue) -> Field {
+    Field { key: fnv1a(name), key_str: None, value }
+}
+
+fn entry(name: &str, class: &str, fields: Vec<Field>) -> (BinValue, BinValue) {
+    (hash(name), BinValue::Embed { name: fnv1a(class), name_str: None, items: fields })
+}
+
+fn entries(items: Vec<(BinValue, BinValue)>) -> BinValue {
+    BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items }
+}
+
+fn prop(version: u32, linked: &[&str], items: Vec<(BinValue, BinValue)>) -> Bin {
+    let mut bin = Bin::new();
+    bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+    bin.sections.insert("version".to_string(), BinValue::U32(version));
+    if version >= 2 {
+        bin.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: linked.iter().map(|l| BinValue::String(l.to_string())).collect(),
+        });
+    }
+    bin.sections.insert("entries".to_string(), entries(items));
+    bin
+}
+
+fn ptch() -> Bin {
+    let patch = |entry: &str, path: &str, value: BinValue| (hash(entry), BinValue::Embed {
+        name: fnv1a("patch"),
+        name_str: None,
+        items: vec![
+            Field { key: fnv1a("path"), key_str: Some("path".to_string()), value: BinValue::String(path.to_string()) },
+            Field { key: fnv1a("value"), key_str: Some("value".to_string()), value },
+        ],
+    });
+    ptch_with(
+        vec![entry("Corpus/Patch/New", "Simple", simple())],
+        vec![
+            patch("Corpus/V3/A", "count", BinValue::U32(7)),
+            patch("Corpus/V3/A", "inner.scale", BinValue::F32(2.0)),
+            patch("Corpus/V3/B", "names", BinValue::List {
+                value_type: BinType::String,
+                items: vec![BinValue::String("patched".to_string())],
+            }),
+            patch("Corpus/V3/B", "child", BinValue::Pointer { name: fnv1a("Simple"), name_str: None, items: simple() }),
+        ],
+    )
+}
+
+fn ptch_with(items: Vec<(BinValue, BinValue)>, patches: Vec<(BinValue, BinValue)>) -> Bin {
+    let mut bin = Bin::new();
+    bin.sections.insert("type".to_string(), BinValue::String("PTCH".to_string()));
+    bin.sections.insert("ptch_header".to_string(), BinValue::U64(DEFAULT_PTCH_HEADER));
+    bin.sections.insert("version".to_string(), BinValue::U32(3));
+    bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+    bin.sections.insert("entries".to_string(), entries(items));
+    bin.sections.insert("patches".to_string(), BinValue::Map {
+        key_type: BinType::Hash,
+        value_type: BinType::Embed,
+        items: patches,
+    });
+    bin
+}
+
+fn simple() -> Vec<Field> {
+    vec![
+        field("count", BinValue::U32(1)),
+        field("label", BinValue::String("simple".to_string())),
+    ]
+}
+
+fn all_types() -> Vec<Field> {
+    let inner = vec![field("scale", BinValue::F32(0.5))];
+    vec![
+        field("none", BinValue::None),
+        field("bool", BinValue::Bool(true)),
+        field("i8", BinValue::I8(-8)),
+        field("u8", BinValue::U8(8)),
+        field("i16", BinValue::I16(-16)),
+        field("u16", BinValue::U16(16)),
+        field("i32", BinValue::I32(-32)),
+        field("u32", BinValue::U32(32)),
+        field("i64", BinValue::I64(-64)),
+        field("u64", BinValue::U64(64)),
+        field("f32", BinValue::F32(1.25)),
+        field("vec2", BinValue::Vec2([1.0, 2.0])),
+        field("vec3", BinValue::Vec3([1.0, 2.0, 3.0])),
+        field("vec4", BinValue::Vec4([1.0, 2.0, 3.0, 4.0])),
+        field("mtx44", BinValue::Mtx44(std::array::from_fn(|i| i as f32))),
+        field("rgba", BinValue::Rgba([255, 128, 0, 64])),
+        field("string", BinValue::String("text".to_string())),
+        field("hash", hash("Corpus/Hash")),
+        field("file", BinValue::File { value: 0x0123_4567_89ab_cdef, name: None }),
+        field("list", BinValue::List {
+            value_type: BinType::U16,
+            items: vec![BinValue::U16(1), BinValue::U16(2), BinValue::U16(3)],
+        }),
+        field("list2", BinValue::List2 {
+            value_type: BinType::String,
+            items: vec![BinValue::String("a".to_string()), BinValue::String("b".to_string())],
+        }),
+        field("pointer", BinValue::Pointer { name: fnv1a("Inner"), name_str: None, items: inner.clone() }),
+        field("embed", BinValue::Embed { name: fnv1a("Inner"), name_str: None, items: inner.clone() }),
+        field("link", BinValue::Link { value: fnv1a("Corpus/Extremes"), name: None }),
+        field("option", BinValue::Option { value_type: BinType::Vec3, item: Some(Box::new(BinValue::Vec3([0.0, 1.0, 0.0]))) }),
+        field("map", BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![
+                (hash("first"), BinValue::Embed { name: fnv1a("Inner"), name_str: None, items: inner.clone() }),
+                (hash("second"), BinValue::Embed { name: fnv1a("Inner"), name_str: None, items: inner }),
+            ],
+        }),
+        field("flag", BinValue::Flag(true)),
+    ]
+}
+
+fn extremes() -> Vec<Field> {
+    vec![
+        field("i8_min", BinValue::I8(i8::MIN)),
+        field("u8_max", BinValue::U8(u8::MAX)),
+        field("i16_min", BinValue::I16(i16::MIN)),
+        field("u16_max", BinValue::U16(u16::MAX)),
+        field("i32_min", BinValue::I32(i32::MIN)),
+        field("u32_max", BinValue::U32(u32::MAX)),
+        field("i64_min", BinValue::I64(i64::MIN)),
+        field("u64_max", BinValue::U64(u64::MAX)),
+        field("f32_max", BinValue::F32(f32::MAX)),
+        field("f32_min_positive", BinValue::F32(f32::MIN_POSITIVE)),
+        field("f32_negative", BinValue::F32(-1.0e-7)),
+        field("hash_zero", BinValue::Hash { value: 0, name: None }),
+        field("file_max", BinValue::File { value: u64::MAX, name: None }),
+        field("unicode", BinValue::String("Ahri \u{2665} \u{963f}\u{72f8}".to_string())),
+        field("escapes", BinValue::String("quote \" backslash \\ newline \n tab \t".to_string())),
+        field("map_u32_keys", BinValue::Map {
+            key_type: BinType::U32,
+            value_type: BinType::Bool,
+            items: vec![(BinValue::U32(0), BinValue::Bool(false)), (BinValue::U32(u32::MAX), BinValue::Bool(true))],
+        }),
+    ]
+}
+
+fn empty_containers() -> Vec<Field> {
+    vec![
+        field("string", BinValue::String(String::new())),
+        field("list", BinValue::List { value_type: BinType::U32, items: vec![] }),
+        field("list2", BinValue::List2 { value_type: BinType::Embed, items: vec![] }),
+        field("map", BinValue::Map { key_type: BinType::String, value_type: BinType::U8, items: vec![] }),
+        field("pointer", BinValue::Pointer { name: fnv1a("Inner"), name_str: None, items: vec![] }),
+        field("null_pointer", BinValue::Pointer { name: 0, name_str: None, items: vec![] }),
+        field("embed", BinValue::Embed { name: fnv1a("Inner"), name_str: None, items: vec![] }),
+        field("option", BinValue::Option { value_type: BinType::String, item: None }),
+        field("list_of_empty", BinValue::List {
+            value_type: BinType::Embed,
+            items: vec![BinValue::Embed { name: fnv1a("Inner"), name_str: None, items: vec![] }],
+        }),
+    ]
+}
+
+fn nested_pointers(depth: usize) -> BinValue {
+    (0..depth).fold(BinValue::U32(depth as u32), |value, _| BinValue::Pointer {
+        name: fnv1a("Node"),
+        name_str: None,
+        items: vec![field("next", value)],
+    })
+}
+
+fn nested_lists(depth: usize) -> BinValue {
+    (0..depth).fold(BinValue::Option { value_type: BinType::U8, item: Some(Box::new(BinValue::U8(1))) }, |value, _| BinValue::List {
+        value_type: BinType::Embed,
+        items: vec![BinValue::Embed { name: fnv1a("Node"), name_str: None, items: vec![field("next", value)] }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read_bin;
+
+    #[test]
+    fn test_corpus_round_trips() {
+        for case in cases() {
+            let data = write_bin(&case.bin).unwrap();
+            assert_eq!(read_bin(&data).unwrap(), case.bin, "binary: {}", case.name);
+
+            let text = crate::text::write_text(&case.bin).unwrap();
+            let from_text = crate::text::read_text(&text).unwrap();
+            assert_eq!(write_bin(&from_text).unwrap(), data, "text: {}", case.name);
+
+            let json = crate::json::write_json(&case.bin).unwrap();
+            let from_json = crate::json::read_json(&json).unwrap();
+            assert_eq!(write_bin(&from_json).unwrap(), data, "json: {}", case.name);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Locating hash files on disk.
+//!
+//! Directories are searched in this order:
+//! 1. paths added with [`HashDiscovery::add_path`] (e.g. from the command line)
+//! 2. the `RITOBIN_HASH_PATHS` environment variable (a platform path list)
+//! 3. the config file `hash_paths.txt` in the RitoShark config directory,
+//!    one directory per line, `#` starts a comment
+//! 4. the RitoShark data directory (`%APPDATA%/RitoShark/Requirements/Hashes`
+//!    on Windows, `$XDG_DATA_HOME/RitoShark/Requirements/Hashes` elsewhere)
+//! 5. `Hashes` next to the executable, then the executable directory itself
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::discovery::HashDiscovery;
+//! use ritobin_rust::unhash::BinUnhasher;
+//!
+//! let mut unhasher = BinUnhasher::new();
+//! if let Some(dir) = HashDiscovery::new().add_path("tools/hashes").discover() {
+//!     ritobin_rust::discovery::load_hash_dir(&mut unhasher, &dir);
+//! }
+//! ```
+
+use crate::progress::{Event, Progress};
+use crate::unhash::BinUnhasher;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding extra hash directories
+pub const HASH_PATHS_ENV: &str = "RITOBIN_HASH_PATHS";
+
+/// Name of the config file listing extra hash directories
+pub const HASH_PATHS_CONFIG: &str = "hash_paths.txt";
+
+/// Hash files looked for in each directory
+pub const HASH_FILES: [&str; 6] = [
+    "hashes.game.txt",
+    "hashes.binentries.txt",
+    "hashes.binhashes.txt",
+    "hashes.bintypes.txt",
+    "hashes.binfields.txt",
+    "hashes.lcu.txt",
+];
+
+/// Builds the list of directories searched for hash files.
+#[derive(Debug, Clone)]
+pub struct HashDiscovery {
+    paths: Vec<PathBuf>,
+    /// Read [`HASH_PATHS_ENV`]
+    pub use_env: bool,
+    /// Read [`HASH_PATHS_CONFIG`] from [`config_dir`]
+    pub use_config: bool,
+    /// Search the data directory and the executable directory
+    pub use_defaults: bool,
+}
+
+impl Default for HashDiscovery {
+    fn default() -> Self {
+        Self { paths: Vec::new(), use_env: true, use_config: true, use_defaults: true }
+    }
+}
+
+impl HashDiscovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Search `path` before every other location; earlier calls take priority
+    pub fn add_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Every directory to search, in priority order, whether or not it exists
+    pub fn search_paths(&self) -> Vec<PathBuf> {
+        let mut out = self.paths.clone();
+        if self.use_env {
+            if let Some(list) = std::env::var_os(HASH_PATHS_ENV) {
+                out.extend(std::env::split_paths(&list).filter(|p| !p.as_os_str().is_empty()));
+            }
+        }
+        if self.use_config {
+            if let Some(config) = config_dir().map(|d| d.join(HASH_PATHS_CONFIG)) {
+                if let Ok(text) = std::fs::read_to_string(config) {
+                    out.extend(parse_config(&text));
+                }
+            }
+        }
+        if self.use_defaults {
+            if let Some(data) = data_dir() {
+                out.push(data.join("Requirements").join("Hashes"));
+            }
+            if let Some(root) = std::env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf)) {
+                out.push(root.join("Hashes"));
+                out.push(root);
+            }
+        }
+        out
+    }
+
+    /// The first search path containing any of the [`HASH_FILES`]
+    pub fn discover(&self) -> Option<PathBuf> {
+        self.search_paths().into_iter().find(|dir| has_hash_files(dir))
+    }
+}
+
+/// Whether `dir` contains any known hash file, as text or binary
+pub fn has_hash_files(dir: &Path) -> bool {
+    HASH_FILES.iter().any(|f| {
+        let path = dir.join(f);
+        path.exists() || path.with_extension("bin").exists()
+    })
+}
+
+/// Load every known hash file found in `dir`; returns how many were loaded
+///
+/// Files that fail to load are skipped; [`load_hash_dir_with_progress`]
+/// reports them as [`Event::Warning`]s.
+pub fn load_hash_dir(unhasher: &mut BinUnhasher, dir: &Path) -> usize {
+    load_hash_dir_with_progress(unhasher, dir, &mut |_: Event| {})
+}
+
+/// [`load_hash_dir`], reporting each file and load failures to `progress`
+pub fn load_hash_dir_with_progress(unhasher: &mut BinUnhasher, dir: &Path, progress: &mut dyn Progress) -> usize {
+    let mut loaded = 0;
+    for file in HASH_FILES {
+        let path = dir.join(file);
+        if !(path.exists() || path.with_extension("bin").exists()) {
+            continue;
+        }
+        progress.event(Event::FileStarted { path: path.clone() });
+        // Uses the binary file when present, the text file otherwise
+        match unhasher.load_auto(&path) {
+            Ok(()) => loaded += 1,
+            Err(e) => progress.event(Event::Warning {
+                message: format!("Failed to load {}: {}", path.display(), e),
+                path: Some(path.clone()),
+            }),
+        }
+        progress.event(Event::FileFinished { path });
+    }
+    loaded
+}
+
+/// RitoShark's per-user config directory
+///
+/// `%APPDATA%/RitoShark` on Windows, `$XDG_CONFIG_HOME/RitoShark` (default
+/// `~/.config/RitoShark`) elsewhere.
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(|d| PathBuf::from(d).join("RitoShark"));
+    }
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|d| d.join("RitoShark"))
+}
+
+/// RitoShark's per-user data directory
+///
+/// `%APPDATA%/RitoShark` on Windows, `$XDG_DATA_HOME/RitoShark` (default
+/// `~/.local/share/RitoShark`) elsewhere.
+pub fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(|d| PathBuf::from(d).join("RitoShark"));
+    }
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|d| d.join("RitoShark"))
+}
+
+fn xdg_dir(var: &str, home_default: &str) -> Option<PathBuf> {
+    // The XDG spec says relative values are to be ignored
+    match std::env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(home_default)),
+    }
+}
+
+fn parse_config(text: &str) -> impl Iterator<Item = PathBuf> + '_ {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_explicit_paths_in_order() {
+        let root = std::env::temp_dir().join(format!("ritobin_discovery_{}", std::process::id()));
+        let (empty, with_hashes) = (root.join("empty"), root.join("hashes"));
+        std::fs::create_dir_all(&empty).unwrap();
+        std::fs::create_dir_all(&with_hashes).unwrap();
+        std::fs::write(with_hashes.join("hashes.binfields.bin"), b"").unwrap();
+
+        let mut discovery = HashDiscovery { use_env: false, use_config: false, use_defaults: false, ..Default::default() };
+        discovery.add_path(&empty).add_path(&with_hashes);
+        assert_eq!(discovery.search_paths(), vec![empty.clone(), with_hashes.clone()]);
+        assert_eq!(discovery.discover(), Some(with_hashes));
+
+        let parsed: Vec<_> = parse_config("# hashes\n/a/b  \n\n  /c # old\n").collect();
+        assert_eq!(parsed, vec![PathBuf::from("/a/b"), PathBuf::from("/c")]);
+        std::fs::remove_dir_all(root).ok();
+    }
+}
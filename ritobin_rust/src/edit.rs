@@ -0,0 +1,535 @@
+//! Addressing and editing values by path, with an undoable edit journal.
+//!
+//! A [`ValuePath`] names one value inside a [`Bin`] using the same notation as
+//! [`compare`](crate::compare) and read errors: a section name followed by
+//! `.field` and `[item]` steps, e.g. `entries[0x1234abcd].mSpell.mDataValues[3]`.
+//!
+//! - Fields are given by name (hashed with FNV1a) or as `0x` hex hashes.
+//! - List and option items are given by index.
+//! - Map items are given by key: a name or `0x` hex hash for hash keys, a
+//!   number for integer keys, a `"quoted"` string for string keys.
+//!
+//! [`set`] and [`remove`] edit a bin directly. [`EditJournal`] performs the
+//! same edits while recording them, so they can be undone, redone, saved and
+//! replayed onto another bin.
+
+use crate::compare::same_key;
+use crate::hash::{fnv1a, Xxh64};
+use crate::model::{Bin, BinType, BinValue, Field};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// One step of a [`ValuePath`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// A top-level section
+    Section(String),
+    /// A field of a pointer or embed, by name or `0x` hash
+    Field(String),
+    /// A list, option or map item, by index or key
+    Item(String),
+}
+
+/// The location of a value inside a [`Bin`].
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::edit::ValuePath;
+///
+/// let path: ValuePath = "entries[Characters/Ahri/CharacterRecords/Root].baseHP".parse()?;
+/// assert_eq!(path.to_string(), "entries[Characters/Ahri/CharacterRecords/Root].baseHP");
+/// assert_eq!(path.segments().len(), 3);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ValuePath {
+    segments: Vec<Segment>,
+}
+
+impl ValuePath {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The value at this path, if present
+    pub fn get<'a>(&self, bin: &'a Bin) -> Option<&'a BinValue> {
+        let (first, rest) = self.split_section();
+        let mut value = bin.sections.get(first)?;
+        for seg in rest {
+            let i = locate(value, seg)?;
+            value = child(value, i);
+        }
+        Some(value)
+    }
+
+    /// The value at this path, if present, for in-place changes
+    ///
+    /// Edits made through this reference bypass any [`EditJournal`].
+    pub fn get_mut<'a>(&self, bin: &'a mut Bin) -> Option<&'a mut BinValue> {
+        let (first, rest) = self.split_section();
+        let mut value = bin.sections.get_mut(first)?;
+        for seg in rest {
+            let i = locate(value, seg)?;
+            value = child_mut(value, i);
+        }
+        Some(value)
+    }
+
+    fn split_section(&self) -> (&str, &[Segment]) {
+        match self.segments.split_first() {
+            Some((Segment::Section(name), rest)) => (name, rest),
+            _ => unreachable!("paths always start with a section"),
+        }
+    }
+
+    /// The path of the containing value and the last step, or `None` for a section
+    fn split_last(&self) -> Option<(ValuePath, &Segment)> {
+        if self.segments.len() < 2 {
+            return None;
+        }
+        let (last, parent) = self.segments.split_last()?;
+        Some((ValuePath { segments: parent.to_vec() }, last))
+    }
+}
+
+impl FromStr for ValuePath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let end = s.find(['.', '[']).unwrap_or(s.len());
+        if end == 0 {
+            return Err(format!("Path '{}' must start with a section name", s));
+        }
+        let mut segments = vec![Segment::Section(s[..end].to_string())];
+        let mut rest = &s[end..];
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let end = tail.find(['.', '[']).unwrap_or(tail.len());
+                if end == 0 {
+                    return Err(format!("Empty field name in path '{}'", s));
+                }
+                segments.push(Segment::Field(tail[..end].to_string()));
+                rest = &tail[end..];
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                // Quoted keys may contain `]`
+                let close = match tail.strip_prefix('"') {
+                    Some(quoted) => quoted.find('"').map(|q| q + 2),
+                    None => Some(0),
+                }
+                .and_then(|from| tail[from..].find(']').map(|i| i + from))
+                .ok_or_else(|| format!("Unclosed '[' in path '{}'", s))?;
+                if close == 0 {
+                    return Err(format!("Empty item in path '{}'", s));
+                }
+                segments.push(Segment::Item(tail[..close].to_string()));
+                rest = &tail[close + 1..];
+            } else {
+                return Err(format!("Expected '.' or '[' in path '{}' at '{}'", s, rest));
+            }
+        }
+        Ok(ValuePath { segments })
+    }
+}
+
+impl TryFrom<String> for ValuePath {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<ValuePath> for String {
+    fn from(path: ValuePath) -> String {
+        path.to_string()
+    }
+}
+
+impl fmt::Display for ValuePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for seg in &self.segments {
+            match seg {
+                Segment::Section(name) => write!(f, "{}", name)?,
+                Segment::Field(name) => write!(f, ".{}", name)?,
+                Segment::Item(label) => write!(f, "[{}]", label)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Set the value at `path`, adding it if the last step doesn't exist yet.
+///
+/// Missing sections, fields, map items and options are added; a list item can
+/// be added at index `len`. Returns the previous value, if any.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::edit::{set, remove};
+/// use ritobin_rust::model::{Bin, BinValue};
+///
+/// let mut bin = Bin::new();
+/// assert_eq!(set(&mut bin, &"version".parse()?, BinValue::U32(3))?, None);
+/// assert_eq!(set(&mut bin, &"version".parse()?, BinValue::U32(2))?, Some(BinValue::U32(3)));
+/// assert_eq!(remove(&mut bin, &"version".parse()?)?, BinValue::U32(2));
+/// # Ok::<(), String>(())
+/// ```
+pub fn set(bin: &mut Bin, path: &ValuePath, value: BinValue) -> Result<Option<BinValue>, String> {
+    if let Some(slot) = path.get_mut(bin) {
+        return Ok(Some(std::mem::replace(slot, value)));
+    }
+    insert(bin, path, value, None)?;
+    Ok(None)
+}
+
+/// Remove the value at `path` from its section, struct, list, map or option.
+pub fn remove(bin: &mut Bin, path: &ValuePath) -> Result<BinValue, String> {
+    remove_at(bin, path).map(|(_, value)| value)
+}
+
+/// Insert a value that isn't present; `position` restores a removed item's place
+///
+/// List items shift the following items up, everything else must not exist yet.
+fn insert(bin: &mut Bin, path: &ValuePath, value: BinValue, position: Option<usize>) -> Result<usize, String> {
+    let is_list = |v: &BinValue| matches!(v, BinValue::List { .. } | BinValue::List2 { .. });
+    let parent_is_list = path.split_last().and_then(|(p, _)| p.get(bin)).is_some_and(is_list);
+    if !parent_is_list && path.get(bin).is_some() {
+        return Err(format!("Path '{}' already exists", path));
+    }
+    let Some((parent_path, last)) = path.split_last() else {
+        let (name, _) = path.split_section();
+        let at = position.unwrap_or(bin.sections.len()).min(bin.sections.len());
+        bin.sections.shift_insert(at, name.to_string(), value);
+        return Ok(at);
+    };
+    let parent = parent_path.get_mut(bin).ok_or_else(|| format!("Path '{}' does not exist", parent_path))?;
+    let not_here = || format!("Can't add '{}' to the value at '{}'", path, parent_path);
+    match (parent, last) {
+        (BinValue::Pointer { name, items, .. }, Segment::Field(field)) if *name != 0 => {
+            insert_field(items, field, value, position)
+        }
+        (BinValue::Embed { items, .. }, Segment::Field(field)) => insert_field(items, field, value, position),
+        (BinValue::List { items, .. } | BinValue::List2 { items, .. }, Segment::Item(label)) => {
+            let at: usize = label.parse().map_err(|_| not_here())?;
+            if at > items.len() {
+                return Err(format!("Index {} is past the end of '{}' ({} items)", at, parent_path, items.len()));
+            }
+            items.insert(at, value);
+            Ok(at)
+        }
+        (BinValue::Option { item: item @ None, .. }, Segment::Item(label)) if label == "0" => {
+            *item = Some(Box::new(value));
+            Ok(0)
+        }
+        (BinValue::Map { key_type, items, .. }, Segment::Item(label)) => {
+            let key = key_from_label(label, *key_type)?;
+            let at = position.unwrap_or(items.len()).min(items.len());
+            items.insert(at, (key, value));
+            Ok(at)
+        }
+        _ => Err(not_here()),
+    }
+}
+
+fn insert_field(items: &mut Vec<Field>, name: &str, value: BinValue, position: Option<usize>) -> Result<usize, String> {
+    let (key, key_str) = match parse_hex(name) {
+        Some(hash) => (u32::try_from(hash).map_err(|_| format!("Field hash '{}' is too large", name))?, None),
+        None => (fnv1a(name), Some(name.to_string())),
+    };
+    let at = position.unwrap_or(items.len()).min(items.len());
+    items.insert(at, Field { key, key_str, value });
+    Ok(at)
+}
+
+/// Remove a value, returning where it was within its container
+fn remove_at(bin: &mut Bin, path: &ValuePath) -> Result<(usize, BinValue), String> {
+    let missing = || format!("Path '{}' does not exist", path);
+    let Some((parent_path, last)) = path.split_last() else {
+        let (name, _) = path.split_section();
+        return bin.sections.shift_remove_full(name).map(|(i, _, v)| (i, v)).ok_or_else(missing);
+    };
+    let parent = parent_path.get_mut(bin).ok_or_else(missing)?;
+    let i = locate(parent, last).ok_or_else(missing)?;
+    let value = match parent {
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => items.remove(i).value,
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => items.remove(i),
+        BinValue::Map { items, .. } => items.remove(i).1,
+        BinValue::Option { item, .. } => *item.take().ok_or_else(missing)?,
+        _ => return Err(missing()),
+    };
+    Ok((i, value))
+}
+
+/// Index of the child named by `seg` within `value`'s items
+fn locate(value: &BinValue, seg: &Segment) -> Option<usize> {
+    match (value, seg) {
+        (BinValue::Pointer { items, .. } | BinValue::Embed { items, .. }, Segment::Field(name)) => {
+            let key = match parse_hex(name) {
+                Some(hash) => u32::try_from(hash).ok()?,
+                None => fnv1a(name),
+            };
+            items.iter().position(|f| f.key == key)
+        }
+        (BinValue::List { items, .. } | BinValue::List2 { items, .. }, Segment::Item(label)) => {
+            label.parse().ok().filter(|i| *i < items.len())
+        }
+        (BinValue::Option { item: Some(_), .. }, Segment::Item(label)) if label == "0" => Some(0),
+        (BinValue::Map { key_type, items, .. }, Segment::Item(label)) => {
+            let key = key_from_label(label, *key_type).ok()?;
+            items.iter().position(|(k, _)| same_key(k, &key))
+        }
+        _ => None,
+    }
+}
+
+fn child(value: &BinValue, i: usize) -> &BinValue {
+    match value {
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => &items[i].value,
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => &items[i],
+        BinValue::Map { items, .. } => &items[i].1,
+        BinValue::Option { item: Some(item), .. } => item,
+        _ => unreachable!("located a child of a value without children"),
+    }
+}
+
+fn child_mut(value: &mut BinValue, i: usize) -> &mut BinValue {
+    match value {
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => &mut items[i].value,
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => &mut items[i],
+        BinValue::Map { items, .. } => &mut items[i].1,
+        BinValue::Option { item: Some(item), .. } => item,
+        _ => unreachable!("located a child of a value without children"),
+    }
+}
+
+pub(crate) fn parse_hex(s: &str) -> Option<u64> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Build a map key of type `key_type` from its path label
+fn key_from_label(label: &str, key_type: BinType) -> Result<BinValue, String> {
+    let bad = || format!("'{}' is not a valid {} key", label, key_type.name());
+    let int = || label.parse::<i128>().map_err(|_| bad());
+    let quoted = label.len() >= 2 && label.starts_with('"') && label.ends_with('"');
+    Ok(match key_type {
+        BinType::String if quoted => BinValue::String(label[1..label.len() - 1].to_string()),
+        BinType::Hash | BinType::Link => {
+            let (value, name) = match parse_hex(label) {
+                Some(hash) => (u32::try_from(hash).map_err(|_| bad())?, None),
+                None => (fnv1a(label), Some(label.to_string())),
+            };
+            if key_type == BinType::Hash {
+                BinValue::Hash { value, name }
+            } else {
+                BinValue::Link { value, name }
+            }
+        }
+        BinType::File => match parse_hex(label) {
+            Some(value) => BinValue::File { value, name: None },
+            None => BinValue::File { value: Xxh64::new(label).0, name: Some(label.to_string()) },
+        },
+        BinType::I8 => BinValue::I8(int()?.try_into().map_err(|_| bad())?),
+        BinType::U8 => BinValue::U8(int()?.try_into().map_err(|_| bad())?),
+        BinType::I16 => BinValue::I16(int()?.try_into().map_err(|_| bad())?),
+        BinType::U16 => BinValue::U16(int()?.try_into().map_err(|_| bad())?),
+        BinType::I32 => BinValue::I32(int()?.try_into().map_err(|_| bad())?),
+        BinType::U32 => BinValue::U32(int()?.try_into().map_err(|_| bad())?),
+        BinType::I64 => BinValue::I64(int()?.try_into().map_err(|_| bad())?),
+        BinType::U64 => BinValue::U64(int()?.try_into().map_err(|_| bad())?),
+        _ => return Err(bad()),
+    })
+}
+
+/// One change made through an [`EditJournal`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditRecord {
+    pub path: ValuePath,
+    /// Value before the edit; `None` if it was added
+    pub old: Option<BinValue>,
+    /// Value after the edit; `None` if it was removed
+    pub new: Option<BinValue>,
+    /// Position within the containing section, struct, list or map
+    pub position: usize,
+}
+
+/// Records edits so they can be undone, redone, saved and replayed.
+///
+/// Undo and redo assume the bin is only changed through the journal in
+/// between; an edit that no longer applies returns an error and leaves the
+/// journal unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::edit::EditJournal;
+/// use ritobin_rust::model::{Bin, BinValue};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("version".to_string(), BinValue::U32(3));
+///
+/// let mut journal = EditJournal::new();
+/// journal.set(&mut bin, &"version".parse()?, BinValue::U32(2))?;
+/// journal.undo(&mut bin)?;
+/// assert_eq!(bin.sections["version"], BinValue::U32(3));
+///
+/// journal.redo(&mut bin)?;
+/// assert_eq!(bin.sections["version"], BinValue::U32(2));
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditJournal {
+    done: Vec<EditRecord>,
+    undone: Vec<EditRecord>,
+}
+
+impl EditJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// [`set`] and record the change
+    pub fn set(&mut self, bin: &mut Bin, path: &ValuePath, value: BinValue) -> Result<(), String> {
+        let record = match path.get_mut(bin) {
+            Some(slot) => {
+                let old = std::mem::replace(slot, value.clone());
+                EditRecord { path: path.clone(), old: Some(old), new: Some(value), position: 0 }
+            }
+            None => {
+                let position = insert(bin, path, value.clone(), None)?;
+                EditRecord { path: path.clone(), old: None, new: Some(value), position }
+            }
+        };
+        self.push(record);
+        Ok(())
+    }
+
+    /// [`remove`] and record the change
+    pub fn remove(&mut self, bin: &mut Bin, path: &ValuePath) -> Result<BinValue, String> {
+        let (position, old) = remove_at(bin, path)?;
+        self.push(EditRecord { path: path.clone(), old: Some(old.clone()), new: None, position });
+        Ok(old)
+    }
+
+    fn push(&mut self, record: EditRecord) {
+        self.done.push(record);
+        self.undone.clear();
+    }
+
+    /// Revert the most recent edit; returns false if there is nothing to undo
+    pub fn undo(&mut self, bin: &mut Bin) -> Result<bool, String> {
+        let Some(record) = self.done.last() else { return Ok(false) };
+        apply(bin, &record.path, record.new.is_some(), record.old.clone(), record.position)?;
+        self.undone.extend(self.done.pop());
+        Ok(true)
+    }
+
+    /// Re-apply the most recently undone edit; returns false if there is nothing to redo
+    pub fn redo(&mut self, bin: &mut Bin) -> Result<bool, String> {
+        let Some(record) = self.undone.last() else { return Ok(false) };
+        apply(bin, &record.path, record.old.is_some(), record.new.clone(), record.position)?;
+        self.done.extend(self.undone.pop());
+        Ok(true)
+    }
+
+    /// Apply every recorded (not undone) edit to another bin, in order
+    pub fn replay(&self, bin: &mut Bin) -> Result<(), String> {
+        for record in &self.done {
+            apply(bin, &record.path, record.old.is_some(), record.new.clone(), record.position)?;
+        }
+        Ok(())
+    }
+
+    /// Edits in the order they were made, excluding undone ones
+    pub fn records(&self) -> &[EditRecord] {
+        &self.done
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Forget all history
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    /// The journal as JSON, for change logs or resuming a session
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        serde_json::from_str(data).map_err(|e| e.to_string())
+    }
+}
+
+/// Move `path` from present/absent (`exists`) to `target`
+fn apply(bin: &mut Bin, path: &ValuePath, exists: bool, target: Option<BinValue>, position: usize) -> Result<(), String> {
+    match (exists, target) {
+        (true, Some(value)) => {
+            let slot = path.get_mut(bin).ok_or_else(|| format!("Path '{}' does not exist", path))?;
+            *slot = value;
+        }
+        (true, None) => {
+            remove_at(bin, path)?;
+        }
+        (false, Some(value)) => {
+            insert(bin, path, value, Some(position))?;
+        }
+        (false, None) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_undo_redo_replay() {
+        let entry = BinValue::Embed { name: 1, name_str: None, items: vec![
+            Field { key: fnv1a("a"), key_str: None, value: BinValue::U32(1) },
+            Field { key: fnv1a("b"), key_str: None, value: BinValue::List {
+                value_type: BinType::U8,
+                items: vec![BinValue::U8(1), BinValue::U8(2), BinValue::U8(3)],
+            } },
+        ] };
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: fnv1a("Root"), name: None }, entry)],
+        });
+        let original = bin.clone();
+        let path = |s: &str| s.parse::<ValuePath>().unwrap();
+
+        let mut journal = EditJournal::new();
+        journal.set(&mut bin, &path("entries[Root].a"), BinValue::U32(5)).unwrap();
+        journal.remove(&mut bin, &path("entries[Root].b[1]")).unwrap();
+        journal.set(&mut bin, &path("entries[0x5d3b4e4a].c"), BinValue::Bool(true)).unwrap_err();
+        journal.set(&mut bin, &path("entries[Root].c"), BinValue::Bool(true)).unwrap();
+        assert_eq!(journal.records().len(), 3);
+        assert_eq!(path("entries[Root].b[1]").get(&bin), Some(&BinValue::U8(3)));
+        let edited = bin.clone();
+
+        let saved = EditJournal::from_json(&journal.to_json().unwrap()).unwrap();
+        let mut copy = original.clone();
+        saved.replay(&mut copy).unwrap();
+        assert_eq!(copy, edited);
+
+        while journal.undo(&mut bin).unwrap() {}
+        assert_eq!(bin, original);
+        while journal.redo(&mut bin).unwrap() {}
+        assert_eq!(bin, edited);
+    }
+}
@@ -0,0 +1,369 @@
+//! Pluggable file formats.
+//!
+//! Each on-disk representation of a [`Bin`] implements [`BinFormat`]. A
+//! [`FormatRegistry`] holds the available formats and is what detection and
+//! the CLI go through, so a new format only needs an implementation and a
+//! `register` call.
+//!
+//! # Examples
+//!
+//! ```
+//! use ritobin_rust::format::FormatRegistry;
+//! use ritobin_rust::model::{Bin, BinValue};
+//!
+//! let formats = FormatRegistry::with_defaults();
+//! let mut bin = Bin::new();
+//! bin.sections.insert("version".to_string(), BinValue::U32(3));
+//!
+//! let json = formats.by_extension("json").unwrap();
+//! let data = json.write(&bin).unwrap();
+//! assert_eq!(json.read(&data).unwrap(), bin);
+//! ```
+
+use crate::binary::BinError;
+use crate::json::{JsonReadOptions, JsonWriteOptions};
+use crate::model::Bin;
+use crate::text::{TextReadOptions, TextSchema, TextWriteOptions};
+use serde::Serialize;
+use thiserror::Error;
+
+/// Error returned by format implementations.
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error(transparent)]
+    Bin(#[from] BinError),
+    #[error("Invalid UTF-8: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Text(#[from] crate::text::TextError),
+    /// Errors reported as plain messages, as by the JSON module
+    #[error("{0}")]
+    Message(String),
+    /// Errors from formats defined outside this crate
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<String> for FormatError {
+    fn from(message: String) -> Self {
+        FormatError::Message(message)
+    }
+}
+
+/// A way of encoding a [`Bin`] as bytes.
+pub trait BinFormat: Send + Sync {
+    /// Short unique name, used on the command line (e.g. `bin`, `text`)
+    fn name(&self) -> &'static str;
+
+    /// File extensions for this format, without the dot; the first is used for output
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether `data` is recognizably in this format from its content alone
+    fn sniff(&self, _data: &[u8]) -> bool {
+        false
+    }
+
+    fn read(&self, data: &[u8]) -> Result<Bin, FormatError>;
+
+    fn write(&self, bin: &Bin) -> Result<Vec<u8>, FormatError>;
+
+    /// Write `bin` to `out`; formats that can encode incrementally override
+    /// this to avoid holding the whole output in memory
+    fn write_to(&self, bin: &Bin, out: &mut dyn std::io::Write) -> Result<(), FormatError> {
+        out.write_all(&self.write(bin)?)?;
+        Ok(())
+    }
+
+    /// Whether [`read`](Self::read) is supported
+    fn can_read(&self) -> bool {
+        true
+    }
+
+    /// Whether [`write`](Self::write) is supported
+    fn can_write(&self) -> bool {
+        true
+    }
+}
+
+/// The native PROP/PTCH binary format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BinaryFormat {
+    /// Verify written data with [`write_bin_checked`](crate::binary::write_bin_checked)
+    pub checked: bool,
+    /// Fail writes larger than this many bytes, see [`write_bin_within`](crate::binary::write_bin_within)
+    pub max_size: Option<u64>,
+    /// Decode entries on this many threads (0 for one per CPU), see [`read_bin_parallel`](crate::binary::read_bin_parallel)
+    pub decode_threads: Option<usize>,
+    /// Encode entries on this many threads (0 for one per CPU), see [`write_bin_parallel`](crate::binary::write_bin_parallel);
+    /// checked writes encode on one thread
+    pub encode_threads: Option<usize>,
+    /// Entry and field order, see [`WriteOptions`](crate::binary::WriteOptions)
+    pub write_options: crate::binary::WriteOptions,
+}
+
+impl BinaryFormat {
+    pub const NAME: &'static str = "bin";
+}
+
+impl BinFormat for BinaryFormat {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["bin"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        data.starts_with(b"PROP") || data.starts_with(b"PTCH")
+    }
+
+    fn read(&self, data: &[u8]) -> Result<Bin, FormatError> {
+        match self.decode_threads {
+            Some(threads) => Ok(crate::binary::read_bin_parallel(data, &Default::default(), threads)?),
+            None => Ok(crate::binary::read_bin(data)?),
+        }
+    }
+
+    fn write(&self, bin: &Bin) -> Result<Vec<u8>, FormatError> {
+        let data = match (self.checked, self.encode_threads) {
+            (true, _) => crate::binary::write_checked(bin, self.write_options)?,
+            (false, threads) => {
+                let threads = threads.map_or(1, crate::binary::resolve_threads);
+                crate::binary::write_bin_impl(bin, threads, self.write_options, None)?
+            }
+        };
+        if let Some(budget) = self.max_size {
+            crate::binary::check_budget(bin, &data, budget)?;
+        }
+        Ok(data)
+    }
+}
+
+/// The ritobin text format (`#PROP_text`).
+#[derive(Debug, Default, Clone)]
+pub struct TextFormat {
+    /// Accept fields without type annotations, see [`TextReadOptions::infer_types`]
+    pub infer_types: bool,
+    /// Field types used when inferring
+    pub schema: Option<TextSchema>,
+    /// Accept C++ ritobin's quirks, see [`TextReadOptions::ritobin_compat`]
+    pub ritobin_compat: bool,
+    pub write_options: TextWriteOptions,
+}
+
+impl TextFormat {
+    pub const NAME: &'static str = "text";
+}
+
+impl BinFormat for TextFormat {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["py"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        // Windows editors may add a byte order mark
+        data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data).starts_with(b"#PROP_text")
+    }
+
+    fn read(&self, data: &[u8]) -> Result<Bin, FormatError> {
+        let opts = TextReadOptions {
+            infer_types: self.infer_types,
+            schema: self.schema.as_ref(),
+            revision: None,
+            ritobin_compat: self.ritobin_compat,
+            ..Default::default()
+        };
+        Ok(crate::text::read_text_with_options(std::str::from_utf8(data)?, &opts)?)
+    }
+
+    fn write(&self, bin: &Bin) -> Result<Vec<u8>, FormatError> {
+        crate::text::write_text_with_options(bin, &self.write_options)
+            .map(String::into_bytes)
+            .map_err(|e| FormatError::Message(e.to_string()))
+    }
+
+    fn write_to(&self, bin: &Bin, out: &mut dyn std::io::Write) -> Result<(), FormatError> {
+        Ok(crate::text::write_text_to(bin, &self.write_options, out)?)
+    }
+}
+
+/// JSON, as produced by [`write_json`](crate::json::write_json).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonFormat {
+    /// Accept C++ ritobin's type names, see [`JsonReadOptions::ritobin_compat`]
+    pub ritobin_compat: bool,
+    pub write_options: JsonWriteOptions,
+}
+
+impl JsonFormat {
+    pub const NAME: &'static str = "json";
+}
+
+impl BinFormat for JsonFormat {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn read(&self, data: &[u8]) -> Result<Bin, FormatError> {
+        let opts = JsonReadOptions { ritobin_compat: self.ritobin_compat };
+        Ok(crate::json::read_json_with_options(std::str::from_utf8(data)?, &opts)?.0)
+    }
+
+    fn write(&self, bin: &Bin) -> Result<Vec<u8>, FormatError> {
+        Ok(crate::json::write_json_with_options(bin, &self.write_options)?.into_bytes())
+    }
+
+    fn write_to(&self, bin: &Bin, out: &mut dyn std::io::Write) -> Result<(), FormatError> {
+        Ok(crate::json::write_json_to(bin, &self.write_options, out)?)
+    }
+}
+
+/// The set of formats available for reading and writing.
+#[derive(Default)]
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn BinFormat>>,
+}
+
+impl FormatRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the binary, text and JSON formats
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(BinaryFormat::default());
+        registry.register(TextFormat::default());
+        registry.register(JsonFormat::default());
+        registry
+    }
+
+    /// Add a format, replacing any registered format with the same name
+    pub fn register<F: BinFormat + 'static>(&mut self, format: F) {
+        match self.formats.iter().position(|f| f.name() == format.name()) {
+            Some(i) => self.formats[i] = Box::new(format),
+            None => self.formats.push(Box::new(format)),
+        }
+    }
+
+    /// Look up a format by name
+    pub fn get(&self, name: &str) -> Option<&dyn BinFormat> {
+        self.iter().find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Look up the format owning a file extension (without the dot)
+    pub fn by_extension(&self, ext: &str) -> Option<&dyn BinFormat> {
+        self.iter().find(|f| f.extensions().iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+
+    /// The first format recognizing `data` by content
+    pub fn sniff(&self, data: &[u8]) -> Option<&dyn BinFormat> {
+        self.iter().find(|f| f.sniff(data))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn BinFormat> {
+        self.formats.iter().map(|f| f.as_ref())
+    }
+
+    /// Names of all registered formats
+    pub fn names(&self) -> Vec<&'static str> {
+        self.iter().map(|f| f.name()).collect()
+    }
+
+    /// The registered formats and compiled-in features
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION"),
+            formats: self.iter()
+                .map(|f| FormatCapability {
+                    name: f.name(),
+                    extensions: f.extensions().iter().map(|e| e.to_string()).collect(),
+                    read: f.can_read(),
+                    write: f.can_write(),
+                })
+                .collect(),
+            features: compiled_features(),
+        }
+    }
+}
+
+/// What this build can read and write, for tools wrapping the CLI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Crate version
+    pub version: &'static str,
+    pub formats: Vec<FormatCapability>,
+    /// Optional Cargo features compiled in, see [`compiled_features`]
+    pub features: Vec<&'static str>,
+}
+
+/// One entry of [`Capabilities::formats`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormatCapability {
+    pub name: &'static str,
+    /// Extensions without the dot; the first is used for output
+    pub extensions: Vec<String>,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// Optional Cargo features this build was compiled with
+pub fn compiled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if cfg!(feature = "wad") {
+        features.push("wad");
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BinValue;
+
+    #[test]
+    fn test_registry_lookup_and_round_trip() {
+        let formats = FormatRegistry::with_defaults();
+        assert_eq!(formats.names(), vec!["bin", "text", "json"]);
+        assert_eq!(formats.by_extension("PY").unwrap().name(), "text");
+        assert_eq!(formats.get("Json").unwrap().extensions(), &["json"]);
+        assert!(formats.get("yaml").is_none());
+        let caps = formats.capabilities();
+        assert_eq!(caps.formats[1].extensions, vec!["py".to_string()]);
+        assert!(caps.formats.iter().all(|f| f.read && f.write));
+        assert_eq!(caps.features.contains(&"mmap"), cfg!(feature = "mmap"));
+        assert_eq!(caps.features.contains(&"wad"), cfg!(feature = "wad"));
+
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        for format in formats.iter() {
+            let data = format.write(&bin).unwrap();
+            let sniffed = formats.sniff(&data).map(|f| f.name());
+            assert!(sniffed.is_none() || sniffed == Some(format.name()));
+            let back = format.read(&data).unwrap();
+            assert_eq!(back.sections["version"], BinValue::U32(3), "{}", format.name());
+        }
+        assert_eq!(formats.sniff(b"\xef\xbb\xbf#PROP_text\r\n").unwrap().name(), "text");
+
+        let text = formats.get("text").unwrap();
+        let mut streamed = Vec::new();
+        text.write_to(&bin, &mut streamed).unwrap();
+        assert_eq!(streamed, text.write(&bin).unwrap());
+    }
+}
@@ -6,7 +6,7 @@ impl Fnv1a {
     pub fn new(s: &str) -> Self {
         let mut h: u32 = 0x811c9dc5;
         for c in s.bytes() {
-            let c = if c >= b'A' && c <= b'Z' {
+            let c = if c.is_ascii_uppercase() {
                 c - b'A' + b'a'
             } else {
                 c
@@ -21,6 +21,15 @@ pub fn fnv1a(s: &str) -> u32 {
     Fnv1a::new(s).0
 }
 
+/// 64-bit FNV-1a of `parts` one after another, as is, for fingerprints and checksums
+pub(crate) fn fnv1a64(parts: &[&[u8]]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in parts.iter().flat_map(|p| p.iter()) {
+        h = (h ^ b as u64).wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
 pub struct Xxh64(pub u64);
 
 impl Xxh64 {
@@ -29,7 +38,17 @@ impl Xxh64 {
     }
 }
 
-fn xxh64(data: &[u8], seed: u64) -> u64 {
+/// XXH64 of `data` with ASCII letters lowercased, as names are hashed
+pub(crate) fn xxh64(data: &[u8], seed: u64) -> u64 {
+    xxh64_with(data, seed, |c| c.to_ascii_lowercase() as u64)
+}
+
+/// XXH64 of `data` as is, for checksums and content keys
+pub(crate) fn xxh64_exact(data: &[u8], seed: u64) -> u64 {
+    xxh64_with(data, seed, |c| c as u64)
+}
+
+fn xxh64_with(data: &[u8], seed: u64, byte: impl Fn(u8) -> u64) -> u64 {
     let len = data.len();
     let end = len;
     let mut ptr = 0;
@@ -40,23 +59,15 @@ fn xxh64(data: &[u8], seed: u64) -> u64 {
     const PRIME4: u64 = 9650029242287828579;
     const PRIME5: u64 = 2870177450012600261;
 
-    let to_lower = |c: u8| -> u64 {
-        if c >= b'A' && c <= b'Z' {
-            (c - b'A' + b'a') as u64
-        } else {
-            c as u64
-        }
-    };
-
     let block = |data: &[u8], idx: usize| -> u64 {
-        to_lower(data[idx])
-            | (to_lower(data[idx + 1]) << 8)
-            | (to_lower(data[idx + 2]) << 16)
-            | (to_lower(data[idx + 3]) << 24)
-            | (to_lower(data[idx + 4]) << 32)
-            | (to_lower(data[idx + 5]) << 40)
-            | (to_lower(data[idx + 6]) << 48)
-            | (to_lower(data[idx + 7]) << 56)
+        byte(data[idx])
+            | (byte(data[idx + 1]) << 8)
+            | (byte(data[idx + 2]) << 16)
+            | (byte(data[idx + 3]) << 24)
+            | (byte(data[idx + 4]) << 32)
+            | (byte(data[idx + 5]) << 40)
+            | (byte(data[idx + 6]) << 48)
+            | (byte(data[idx + 7]) << 56)
     };
 
     let rotl = |x: u64, r: u32| -> u64 { x.rotate_left(r) };
@@ -104,17 +115,17 @@ fn xxh64(data: &[u8], seed: u64) -> u64 {
     }
 
     if ptr + 4 <= end {
-        let k1 = to_lower(data[ptr])
-            | (to_lower(data[ptr + 1]) << 8)
-            | (to_lower(data[ptr + 2]) << 16)
-            | (to_lower(data[ptr + 3]) << 24);
+        let k1 = byte(data[ptr])
+            | (byte(data[ptr + 1]) << 8)
+            | (byte(data[ptr + 2]) << 16)
+            | (byte(data[ptr + 3]) << 24);
         result ^= k1.wrapping_mul(PRIME1);
         result = rotl(result, 23).wrapping_mul(PRIME2).wrapping_add(PRIME3);
         ptr += 4;
     }
 
     while ptr < end {
-        result ^= to_lower(data[ptr]).wrapping_mul(PRIME5);
+        result ^= byte(data[ptr]).wrapping_mul(PRIME5);
         result = rotl(result, 11).wrapping_mul(PRIME1);
         ptr += 1;
     }
@@ -127,3 +138,209 @@ fn xxh64(data: &[u8], seed: u64) -> u64 {
 
     result
 }
+
+/// XXH3 (64-bit, no seed) of `data` as is, for WAD chunk checksums
+#[cfg(feature = "wad")]
+pub(crate) fn xxh3_64(data: &[u8]) -> u64 {
+    use xxh3::*;
+
+    let len = data.len();
+    match len {
+        0 => xxh64_avalanche(read64(&SECRET, 56) ^ read64(&SECRET, 64)),
+        1..=3 => {
+            let combined = ((data[0] as u32) << 16) | ((data[len >> 1] as u32) << 24) | data[len - 1] as u32 | ((len as u32) << 8);
+            xxh64_avalanche(combined as u64 ^ (read32(&SECRET, 0) ^ read32(&SECRET, 4)) as u64)
+        }
+        4..=8 => {
+            let input = read32(data, len - 4) as u64 | ((read32(data, 0) as u64) << 32);
+            rrmxmx(input ^ (read64(&SECRET, 8) ^ read64(&SECRET, 16)), len as u64)
+        }
+        9..=16 => {
+            let lo = read64(data, 0) ^ read64(&SECRET, 24) ^ read64(&SECRET, 32);
+            let hi = read64(data, len - 8) ^ read64(&SECRET, 40) ^ read64(&SECRET, 48);
+            let acc = (len as u64).wrapping_add(lo.swap_bytes()).wrapping_add(hi).wrapping_add(fold_mul(lo, hi));
+            avalanche(acc)
+        }
+        17..=128 => {
+            let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+            // Pairs of 16-byte lanes from both ends, more of them for longer input
+            for i in (0..(len - 1) / 32 + 1).rev() {
+                acc = acc.wrapping_add(mix16(data, 16 * i, 32 * i));
+                acc = acc.wrapping_add(mix16(data, len - 16 * (i + 1), 32 * i + 16));
+            }
+            avalanche(acc)
+        }
+        129..=240 => {
+            let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+            for i in 0..8 {
+                acc = acc.wrapping_add(mix16(data, 16 * i, 16 * i));
+            }
+            acc = avalanche(acc);
+            for i in 8..len / 16 {
+                acc = acc.wrapping_add(mix16(data, 16 * i, 16 * (i - 8) + 3));
+            }
+            avalanche(acc.wrapping_add(mix16(data, len - 16, 136 - 17)))
+        }
+        _ => long(data),
+    }
+}
+
+#[cfg(feature = "wad")]
+mod xxh3 {
+    pub const PRIME32_1: u64 = 0x9E3779B1;
+    pub const PRIME32_2: u64 = 0x85EBCA77;
+    pub const PRIME32_3: u64 = 0xC2B2AE3D;
+    pub const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+    pub const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+    pub const PRIME64_3: u64 = 0x165667B19E3779F9;
+    pub const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+    pub const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+    /// The default secret
+    pub const SECRET: [u8; 192] = [
+        0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+        0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+        0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+        0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+        0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+        0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+        0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+        0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+        0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+        0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+        0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+        0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+    ];
+
+    pub fn read32(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    pub fn read64(data: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+    }
+
+    pub fn fold_mul(a: u64, b: u64) -> u64 {
+        let product = a as u128 * b as u128;
+        product as u64 ^ (product >> 64) as u64
+    }
+
+    pub fn xxh64_avalanche(mut h: u64) -> u64 {
+        h ^= h >> 33;
+        h = h.wrapping_mul(PRIME64_2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(PRIME64_3);
+        h ^ (h >> 32)
+    }
+
+    pub fn avalanche(mut h: u64) -> u64 {
+        h ^= h >> 37;
+        h = h.wrapping_mul(0x165667919E3779F9);
+        h ^ (h >> 32)
+    }
+
+    pub fn rrmxmx(mut h: u64, len: u64) -> u64 {
+        h ^= h.rotate_left(49) ^ h.rotate_left(24);
+        h = h.wrapping_mul(0x9FB21C651E98DF25);
+        h ^= (h >> 35).wrapping_add(len);
+        h = h.wrapping_mul(0x9FB21C651E98DF25);
+        h ^ (h >> 28)
+    }
+
+    /// Mix 16 bytes of `data` at `at` with the secret at `secret`
+    pub fn mix16(data: &[u8], at: usize, secret: usize) -> u64 {
+        fold_mul(read64(data, at) ^ read64(&SECRET, secret), read64(data, at + 8) ^ read64(&SECRET, secret + 8))
+    }
+
+    fn accumulate_stripe(acc: &mut [u64; 8], stripe: &[u8], secret: usize) {
+        for i in 0..8 {
+            let value = read64(stripe, 8 * i);
+            let key = value ^ read64(&SECRET, secret + 8 * i);
+            acc[i ^ 1] = acc[i ^ 1].wrapping_add(value);
+            acc[i] = acc[i].wrapping_add((key & 0xffff_ffff).wrapping_mul(key >> 32));
+        }
+    }
+
+    /// Input over 240 bytes, in blocks of 16 stripes of 64 bytes
+    pub fn long(data: &[u8]) -> u64 {
+        const STRIPES: usize = (SECRET.len() - 64) / 8;
+        const BLOCK: usize = 64 * STRIPES;
+        let mut acc = [PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1];
+        let blocks = (data.len() - 1) / BLOCK;
+        for block in data.chunks_exact(BLOCK).take(blocks) {
+            for (n, stripe) in block.chunks_exact(64).enumerate() {
+                accumulate_stripe(&mut acc, stripe, 8 * n);
+            }
+            for (i, a) in acc.iter_mut().enumerate() {
+                *a ^= *a >> 47;
+                *a ^= read64(&SECRET, SECRET.len() - 64 + 8 * i);
+                *a = a.wrapping_mul(PRIME32_1);
+            }
+        }
+        let rest = &data[blocks * BLOCK..];
+        for (n, stripe) in rest[..rest.len() - 1].chunks_exact(64).enumerate() {
+            accumulate_stripe(&mut acc, stripe, 8 * n);
+        }
+        accumulate_stripe(&mut acc, &data[data.len() - 64..], SECRET.len() - 64 - 7);
+
+        let mut result = (data.len() as u64).wrapping_mul(PRIME64_1);
+        for i in 0..4 {
+            result = result.wrapping_add(fold_mul(acc[2 * i] ^ read64(&SECRET, 11 + 16 * i), acc[2 * i + 1] ^ read64(&SECRET, 11 + 16 * i + 8)));
+        }
+        avalanche(result)
+    }
+}
+
+/// SHA-256 of `data`, for the chunk checksums of WAD archives before version 3.1
+#[cfg(feature = "wad")]
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+    // A 1 bit, zeros up to 8 bytes short of a block, then the length in bits
+    let mut tail = data[data.len() / 64 * 64..].to_vec();
+    tail.push(0x80);
+    tail.resize((tail.len() + 8).next_multiple_of(64) - 8, 0);
+    tail.extend_from_slice(&((data.len() as u64) << 3).to_be_bytes());
+
+    for block in data.chunks_exact(64).chain(tail.chunks_exact(64)) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (out, s) in digest.chunks_exact_mut(4).zip(state) {
+        out.copy_from_slice(&s.to_be_bytes());
+    }
+    digest
+}
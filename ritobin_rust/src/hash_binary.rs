@@ -3,7 +3,8 @@ use std::collections::HashMap;
 use std::io::{Read, Result, Write};
 
 const MAGIC: &[u8; 4] = b"HHSH";
-const VERSION: i32 = 1;
+/// Version of the binary hash format read and written here
+pub const VERSION: i32 = 1;
 
 /// Writer for binary hash files compatible with C# implementation
 /// 
@@ -89,6 +90,46 @@ impl<R: Read> BinaryHashReader<R> {
     /// 
     /// Returns (fnv1a_map, xxh64_map)
     pub fn read_hashes(&mut self) -> Result<(HashMap<u32, String>, HashMap<u64, String>)> {
+        let (fnv1a_count, xxh64_count) = self.read_header()?;
+
+        // Pre-allocate with capacity for better performance
+        let mut fnv1a = HashMap::with_capacity(fnv1a_count);
+        let mut xxh64 = HashMap::with_capacity(xxh64_count);
+
+        // Read FNV1a entries
+        for _ in 0..fnv1a_count {
+            let hash = self.reader.read_u32::<LittleEndian>()?;
+            let string = self.read_string()?;
+            fnv1a.insert(hash, string);
+        }
+
+        // Read XXH64 entries
+        for _ in 0..xxh64_count {
+            let hash = self.reader.read_u64::<LittleEndian>()?;
+            let string = self.read_string()?;
+            xxh64.insert(hash, string);
+        }
+
+        Ok((fnv1a, xxh64))
+    }
+
+    /// Read every entry in file order, keeping repeated hashes
+    pub fn read_entries(&mut self) -> Result<HashFileEntries> {
+        let (fnv1a_count, xxh64_count) = self.read_header()?;
+        let mut entries = HashFileEntries::default();
+        for _ in 0..fnv1a_count {
+            let hash = self.reader.read_u32::<LittleEndian>()?;
+            entries.fnv1a.push((hash, self.read_string()?));
+        }
+        for _ in 0..xxh64_count {
+            let hash = self.reader.read_u64::<LittleEndian>()?;
+            entries.xxh64.push((hash, self.read_string()?));
+        }
+        Ok(entries)
+    }
+
+    /// Check magic and version, returning the FNV1a and XXH64 counts
+    fn read_header(&mut self) -> Result<(usize, usize)> {
         // Read and verify header
         let mut magic = [0u8; 4];
         self.reader.read_exact(&mut magic)?;
@@ -110,26 +151,7 @@ impl<R: Read> BinaryHashReader<R> {
 
         let fnv1a_count = self.reader.read_i32::<LittleEndian>()? as usize;
         let xxh64_count = self.reader.read_i32::<LittleEndian>()? as usize;
-
-        // Pre-allocate with capacity for better performance
-        let mut fnv1a = HashMap::with_capacity(fnv1a_count);
-        let mut xxh64 = HashMap::with_capacity(xxh64_count);
-
-        // Read FNV1a entries
-        for _ in 0..fnv1a_count {
-            let hash = self.reader.read_u32::<LittleEndian>()?;
-            let string = self.read_string()?;
-            fnv1a.insert(hash, string);
-        }
-
-        // Read XXH64 entries
-        for _ in 0..xxh64_count {
-            let hash = self.reader.read_u64::<LittleEndian>()?;
-            let string = self.read_string()?;
-            xxh64.insert(hash, string);
-        }
-
-        Ok((fnv1a, xxh64))
+        Ok((fnv1a_count, xxh64_count))
     }
 
     /// Read string with .NET BinaryReader compatible length prefix
@@ -168,6 +190,58 @@ impl<R: Read> BinaryHashReader<R> {
     }
 }
 
+/// The entries of a binary hash file as stored, from [`BinaryHashReader::read_entries`]
+#[derive(Debug, Default, Clone)]
+pub struct HashFileEntries {
+    pub fnv1a: Vec<(u32, String)>,
+    pub xxh64: Vec<(u64, String)>,
+}
+
+/// Duplicate and conflict statistics for one hash kind
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HashStats {
+    pub count: usize,
+    /// Entries repeating an earlier hash with the same string
+    pub duplicates: usize,
+    /// Entries repeating an earlier hash with a different string (the last one wins when loading)
+    pub conflicts: usize,
+    /// Entries whose string doesn't hash to the stored hash
+    pub mismatches: usize,
+}
+
+impl HashStats {
+    fn collect<H: std::hash::Hash + Eq + Copy>(entries: &[(H, String)], hash: impl Fn(&str) -> H) -> Self {
+        let mut seen: HashMap<H, &str> = HashMap::with_capacity(entries.len());
+        let mut stats = Self { count: entries.len(), ..Default::default() };
+        for (h, s) in entries {
+            match seen.insert(*h, s) {
+                Some(prev) if prev == s => stats.duplicates += 1,
+                Some(_) => stats.conflicts += 1,
+                None => {}
+            }
+            if hash(s) != *h {
+                stats.mismatches += 1;
+            }
+        }
+        stats
+    }
+}
+
+impl HashFileEntries {
+    /// Duplicate, conflict and mismatch counts for the FNV1a and XXH64 entries
+    pub fn stats(&self) -> (HashStats, HashStats) {
+        (
+            HashStats::collect(&self.fnv1a, crate::hash::fnv1a),
+            HashStats::collect(&self.xxh64, |s| crate::hash::Xxh64::new(s).0),
+        )
+    }
+
+    /// Total bytes of names
+    pub fn string_bytes(&self) -> usize {
+        self.fnv1a.iter().map(|(_, s)| s.len()).chain(self.xxh64.iter().map(|(_, s)| s.len())).sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +308,29 @@ mod tests {
         assert_eq!(xxh64, decoded_xxh64);
     }
 
+    #[test]
+    fn test_entry_stats() {
+        use crate::hash::fnv1a;
+
+        let mut buf = Vec::new();
+        let mut writer = BinaryHashWriter::new(&mut buf);
+        writer.writer.write_all(MAGIC).unwrap();
+        writer.writer.write_i32::<LittleEndian>(VERSION).unwrap();
+        writer.writer.write_i32::<LittleEndian>(4).unwrap();
+        writer.writer.write_i32::<LittleEndian>(0).unwrap();
+        for (hash, name) in [(fnv1a("a"), "a"), (fnv1a("a"), "a"), (fnv1a("a"), "A"), (1, "b")] {
+            writer.writer.write_u32::<LittleEndian>(hash).unwrap();
+            writer.write_string(name).unwrap();
+        }
+
+        let entries = BinaryHashReader::new(&buf[..]).read_entries().unwrap();
+        assert_eq!(entries.fnv1a.len(), 4);
+        assert_eq!(entries.string_bytes(), 4);
+        let (fnv1a_stats, xxh64_stats) = entries.stats();
+        assert_eq!(fnv1a_stats, HashStats { count: 4, duplicates: 1, conflicts: 1, mismatches: 1 });
+        assert_eq!(xxh64_stats, HashStats::default());
+    }
+
     #[test]
     fn test_empty_hashes() {
         let fnv1a = HashMap::new();
@@ -0,0 +1,232 @@
+//! Every hash in a bin, with where it appears and whether it has a name.
+//!
+//! [`HashReport::collect`] (or [`Bin::collect_hashes`]) walks a bin once and
+//! groups each FNV1a hash (entry keys, classes, fields, `hash` and `link`
+//! values) and XXH64 hash (`file` values) by value, in the order first seen.
+//! Coverage statistics, searches and hash cracking can all work from the
+//! report instead of walking the bin themselves.
+//!
+//! # Examples
+//!
+//! ```
+//! use ritobin_rust::hash_report::{HashId, HashRole};
+//! use ritobin_rust::model::{Bin, BinType, BinValue, Field};
+//!
+//! let mut bin = Bin::new();
+//! bin.sections.insert("entries".to_string(), BinValue::Map {
+//!     key_type: BinType::Hash,
+//!     value_type: BinType::Embed,
+//!     items: vec![(BinValue::Hash { value: 1, name: Some("Root".to_string()) }, BinValue::Embed {
+//!         name: 2,
+//!         name_str: None,
+//!         items: vec![Field { key: 3, key_str: None, value: BinValue::Link { value: 1, name: None } }],
+//!     })],
+//! });
+//!
+//! let report = bin.collect_hashes();
+//! let root = report.get(HashId::Fnv1a(1)).unwrap();
+//! assert_eq!(root.name.as_deref(), Some("Root"));
+//! assert_eq!(root.count(), 2);
+//! assert_eq!(root.occurrences[1].path, "entries[0x00000001].0x00000003");
+//! assert_eq!(root.occurrences[1].role, HashRole::Link);
+//! assert_eq!(report.unresolved().count(), 2);
+//! ```
+
+use crate::compare::{field_label, key_label};
+use crate::model::{Bin, BinValue};
+use crate::unhash::NameStats;
+use indexmap::IndexMap;
+
+/// A hash value and the function it was made with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashId {
+    Fnv1a(u32),
+    Xxh64(u64),
+}
+
+impl std::fmt::Display for HashId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashId::Fnv1a(hash) => write!(f, "{:#010x}", hash),
+            HashId::Xxh64(hash) => write!(f, "{:#018x}", hash),
+        }
+    }
+}
+
+/// What a hash names where it appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashRole {
+    /// Class of a pointer or embed
+    Class,
+    /// Key of a field
+    Field,
+    /// A `hash` value, including entry keys and other map keys
+    Hash,
+    /// A `link` value, naming another entry
+    Link,
+    /// A `file` value, naming a game file
+    File,
+}
+
+/// One place a hash appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashOccurrence {
+    /// Path of the value, e.g. `entries[0x1234abcd].0x2c3a9e81[3]`; for a
+    /// class, the path of the structure
+    pub path: String,
+    pub role: HashRole,
+    /// Whether the name is known at this occurrence
+    pub resolved: bool,
+}
+
+/// Everything known about one hash in a bin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashUsage {
+    /// The name from the first occurrence that has one
+    pub name: Option<String>,
+    /// Every occurrence, in bin order
+    pub occurrences: Vec<HashOccurrence>,
+}
+
+impl HashUsage {
+    /// How many times the hash appears
+    pub fn count(&self) -> usize {
+        self.occurrences.len()
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.name.is_some()
+    }
+}
+
+/// Every hash in a bin, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct HashReport {
+    hashes: IndexMap<HashId, HashUsage>,
+}
+
+impl HashReport {
+    /// Walk every section of `bin`
+    pub fn collect(bin: &Bin) -> Self {
+        let mut report = Self::default();
+        for (key, value) in &bin.sections {
+            report.visit(key, value);
+        }
+        report
+    }
+
+    pub fn get(&self, id: HashId) -> Option<&HashUsage> {
+        self.hashes.get(&id)
+    }
+
+    /// Every distinct hash, in the order first seen
+    pub fn iter(&self) -> impl Iterator<Item = (HashId, &HashUsage)> {
+        self.hashes.iter().map(|(id, usage)| (*id, usage))
+    }
+
+    /// Hashes with no name anywhere they appear
+    pub fn unresolved(&self) -> impl Iterator<Item = (HashId, &HashUsage)> {
+        self.iter().filter(|(_, usage)| !usage.is_resolved())
+    }
+
+    /// Number of distinct hashes
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Resolved and unresolved occurrences, as counted by [`NameStats::collect`]
+    pub fn name_stats(&self) -> NameStats {
+        let mut stats = NameStats::default();
+        for occurrence in self.hashes.values().flat_map(|usage| &usage.occurrences) {
+            if occurrence.resolved {
+                stats.resolved += 1;
+            } else {
+                stats.unresolved += 1;
+            }
+        }
+        stats
+    }
+
+    fn add(&mut self, id: HashId, name: &Option<String>, path: &str, role: HashRole) {
+        let usage = self.hashes.entry(id).or_default();
+        if usage.name.is_none() {
+            usage.name.clone_from(name);
+        }
+        usage.occurrences.push(HashOccurrence { path: path.to_string(), role, resolved: name.is_some() });
+    }
+
+    fn visit(&mut self, path: &str, value: &BinValue) {
+        match value {
+            BinValue::Hash { value, name } => self.add(HashId::Fnv1a(*value), name, path, HashRole::Hash),
+            BinValue::Link { value, name } => self.add(HashId::Fnv1a(*value), name, path, HashRole::Link),
+            BinValue::File { value, name } => self.add(HashId::Xxh64(*value), name, path, HashRole::File),
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for (i, item) in items.iter().enumerate() {
+                    self.visit(&format!("{}[{}]", path, i), item);
+                }
+            },
+            BinValue::Option { item: Some(inner), .. } => self.visit(path, inner),
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    let item_path = format!("{}[{}]", path, key_label(k));
+                    self.visit(&item_path, k);
+                    self.visit(&item_path, v);
+                }
+            },
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                // Null pointers carry no class hash
+                if *name != 0 || matches!(value, BinValue::Embed { .. }) {
+                    self.add(HashId::Fnv1a(*name), name_str, path, HashRole::Class);
+                }
+                for field in items {
+                    let field_path = format!("{}.{}", path, field_label(field));
+                    self.add(HashId::Fnv1a(field.key), &field.key_str, &field_path, HashRole::Field);
+                    self.visit(&field_path, &field.value);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BinType, Field};
+
+    #[test]
+    fn test_collect_hashes_matches_name_stats() {
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 10, name: None }, BinValue::Embed {
+                name: 20,
+                name_str: Some("Record".to_string()),
+                items: vec![
+                    Field { key: 30, key_str: None, value: BinValue::Pointer { name: 0, name_str: None, items: vec![] } },
+                    Field { key: 31, key_str: None, value: BinValue::List {
+                        value_type: BinType::File,
+                        items: vec![BinValue::File { value: 7, name: None }, BinValue::File { value: 7, name: Some("a.dds".to_string()) }],
+                    } },
+                ],
+            })],
+        });
+
+        let report = bin.collect_hashes();
+        assert_eq!(report.name_stats(), NameStats::collect(&bin));
+        let ids: Vec<_> = report.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [HashId::Fnv1a(10), HashId::Fnv1a(20), HashId::Fnv1a(30), HashId::Fnv1a(31), HashId::Xxh64(7)]);
+
+        let file = report.get(HashId::Xxh64(7)).unwrap();
+        assert_eq!(file.name.as_deref(), Some("a.dds"));
+        let paths: Vec<_> = file.occurrences.iter().map(|o| (o.path.as_str(), o.resolved)).collect();
+        assert_eq!(paths, [("entries[0x0000000a].0x0000001f[0]", false), ("entries[0x0000000a].0x0000001f[1]", true)]);
+        assert_eq!(report.get(HashId::Fnv1a(20)).unwrap().occurrences[0].role, HashRole::Class);
+        assert_eq!(report.unresolved().count(), 3);
+    }
+}
@@ -1,205 +1,509 @@
 use crate::model::{Bin, BinType, BinValue, Field};
-use serde_json::{Map, Value};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value;
 use std::str::FromStr;
 
+pub mod simple;
+
+/// Options for [`write_json_with_options`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JsonWriteOptions {
+    /// Write hashes that have no name (of hashes, files, links, classes and
+    /// fields) as `"0x…"` strings rather than numbers, which JavaScript
+    /// can't hold exactly above 2^53
+    pub hex_hashes: bool,
+}
+
 pub fn write_json(bin: &Bin) -> Result<String, String> {
-    let mut root = Map::new();
-    for (key, value) in &bin.sections {
-        let mut section = Map::new();
-        section.insert("type".to_string(), Value::String(get_type_name(value).to_string()));
-        section.insert("value".to_string(), bin_value_to_json(value));
-        root.insert(key.clone(), Value::Object(section));
-    }
-    serde_json::to_string_pretty(&Value::Object(root)).map_err(|e| e.to_string())
+    write_json_with_options(bin, &JsonWriteOptions::default())
+}
+
+/// Write a bin as JSON with explicit options.
+///
+/// [`read_json`] reads hashes written either way.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::json::{read_json, write_json_with_options, JsonWriteOptions};
+/// use ritobin_rust::model::{Bin, BinValue};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("skin".to_string(), BinValue::File { value: u64::MAX, name: None });
+/// let json = write_json_with_options(&bin, &JsonWriteOptions { hex_hashes: true })?;
+/// assert!(json.contains("\"0xffffffffffffffff\""));
+/// assert_eq!(read_json(&json)?, bin);
+/// # Ok::<(), String>(())
+/// ```
+pub fn write_json_with_options(bin: &Bin, opts: &JsonWriteOptions) -> Result<String, String> {
+    serde_json::to_string_pretty(&JsonBin(bin, *opts)).map_err(|e| e.to_string())
+}
+
+/// Write a bin as JSON straight to `out`, without building the JSON in
+/// memory first; the output is the same as [`write_json_with_options`]'s.
+///
+/// `out` gets many small writes; wrap files in a [`std::io::BufWriter`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::json::{write_json_to, JsonWriteOptions};
+///
+/// let bin = ritobin_rust::binary::read_bin(&std::fs::read("Map11.bin")?)?;
+/// let file = std::io::BufWriter::new(std::fs::File::create("Map11.json")?);
+/// write_json_to(&bin, &JsonWriteOptions::default(), file)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn write_json_to<W: std::io::Write>(bin: &Bin, opts: &JsonWriteOptions, out: W) -> std::io::Result<()> {
+    Ok(serde_json::to_writer_pretty(out, &JsonBin(bin, *opts))?)
+}
+
+/// Options for [`read_json_with_options`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JsonReadOptions {
+    /// Also accept type names the way the C++ ritobin writes them: in any
+    /// case, with common aliases, and with its older names `path`,
+    /// `struct`/`ptr` and `bitbool` for `file`, `pointer` and `flag`
+    ///
+    /// The layout of its JSON is otherwise the one this crate writes.
+    pub ritobin_compat: bool,
 }
 
 pub fn read_json(data: &str) -> Result<Bin, String> {
+    read_json_with_warnings(data).map(|(bin, _)| bin)
+}
+
+/// Read JSON written by the C++ ritobin; see [`JsonReadOptions::ritobin_compat`]
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::json::read_ritobin_cpp;
+/// use ritobin_rust::model::BinValue;
+///
+/// let json = r#"{ "skin": { "type": "path", "value": "ASSETS/Ahri.skn" } }"#;
+/// let bin = read_ritobin_cpp(json)?;
+/// assert!(matches!(&bin.sections["skin"], BinValue::File { name: Some(name), .. } if name == "ASSETS/Ahri.skn"));
+/// # Ok::<(), String>(())
+/// ```
+pub fn read_ritobin_cpp(data: &str) -> Result<Bin, String> {
+    read_json_with_options(data, &JsonReadOptions { ritobin_compat: true }).map(|(bin, _)| bin)
+}
+
+/// Read a bin from JSON coming from `reader`, without first loading the
+/// text into a string
+///
+/// Only the text is never held whole: the document is still parsed into a
+/// full JSON tree before any of it is converted, so peak memory is that tree
+/// plus the bin, several times the size of the text.
+///
+/// Unbuffered readers such as files are slow to parse from; wrap them in a
+/// [`std::io::BufReader`].
+pub fn read_json_from<R: std::io::Read>(reader: R) -> Result<Bin, String> {
+    let root: Value = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+    json_to_bin(&root, &JsonReadOptions::default(), &mut Vec::new())
+}
+
+/// Read a bin like [`read_json`], also listing numbers that did not fit
+/// their type and were wrapped or rounded to infinity
+pub fn read_json_with_warnings(data: &str) -> Result<(Bin, Vec<String>), String> {
+    read_json_with_options(data, &JsonReadOptions::default())
+}
+
+/// Read a bin with explicit options, also listing warnings as
+/// [`read_json_with_warnings`] does
+pub fn read_json_with_options(data: &str, opts: &JsonReadOptions) -> Result<(Bin, Vec<String>), String> {
+    let mut warnings = Vec::new();
     let root: Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let bin = json_to_bin(&root, opts, &mut warnings)?;
+    Ok((bin, warnings))
+}
+
+fn json_to_bin(root: &Value, opts: &JsonReadOptions, warnings: &mut Vec<String>) -> Result<Bin, String> {
     let root_obj = root.as_object().ok_or("Root must be an object")?;
     
     let mut bin = Bin::new();
     for (key, val) in root_obj {
         let val_obj = val.as_object().ok_or(format!("Section {} must be an object", key))?;
         let type_str = val_obj.get("type").and_then(|v| v.as_str()).ok_or(format!("Section {} missing type", key))?;
-        let type_ = BinType::from_str(type_str).map_err(|_| format!("Unknown type: {}", type_str))?;
+        let type_ = parse_type(type_str, opts).ok_or(format!("Unknown type: {}", type_str))?;
         
         let value_json = val_obj.get("value").ok_or(format!("Section {} missing value", key))?;
-        let value = json_to_bin_value(value_json, type_)?;
+        let value = json_to_bin_value(value_json, type_, opts, warnings)?;
         bin.sections.insert(key.clone(), value);
     }
+    crate::string_table::expand_strings(&mut bin)?;
     Ok(bin)
 }
 
-fn bin_value_to_json(value: &BinValue) -> Value {
-    match value {
-        BinValue::None => Value::Null,
-        BinValue::Bool(v) => Value::Bool(*v),
-        BinValue::I8(v) => Value::Number((*v).into()),
-        BinValue::U8(v) => Value::Number((*v).into()),
-        BinValue::I16(v) => Value::Number((*v).into()),
-        BinValue::U16(v) => Value::Number((*v).into()),
-        BinValue::I32(v) => Value::Number((*v).into()),
-        BinValue::U32(v) => Value::Number((*v).into()),
-        BinValue::I64(v) => Value::Number((*v).into()),
-        BinValue::U64(v) => Value::Number((*v).into()),
-        BinValue::F32(v) => serde_json::Number::from_f64(*v as f64).map(Value::Number).unwrap_or(Value::Null),
-        BinValue::Vec2(v) => Value::Array(v.iter().map(|x| serde_json::Number::from_f64(*x as f64).map(Value::Number).unwrap_or(Value::Null)).collect()),
-        BinValue::Vec3(v) => Value::Array(v.iter().map(|x| serde_json::Number::from_f64(*x as f64).map(Value::Number).unwrap_or(Value::Null)).collect()),
-        BinValue::Vec4(v) => Value::Array(v.iter().map(|x| serde_json::Number::from_f64(*x as f64).map(Value::Number).unwrap_or(Value::Null)).collect()),
-        BinValue::Mtx44(v) => Value::Array(v.iter().map(|x| serde_json::Number::from_f64(*x as f64).map(Value::Number).unwrap_or(Value::Null)).collect()),
-        BinValue::Rgba(v) => Value::Array(v.iter().map(|x| Value::Number((*x).into())).collect()),
-        BinValue::String(v) => Value::String(v.clone()),
-        BinValue::Hash { value, name } => {
-            if let Some(s) = name {
-                Value::String(s.clone())
-            } else {
-                Value::Number((*value).into())
-            }
-        },
-        BinValue::File { value, name } => {
-            if let Some(s) = name {
-                Value::String(s.clone())
-            } else {
-                Value::Number((*value).into())
-            }
-        },
-        BinValue::Link { value, name } => {
-            if let Some(s) = name {
-                Value::String(s.clone())
-            } else {
-                Value::Number((*value).into())
-            }
+/// A JSON Schema (draft-07) for the JSON [`write_json`] writes, for
+/// validating converted files or for editor completion
+///
+/// Each section, field and container item is checked against the type it is
+/// tagged with. Hashes may be names, numbers or `"0x…"` strings, and integers
+/// numbers or `"0x…"` strings, as [`read_json`] accepts them.
+///
+/// # Examples
+///
+/// ```
+/// let schema = ritobin_rust::json::schema();
+/// assert_eq!(schema["definitions"]["vec3"]["maxItems"], 3);
+/// std::fs::write(std::env::temp_dir().join("bin.schema.json"), schema.to_string())?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn schema() -> Value {
+    let types: Vec<BinType> = (0..=18).chain(0x80..=0x87).filter_map(|b| BinType::try_from(b).ok()).collect();
+    let names: Vec<&str> = types.iter().map(|t| get_bin_type_name(*t)).collect();
+    // `then` for each type: the schema holding its value, given `{ "$ref": ... }`
+    let by_type = |tag: &str, then: &dyn Fn(Value) -> Value| -> Vec<Value> {
+        names.iter().map(|name| serde_json::json!({
+            "if": { "required": [tag], "properties": { tag: { "const": name } } },
+            "then": then(serde_json::json!({ "$ref": format!("#/definitions/{}", name) })),
+        })).collect()
+    };
+    let value = |r: Value| serde_json::json!({ "properties": { "value": r } });
+    let items = |r: Value| serde_json::json!({ "properties": { "items": { "items": r } } });
+    let integer = |min: i128, max: i128| serde_json::json!({
+        "anyOf": [{ "type": "integer", "minimum": min, "maximum": max }, { "$ref": "#/definitions/hexString" }],
+    });
+    let floats = |n: usize| serde_json::json!({ "type": "array", "items": { "$ref": "#/definitions/f32" }, "minItems": n, "maxItems": n });
+    let list = |max_items: Option<usize>| {
+        let mut list = serde_json::json!({
+            "type": "object",
+            "required": ["items", "valueType"],
+            "properties": { "items": { "type": "array" }, "valueType": { "$ref": "#/definitions/typeName" } },
+            "allOf": by_type("valueType", &items),
+        });
+        if let Some(max) = max_items {
+            list["properties"]["items"]["maxItems"] = max.into();
+        }
+        list
+    };
+    let class = serde_json::json!({
+        "type": "object",
+        "required": ["items", "name"],
+        "properties": {
+            "name": { "$ref": "#/definitions/hash" },
+            "items": { "type": "array", "items": { "$ref": "#/definitions/field" } },
         },
-        BinValue::Flag(v) => Value::Bool(*v),
-        
-        BinValue::List { value_type, items } | BinValue::List2 { value_type, items } => {
-            let mut map = Map::new();
-            map.insert("valueType".to_string(), Value::String(get_bin_type_name(*value_type).to_string()));
-            let json_items: Vec<Value> = items.iter().map(|i| bin_value_to_json(i)).collect();
-            map.insert("items".to_string(), Value::Array(json_items));
-            Value::Object(map)
+    });
+    let map_items = |part: &'static str| move |r: Value| serde_json::json!({ "properties": { "items": { "items": { "properties": { part: r } } } } });
+    let mut field_types = names.clone();
+    field_types.push("raw");
+
+    let definitions = serde_json::json!({
+        "typeName": { "enum": names },
+        "hexString": { "type": "string", "pattern": "^0[xX][0-9a-fA-F]{1,16}$" },
+        "section": {
+            "type": "object",
+            "required": ["type", "value"],
+            "properties": { "type": { "$ref": "#/definitions/typeName" }, "value": {} },
+            "allOf": by_type("type", &value),
         },
-        BinValue::Option { value_type, item } => {
-            let mut map = Map::new();
-            map.insert("valueType".to_string(), Value::String(get_bin_type_name(*value_type).to_string()));
-            let mut json_items = Vec::new();
-            if let Some(inner) = item {
-                json_items.push(bin_value_to_json(inner));
-            }
-            map.insert("items".to_string(), Value::Array(json_items));
-            Value::Object(map)
+        "field": {
+            "type": "object",
+            "required": ["key", "type", "value"],
+            "properties": { "key": { "$ref": "#/definitions/hash" }, "type": { "enum": field_types }, "value": {} },
+            "allOf": by_type("type", &value).into_iter().chain([serde_json::json!({
+                "if": { "required": ["type"], "properties": { "type": { "const": "raw" } } },
+                "then": value(serde_json::json!({ "$ref": "#/definitions/raw" })),
+            })]).collect::<Vec<_>>(),
         },
-        BinValue::Map { key_type, value_type, items } => {
-            let mut map = Map::new();
-            map.insert("keyType".to_string(), Value::String(get_bin_type_name(*key_type).to_string()));
-            map.insert("valueType".to_string(), Value::String(get_bin_type_name(*value_type).to_string()));
-            let mut json_items = Vec::new();
-            for (k, v) in items {
-                let mut item_map = Map::new();
-                item_map.insert("key".to_string(), bin_value_to_json(k));
-                item_map.insert("value".to_string(), bin_value_to_json(v));
-                json_items.push(Value::Object(item_map));
-            }
-            map.insert("items".to_string(), Value::Array(json_items));
-            Value::Object(map)
+        "raw": {
+            "type": "object",
+            "required": ["bytes", "typeByte"],
+            "properties": {
+                "absorbedFields": { "type": "integer", "minimum": 0, "maximum": u16::MAX },
+                "bytes": { "type": "string", "pattern": "^([0-9a-f]{2})*$" },
+                "typeByte": { "type": "integer", "minimum": 0, "maximum": u8::MAX },
+            },
         },
-        BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
-            let mut map = Map::new();
-            if let Some(s) = name_str {
-                map.insert("name".to_string(), Value::String(s.clone()));
-            } else {
-                map.insert("name".to_string(), Value::Number((*name).into()));
-            }
-            let mut json_items = Vec::new();
-            for field in items {
-                let mut field_map = Map::new();
-                if let Some(s) = &field.key_str {
-                    field_map.insert("key".to_string(), Value::String(s.clone()));
-                } else {
-                    field_map.insert("key".to_string(), Value::Number(field.key.into()));
-                }
-                field_map.insert("type".to_string(), Value::String(get_type_name(&field.value).to_string()));
-                field_map.insert("value".to_string(), bin_value_to_json(&field.value));
-                json_items.push(Value::Object(field_map));
-            }
-            map.insert("items".to_string(), Value::Array(json_items));
-            Value::Object(map)
+        "none": { "type": "null" },
+        "bool": { "type": "boolean" },
+        "flag": { "type": "boolean" },
+        "i8": integer(i8::MIN.into(), i8::MAX.into()),
+        "u8": integer(0, u8::MAX.into()),
+        "i16": integer(i16::MIN.into(), i16::MAX.into()),
+        "u16": integer(0, u16::MAX.into()),
+        "i32": integer(i32::MIN.into(), i32::MAX.into()),
+        "u32": integer(0, u32::MAX.into()),
+        "i64": integer(i64::MIN.into(), i64::MAX.into()),
+        "u64": integer(0, u64::MAX.into()),
+        // Infinities and NaN are written as null
+        "f32": { "type": ["number", "null"] },
+        "vec2": floats(2),
+        "vec3": floats(3),
+        "vec4": floats(4),
+        "mtx44": floats(16),
+        "rgba": { "type": "array", "items": { "$ref": "#/definitions/u8" }, "minItems": 4, "maxItems": 4 },
+        "string": { "type": "string" },
+        "hash": { "anyOf": [{ "type": "string" }, { "type": "integer", "minimum": 0, "maximum": u32::MAX }] },
+        "link": { "$ref": "#/definitions/hash" },
+        "file": { "anyOf": [{ "type": "string" }, { "type": "integer", "minimum": 0, "maximum": u64::MAX }] },
+        "list": list(None),
+        "list2": list(None),
+        "option": list(Some(1)),
+        "map": {
+            "type": "object",
+            "required": ["items", "keyType", "valueType"],
+            "properties": {
+                "items": { "type": "array", "items": { "type": "object", "required": ["key", "value"] } },
+                "keyType": { "$ref": "#/definitions/typeName" },
+                "valueType": { "$ref": "#/definitions/typeName" },
+            },
+            "allOf": by_type("keyType", &map_items("key")).into_iter().chain(by_type("valueType", &map_items("value"))).collect::<Vec<_>>(),
         },
+        "pointer": class,
+        "embed": { "$ref": "#/definitions/pointer" },
+    });
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "ritobin bin JSON",
+        "type": "object",
+        "additionalProperties": { "$ref": "#/definitions/section" },
+        "definitions": definitions,
+    })
+}
+
+// The writer serializes the bin directly rather than through `Value`, so
+// nothing the size of the output is built. Object keys are written in
+// sorted order, as `Value`'s map would hold them.
+
+/// A bin as the root object: `{ "section": { "type": ..., "value": ... } }`
+struct JsonBin<'a>(&'a Bin, JsonWriteOptions);
+
+impl Serialize for JsonBin<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut sections: Vec<_> = self.0.sections.iter().collect();
+        sections.sort_by(|a, b| a.0.cmp(b.0));
+        let mut map = serializer.serialize_map(Some(sections.len()))?;
+        for (key, value) in sections {
+            map.serialize_entry(key, &JsonSection(value, self.1))?;
+        }
+        map.end()
+    }
+}
+
+struct JsonSection<'a>(&'a BinValue, JsonWriteOptions);
+
+impl Serialize for JsonSection<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("type", get_type_name(self.0))?;
+        map.serialize_entry("value", &JsonValue(self.0, self.1))?;
+        map.end()
+    }
+}
+
+struct JsonValue<'a>(&'a BinValue, JsonWriteOptions);
+
+impl Serialize for JsonValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let opts = self.1;
+        match self.0 {
+            BinValue::None => serializer.serialize_unit(),
+            BinValue::Bool(v) | BinValue::Flag(v) => serializer.serialize_bool(*v),
+            BinValue::I8(v) => serializer.serialize_i8(*v),
+            BinValue::U8(v) => serializer.serialize_u8(*v),
+            BinValue::I16(v) => serializer.serialize_i16(*v),
+            BinValue::U16(v) => serializer.serialize_u16(*v),
+            BinValue::I32(v) => serializer.serialize_i32(*v),
+            BinValue::U32(v) => serializer.serialize_u32(*v),
+            BinValue::I64(v) => serializer.serialize_i64(*v),
+            BinValue::U64(v) => serializer.serialize_u64(*v),
+            BinValue::F32(v) => JsonF32(*v).serialize(serializer),
+            BinValue::Vec2(v) => serializer.collect_seq(v.iter().map(|x| JsonF32(*x))),
+            BinValue::Vec3(v) => serializer.collect_seq(v.iter().map(|x| JsonF32(*x))),
+            BinValue::Vec4(v) => serializer.collect_seq(v.iter().map(|x| JsonF32(*x))),
+            BinValue::Mtx44(v) => serializer.collect_seq(v.iter().map(|x| JsonF32(*x))),
+            BinValue::Rgba(v) => serializer.collect_seq(v),
+            BinValue::String(v) => serializer.serialize_str(v),
+            BinValue::Hash { value, name } | BinValue::Link { value, name } => JsonName::u32(*value, name, opts).serialize(serializer),
+            BinValue::File { value, name } => JsonName { value: *value, digits: 16, name, opts }.serialize(serializer),
+            BinValue::Raw { type_byte, bytes, absorbed_fields } => {
+                let mut map = serializer.serialize_map(Some(3))?;
+                map.serialize_entry("absorbedFields", absorbed_fields)?;
+                map.serialize_entry("bytes", &crate::model::hex_encode(bytes))?;
+                map.serialize_entry("typeByte", type_byte)?;
+                map.end()
+            },
+            
+            BinValue::List { value_type, items } | BinValue::List2 { value_type, items } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("items", &JsonItems(items, opts))?;
+                map.serialize_entry("valueType", get_bin_type_name(*value_type))?;
+                map.end()
+            },
+            BinValue::Option { value_type, item } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("items", &JsonItems(item.as_deref().map(std::slice::from_ref).unwrap_or_default(), opts))?;
+                map.serialize_entry("valueType", get_bin_type_name(*value_type))?;
+                map.end()
+            },
+            BinValue::Map { key_type, value_type, items } => {
+                let mut map = serializer.serialize_map(Some(3))?;
+                map.serialize_entry("items", &JsonMapItems(items, opts))?;
+                map.serialize_entry("keyType", get_bin_type_name(*key_type))?;
+                map.serialize_entry("valueType", get_bin_type_name(*value_type))?;
+                map.end()
+            },
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("items", &JsonFields(items, opts))?;
+                map.serialize_entry("name", &JsonName::u32(*name, name_str, opts))?;
+                map.end()
+            },
+        }
+    }
+}
+
+/// An f32, or `null` where JSON has no number for it
+struct JsonF32(f32);
+
+impl Serialize for JsonF32 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_finite() {
+            serializer.serialize_f64(self.0 as f64)
+        } else {
+            serializer.serialize_unit()
+        }
+    }
+}
+
+/// A hash as its name if it has one, else its value as a number or, with
+/// [`JsonWriteOptions::hex_hashes`], as `digits` hex digits
+struct JsonName<'a> {
+    value: u64,
+    digits: usize,
+    name: &'a Option<String>,
+    opts: JsonWriteOptions,
+}
+
+impl<'a> JsonName<'a> {
+    fn u32(value: u32, name: &'a Option<String>, opts: JsonWriteOptions) -> Self {
+        JsonName { value: value as u64, digits: 8, name, opts }
     }
 }
 
-fn json_to_bin_value(json: &Value, type_: BinType) -> Result<BinValue, String> {
+impl Serialize for JsonName<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name {
+            Some(name) => serializer.serialize_str(name),
+            None if self.opts.hex_hashes => serializer.collect_str(&format_args!("{:#0width$x}", self.value, width = self.digits + 2)),
+            None => serializer.serialize_u64(self.value),
+        }
+    }
+}
+
+struct JsonItems<'a>(&'a [BinValue], JsonWriteOptions);
+
+impl Serialize for JsonItems<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|item| JsonValue(item, self.1)))
+    }
+}
+
+struct JsonMapItems<'a>(&'a [(BinValue, BinValue)], JsonWriteOptions);
+
+impl Serialize for JsonMapItems<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|(key, value)| {
+            let mut item = std::collections::BTreeMap::new();
+            item.insert("key", JsonValue(key, self.1));
+            item.insert("value", JsonValue(value, self.1));
+            item
+        }))
+    }
+}
+
+struct JsonFields<'a>(&'a [Field], JsonWriteOptions);
+
+impl Serialize for JsonFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|field| JsonField(field, self.1)))
+    }
+}
+
+struct JsonField<'a>(&'a Field, JsonWriteOptions);
+
+impl Serialize for JsonField<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let field = self.0;
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("key", &JsonName::u32(field.key, &field.key_str, self.1))?;
+        map.serialize_entry("type", get_type_name(&field.value))?;
+        map.serialize_entry("value", &JsonValue(&field.value, self.1))?;
+        map.end()
+    }
+}
+
+fn json_to_bin_value(json: &Value, type_: BinType, opts: &JsonReadOptions, warnings: &mut Vec<String>) -> Result<BinValue, String> {
     match type_ {
         BinType::None => Ok(BinValue::None),
         BinType::Bool => Ok(BinValue::Bool(json.as_bool().ok_or("Expected bool")?)),
-        BinType::I8 => Ok(BinValue::I8(json.as_i64().ok_or("Expected number")? as i8)),
-        BinType::U8 => Ok(BinValue::U8(json.as_u64().ok_or("Expected number")? as u8)),
-        BinType::I16 => Ok(BinValue::I16(json.as_i64().ok_or("Expected number")? as i16)),
-        BinType::U16 => Ok(BinValue::U16(json.as_u64().ok_or("Expected number")? as u16)),
-        BinType::I32 => Ok(BinValue::I32(json.as_i64().ok_or("Expected number")? as i32)),
-        BinType::U32 => Ok(BinValue::U32(json.as_u64().ok_or("Expected number")? as u32)),
-        BinType::I64 => Ok(BinValue::I64(json.as_i64().ok_or("Expected number")?)),
-        BinType::U64 => Ok(BinValue::U64(json.as_u64().ok_or("Expected number")?)),
-        BinType::F32 => Ok(BinValue::F32(json.as_f64().ok_or("Expected number")? as f32)),
+        BinType::I8 => Ok(BinValue::I8(fit(json_to_i64(json)?, "i8", warnings, |v| v as i8))),
+        BinType::U8 => Ok(BinValue::U8(fit(json_to_u64(json)?, "u8", warnings, |v| v as u8))),
+        BinType::I16 => Ok(BinValue::I16(fit(json_to_i64(json)?, "i16", warnings, |v| v as i16))),
+        BinType::U16 => Ok(BinValue::U16(fit(json_to_u64(json)?, "u16", warnings, |v| v as u16))),
+        BinType::I32 => Ok(BinValue::I32(fit(json_to_i64(json)?, "i32", warnings, |v| v as i32))),
+        BinType::U32 => Ok(BinValue::U32(fit(json_to_u64(json)?, "u32", warnings, |v| v as u32))),
+        BinType::I64 => Ok(BinValue::I64(json_to_i64(json)?)),
+        BinType::U64 => Ok(BinValue::U64(json_to_u64(json)?)),
+        BinType::F32 => Ok(BinValue::F32(narrow_f32(json.as_f64().ok_or("Expected number")?, warnings))),
         BinType::Vec2 => {
             let arr = json.as_array().ok_or("Expected array")?;
             if arr.len() != 2 { return Err("Expected array of length 2".to_string()); }
-            Ok(BinValue::Vec2([arr[0].as_f64().unwrap_or(0.0) as f32, arr[1].as_f64().unwrap_or(0.0) as f32]))
+            Ok(BinValue::Vec2([narrow_f32(arr[0].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[1].as_f64().unwrap_or(0.0), warnings)]))
         },
         BinType::Vec3 => {
             let arr = json.as_array().ok_or("Expected array")?;
             if arr.len() != 3 { return Err("Expected array of length 3".to_string()); }
-            Ok(BinValue::Vec3([arr[0].as_f64().unwrap_or(0.0) as f32, arr[1].as_f64().unwrap_or(0.0) as f32, arr[2].as_f64().unwrap_or(0.0) as f32]))
+            Ok(BinValue::Vec3([narrow_f32(arr[0].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[1].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[2].as_f64().unwrap_or(0.0), warnings)]))
         },
         BinType::Vec4 => {
             let arr = json.as_array().ok_or("Expected array")?;
             if arr.len() != 4 { return Err("Expected array of length 4".to_string()); }
-            Ok(BinValue::Vec4([arr[0].as_f64().unwrap_or(0.0) as f32, arr[1].as_f64().unwrap_or(0.0) as f32, arr[2].as_f64().unwrap_or(0.0) as f32, arr[3].as_f64().unwrap_or(0.0) as f32]))
+            Ok(BinValue::Vec4([narrow_f32(arr[0].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[1].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[2].as_f64().unwrap_or(0.0), warnings), narrow_f32(arr[3].as_f64().unwrap_or(0.0), warnings)]))
         },
         BinType::Mtx44 => {
             let arr = json.as_array().ok_or("Expected array")?;
             if arr.len() != 16 { return Err("Expected array of length 16".to_string()); }
             let mut m = [0.0; 16];
-            for i in 0..16 { m[i] = arr[i].as_f64().unwrap_or(0.0) as f32; }
+            for i in 0..16 { m[i] = narrow_f32(arr[i].as_f64().unwrap_or(0.0), warnings); }
             Ok(BinValue::Mtx44(m))
         },
         BinType::Rgba => {
             let arr = json.as_array().ok_or("Expected array")?;
             if arr.len() != 4 { return Err("Expected array of length 4".to_string()); }
-            Ok(BinValue::Rgba([arr[0].as_u64().unwrap_or(0) as u8, arr[1].as_u64().unwrap_or(0) as u8, arr[2].as_u64().unwrap_or(0) as u8, arr[3].as_u64().unwrap_or(0) as u8]))
+            let mut rgba = [0u8; 4];
+            for (channel, v) in rgba.iter_mut().zip(arr) {
+                *channel = fit(json_to_u64(v).unwrap_or(0), "u8", warnings, |v| v as u8);
+            }
+            Ok(BinValue::Rgba(rgba))
         },
         BinType::String => Ok(BinValue::String(json.as_str().ok_or("Expected string")?.to_string())),
         BinType::Hash => {
-            if let Some(s) = json.as_str() {
-                Ok(BinValue::Hash { value: crate::hash::fnv1a(s), name: Some(s.to_string()) })
-            } else {
-                Ok(BinValue::Hash { value: json.as_u64().ok_or("Expected hash")? as u32, name: None })
-            }
+            let (value, name) = json_to_hash(json, |s| crate::hash::fnv1a(s) as u64, "Expected hash")?;
+            Ok(BinValue::Hash { value: fit(value, "hash", warnings, |v| v as u32), name })
         },
         BinType::File => {
-            if let Some(s) = json.as_str() {
-                Ok(BinValue::File { value: crate::hash::Xxh64::new(s).0, name: Some(s.to_string()) })
-            } else {
-                Ok(BinValue::File { value: json.as_u64().ok_or("Expected file hash")?, name: None })
-            }
+            let (value, name) = json_to_hash(json, |s| crate::hash::Xxh64::new(s).0, "Expected file hash")?;
+            Ok(BinValue::File { value, name })
         },
         BinType::Link => {
-            if let Some(s) = json.as_str() {
-                Ok(BinValue::Link { value: crate::hash::fnv1a(s), name: Some(s.to_string()) })
-            } else {
-                Ok(BinValue::Link { value: json.as_u64().ok_or("Expected link hash")? as u32, name: None })
-            }
+            let (value, name) = json_to_hash(json, |s| crate::hash::fnv1a(s) as u64, "Expected link hash")?;
+            Ok(BinValue::Link { value: fit(value, "link", warnings, |v| v as u32), name })
         },
         BinType::Flag => Ok(BinValue::Flag(json.as_bool().ok_or("Expected bool")?)),
         
         BinType::List | BinType::List2 => {
             let obj = json.as_object().ok_or("Expected object for list")?;
             let value_type_str = obj.get("valueType").and_then(|v| v.as_str()).ok_or("Missing valueType")?;
-            let value_type = BinType::from_str(value_type_str).map_err(|_| "Unknown valueType")?;
+            let value_type = parse_type(value_type_str, opts).ok_or("Unknown valueType")?;
             let items_arr = obj.get("items").and_then(|v| v.as_array()).ok_or("Missing items")?;
             let mut items = Vec::new();
             for item in items_arr {
-                items.push(json_to_bin_value(item, value_type)?);
+                items.push(json_to_bin_value(item, value_type, opts, warnings)?);
             }
             if type_ == BinType::List {
                 Ok(BinValue::List { value_type, items })
@@ -210,12 +514,12 @@ fn json_to_bin_value(json: &Value, type_: BinType) -> Result<BinValue, String> {
         BinType::Option => {
             let obj = json.as_object().ok_or("Expected object for option")?;
             let value_type_str = obj.get("valueType").and_then(|v| v.as_str()).ok_or("Missing valueType")?;
-            let value_type = BinType::from_str(value_type_str).map_err(|_| "Unknown valueType")?;
+            let value_type = parse_type(value_type_str, opts).ok_or("Unknown valueType")?;
             let items_arr = obj.get("items").and_then(|v| v.as_array()).ok_or("Missing items")?;
             let item = if items_arr.is_empty() {
                 None
             } else {
-                Some(Box::new(json_to_bin_value(&items_arr[0], value_type)?))
+                Some(Box::new(json_to_bin_value(&items_arr[0], value_type, opts, warnings)?))
             };
             Ok(BinValue::Option { value_type, item })
         },
@@ -223,14 +527,14 @@ fn json_to_bin_value(json: &Value, type_: BinType) -> Result<BinValue, String> {
             let obj = json.as_object().ok_or("Expected object for map")?;
             let key_type_str = obj.get("keyType").and_then(|v| v.as_str()).ok_or("Missing keyType")?;
             let value_type_str = obj.get("valueType").and_then(|v| v.as_str()).ok_or("Missing valueType")?;
-            let key_type = BinType::from_str(key_type_str).map_err(|_| "Unknown keyType")?;
-            let value_type = BinType::from_str(value_type_str).map_err(|_| "Unknown valueType")?;
+            let key_type = parse_type(key_type_str, opts).ok_or("Unknown keyType")?;
+            let value_type = parse_type(value_type_str, opts).ok_or("Unknown valueType")?;
             let items_arr = obj.get("items").and_then(|v| v.as_array()).ok_or("Missing items")?;
             let mut items = Vec::new();
             for item in items_arr {
                 let item_obj = item.as_object().ok_or("Expected object for map item")?;
-                let k = json_to_bin_value(item_obj.get("key").ok_or("Missing key")?, key_type)?;
-                let v = json_to_bin_value(item_obj.get("value").ok_or("Missing value")?, value_type)?;
+                let k = json_to_bin_value(item_obj.get("key").ok_or("Missing key")?, key_type, opts, warnings)?;
+                let v = json_to_bin_value(item_obj.get("value").ok_or("Missing value")?, value_type, opts, warnings)?;
                 items.push((k, v));
             }
             Ok(BinValue::Map { key_type, value_type, items })
@@ -238,26 +542,25 @@ fn json_to_bin_value(json: &Value, type_: BinType) -> Result<BinValue, String> {
         BinType::Pointer | BinType::Embed => {
             let obj = json.as_object().ok_or("Expected object for class")?;
             let name_json = obj.get("name").ok_or("Missing name")?;
-            let (name, name_str) = if let Some(s) = name_json.as_str() {
-                (crate::hash::fnv1a(s), Some(s.to_string()))
-            } else {
-                (name_json.as_u64().unwrap_or(0) as u32, None)
-            };
+            let (name, name_str) = json_to_hash(name_json, |s| crate::hash::fnv1a(s) as u64, "Expected class hash")?;
+            let name = fit(name, "class hash", warnings, |v| v as u32);
             
             let items_arr = obj.get("items").and_then(|v| v.as_array()).ok_or("Missing items")?;
             let mut items = Vec::new();
             for item in items_arr {
                 let item_obj = item.as_object().ok_or("Expected object for field")?;
                 let key_json = item_obj.get("key").ok_or("Missing key")?;
-                let (key, key_str) = if let Some(s) = key_json.as_str() {
-                    (crate::hash::fnv1a(s), Some(s.to_string()))
-                } else {
-                    (key_json.as_u64().unwrap_or(0) as u32, None)
-                };
+                let (key, key_str) = json_to_hash(key_json, |s| crate::hash::fnv1a(s) as u64, "Expected field hash")?;
+                let key = fit(key, "field hash", warnings, |v| v as u32);
                 
                 let type_str = item_obj.get("type").and_then(|v| v.as_str()).ok_or("Missing field type")?;
-                let field_type = BinType::from_str(type_str).map_err(|_| "Unknown field type")?;
-                let value = json_to_bin_value(item_obj.get("value").ok_or("Missing value")?, field_type)?;
+                let value_json = item_obj.get("value").ok_or("Missing value")?;
+                let value = if type_str == "raw" {
+                    json_to_raw(value_json)?
+                } else {
+                    let field_type = parse_type(type_str, opts).ok_or("Unknown field type")?;
+                    json_to_bin_value(value_json, field_type, opts, warnings)?
+                };
                 
                 items.push(Field { key, key_str, value });
             }
@@ -271,6 +574,82 @@ fn json_to_bin_value(json: &Value, type_: BinType) -> Result<BinValue, String> {
     }
 }
 
+/// A type name as written by this crate or, with
+/// [`JsonReadOptions::ritobin_compat`], by the C++ ritobin
+fn parse_type(name: &str, opts: &JsonReadOptions) -> Option<BinType> {
+    if opts.ritobin_compat {
+        crate::text::type_from_name(name, true)
+    } else {
+        BinType::from_str(name).ok()
+    }
+}
+
+/// A hash given by name, as a `"0x…"` string or as a number; names are
+/// hashed with `hash_name`
+fn json_to_hash(json: &Value, hash_name: fn(&str) -> u64, expected: &str) -> Result<(u64, Option<String>), String> {
+    match json.as_str() {
+        Some(s) => Ok(match hex_number(s) {
+            Some(value) => (value, None),
+            None => (hash_name(s), Some(s.to_string())),
+        }),
+        None => Ok((json.as_u64().ok_or(expected)?, None)),
+    }
+}
+
+/// An unsigned integer written as a number or a `"0x…"` string
+fn json_to_u64(json: &Value) -> Result<u64, String> {
+    match json.as_str() {
+        Some(s) => hex_number(s).ok_or_else(|| format!("Expected number or 0x hex string, found \"{}\"", s)),
+        None => json.as_u64().ok_or_else(|| "Expected number".to_string()),
+    }
+}
+
+/// A signed integer written as a number or as a `"0x…"` string of its
+/// (non-negative) value
+fn json_to_i64(json: &Value) -> Result<i64, String> {
+    match json.as_str() {
+        Some(s) => hex_number(s).and_then(|v| i64::try_from(v).ok()).ok_or_else(|| format!("Expected number or 0x hex string, found \"{}\"", s)),
+        None => json.as_i64().ok_or_else(|| "Expected number".to_string()),
+    }
+}
+
+/// The value of a `"0x…"` string of up to 16 hex digits
+fn hex_number(s: &str) -> Option<u64> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    let valid = !digits.is_empty() && digits.len() <= 16 && digits.bytes().all(|b| b.is_ascii_hexdigit());
+    valid.then(|| u64::from_str_radix(digits, 16).ok()).flatten()
+}
+
+/// `value` as `T`, wrapped by `wrap` and noted in `warnings` if it doesn't fit
+fn fit<W: Copy + std::fmt::Display, T: TryFrom<W> + std::fmt::Display>(value: W, type_name: &str, warnings: &mut Vec<String>, wrap: fn(W) -> T) -> T {
+    T::try_from(value).unwrap_or_else(|_| {
+        let wrapped = wrap(value);
+        warnings.push(format!("{} does not fit in {}, stored as {}", value, type_name, wrapped));
+        wrapped
+    })
+}
+
+/// `value` as f32, noting finite values too large for it in `warnings`
+fn narrow_f32(value: f64, warnings: &mut Vec<String>) -> f32 {
+    let narrowed = value as f32;
+    if narrowed.is_infinite() && value.is_finite() {
+        warnings.push(format!("{:e} does not fit in f32, stored as {}", value, narrowed));
+    }
+    narrowed
+}
+
+/// Read a preserved unknown value written as `{ typeByte, absorbedFields, bytes }`
+fn json_to_raw(json: &Value) -> Result<BinValue, String> {
+    let obj = json.as_object().ok_or("Expected object for raw value")?;
+    let type_byte = obj.get("typeByte").and_then(|v| v.as_u64()).and_then(|v| u8::try_from(v).ok()).ok_or("Missing typeByte")?;
+    let absorbed_fields = match obj.get("absorbedFields") {
+        Some(v) => v.as_u64().and_then(|v| u16::try_from(v).ok()).ok_or("Invalid absorbedFields")?,
+        None => 0,
+    };
+    let bytes = crate::model::hex_decode(obj.get("bytes").and_then(|v| v.as_str()).ok_or("Missing bytes")?)?;
+    Ok(BinValue::Raw { type_byte, bytes, absorbed_fields })
+}
+
 fn get_bin_type_name(t: BinType) -> &'static str {
     match t {
         BinType::None => "none",
@@ -332,6 +711,7 @@ fn get_type_name(v: &BinValue) -> &'static str {
         BinValue::Option { .. } => "option",
         BinValue::Map { .. } => "map",
         BinValue::Flag(_) => "flag",
+        BinValue::Raw { .. } => "raw",
     }
 }
 
@@ -366,4 +746,152 @@ mod tests {
             panic!("Expected List");
         }
     }
+
+    #[test]
+    fn test_read_json_warns_on_narrowed_numbers() {
+        let json = r#"{
+            "small": { "type": "u8", "value": 300 },
+            "color": { "type": "rgba", "value": [1, 2, 3, 256] },
+            "big": { "type": "f32", "value": 1e300 },
+            "fine": { "type": "i16", "value": -5 }
+        }"#;
+        let (bin, warnings) = read_json_with_warnings(json).unwrap();
+        assert_eq!(bin.sections["small"], BinValue::U8(44));
+        assert_eq!(bin.sections["color"], BinValue::Rgba([1, 2, 3, 0]));
+        assert_eq!(bin.sections["fine"], BinValue::I16(-5));
+        // Sections are read in key order
+        assert_eq!(warnings, [
+            "1e300 does not fit in f32, stored as inf",
+            "256 does not fit in u8, stored as 0",
+            "300 does not fit in u8, stored as 44",
+        ]);
+        assert_eq!(read_json(json).unwrap(), bin);
+    }
+
+    #[test]
+    fn test_json_streams() {
+        let mut bin = Bin::new();
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: 1, name: None },
+                BinValue::Embed {
+                    name: crate::hash::fnv1a("Thing"),
+                    name_str: Some("Thing".to_string()),
+                    items: vec![Field { key: 2, key_str: None, value: BinValue::Vec2([0.5, f32::NAN]) }],
+                },
+            )],
+        });
+
+        let mut streamed = Vec::new();
+        write_json_to(&bin, &JsonWriteOptions::default(), &mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed.clone()).unwrap(), write_json(&bin).unwrap());
+        let read = read_json_from(streamed.as_slice()).unwrap();
+        assert_eq!(read.sections["version"], BinValue::U32(3));
+        // NaN has no JSON number and reads back as 0
+        assert!(matches!(&read.sections["entries"], BinValue::Map { items, .. }
+            if matches!(&items[0].1, BinValue::Embed { items, .. } if items[0].value == BinValue::Vec2([0.5, 0.0]))));
+    }
+
+    #[test]
+    fn test_json_hex_hashes() {
+        let mut bin = Bin::new();
+        bin.sections.insert("file".to_string(), BinValue::File { value: 0xfedc_ba98_7654_3210, name: None });
+        bin.sections.insert("link".to_string(), BinValue::Link { value: 0xab, name: None });
+        bin.sections.insert("named".to_string(), BinValue::Hash { value: crate::hash::fnv1a("Name"), name: Some("Name".to_string()) });
+        bin.sections.insert("embed".to_string(), BinValue::Embed {
+            name: 0x1234,
+            name_str: None,
+            items: vec![Field { key: 0x5678, key_str: None, value: BinValue::Hash { value: 1, name: None } }],
+        });
+
+        let json = write_json_with_options(&bin, &JsonWriteOptions { hex_hashes: true }).unwrap();
+        for hex in ["\"0xfedcba9876543210\"", "\"0x000000ab\"", "\"0x00001234\"", "\"0x00005678\"", "\"0x00000001\"", "\"Name\""] {
+            assert!(json.contains(hex), "{} in {}", hex, json);
+        }
+        assert_eq!(read_json(&json).unwrap(), bin);
+        assert_eq!(read_json(&write_json(&bin).unwrap()).unwrap(), bin);
+        // Too many digits is a name, not a value
+        let long = r#"{ "h": { "type": "hash", "value": "0x12345678901234567" } }"#;
+        assert_eq!(read_json(long).unwrap().sections["h"], BinValue::Hash { value: crate::hash::fnv1a("0x12345678901234567"), name: Some("0x12345678901234567".to_string()) });
+    }
+
+    #[test]
+    fn test_read_json_hex_integers() {
+        let json = r#"{
+            "a": { "type": "u32", "value": "0x12345678" },
+            "b": { "type": "u64", "value": "0XFFFFFFFFFFFFFFFF" },
+            "c": { "type": "i16", "value": "0x7f" },
+            "d": { "type": "u8", "value": "0x1ff" },
+            "e": { "type": "rgba", "value": ["0xff", 0, "0x80", 255] }
+        }"#;
+        let (bin, warnings) = read_json_with_warnings(json).unwrap();
+        assert_eq!(bin.sections["a"], BinValue::U32(0x1234_5678));
+        assert_eq!(bin.sections["b"], BinValue::U64(u64::MAX));
+        assert_eq!(bin.sections["c"], BinValue::I16(0x7f));
+        assert_eq!(bin.sections["d"], BinValue::U8(0xff));
+        assert_eq!(bin.sections["e"], BinValue::Rgba([0xff, 0, 0x80, 0xff]));
+        assert_eq!(warnings, ["511 does not fit in u8, stored as 255"]);
+
+        let err = read_json(r#"{ "a": { "type": "i64", "value": "0x8000000000000000" } }"#).unwrap_err();
+        assert_eq!(err, "Expected number or 0x hex string, found \"0x8000000000000000\"");
+        assert!(read_json(r#"{ "a": { "type": "u32", "value": "12" } }"#).is_err());
+    }
+
+    #[test]
+    fn test_read_ritobin_cpp() {
+        // Laid out the way the C++ ritobin writes, with its older type names
+        let json = r#"{
+            "type": { "type": "string", "value": "PROP" },
+            "entries": { "type": "map", "value": {
+                "keyType": "hash",
+                "valueType": "embed",
+                "items": [{ "key": "0x0000000a", "value": { "name": "SkinData", "items": [
+                    { "key": "model", "type": "path", "value": "ASSETS/Ahri.skn" },
+                    { "key": "hidden", "type": "bitbool", "value": true },
+                    { "key": "extra", "type": "struct", "value": { "name": "SkinExtra", "items": [] } },
+                    { "key": "tags", "type": "List", "value": { "valueType": "STRING", "items": ["a"] } }
+                ] } }]
+            } }
+        }"#;
+        assert_eq!(read_json(json).unwrap_err(), "Unknown field type");
+        let bin = read_ritobin_cpp(json).unwrap();
+        let BinValue::Map { items, .. } = &bin.sections["entries"] else { panic!("entries is not a map") };
+        assert_eq!(items[0].0, BinValue::Hash { value: 10, name: None });
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!("entry is not an embed") };
+        let types: Vec<_> = fields.iter().map(|f| get_type_name(&f.value)).collect();
+        assert_eq!(types, ["file", "flag", "pointer", "list"]);
+    }
+
+    #[test]
+    fn test_schema_covers_every_type() {
+        let schema = schema();
+        let definitions = schema["definitions"].as_object().unwrap();
+        let names = schema["definitions"]["typeName"]["enum"].as_array().unwrap();
+        assert_eq!(names.len(), 27);
+        for name in names {
+            assert!(definitions.contains_key(name.as_str().unwrap()), "no definition for {}", name);
+        }
+
+        // Every reference points at a definition
+        fn refs<'a>(json: &'a Value, out: &mut Vec<&'a str>) {
+            match json {
+                Value::Object(map) => {
+                    out.extend(map.get("$ref").and_then(|r| r.as_str()));
+                    map.values().for_each(|v| refs(v, out));
+                }
+                Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+                _ => {}
+            }
+        }
+        let mut found = Vec::new();
+        refs(&schema, &mut found);
+        for r in found {
+            let name = r.strip_prefix("#/definitions/").unwrap();
+            assert!(definitions.contains_key(name), "dangling {}", r);
+        }
+        assert_eq!(schema["definitions"]["u64"]["anyOf"][0]["maximum"], u64::MAX);
+    }
 }
@@ -0,0 +1,414 @@
+//! A simplified JSON layout for reading and editing bins by hand.
+//!
+//! [`write_simple`] writes values the way one would write them in JSON
+//! without a bin in mind: structures become objects keyed by field name,
+//! lists become arrays and maps become objects keyed by their keys. The types
+//! this drops go to a second, companion file, which [`read_simple`] needs to
+//! rebuild the bin:
+//!
+//! ```json
+//! {
+//!   "classes": { "SkinData": [{ "name": "mScale", "type": "f32" }] },
+//!   "sections": { "entries": "map[hash,embed]", "version": "u32" }
+//! }
+//! ```
+//!
+//! Structures name their class in a `"__class"` key, and hashes without a
+//! name are written as `"0x…"` strings. The round trip loses what plain JSON
+//! can't hold: sections and map items come back sorted by key, fields in the
+//! order their class lists them, and non-finite floats as 0.
+
+use super::{fit, json_to_hash, json_to_raw, narrow_f32, JsonF32, JsonName, JsonWriteOptions};
+use crate::model::{Bin, BinType, BinValue, Field};
+use serde::ser::{Error as _, Serialize, SerializeMap, Serializer};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The key naming a structure's class
+const CLASS_KEY: &str = "__class";
+
+const HEX_HASHES: JsonWriteOptions = JsonWriteOptions { hex_hashes: true };
+
+/// The two files of a bin in the simplified layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleJson {
+    /// The values
+    pub data: String,
+    /// The types of sections and class fields, see the [module docs](self)
+    pub types: String,
+}
+
+/// The path of the types file that goes with the data file `json_path`
+pub fn types_path(json_path: &Path) -> PathBuf {
+    json_path.with_extension("types.json")
+}
+
+/// Write a bin in the simplified layout.
+///
+/// Fails if two structures of one class hold the same field with different
+/// types, or if a map is keyed by something other than a number, string or
+/// hash.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::json::simple::{read_simple, write_simple};
+/// use ritobin_rust::model::{Bin, BinType, BinValue};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("linked".to_string(), BinValue::List {
+///     value_type: BinType::String,
+///     items: vec![BinValue::String("common.bin".to_string())],
+/// });
+/// let simple = write_simple(&bin)?;
+/// assert!(simple.data.contains("\"linked\": [\n    \"common.bin\"\n  ]"));
+/// assert!(simple.types.contains("\"linked\": \"list[string]\""));
+/// assert_eq!(read_simple(&simple.data, &simple.types)?, bin);
+/// # Ok::<(), String>(())
+/// ```
+pub fn write_simple(bin: &Bin) -> Result<SimpleJson, String> {
+    let mut types = Types::default();
+    for (name, value) in &bin.sections {
+        types.sections.insert(name.clone(), type_string(value));
+        types.collect(value)?;
+    }
+    let data = serde_json::to_string_pretty(&SimpleBin(bin)).map_err(|e| e.to_string())?;
+    let types = serde_json::to_string_pretty(&types.to_json()).map_err(|e| e.to_string())?;
+    Ok(SimpleJson { data, types })
+}
+
+/// Read a bin from the data and types files written by [`write_simple`]
+pub fn read_simple(data: &str, types: &str) -> Result<Bin, String> {
+    let data: Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let types: Value = serde_json::from_str(types).map_err(|e| format!("Types: {}", e))?;
+    let data = data.as_object().ok_or("Root must be an object")?;
+    let types = types.as_object().ok_or("Types root must be an object")?;
+
+    let mut classes = HashMap::new();
+    if let Some(json) = types.get("classes") {
+        for (class, fields) in json.as_object().ok_or("Types classes must be an object")? {
+            let fields = fields.as_array().ok_or_else(|| format!("Fields of class {} must be an array", class))?;
+            let fields = fields.iter().map(|field| {
+                let name = field.get("name").and_then(|v| v.as_str());
+                let type_ = field.get("type").and_then(|v| v.as_str());
+                match (name, type_) {
+                    (Some(name), Some("raw")) => Ok((name.to_string(), None)),
+                    (Some(name), Some(type_)) => Ok((name.to_string(), Some(parse_type_string(type_)?))),
+                    _ => Err(format!("Fields of class {} need a name and type", class)),
+                }
+            }).collect::<Result<Vec<_>, String>>()?;
+            classes.insert(class.clone(), fields);
+        }
+    }
+    let reader = Reader { classes };
+
+    let sections = types.get("sections").and_then(|v| v.as_object()).ok_or("Types missing sections")?;
+    if let Some(name) = data.keys().find(|name| !sections.contains_key(*name)) {
+        return Err(format!("Section {} has no type", name));
+    }
+    let mut bin = Bin::new();
+    for (name, type_) in sections {
+        let type_ = parse_type_string(type_.as_str().ok_or_else(|| format!("Type of section {} must be a string", name))?)?;
+        let json = data.get(name).ok_or_else(|| format!("Missing section {}", name))?;
+        let value = reader.value(json, type_).map_err(|e| format!("Section {}: {}", name, e))?;
+        bin.sections.insert(name.clone(), value);
+    }
+    crate::string_table::expand_strings(&mut bin)?;
+    Ok(bin)
+}
+
+/// A type as written in the types file: `u32`, `list[string]`, `map[hash,embed]`...
+#[derive(Debug, Clone, Copy)]
+struct TypeDesc {
+    type_: BinType,
+    key_type: BinType,
+    value_type: BinType,
+}
+
+impl TypeDesc {
+    fn of(type_: BinType) -> Self {
+        TypeDesc { type_, key_type: BinType::None, value_type: BinType::None }
+    }
+}
+
+fn type_string(value: &BinValue) -> String {
+    match value {
+        BinValue::List { value_type, .. } => format!("list[{}]", value_type.name()),
+        BinValue::List2 { value_type, .. } => format!("list2[{}]", value_type.name()),
+        BinValue::Option { value_type, .. } => format!("option[{}]", value_type.name()),
+        BinValue::Map { key_type, value_type, .. } => format!("map[{},{}]", key_type.name(), value_type.name()),
+        value => super::get_type_name(value).to_string(),
+    }
+}
+
+fn parse_type_string(s: &str) -> Result<TypeDesc, String> {
+    let unknown = || format!("Unknown type: {}", s);
+    let name = |s: &str| BinType::from_str(s.trim()).map_err(|_| unknown());
+    let Some((outer, inner)) = s.strip_suffix(']').and_then(|s| s.split_once('[')) else {
+        return name(s).map(TypeDesc::of);
+    };
+    let type_ = name(outer)?;
+    match (type_, inner.split_once(',')) {
+        (BinType::List | BinType::List2 | BinType::Option, None) => {
+            Ok(TypeDesc { value_type: name(inner)?, ..TypeDesc::of(type_) })
+        }
+        (BinType::Map, Some((key, value))) => Ok(TypeDesc { type_, key_type: name(key)?, value_type: name(value)? }),
+        _ => Err(unknown()),
+    }
+}
+
+/// The section types and the fields of each class, in the order they were seen
+#[derive(Default)]
+struct Types {
+    sections: BTreeMap<String, String>,
+    classes: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl Types {
+    fn collect(&mut self, value: &BinValue) -> Result<(), String> {
+        match value {
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                items.iter().try_for_each(|item| self.collect(item))
+            }
+            BinValue::Option { item: Some(item), .. } => self.collect(item),
+            BinValue::Map { items, .. } => items.iter().try_for_each(|(_, v)| self.collect(v)),
+            BinValue::Pointer { name: 0, items, .. } if items.is_empty() => Ok(()),
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                let class = hash_label(*name, name_str);
+                let fields = self.classes.entry(class.clone()).or_default();
+                // A field missing from earlier structures goes after the one it follows here
+                let mut next = 0;
+                for field in items {
+                    let label = crate::compare::field_label(field);
+                    let type_ = type_string(&field.value);
+                    match fields.iter().position(|(name, _)| *name == label) {
+                        Some(i) if fields[i].1 != type_ => {
+                            return Err(format!("Field {} of class {} is both {} and {}", label, class, fields[i].1, type_));
+                        }
+                        Some(i) => next = next.max(i + 1),
+                        None => {
+                            fields.insert(next, (label, type_));
+                            next += 1;
+                        }
+                    }
+                }
+                items.iter().try_for_each(|field| self.collect(&field.value))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let classes: Map<String, Value> = self.classes.iter().map(|(class, fields)| {
+            let fields = fields.iter().map(|(name, type_)| json!({ "name": name, "type": type_ })).collect();
+            (class.clone(), Value::Array(fields))
+        }).collect();
+        json!({ "classes": classes, "sections": self.sections })
+    }
+}
+
+struct SimpleBin<'a>(&'a Bin);
+
+impl Serialize for SimpleBin<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut sections: Vec<_> = self.0.sections.iter().collect();
+        sections.sort_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_map(sections.into_iter().map(|(name, value)| (name, SimpleValue(value))))
+    }
+}
+
+struct SimpleValue<'a>(&'a BinValue);
+
+impl Serialize for SimpleValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                serializer.collect_seq(items.iter().map(SimpleValue))
+            }
+            BinValue::Option { item: None, .. } => serializer.serialize_unit(),
+            BinValue::Option { item: Some(item), .. } => SimpleValue(item).serialize(serializer),
+            BinValue::Map { items, .. } => {
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for (key, value) in items {
+                    map.serialize_entry(&key_string(key).map_err(S::Error::custom)?, &SimpleValue(value))?;
+                }
+                map.end()
+            }
+            BinValue::Pointer { name: 0, items, .. } if items.is_empty() => serializer.serialize_unit(),
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                let mut map = serializer.serialize_map(Some(items.len() + 1))?;
+                map.serialize_entry(CLASS_KEY, &JsonName::u32(*name, name_str, HEX_HASHES))?;
+                for field in items {
+                    map.serialize_entry(&crate::compare::field_label(field), &SimpleValue(&field.value))?;
+                }
+                map.end()
+            }
+            BinValue::F32(v) => JsonF32(*v).serialize(serializer),
+            BinValue::Hash { value, name } | BinValue::Link { value, name } => JsonName::u32(*value, name, HEX_HASHES).serialize(serializer),
+            BinValue::File { value, name } => JsonName { value: *value, digits: 16, name, opts: HEX_HASHES }.serialize(serializer),
+            value => super::JsonValue(value, HEX_HASHES).serialize(serializer),
+        }
+    }
+}
+
+/// A hash's name, or its value in hex
+fn hash_label(value: u32, name: &Option<String>) -> String {
+    name.clone().unwrap_or_else(|| format!("{:#010x}", value))
+}
+
+/// A map key as an object key
+fn key_string(key: &BinValue) -> Result<String, String> {
+    Ok(match key {
+        BinValue::Hash { value, name } | BinValue::Link { value, name } => hash_label(*value, name),
+        BinValue::File { value, name } => name.clone().unwrap_or_else(|| format!("{:#018x}", value)),
+        BinValue::String(s) => s.clone(),
+        BinValue::Bool(v) => v.to_string(),
+        BinValue::I8(v) => v.to_string(),
+        BinValue::U8(v) => v.to_string(),
+        BinValue::I16(v) => v.to_string(),
+        BinValue::U16(v) => v.to_string(),
+        BinValue::I32(v) => v.to_string(),
+        BinValue::U32(v) => v.to_string(),
+        BinValue::I64(v) => v.to_string(),
+        BinValue::U64(v) => v.to_string(),
+        key => return Err(format!("Can't write a {} map key as an object key", super::get_type_name(key))),
+    })
+}
+
+struct Reader {
+    /// Each class's fields in order, typed `None` for raw fields
+    classes: HashMap<String, Vec<(String, Option<TypeDesc>)>>,
+}
+
+impl Reader {
+    fn value(&self, json: &Value, desc: TypeDesc) -> Result<BinValue, String> {
+        // Numbers that don't fit are wrapped as the full reader does, just not reported
+        let warnings = &mut Vec::new();
+        match desc.type_ {
+            BinType::F32 if json.is_null() => Ok(BinValue::F32(0.0)),
+            BinType::F32 => Ok(BinValue::F32(narrow_f32(json.as_f64().ok_or("Expected number")?, warnings))),
+            BinType::List | BinType::List2 => {
+                let items = json.as_array().ok_or("Expected array")?
+                    .iter()
+                    .map(|item| self.value(item, TypeDesc::of(desc.value_type)))
+                    .collect::<Result<_, _>>()?;
+                Ok(if desc.type_ == BinType::List {
+                    BinValue::List { value_type: desc.value_type, items }
+                } else {
+                    BinValue::List2 { value_type: desc.value_type, items }
+                })
+            }
+            BinType::Option => {
+                let item = match json {
+                    Value::Null => None,
+                    json => Some(Box::new(self.value(json, TypeDesc::of(desc.value_type))?)),
+                };
+                Ok(BinValue::Option { value_type: desc.value_type, item })
+            }
+            BinType::Map => {
+                let items = json.as_object().ok_or("Expected object for map")?
+                    .iter()
+                    .map(|(key, value)| Ok((key_value(key, desc.key_type)?, self.value(value, TypeDesc::of(desc.value_type))?)))
+                    .collect::<Result<_, String>>()?;
+                Ok(BinValue::Map { key_type: desc.key_type, value_type: desc.value_type, items })
+            }
+            BinType::Pointer if json.is_null() => Ok(BinValue::Pointer { name: 0, name_str: None, items: Vec::new() }),
+            BinType::Pointer | BinType::Embed => {
+                let obj = json.as_object().ok_or("Expected object for class")?;
+                let class = obj.get(CLASS_KEY).and_then(|v| v.as_str()).ok_or("Missing __class")?;
+                let (name, name_str) = json_to_hash(&Value::from(class), |s| crate::hash::fnv1a(s) as u64, "Expected class hash")?;
+                let name = fit(name, "class hash", warnings, |v| v as u32);
+                let fields = self.classes.get(class).map(Vec::as_slice).unwrap_or_default();
+                if let Some(key) = obj.keys().find(|key| *key != CLASS_KEY && !fields.iter().any(|(name, _)| name == *key)) {
+                    return Err(format!("Class {} has no field {}", class, key));
+                }
+                let mut items = Vec::new();
+                for (field, type_) in fields {
+                    let Some(json) = obj.get(field) else { continue };
+                    let (key, key_str) = json_to_hash(&Value::from(field.as_str()), |s| crate::hash::fnv1a(s) as u64, "Expected field hash")?;
+                    let value = match type_ {
+                        Some(type_) => self.value(json, *type_).map_err(|e| format!("{}.{}: {}", class, field, e))?,
+                        None => json_to_raw(json)?,
+                    };
+                    items.push(Field { key: fit(key, "field hash", warnings, |v| v as u32), key_str, value });
+                }
+                Ok(if desc.type_ == BinType::Pointer {
+                    BinValue::Pointer { name, name_str, items }
+                } else {
+                    BinValue::Embed { name, name_str, items }
+                })
+            }
+            type_ => super::json_to_bin_value(json, type_, &super::JsonReadOptions::default(), warnings),
+        }
+    }
+}
+
+/// A map key read back from its object key
+fn key_value(key: &str, key_type: BinType) -> Result<BinValue, String> {
+    let number = || Value::from_str(key).map_err(|_| format!("Expected {} map key, found \"{}\"", key_type.name(), key));
+    match key_type {
+        BinType::String => Ok(BinValue::String(key.to_string())),
+        BinType::Hash | BinType::File | BinType::Link => {
+            super::json_to_bin_value(&Value::from(key), key_type, &super::JsonReadOptions::default(), &mut Vec::new())
+        }
+        _ => super::json_to_bin_value(&number()?, key_type, &super::JsonReadOptions::default(), &mut Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_simple_round_trip() {
+        let embed = |items: Vec<Field>| BinValue::Embed {
+            name: crate::hash::fnv1a("SkinData"),
+            name_str: Some("SkinData".to_string()),
+            items,
+        };
+        let field = |name: &str, value| Field { key: crate::hash::fnv1a(name), key_str: Some(name.to_string()), value };
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![
+                (BinValue::Hash { value: 1, name: None }, embed(vec![
+                    field("mScale", BinValue::F32(1.5)),
+                    field("mIcon", BinValue::Pointer { name: 0, name_str: None, items: vec![] }),
+                ])),
+                (BinValue::Hash { value: 2, name: None }, embed(vec![
+                    field("mScale", BinValue::F32(2.0)),
+                    field("mTags", BinValue::Map {
+                        key_type: BinType::U32,
+                        value_type: BinType::String,
+                        items: vec![(BinValue::U32(7), BinValue::String("seven".to_string()))],
+                    }),
+                    Field { key: 0xabc, key_str: None, value: BinValue::File { value: 0xdef, name: None } },
+                    field("mIcon", BinValue::Pointer { name: 5, name_str: None, items: vec![] }),
+                ])),
+            ],
+        });
+
+        let simple = write_simple(&bin).unwrap();
+        let data: Value = serde_json::from_str(&simple.data).unwrap();
+        assert_eq!(data["entries"]["0x00000001"], json!({ "__class": "SkinData", "mScale": 1.5, "mIcon": null }));
+        assert_eq!(data["entries"]["0x00000002"]["mTags"], json!({ "7": "seven" }));
+        assert_eq!(data["entries"]["0x00000002"]["0x00000abc"], json!("0x0000000000000def"));
+        let types: Value = serde_json::from_str(&simple.types).unwrap();
+        assert_eq!(types["sections"]["entries"], "map[hash,embed]");
+        // The field only the second structure has goes where it had it
+        let names: Vec<_> = types["classes"]["SkinData"].as_array().unwrap().iter().map(|f| f["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["mScale", "mTags", "0x00000abc", "mIcon"]);
+        assert_eq!(read_simple(&simple.data, &simple.types).unwrap(), bin);
+
+        // Fields of one class must keep their type
+        if let BinValue::Map { items, .. } = bin.sections.get_mut("entries").unwrap() {
+            if let BinValue::Embed { items, .. } = &mut items[1].1 {
+                items[0].value = BinValue::U32(2);
+            }
+        }
+        assert_eq!(write_simple(&bin).unwrap_err(), "Field mScale of class SkinData is both f32 and u32");
+    }
+}
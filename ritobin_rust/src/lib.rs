@@ -72,5 +72,27 @@ pub mod text;
 pub mod unhash;
 pub mod json;
 pub mod hash_binary;
+pub mod signature;
+pub mod profile;
+pub mod string_table;
+pub mod compare;
+pub mod rekey;
+pub mod format;
+pub mod manifest;
+pub mod preview;
+pub mod discovery;
+pub mod paths;
+pub mod edit;
+pub mod migrate;
+pub mod corpus;
+pub mod annotations;
+pub mod progress;
+pub mod cache;
+pub mod bundle;
+pub mod hash_report;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "wad")]
+pub mod wad;
 
 pub use model::Bin;
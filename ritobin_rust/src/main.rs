@@ -1,13 +1,167 @@
 use clap::{Parser, Subcommand, ValueEnum};
 use std::path::{Path, PathBuf};
-use ritobin_rust::binary::{read_bin, write_bin};
+use ritobin_rust::binary::{read_bin, read_bin_recover, read_bin_with_options, read_bin_with_report, read_bin_with_warnings, BinError, DuplicateKeys, ReadOptions, WriteOptions};
+use ritobin_rust::format::{BinFormat, BinaryFormat, FormatError, FormatRegistry, JsonFormat, TextFormat};
+use ritobin_rust::json::{read_json_with_options, JsonReadOptions, JsonWriteOptions};
+use ritobin_rust::text::{DuplicateSections, HashComments, TextWriteOptions};
+use ritobin_rust::discovery::{self, HashDiscovery};
+use ritobin_rust::paths::long_path;
+use ritobin_rust::preview::{self, PreviewOptions};
+use ritobin_rust::progress::Event;
 use walkdir::WalkDir;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
-enum Format {
-    Bin,
-    Json,
-    Text,
+/// A registered file format
+type Format = &'static dyn BinFormat;
+
+/// Formats for this run, set once in `main`
+static FORMATS: std::sync::OnceLock<FormatRegistry> = std::sync::OnceLock::new();
+
+/// The `--schema` types, also used outside the text format by `--recover`
+static SCHEMA: std::sync::OnceLock<ritobin_rust::text::TextSchema> = std::sync::OnceLock::new();
+
+/// Warnings printed so far, reported together with `--strict`
+static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Print a warning and keep it for the `--strict` report
+fn warn(message: String) {
+    eprintln!("Warning: {}", message);
+    WARNINGS.lock().unwrap().push(message);
+}
+
+fn warning_count() -> usize {
+    WARNINGS.lock().unwrap().len()
+}
+
+/// With `--strict`, fail listing every warning printed during the run
+fn strict_report(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let warnings = WARNINGS.lock().unwrap();
+    if !cli.strict || warnings.is_empty() {
+        return Ok(());
+    }
+    eprintln!("\n=== {} warning(s) with --strict ===", warnings.len());
+    for warning in warnings.iter() {
+        eprintln!("  {}", warning);
+    }
+    Err(format!("{} warning(s) with --strict", warnings.len()).into())
+}
+
+fn formats() -> &'static FormatRegistry {
+    FORMATS.get_or_init(FormatRegistry::with_defaults)
+}
+
+/// Look up a registered format by name
+fn format_named(name: &str) -> Result<Format, String> {
+    formats().get(name).ok_or_else(|| format!(
+        "Unknown format '{}' (available: {})", name, formats().names().join(", ")
+    ))
+}
+
+/// Which file extensions belong to which format.
+///
+/// User mappings (from `--ext-map` and `RITOBIN_EXTENSIONS`) take priority over
+/// the extensions each registered format declares. The last user mapping for a
+/// format also becomes the extension written for that format.
+#[derive(Debug, Clone)]
+struct ExtensionMap {
+    user: Vec<(String, &'static str)>,
+}
+
+impl ExtensionMap {
+    /// Parse a list of `EXT=FORMAT` mappings, e.g. `ritobin=text`
+    fn parse<'a>(specs: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut user = Vec::new();
+        for spec in specs {
+            let spec = spec.trim();
+            if spec.is_empty() {
+                continue;
+            }
+            let (ext, format) = spec.split_once('=')
+                .ok_or_else(|| format!("Invalid extension mapping '{}', expected EXT=FORMAT", spec))?;
+            let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+            if ext.is_empty() {
+                return Err(format!("Invalid extension mapping '{}', extension is empty", spec));
+            }
+            let format = format_named(format.trim())
+                .map_err(|e| format!("{} in extension mapping '{}'", e, spec))?;
+            user.push((ext, format.name()));
+        }
+        Ok(Self { user })
+    }
+
+    /// Format for a file extension, if it is mapped
+    fn format_for(&self, ext: &str) -> Option<Format> {
+        let ext = ext.to_ascii_lowercase();
+        match self.user.iter().rev().find(|(e, _)| *e == ext) {
+            Some((_, name)) => formats().get(name),
+            None => formats().by_extension(&ext),
+        }
+    }
+
+    /// Format for a path, based on its extension
+    fn format_for_path(&self, path: &Path) -> Option<Format> {
+        path.extension().and_then(|e| e.to_str()).and_then(|e| self.format_for(e))
+    }
+
+    /// Extension to write for a format
+    fn extension_for(&self, format: Format) -> &str {
+        self.user.iter().rev()
+            .find(|(_, name)| *name == format.name())
+            .map(|(e, _)| e.as_str())
+            .or_else(|| format.extensions().first().copied())
+            .unwrap_or_default()
+    }
+}
+
+/// Extension mappings for this run, set once in `main`
+static EXTENSIONS: std::sync::OnceLock<ExtensionMap> = std::sync::OnceLock::new();
+
+fn extensions() -> &'static ExtensionMap {
+    EXTENSIONS.get_or_init(|| ExtensionMap { user: Vec::new() })
+}
+
+/// Whether a path has an extension mapped to the binary format
+fn is_bin_path(path: &Path) -> bool {
+    extensions().format_for_path(path).is_some_and(|f| f.name() == BinaryFormat::NAME)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum NameCaseArg {
+    /// Keep names as typed in the source, fill the rest from hash files
+    Preserve,
+    /// Re-case names to match the hash files
+    Table,
+    /// Lowercase all names
+    Lower,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum HashCommentsArg {
+    /// `0x1234abcd # Characters/Ahri`
+    Name,
+    /// `"Characters/Ahri" # 0x1234abcd`
+    Hex,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum DuplicateSectionsArg {
+    /// Keep the last value for each name
+    KeepLast,
+    /// Keep the first value for each name
+    KeepFirst,
+    /// Fail on the first repeated name
+    Error,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum DuplicateKeysArg {
+    /// Keep every item, as stored
+    KeepAll,
+    /// Keep the first item with each key
+    KeepFirst,
+    /// Keep the last value for each key
+    KeepLast,
+    /// Fail on the first repeated key
+    Error,
 }
 
 #[derive(Parser)]
@@ -24,10 +178,20 @@ struct Cli {
     #[arg(short, long, global = true)]
     output: Option<PathBuf>,
 
+    /// Where to write, mirroring the input layout, when the output location is read-only
+    /// (e.g. a game install; also read from RITOBIN_FALLBACK_OUTPUT)
+    #[arg(long, global = true, value_name = "DIR")]
+    fallback_output: Option<PathBuf>,
+
     /// Directory to load hashes from
     #[arg(short = 'd', long, global = true)]
     dir: Option<PathBuf>,
 
+    /// Extra directory to search for hashes before the default locations (repeatable;
+    /// also read from RITOBIN_HASH_PATHS and the hash_paths.txt config file)
+    #[arg(long, global = true, value_name = "DIR")]
+    hash_path: Vec<PathBuf>,
+
     /// Recursive directory processing
     #[arg(short, long, global = true)]
     recursive: bool,
@@ -40,15 +204,252 @@ struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
-    /// Explicit input format
-    #[arg(short = 'i', long, global = true)]
-    input_format: Option<Format>,
+    /// Explicit input format (bin, text, json)
+    #[arg(short = 'i', long, global = true, value_parser = parse_format_name)]
+    input_format: Option<String>,
+
+    /// Explicit output format (bin, text, json)
+    #[arg(long, global = true, value_parser = parse_format_name)]
+    output_format: Option<String>,
+
+    /// Write several formats from one read, e.g. `--emit text,json` (extension set per format)
+    #[arg(long, global = true, value_name = "FORMAT", value_delimiter = ',', value_parser = parse_format_name, conflicts_with = "output_format")]
+    emit: Vec<String>,
+
+    /// How to case names written into the output
+    #[arg(long, global = true, value_enum, default_value_t = NameCaseArg::Preserve)]
+    name_case: NameCaseArg,
+
+    /// Accept text fields without `: type` annotations, inferring their types
+    #[arg(long, global = true)]
+    infer_types: bool,
+
+    /// Accept the type names of C++ ritobin text and JSON files, and the unseparated vector components of its text
+    #[arg(long, global = true)]
+    ritobin_compat: bool,
+
+    /// Bin/text/JSON file whose field types are used when inferring text types
+    #[arg(long, global = true, requires = "infer_types")]
+    schema: Option<PathBuf>,
+
+    /// Save names discovered this session (not in the loaded hash files) to a binary hash file
+    #[arg(long, global = true)]
+    export_session: Option<PathBuf>,
+
+    /// Map a file extension to a format, e.g. `--ext-map ritobin=text,dat=bin`
+    /// (also read from RITOBIN_EXTENSIONS)
+    #[arg(long, global = true, value_name = "EXT=FORMAT", value_delimiter = ',')]
+    ext_map: Vec<String>,
+
+    /// Only convert/show entries in this index range, e.g. `100..200`, `..50`, `1000..`
+    #[arg(long, global = true, value_name = "START..END", value_parser = parse_entry_range)]
+    entries: Option<std::ops::Range<usize>>,
+
+    /// Only convert/show these entries, by hash (`0x1234abcd`) or name
+    #[arg(long, global = true, value_name = "HASH", value_delimiter = ',', value_parser = parse_entry_hash)]
+    entry_hash: Vec<u32>,
+
+    /// Write entries of a class to a subdirectory next to the output instead,
+    /// e.g. `--route class=VfxSystemDefinitionData:vfx/,class=SpellObject:spells/`
+    /// (first matching rule wins; classes by name or `0x` hash)
+    #[arg(long, global = true, value_name = "class=CLASS:DIR", value_delimiter = ',', value_parser = parse_route)]
+    route: Vec<Route>,
+
+    /// Write entries after the entries they link to, failing if links form a cycle
+    /// (for loaders that stream entries in order)
+    #[arg(long, global = true)]
+    order_by_links: bool,
+
+    /// Move repeated strings of at least MIN_LEN bytes (`--dedup-strings=16`, default 32) into a string table
+    /// when writing text or JSON
+    #[arg(long, global = true, value_name = "MIN_LEN", num_args = 0..=1, require_equals = true, default_missing_value = "32")]
+    dedup_strings: Option<usize>,
+
+    /// Write only the first N items of each list and map in text output, noting how many were left out
+    /// (for previews and diffs; the output no longer holds the whole file)
+    #[arg(long, global = true, value_name = "N")]
+    max_items: Option<usize>,
+
+    /// Spaces per nesting level in text output
+    #[arg(long, global = true, value_name = "N", default_value_t = TextWriteOptions::default().indent_width)]
+    indent: usize,
+
+    /// Indent text output with tabs instead of spaces
+    #[arg(long, global = true)]
+    indent_tabs: bool,
+
+    /// Write a comma after every item of a multi-line block in text output
+    #[arg(long, global = true)]
+    trailing_commas: bool,
+
+    /// Write unnamed hashes in text output with uppercase hex digits
+    #[arg(long, global = true)]
+    uppercase_hex: bool,
+
+    /// Write lists of up to N plain values on one line in text output
+    #[arg(long, global = true, value_name = "N", default_value_t = 0)]
+    inline_lists: usize,
+
+    /// Write named hashes in text output as hex with the name in a comment, or the other way round
+    #[arg(long, global = true, value_enum)]
+    hash_comments: Option<HashCommentsArg>,
+
+    /// Write hashes without a name in JSON output as "0x…" strings, which JavaScript reads exactly
+    #[arg(long, global = true)]
+    json_hex_hashes: bool,
+
+    /// Write JSON as plain objects and arrays, with the types in a .types.json file next to it, and read it back from the pair
+    #[arg(long, global = true)]
+    simple_json: bool,
+
+    /// Re-read every written .bin in memory and refuse to save it if it differs from the source
+    #[arg(long, global = true)]
+    safe_write: bool,
+
+    /// Decode the entries of .bin input on N threads (0 for one per CPU); helps with large map bins
+    #[arg(long, global = true, value_name = "N")]
+    decode_threads: Option<usize>,
+
+    /// Encode the entries of .bin output on N threads (0 for one per CPU); helps with large merged bins
+    #[arg(long, global = true, value_name = "N")]
+    encode_threads: Option<usize>,
+
+    /// Write .bin entries sorted by hash and fields sorted by key, so equal bins give identical bytes
+    #[arg(long, global = true)]
+    deterministic: bool,
+
+    /// Refuse to write a .bin larger than BYTES, listing the largest entries and strings to trim
+    #[arg(long, global = true, value_name = "BYTES")]
+    max_size: Option<u64>,
+
+    /// Skip damaged entries in .bin input and malformed sections in text input instead of failing, listing what was skipped
+    #[arg(long, global = true)]
+    recover: bool,
+
+    /// Fail instead of warning when text or JSON written as .bin holds unknown names that look mistyped
+    #[arg(long, global = true)]
+    strict_names: bool,
+
+    /// Treat every warning as an error: files with lossy strings, skipped size mismatches,
+    /// names missing from the hash tables or numbers that don't fit their type are not
+    /// written, and the run fails with a list of all warnings (for CI)
+    #[arg(long, global = true)]
+    strict: bool,
 
-    /// Explicit output format
+    /// Keep .bin fields with unrecognised type bytes as raw bytes instead of failing
     #[arg(long, global = true)]
-    output_format: Option<Format>,
+    keep_unknown_types: bool,
+
+    /// Report keys repeated within a map in .bin input and decide which items to keep
+    #[arg(long, global = true, value_enum, value_name = "POLICY")]
+    duplicate_keys: Option<DuplicateKeysArg>,
+
+    /// Report section names used more than once in text input and decide which value to keep
+    #[arg(long, global = true, value_enum, value_name = "POLICY")]
+    duplicate_sections: Option<DuplicateSectionsArg>,
+
+    /// Show notes from the input's .meta.json sidecar as comments in text output
+    #[arg(long, global = true)]
+    annotations: bool,
+}
+
+/// The built-in formats, the only ones known while the command line is parsed
+const FORMAT_NAMES: [&str; 3] = [BinaryFormat::NAME, TextFormat::NAME, JsonFormat::NAME];
+
+fn parse_format_name(s: &str) -> Result<String, String> {
+    // Runs before `main` configures `FORMATS`, which must not be initialized here
+    FORMAT_NAMES.iter()
+        .find(|name| name.eq_ignore_ascii_case(s))
+        .map(|name| name.to_string())
+        .ok_or_else(|| format!("Unknown format '{}' (available: {})", s, FORMAT_NAMES.join(", ")))
+}
+
+fn parse_entry_range(s: &str) -> Result<std::ops::Range<usize>, String> {
+    let (start, end) = s.split_once("..").ok_or("expected START..END")?;
+    let bound = |v: &str, default| match v.trim() {
+        "" => Ok(default),
+        v => v.parse::<usize>().map_err(|e| format!("invalid bound '{}': {}", v, e)),
+    };
+    Ok(bound(start, 0)?..bound(end, usize::MAX)?)
+}
+
+fn parse_entry_hash(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).map_err(|e| format!("invalid hash '{}': {}", s, e)),
+        None => Ok(ritobin_rust::hash::fnv1a(s)),
+    }
+}
+
+/// A `--route` rule: entries of `class` are written under `dir`
+#[derive(Debug, Clone)]
+struct Route {
+    class: u32,
+    dir: PathBuf,
+}
+
+fn parse_route(s: &str) -> Result<Route, String> {
+    let rule = s.trim().strip_prefix("class=").ok_or_else(|| format!("route '{}' must look like class=CLASS:DIR", s))?;
+    let (class, dir) = rule.rsplit_once(':').ok_or_else(|| format!("route '{}' has no output directory", s))?;
+    if class.is_empty() || dir.is_empty() {
+        return Err(format!("route '{}' must look like class=CLASS:DIR", s));
+    }
+    Ok(Route { class: parse_entry_hash(class)?, dir: PathBuf::from(dir) })
+}
+
+/// Move the entries matched by `--route` rules out of `bin`, with the
+/// directory each group goes to
+fn route_entries(cli: &Cli, bin: &mut ritobin_rust::model::Bin) -> Vec<(PathBuf, ritobin_rust::model::Bin)> {
+    let mut out = Vec::new();
+    for route in &cli.route {
+        let routed = bin.split_off_entries(|_, value| {
+            matches!(value, ritobin_rust::model::BinValue::Embed { name, .. } if *name == route.class)
+        });
+        if routed.entry_count() > 0 {
+            out.push((route.dir.clone(), routed));
+        }
+    }
+    out
+}
+
+/// Drop entries not selected by `--entries` / `--entry-hash`
+fn select_entries(cli: &Cli, bin: &mut ritobin_rust::model::Bin) {
+    if cli.entries.is_none() && cli.entry_hash.is_empty() {
+        return;
+    }
+    let removed = bin.retain_entries(|index, hash| {
+        cli.entries.as_ref().is_none_or(|r| r.contains(&index))
+            && (cli.entry_hash.is_empty() || cli.entry_hash.contains(&hash))
+    });
+    if cli.verbose {
+        println!("Skipped {} entries outside the selection", removed);
+    }
 }
 
+/// Apply `--order-by-links`
+fn order_by_links(cli: &Cli, bin: &mut ritobin_rust::model::Bin) -> Result<(), String> {
+    if !cli.order_by_links {
+        return Ok(());
+    }
+    bin.sort_entries_by_links().map_err(|cycle| {
+        let path: Vec<String> = cycle.iter().chain(cycle.first()).map(|h| format!("0x{:08x}", h)).collect();
+        format!("Entries link in a cycle: {}", path.join(" -> "))
+    })
+}
+
+
+#[derive(Subcommand)]
+enum HashesCommand {
+    /// Show counts, size, duplicates and conflicts of a binary hash file
+    Inspect {
+        /// Binary hash file (e.g. hashes.game.bin)
+        input: PathBuf,
+
+        /// Also list the entries in this index range, e.g. `0..20` (FNV1a first, then XXH64)
+        #[arg(long, value_name = "START..END", value_parser = parse_entry_range)]
+        dump: Option<std::ops::Range<usize>>,
+    },
+}
 
 #[derive(Subcommand)]
 enum Commands {
@@ -60,12 +461,23 @@ enum Commands {
         /// Output binary file (if single input) or directory (if multiple)
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Write a delta with only the names missing from this binary hash file
+        /// (a directory of same-named .bin files with multiple inputs)
+        #[arg(long)]
+        base: Option<PathBuf>,
         
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
     },
     
+    /// Work with binary hash files
+    Hashes {
+        #[command(subcommand)]
+        command: HashesCommand,
+    },
+
     /// Convert bin files between formats
     Convert {
         /// Input file or directory
@@ -92,6 +504,10 @@ enum Commands {
         /// Show detailed field information
         #[arg(short = 'D', long)]
         detailed: bool,
+
+        /// Maximum characters shown in string previews
+        #[arg(long, default_value_t = 50)]
+        preview_len: usize,
     },
     
     /// Validate bin file structure
@@ -102,6 +518,199 @@ enum Commands {
         /// Recursive directory validation
         #[arg(short, long)]
         recursive: bool,
+
+        /// Number of worker threads (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Stop after the first invalid file
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Print a per-file JSON report instead of console text
+        #[arg(long)]
+        json: bool,
+
+        /// Fail when an entry or container does not use exactly its declared size
+        #[arg(long)]
+        strict_sizes: bool,
+
+        /// Fail when there is data after the last section
+        #[arg(long)]
+        deny_trailing_data: bool,
+
+        /// Maximum nesting depth of values
+        #[arg(long, default_value_t = ReadOptions::default().max_depth)]
+        max_depth: usize,
+
+        /// Maximum number of items in one container
+        #[arg(long, default_value_t = ReadOptions::default().max_items)]
+        max_container_items: u32,
+
+        /// Also fail files that are not rewritten byte-for-byte, reporting the first differing offset
+        #[arg(long)]
+        roundtrip: bool,
+    },
+
+    /// Re-check names and rewrite .bin files as normalized .bin (no format change)
+    UnhashFiles {
+        /// Input bin file or directory
+        input: PathBuf,
+
+        /// Output directory (files are rewritten in place if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Recursive directory processing
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// Show which entries, classes and value kinds take up the bytes of a bin file
+    Profile {
+        /// Input bin file
+        input: PathBuf,
+
+        /// Number of entries and classes to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare .bin files against the .py files the C++ ritobin produced for them
+    Compare {
+        /// Directory of .bin/.py pairs (or a single .bin with a .py next to it)
+        input: PathBuf,
+
+        /// Recursive directory processing
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Maximum relative difference for floats to still match
+        #[arg(long, default_value_t = 1e-6)]
+        float_tolerance: f32,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Rewrite entry paths, links, file paths and strings from one prefix to another
+    /// (e.g. to port a skin between champions)
+    Rekey {
+        /// Input bin file
+        input: PathBuf,
+
+        /// Prefix replacement, e.g. `Characters/Ahri/=Characters/Annie/` (repeatable, first match wins)
+        #[arg(long = "prefix", value_name = "FROM=TO", required = true)]
+        prefixes: Vec<String>,
+    },
+    /// Apply a PTCH file to the bin it patches, to preview a hotfix
+    ApplyPatch {
+        /// Base bin file
+        input: PathBuf,
+
+        /// PTCH file to apply
+        #[arg(long)]
+        patch: PathBuf,
+    },
+    /// Write a PTCH file holding only what changed between two versions of a bin
+    MakePatch {
+        /// Original bin file
+        old: PathBuf,
+
+        /// Modified bin file
+        new: PathBuf,
+    },
+
+    /// Add notes, TODOs or tags to a value in a bin's .meta.json sidecar
+    Annotate {
+        /// Bin file the sidecar belongs to
+        input: PathBuf,
+
+        /// Value path, e.g. `entries[Characters/Ahri/CharacterRecords/Root].baseHP`
+        path: String,
+
+        /// Set the note (an empty note removes it)
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Add a TODO (repeatable)
+        #[arg(long = "todo")]
+        todos: Vec<String>,
+
+        /// Add a tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Remove the annotation for this path
+        #[arg(long, conflicts_with_all = ["note", "todos", "tags"])]
+        clear: bool,
+    },
+
+    /// Find bins embedded in another file (a container, a memory dump) and write each out
+    ExtractBins {
+        /// File to search
+        input: PathBuf,
+
+        /// Output directory (defaults to the input's directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write the generated edge-case bins used for conformance testing
+    Corpus {
+        /// Output directory
+        output: PathBuf,
+    },
+
+    /// List supported formats, extensions and compiled-in features
+    Formats {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Write a JSON Schema of the JSON output, for validators and editors (to -o or stdout)
+    JsonSchema,
+
+    /// Write a JSON manifest of per-entry sizes and checksums (to -o or stdout)
+    Manifest {
+        /// Input bin file or directory
+        input: PathBuf,
+
+        /// Recursive directory processing
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// Find entries with identical structure (class, fields, field types) across files
+    /// Gather a champion's entries (record, spells, skins, the VFX they link to) from a game directory into one file
+    Bundle {
+        /// Champion name as in its directory, e.g. Ahri
+        champion: String,
+
+        /// Game or extracted data directory to search for .bin files
+        input: PathBuf,
+    },
+    Signatures {
+        /// Input bin file or directory
+        input: PathBuf,
+
+        /// Recursive directory processing
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Only report signatures shared by at least this many entries
+        #[arg(long, default_value_t = 2)]
+        min_count: usize,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
     },
 }
 
@@ -109,29 +718,105 @@ enum Commands {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
+    let schema = load_schema(&cli)?;
+    if let Some(schema) = &schema {
+        SCHEMA.set(schema.clone()).ok();
+    }
+    FORMATS.set(format_registry(&cli, schema))
+        .map_err(|_| "internal error: formats were looked up before the command line configured them")?;
+
+    let env_map = std::env::var("RITOBIN_EXTENSIONS").unwrap_or_default();
+    let ext_map = ExtensionMap::parse(
+        env_map.split(',').chain(cli.ext_map.iter().map(String::as_str))
+    )?;
+    EXTENSIONS.set(ext_map).ok();
+
     match &cli.command {
-        Some(Commands::ConvertHashes { input, output, verbose }) => {
-            convert_hashes_command(input, output.as_deref(), *verbose)?;
+        Some(Commands::ConvertHashes { input, output, base, verbose }) => {
+            convert_hashes_command(input, output.as_deref(), base.as_deref(), *verbose)?;
+        }
+        Some(Commands::Hashes { command: HashesCommand::Inspect { input, dump } }) => {
+            inspect_hashes_command(input, dump.clone())?;
+        }
+        Some(Commands::Info { input, detailed, preview_len }) => {
+            let preview = PreviewOptions { max_chars: *preview_len, ..Default::default() };
+            info_command(input, *detailed, &preview, &cli)?;
+        }
+        Some(Commands::Validate { input, recursive, jobs, fail_fast, json, strict_sizes, deny_trailing_data, max_depth, max_container_items, roundtrip }) => {
+            let opts = ReadOptions {
+                strict_sizes: *strict_sizes,
+                allow_trailing_data: !*deny_trailing_data,
+                max_depth: *max_depth,
+                preserve_unknown_types: cli.keep_unknown_types,
+                max_items: *max_container_items,
+                ..Default::default()
+            };
+            let checks = ValidateChecks { roundtrip: *roundtrip, strict: cli.strict };
+            validate_command(input, *recursive, *jobs, *fail_fast, *json, checks, &opts)?;
+        }
+        Some(Commands::UnhashFiles { input, output, recursive }) => {
+            unhash_files_command(input, output.as_deref(), *recursive, &cli)?;
+        }
+        Some(Commands::Profile { input, top, json }) => {
+            profile_command(input, *top, *json, &cli)?;
+        }
+        Some(Commands::Compare { input, recursive, float_tolerance, json }) => {
+            compare_command(input, *recursive, *float_tolerance, *json, &cli)?;
+        }
+        Some(Commands::Rekey { input, prefixes }) => {
+            rekey_command(input, prefixes, &cli)?;
+        }
+        Some(Commands::ApplyPatch { input, patch }) => {
+            apply_patch_command(input, patch, &cli)?;
+        }
+        Some(Commands::MakePatch { old, new }) => {
+            make_patch_command(old, new, &cli)?;
         }
-        Some(Commands::Info { input, detailed }) => {
-            info_command(input, *detailed)?;
+        Some(Commands::Annotate { input, path, note, todos, tags, clear }) => {
+            annotate_command(input, path, note.as_deref(), todos, tags, *clear)?;
         }
-        Some(Commands::Validate { input, recursive }) => {
-            validate_command(input, *recursive)?;
+        Some(Commands::ExtractBins { input, output }) => {
+            extract_bins_command(input, output.as_deref())?;
         }
-        Some(Commands::Convert { input, output, recursive, verbose }) => {
+        Some(Commands::Corpus { output }) => {
+            for path in ritobin_rust::corpus::write_corpus(output)? {
+                println!("Wrote {}", path.display());
+            }
+        }
+        Some(Commands::Formats { json }) => {
+            formats_command(*json)?;
+        }
+        Some(Commands::JsonSchema) => {
+            let json = serde_json::to_string_pretty(&ritobin_rust::json::schema())?;
+            match &cli.output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        }
+        Some(Commands::Manifest { input, recursive }) => {
+            manifest_command(input, *recursive, cli.output.as_deref())?;
+        }
+        Some(Commands::Bundle { champion, input }) => {
+            bundle_command(champion, input, &cli)?;
+        }
+        Some(Commands::Signatures { input, recursive, min_count, json }) => {
+            signatures_command(input, *recursive, *min_count, *json)?;
+        }
+        Some(Commands::Convert { input, output, recursive, verbose: _ }) => {
             // Similar to default behavior but explicit
             // Similar to default behavior but explicit
-            let unhasher = setup_unhasher(&cli);
+            let mut unhasher = setup_unhasher(&cli);
 
+            if input.is_dir() && !recursive {
+                return Err("Input is a directory but --recursive is not specified".into());
+            }
+            let output = writable_output(input, output.as_deref(), &cli)?;
             if input.is_dir() {
-                if !recursive {
-                    return Err("Input is a directory but --recursive is not specified".into());
-                }
                 process_directory(input, output.as_deref(), &cli, &mut unhasher)?;
             } else {
                 process_file(input, output.as_deref(), &cli, &mut unhasher)?;
             }
+            export_session(&cli, &unhasher)?;
         }
         None => {
             // Default behavior - convert bin files
@@ -155,8 +840,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let unhasher = setup_unhasher(&cli);
 
                 // Process the file
-                let data = std::fs::read(input)?;
-                let mut bin = read_bin(&data)?;
+                let data = read_file(input)?;
+                let mut bin = format_named(BinaryFormat::NAME)?.read(&data)?;
+                drop(data);
+                select_entries(&cli, &mut bin);
                 
                 // Unhash
                 if let Some(u) = &unhasher {
@@ -164,9 +851,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 
                 // Output to same directory with .py extension
-                let output_path = input.with_extension("py");
-                let text = ritobin_rust::text::write_text(&bin)?;
-                std::fs::write(&output_path, text)?;
+                let text_format = format_named(TextFormat::NAME)?;
+                let output_path = input.with_extension(extensions().extension_for(text_format));
+                write_output(&bin, &output_path, text_format)?;
                 
                 println!("✓ Converted to: {}", output_path.display());
                 println!("\nPress Enter to exit...");
@@ -178,26 +865,98 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Standard mode with full options
             // Standard mode with full options
-            let unhasher = setup_unhasher(&cli);
+            let mut unhasher = setup_unhasher(&cli);
 
+            if input.is_dir() && !cli.recursive {
+                return Err("Input is a directory but --recursive is not specified".into());
+            }
+            let output = writable_output(input, cli.output.as_deref(), &cli)?;
             if input.is_dir() {
-                if !cli.recursive {
-                    return Err("Input is a directory but --recursive is not specified".into());
-                }
-                process_directory(input, cli.output.as_deref(), &cli, &mut unhasher)?;
+                process_directory(input, output.as_deref(), &cli, &mut unhasher)?;
             } else {
-                process_file(input, cli.output.as_deref(), &cli, &mut unhasher)?;
+                process_file(input, output.as_deref(), &cli, &mut unhasher)?;
             }
+            export_session(&cli, &unhasher)?;
         }
 
     }
-    
+
+    strict_report(&cli)
+}
+
+/// The formats for this run, configured by the command line
+fn format_registry(cli: &Cli, schema: Option<ritobin_rust::text::TextSchema>) -> FormatRegistry {
+    let mut registry = FormatRegistry::with_defaults();
+    registry.register(BinaryFormat {
+        checked: cli.safe_write,
+        max_size: cli.max_size,
+        decode_threads: cli.decode_threads,
+        encode_threads: cli.encode_threads,
+        write_options: WriteOptions {
+            sort_entries_by_hash: cli.deterministic,
+            sort_fields_by_key: cli.deterministic,
+            ..Default::default()
+        },
+    });
+    registry.register(TextFormat {
+        infer_types: cli.infer_types,
+        schema,
+        ritobin_compat: cli.ritobin_compat,
+        write_options: text_write_options(cli),
+    });
+    registry.register(JsonFormat {
+        ritobin_compat: cli.ritobin_compat,
+        write_options: JsonWriteOptions { hex_hashes: cli.json_hex_hashes },
+    });
+    registry
+}
+
+/// The output to use for converting `input`, checked for write access before anything is written.
+///
+/// Outputs go to `output`, or next to the inputs without one. If that location
+/// is read-only (a game install, say), they go to the `--fallback-output` root
+/// instead, mirroring the input layout, so a batch doesn't fail halfway with
+/// some files written and others not.
+fn writable_output(input: &Path, output: Option<&Path>, cli: &Cli) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let target = match output {
+        Some(out) => out,
+        None if input.is_dir() => input,
+        None => input.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")),
+    };
+    if ritobin_rust::paths::can_write_into(target) {
+        return Ok(output.map(Path::to_path_buf));
+    }
+    let root = cli.fallback_output.clone()
+        .or_else(|| std::env::var_os("RITOBIN_FALLBACK_OUTPUT").map(PathBuf::from))
+        .ok_or_else(|| format!("{} is read-only; use --fallback-output DIR to write somewhere else", target.display()))?;
+    if !ritobin_rust::paths::can_write_into(&root) {
+        return Err(format!("{} is read-only, and so is the fallback output {}", target.display(), root.display()).into());
+    }
+    eprintln!("{} is read-only; writing to {} instead", target.display(), root.display());
+    std::fs::create_dir_all(long_path(&root))?;
+    Ok(Some(root))
+}
+
+fn extract_bins_command(input: &Path, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let data = read_file(input)?;
+    let blobs = ritobin_rust::binary::find_prop_blobs(&data);
+    let output_dir = output.or(input.parent()).unwrap_or(Path::new("."));
+    std::fs::create_dir_all(long_path(output_dir))?;
+
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    for range in &blobs {
+        let path = output_dir.join(format!("{}_{:08x}.bin", stem, range.start));
+        std::fs::write(long_path(&path), &data[range.clone()])?;
+        println!("✓ {} bytes at {:#x} -> {}", range.len(), range.start, path.display());
+    }
+    println!("Found {} bins in {}", blobs.len(), input.display());
     Ok(())
 }
 
 fn convert_hashes_command(
     inputs: &[PathBuf],
     output: Option<&Path>,
+    base: Option<&Path>,
     verbose: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use ritobin_rust::unhash::BinUnhasher;
@@ -206,11 +965,21 @@ fn convert_hashes_command(
         return Err("No input files specified".into());
     }
 
+    // Full conversion, or a delta against the base file
+    let convert = |input: &Path, base: Option<PathBuf>, output_path: &Path| -> std::io::Result<usize> {
+        match base {
+            Some(base) => BinUnhasher::convert_text_to_delta(input, base, output_path),
+            None => BinUnhasher::convert_text_to_binary(input, output_path),
+        }
+    };
+
     if inputs.len() == 1 {
         // Single file conversion
         let input = &inputs[0];
         let output_path = if let Some(out) = output {
             out.to_path_buf()
+        } else if base.is_some() {
+            BinUnhasher::delta_path(input)
         } else {
             // Default: replace .txt with .bin
             let mut p = input.clone();
@@ -222,10 +991,7 @@ fn convert_hashes_command(
             println!("Converting {} to {}", input.display(), output_path.display());
         }
 
-        let count = BinUnhasher::convert_text_to_binary(
-            input.to_str().unwrap(),
-            output_path.to_str().unwrap(),
-        )?;
+        let count = convert(input, base.map(Path::to_path_buf), &output_path)?;
 
         println!("✓ Converted {} hashes to {}", count, output_path.display());
     } else {
@@ -235,18 +1001,20 @@ fn convert_hashes_command(
 
         let mut total_count = 0;
         for input in inputs {
-            let output_path = output_dir.join(
-                input.file_name().unwrap()
-            ).with_extension("bin");
+            let file_name = input.file_name().ok_or_else(|| format!("Not a file: {}", input.display()))?;
+            let (output_path, input_base) = match base {
+                Some(base_dir) => (
+                    BinUnhasher::delta_path(&output_dir.join(file_name)),
+                    Some(base_dir.join(file_name).with_extension("bin")),
+                ),
+                None => (output_dir.join(file_name).with_extension("bin"), None),
+            };
 
             if verbose {
                 println!("Converting {} to {}", input.display(), output_path.display());
             }
 
-            let count = BinUnhasher::convert_text_to_binary(
-                input.to_str().unwrap(),
-                output_path.to_str().unwrap(),
-            )?;
+            let count = convert(input, input_base, &output_path)?;
 
             total_count += count;
             println!("✓ Converted {} hashes from {}", count, input.display());
@@ -258,12 +1026,45 @@ fn convert_hashes_command(
     Ok(())
 }
 
+fn inspect_hashes_command(input: &Path, dump: Option<std::ops::Range<usize>>) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::hash_binary::{BinaryHashReader, HashStats};
+
+    let size = std::fs::metadata(long_path(input))?.len();
+    let file = std::io::BufReader::new(std::fs::File::open(long_path(input))?);
+    let entries = BinaryHashReader::new(file).read_entries()?;
+    let (fnv1a, xxh64) = entries.stats();
+
+    println!("File: {}", input.display());
+    println!("  Version: {}", ritobin_rust::hash_binary::VERSION);
+    println!("  Size: {} bytes ({} bytes of names)", size, entries.string_bytes());
+    let print_stats = |kind: &str, stats: &HashStats| {
+        println!("  {}: {} entries, {} duplicates, {} conflicts, {} not matching their hash",
+            kind, stats.count, stats.duplicates, stats.conflicts, stats.mismatches);
+    };
+    print_stats("FNV1a", &fnv1a);
+    print_stats("XXH64", &xxh64);
+
+    if let Some(range) = dump {
+        let all = entries.fnv1a.iter().map(|(h, s)| (format!("0x{:08x}", h), s))
+            .chain(entries.xxh64.iter().map(|(h, s)| (format!("0x{:016x}", h), s)));
+        for (index, (hash, name)) in all.enumerate().skip(range.start).take(range.len()) {
+            println!("  [{}] {} {}", index, hash, name);
+        }
+    }
+    Ok(())
+}
+
 fn setup_unhasher(cli: &Cli) -> Option<ritobin_rust::unhash::BinUnhasher> {
     if cli.keep_hashed {
         return None;
     }
 
     let mut unhasher = ritobin_rust::unhash::BinUnhasher::new();
+    unhasher.set_name_case(match cli.name_case {
+        NameCaseArg::Preserve => ritobin_rust::unhash::NameCase::Preserve,
+        NameCaseArg::Table => ritobin_rust::unhash::NameCase::Table,
+        NameCaseArg::Lower => ritobin_rust::unhash::NameCase::Lower,
+    });
     let mut loaded = false;
 
     // 1. Explicit directory (highest priority)
@@ -273,96 +1074,68 @@ fn setup_unhasher(cli: &Cli) -> Option<ritobin_rust::unhash::BinUnhasher> {
                  loaded = true;
              }
         } else {
-             eprintln!("Warning: Specified hash directory does not exist: {}", dir.display());
+             warn(format!("Specified hash directory does not exist: {}", dir.display()));
         }
     } 
     
     // 2. Auto-discovery (if no explicit dir provided)
-    if !loaded && cli.dir.is_none() {
-        // Try AppData
-        if let Ok(appdata) = std::env::var("APPDATA") {
-            let path = PathBuf::from(appdata).join("RitoShark/Requirements/Hashes");
-            if path.exists() {
-                if cli.verbose { println!("Checking hash path: {}", path.display()); }
-                if load_hashes(&mut unhasher, &path, cli.verbose) {
-                    loaded = true;
-                }
+    if cli.dir.is_none() {
+        let mut discovery = HashDiscovery::new();
+        for path in &cli.hash_path {
+            discovery.add_path(path);
+        }
+        let search_paths = discovery.search_paths();
+        for path in &search_paths {
+            if cli.verbose { println!("Checking hash path: {}", path.display()); }
+            if load_hashes(&mut unhasher, path, cli.verbose) {
+                loaded = true;
+                break;
             }
         }
 
-        // Try Executable Directory (Root)
+        // 3. Prompt if nothing found
         if !loaded {
-            if let Ok(exe_path) = std::env::current_exe() {
-                if let Some(root) = exe_path.parent() {
-                    // Try "Hashes" folder in root
-                    let hashes_dir = root.join("Hashes");
-                    if hashes_dir.exists() {
-                        if cli.verbose { println!("Checking hash path: {}", hashes_dir.display()); }
-                        if load_hashes(&mut unhasher, &hashes_dir, cli.verbose) {
-                            loaded = true;
-                        }
-                    }
-                    
-                    // Try root itself if still not loaded
-                    if !loaded {
-                        if cli.verbose { println!("Checking hash path: {}", root.display()); }
-                        if load_hashes(&mut unhasher, root, cli.verbose) {
-                            loaded = true;
-                        }
-                    }
-                }
+            eprintln!("⚠️  No hashes found.");
+            for path in &search_paths {
+                eprintln!("Checked: {}", path.display());
+            }
+            eprint!("\nDo you want to continue without unhashing? [y/N]: ");
+            use std::io::Write;
+            std::io::stdout().flush().ok();
+
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input).ok();
+            if input.trim().to_lowercase() != "y" {
+                std::process::exit(0);
             }
-        }
-    }
-    
-    // 3. Prompt if nothing found
-    if !loaded && cli.dir.is_none() {
-        eprintln!("⚠️  No hashes found.");
-        eprintln!("Checked: %APPDATA%/RitoShark/Requirements/Hashes");
-        eprintln!("Checked: Executable directory (and /Hashes subdirectory)");
-        eprint!("\nDo you want to continue without unhashing? [y/N]: ");
-        use std::io::Write;
-        std::io::stdout().flush().ok();
-        
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).ok();
-        if input.trim().to_lowercase() != "y" {
-            std::process::exit(0);
         }
     }
 
     Some(unhasher)
 }
 
+fn export_session(
+    cli: &Cli,
+    unhasher: &Option<ritobin_rust::unhash::BinUnhasher>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(path), Some(u)) = (&cli.export_session, unhasher) else {
+        return Ok(());
+    };
+    u.save_session_binary_file(path)?;
+    println!("✓ Exported {} session names to {}", u.session().len(), path.display());
+    Ok(())
+}
+
 fn load_hashes(unhasher: &mut ritobin_rust::unhash::BinUnhasher, dir: &Path, verbose: bool) -> bool {
-    let files = [
-        "hashes.game.txt",
-        "hashes.binentries.txt",
-        "hashes.binhashes.txt",
-        "hashes.bintypes.txt",
-        "hashes.binfields.txt",
-        "hashes.lcu.txt",
-    ];
-    
-    let mut loaded_any = false;
-    for file in files {
-        let path = dir.join(file);
-        if path.exists() {
-            if let Some(path_str) = path.to_str() {
-                if verbose { println!("Loading hashes from {}", path_str); }
-                // Use auto-loading which tries binary first, then text
-                match unhasher.load_auto(path_str) {
-                    Ok(_) => loaded_any = true,
-                    Err(e) => {
-                        if verbose {
-                            eprintln!("Warning: Failed to load {}: {}", path_str, e);
-                        }
-                    }
-                }
-            }
-        }
+    if verbose && discovery::has_hash_files(dir) {
+        println!("Loading hashes from {}", dir.display());
     }
-    loaded_any
+    let mut report = |event: Event| {
+        if let Event::Warning { message, .. } = event {
+            warn(message);
+        }
+    };
+    discovery::load_hash_dir_with_progress(unhasher, dir, &mut report) > 0
 }
 
 fn process_directory(
@@ -374,16 +1147,21 @@ fn process_directory(
     for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
         let path = entry.path();
         if path.is_file() {
-            // Determine relative path to mirror structure if output_dir is set
-            let relative_path = path.strip_prefix(input_dir).unwrap_or(path);
-            let output_path = if let Some(out_dir) = output_dir {
-                Some(out_dir.join(relative_path))
-            } else {
-                None
+            // Mirror the input structure under output_dir; process_file names the file
+            let relative_dir = path.parent().and_then(|p| p.strip_prefix(input_dir).ok()).unwrap_or(Path::new(""));
+            let output_path = match output_dir {
+                Some(out_dir) => {
+                    let dir = out_dir.join(relative_dir);
+                    std::fs::create_dir_all(long_path(&dir))?;
+                    Some(dir)
+                }
+                None => None,
             };
-            
+
             if let Err(e) = process_file(path, output_path.as_deref(), cli, unhasher) {
-                if cli.verbose {
+                if cli.strict {
+                    warn(format!("Skipped {}: {}", path.display(), e));
+                } else if cli.verbose {
                     eprintln!("Skipping {}: {}", path.display(), e);
                 }
             }
@@ -392,61 +1170,223 @@ fn process_directory(
     Ok(())
 }
 
-fn process_file(
-    input_path: &Path, 
-    output_path: Option<&Path>, 
-    cli: &Cli, 
-    unhasher: &mut Option<ritobin_rust::unhash::BinUnhasher>
-) -> Result<(), Box<dyn std::error::Error>> {
-    let data = std::fs::read(input_path)?;
+/// The contents of an input file, mapped into memory with the `mmap` feature
+type FileData = Box<dyn std::ops::Deref<Target = [u8]> + Send + Sync>;
+
+/// Read an input file, mapping it instead of copying it to the heap when built with `mmap`
+fn read_file(path: &Path) -> std::io::Result<FileData> {
+    #[cfg(feature = "mmap")]
+    {
+        let file = std::fs::File::open(long_path(path))?;
+        // SAFETY: every caller drops the map before writing any file, so the
+        // tool never changes a file it has mapped, even when an output
+        // replaces its input. Other programs writing to an input during a run
+        // are outside its control; that is the trade-off the `mmap` feature
+        // makes, as its Cargo.toml entry says.
+        Ok(Box::new(unsafe { ritobin_rust::mmap::Mmap::open(&file)? }))
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        Ok(Box::new(std::fs::read(long_path(path))?))
+    }
+}
+
+/// Read a bin in any format, honoring `--input-format` and the text options
+fn read_input(path: &Path, cli: &Cli) -> Result<(ritobin_rust::model::Bin, Format), Box<dyn std::error::Error>> {
+    let data = read_file(path)?;
     
     // Detect input format
-    let input_format = if let Some(fmt) = cli.input_format {
-        fmt
-    } else {
-        detect_format(&data, input_path)
+    let format = match &cli.input_format {
+        Some(name) => format_named(name)?,
+        None => detect_format(&data, path),
     };
 
     if cli.verbose {
-        println!("Processing {} as {:?}", input_path.display(), input_format);
+        println!("Processing {} as {}", path.display(), format.name());
     }
 
-    let mut bin = match input_format {
-        Format::Bin => read_bin(&data)?,
-        Format::Json => {
-            let s = String::from_utf8(data)?;
-            ritobin_rust::json::read_json(&s)?
-        },
-        Format::Text => {
-            // Text reading not fully implemented in read_text yet? 
-            // Wait, read_text IS implemented in src/text.rs.
-            // But main.rs previously only used read_bin or json.
-            // Let's check if read_text is exposed.
-            // src/text.rs has `read_text`.
-            let s = String::from_utf8(data)?;
-            ritobin_rust::text::read_text(&s)?
-        },
-    };
+    if cli.simple_json && format.name() == JsonFormat::NAME {
+        let types_path = ritobin_rust::json::simple::types_path(path);
+        let types = std::fs::read_to_string(long_path(&types_path)).map_err(|e| format!("{}: {}", types_path.display(), e))?;
+        let bin = ritobin_rust::json::simple::read_simple(std::str::from_utf8(&data)?, &types)?;
+        return Ok((bin, format));
+    }
+
+    if cli.strict && format.name() == JsonFormat::NAME {
+        let opts = JsonReadOptions { ritobin_compat: cli.ritobin_compat };
+        let (bin, warnings) = read_json_with_options(std::str::from_utf8(&data)?, &opts)?;
+        for w in warnings {
+            warn(format!("{}: {}", path.display(), w));
+        }
+        return Ok((bin, format));
+    }
+
+    if (cli.recover || cli.duplicate_sections.is_some()) && format.name() == TextFormat::NAME {
+        let duplicate_sections = match cli.duplicate_sections {
+            None | Some(DuplicateSectionsArg::KeepLast) => DuplicateSections::KeepLast,
+            Some(DuplicateSectionsArg::KeepFirst) => DuplicateSections::KeepFirst,
+            Some(DuplicateSectionsArg::Error) => DuplicateSections::Error,
+        };
+        let opts = ritobin_rust::text::TextReadOptions {
+            infer_types: cli.infer_types,
+            schema: SCHEMA.get(),
+            revision: None,
+            ritobin_compat: cli.ritobin_compat,
+            duplicate_sections,
+            ..Default::default()
+        };
+        let text = std::str::from_utf8(&data)?;
+        if !cli.recover {
+            let (bin, duplicates) = ritobin_rust::text::read_text_with_duplicates(text, &opts)?;
+            for d in &duplicates {
+                warn(format!("{}: section {} on line {} repeats an earlier one", path.display(), d.key, d.line));
+            }
+            return Ok((bin, format));
+        }
+        let recovered = ritobin_rust::text::read_text_recover(text, &opts)?;
+        for e in &recovered.errors {
+            warn(format!("{}: skipped section at {}", path.display(), e));
+        }
+        return Ok((recovered.bin, format));
+    }
+
+    if (cli.recover || cli.keep_unknown_types || cli.duplicate_keys.is_some() || cli.strict) && format.name() == BinaryFormat::NAME {
+        let duplicate_keys = match cli.duplicate_keys {
+            None | Some(DuplicateKeysArg::KeepAll) => DuplicateKeys::KeepAll,
+            Some(DuplicateKeysArg::KeepFirst) => DuplicateKeys::KeepFirst,
+            Some(DuplicateKeysArg::KeepLast) => DuplicateKeys::KeepLast,
+            Some(DuplicateKeysArg::Error) => DuplicateKeys::Error,
+        };
+        let opts = ReadOptions { preserve_unknown_types: cli.keep_unknown_types, duplicate_keys, ..Default::default() };
+        if !cli.recover && cli.duplicate_keys.is_none() && !cli.strict {
+            return Ok((read_bin_with_options(&data, &opts)?, format));
+        }
+        let (bin, duplicates, warnings) = if cli.recover {
+            let recovered = read_bin_recover(&data, &opts)?;
+            for s in &recovered.skipped {
+                warn(format!("{}: skipped entry 0x{:08x} at offset {}: {}", path.display(), s.hash, s.offset, s.error));
+            }
+            (recovered.bin, recovered.duplicates, recovered.warnings)
+        } else {
+            let report = read_bin_with_report(&data, &opts)?;
+            (report.bin, report.duplicates, report.warnings)
+        };
+        if cli.duplicate_keys.is_some() {
+            for d in &duplicates {
+                let at = ritobin_rust::binary::events::path_at(&data, d.offset).unwrap_or_default();
+                warn(format!("{}: duplicate map key {} at offset {} ({})", path.display(), d.key, d.offset, at));
+            }
+        }
+        if cli.strict {
+            for w in &warnings {
+                warn(format!("{}: {}", path.display(), w));
+            }
+        }
+        return Ok((bin, format));
+    }
+
+    let bin = format.read(&data)?;
+    Ok((bin, format))
+}
+
+fn process_file(
+    input_path: &Path, 
+    output_path: Option<&Path>, 
+    cli: &Cli, 
+    unhasher: &mut Option<ritobin_rust::unhash::BinUnhasher>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let warnings_before = warning_count();
+    let (mut bin, input_format) = read_input(input_path, cli)?;
+
+    select_entries(cli, &mut bin);
+    order_by_links(cli, &mut bin)?;
 
     // Unhash if needed
     if let Some(u) = unhasher {
+        if cli.export_session.is_some() {
+            u.learn_from_bin(&bin);
+        }
         u.unhash_bin(&mut bin);
     }
 
-    // Determine output format
-    let output_format = if let Some(fmt) = cli.output_format {
-        fmt
-    } else if let Some(out) = output_path {
-        detect_format_from_extension(out)
+    // Determine output formats
+    let output_formats = if !cli.emit.is_empty() {
+        cli.emit.iter().map(|name| format_named(name)).collect::<Result<Vec<_>, _>>()?
+    } else if let Some(name) = &cli.output_format {
+        vec![format_named(name)?]
+    } else if let Some(out) = output_path.filter(|out| !out.is_dir()) {
+        vec![detect_format_from_extension(out)]
+    } else if input_format.name() == BinaryFormat::NAME {
+        vec![format_named(TextFormat::NAME)?] // Default bin -> py
     } else {
-        // Infer from input
-        match input_format {
-            Format::Bin => Format::Text, // Default bin -> py
-            Format::Json => Format::Bin, // Default json -> bin
-            Format::Text => Format::Bin, // Default py -> bin
-        }
+        vec![format_named(BinaryFormat::NAME)?] // Default py/json -> bin
+    };
+
+    if input_format.name() != BinaryFormat::NAME && output_formats.iter().any(|f| f.name() == BinaryFormat::NAME) {
+        check_typed_names(input_path, cli, &bin, unhasher.as_ref())?;
+    }
+    if cli.strict && warning_count() > warnings_before {
+        return Err(format!("{}: not written, {} warning(s) with --strict", input_path.display(), warning_count() - warnings_before).into());
+    }
+
+    // Each format gets its own copy, as routing and string tables change the bin
+    let last = output_formats.len() - 1;
+    for (i, output_format) in output_formats.into_iter().enumerate() {
+        let bin = if i == last { std::mem::take(&mut bin) } else { bin.clone() };
+        write_converted(input_path, output_path, cli, bin, output_format)?;
+    }
+    Ok(())
+}
+
+/// Warn about names typed into text or JSON that the hash tables don't know
+/// and that look mistyped, as they will hash to values the game won't look
+/// up; with `--strict-names`, fail instead. With `--strict` and hash tables
+/// loaded, every name they don't know is a warning.
+fn check_typed_names(
+    input_path: &Path,
+    cli: &Cli,
+    bin: &ritobin_rust::model::Bin,
+    unhasher: Option<&ritobin_rust::unhash::BinUnhasher>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let suspicious = match unhasher {
+        Some(u) => u.suspicious_names(bin),
+        None => ritobin_rust::unhash::BinUnhasher::new().suspicious_names(bin),
     };
+    if let (true, Some(u)) = (cli.strict, unhasher) {
+        use ritobin_rust::hash_report::HashId;
+        for (id, usage) in bin.collect_hashes().iter() {
+            let known = match id {
+                HashId::Fnv1a(hash) => u.resolve_fnv1a(hash).is_some(),
+                HashId::Xxh64(hash) => u.resolve_xxh64(hash).is_some(),
+            };
+            match &usage.name {
+                Some(name) if !known && !suspicious.iter().any(|s| &s.name == name) => {
+                    warn(format!("{}: name {:?} is not in the hash tables and is written as its hash", input_path.display(), name));
+                }
+                _ => {}
+            }
+        }
+    }
+    if suspicious.is_empty() {
+        return Ok(());
+    }
+    for s in &suspicious {
+        warn(format!("{}: unknown name {:?} {}", input_path.display(), s.name, s.reason));
+    }
+    if cli.strict_names {
+        return Err(format!("{}: {} unknown name(s) look mistyped (--strict-names)", input_path.display(), suspicious.len()).into());
+    }
+    Ok(())
+}
 
+/// Write the converted `bin` as `output_format`, applying routing, string tables and annotations
+fn write_converted(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    cli: &Cli,
+    mut bin: ritobin_rust::model::Bin,
+    output_format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Determine output path
     let final_output_path = if let Some(out) = output_path {
         // If output is a directory (and we are processing a single file), join filename
@@ -455,11 +1395,7 @@ fn process_file(
         // Unless it's a directory?
         if out.is_dir() {
             let name = input_path.file_stem().unwrap_or_default();
-            let ext = match output_format {
-                Format::Bin => "bin",
-                Format::Json => "json",
-                Format::Text => "py",
-            };
+            let ext = extensions().extension_for(output_format);
             out.join(format!("{}.{}", name.to_string_lossy(), ext))
         } else {
             // If explicit output path given, check if extension matches format?
@@ -469,91 +1405,768 @@ fn process_file(
             // If output_path was constructed by process_directory, it might have original extension.
             // We should probably change extension.
             let mut p = out.to_path_buf();
-            p.set_extension(match output_format {
-                Format::Bin => "bin",
-                Format::Json => "json",
-                Format::Text => "py",
-            });
+            p.set_extension(extensions().extension_for(output_format));
             p
         }
     } else {
         let mut p = input_path.to_path_buf();
-        p.set_extension(match output_format {
-            Format::Bin => "bin",
-            Format::Json => "json",
-            Format::Text => "py",
-        });
+        p.set_extension(extensions().extension_for(output_format));
         p
     };
 
     // Create parent directories if needed
     if let Some(parent) = final_output_path.parent() {
-        std::fs::create_dir_all(parent)?;
+        std::fs::create_dir_all(long_path(parent))?;
     }
 
     if cli.verbose {
-        println!("Writing to {} as {:?}", final_output_path.display(), output_format);
+        println!("Writing to {} as {}", final_output_path.display(), output_format.name());
     }
 
-    match output_format {
-        Format::Bin => {
-            let bytes = write_bin(&bin)?;
-            std::fs::write(final_output_path, bytes)?;
-        },
-        Format::Json => {
-            let s = ritobin_rust::json::write_json(&bin)?;
-            std::fs::write(final_output_path, s)?;
-        },
-        Format::Text => {
-            let s = ritobin_rust::text::write_text(&bin)?;
-            std::fs::write(final_output_path, s)?;
-        },
+    let had_entries = bin.entry_count() > 0;
+    for (dir, mut routed) in route_entries(cli, &mut bin) {
+        let file_name = final_output_path.file_name().ok_or("Output path has no file name")?;
+        let routed_path = final_output_path.with_file_name("").join(dir).join(file_name);
+        std::fs::create_dir_all(long_path(routed_path.parent().unwrap_or(Path::new(""))))?;
+        if cli.verbose {
+            println!("Writing {} routed entries to {}", routed.entry_count(), routed_path.display());
+        }
+        dedup_strings(cli, &mut routed, output_format);
+        write_converted_output(cli, &routed, &routed_path, output_format)?;
+    }
+    // Everything was routed elsewhere
+    let has_patches = matches!(bin.sections.get("patches"), Some(ritobin_rust::model::BinValue::Map { items, .. }) if !items.is_empty());
+    if had_entries && bin.entry_count() == 0 && !has_patches {
+        return Ok(());
     }
 
+    dedup_strings(cli, &mut bin, output_format);
+
+    if cli.annotations && output_format.name() == TextFormat::NAME {
+        if let Some(notes) = ritobin_rust::annotations::Annotations::load_sidecar(input_path)? {
+            let opts = text_write_options(cli);
+            let text = ritobin_rust::text::write_text_annotated(&bin, &opts, &notes)?;
+            std::fs::write(long_path(&final_output_path), text)?;
+            return Ok(());
+        }
+    }
+
+    write_converted_output(cli, &bin, &final_output_path, output_format)
+}
+
+/// Write `bin` like [`write_output`], or as simplified JSON with `--simple-json`
+fn write_converted_output(cli: &Cli, bin: &ritobin_rust::model::Bin, path: &Path, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.simple_json && format.name() == JsonFormat::NAME {
+        let simple = ritobin_rust::json::simple::write_simple(bin)?;
+        std::fs::write(long_path(path), simple.data)?;
+        std::fs::write(long_path(&ritobin_rust::json::simple::types_path(path)), simple.types)?;
+        return Ok(());
+    }
+    write_output(bin, path, format)
+}
+
+/// Apply `--dedup-strings` when writing text or JSON
+fn dedup_strings(cli: &Cli, bin: &mut ritobin_rust::model::Bin, output_format: Format) {
+    if let Some(min_len) = cli.dedup_strings {
+        if output_format.name() != BinaryFormat::NAME {
+            use ritobin_rust::string_table::{intern_strings, StringTableOptions};
+            let count = intern_strings(bin, StringTableOptions { min_len, ..Default::default() });
+            if cli.verbose {
+                println!("Moved {} repeated strings into the string table", count);
+            }
+        }
+    }
+}
+
+/// Encode a bin, listing every difference when `--safe-write` rejects it
+fn encode(bin: &ritobin_rust::model::Bin, format: Format) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    format.write(bin).map_err(report_write_error)
+}
+
+fn report_write_error(e: FormatError) -> Box<dyn std::error::Error> {
+    if let FormatError::Bin(BinError::RoundTripMismatch(diffs)) = &e {
+        eprintln!("Refusing to write: the encoded bin reads back differently");
+        for d in diffs {
+            eprintln!("  {}: {}", d.path, d.message);
+        }
+    }
+    if let FormatError::Bin(BinError::OverBudget(report)) = &e {
+        print_budget_report(report);
+    }
+    e.into()
+}
+
+fn print_budget_report(report: &ritobin_rust::profile::BudgetReport) {
+    let label = |hash: u32, name: &Option<String>| match name {
+        Some(n) => n.clone(),
+        None => format!("{:#010x}", hash),
+    };
+    eprintln!("Refusing to write: {} bytes is {} over the budget of {}", report.size, report.excess(), report.budget);
+    eprintln!("Largest entries:");
+    for e in report.entries.iter().take(10) {
+        eprintln!("  {:>10} bytes  {} ({})", e.bytes, label(e.hash, &e.name), label(e.class, &e.class_name));
+    }
+    if !report.strings.is_empty() {
+        eprintln!("Longest strings:");
+        for s in report.strings.iter().take(10) {
+            let text: String = s.text.chars().take(60).collect();
+            eprintln!("  {:>10} bytes  {}: {:?}", s.bytes, label(s.entry, &s.entry_name), text);
+        }
+    }
+    let suggested = report.suggested_entries();
+    if suggested.iter().map(|e| e.bytes).sum::<u64>() >= report.excess() {
+        eprintln!("Removing the {} largest entries would fit the budget", suggested.len());
+    } else {
+        eprintln!("The budget is too small even without any entries");
+    }
+}
+
+fn write_output(
+    bin: &ritobin_rust::model::Bin,
+    path: &Path,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Streamed, so large bins aren't held in memory twice as text
+    let path = long_path(path);
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    let written = format.write_to(bin, &mut out).and_then(|()| Ok(std::io::Write::flush(&mut out)?));
+    if let Err(e) = written {
+        // Don't leave a partial file behind
+        std::fs::remove_file(&path).ok();
+        return Err(report_write_error(e));
+    }
     Ok(())
 }
 
+fn text_write_options(cli: &Cli) -> TextWriteOptions {
+    TextWriteOptions {
+        max_items_per_container: cli.max_items,
+        indent_width: cli.indent,
+        indent_tabs: cli.indent_tabs,
+        trailing_commas: cli.trailing_commas,
+        uppercase_hex: cli.uppercase_hex,
+        max_inline_items: cli.inline_lists,
+        hash_comments: match cli.hash_comments {
+            None => HashComments::Off,
+            Some(HashCommentsArg::Name) => HashComments::Name,
+            Some(HashCommentsArg::Hex) => HashComments::Hex,
+        },
+        ..Default::default()
+    }
+}
+
+/// Field types for text inference, loaded from `--schema`
+fn load_schema(cli: &Cli) -> Result<Option<ritobin_rust::text::TextSchema>, Box<dyn std::error::Error>> {
+    let Some(path) = &cli.schema else {
+        return Ok(None);
+    };
+    // The schema itself is read with the plain built-in formats
+    let registry = FormatRegistry::with_defaults();
+    let data = std::fs::read(path)?;
+    let format = registry.sniff(&data)
+        .or_else(|| path.extension().and_then(|e| e.to_str()).and_then(|e| registry.by_extension(e)))
+        .ok_or_else(|| format!("Can't tell the format of schema file {}", path.display()))?;
+    let bin = format.read(&data)?;
+    Ok(Some(ritobin_rust::text::TextSchema::from_bin(&bin)))
+}
+
 fn detect_format(data: &[u8], path: &Path) -> Format {
-    if data.len() >= 4 && (&data[0..4] == b"PROP" || &data[0..4] == b"PTCH") {
-        return Format::Bin;
+    // Content first (PROP/PTCH magic, #PROP_text), then the extension; a
+    // byte order mark from a Windows editor doesn't count as content
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    if let Some(format) = formats().sniff(data) {
+        return format;
     }
-    
-    // Check for #PROP_text
-    if data.len() >= 10 && &data[0..10] == b"#PROP_text" {
-        return Format::Text;
+    detect_format_from_extension(path)
+}
+
+fn detect_format_from_extension(path: &Path) -> Format {
+    extensions().format_for_path(path)
+        .or_else(|| formats().get(TextFormat::NAME))
+        .expect("text format is always registered") // Default
+}
+
+fn unhash_files_command(
+    input: &Path,
+    output: Option<&Path>,
+    recursive: bool,
+    cli: &Cli,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let unhasher = setup_unhasher(cli);
+
+    if input.is_dir() && !recursive {
+        return Err("Input is a directory but --recursive is not specified".into());
     }
+    let output = writable_output(input, output, cli)?;
+    let output = output.as_deref();
+    let files: Vec<PathBuf> = if input.is_dir() {
+        WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_bin_path(e.path()))
+            .map(|e| e.into_path())
+            .collect()
+    } else {
+        vec![input.to_path_buf()]
+    };
 
-    // Check extension
-    if let Some(ext) = path.extension() {
-        if ext == "bin" { return Format::Bin; }
-        if ext == "json" { return Format::Json; }
-        if ext == "py" { return Format::Text; }
+    let mut rewritten = 0;
+    let mut failed = 0;
+    let mut total = ritobin_rust::unhash::NameStats::default();
+    for path in &files {
+        let target = match output {
+            Some(out_dir) if input.is_dir() => out_dir.join(path.strip_prefix(input).unwrap_or(path)),
+            Some(out_dir) => out_dir.join(path.file_name().unwrap_or_default()),
+            None => path.clone(),
+        };
+
+        match unhash_single_file(path, &target, &unhasher) {
+            Ok(stats) => {
+                rewritten += 1;
+                total.resolved += stats.resolved;
+                total.unresolved += stats.unresolved;
+                if cli.verbose {
+                    println!("✓ {} ({} resolved, {} unresolved)", target.display(), stats.resolved, stats.unresolved);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ {}: {}", path.display(), e);
+            }
+        }
     }
 
-    // Fallback: try to parse as JSON?
-    // Or assume Text if it looks like text?
-    // For now default to Text if not binary magic.
-    Format::Text
+    println!("\n=== Unhash Summary ===");
+    println!("Rewritten: {}", rewritten);
+    println!("Failed: {}", failed);
+    println!("Names resolved: {}", total.resolved);
+    println!("Names unresolved: {}", total.unresolved);
+
+    if failed > 0 {
+        return Err(format!("{} file(s) could not be rewritten", failed).into());
+    }
+    Ok(())
 }
 
-fn detect_format_from_extension(path: &Path) -> Format {
-    if let Some(ext) = path.extension() {
-        if ext == "bin" { return Format::Bin; }
-        if ext == "json" { return Format::Json; }
-        if ext == "py" { return Format::Text; }
+fn unhash_single_file(
+    input: &Path,
+    target: &Path,
+    unhasher: &Option<ritobin_rust::unhash::BinUnhasher>,
+) -> Result<ritobin_rust::unhash::NameStats, Box<dyn std::error::Error>> {
+    let data = read_file(input)?;
+    let mut bin = read_bin(&data)?;
+    // `target` may be `input`
+    drop(data);
+    if let Some(u) = unhasher {
+        u.unhash_bin(&mut bin);
     }
-    Format::Text // Default
+    let stats = ritobin_rust::unhash::NameStats::collect(&bin);
+    let bytes = encode(&bin, format_named(BinaryFormat::NAME)?)?;
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(long_path(parent))?;
+    }
+    // Write next to the target first so an in-place rewrite never leaves a truncated file
+    let tmp = target.with_extension("bin.tmp");
+    std::fs::write(long_path(&tmp), bytes)?;
+    std::fs::rename(long_path(&tmp), long_path(target))?;
+    Ok(stats)
 }
 
-fn info_command(input: &Path, detailed: bool) -> Result<(), Box<dyn std::error::Error>> {
-    use ritobin_rust::model::{BinValue, BinType};
-    
-    let data = std::fs::read(input)?;
-    let bin = read_bin(&data)?;
+fn signatures_command(
+    input: &Path,
+    recursive: bool,
+    min_count: usize,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::signature::SignatureIndex;
+
+    let files: Vec<PathBuf> = if input.is_dir() {
+        if !recursive {
+            return Err("Input is a directory but --recursive is not specified".into());
+        }
+        WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_bin_path(e.path()))
+            .map(|e| e.into_path())
+            .collect()
+    } else {
+        vec![input.to_path_buf()]
+    };
+
+    let mut index = SignatureIndex::new();
+    for path in &files {
+        let data = std::fs::read(path)?;
+        match read_bin(&data) {
+            Ok(bin) => index.add_bin(&path.display().to_string(), &bin),
+            Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    let shared = index.shared(min_count);
+    if json {
+        let groups: Vec<serde_json::Value> = shared.iter().map(|(sig, locs)| {
+            serde_json::json!({
+                "fingerprint": format!("{:016x}", sig.fingerprint()),
+                "class": format!("{:#010x}", sig.class),
+                "fields": sig.fields.iter().zip(&sig.types)
+                    .map(|(k, t)| serde_json::json!({ "key": format!("{:#010x}", k), "type": t.name() }))
+                    .collect::<Vec<_>>(),
+                "entries": locs.iter()
+                    .map(|l| serde_json::json!({ "source": l.source, "entry": format!("{:#010x}", l.entry) }))
+                    .collect::<Vec<_>>(),
+            })
+        }).collect();
+        println!("{}", serde_json::to_string_pretty(&groups)?);
+        return Ok(());
+    }
+
+    println!("=== Entry Signatures ===");
+    println!("Files: {}", files.len());
+    println!("Distinct signatures: {}", index.len());
+    println!("Shared by {}+ entries: {}", min_count, shared.len());
+    for (sig, locs) in shared {
+        println!();
+        println!("{:016x}  class 0x{:08x}, {} fields, {} entries", sig.fingerprint(), sig.class, sig.fields.len(), locs.len());
+        for loc in locs {
+            println!("  {}  0x{:08x}", loc.source, loc.entry);
+        }
+    }
+    Ok(())
+}
+
+fn bundle_command(champion: &str, input: &Path, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::binary::LazyBin;
+
+    let mut data = Vec::new();
+    for entry in WalkDir::new(input).into_iter().filter_map(|e| e.ok()) {
+        if entry.path().is_file() && is_bin_path(entry.path()) {
+            data.push((entry.path().to_path_buf(), read_file(entry.path())?));
+        }
+    }
+    let mut files = Vec::with_capacity(data.len());
+    for (path, data) in &data {
+        match LazyBin::parse(data) {
+            Ok(bin) => files.push((path.clone(), bin)),
+            Err(e) => warn(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    let mut bundle = ritobin_rust::bundle::champion_bundle(&files, champion)?;
+    if let Some(u) = setup_unhasher(cli) {
+        u.unhash_bin(&mut bundle.bin);
+    }
+    let output = cli.output.clone().unwrap_or_else(|| PathBuf::from(format!("{}.py", champion)));
+    let output_format = match &cli.output_format {
+        Some(name) => format_named(name)?,
+        None => extensions().format_for_path(&output).unwrap_or(format_named(TextFormat::NAME)?),
+    };
+    // The output may be one of the mapped inputs
+    let file_count = files.len();
+    drop(files);
+    drop(data);
+    write_output(&bundle.bin, &output, output_format)?;
+
+    let count = match bundle.bin.sections.get("entries") {
+        Some(ritobin_rust::model::BinValue::Map { items, .. }) => items.len(),
+        _ => 0,
+    };
+    println!("Wrote {} entries from {} of {} files to {}", count, bundle.sources.len(), file_count, output.display());
+    for source in &bundle.sources {
+        println!("  {}", source.display());
+    }
+    Ok(())
+}
+
+fn rekey_command(input: &Path, prefixes: &[String], cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::rekey::Rekey;
+
+    let mut rekey = Rekey::new();
+    for spec in prefixes {
+        let (from, to) = spec.split_once('=')
+            .ok_or_else(|| format!("Invalid prefix '{}', expected FROM=TO", spec))?;
+        rekey.add(from, to);
+    }
+
+    let (mut bin, input_format) = read_input(input, cli)?;
+    match setup_unhasher(cli) {
+        Some(u) => u.unhash_bin(&mut bin),
+        None => warn("hashes are kept, only values with names already in the file can be re-keyed".to_string()),
+    }
+    let stats = rekey.apply(&mut bin);
+
+    let output = cli.output.clone().unwrap_or_else(|| input.to_path_buf());
+    let output_format = match &cli.output_format {
+        Some(name) => format_named(name)?,
+        None => extensions().format_for_path(&output).unwrap_or(input_format),
+    };
+    write_output(&bin, &output, output_format)?;
+
+    println!("Re-keyed {} values in {}", stats.total(), output.display());
+    println!("  Entries: {}", stats.entries);
+    println!("  Hashes: {}", stats.hashes);
+    println!("  Links: {}", stats.links);
+    println!("  Files: {}", stats.files);
+    println!("  Strings: {}", stats.strings);
+    Ok(())
+}
+
+fn apply_patch_command(input: &Path, patch: &Path, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut bin, input_format) = read_input(input, cli)?;
+    let (patch_bin, _) = read_input(patch, cli)?;
+    let report = ritobin_rust::binary::patch::apply(&mut bin, &patch_bin)?;
+    if let Some(u) = setup_unhasher(cli) {
+        u.unhash_bin(&mut bin);
+    }
+
+    // Never overwrite the base unless asked to
+    let output = cli.output.clone().unwrap_or_else(|| {
+        let mut name = input.file_stem().unwrap_or_default().to_os_string();
+        name.push("_patched");
+        if let Some(ext) = input.extension() {
+            name.push(".");
+            name.push(ext);
+        }
+        input.with_file_name(name)
+    });
+    let output_format = match &cli.output_format {
+        Some(name) => format_named(name)?,
+        None => extensions().format_for_path(&output).unwrap_or(input_format),
+    };
+    write_output(&bin, &output, output_format)?;
+
+    println!("Applied {} patches and {} entries to {}", report.applied, report.entries, output.display());
+    for (target, reason) in &report.failed {
+        warn(format!("patch {} not applied: {}", target, reason));
+    }
+    Ok(())
+}
+
+fn make_patch_command(old: &Path, new: &Path, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let (old_bin, _) = read_input(old, cli)?;
+    let (new_bin, _) = read_input(new, cli)?;
+    let patch = ritobin_rust::binary::patch::diff(&old_bin, &new_bin);
+
+    let output = cli.output.clone().ok_or("make-patch needs an output file (-o)")?;
+    let output_format = match &cli.output_format {
+        Some(name) => format_named(name)?,
+        None => extensions().format_for_path(&output).unwrap_or(format_named(BinaryFormat::NAME)?),
+    };
+    write_output(&patch, &output, output_format)?;
+
+    let count = |section: &str| match patch.sections.get(section) {
+        Some(ritobin_rust::model::BinValue::Map { items, .. }) => items.len(),
+        _ => 0,
+    };
+    println!("Wrote {} patches and {} whole entries to {}", count("patches"), count("entries"), output.display());
+    Ok(())
+}
+
+fn annotate_command(
+    input: &Path,
+    path: &str,
+    note: Option<&str>,
+    todos: &[String],
+    tags: &[String],
+    clear: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::annotations::Annotations;
+
+    let path: ritobin_rust::edit::ValuePath = path.parse()?;
+    let sidecar = Annotations::sidecar_path(input);
+    let mut notes = Annotations::load_sidecar(input)?.unwrap_or_default();
+    if clear {
+        notes.remove(&path);
+    } else {
+        let annotation = notes.entry(path.clone());
+        if let Some(note) = note {
+            annotation.note = (!note.is_empty()).then(|| note.to_string());
+        }
+        annotation.todos.extend(todos.iter().cloned());
+        for tag in tags {
+            if !annotation.tags.contains(tag) {
+                annotation.tags.push(tag.clone());
+            }
+        }
+        if annotation.is_empty() {
+            notes.remove(&path);
+        }
+    }
+    notes.save(&sidecar)?;
+    println!("{} annotations in {}", notes.len(), sidecar.display());
+    Ok(())
+}
+
+fn formats_command(json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut caps = formats().capabilities();
+    // Include mappings from --ext-map and RITOBIN_EXTENSIONS
+    for (ext, name) in &extensions().user {
+        if let Some(format) = caps.formats.iter_mut().find(|f| f.name == *name) {
+            if !format.extensions.contains(ext) {
+                format.extensions.push(ext.clone());
+            }
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&caps)?);
+        return Ok(());
+    }
+    println!("ritobin_rust {}", caps.version);
+    println!("Formats:");
+    for f in &caps.formats {
+        let modes = match (f.read, f.write) {
+            (true, true) => "read/write",
+            (true, false) => "read",
+            (false, true) => "write",
+            (false, false) => "-",
+        };
+        println!("  {:<8} {:<10} .{}", f.name, modes, f.extensions.join(", ."));
+    }
+    let features = if caps.features.is_empty() { "none".to_string() } else { caps.features.join(", ") };
+    println!("Features: {}", features);
+    Ok(())
+}
+
+fn compare_command(
+    input: &Path,
+    recursive: bool,
+    float_tolerance: f32,
+    json: bool,
+    cli: &Cli,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::compare::{compare_bins, CompareOptions, Divergence};
+
+    let files: Vec<PathBuf> = if input.is_dir() {
+        if !recursive {
+            return Err("Input is a directory but --recursive is not specified".into());
+        }
+        WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_bin_path(e.path()))
+            .map(|e| e.into_path())
+            .collect()
+    } else {
+        vec![input.to_path_buf()]
+    };
+
+    let opts = CompareOptions { float_tolerance };
+    let text_format = format_named(TextFormat::NAME)?;
+    let compare_pair = |bin_path: &Path, text_path: &Path| -> Result<Vec<Divergence>, Box<dyn std::error::Error>> {
+        let bin = read_bin(&std::fs::read(bin_path)?)?;
+        let text = text_format.read(&std::fs::read(text_path)?)?;
+        Ok(compare_bins(&bin, &text, opts))
+    };
+
+    let mut results = Vec::new();
+    let mut unpaired = 0;
+    for bin_path in &files {
+        let text_path = bin_path.with_extension(extensions().extension_for(text_format));
+        if !text_path.is_file() {
+            unpaired += 1;
+            if cli.verbose {
+                eprintln!("No {} next to {}", text_path.display(), bin_path.display());
+            }
+            continue;
+        }
+        results.push((bin_path, compare_pair(bin_path, &text_path)));
+    }
+
+    let matching = results.iter().filter(|(_, r)| matches!(r, Ok(d) if d.is_empty())).count();
+    let diverging = results.len() - matching;
+
+    if json {
+        let report = serde_json::json!({
+            "pairs": results.len(),
+            "matching": matching,
+            "diverging": diverging,
+            "unpaired": unpaired,
+            "files": results.iter().map(|(path, result)| match result {
+                Ok(diffs) => serde_json::json!({
+                    "path": path.display().to_string(),
+                    "status": if diffs.is_empty() { "match" } else { "diverge" },
+                    "divergences": diffs.iter()
+                        .map(|d| serde_json::json!({ "path": d.path, "message": d.message }))
+                        .collect::<Vec<_>>(),
+                }),
+                Err(e) => serde_json::json!({
+                    "path": path.display().to_string(),
+                    "status": "error",
+                    "error": e.to_string(),
+                }),
+            }).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for (path, result) in &results {
+            match result {
+                Ok(diffs) if diffs.is_empty() => println!("✓ {}", path.display()),
+                Ok(diffs) => {
+                    println!("✗ {} ({} divergences)", path.display(), diffs.len());
+                    for d in diffs {
+                        println!("  {}: {}", d.path, d.message);
+                    }
+                }
+                Err(e) => println!("✗ {}: {}", path.display(), e),
+            }
+        }
+        println!("\n=== Comparison Summary ===");
+        println!("Pairs: {}", results.len());
+        println!("Matching: {}", matching);
+        println!("Diverging: {}", diverging);
+        if unpaired > 0 {
+            println!("Without a text file: {}", unpaired);
+        }
+    }
+
+    if diverging > 0 {
+        return Err(format!("{} file(s) diverge", diverging).into());
+    }
+    Ok(())
+}
+
+fn manifest_command(input: &Path, recursive: bool, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::manifest::entry_checksums;
+
+    let files: Vec<PathBuf> = if input.is_dir() {
+        if !recursive {
+            return Err("Input is a directory but --recursive is not specified".into());
+        }
+        let mut files: Vec<PathBuf> = WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_bin_path(e.path()))
+            .map(|e| e.into_path())
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![input.to_path_buf()]
+    };
+
+    let mut manifest = Vec::with_capacity(files.len());
+    for path in &files {
+        let bin = read_bin(&std::fs::read(path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let entries: Vec<serde_json::Value> = entry_checksums(&bin)?.iter().map(|e| {
+            let mut entry = serde_json::json!({
+                "entry": format!("{:#010x}", e.hash),
+                "class": format!("{:#010x}", e.class),
+                "size": e.size,
+                "checksum": format!("{:016x}", e.checksum),
+            });
+            if let Some(name) = &e.name {
+                entry["name"] = serde_json::json!(name);
+            }
+            entry
+        }).collect();
+        // Paths relative to the input directory so manifests compare across installs
+        let rel = if input.is_dir() { path.strip_prefix(input).unwrap_or(path) } else { path.as_path() };
+        manifest.push(serde_json::json!({
+            "path": rel.to_string_lossy().replace('\\', "/"),
+            "entries": entries,
+        }));
+    }
+
+    let json = serde_json::to_string_pretty(&serde_json::json!({ "files": manifest }))?;
+    match output {
+        Some(path) => std::fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn profile_command(input: &Path, top: usize, json: bool, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    use ritobin_rust::profile::SizeProfile;
+
+    let data = read_file(input)?;
+    let mut bin = read_bin(&data)?;
+    select_entries(cli, &mut bin);
+    if let Some(u) = setup_unhasher(cli) {
+        u.unhash_bin(&mut bin);
+    }
+    let profile = SizeProfile::from_bin(&bin);
+    let classes = profile.classes();
+    let entries = profile.largest_entries();
+    let label = |hash: u32, name: &Option<String>| match name {
+        Some(n) => n.clone(),
+        None => format!("{:#010x}", hash),
+    };
+
+    if json {
+        let report = serde_json::json!({
+            "file": input.display().to_string(),
+            "total": profile.total,
+            "kinds": profile.kinds.iter()
+                .map(|(k, b)| (k.name().to_string(), serde_json::json!(b)))
+                .collect::<serde_json::Map<_, _>>(),
+            "types": profile.types.iter()
+                .map(|(t, s)| (t.name().to_string(), serde_json::json!({ "count": s.count, "bytes": s.bytes })))
+                .collect::<serde_json::Map<_, _>>(),
+            "classes": classes.iter().take(top)
+                .map(|c| serde_json::json!({ "class": label(c.class, &c.name), "count": c.count, "bytes": c.bytes }))
+                .collect::<Vec<_>>(),
+            "entries": entries.iter().take(top)
+                .map(|e| serde_json::json!({ "entry": label(e.hash, &e.name), "class": label(e.class, &e.class_name), "bytes": e.bytes }))
+                .collect::<Vec<_>>(),
+            "patches": profile.patches,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let percent = |bytes: u64| bytes as f64 * 100.0 / profile.total.max(1) as f64;
+    println!("=== Size Profile ===");
+    println!("File: {}", input.display());
+    println!("Encoded size: {} bytes ({} entries)", profile.total, profile.entries.len());
+    println!();
+    println!("=== By Kind ===");
+    for (kind, bytes) in &profile.kinds {
+        println!("  {:<10} {:>10} bytes  {:5.1}%", kind.name(), bytes, percent(*bytes));
+    }
+    println!();
+    println!("=== By Type ===");
+    let mut types: Vec<_> = profile.types.iter().collect();
+    types.sort_by_key(|(_, size)| std::cmp::Reverse(size.bytes));
+    for (type_, size) in types {
+        println!("  {:<10} {:>10} bytes  {:5.1}%  ({} values)", type_.name(), size.bytes, percent(size.bytes), size.count);
+    }
+    println!();
+    println!("=== Top Classes ===");
+    for c in classes.iter().take(top) {
+        println!("  {:>10} bytes  {:5.1}%  {} ({} entries)", c.bytes, percent(c.bytes), label(c.class, &c.name), c.count);
+    }
+    println!();
+    println!("=== Top Entries ===");
+    for e in entries.iter().take(top) {
+        println!("  {:>10} bytes  {:5.1}%  {} ({})", e.bytes, percent(e.bytes), label(e.hash, &e.name), label(e.class, &e.class_name));
+    }
+    if profile.patches > 0 {
+        println!();
+        println!("Patches: {} bytes", profile.patches);
+    }
+    Ok(())
+}
+
+fn info_command(input: &Path, detailed: bool, preview: &PreviewOptions, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let data = read_file(input)?;
+    // Only the header of a text file is read, however large it is
+    let text = detect_format(&data, input).name() == TextFormat::NAME;
+    let mut bin = if text {
+        ritobin_rust::text::read_header(std::str::from_utf8(&data)?)?
+    } else {
+        read_bin(&data)?
+    };
+    select_entries(cli, &mut bin);
     
     println!("=== Bin File Information ===");
     println!("File: {}", input.display());
     println!("Size: {} bytes", data.len());
+    if text {
+        println!("Text file: showing the type, version and linked sections only");
+    }
     println!();
     
     println!("=== Sections ===");
@@ -562,14 +2175,14 @@ fn info_command(input: &Path, detailed: bool) -> Result<(), Box<dyn std::error::
     
     for (name, value) in &bin.sections {
         println!("  {}:", name);
-        print_value_info(value, detailed, 2);
+        print_value_info(value, detailed, preview, 2);
         println!();
     }
     
     Ok(())
 }
 
-fn print_value_info(value: &ritobin_rust::model::BinValue, detailed: bool, indent: usize) {
+fn print_value_info(value: &ritobin_rust::model::BinValue, detailed: bool, preview: &PreviewOptions, indent: usize) {
     use ritobin_rust::model::BinValue;
     let prefix = " ".repeat(indent);
     
@@ -594,8 +2207,7 @@ fn print_value_info(value: &ritobin_rust::model::BinValue, detailed: bool, inden
             if detailed {
                 println!("{}Type: String, Value: {}", prefix, v);
             } else {
-                let preview = if v.len() > 50 { format!("{}...", &v[..50]) } else { v.clone() };
-                println!("{}Type: String, Length: {}, Preview: {}", prefix, v.len(), preview);
+                println!("{}Type: String, Length: {}, Preview: {}", prefix, v.len(), preview::preview(v, preview));
             }
         },
         BinValue::Hash { value, name } => {
@@ -618,7 +2230,7 @@ fn print_value_info(value: &ritobin_rust::model::BinValue, detailed: bool, inden
                 println!("{}  Items:", prefix);
                 for (i, item) in items.iter().take(3).enumerate() {
                     println!("{}    [{}]:", prefix, i);
-                    print_value_info(item, false, indent + 6);
+                    print_value_info(item, false, preview, indent + 6);
                 }
                 if items.len() > 3 {
                     println!("{}    ... and {} more", prefix, items.len() - 3);
@@ -660,77 +2272,344 @@ fn print_value_info(value: &ritobin_rust::model::BinValue, detailed: bool, inden
             println!("{}Type: Map<{:?}, {:?}>, Count: {}", prefix, key_type, value_type, items.len());
         },
         BinValue::Flag(v) => println!("{}Type: Flag, Value: {}", prefix, v),
+        BinValue::Raw { type_byte, bytes, .. } => {
+            println!("{}Type: Unknown (0x{:02x}), Size: {} bytes", prefix, type_byte, bytes.len());
+        },
     }
 }
 
-fn validate_command(input: &Path, recursive: bool) -> Result<(), Box<dyn std::error::Error>> {
-    if input.is_dir() {
+fn validate_command(
+    input: &Path,
+    recursive: bool,
+    jobs: Option<usize>,
+    fail_fast: bool,
+    json: bool,
+    checks: ValidateChecks,
+    opts: &ReadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let files: Vec<PathBuf> = if input.is_dir() {
         if !recursive {
             return Err("Input is a directory but --recursive is not specified".into());
         }
-        validate_directory(input)?;
+        WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && is_bin_path(e.path()))
+            .map(|e| e.into_path())
+            .collect()
     } else {
-        validate_single_file(input)?;
-    }
-    Ok(())
-}
+        vec![input.to_path_buf()]
+    };
 
-fn validate_directory(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    use walkdir::WalkDir;
-    
-    let mut total = 0;
-    let mut valid = 0;
-    let mut invalid = 0;
-    
-    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("bin") {
-            total += 1;
-            match validate_single_file(path) {
-                Ok(_) => valid += 1,
-                Err(e) => {
-                    invalid += 1;
-                    eprintln!("✗ {}: {}", path.display(), e);
-                }
-            }
+    let jobs = jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, files.len().max(1));
+    let reports = validate_files(&files, jobs, fail_fast, !json, checks, opts);
+
+    let valid = reports.iter().filter(|r| r.error.is_none()).count();
+    let invalid = reports.len() - valid;
+    let skipped = files.len() - reports.len();
+
+    if json {
+        let report = serde_json::json!({
+            "total": files.len(),
+            "valid": valid,
+            "invalid": invalid,
+            "skipped": skipped,
+            "files": reports.iter().map(ValidationReport::to_json).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if files.len() > 1 || input.is_dir() {
+        println!("\n=== Validation Summary ===");
+        println!("Total files: {}", files.len());
+        println!("Valid: {}", valid);
+        println!("Invalid: {}", invalid);
+        if skipped > 0 {
+            println!("Skipped: {} (--fail-fast)", skipped);
         }
     }
-    
-    println!("\n=== Validation Summary ===");
-    println!("Total files: {}", total);
-    println!("Valid: {}", valid);
-    println!("Invalid: {}", invalid);
-    
+
     if invalid > 0 {
         return Err(format!("{} file(s) failed validation", invalid).into());
     }
-    
+
     Ok(())
 }
 
-fn validate_single_file(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let data = std::fs::read(path)?;
-    
-    // Try to read the file
-    let bin = read_bin(&data)?;
-    
+/// Checks `validate` makes beyond reading each file
+#[derive(Clone, Copy)]
+struct ValidateChecks {
+    /// Rewrite the file and compare it with the original
+    roundtrip: bool,
+    /// Fail files with warnings
+    strict: bool,
+}
+
+/// Outcome of validating one file
+struct ValidationReport {
+    path: PathBuf,
+    sections: usize,
+    warnings: Vec<String>,
+    error: Option<String>,
+    /// Byte offset and value path of a read error, when known
+    error_offset: Option<u64>,
+    error_path: Option<String>,
+}
+
+impl ValidationReport {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "path": self.path.display().to_string(),
+            "status": if self.error.is_some() { "invalid" } else { "valid" },
+            "sections": self.sections,
+            "warnings": self.warnings,
+            "error": self.error,
+            "error_offset": self.error_offset,
+            "error_path": self.error_path,
+        })
+    }
+
+    /// The report as printed, every line ending in a newline
+    fn text(&self) -> String {
+        if let Some(e) = &self.error {
+            return format!("✗ {}: {}\n", self.path.display(), e);
+        }
+        let mut text = format!("✓ {}\n  Sections: {}\n", self.path.display(), self.sections);
+        for warning in &self.warnings {
+            text += &format!("  Warning: {}\n", warning);
+        }
+        text
+    }
+
+    /// Print the report in one write, so reports of parallel workers don't interleave
+    fn print(&self) {
+        match &self.error {
+            Some(_) => eprint!("{}", self.text()),
+            None => print!("{}", self.text()),
+        }
+    }
+}
+
+/// Validate `files` on `jobs` worker threads, returning reports in input order.
+///
+/// With `fail_fast`, workers stop picking up new files after the first failure,
+/// so the result may be shorter than `files`.
+fn validate_files(files: &[PathBuf], jobs: usize, fail_fast: bool, print: bool, checks: ValidateChecks, opts: &ReadOptions) -> Vec<ValidationReport> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new(Vec::with_capacity(files.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                if fail_fast && failed.load(Ordering::Relaxed) {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(i) else { break };
+                let report = validate_single_file(path, checks, opts);
+                if report.error.is_some() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                if print {
+                    report.print();
+                }
+                results.lock().unwrap().push((i, report));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+fn validate_single_file(path: &Path, checks: ValidateChecks, opts: &ReadOptions) -> ValidationReport {
+    let mut report = ValidationReport {
+        path: path.to_path_buf(),
+        sections: 0,
+        warnings: Vec::new(),
+        error: None,
+        error_offset: None,
+        error_path: None,
+    };
+
+    let data = match read_file(path) {
+        Ok(data) => data,
+        Err(e) => {
+            report.error = Some(BinError::from(e).to_string());
+            return report;
+        }
+    };
+    let bin = match read_bin_with_warnings(&data, opts) {
+        Ok((bin, warnings)) => {
+            report.warnings.extend(warnings.iter().map(ToString::to_string));
+            bin
+        }
+        Err(e) => {
+            report.error = Some(e.to_string());
+            report.error_offset = e.offset();
+            report.error_path = e.path().map(String::from);
+            return report;
+        }
+    };
+
     // Basic validation
+    report.sections = bin.sections.len();
     if bin.sections.is_empty() {
-        return Err("File has no sections".into());
+        report.error = Some("File has no sections".to_string());
+        return report;
     }
-    
+
     // Check for common sections
-    let has_type = bin.sections.contains_key("type");
-    let has_version = bin.sections.contains_key("version");
-    
-    println!("✓ {}", path.display());
-    println!("  Sections: {}", bin.sections.len());
-    if !has_type {
-        println!("  Warning: Missing 'type' section");
+    if !bin.sections.contains_key("type") {
+        report.warnings.push("Missing 'type' section".to_string());
     }
-    if !has_version {
-        println!("  Warning: Missing 'version' section");
+    if !bin.sections.contains_key("version") {
+        report.warnings.push("Missing 'version' section".to_string());
+    }
+
+    if checks.roundtrip {
+        match ritobin_rust::binary::verify_roundtrip_with_options(&data, opts) {
+            Ok(rt) if rt.is_identical() => {}
+            Ok(rt) => {
+                report.error = Some(format!("Rewritten data {}", rt));
+                report.error_offset = rt.first_difference;
+                report.error_path = rt.path;
+            }
+            Err(e) => report.error = Some(format!("Could not rewrite: {}", e)),
+        }
+    }
+    if checks.strict && report.error.is_none() && !report.warnings.is_empty() {
+        report.error = Some(format!("{} warning(s) with --strict: {}", report.warnings.len(), report.warnings.join("; ")));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ritobin_rust::model::{Bin, BinType, BinValue, Field};
+
+    /// Warnings printed so far that mention `path`
+    fn warnings_for(path: &Path) -> Vec<String> {
+        let prefix = path.display().to_string();
+        WARNINGS.lock().unwrap().iter().filter(|w| w.starts_with(&prefix)).cloned().collect()
+    }
+
+    #[test]
+    fn test_strict_reports_read_warnings_with_other_bin_options() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed { name: 2, name_str: None, items: vec![
+                Field { key: 3, key_str: None, value: BinValue::String("~~~~".to_string()) },
+                Field { key: 4, key_str: None, value: BinValue::Map {
+                    key_type: BinType::U8,
+                    value_type: BinType::U8,
+                    items: vec![(BinValue::U8(5), BinValue::U8(6)), (BinValue::U8(5), BinValue::U8(7))],
+                }},
+            ] })],
+        });
+        let mut data = ritobin_rust::binary::write_bin(&bin).unwrap();
+        let at = data.windows(4).position(|w| w == b"~~~~").unwrap();
+        data[at] = 0xff;
+
+        for (i, args) in [&["--strict", "--duplicate-keys", "keep-first"][..], &["--strict", "--recover"]].into_iter().enumerate() {
+            let path = std::env::temp_dir().join(format!("ritobin_strict_{}_{}.bin", std::process::id(), i));
+            std::fs::write(&path, &data).unwrap();
+            let cli = Cli::try_parse_from(["ritobin_rust"].iter().chain(args)).unwrap();
+            let result = read_input(&path, &cli);
+            std::fs::remove_file(&path).ok();
+            result.unwrap();
+            let warnings = warnings_for(&path);
+            assert!(warnings.iter().any(|w| w.ends_with("is not valid UTF-8")), "{:?}: {:?}", args, warnings);
+            assert_eq!(warnings.iter().any(|w| w.contains("duplicate map key")), i == 0, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_validation_report_text() {
+        let mut report = ValidationReport {
+            path: PathBuf::from("a.bin"),
+            sections: 2,
+            warnings: vec!["Missing 'type' section".to_string(), "Missing 'version' section".to_string()],
+            error: None,
+            error_offset: None,
+            error_path: None,
+        };
+        assert_eq!(
+            report.text(),
+            "✓ a.bin\n  Sections: 2\n  Warning: Missing 'type' section\n  Warning: Missing 'version' section\n"
+        );
+        report.error = Some("Invalid magic bytes".to_string());
+        assert_eq!(report.text(), "✗ a.bin: Invalid magic bytes\n");
+    }
+
+    #[test]
+    fn test_hash_load_failures_are_warnings() {
+        let dir = std::env::temp_dir().join(format!("ritobin_load_hashes_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hashes.binfields.bin");
+        std::fs::write(&path, b"not a hash file").unwrap();
+        let loaded = load_hashes(&mut ritobin_rust::unhash::BinUnhasher::new(), &dir, false);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(!loaded);
+        let message = format!("Failed to load {}", dir.join("hashes.binfields.txt").display());
+        assert!(WARNINGS.lock().unwrap().iter().any(|w| w.starts_with(&message)), "{:?}", WARNINGS.lock().unwrap());
+    }
+
+    #[test]
+    fn test_input_format_keeps_configured_options() {
+        let text = "#PROP_text\ntype: string = \"PROP\"\nversion: u32 = 3\nentries: map[hash,embed] = {\n    \"Foo\" = Bar {\n        x = 1\n    }\n}\n";
+        let cli = Cli::try_parse_from(["ritobin_rust", "-i", "TEXT", "--output-format", "bin", "--infer-types"]).unwrap();
+        assert_eq!((cli.input_format.as_deref(), cli.output_format.as_deref()), (Some(TextFormat::NAME), Some(BinaryFormat::NAME)));
+        let registry = format_registry(&cli, None);
+        let bin = registry.get(TextFormat::NAME).unwrap().read(text.as_bytes()).unwrap();
+        assert_eq!(bin.sections["entries"].hash_keys().count(), 1);
+
+        let plain = Cli::try_parse_from(["ritobin_rust", "-i", "text"]).unwrap();
+        assert!(format_registry(&plain, None).get(TextFormat::NAME).unwrap().read(text.as_bytes()).is_err());
+        assert!(Cli::try_parse_from(["ritobin_rust", "-i", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_order_by_links() {
+        let entry = |hash: u32, links: &[u32]| (BinValue::Hash { value: hash, name: None }, BinValue::Embed {
+            name: 0,
+            name_str: None,
+            items: links.iter().map(|&link| Field { key: link, key_str: None, value: BinValue::Link { value: link, name: None } }).collect(),
+        });
+        let bin = |items| {
+            let mut bin = Bin::new();
+            bin.sections.insert("entries".to_string(), BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items });
+            bin
+        };
+        let order = |bin: &Bin| bin.sections["entries"].hash_keys().map(|(h, _)| h).collect::<Vec<_>>();
+        let on = Cli::try_parse_from(["ritobin_rust", "--order-by-links"]).unwrap();
+        let off = Cli::try_parse_from(["ritobin_rust"]).unwrap();
+
+        // Links to entries outside the bin are ignored
+        let mut linked = bin(vec![entry(1, &[3, 99]), entry(2, &[]), entry(3, &[2]), entry(4, &[1])]);
+        order_by_links(&off, &mut linked).unwrap();
+        assert_eq!(order(&linked), [1, 2, 3, 4]);
+        order_by_links(&on, &mut linked).unwrap();
+        assert_eq!(order(&linked), [2, 3, 1, 4]);
+
+        let mut cycle = bin(vec![entry(3, &[]), entry(1, &[2]), entry(2, &[1])]);
+        order_by_links(&off, &mut cycle).unwrap();
+        assert_eq!(
+            order_by_links(&on, &mut cycle).unwrap_err(),
+            "Entries link in a cycle: 0x00000001 -> 0x00000002 -> 0x00000001",
+        );
+        assert_eq!(order(&cycle), [3, 1, 2]);
     }
-    
-    Ok(())
 }
@@ -0,0 +1,114 @@
+//! Per-entry checksums for delta updates.
+//!
+//! Each entry gets its encoded size and a 64-bit checksum of its class hash and
+//! encoded record. Both depend only on the entry's content (not on names,
+//! position in the file or other entries), so comparing manifests of two game
+//! versions tells exactly which entries need to be sent.
+
+use crate::binary::{encode_entry, BinError};
+use crate::hash::fnv1a64;
+use crate::model::{Bin, BinValue};
+use std::collections::HashMap;
+
+/// Checksum of one entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryChecksum {
+    /// Entry key hash
+    pub hash: u32,
+    /// Entry name, if unhashed
+    pub name: Option<String>,
+    /// Class hash of the entry's embed
+    pub class: u32,
+    /// Size of the encoded entry record in bytes
+    pub size: u64,
+    /// FNV-1a 64 of the class hash followed by the encoded record
+    pub checksum: u64,
+}
+
+/// Checksums of every entry in `bin`, in file order.
+pub fn entry_checksums(bin: &Bin) -> Result<Vec<EntryChecksum>, BinError> {
+    let mut out = Vec::new();
+    if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+        for (key, value) in items {
+            let (BinValue::Hash { value: hash, name }, BinValue::Embed { name: class, items: fields, .. }) = (key, value) else {
+                continue;
+            };
+            let record = encode_entry(*hash, fields)?;
+            out.push(EntryChecksum {
+                hash: *hash,
+                name: name.clone(),
+                class: *class,
+                size: record.len() as u64,
+                checksum: fnv1a64(&[&class.to_le_bytes(), &record]),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// How an entry changed between two manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Entries that differ between `old` and `new`, keyed by entry hash.
+///
+/// Added and modified entries are listed in `new`'s order, followed by removed
+/// entries in `old`'s order.
+pub fn changed_entries(old: &[EntryChecksum], new: &[EntryChecksum]) -> Vec<(u32, EntryChange)> {
+    let old_by_hash: HashMap<u32, u64> = old.iter().map(|e| (e.hash, e.checksum)).collect();
+    let new_by_hash: HashMap<u32, u64> = new.iter().map(|e| (e.hash, e.checksum)).collect();
+    let mut out = Vec::new();
+    for e in new {
+        match old_by_hash.get(&e.hash) {
+            None => out.push((e.hash, EntryChange::Added)),
+            Some(c) if *c != e.checksum => out.push((e.hash, EntryChange::Modified)),
+            Some(_) => {}
+        }
+    }
+    for e in old {
+        if !new_by_hash.contains_key(&e.hash) {
+            out.push((e.hash, EntryChange::Removed));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BinType, Field};
+
+    fn bin_with(entries: Vec<(u32, Vec<Field>)>) -> Bin {
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: entries.into_iter()
+                .map(|(h, fields)| (
+                    BinValue::Hash { value: h, name: None },
+                    BinValue::Embed { name: 99, name_str: None, items: fields },
+                ))
+                .collect(),
+        });
+        bin
+    }
+
+    #[test]
+    fn test_checksums_track_content_only() {
+        let f = |v| vec![Field { key: 1, key_str: None, value: BinValue::U32(v) }];
+        let old = entry_checksums(&bin_with(vec![(1, f(1)), (2, f(2)), (3, f(3))])).unwrap();
+        let new = entry_checksums(&bin_with(vec![(4, f(4)), (2, f(20)), (1, f(1))])).unwrap();
+
+        assert_eq!(old[0].size, 4 + 4 + 2 + 4 + 1 + 4);
+        assert_eq!(old[0].checksum, new[2].checksum);
+        assert_eq!(changed_entries(&old, &new), vec![
+            (4, EntryChange::Added),
+            (2, EntryChange::Modified),
+            (3, EntryChange::Removed),
+        ]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Mechanical upgrades for fields whose type changed between patches.
+//!
+//! A migration names a field by its class and field path, e.g.
+//! `SkinCharacterDataProperties.skinScale` or `VfxEmitterDefinitionData.birthColor.constantValue`,
+//! with names hashed as FNV1a or given as `0x` hex hashes. Every structure of
+//! that class, at any depth, is followed down the path; where a step is a list,
+//! option or map the walk continues into each of its values. Values still of
+//! the old type are passed to the converter, values of any other type are
+//! assumed to be migrated already and left alone.
+//!
+//! # Examples
+//!
+//! ```
+//! use ritobin_rust::migrate::{self, Migrator};
+//! use ritobin_rust::model::{Bin, BinType, BinValue, Field};
+//!
+//! let mut bin = Bin::new();
+//! bin.sections.insert("data".to_string(), BinValue::Embed {
+//!     name: ritobin_rust::hash::fnv1a("Particle"),
+//!     name_str: None,
+//!     items: vec![Field { key: ritobin_rust::hash::fnv1a("offset"), key_str: None, value: BinValue::List {
+//!         value_type: BinType::F32,
+//!         items: vec![BinValue::F32(1.0), BinValue::F32(2.0)],
+//!     } }],
+//! });
+//!
+//! let mut migrator = Migrator::new();
+//! migrator.migrate("Particle.offset", BinType::List, BinType::Vec2, migrate::list_to_vec(BinType::Vec2))?;
+//! assert_eq!(migrator.apply(&mut bin)?, 1);
+//! # Ok::<(), String>(())
+//! ```
+
+use crate::binary::get_value_type;
+use crate::hash::fnv1a;
+use crate::model::{Bin, BinType, BinValue, Field};
+
+type Sections = indexmap::IndexMap<String, BinValue>;
+
+/// Turns a value of a migration's old type into its new type
+pub type Converter = Box<dyn Fn(BinValue) -> Result<BinValue, String>>;
+
+/// One field type change.
+pub struct Migration {
+    field_path: String,
+    class: u32,
+    fields: Vec<u32>,
+    old_type: BinType,
+    new_type: BinType,
+    convert: Converter,
+}
+
+impl Migration {
+    pub fn new<F>(field_path: &str, old_type: BinType, new_type: BinType, convert: F) -> Result<Self, String>
+    where
+        F: Fn(BinValue) -> Result<BinValue, String> + 'static,
+    {
+        let mut names = field_path.split('.');
+        let class = names.next().map(name_hash).transpose()?;
+        let fields = names.map(name_hash).collect::<Result<Vec<_>, _>>()?;
+        let Some(class) = class.filter(|_| !fields.is_empty()) else {
+            return Err(format!("Field path '{}' must be Class.field", field_path));
+        };
+        Ok(Self { field_path: field_path.to_string(), class, fields, old_type, new_type, convert: Box::new(convert) })
+    }
+
+    pub fn field_path(&self) -> &str {
+        &self.field_path
+    }
+
+    /// Migrate every matching field in `bin`; returns how many were converted
+    ///
+    /// The migration runs on a copy of the sections, so on error `bin` is
+    /// left as it was.
+    pub fn apply(&self, bin: &mut Bin) -> Result<usize, String> {
+        let mut sections = bin.sections.clone();
+        let count = self.apply_to(&mut sections)?;
+        bin.sections = sections;
+        Ok(count)
+    }
+
+    fn apply_to(&self, sections: &mut Sections) -> Result<usize, String> {
+        let mut count = 0;
+        for value in sections.values_mut() {
+            self.walk(value, &mut count)?;
+        }
+        Ok(count)
+    }
+
+    /// Find structures of the migration's class anywhere under `value`
+    fn walk(&self, value: &mut BinValue, count: &mut usize) -> Result<(), String> {
+        if let BinValue::Pointer { name, items, .. } | BinValue::Embed { name, items, .. } = value {
+            if *name == self.class {
+                self.follow(items, &self.fields, count)?;
+            }
+        }
+        for child in children(value) {
+            self.walk(child, count)?;
+        }
+        Ok(())
+    }
+
+    /// Follow the remaining `path` from a structure's fields
+    fn follow(&self, items: &mut [Field], path: &[u32], count: &mut usize) -> Result<(), String> {
+        let Some(field) = items.iter_mut().find(|f| f.key == path[0]) else {
+            return Ok(());
+        };
+        if path.len() == 1 {
+            return self.convert(&mut field.value, count);
+        }
+        self.follow_value(&mut field.value, &path[1..], count)
+    }
+
+    fn follow_value(&self, value: &mut BinValue, path: &[u32], count: &mut usize) -> Result<(), String> {
+        match value {
+            BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => self.follow(items, path, count),
+            _ => children(value).try_for_each(|child| self.follow_value(child, path, count)),
+        }
+    }
+
+    fn convert(&self, value: &mut BinValue, count: &mut usize) -> Result<(), String> {
+        if get_value_type(value) != self.old_type {
+            return Ok(());
+        }
+        // Only ever a copy of the bin, which is dropped if this fails
+        let new = (self.convert)(std::mem::replace(value, BinValue::None)).map_err(|e| format!("{}: {}", self.field_path, e))?;
+        if get_value_type(&new) != self.new_type {
+            return Err(format!(
+                "{}: converter produced {} instead of {}",
+                self.field_path,
+                get_value_type(&new).name(),
+                self.new_type.name()
+            ));
+        }
+        *value = new;
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// An ordered set of [`Migration`]s, applied one after another.
+#[derive(Default)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert `field_path` from `old_type` to `new_type` with `convert`
+    pub fn migrate<F>(&mut self, field_path: &str, old_type: BinType, new_type: BinType, convert: F) -> Result<&mut Self, String>
+    where
+        F: Fn(BinValue) -> Result<BinValue, String> + 'static,
+    {
+        self.migrations.push(Migration::new(field_path, old_type, new_type, convert)?);
+        Ok(self)
+    }
+
+    pub fn add(&mut self, migration: Migration) -> &mut Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// Apply every migration in order; returns the total number of fields converted
+    ///
+    /// Either all of them are applied or, on error, none: `bin` is left as it was.
+    pub fn apply(&self, bin: &mut Bin) -> Result<usize, String> {
+        let mut sections = bin.sections.clone();
+        let count = self.migrations.iter().map(|m| m.apply_to(&mut sections)).sum::<Result<usize, String>>()?;
+        bin.sections = sections;
+        Ok(count)
+    }
+}
+
+/// Apply a single migration to `bin`; returns how many fields were converted
+pub fn migrate<F>(bin: &mut Bin, field_path: &str, old_type: BinType, new_type: BinType, convert: F) -> Result<usize, String>
+where
+    F: Fn(BinValue) -> Result<BinValue, String> + 'static,
+{
+    Migration::new(field_path, old_type, new_type, convert)?.apply(bin)
+}
+
+fn name_hash(name: &str) -> Result<u32, String> {
+    if name.is_empty() {
+        return Err("Empty name in field path".to_string());
+    }
+    match crate::edit::parse_hex(name) {
+        Some(hash) => u32::try_from(hash).map_err(|_| format!("Hash '{}' is out of range", name)),
+        None => Ok(fnv1a(name)),
+    }
+}
+
+/// Direct children of a value: field values, list and option items, map values
+fn children(value: &mut BinValue) -> Box<dyn Iterator<Item = &mut BinValue> + '_> {
+    match value {
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => Box::new(items.iter_mut().map(|f| &mut f.value)),
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => Box::new(items.iter_mut()),
+        BinValue::Option { item: Some(item), .. } => Box::new(std::iter::once(item.as_mut())),
+        BinValue::Map { items, .. } => Box::new(items.iter_mut().map(|(_, v)| v)),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+// ============================================================================
+// Built-in converters
+// ============================================================================
+
+fn as_f64(value: &BinValue) -> Option<f64> {
+    Some(match value {
+        BinValue::Bool(v) | BinValue::Flag(v) => *v as u8 as f64,
+        BinValue::I8(v) => *v as f64,
+        BinValue::U8(v) => *v as f64,
+        BinValue::I16(v) => *v as f64,
+        BinValue::U16(v) => *v as f64,
+        BinValue::I32(v) => *v as f64,
+        BinValue::U32(v) => *v as f64,
+        BinValue::I64(v) => *v as f64,
+        BinValue::U64(v) => *v as f64,
+        BinValue::F32(v) => *v as f64,
+        _ => return None,
+    })
+}
+
+fn floats(items: &[BinValue]) -> Result<Vec<f32>, String> {
+    items.iter()
+        .map(|v| as_f64(v).map(|f| f as f32).ok_or_else(|| format!("{} is not a number", get_value_type(v).name())))
+        .collect()
+}
+
+/// Convert between number and bool types, failing when the value doesn't fit `to`
+pub fn numeric(to: BinType) -> impl Fn(BinValue) -> Result<BinValue, String> {
+    move |value| {
+        let n = as_f64(&value).ok_or_else(|| format!("{} is not a number", get_value_type(&value).name()))?;
+        let fits = |min: f64, max: f64| n.fract() == 0.0 && (min..=max).contains(&n);
+        let out_of_range = || format!("{} does not fit in {}", n, to.name());
+        Ok(match to {
+            BinType::Bool => BinValue::Bool(n != 0.0),
+            BinType::Flag => BinValue::Flag(n != 0.0),
+            BinType::F32 => BinValue::F32(n as f32),
+            BinType::I8 if fits(i8::MIN as f64, i8::MAX as f64) => BinValue::I8(n as i8),
+            BinType::U8 if fits(0.0, u8::MAX as f64) => BinValue::U8(n as u8),
+            BinType::I16 if fits(i16::MIN as f64, i16::MAX as f64) => BinValue::I16(n as i16),
+            BinType::U16 if fits(0.0, u16::MAX as f64) => BinValue::U16(n as u16),
+            BinType::I32 if fits(i32::MIN as f64, i32::MAX as f64) => BinValue::I32(n as i32),
+            BinType::U32 if fits(0.0, u32::MAX as f64) => BinValue::U32(n as u32),
+            BinType::I64 if fits(i64::MIN as f64, i64::MAX as f64) => BinValue::I64(n as i64),
+            BinType::U64 if fits(0.0, u64::MAX as f64) => BinValue::U64(n as u64),
+            BinType::I8 | BinType::U8 | BinType::I16 | BinType::U16 | BinType::I32 | BinType::U32 | BinType::I64 | BinType::U64 => {
+                return Err(out_of_range());
+            }
+            _ => return Err(format!("{} is not a numeric type", to.name())),
+        })
+    }
+}
+
+/// A list of numbers to a `vec2`, `vec3`, `vec4` or `mtx44` of the same length
+pub fn list_to_vec(to: BinType) -> impl Fn(BinValue) -> Result<BinValue, String> {
+    move |value| {
+        let (BinValue::List { items, .. } | BinValue::List2 { items, .. }) = value else {
+            return Err("expected a list".to_string());
+        };
+        let v = floats(&items)?;
+        let wrong_len = |n: usize| format!("expected {} items for {}, found {}", n, to.name(), v.len());
+        Ok(match to {
+            BinType::Vec2 => BinValue::Vec2(v.as_slice().try_into().map_err(|_| wrong_len(2))?),
+            BinType::Vec3 => BinValue::Vec3(v.as_slice().try_into().map_err(|_| wrong_len(3))?),
+            BinType::Vec4 => BinValue::Vec4(v.as_slice().try_into().map_err(|_| wrong_len(4))?),
+            BinType::Mtx44 => BinValue::Mtx44(v.as_slice().try_into().map_err(|_| wrong_len(16))?),
+            _ => return Err(format!("{} is not a vector type", to.name())),
+        })
+    }
+}
+
+/// A `vec2`, `vec3`, `vec4` or `mtx44` to a `list[f32]`
+pub fn vec_to_list() -> impl Fn(BinValue) -> Result<BinValue, String> {
+    |value| {
+        let items: Vec<f32> = match value {
+            BinValue::Vec2(v) => v.to_vec(),
+            BinValue::Vec3(v) => v.to_vec(),
+            BinValue::Vec4(v) => v.to_vec(),
+            BinValue::Mtx44(v) => v.to_vec(),
+            other => return Err(format!("expected a vector, found {}", get_value_type(&other).name())),
+        };
+        Ok(BinValue::List { value_type: BinType::F32, items: items.into_iter().map(BinValue::F32).collect() })
+    }
+}
+
+/// A list to an embed of class `class`, its items becoming the fields named in `fields`, in order
+///
+/// Extra list items are an error; missing ones leave their fields out.
+pub fn list_to_embed(class: &str, fields: &[&str]) -> impl Fn(BinValue) -> Result<BinValue, String> {
+    let class = class.to_string();
+    let fields: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+    move |value| {
+        let (BinValue::List { items, .. } | BinValue::List2 { items, .. }) = value else {
+            return Err("expected a list".to_string());
+        };
+        if items.len() > fields.len() {
+            return Err(format!("{} items but only {} field names", items.len(), fields.len()));
+        }
+        let items = fields.iter()
+            .zip(items)
+            .map(|(name, value)| Field { key: fnv1a(name), key_str: Some(name.clone()), value })
+            .collect();
+        Ok(BinValue::Embed { name: fnv1a(&class), name_str: Some(class.clone()), items })
+    }
+}
+
+/// Any value to an `option` holding it
+pub fn wrap_option() -> impl Fn(BinValue) -> Result<BinValue, String> {
+    |value| Ok(BinValue::Option { value_type: get_value_type(&value), item: Some(Box::new(value)) })
+}
+
+/// Any value to a single-item `list`
+pub fn wrap_list() -> impl Fn(BinValue) -> Result<BinValue, String> {
+    |value| Ok(BinValue::List { value_type: get_value_type(&value), items: vec![value] })
+}
+
+/// Between `hash`, `link` and `u32`, keeping the hash value and any resolved name
+pub fn rehash(to: BinType) -> impl Fn(BinValue) -> Result<BinValue, String> {
+    move |value| {
+        let (value, name) = match value {
+            BinValue::Hash { value, name } | BinValue::Link { value, name } => (value, name),
+            BinValue::U32(value) => (value, None),
+            other => return Err(format!("expected a hash, found {}", get_value_type(&other).name())),
+        };
+        Ok(match to {
+            BinType::Hash => BinValue::Hash { value, name },
+            BinType::Link => BinValue::Link { value, name },
+            BinType::U32 => BinValue::U32(value),
+            _ => return Err(format!("{} is not a hash type", to.name())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_nested_list_to_embed() {
+        let spawner = |value: BinValue| BinValue::Embed {
+            name: fnv1a("Spawner"),
+            name_str: None,
+            items: vec![Field { key: fnv1a("range"), key_str: None, value }],
+        };
+        let range = BinValue::List { value_type: BinType::F32, items: vec![BinValue::F32(1.0), BinValue::F32(2.0)] };
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(BinValue::Hash { value: 1, name: None }, BinValue::Embed {
+                name: fnv1a("Emitter"),
+                name_str: None,
+                items: vec![Field { key: fnv1a("spawners"), key_str: None, value: BinValue::List {
+                    value_type: BinType::Embed,
+                    items: vec![spawner(range.clone()), spawner(BinValue::F32(3.0))],
+                } }],
+            })],
+        });
+
+        let mut migrator = Migrator::new();
+        migrator.migrate("Emitter.spawners.range", BinType::List, BinType::Embed, list_to_embed("Range", &["min", "max"])).unwrap();
+        assert_eq!(migrator.apply(&mut bin).unwrap(), 1);
+        // Already migrated (and unrelated) values are left as they are
+        assert_eq!(migrator.apply(&mut bin).unwrap(), 0);
+
+        let migrated = &bin.sections["entries"];
+        let BinValue::Map { items, .. } = migrated else { panic!() };
+        let BinValue::Embed { items, .. } = &items[0].1 else { panic!() };
+        let BinValue::List { items: spawners, .. } = &items[0].value else { panic!() };
+        let BinValue::Embed { items, .. } = &spawners[0] else { panic!() };
+        let BinValue::Embed { name, items: range, .. } = &items[0].value else { panic!() };
+        assert_eq!(*name, fnv1a("Range"));
+        assert_eq!(range[1], Field { key: fnv1a("max"), key_str: Some("max".to_string()), value: BinValue::F32(2.0) });
+        assert_eq!(spawners[1], spawner(BinValue::F32(3.0)));
+
+        let before = bin.clone();
+        let err = migrate(&mut bin, "Spawner.range", BinType::F32, BinType::I8, numeric(BinType::U32)).unwrap_err();
+        assert!(err.contains("produced u32"));
+        assert_eq!(bin, before);
+        // A later failure undoes the migrations before it too
+        let mut migrator = Migrator::new();
+        migrator.migrate("Spawner.range", BinType::F32, BinType::List, wrap_list()).unwrap();
+        migrator.migrate("Range.min", BinType::F32, BinType::I8, numeric(BinType::U32)).unwrap();
+        assert!(migrator.apply(&mut bin).unwrap_err().contains("produced u32"));
+        assert_eq!(bin, before);
+        assert!(numeric(BinType::U8)(BinValue::I32(300)).is_err());
+        assert!(Migration::new("range", BinType::F32, BinType::I32, numeric(BinType::I32)).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Read-only file mapping for the `mmap` feature.
+//!
+//! Uses `mmap(2)` directly on Unix so no extra dependency is needed; other
+//! platforms fall back to reading the file into memory. The file must not be
+//! modified while it is mapped, which is why [`Mmap::open`] and
+//! [`read_bin_mmap`](crate::binary::read_bin_mmap) are `unsafe`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let file = std::fs::File::open("Map11.bin")?;
+//! // SAFETY: nothing else writes to Map11.bin while it is read
+//! let map = unsafe { ritobin_rust::mmap::Mmap::open(&file)? };
+//! let bin = ritobin_rust::binary::read_bin(&map)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+/// A read-only view of a whole file.
+///
+/// Dereferences to the file's bytes.
+pub struct Mmap {
+    #[cfg(unix)]
+    ptr: *mut std::ffi::c_void,
+    #[cfg(unix)]
+    len: usize,
+    #[cfg(not(unix))]
+    data: Vec<u8>,
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_int, c_long, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    // `off_t` is a `long` on Linux and on every 64-bit Unix; 32-bit BSDs,
+    // where it is 64 bits wide, are not supported
+    extern "C" {
+        pub fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+impl Mmap {
+    /// Map all of `file`
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written to, by this or any other
+    /// process, while the map is alive. The bytes would change under the
+    /// `&[u8]` it derefs to, and reading past a truncated end raises `SIGBUS`.
+    #[cfg(unix)]
+    pub unsafe fn open(file: &File) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        if len == 0 {
+            // mmap rejects empty mappings
+            return Ok(Self { ptr: std::ptr::null_mut(), len: 0 });
+        }
+        // SAFETY: a fresh private read-only mapping of an open file descriptor;
+        // the pointer is only used for `len` bytes and unmapped on drop.
+        let ptr = unsafe {
+            sys::mmap(std::ptr::null_mut(), len, sys::PROT_READ, sys::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == sys::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// Read all of `file`; mapping is only implemented on Unix
+    ///
+    /// # Safety
+    ///
+    /// Always safe here; `unsafe` to match the Unix version.
+    #[cfg(not(unix))]
+    pub unsafe fn open(file: &File) -> io::Result<Self> {
+        use std::io::Read;
+
+        let mut data = Vec::new();
+        (&*file).read_to_end(&mut data)?;
+        Ok(Self { data })
+    }
+}
+
+// SAFETY: the mapping is read-only and owned by `self`, so it can be read
+// from any thread and unmapped from whichever thread drops it
+#[cfg(unix)]
+unsafe impl Send for Mmap {}
+#[cfg(unix)]
+unsafe impl Sync for Mmap {}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: `ptr` maps `len` readable bytes for the lifetime of `self`
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: unmapping exactly the region returned by mmap
+            unsafe {
+                sys::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
@@ -18,7 +18,7 @@ use serde::{Deserialize, Serialize};
 /// assert!(BinType::Map.is_container());
 /// assert!(!BinType::String.is_container());
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BinType {
     None = 0,
     Bool = 1,
@@ -39,7 +39,7 @@ pub enum BinType {
     String = 16,
     Hash = 17,
     File = 18,
-    List = 0x80 | 0,
+    List = 0x80,
     List2 = 0x80 | 1,
     Pointer = 0x80 | 2,
     Embed = 0x80 | 3,
@@ -100,6 +100,64 @@ impl BinType {
     pub fn is_container(&self) -> bool {
         matches!(self, BinType::Option | BinType::List | BinType::List2 | BinType::Map)
     }
+
+    /// The smallest number of bytes a value of this type takes in a `.bin` file.
+    ///
+    /// Fixed-size types always take exactly this much; strings, containers and
+    /// structures take at least their length, count and class fields.
+    pub fn min_size(&self) -> u64 {
+        match self {
+            BinType::None => 0,
+            BinType::Bool | BinType::I8 | BinType::U8 | BinType::Flag => 1,
+            BinType::I16 | BinType::U16 | BinType::String => 2,
+            BinType::I32 | BinType::U32 | BinType::F32 | BinType::Rgba | BinType::Hash | BinType::Link => 4,
+            BinType::I64 | BinType::U64 | BinType::Vec2 | BinType::File => 8,
+            BinType::Vec3 => 12,
+            BinType::Vec4 => 16,
+            BinType::Mtx44 => 64,
+            // Element type and "none" flag, or a null class hash
+            BinType::Option => 2,
+            BinType::Pointer => 4,
+            // Class hash, size and field count
+            BinType::Embed => 10,
+            // Element type(s), size and count
+            BinType::List | BinType::List2 => 9,
+            BinType::Map => 10,
+        }
+    }
+
+    /// The lowercase type name used by the text and JSON formats (inverse of `FromStr`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            BinType::None => "none",
+            BinType::Bool => "bool",
+            BinType::I8 => "i8",
+            BinType::U8 => "u8",
+            BinType::I16 => "i16",
+            BinType::U16 => "u16",
+            BinType::I32 => "i32",
+            BinType::U32 => "u32",
+            BinType::I64 => "i64",
+            BinType::U64 => "u64",
+            BinType::F32 => "f32",
+            BinType::Vec2 => "vec2",
+            BinType::Vec3 => "vec3",
+            BinType::Vec4 => "vec4",
+            BinType::Mtx44 => "mtx44",
+            BinType::Rgba => "rgba",
+            BinType::String => "string",
+            BinType::Hash => "hash",
+            BinType::File => "file",
+            BinType::List => "list",
+            BinType::List2 => "list2",
+            BinType::Pointer => "pointer",
+            BinType::Embed => "embed",
+            BinType::Link => "link",
+            BinType::Option => "option",
+            BinType::Map => "map",
+            BinType::Flag => "flag",
+        }
+    }
 }
 
 
@@ -225,6 +283,145 @@ pub enum BinValue {
     },
     /// Boolean flag
     Flag(bool),
+    /// Undecoded field value, kept so unknown types round-trip byte for byte
+    ///
+    /// Only produced by readers with `preserve_unknown_types` set. `bytes` is
+    /// the encoding that follows the field's type byte. When `type_byte` is
+    /// itself unknown its size can't be determined, so `bytes` runs to the end
+    /// of the enclosing structure and also holds the `absorbed_fields` fields
+    /// that followed it.
+    Raw {
+        type_byte: u8,
+        bytes: Vec<u8>,
+        absorbed_fields: u16,
+    },
+}
+
+/// Lookups on maps keyed by `hash`, such as the `entries` section.
+///
+/// Names are hashed with FNV1a, so lookups by name are case-insensitive and
+/// work whether or not the keys have been unhashed. On any other value these
+/// find nothing.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::{BinType, BinValue};
+///
+/// let mut entries = BinValue::Map {
+///     key_type: BinType::Hash,
+///     value_type: BinType::U32,
+///     items: vec![(BinValue::Hash { value: ritobin_rust::hash::fnv1a("Ahri"), name: None }, BinValue::U32(1))],
+/// };
+/// assert_eq!(entries.get_by_name("ahri"), Some(&BinValue::U32(1)));
+///
+/// entries.rename_key(ritobin_rust::hash::fnv1a("Ahri"), "Annie")?;
+/// assert_eq!(entries.hash_keys().next(), Some((ritobin_rust::hash::fnv1a("Annie"), Some("Annie"))));
+/// # Ok::<(), String>(())
+/// ```
+impl BinValue {
+    /// The map value stored under the key `hash`
+    pub fn get_by_hash(&self, hash: u32) -> Option<&BinValue> {
+        let BinValue::Map { items, .. } = self else { return None };
+        items.iter().find(|(k, _)| key_hash(k) == Some(hash)).map(|(_, v)| v)
+    }
+
+    pub fn get_by_hash_mut(&mut self, hash: u32) -> Option<&mut BinValue> {
+        let BinValue::Map { items, .. } = self else { return None };
+        items.iter_mut().find(|(k, _)| key_hash(k) == Some(hash)).map(|(_, v)| v)
+    }
+
+    /// The map value stored under the key named `name`
+    pub fn get_by_name(&self, name: &str) -> Option<&BinValue> {
+        self.get_by_hash(crate::hash::fnv1a(name))
+    }
+
+    pub fn get_by_name_mut(&mut self, name: &str) -> Option<&mut BinValue> {
+        self.get_by_hash_mut(crate::hash::fnv1a(name))
+    }
+
+    /// Each hash key of the map with its resolved name, if known
+    pub fn hash_keys(&self) -> impl Iterator<Item = (u32, Option<&str>)> {
+        self.hash_items().map(|(hash, name, _)| (hash, name))
+    }
+
+    /// Each item of the map as (key hash, resolved key name, value)
+    pub fn hash_items(&self) -> impl Iterator<Item = (u32, Option<&str>, &BinValue)> {
+        let items = match self {
+            BinValue::Map { items, .. } => items.as_slice(),
+            _ => &[],
+        };
+        items.iter().filter_map(|(k, v)| match k {
+            BinValue::Hash { value, name } => Some((*value, name.as_deref(), v)),
+            _ => None,
+        })
+    }
+
+    /// Re-key the item under `hash` to `new_name`, keeping its position
+    ///
+    /// Fails if there is no such item or another item already uses the new name.
+    pub fn rename_key(&mut self, hash: u32, new_name: &str) -> Result<(), String> {
+        let new_hash = crate::hash::fnv1a(new_name);
+        let BinValue::Map { items, .. } = self else {
+            return Err("Not a map".to_string());
+        };
+        if new_hash != hash && items.iter().any(|(k, _)| key_hash(k) == Some(new_hash)) {
+            return Err(format!("Key {} already exists", new_name));
+        }
+        let (key, _) = items.iter_mut()
+            .find(|(k, _)| key_hash(k) == Some(hash))
+            .ok_or_else(|| format!("No key {:#010x}", hash))?;
+        *key = BinValue::Hash { value: new_hash, name: Some(new_name.to_string()) };
+        Ok(())
+    }
+}
+
+pub(crate) fn key_hash(key: &BinValue) -> Option<u32> {
+    match key {
+        BinValue::Hash { value, .. } => Some(*value),
+        _ => None,
+    }
+}
+
+/// Call `f` with the target of every `Link` inside `value`
+pub(crate) fn visit_links(value: &BinValue, f: &mut impl FnMut(u32)) {
+    match value {
+        BinValue::Link { value, .. } => f(*value),
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+            for item in items {
+                visit_links(item, f);
+            }
+        }
+        BinValue::Option { item: Some(inner), .. } => visit_links(inner, f),
+        BinValue::Map { items, .. } => {
+            for (k, v) in items {
+                visit_links(k, f);
+                visit_links(v, f);
+            }
+        }
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+            for field in items {
+                visit_links(&field.value, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Lowercase hex encoding of `bytes`, as used for [`BinValue::Raw`] in text and JSON
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Inverse of [`hex_encode`]
+pub(crate) fn hex_decode(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(format!("Invalid hex string: {}", s));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format!("Invalid hex string: {}", s)))
+        .collect()
 }
 
 /// A field in a `Pointer` or `Embed` structure.
@@ -271,6 +468,336 @@ impl Bin {
             sections: indexmap::IndexMap::new(),
         }
     }
+
+    /// Move section `key` to just before section `before`.
+    ///
+    /// Returns false, changing nothing, if either section is missing.
+    pub fn move_section_before(&mut self, key: &str, before: &str) -> bool {
+        let (Some(from), Some(to)) = (self.sections.get_index_of(key), self.sections.get_index_of(before)) else {
+            return false;
+        };
+        // Removing `from` shifts everything after it down by one
+        self.sections.move_index(from, if from < to { to - 1 } else { to });
+        true
+    }
+
+    /// Put the sections in the order the binary format stores them: `type`,
+    /// `ptch_header`, `version`, `linked`, `entries`, `patches`, then any
+    /// others by name.
+    pub fn sort_sections(&mut self) {
+        const ORDER: [&str; 6] = ["type", "ptch_header", "version", "linked", "entries", "patches"];
+        let rank = |key: &str| ORDER.iter().position(|k| *k == key).unwrap_or(ORDER.len());
+        self.sections.sort_by(|a, _, b, _| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    }
+
+    /// Sort sections with a comparator on (name, value) pairs
+    pub fn sort_sections_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&str, &BinValue, &str, &BinValue) -> std::cmp::Ordering,
+    {
+        self.sections.sort_by(|k1, v1, k2, v2| compare(k1, v1, k2, v2));
+    }
+
+    /// Sort the `entries` and `patches` maps by key hash
+    pub fn sort_entries(&mut self) {
+        self.sort_entries_by_key(|hash, _| hash);
+    }
+
+    /// Sort the `entries` and `patches` maps by a key computed from each (hash, value)
+    ///
+    /// The sort is stable, so entries with equal keys keep their order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ritobin_rust::model::{Bin, BinType, BinValue};
+    ///
+    /// let mut bin = Bin::new();
+    /// let entry = |h| (BinValue::Hash { value: h, name: None }, BinValue::None);
+    /// bin.sections.insert("entries".to_string(), BinValue::Map {
+    ///     key_type: BinType::Hash,
+    ///     value_type: BinType::Embed,
+    ///     items: vec![entry(3), entry(1), entry(2)],
+    /// });
+    /// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+    ///
+    /// bin.sort_entries();
+    /// bin.sort_sections();
+    /// assert_eq!(bin.sections.keys().collect::<Vec<_>>(), ["type", "entries"]);
+    /// assert_eq!(bin.sections["entries"].hash_keys().map(|(h, _)| h).collect::<Vec<_>>(), [1, 2, 3]);
+    /// ```
+    pub fn sort_entries_by_key<K: Ord, F: FnMut(u32, &BinValue) -> K>(&mut self, mut key: F) {
+        for section in ["entries", "patches"] {
+            if let Some(BinValue::Map { items, .. }) = self.sections.get_mut(section) {
+                items.sort_by_cached_key(|(k, v)| key(key_hash(k).unwrap_or(0), v));
+            }
+        }
+    }
+
+    /// Order the `entries` map so every entry comes after the entries it links to.
+    ///
+    /// Links to entries outside this bin, and entries linking to themselves,
+    /// are ignored; otherwise entries keep their relative order. If the links
+    /// form a cycle nothing is moved and the key hashes of the cycle are
+    /// returned, each linking to the next and the last to the first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ritobin_rust::model::{Bin, BinType, BinValue, Field};
+    ///
+    /// let mut bin = Bin::new();
+    /// let entry = |h, link: u32| (BinValue::Hash { value: h, name: None }, BinValue::Embed {
+    ///     name: 0,
+    ///     name_str: None,
+    ///     items: vec![Field { key: 1, key_str: None, value: BinValue::Link { value: link, name: None } }],
+    /// });
+    /// bin.sections.insert("entries".to_string(), BinValue::Map {
+    ///     key_type: BinType::Hash,
+    ///     value_type: BinType::Embed,
+    ///     items: vec![entry(1, 2), entry(2, 3), entry(3, 0)],
+    /// });
+    ///
+    /// bin.sort_entries_by_links().unwrap();
+    /// assert_eq!(bin.sections["entries"].hash_keys().map(|(h, _)| h).collect::<Vec<_>>(), [3, 2, 1]);
+    ///
+    /// bin.sections["entries"] = BinValue::Map {
+    ///     key_type: BinType::Hash,
+    ///     value_type: BinType::Embed,
+    ///     items: vec![entry(1, 2), entry(2, 3), entry(3, 1)],
+    /// };
+    /// assert_eq!(bin.sort_entries_by_links(), Err(vec![1, 2, 3]));
+    /// ```
+    pub fn sort_entries_by_links(&mut self) -> Result<(), Vec<u32>> {
+        let Some(BinValue::Map { items, .. }) = self.sections.get_mut("entries") else {
+            return Ok(());
+        };
+        let positions: std::collections::HashMap<u32, usize> = items.iter().enumerate()
+            .filter_map(|(i, (k, _))| Some((key_hash(k)?, i)))
+            .collect();
+        let links: Vec<Vec<usize>> = items.iter().enumerate().map(|(i, (_, v))| {
+            let mut targets = Vec::new();
+            visit_links(v, &mut |hash| {
+                if let Some(&target) = positions.get(&hash) {
+                    if target != i {
+                        targets.push(target);
+                    }
+                }
+            });
+            targets
+        }).collect();
+
+        // Depth-first, emitting an entry once all of its targets have been
+        const NEW: u8 = 0;
+        const OPEN: u8 = 1;
+        const DONE: u8 = 2;
+        let mut state = vec![NEW; items.len()];
+        let mut order = Vec::with_capacity(items.len());
+        for root in 0..items.len() {
+            if state[root] != NEW {
+                continue;
+            }
+            state[root] = OPEN;
+            let mut stack = vec![(root, 0)];
+            while let Some(&(node, next)) = stack.last() {
+                let Some(&target) = links[node].get(next) else {
+                    state[node] = DONE;
+                    order.push(node);
+                    stack.pop();
+                    continue;
+                };
+                stack.last_mut().expect("stack is not empty").1 += 1;
+                match state[target] {
+                    NEW => {
+                        state[target] = OPEN;
+                        stack.push((target, 0));
+                    }
+                    OPEN => {
+                        let start = stack.iter().position(|&(n, _)| n == target).expect("open entries are on the stack");
+                        return Err(stack[start..].iter().map(|&(n, _)| key_hash(&items[n].0).unwrap_or(0)).collect());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut slots: Vec<_> = std::mem::take(items).into_iter().map(Some).collect();
+        *items = order.into_iter().map(|i| slots[i].take().expect("each entry is ordered once")).collect();
+        Ok(())
+    }
+
+    /// Every hash in the bin with where it appears and whether it is named,
+    /// see [`HashReport`](crate::hash_report::HashReport)
+    pub fn collect_hashes(&self) -> crate::hash_report::HashReport {
+        crate::hash_report::HashReport::collect(self)
+    }
+
+    /// The entry named `name` in the `entries` section
+    pub fn entry(&self, name: &str) -> Option<&BinValue> {
+        self.sections.get("entries")?.get_by_name(name)
+    }
+
+    pub fn entry_mut(&mut self, name: &str) -> Option<&mut BinValue> {
+        self.sections.get_mut("entries")?.get_by_name_mut(name)
+    }
+
+    /// Keep only the entries for which `keep(index, hash)` returns true.
+    ///
+    /// Applies to the `"entries"` and `"patches"` maps, where `index` is the
+    /// entry's position within its map. Returns the number of entries removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ritobin_rust::model::{Bin, BinType, BinValue};
+    ///
+    /// let mut bin = Bin::new();
+    /// let entry = |h| (BinValue::Hash { value: h, name: None }, BinValue::None);
+    /// bin.sections.insert("entries".to_string(), BinValue::Map {
+    ///     key_type: BinType::Hash,
+    ///     value_type: BinType::Embed,
+    ///     items: vec![entry(1), entry(2), entry(3)],
+    /// });
+    ///
+    /// assert_eq!(bin.retain_entries(|i, _| i < 2), 1);
+    /// ```
+    pub fn retain_entries<F: FnMut(usize, u32) -> bool>(&mut self, mut keep: F) -> usize {
+        let mut removed = 0;
+        for section in ["entries", "patches"] {
+            if let Some(BinValue::Map { items, .. }) = self.sections.get_mut(section) {
+                let before = items.len();
+                let mut index = 0;
+                items.retain(|(key, _)| {
+                    let hash = match key {
+                        BinValue::Hash { value, .. } => *value,
+                        _ => 0,
+                    };
+                    index += 1;
+                    keep(index - 1, hash)
+                });
+                removed += before - items.len();
+            }
+        }
+        removed
+    }
+
+    /// Move the entries for which `take(hash, value)` returns true into a new bin.
+    ///
+    /// The new bin has copies of every other section (`type`, `version`,
+    /// `linked`, ...) but no patches, so it can be written on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ritobin_rust::model::{Bin, BinType, BinValue};
+    ///
+    /// let mut bin = Bin::new();
+    /// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+    /// let entry = |h, class| (BinValue::Hash { value: h, name: None }, BinValue::Embed { name: class, name_str: None, items: vec![] });
+    /// bin.sections.insert("entries".to_string(), BinValue::Map {
+    ///     key_type: BinType::Hash,
+    ///     value_type: BinType::Embed,
+    ///     items: vec![entry(1, 10), entry(2, 20), entry(3, 10)],
+    /// });
+    ///
+    /// let tens = bin.split_off_entries(|_, value| matches!(value, BinValue::Embed { name: 10, .. }));
+    /// assert_eq!(tens.sections["entries"].hash_keys().map(|(h, _)| h).collect::<Vec<_>>(), [1, 3]);
+    /// assert_eq!(bin.sections["entries"].hash_keys().map(|(h, _)| h).collect::<Vec<_>>(), [2]);
+    /// assert_eq!(tens.sections["type"], bin.sections["type"]);
+    /// ```
+    pub fn split_off_entries<F: FnMut(u32, &BinValue) -> bool>(&mut self, mut take: F) -> Bin {
+        let mut out = Bin::new();
+        for (key, value) in &mut self.sections {
+            match (key.as_str(), value) {
+                ("entries", BinValue::Map { key_type, value_type, items }) => {
+                    let (taken, kept) = std::mem::take(items).into_iter()
+                        .partition(|(k, v)| take(key_hash(k).unwrap_or(0), v));
+                    *items = kept;
+                    out.sections.insert(key.clone(), BinValue::Map { key_type: *key_type, value_type: *value_type, items: taken });
+                }
+                ("patches", _) => {}
+                (_, value) => {
+                    out.sections.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        out
+    }
+
+    /// Paths in the `linked` section, the files this bin depends on
+    pub fn linked_files(&self) -> impl Iterator<Item = &str> {
+        let items = match self.sections.get("linked") {
+            Some(BinValue::List { items, .. }) => items.as_slice(),
+            _ => &[],
+        };
+        items.iter().filter_map(|item| match item {
+            BinValue::String(s) => Some(s.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Add `path` to the `linked` section, creating it after `version` if needed.
+    ///
+    /// Fails if the bin's version is below 2, which has no linked files.
+    /// Returns false if `path` was already linked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ritobin_rust::model::{Bin, BinValue};
+    ///
+    /// let mut bin = Bin::new();
+    /// bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+    /// bin.sections.insert("version".to_string(), BinValue::U32(3));
+    ///
+    /// assert_eq!(bin.add_linked("DATA/Shared.bin"), Ok(true));
+    /// assert_eq!(bin.add_linked("DATA/Shared.bin"), Ok(false));
+    /// assert_eq!(bin.linked_files().collect::<Vec<_>>(), ["DATA/Shared.bin"]);
+    /// assert!(bin.remove_linked("DATA/Shared.bin"));
+    /// assert_eq!(bin.linked_files().count(), 0);
+    ///
+    /// bin.sections.insert("version".to_string(), BinValue::U32(1));
+    /// assert!(bin.add_linked("DATA/Shared.bin").is_err());
+    /// ```
+    pub fn add_linked(&mut self, path: &str) -> Result<bool, String> {
+        match self.sections.get("version") {
+            Some(BinValue::U32(v)) if *v >= 2 => {}
+            Some(BinValue::U32(v)) => return Err(format!("Version {} bins have no linked files", v)),
+            _ => return Err("Bin has no version section".to_string()),
+        }
+        if self.linked_files().any(|p| p == path) {
+            return Ok(false);
+        }
+        if !self.sections.contains_key("linked") {
+            let at = self.sections.get_index_of("version").map_or(self.sections.len(), |i| i + 1);
+            self.sections.shift_insert(at, "linked".to_string(), BinValue::List { value_type: BinType::String, items: Vec::new() });
+        }
+        match self.sections.get_mut("linked") {
+            Some(BinValue::List { value_type: BinType::String, items }) => {
+                items.push(BinValue::String(path.to_string()));
+                Ok(true)
+            }
+            _ => Err("linked section is not a list of strings".to_string()),
+        }
+    }
+
+    /// Remove `path` from the `linked` section; returns false if it wasn't linked
+    pub fn remove_linked(&mut self, path: &str) -> bool {
+        let Some(BinValue::List { items, .. }) = self.sections.get_mut("linked") else {
+            return false;
+        };
+        let before = items.len();
+        items.retain(|item| !matches!(item, BinValue::String(s) if s == path));
+        items.len() != before
+    }
+
+    /// Number of items in the `entries` section
+    pub fn entry_count(&self) -> usize {
+        match self.sections.get("entries") {
+            Some(BinValue::Map { items, .. }) => items.len(),
+            _ => 0,
+        }
+    }
 }
 
 impl Default for Bin {
@@ -0,0 +1,95 @@
+//! Path helpers for deep game install directories.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// `path` in a form that can be opened regardless of length.
+///
+/// On Windows, paths of `MAX_PATH` (260) characters or more are made absolute
+/// and given the extended-length `\\?\` prefix (`\\?\UNC\` for network shares).
+/// Everywhere else, and for short paths, `path` is returned unchanged.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+        if path.as_os_str().len() >= MAX_PATH {
+            if let Some(extended) = extended_length(path) {
+                return Cow::Owned(extended);
+            }
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+#[cfg(windows)]
+fn extended_length(path: &Path) -> Option<std::path::PathBuf> {
+    use std::ffi::OsString;
+
+    // `absolute` also resolves `.`/`..` and `/`, which `\\?\` paths don't allow
+    let absolute = std::path::absolute(path).ok()?;
+    let s = absolute.as_os_str().to_str()?;
+    if s.starts_with(r"\\?\") {
+        return Some(absolute);
+    }
+    let mut out = OsString::new();
+    match s.strip_prefix(r"\\") {
+        Some(share) => {
+            out.push(r"\\?\UNC\");
+            out.push(share);
+        }
+        None => {
+            out.push(r"\\?\");
+            out.push(s);
+        }
+    }
+    Some(out.into())
+}
+
+/// Whether files can be created at `path` (a file or directory that may not exist yet).
+///
+/// Checks the nearest existing directory by creating and removing a probe
+/// file in it, since permission bits alone don't tell (ACLs, read-only
+/// mounts, protected install folders on Windows).
+pub fn can_write_into(path: &Path) -> bool {
+    let Some(dir) = nearest_existing_dir(path) else {
+        return false;
+    };
+    let probe = dir.join(format!(".ritobin_write_probe_{}", std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(long_path(&probe)) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(long_path(&probe));
+            true
+        }
+        // Left over from an earlier run; creating it worked then
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => true,
+        Err(_) => false,
+    }
+}
+
+/// `path` itself if it is a directory, else its closest existing ancestor
+fn nearest_existing_dir(path: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(path).ok()?;
+    absolute.ancestors().find(|p| p.is_dir()).map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_paths_unchanged() {
+        let path = Path::new("data/characters/ahri/ahri.bin");
+        assert!(matches!(long_path(path), Cow::Borrowed(p) if p == path));
+
+        let deep: std::path::PathBuf = std::iter::repeat_n("directory", 40).collect();
+        let long = long_path(&deep);
+        if cfg!(windows) {
+            assert!(long.to_string_lossy().starts_with(r"\\?\"));
+        } else {
+            assert_eq!(long, deep);
+        }
+
+        let dir = std::env::temp_dir();
+        assert!(can_write_into(&dir.join("not/created/yet.py")));
+    }
+}
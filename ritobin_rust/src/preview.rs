@@ -0,0 +1,54 @@
+//! Short previews of string values for listings such as `info`.
+
+use std::borrow::Cow;
+
+/// How [`preview`] shortens strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewOptions {
+    /// Maximum number of characters kept from the string
+    pub max_chars: usize,
+    /// Appended when the string was shortened
+    pub ellipsis: String,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self { max_chars: 50, ellipsis: "...".to_string() }
+    }
+}
+
+/// The first `max_chars` characters of `s`, followed by the ellipsis if anything was cut.
+///
+/// Cuts on character boundaries, so multi-byte UTF-8 is never split.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::preview::{preview, PreviewOptions};
+///
+/// let opts = PreviewOptions { max_chars: 3, ..Default::default() };
+/// assert_eq!(preview("héllo", &opts), "hél...");
+/// assert_eq!(preview("hé", &opts), "hé");
+/// ```
+pub fn preview<'a>(s: &'a str, opts: &PreviewOptions) -> Cow<'a, str> {
+    match s.char_indices().nth(opts.max_chars) {
+        Some((end, _)) => Cow::Owned(format!("{}{}", &s[..end], opts.ellipsis)),
+        None => Cow::Borrowed(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_multibyte_boundary() {
+        let s = "a".repeat(49) + "日本語";
+        let opts = PreviewOptions::default();
+        assert_eq!(preview(&s, &opts), format!("{}日...", "a".repeat(49)));
+
+        let opts = PreviewOptions { max_chars: 0, ellipsis: "…".to_string() };
+        assert_eq!(preview("x", &opts), "…");
+        assert_eq!(preview("", &opts), "");
+    }
+}
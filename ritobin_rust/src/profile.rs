@@ -0,0 +1,429 @@
+//! Byte-size attribution for binary bins.
+//!
+//! Walks a `Bin` and computes how many bytes each part takes once written with
+//! [`write_bin`](crate::binary::write_bin): per entry, per class, per value type
+//! and per kind of data (strings, numbers, hashes, structure overhead).
+
+use crate::binary::get_value_type;
+use crate::model::{Bin, BinType, BinValue, Field};
+use std::collections::{BTreeMap, HashMap};
+
+/// Broad category of encoded bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SizeKind {
+    /// String contents and their length prefixes
+    Strings,
+    /// Booleans, integers, floats, vectors, matrices and colors
+    Numbers,
+    /// Hash, file and link values
+    Hashes,
+    /// Type tags, size and count fields, field keys and class names
+    Structure,
+    /// File magic, version, linked files and the entry hash table
+    Header,
+}
+
+impl SizeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SizeKind::Strings => "strings",
+            SizeKind::Numbers => "numbers",
+            SizeKind::Hashes => "hashes",
+            SizeKind::Structure => "structure",
+            SizeKind::Header => "header",
+        }
+    }
+
+    fn of(type_: BinType) -> Self {
+        match type_ {
+            BinType::String => SizeKind::Strings,
+            BinType::Hash | BinType::File | BinType::Link => SizeKind::Hashes,
+            t if t.is_primitive() => SizeKind::Numbers,
+            _ => SizeKind::Structure,
+        }
+    }
+}
+
+/// Encoded size of one entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySize {
+    /// Entry key hash
+    pub hash: u32,
+    /// Entry name, if unhashed
+    pub name: Option<String>,
+    /// Class hash of the entry's embed
+    pub class: u32,
+    /// Class name, if unhashed
+    pub class_name: Option<String>,
+    /// Bytes taken by the entry, including its length field and its slot in the hash table
+    pub bytes: u64,
+}
+
+/// Encoded size of all entries sharing a class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassSize {
+    pub class: u32,
+    /// Class name, if unhashed
+    pub name: Option<String>,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Bytes taken by values of one type (excluding nested values of other types).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeSize {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Encoded size of one string value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringSize {
+    /// Hash of the entry holding the string
+    pub entry: u32,
+    /// Entry name, if unhashed
+    pub entry_name: Option<String>,
+    /// Bytes taken by the string, including its length prefix
+    pub bytes: u64,
+    pub text: String,
+}
+
+/// Why a bin did not fit a size budget, see [`write_bin_within`](crate::binary::write_bin_within).
+#[derive(Debug, Clone)]
+pub struct BudgetReport {
+    /// Encoded size in bytes
+    pub size: u64,
+    /// Allowed size in bytes
+    pub budget: u64,
+    /// Every entry, largest first
+    pub entries: Vec<EntrySize>,
+    /// The longest strings, longest first
+    pub strings: Vec<StringSize>,
+}
+
+impl BudgetReport {
+    /// Number of strings kept in [`strings`](Self::strings)
+    pub const MAX_STRINGS: usize = 20;
+
+    pub fn new(bin: &Bin, size: u64, budget: u64) -> Self {
+        let profile = SizeProfile::from_bin(bin);
+        let mut strings = SizeProfile::largest_strings(bin);
+        strings.truncate(Self::MAX_STRINGS);
+        Self {
+            size,
+            budget,
+            entries: profile.largest_entries().into_iter().cloned().collect(),
+            strings,
+        }
+    }
+
+    /// Bytes to remove to fit the budget
+    pub fn excess(&self) -> u64 {
+        self.size.saturating_sub(self.budget)
+    }
+
+    /// The fewest largest entries whose removal alone would fit the budget
+    pub fn suggested_entries(&self) -> &[EntrySize] {
+        let mut freed = 0;
+        let count = self.entries.iter().take_while(|e| {
+            let more = freed < self.excess();
+            freed += e.bytes;
+            more
+        }).count();
+        &self.entries[..count]
+    }
+}
+
+/// Size breakdown of a whole bin.
+#[derive(Debug, Clone, Default)]
+pub struct SizeProfile {
+    /// Total encoded size in bytes
+    pub total: u64,
+    /// Bytes per kind of data; sums to `total`
+    pub kinds: BTreeMap<SizeKind, u64>,
+    /// Bytes per value type
+    pub types: BTreeMap<BinType, TypeSize>,
+    /// Entries in file order
+    pub entries: Vec<EntrySize>,
+    /// Bytes taken by the `patches` section of PTCH files
+    pub patches: u64,
+}
+
+impl SizeProfile {
+    /// Profile `bin` as it would be written by `write_bin`.
+    pub fn from_bin(bin: &Bin) -> Self {
+        let mut p = Self::default();
+        let is_patch = matches!(bin.sections.get("type"), Some(BinValue::String(s)) if s == "PTCH");
+        let version = match bin.sections.get("version") {
+            Some(BinValue::U32(v)) => *v,
+            _ => 0,
+        };
+
+        // Magic(s) and version
+        p.add(SizeKind::Header, if is_patch { 4 + 8 + 4 + 4 } else { 4 + 4 });
+
+        if version >= 2 {
+            p.add(SizeKind::Header, 4);
+            if let Some(BinValue::List { items, .. }) = bin.sections.get("linked") {
+                for item in items {
+                    if let BinValue::String(s) = item {
+                        p.add(SizeKind::Header, 2 + s.len() as u64);
+                    }
+                }
+            }
+        }
+
+        p.add(SizeKind::Header, 4);
+        if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+            for (key, value) in items {
+                let (BinValue::Hash { value: hash, name }, BinValue::Embed { name: class, name_str: class_name, items: fields }) = (key, value) else {
+                    continue;
+                };
+                let before = p.total;
+                // Slot in the class hash table, length field, key hash and field count
+                p.add(SizeKind::Header, 4);
+                p.add(SizeKind::Structure, 4 + 4 + 2);
+                p.add_fields(fields);
+                p.entries.push(EntrySize {
+                    hash: *hash,
+                    name: name.clone(),
+                    class: *class,
+                    class_name: class_name.clone(),
+                    bytes: p.total - before,
+                });
+            }
+        }
+
+        if is_patch && version >= 3 {
+            let before = p.total;
+            p.add(SizeKind::Structure, 4);
+            if let Some(BinValue::Map { items, .. }) = bin.sections.get("patches") {
+                let path_hash = crate::hash::fnv1a("path");
+                let value_hash = crate::hash::fnv1a("value");
+                for (key, value) in items {
+                    if !matches!(key, BinValue::Hash { .. }) {
+                        continue;
+                    }
+                    // Key hash and length field
+                    p.add(SizeKind::Structure, 4 + 4);
+                    let BinValue::Embed { items: fields, .. } = value else { continue };
+                    let path = fields.iter().find(|f| f.key == path_hash);
+                    let val = fields.iter().find(|f| f.key == value_hash);
+                    if let (Some(path), Some(val)) = (path, val) {
+                        p.add(SizeKind::Structure, 1);
+                        if let BinValue::String(s) = &path.value {
+                            p.add(SizeKind::Strings, 2 + s.len() as u64);
+                        }
+                        p.add_value(&val.value);
+                    }
+                }
+            }
+            p.patches = p.total - before;
+        }
+
+        p
+    }
+
+    /// Entries grouped by class, largest first
+    pub fn classes(&self) -> Vec<ClassSize> {
+        let mut by_class: HashMap<u32, ClassSize> = HashMap::new();
+        for e in &self.entries {
+            let c = by_class.entry(e.class).or_insert_with(|| ClassSize {
+                class: e.class,
+                name: e.class_name.clone(),
+                count: 0,
+                bytes: 0,
+            });
+            c.count += 1;
+            c.bytes += e.bytes;
+        }
+        let mut out: Vec<_> = by_class.into_values().collect();
+        out.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.class.cmp(&b.class)));
+        out
+    }
+
+    /// Entries sorted by size, largest first
+    pub fn largest_entries(&self) -> Vec<&EntrySize> {
+        let mut out: Vec<_> = self.entries.iter().collect();
+        out.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.hash.cmp(&b.hash)));
+        out
+    }
+
+    /// Strings sorted by length, longest first, with the entry holding them
+    pub fn largest_strings(bin: &Bin) -> Vec<StringSize> {
+        let mut out = Vec::new();
+        if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+            for (key, value) in items {
+                let BinValue::Hash { value: hash, name } = key else {
+                    continue;
+                };
+                let mut strings = Vec::new();
+                collect_strings(value, &mut strings);
+                out.extend(strings.into_iter().map(|s| StringSize {
+                    entry: *hash,
+                    entry_name: name.clone(),
+                    bytes: 2 + s.len() as u64,
+                    text: s.to_string(),
+                }));
+            }
+        }
+        out.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.entry.cmp(&b.entry)));
+        out
+    }
+
+    fn add(&mut self, kind: SizeKind, bytes: u64) {
+        *self.kinds.entry(kind).or_default() += bytes;
+        self.total += bytes;
+    }
+
+    fn add_type(&mut self, type_: BinType, bytes: u64) {
+        let t = self.types.entry(type_).or_default();
+        t.count += 1;
+        t.bytes += bytes;
+        self.add(SizeKind::of(type_), bytes);
+    }
+
+    fn add_fields(&mut self, fields: &[Field]) {
+        for field in fields {
+            // Field key and type tag
+            self.add(SizeKind::Structure, 4 + 1);
+            self.add_value(&field.value);
+        }
+    }
+
+    fn add_value(&mut self, value: &BinValue) {
+        let type_ = get_value_type(value);
+        match value {
+            BinValue::None => self.add_type(type_, 0),
+            BinValue::Bool(_) | BinValue::I8(_) | BinValue::U8(_) | BinValue::Flag(_) => self.add_type(type_, 1),
+            BinValue::I16(_) | BinValue::U16(_) => self.add_type(type_, 2),
+            BinValue::I32(_) | BinValue::U32(_) | BinValue::F32(_) | BinValue::Rgba(_) => self.add_type(type_, 4),
+            BinValue::Hash { .. } | BinValue::Link { .. } => self.add_type(type_, 4),
+            BinValue::I64(_) | BinValue::U64(_) | BinValue::Vec2(_) | BinValue::File { .. } => self.add_type(type_, 8),
+            BinValue::Vec3(_) => self.add_type(type_, 12),
+            BinValue::Vec4(_) => self.add_type(type_, 16),
+            BinValue::Mtx44(_) => self.add_type(type_, 64),
+            BinValue::String(s) => self.add_type(type_, 2 + s.len() as u64),
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                // Element type, size and count
+                self.add_type(type_, 1 + 4 + 4);
+                for item in items {
+                    self.add_value(item);
+                }
+            }
+            BinValue::Pointer { name, items, .. } => {
+                if *name == 0 {
+                    self.add_type(type_, 4);
+                } else {
+                    // Class, size and field count
+                    self.add_type(type_, 4 + 4 + 2);
+                    self.add_fields(items);
+                }
+            }
+            BinValue::Embed { items, .. } => {
+                self.add_type(type_, 4 + 4 + 2);
+                self.add_fields(items);
+            }
+            BinValue::Option { item, .. } => {
+                // Element type and presence flag
+                self.add_type(type_, 1 + 1);
+                if let Some(item) = item {
+                    self.add_value(item);
+                }
+            }
+            BinValue::Map { items, .. } => {
+                // Key type, value type, size and count
+                self.add_type(type_, 1 + 1 + 4 + 4);
+                for (k, v) in items {
+                    self.add_value(k);
+                    self.add_value(v);
+                }
+            }
+            BinValue::Raw { bytes, .. } => self.add_type(type_, bytes.len() as u64),
+        }
+    }
+}
+
+fn collect_strings<'a>(value: &'a BinValue, out: &mut Vec<&'a str>) {
+    match value {
+        BinValue::String(s) => out.push(s),
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+            items.iter().for_each(|item| collect_strings(item, out));
+        }
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+            items.iter().for_each(|field| collect_strings(&field.value, out));
+        }
+        BinValue::Option { item: Some(item), .. } => collect_strings(item, out),
+        BinValue::Map { items, .. } => {
+            for (k, v) in items {
+                collect_strings(k, out);
+                collect_strings(v, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{write_bin, write_bin_within, BinError};
+
+    #[test]
+    fn test_profile_matches_written_size() {
+        let fields = vec![
+            Field { key: 1, key_str: None, value: BinValue::String("Ahri".to_string()) },
+            Field { key: 2, key_str: None, value: BinValue::F32(590.0) },
+            Field { key: 3, key_str: None, value: BinValue::List {
+                value_type: BinType::Vec3,
+                items: vec![BinValue::Vec3([0.0; 3]), BinValue::Vec3([1.0; 3])],
+            } },
+            Field { key: 4, key_str: None, value: BinValue::Pointer { name: 0, name_str: None, items: vec![] } },
+            Field { key: 5, key_str: None, value: BinValue::Option {
+                value_type: BinType::Hash,
+                item: Some(Box::new(BinValue::Hash { value: 7, name: None })),
+            } },
+            Field { key: 6, key_str: None, value: BinValue::Map {
+                key_type: BinType::U32,
+                value_type: BinType::Embed,
+                items: vec![(BinValue::U32(1), BinValue::Embed { name: 9, name_str: None, items: vec![] })],
+            } },
+        ];
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List {
+            value_type: BinType::String,
+            items: vec![BinValue::String("DATA/a.bin".to_string())],
+        });
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![
+                (BinValue::Hash { value: 10, name: None }, BinValue::Embed { name: 100, name_str: None, items: fields }),
+                (BinValue::Hash { value: 11, name: None }, BinValue::Embed { name: 100, name_str: None, items: vec![] }),
+            ],
+        });
+
+        let profile = SizeProfile::from_bin(&bin);
+        assert_eq!(profile.total, write_bin(&bin).unwrap().len() as u64);
+        assert_eq!(profile.kinds.values().sum::<u64>(), profile.total);
+        assert_eq!(profile.entries.len(), 2);
+        assert_eq!(profile.entries[1].bytes, 4 + 4 + 4 + 2);
+        assert_eq!(profile.types[&BinType::String], TypeSize { count: 1, bytes: 6 });
+
+        let classes = profile.classes();
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].count, 2);
+        assert_eq!(classes[0].bytes, profile.entries.iter().map(|e| e.bytes).sum::<u64>());
+
+        assert!(write_bin_within(&bin, profile.total).is_ok());
+        let Err(BinError::OverBudget(report)) = write_bin_within(&bin, profile.total - 1) else {
+            panic!("expected an over-budget error");
+        };
+        assert_eq!(report.excess(), 1);
+        assert_eq!(report.entries[0].hash, 10);
+        assert_eq!(report.suggested_entries().len(), 1);
+        assert_eq!(report.strings, vec![StringSize { entry: 10, entry_name: None, bytes: 6, text: "Ahri".to_string() }]);
+    }
+}
@@ -0,0 +1,133 @@
+//! Progress events for long-running operations.
+//!
+//! Functions ending in `_with_progress` report what they are doing to a
+//! [`Progress`] sink instead of printing, so front-ends can show progress
+//! without parsing the command line tool's output. Closures taking an
+//! [`Event`] and `mpsc` senders are sinks, the latter for reporting to a UI
+//! thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::progress::Event;
+//! use ritobin_rust::unhash::BinUnhasher;
+//!
+//! let (tx, rx) = std::sync::mpsc::channel();
+//! std::thread::spawn(move || {
+//!     let mut unhasher = BinUnhasher::new();
+//!     let mut tx = tx;
+//!     ritobin_rust::discovery::load_hash_dir_with_progress(&mut unhasher, "hashes".as_ref(), &mut tx);
+//! });
+//! for event in rx {
+//!     if let Event::FileStarted { path } = event {
+//!         println!("Loading {}", path.display());
+//!     }
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use crate::unhash::NameStats;
+
+/// Something that happened during a long-running operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Started reading or loading a file
+    FileStarted { path: PathBuf },
+    /// Finished with a file, whether or not it succeeded
+    FileFinished { path: PathBuf },
+    /// Decoded entry `index` of `total`
+    EntryParsed { hash: u32, index: usize, total: usize },
+    /// Encoded entry `index` of `total`
+    EntryWritten { hash: u32, index: usize, total: usize },
+    /// Names resolved in a bin after unhashing
+    HashesResolved(NameStats),
+    /// A problem that did not stop the operation
+    Warning { path: Option<PathBuf>, message: String },
+}
+
+/// Receives [`Event`]s
+pub trait Progress {
+    fn event(&mut self, event: Event);
+}
+
+impl<F: FnMut(Event)> Progress for F {
+    fn event(&mut self, event: Event) {
+        self(event)
+    }
+}
+
+impl Progress for Sender<Event> {
+    /// Sends the event, ignoring a receiver that has gone away
+    fn event(&mut self, event: Event) {
+        let _ = self.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{read_bin_with_progress, write_bin, write_bin_with_progress, ReadOptions, WriteOptions};
+    use crate::model::{Bin, BinType, BinValue, Field};
+    use crate::unhash::BinUnhasher;
+
+    #[test]
+    fn test_progress_events() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: (1..=3).map(|h| (
+                BinValue::Hash { value: h, name: None },
+                BinValue::Embed { name: 7, name_str: None, items: vec![] },
+            )).collect(),
+        });
+        let data = write_bin(&bin).unwrap();
+
+        let (mut tx, rx) = std::sync::mpsc::channel();
+        let mut read = read_bin_with_progress(&data, &ReadOptions::default(), &mut tx).unwrap();
+        drop(tx);
+        let events: Vec<_> = rx.into_iter().collect();
+        assert_eq!(events, (0..3).map(|i| Event::EntryParsed { hash: i as u32 + 1, index: i, total: 3 }).collect::<Vec<_>>());
+
+        let mut written = Vec::new();
+        let out = write_bin_with_progress(&bin, &WriteOptions::default(), &mut |e| written.push(e)).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(written, (0..3).map(|i| Event::EntryWritten { hash: i as u32 + 1, index: i, total: 3 }).collect::<Vec<_>>());
+        let mut parallel = Vec::new();
+        assert_eq!(crate::binary::write_bin_impl(&bin, 2, WriteOptions::default(), Some(&mut |e| parallel.push(e))).unwrap(), data);
+        assert_eq!(parallel, written);
+
+        let mut stats = None;
+        BinUnhasher::new().unhash_bin_with_progress(&mut read, &mut |e| if let Event::HashesResolved(s) = e { stats = Some(s) });
+        assert_eq!(stats, Some(NameStats { resolved: 0, unresolved: 6 }));
+    }
+
+    #[test]
+    fn test_damaged_entry_not_reported() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: [1, 0xaabbccdd, 3].into_iter().map(|h| (
+                BinValue::Hash { value: h, name: None },
+                BinValue::Embed { name: 7, name_str: None, items: vec![Field { key: h, key_str: None, value: BinValue::U32(0) }] },
+            )).collect(),
+        });
+        let mut data = write_bin(&bin).unwrap();
+        // Give the second entry's field an unknown type byte
+        let key = 0xaabbccdd_u32.to_le_bytes();
+        let field = data.windows(4).rposition(|w| w == key).unwrap();
+        data[field + 4] = 0xee;
+
+        let mut events = Vec::new();
+        assert!(read_bin_with_progress(&data, &ReadOptions::default(), &mut |e| events.push(e)).is_err());
+        assert_eq!(events, [Event::EntryParsed { hash: 1, index: 0, total: 3 }]);
+    }
+}
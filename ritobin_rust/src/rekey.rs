@@ -0,0 +1,196 @@
+//! Path prefix re-keying, the core of porting a skin from one champion to another.
+//!
+//! Every named hash, link and file path plus every string value starting with a
+//! source prefix (case-insensitively, as the game's hashes are) gets the prefix
+//! replaced and its hash recomputed. Hashes without a resolved name can't be
+//! matched, so bins should be unhashed before re-keying.
+
+use crate::hash::{fnv1a, Xxh64};
+use crate::model::{Bin, BinValue};
+
+/// Number of values rewritten by [`Rekey::apply`], by kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RekeyStats {
+    /// Entry keys in the `entries` and `patches` sections
+    pub entries: usize,
+    /// Other hash values (map keys, hash fields)
+    pub hashes: usize,
+    pub links: usize,
+    pub files: usize,
+    pub strings: usize,
+}
+
+impl RekeyStats {
+    pub fn total(&self) -> usize {
+        self.entries + self.hashes + self.links + self.files + self.strings
+    }
+}
+
+/// A set of prefix replacements.
+///
+/// Rules are tried in insertion order and the first matching prefix wins, so
+/// more specific prefixes should be added first.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::{Bin, BinValue};
+/// use ritobin_rust::rekey::Rekey;
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("path".to_string(), BinValue::Link {
+///     value: ritobin_rust::hash::fnv1a("Characters/Ahri/Skins/Skin0"),
+///     name: Some("Characters/Ahri/Skins/Skin0".to_string()),
+/// });
+///
+/// let mut rekey = Rekey::new();
+/// rekey.add("Characters/Ahri/", "Characters/Annie/");
+/// let stats = rekey.apply(&mut bin);
+///
+/// assert_eq!(stats.links, 1);
+/// assert_eq!(bin.sections["path"], BinValue::Link {
+///     value: ritobin_rust::hash::fnv1a("Characters/Annie/Skins/Skin0"),
+///     name: Some("Characters/Annie/Skins/Skin0".to_string()),
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Rekey {
+    rules: Vec<(String, String)>,
+}
+
+impl Rekey {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace `from` with `to` where a name or string starts with `from`
+    pub fn add(&mut self, from: &str, to: &str) -> &mut Self {
+        self.rules.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// The rewritten form of `s`, if any rule matches
+    pub fn rewrite(&self, s: &str) -> Option<String> {
+        self.rules.iter().find_map(|(from, to)| {
+            let head = s.get(..from.len())?;
+            head.eq_ignore_ascii_case(from).then(|| format!("{}{}", to, &s[from.len()..]))
+        })
+    }
+
+    /// Rewrite every matching value in `bin`
+    pub fn apply(&self, bin: &mut Bin) -> RekeyStats {
+        let mut stats = RekeyStats::default();
+        for (section, value) in bin.sections.iter_mut() {
+            if let ("entries" | "patches", BinValue::Map { items, .. }) = (section.as_str(), &mut *value) {
+                for (key, entry) in items {
+                    if self.rewrite_value(key, &mut stats) {
+                        stats.hashes -= 1;
+                        stats.entries += 1;
+                    }
+                    self.walk(entry, &mut stats);
+                }
+            } else {
+                self.walk(value, &mut stats);
+            }
+        }
+        stats
+    }
+
+    fn walk(&self, value: &mut BinValue, stats: &mut RekeyStats) {
+        if self.rewrite_value(value, stats) {
+            return;
+        }
+        match value {
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                items.iter_mut().for_each(|v| self.walk(v, stats));
+            }
+            BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+                items.iter_mut().for_each(|f| self.walk(&mut f.value, stats));
+            }
+            BinValue::Option { item: Some(item), .. } => self.walk(item, stats),
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    self.walk(k, stats);
+                    self.walk(v, stats);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rewrite a single leaf value; returns whether it changed
+    fn rewrite_value(&self, value: &mut BinValue, stats: &mut RekeyStats) -> bool {
+        match value {
+            BinValue::Hash { value: h, name: Some(n) } => {
+                let Some(new) = self.rewrite(n) else { return false };
+                *h = fnv1a(&new);
+                *n = new;
+                stats.hashes += 1;
+            }
+            BinValue::Link { value: h, name: Some(n) } => {
+                let Some(new) = self.rewrite(n) else { return false };
+                *h = fnv1a(&new);
+                *n = new;
+                stats.links += 1;
+            }
+            BinValue::File { value: h, name: Some(n) } => {
+                let Some(new) = self.rewrite(n) else { return false };
+                *h = Xxh64::new(&new).0;
+                *n = new;
+                stats.files += 1;
+            }
+            BinValue::String(s) => {
+                let Some(new) = self.rewrite(s) else { return false };
+                *s = new;
+                stats.strings += 1;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BinType, Field};
+
+    #[test]
+    fn test_rekey_entries_and_paths() {
+        let named = |s: &str| BinValue::Hash { value: fnv1a(s), name: Some(s.to_string()) };
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                named("Characters/Ahri/Skins/Skin1"),
+                BinValue::Embed { name: 1, name_str: None, items: vec![
+                    Field { key: 1, key_str: None, value: BinValue::File {
+                        value: Xxh64::new("ASSETS/Characters/Ahri/Skins/Skin01/Ahri.dds").0,
+                        name: Some("ASSETS/Characters/Ahri/Skins/Skin01/Ahri.dds".to_string()),
+                    } },
+                    Field { key: 2, key_str: None, value: BinValue::String("characters/ahri/hud/icon.dds".to_string()) },
+                    Field { key: 3, key_str: None, value: BinValue::String("Characters/Ahrix".to_string()) },
+                    Field { key: 4, key_str: None, value: BinValue::Hash { value: 7, name: None } },
+                ] },
+            )],
+        });
+
+        let mut rekey = Rekey::new();
+        rekey.add("ASSETS/Characters/Ahri/", "ASSETS/Characters/Annie/")
+            .add("Characters/Ahri/", "Characters/Annie/");
+        let stats = rekey.apply(&mut bin);
+        assert_eq!(stats, RekeyStats { entries: 1, hashes: 0, links: 0, files: 1, strings: 1 });
+
+        let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") else { panic!() };
+        assert_eq!(items[0].0, named("Characters/Annie/Skins/Skin1"));
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!() };
+        assert_eq!(fields[0].value, BinValue::File {
+            value: Xxh64::new("assets/characters/annie/skins/skin01/ahri.dds").0,
+            name: Some("ASSETS/Characters/Annie/Skins/Skin01/Ahri.dds".to_string()),
+        });
+        assert_eq!(fields[1].value, BinValue::String("Characters/Annie/hud/icon.dds".to_string()));
+        assert_eq!(fields[2].value, BinValue::String("Characters/Ahrix".to_string()));
+        assert_eq!(fields[3].value, BinValue::Hash { value: 7, name: None });
+    }
+}
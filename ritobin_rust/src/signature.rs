@@ -0,0 +1,162 @@
+//! Structural signatures for bin entries.
+//!
+//! A signature captures the shape of an entry - its class, the sorted set of
+//! field hashes and the type of each field - while ignoring the values. Entries
+//! sharing a signature are built from the same schema, which is useful for
+//! schema research and for spotting duplicated content across files.
+
+use crate::binary::get_value_type;
+use crate::model::{Bin, BinType, BinValue};
+use std::collections::HashMap;
+
+/// Compact structural description of one entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntrySignature {
+    /// Class hash of the entry's embed
+    pub class: u32,
+    /// Field hashes, sorted ascending
+    pub fields: Vec<u32>,
+    /// Value type of each field, in the same order as `fields`
+    pub types: Vec<BinType>,
+}
+
+impl EntrySignature {
+    /// Build the signature of an entry value (an `Embed`, or a `Pointer` for patch-like data).
+    ///
+    /// Returns `None` for values that aren't structures.
+    pub fn from_value(value: &BinValue) -> Option<Self> {
+        let (class, items) = match value {
+            BinValue::Embed { name, items, .. } | BinValue::Pointer { name, items, .. } => (*name, items),
+            _ => return None,
+        };
+        let mut pairs: Vec<(u32, BinType)> = items.iter()
+            .map(|f| (f.key, get_value_type(&f.value)))
+            .collect();
+        pairs.sort();
+        let (fields, types) = pairs.into_iter().unzip();
+        Some(Self { class, fields, types })
+    }
+
+    /// A stable 64-bit fingerprint of the signature (FNV-1a over its encoding)
+    pub fn fingerprint(&self) -> u64 {
+        let mut encoding = Vec::with_capacity(4 + self.fields.len() * 5);
+        encoding.extend_from_slice(&self.class.to_le_bytes());
+        for (key, type_) in self.fields.iter().zip(&self.types) {
+            encoding.extend_from_slice(&key.to_le_bytes());
+            encoding.push(*type_ as u8);
+        }
+        crate::hash::fnv1a64(&[&encoding])
+    }
+}
+
+/// Signatures of every entry in `bin`, keyed by entry hash, in file order.
+pub fn entry_signatures(bin: &Bin) -> Vec<(u32, EntrySignature)> {
+    let mut out = Vec::new();
+    if let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") {
+        for (key, value) in items {
+            if let (BinValue::Hash { value: h, .. }, Some(sig)) = (key, EntrySignature::from_value(value)) {
+                out.push((*h, sig));
+            }
+        }
+    }
+    out
+}
+
+/// Location of an entry that contributed a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryLocation {
+    /// Caller-supplied source name, typically the file path
+    pub source: String,
+    /// Entry key hash
+    pub entry: u32,
+}
+
+/// Groups entries from many bins by signature.
+#[derive(Debug, Default)]
+pub struct SignatureIndex {
+    groups: HashMap<EntrySignature, Vec<EntryLocation>>,
+}
+
+impl SignatureIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add every entry of `bin` under the given source name
+    pub fn add_bin(&mut self, source: &str, bin: &Bin) {
+        for (entry, sig) in entry_signatures(bin) {
+            self.groups.entry(sig).or_default().push(EntryLocation {
+                source: source.to_string(),
+                entry,
+            });
+        }
+    }
+
+    /// Number of distinct signatures seen
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Signatures shared by at least `min_count` entries, largest groups first
+    pub fn shared(&self, min_count: usize) -> Vec<(&EntrySignature, &[EntryLocation])> {
+        let mut out: Vec<_> = self.groups.iter()
+            .filter(|(_, locs)| locs.len() >= min_count)
+            .map(|(sig, locs)| (sig, locs.as_slice()))
+            .collect();
+        out.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Field;
+
+    fn entry(hash: u32, fields: Vec<Field>) -> (BinValue, BinValue) {
+        (
+            BinValue::Hash { value: hash, name: None },
+            BinValue::Embed { name: 0xc1a55, name_str: None, items: fields },
+        )
+    }
+
+    #[test]
+    fn test_signature_ignores_values_and_field_order() {
+        let a = entry(1, vec![
+            Field { key: 2, key_str: None, value: BinValue::F32(1.0) },
+            Field { key: 1, key_str: None, value: BinValue::String("a".to_string()) },
+        ]);
+        let b = entry(2, vec![
+            Field { key: 1, key_str: None, value: BinValue::String("b".to_string()) },
+            Field { key: 2, key_str: None, value: BinValue::F32(9.0) },
+        ]);
+        let c = entry(3, vec![
+            Field { key: 1, key_str: None, value: BinValue::U32(0) },
+            Field { key: 2, key_str: None, value: BinValue::F32(9.0) },
+        ]);
+
+        let sig_a = EntrySignature::from_value(&a.1).unwrap();
+        assert_eq!(sig_a.fields, vec![1, 2]);
+        assert_eq!(sig_a.types, vec![BinType::String, BinType::F32]);
+        assert_eq!(sig_a, EntrySignature::from_value(&b.1).unwrap());
+        assert_eq!(sig_a.fingerprint(), EntrySignature::from_value(&b.1).unwrap().fingerprint());
+        assert_ne!(sig_a, EntrySignature::from_value(&c.1).unwrap());
+
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![a, b, c],
+        });
+        let mut index = SignatureIndex::new();
+        index.add_bin("one.bin", &bin);
+        assert_eq!(index.len(), 2);
+        let shared = index.shared(2);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].1.iter().map(|l| l.entry).collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,210 @@
+//! String deduplication for text and JSON exports.
+//!
+//! [`intern_strings`] moves long strings that repeat across a bin into a
+//! `__strings` section and replaces each occurrence with a short `@str:N`
+//! reference. [`expand_strings`] reverses this, and is applied automatically
+//! by [`read_text`](crate::text::read_text) and [`read_json`](crate::json::read_json).
+//!
+//! Strings that already start with `@str:` are always moved into the table, so
+//! a reference can never be confused with a literal string.
+
+use crate::model::{Bin, BinType, BinValue};
+use std::collections::HashMap;
+
+/// Name of the section holding the string table
+pub const STRING_TABLE_SECTION: &str = "__strings";
+
+/// Prefix of a string table reference
+pub const STRING_REF_PREFIX: &str = "@str:";
+
+/// Which strings are worth moving into the table.
+#[derive(Debug, Clone, Copy)]
+pub struct StringTableOptions {
+    /// Minimum string length in bytes
+    pub min_len: usize,
+    /// Minimum number of occurrences
+    pub min_count: usize,
+}
+
+impl Default for StringTableOptions {
+    fn default() -> Self {
+        Self { min_len: 32, min_count: 2 }
+    }
+}
+
+/// Replace repeated strings in `bin` with references into a `__strings` section.
+///
+/// Returns the number of strings placed in the table. Does nothing (and returns 0)
+/// if no string qualifies.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::{Bin, BinValue};
+/// use ritobin_rust::string_table::{expand_strings, intern_strings, StringTableOptions};
+///
+/// let path = "ASSETS/Characters/Ahri/Skins/Base/Ahri_Base_TX_CM.dds".to_string();
+/// let mut bin = Bin::new();
+/// bin.sections.insert("a".to_string(), BinValue::String(path.clone()));
+/// bin.sections.insert("b".to_string(), BinValue::String(path.clone()));
+/// let original = bin.clone();
+///
+/// assert_eq!(intern_strings(&mut bin, StringTableOptions::default()), 1);
+/// assert_eq!(bin.sections["a"], BinValue::String("@str:0".to_string()));
+///
+/// expand_strings(&mut bin).unwrap();
+/// assert_eq!(bin, original);
+/// ```
+pub fn intern_strings(bin: &mut Bin, opts: StringTableOptions) -> usize {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for (key, value) in &bin.sections {
+        if key != STRING_TABLE_SECTION {
+            visit_strings(value, &mut |s| *counts.entry(s.clone()).or_default() += 1);
+        }
+    }
+
+    // Table order follows first occurrence so output is deterministic
+    let mut table: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (key, value) in &bin.sections {
+        if key == STRING_TABLE_SECTION {
+            continue;
+        }
+        visit_strings(value, &mut |s| {
+            let qualifies = s.starts_with(STRING_REF_PREFIX)
+                || (s.len() >= opts.min_len && counts[s] >= opts.min_count);
+            if qualifies && !index.contains_key(s) {
+                index.insert(s.clone(), table.len());
+                table.push(s.clone());
+            }
+        });
+    }
+    if table.is_empty() {
+        return 0;
+    }
+
+    for (key, value) in bin.sections.iter_mut() {
+        if key != STRING_TABLE_SECTION {
+            visit_strings_mut(value, &mut |s| {
+                if let Some(i) = index.get(s.as_str()) {
+                    *s = format!("{}{}", STRING_REF_PREFIX, i);
+                }
+            });
+        }
+    }
+
+    let count = table.len();
+    bin.sections.insert(STRING_TABLE_SECTION.to_string(), BinValue::List {
+        value_type: BinType::String,
+        items: table.into_iter().map(BinValue::String).collect(),
+    });
+    count
+}
+
+/// Resolve `@str:N` references and remove the `__strings` section.
+///
+/// Bins without a string table are left untouched.
+pub fn expand_strings(bin: &mut Bin) -> Result<(), String> {
+    let table = match bin.sections.shift_remove(STRING_TABLE_SECTION) {
+        None => return Ok(()),
+        Some(BinValue::List { items, .. }) => items.into_iter()
+            .map(|item| match item {
+                BinValue::String(s) => Ok(s),
+                other => Err(format!("String table holds a non-string value: {:?}", other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err(format!("Section {} must be a list of strings", STRING_TABLE_SECTION)),
+    };
+
+    let mut error = None;
+    for value in bin.sections.values_mut() {
+        visit_strings_mut(value, &mut |s| {
+            if let Some(i) = s.strip_prefix(STRING_REF_PREFIX) {
+                match i.parse::<usize>().ok().and_then(|i| table.get(i)) {
+                    Some(resolved) => *s = resolved.clone(),
+                    None => { error.get_or_insert_with(|| format!("Invalid string reference: {}", s)); }
+                }
+            }
+        });
+    }
+    error.map_or(Ok(()), Err)
+}
+
+fn visit_strings(value: &BinValue, f: &mut impl FnMut(&String)) {
+    match value {
+        BinValue::String(s) => f(s),
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+            items.iter().for_each(|v| visit_strings(v, f));
+        }
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+            items.iter().for_each(|field| visit_strings(&field.value, f));
+        }
+        BinValue::Option { item: Some(item), .. } => visit_strings(item, f),
+        BinValue::Map { items, .. } => {
+            for (k, v) in items {
+                visit_strings(k, f);
+                visit_strings(v, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn visit_strings_mut(value: &mut BinValue, f: &mut impl FnMut(&mut String)) {
+    match value {
+        BinValue::String(s) => f(s),
+        BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+            items.iter_mut().for_each(|v| visit_strings_mut(v, f));
+        }
+        BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+            items.iter_mut().for_each(|field| visit_strings_mut(&mut field.value, f));
+        }
+        BinValue::Option { item: Some(item), .. } => visit_strings_mut(item, f),
+        BinValue::Map { items, .. } => {
+            for (k, v) in items {
+                visit_strings_mut(k, f);
+                visit_strings_mut(v, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Field;
+
+    #[test]
+    fn test_string_table_round_trip() {
+        let long = "DATA/Characters/Ahri/Skins/Skin0.bin".to_string();
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: 1, name: None },
+                BinValue::Embed { name: 2, name_str: None, items: vec![
+                    Field { key: 1, key_str: None, value: BinValue::String(long.clone()) },
+                    Field { key: 2, key_str: None, value: BinValue::List {
+                        value_type: BinType::String,
+                        items: vec![BinValue::String(long.clone()), BinValue::String("@str:0".to_string())],
+                    } },
+                ] },
+            )],
+        });
+        let original = bin.clone();
+
+        // The long path repeats; the literal "@str:0" must be escaped through the table
+        assert_eq!(intern_strings(&mut bin, StringTableOptions::default()), 2);
+        assert_eq!(bin.sections["type"], BinValue::String("PROP".to_string()));
+
+        let text = crate::text::write_text(&bin).unwrap();
+        assert_eq!(text.matches(long.as_str()).count(), 1);
+        assert_eq!(crate::text::read_text(&text).unwrap(), original);
+
+        let json = crate::json::write_json(&bin).unwrap();
+        assert_eq!(crate::json::read_json(&json).unwrap(), original);
+    }
+}
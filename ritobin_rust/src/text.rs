@@ -1,270 +1,664 @@
 use crate::model::{Bin, BinType, BinValue};
+use std::collections::HashMap;
 use std::fmt::Write;
 
+mod parser;
+
+pub(crate) use parser::type_from_name;
+
 pub fn write_text(bin: &Bin) -> Result<String, std::fmt::Error> {
-    let mut writer = TextWriter::new();
-    writer.write_raw("#PROP_text\n");
+    write_text_with_options(bin, &TextWriteOptions::default())
+}
+
+/// Revision of the text format, given in the header as `#PROP_text v2`.
+///
+/// The reader picks its parsing rules by the revision of the file, so syntax
+/// added in a later revision never changes how older files read. Files
+/// without a revision, including everything written by ritobin, are
+/// [`TextRevision::V1`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextRevision {
+    /// The original format, with a bare `#PROP_text` header or none at all
+    V1,
+    /// Same syntax as V1, with the revision in the header (default)
+    #[default]
+    V2,
+}
+
+impl TextRevision {
+    /// The newest revision, written by default
+    pub const CURRENT: TextRevision = TextRevision::V2;
+
+    pub fn number(self) -> u32 {
+        match self {
+            TextRevision::V1 => 1,
+            TextRevision::V2 => 2,
+        }
+    }
+
+    pub fn from_number(number: u32) -> Option<Self> {
+        match number {
+            1 => Some(TextRevision::V1),
+            2 => Some(TextRevision::V2),
+            _ => None,
+        }
+    }
+
+    /// The header line starting a file of this revision
+    pub fn header(self) -> String {
+        match self {
+            TextRevision::V1 => "#PROP_text\n".to_string(),
+            _ => format!("#PROP_text v{}\n", self.number()),
+        }
+    }
+
+    /// The revision named in the header of `data`; V1 when there is no
+    /// header or it names no revision
+    pub fn detect(data: &str) -> Result<Self, String> {
+        let first = data.trim_start_matches('\u{feff}').trim_start().lines().next().unwrap_or("");
+        let Some(rest) = first.strip_prefix("#PROP_text") else { return Ok(TextRevision::V1) };
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Ok(TextRevision::V1);
+        }
+        let number = rest.strip_prefix('v')
+            .and_then(|n| n.parse::<u32>().ok())
+            .ok_or_else(|| format!("Invalid text format revision '{}' in header", rest))?;
+        Self::from_number(number).ok_or_else(|| format!(
+            "Text format revision v{} is newer than this version supports (v{})",
+            number,
+            Self::CURRENT.number(),
+        ))
+    }
+}
+
+/// Options for [`write_text_with_options`].
+///
+/// The layout options only change how the text looks, never what it reads
+/// back as; set them to match the files another tool writes so diffs against
+/// them stay small.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextWriteOptions {
+    /// Write only the first N items of each list and map, followed by a
+    /// `# ... 10234 more items` comment; `None` writes everything
+    ///
+    /// Meant for previews and diffs: the hidden items are gone when the
+    /// output is read back.
+    pub max_items_per_container: Option<usize>,
+    /// Revision to write; older revisions are for tools that expect their header
+    pub revision: TextRevision,
+    /// Spaces per nesting level (default 2); ignored with `indent_tabs`
+    pub indent_width: usize,
+    /// Indent with one tab per nesting level instead of spaces
+    pub indent_tabs: bool,
+    /// Write a `,` after every item of a multi-line block
+    pub trailing_commas: bool,
+    /// Write the digits of unnamed hashes as `0xABCD` rather than `0xabcd`
+    pub uppercase_hex: bool,
+    /// Write lists of up to N plain values on one line, as `{ 1, 2, 3 }`;
+    /// 0 (the default) always writes one item per line
+    pub max_inline_items: usize,
+    /// Write named hashes with the other form as a trailing comment
+    pub hash_comments: HashComments,
+}
+
+/// How a `hash`, `file` or `link` that has a name is written, see
+/// [`TextWriteOptions::hash_comments`].
+///
+/// A comment only goes where the value ends its line, so map keys and
+/// one-line lists always hold just the name.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashComments {
+    /// The name alone: `"Characters/Ahri"`
+    #[default]
+    Off,
+    /// The hex value, with the name as a comment: `0x1234abcd # Characters/Ahri`
+    ///
+    /// The value reads back without its name, so it survives changes to the
+    /// hash tables; the comment is for the reader.
+    Name,
+    /// The name, with the hex value as a comment: `"Characters/Ahri" # 0x1234abcd`
+    Hex,
+}
+
+impl Default for TextWriteOptions {
+    fn default() -> Self {
+        Self {
+            max_items_per_container: None,
+            revision: TextRevision::default(),
+            indent_width: 2,
+            indent_tabs: false,
+            trailing_commas: false,
+            uppercase_hex: false,
+            max_inline_items: 0,
+            hash_comments: HashComments::Off,
+        }
+    }
+}
+
+/// Write a bin as text with explicit writer options.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::{Bin, BinType, BinValue};
+/// use ritobin_rust::text::{write_text_with_options, TextRevision, TextWriteOptions};
+///
+/// let mut bin = Bin::new();
+/// bin.sections.insert("ids".to_string(), BinValue::List {
+///     value_type: BinType::Hash,
+///     items: vec![BinValue::Hash { value: 0xab, name: None }, BinValue::Hash { value: 0xcd, name: None }],
+/// });
+/// let opts = TextWriteOptions { uppercase_hex: true, max_inline_items: 4, revision: TextRevision::V1, ..Default::default() };
+/// assert_eq!(write_text_with_options(&bin, &opts)?, "#PROP_text\nids: list[hash] = { 0xAB, 0xCD }\n");
+/// # Ok::<(), std::fmt::Error>(())
+/// ```
+pub fn write_text_with_options(bin: &Bin, opts: &TextWriteOptions) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+    write_text_fmt(bin, opts, &mut out)?;
+    Ok(out)
+}
+
+/// Write a bin as text straight to `out`, without holding the whole text in
+/// memory.
+///
+/// `out` gets many small writes; wrap files in a [`std::io::BufWriter`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use ritobin_rust::text::{write_text_to, TextWriteOptions};
+///
+/// let bin = ritobin_rust::binary::read_bin(&std::fs::read("Map11.bin")?)?;
+/// let file = std::io::BufWriter::new(std::fs::File::create("Map11.py")?);
+/// write_text_to(&bin, &TextWriteOptions::default(), file)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn write_text_to<W: std::io::Write>(bin: &Bin, opts: &TextWriteOptions, out: W) -> std::io::Result<()> {
+    let mut adapter = IoAdapter { inner: out, error: None };
+    match write_text_fmt(bin, opts, &mut adapter) {
+        Ok(()) => adapter.inner.flush(),
+        Err(std::fmt::Error) => Err(adapter.error.unwrap_or_else(|| std::io::Error::other("formatting failed"))),
+    }
+}
+
+fn write_text_fmt<W: Write>(bin: &Bin, opts: &TextWriteOptions, out: W) -> std::fmt::Result {
+    let mut writer = TextWriter::new(out, opts);
+    writer.write_raw(&opts.revision.header())?;
     for (key, value) in &bin.sections {
         writer.write_section(key, value)?;
     }
-    Ok(writer.buffer)
+    Ok(())
 }
 
+/// Writes text to an [`std::io::Write`], keeping the I/O error that a
+/// [`std::fmt::Error`] can't carry
+struct IoAdapter<W> {
+    inner: W,
+    error: Option<std::io::Error>,
+}
+
+impl<W: std::io::Write> Write for IoAdapter<W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            std::fmt::Error
+        })
+    }
+}
 
+/// Write a bin as text with [`Annotations`](crate::annotations::Annotations)
+/// shown as `#` comments above the values they belong to.
+///
+/// Annotations whose path isn't found in `bin` are listed at the top. The
+/// comments are skipped when the text is read back.
+pub fn write_text_annotated(
+    bin: &Bin,
+    opts: &TextWriteOptions,
+    annotations: &crate::annotations::Annotations,
+) -> Result<String, std::fmt::Error> {
+    let mut text = String::new();
+    let mut writer = TextWriter::new(&mut text, opts);
+    writer.write_raw(&opts.revision.header())?;
+    for (path, annotation, value) in annotations.resolve(bin) {
+        match value {
+            Some(value) => {
+                writer.comments.entry(value as *const BinValue).or_default().extend(annotation.comment_lines());
+            }
+            None => writeln!(writer.out, "# {} (not found): {}", path, annotation.comment_lines().join("; "))?,
+        }
+    }
+    for (key, value) in &bin.sections {
+        writer.write_comments(value)?;
+        writer.write_section(key, value)?;
+    }
+    Ok(text)
+}
 
-struct TextWriter {
-    buffer: String,
+struct TextWriter<W> {
+    out: W,
     indent_level: usize,
-    indent_size: usize,
+    opts: TextWriteOptions,
+    /// Comment lines to write above values, keyed by the value's address
+    comments: HashMap<*const BinValue, Vec<String>>,
+    /// Comment to write at the end of the current line
+    line_comment: Option<String>,
 }
 
-impl TextWriter {
-    fn new() -> Self {
+impl<W: Write> TextWriter<W> {
+    fn new(out: W, opts: &TextWriteOptions) -> Self {
         Self {
-            buffer: String::new(),
+            out,
             indent_level: 0,
-            indent_size: 2,
+            opts: *opts,
+            comments: HashMap::new(),
+            line_comment: None,
         }
     }
 
     fn indent(&mut self) {
-        self.indent_level += self.indent_size;
+        self.indent_level += 1;
     }
 
     fn dedent(&mut self) {
-        self.indent_level -= self.indent_size;
+        self.indent_level -= 1;
     }
 
-    fn pad(&mut self) {
-        for _ in 0..self.indent_level {
-            self.buffer.push(' ');
+    fn pad(&mut self) -> std::fmt::Result {
+        if self.opts.indent_tabs {
+            (0..self.indent_level).try_for_each(|_| self.out.write_char('\t'))
+        } else {
+            (0..self.indent_level * self.opts.indent_width).try_for_each(|_| self.out.write_char(' '))
         }
     }
 
-    fn write_raw(&mut self, s: &str) {
-        self.buffer.push_str(s);
+    /// End an item of a multi-line block
+    fn end_item(&mut self) -> std::fmt::Result {
+        if self.opts.trailing_commas {
+            self.write_raw(",")?;
+        }
+        self.end_line()
     }
 
-    fn write_section(&mut self, key: &str, value: &BinValue) -> Result<(), std::fmt::Error> {
-        self.write_raw(key);
-        self.write_raw(": ");
-        self.write_type(value);
-        self.write_raw(" = ");
-        self.write_value(value)?;
-        self.write_raw("\n");
+    /// End a line, after its trailing comment if there is one
+    fn end_line(&mut self) -> std::fmt::Result {
+        if let Some(comment) = self.line_comment.take() {
+            write!(self.out, " # {}", comment)?;
+        }
+        self.write_raw("\n")
+    }
+
+    /// A value that ends its line, with a named hash written as
+    /// [`TextWriteOptions::hash_comments`] asks
+    fn write_line_value(&mut self, value: &BinValue) -> std::fmt::Result {
+        let (hash, name) = match value {
+            BinValue::Hash { value, name: Some(name) } | BinValue::Link { value, name: Some(name) } => (u64::from(*value), name),
+            BinValue::File { value, name: Some(name) } => (*value, name),
+            _ => return self.write_value(value),
+        };
+        match self.opts.hash_comments {
+            // A name with a line break can't go in a comment
+            HashComments::Name if !name.contains(['\n', '\r']) => {
+                self.write_hex(hash)?;
+                self.line_comment = Some(name.clone());
+            },
+            HashComments::Hex => {
+                self.write_value(value)?;
+                let mut comment = String::new();
+                TextWriter::new(&mut comment, &self.opts).write_hex(hash)?;
+                self.line_comment = Some(comment);
+            },
+            _ => self.write_value(value)?,
+        }
         Ok(())
     }
 
-    fn write_type(&mut self, value: &BinValue) {
+    /// A quoted string, escaping what the parser would otherwise misread
+    ///
+    /// Printable non-ASCII text is written as-is; control characters become
+    /// `\xNN` or `\uNNNN`.
+    fn write_string(&mut self, s: &str) -> Result<(), std::fmt::Error> {
+        self.out.write_char('"')?;
+        for c in s.chars() {
+            match c {
+                '"' => self.out.write_str("\\\"")?,
+                '\\' => self.out.write_str("\\\\")?,
+                '\n' => self.out.write_str("\\n")?,
+                '\r' => self.out.write_str("\\r")?,
+                '\t' => self.out.write_str("\\t")?,
+                c if c.is_ascii_control() => write!(self.out, "\\x{:02x}", c as u32)?,
+                c if c.is_control() => write!(self.out, "\\u{:04x}", c as u32)?,
+                c => self.out.write_char(c)?,
+            }
+        }
+        self.out.write_char('"')?;
+        Ok(())
+    }
+
+    /// A float that reads back with the same bits: `inf`, `-inf`, `NaN`, or
+    /// `nan(0x...)` with the bits of any other NaN; finite values in the
+    /// shortest form, using an exponent only where `exponent` allows
+    fn write_float(&mut self, v: f32, exponent: bool) -> Result<(), std::fmt::Error> {
+        if v.is_nan() && v.to_bits() != f32::NAN.to_bits() {
+            write!(self.out, "nan({:#010x})", v.to_bits())
+        } else if exponent {
+            write!(self.out, "{:?}", v)
+        } else {
+            write!(self.out, "{}", v)
+        }
+    }
+
+    /// A vector, as `{ x, y, ... }`
+    fn write_floats(&mut self, v: &[f32]) -> Result<(), std::fmt::Error> {
+        self.write_raw("{ ")?;
+        for (i, val) in v.iter().enumerate() {
+            if i > 0 {
+                self.write_raw(", ")?;
+            }
+            self.write_float(*val, false)?;
+        }
+        self.write_raw(" }")?;
+        Ok(())
+    }
+
+    /// An unnamed hash, as `0x` and the digits in the configured case
+    fn write_hex(&mut self, value: u64) -> Result<(), std::fmt::Error> {
+        if self.opts.uppercase_hex {
+            write!(self.out, "0x{:X}", value)
+        } else {
+            write!(self.out, "{:#x}", value)
+        }
+    }
+
+    /// Whether `items` go on one line: few enough, all plain values and none commented
+    fn inline(&self, items: &[BinValue]) -> bool {
+        items.len() <= self.opts.max_inline_items
+            && self.shown(items.len()) == items.len()
+            && items.iter().all(|item| !matches!(item,
+                BinValue::List { .. } | BinValue::List2 { .. } | BinValue::Option { .. } | BinValue::Map { .. }
+                | BinValue::Pointer { .. } | BinValue::Embed { .. } | BinValue::Mtx44(_)))
+            && items.iter().all(|item| !self.comments.contains_key(&(item as *const BinValue)))
+    }
+
+    fn write_raw(&mut self, s: &str) -> std::fmt::Result {
+        self.out.write_str(s)
+    }
+
+    /// How many of `len` container items to write
+    fn shown(&self, len: usize) -> usize {
+        self.opts.max_items_per_container.map_or(len, |max| max.min(len))
+    }
+
+    /// Mark the items left out of a folded container
+    fn write_folded(&mut self, hidden: usize) -> Result<(), std::fmt::Error> {
+        if hidden > 0 {
+            self.pad()?;
+            writeln!(self.out, "# ... {} more items", hidden)?;
+        }
+        Ok(())
+    }
+
+    /// Write the comments attached to `value`, if any, each on its own line
+    fn write_comments(&mut self, value: &BinValue) -> Result<(), std::fmt::Error> {
+        if self.comments.is_empty() {
+            return Ok(());
+        }
+        if let Some(lines) = self.comments.remove(&(value as *const BinValue)) {
+            for line in lines {
+                self.pad()?;
+                writeln!(self.out, "# {}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_section(&mut self, key: &str, value: &BinValue) -> Result<(), std::fmt::Error> {
+        // Keys that aren't plain names, e.g. from other tools, are quoted
+        if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            self.write_raw(key)?;
+        } else {
+            self.write_string(key)?;
+        }
+        self.write_raw(": ")?;
+        self.write_type(value)?;
+        self.write_raw(" = ")?;
+        self.write_line_value(value)?;
+        self.end_line()
+    }
+
+    fn write_type(&mut self, value: &BinValue) -> std::fmt::Result {
         let type_name = get_type_name(value);
-        self.write_raw(&type_name);
+        self.write_raw(type_name)?;
         
         match value {
             BinValue::List { value_type, .. } => {
-                self.write_raw("[");
-                self.write_raw(get_bin_type_name(*value_type));
-                self.write_raw("]");
+                self.write_raw("[")?;
+                self.write_raw(get_bin_type_name(*value_type))?;
+                self.write_raw("]")?;
             },
             BinValue::List2 { value_type, .. } => {
-                self.write_raw("[");
-                self.write_raw(get_bin_type_name(*value_type));
-                self.write_raw("]");
+                self.write_raw("[")?;
+                self.write_raw(get_bin_type_name(*value_type))?;
+                self.write_raw("]")?;
             },
             BinValue::Option { value_type, .. } => {
-                self.write_raw("[");
-                self.write_raw(get_bin_type_name(*value_type));
-                self.write_raw("]");
+                self.write_raw("[")?;
+                self.write_raw(get_bin_type_name(*value_type))?;
+                self.write_raw("]")?;
             },
             BinValue::Map { key_type, value_type, .. } => {
-                self.write_raw("[");
-                self.write_raw(get_bin_type_name(*key_type));
-                self.write_raw(",");
-                self.write_raw(get_bin_type_name(*value_type));
-                self.write_raw("]");
+                self.write_raw("[")?;
+                self.write_raw(get_bin_type_name(*key_type))?;
+                self.write_raw(",")?;
+                self.write_raw(get_bin_type_name(*value_type))?;
+                self.write_raw("]")?;
+            },
+            BinValue::Raw { type_byte, absorbed_fields, .. } => {
+                write!(self.out, "[{:#04x}", type_byte)?;
+                if *absorbed_fields != 0 {
+                    write!(self.out, ",{}", absorbed_fields)?;
+                }
+                self.write_raw("]")?;
             },
             _ => {}
         }
+        Ok(())
     }
 
     fn write_value(&mut self, value: &BinValue) -> Result<(), std::fmt::Error> {
         match value {
-            BinValue::None => self.write_raw("null"),
-            BinValue::Bool(v) => self.write_raw(if *v { "true" } else { "false" }),
-            BinValue::I8(v) => write!(self.buffer, "{}", v)?,
-            BinValue::U8(v) => write!(self.buffer, "{}", v)?,
-            BinValue::I16(v) => write!(self.buffer, "{}", v)?,
-            BinValue::U16(v) => write!(self.buffer, "{}", v)?,
-            BinValue::I32(v) => write!(self.buffer, "{}", v)?,
-            BinValue::U32(v) => write!(self.buffer, "{}", v)?,
-            BinValue::I64(v) => write!(self.buffer, "{}", v)?,
-            BinValue::U64(v) => write!(self.buffer, "{}", v)?,
-            BinValue::F32(v) => write!(self.buffer, "{:?}", v)?,
-            BinValue::Vec2(v) => {
-                write!(self.buffer, "{{ {}, {} }}", v[0], v[1])?;
-            },
-            BinValue::Vec3(v) => {
-                write!(self.buffer, "{{ {}, {}, {} }}", v[0], v[1], v[2])?;
-            },
-            BinValue::Vec4(v) => {
-                write!(self.buffer, "{{ {}, {}, {}, {} }}", v[0], v[1], v[2], v[3])?;
-            },
+            BinValue::None => self.write_raw("null")?,
+            BinValue::Bool(v) => self.write_raw(if *v { "true" } else { "false" })?,
+            BinValue::I8(v) => write!(self.out, "{}", v)?,
+            BinValue::U8(v) => write!(self.out, "{}", v)?,
+            BinValue::I16(v) => write!(self.out, "{}", v)?,
+            BinValue::U16(v) => write!(self.out, "{}", v)?,
+            BinValue::I32(v) => write!(self.out, "{}", v)?,
+            BinValue::U32(v) => write!(self.out, "{}", v)?,
+            BinValue::I64(v) => write!(self.out, "{}", v)?,
+            BinValue::U64(v) => write!(self.out, "{}", v)?,
+            BinValue::F32(v) => self.write_float(*v, true)?,
+            BinValue::Vec2(v) => self.write_floats(v)?,
+            BinValue::Vec3(v) => self.write_floats(v)?,
+            BinValue::Vec4(v) => self.write_floats(v)?,
             BinValue::Mtx44(v) => {
                 self.indent();
-                self.write_raw("{\n");
-                self.pad();
+                self.write_raw("{\n")?;
+                self.pad()?;
                 for (i, val) in v.iter().enumerate() {
-                    write!(self.buffer, "{}", val)?;
+                    self.write_float(*val, false)?;
                     if i % 4 == 3 {
-                        self.write_raw("\n");
+                        self.write_raw("\n")?;
                         if i == 15 {
                             self.dedent();
                         }
-                        self.pad();
+                        self.pad()?;
                     } else {
-                        self.write_raw(", ");
+                        self.write_raw(", ")?;
                     }
                 }
-                self.write_raw("}");
+                self.write_raw("}")?;
             },
             BinValue::Rgba(v) => {
-                write!(self.buffer, "{{ {}, {}, {}, {} }}", v[0], v[1], v[2], v[3])?;
-            },
-            BinValue::String(v) => {
-                write!(self.buffer, "{:?}", v)?;
+                write!(self.out, "{{ {}, {}, {}, {} }}", v[0], v[1], v[2], v[3])?;
             },
+            BinValue::String(v) => self.write_string(v)?,
             BinValue::Hash { value, name } => {
                 if let Some(s) = name {
-                    write!(self.buffer, "{:?}", s)?;
+                    self.write_string(s)?;
                 } else {
-                    write!(self.buffer, "{:#x}", value)?;
+                    self.write_hex(u64::from(*value))?;
                 }
             },
             BinValue::File { value, name } => {
                 if let Some(s) = name {
-                    write!(self.buffer, "{:?}", s)?;
+                    self.write_string(s)?;
                 } else {
-                    write!(self.buffer, "{:#x}", value)?;
+                    self.write_hex(*value)?;
                 }
             },
             BinValue::Link { value, name } => {
                 if let Some(s) = name {
-                    write!(self.buffer, "{:?}", s)?;
+                    self.write_string(s)?;
                 } else {
-                    write!(self.buffer, "{:#x}", value)?;
+                    self.write_hex(u64::from(*value))?;
                 }
             },
-            BinValue::Flag(v) => self.write_raw(if *v { "true" } else { "false" }),
+            BinValue::Flag(v) => self.write_raw(if *v { "true" } else { "false" })?,
+            BinValue::Raw { bytes, .. } => write!(self.out, "\"{}\"", crate::model::hex_encode(bytes))?,
             
             BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
                 if items.is_empty() {
-                    self.write_raw("{}");
+                    self.write_raw("{}")?;
+                } else if self.inline(items) {
+                    self.write_raw("{ ")?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            self.write_raw(", ")?;
+                        }
+                        self.write_value(item)?;
+                    }
+                    self.write_raw(" }")?;
                 } else {
-                    self.write_raw("{\n");
+                    self.write_raw("{\n")?;
                     self.indent();
-                    for item in items {
-                        self.pad();
-                        self.write_value(item)?;
-                        self.write_raw("\n");
+                    let shown = self.shown(items.len());
+                    for item in &items[..shown] {
+                        self.write_comments(item)?;
+                        self.pad()?;
+                        self.write_line_value(item)?;
+                        self.end_item()?;
                     }
+                    self.write_folded(items.len() - shown)?;
                     self.dedent();
-                    self.pad();
-                    self.write_raw("}");
+                    self.pad()?;
+                    self.write_raw("}")?;
                 }
             },
             BinValue::Option { item, .. } => {
                 if let Some(inner) = item {
-                    self.write_raw("{\n");
+                    self.write_raw("{\n")?;
                     self.indent();
-                    self.pad();
-                    self.write_value(inner)?;
-                    self.write_raw("\n");
+                    self.write_comments(inner)?;
+                    self.pad()?;
+                    self.write_line_value(inner)?;
+                    self.end_item()?;
                     self.dedent();
-                    self.pad();
-                    self.write_raw("}");
+                    self.pad()?;
+                    self.write_raw("}")?;
                 } else {
-                    self.write_raw("{}");
+                    self.write_raw("{}")?;
                 }
             },
             BinValue::Map { items, .. } => {
                 if items.is_empty() {
-                    self.write_raw("{}");
+                    self.write_raw("{}")?;
                 } else {
-                    self.write_raw("{\n");
+                    self.write_raw("{\n")?;
                     self.indent();
-                    for (key, value) in items {
-                        self.pad();
+                    let shown = self.shown(items.len());
+                    for (key, value) in &items[..shown] {
+                        self.write_comments(value)?;
+                        self.pad()?;
                         self.write_value(key)?;
-                        self.write_raw(" = ");
-                        self.write_value(value)?;
-                        self.write_raw("\n");
+                        self.write_raw(" = ")?;
+                        self.write_line_value(value)?;
+                        self.end_item()?;
                     }
+                    self.write_folded(items.len() - shown)?;
                     self.dedent();
-                    self.pad();
-                    self.write_raw("}");
+                    self.pad()?;
+                    self.write_raw("}")?;
                 }
             },
             BinValue::Pointer { name, name_str, items } => {
                 if *name == 0 && items.is_empty() {
-                    self.write_raw("null");
+                    self.write_raw("null")?;
                 } else {
                     if let Some(s) = name_str {
-                        self.write_raw(s);
-                        self.write_raw(" ");
+                        self.write_raw(s)?;
+                        self.write_raw(" ")?;
                     } else {
-                        write!(self.buffer, "{:#x} ", name)?;
+                        self.write_hex(u64::from(*name))?;
+                        self.write_raw(" ")?;
                     }
                     if items.is_empty() {
-                        self.write_raw("{}");
+                        self.write_raw("{}")?;
                     } else {
-                        self.write_raw("{\n");
+                        self.write_raw("{\n")?;
                         self.indent();
                         for field in items {
-                            self.pad();
+                            self.write_comments(&field.value)?;
+                            self.pad()?;
                             if let Some(s) = &field.key_str {
-                                self.write_raw(s);
-                                self.write_raw(": ");
+                                self.write_raw(s)?;
+                                self.write_raw(": ")?;
                             } else {
-                                write!(self.buffer, "{:#x}: ", field.key)?;
+                                self.write_hex(u64::from(field.key))?;
+                                self.write_raw(": ")?;
                             }
-                            self.write_type(&field.value);
-                            self.write_raw(" = ");
-                            self.write_value(&field.value)?;
-                            self.write_raw("\n");
+                            self.write_type(&field.value)?;
+                            self.write_raw(" = ")?;
+                            self.write_line_value(&field.value)?;
+                            self.end_item()?;
                         }
                         self.dedent();
-                        self.pad();
-                        self.write_raw("}");
+                        self.pad()?;
+                        self.write_raw("}")?;
                     }
                 }
             },
             BinValue::Embed { name, name_str, items } => {
                 if let Some(s) = name_str {
-                    self.write_raw(s);
-                    self.write_raw(" ");
+                    self.write_raw(s)?;
+                    self.write_raw(" ")?;
                 } else {
-                    write!(self.buffer, "{:#x} ", name)?;
+                    self.write_hex(u64::from(*name))?;
+                    self.write_raw(" ")?;
                 }
                 if items.is_empty() {
-                    self.write_raw("{}");
+                    self.write_raw("{}")?;
                 } else {
-                    self.write_raw("{\n");
+                    self.write_raw("{\n")?;
                     self.indent();
                     for field in items {
-                        self.pad();
+                        self.write_comments(&field.value)?;
+                        self.pad()?;
                         if let Some(s) = &field.key_str {
-                            self.write_raw(s);
-                            self.write_raw(": ");
+                            self.write_raw(s)?;
+                            self.write_raw(": ")?;
                         } else {
-                            write!(self.buffer, "{:#x}: ", field.key)?;
+                            self.write_hex(u64::from(field.key))?;
+                            self.write_raw(": ")?;
                         }
-                        self.write_type(&field.value);
-                        self.write_raw(" = ");
-                        self.write_value(&field.value)?;
-                        self.write_raw("\n");
+                        self.write_type(&field.value)?;
+                        self.write_raw(" = ")?;
+                        self.write_line_value(&field.value)?;
+                        self.end_item()?;
                     }
                     self.dedent();
-                    self.pad();
-                    self.write_raw("}");
+                    self.pad()?;
+                    self.write_raw("}")?;
                 }
             },
         }
@@ -272,593 +666,519 @@ impl TextWriter {
     }
 }
 
-use nom::{
-    IResult,
-    branch::alt,
-    bytes::complete::{tag, take_while1, take_until, is_not},
-    character::complete::{char, multispace1, digit1, hex_digit1, one_of},
-    combinator::{map, opt, value, map_res},
-    multi::{many0, separated_list0},
-    sequence::{delimited, preceded, terminated, tuple, pair},
-};
-
-type ParseResult<'a, T> = IResult<&'a str, T>;
+/// A byte range of a text file, `start..end`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
-// ============================================================================
-// Basic Parsers
-// ============================================================================
+/// A text file that could not be parsed, with where and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextError {
+    /// Line of the problem, from 1
+    pub line: usize,
+    /// Column of the problem in characters, from 1
+    pub column: usize,
+    /// What the parser was looking for, e.g. `u8` or `'}'`
+    pub expected: String,
+    /// The token found instead; empty at the end of the input
+    pub found: String,
+    /// Where `found` is in the input
+    pub span: Span,
+}
 
-/// Parse whitespace and comments
-fn ws(input: &str) -> ParseResult<()> {
-    value(
-        (),
-        many0(alt((
-            value((), multispace1),
-            value((), pair(char('#'), alt((take_until("\n"), take_while1(|_| true))))),
-        )))
-    )(input)
-}
-
-/// Parse an identifier (alphanumeric + underscore)
-fn identifier(input: &str) -> ParseResult<&str> {
-    preceded(
-        ws,
-        take_while1(|c: char| c.is_alphanumeric() || c == '_')
-    )(input)
-}
-
-/// Parse a word (can include +, -, .)
-fn word(input: &str) -> ParseResult<&str> {
-    preceded(
-        ws,
-        take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '+' || c == '-' || c == '.')
-    )(input)
-}
-
-/// Parse a quoted string with escape sequences
-fn quoted_string(input: &str) -> ParseResult<String> {
-    preceded(
-        ws,
-        alt((
-            delimited(
-                char('"'),
-                map(
-                    many0(alt((
-                        map(is_not("\\\""), |s: &str| s.to_string()),
-                        map(preceded(char('\\'), one_of("nrt\\\"'")), |c| {
-                            match c {
-                                'n' => "\n".to_string(),
-                                'r' => "\r".to_string(),
-                                't' => "\t".to_string(),
-                                '\\' => "\\".to_string(),
-                                '"' => "\"".to_string(),
-                                '\'' => "'".to_string(),
-                                _ => c.to_string(),
-                            }
-                        }),
-                    ))),
-                    |parts| parts.join("")
-                ),
-                char('"')
-            ),
-            delimited(
-                char('\''),
-                map(
-                    many0(alt((
-                        map(is_not("\\'"), |s: &str| s.to_string()),
-                        map(preceded(char('\\'), one_of("nrt\\\"'")), |c| {
-                            match c {
-                                'n' => "\n".to_string(),
-                                'r' => "\r".to_string(),
-                                't' => "\t".to_string(),
-                                '\\' => "\\".to_string(),
-                                '"' => "\"".to_string(),
-                                '\'' => "'".to_string(),
-                                _ => c.to_string(),
-                            }
-                        }),
-                    ))),
-                    |parts| parts.join("")
-                ),
-                char('\'')
-            ),
-        ))
-    )(input)
-}
-
-/// Parse a hex u32 (0x12345678)
-fn hex_u32(input: &str) -> ParseResult<u32> {
-    preceded(
-        ws,
-        alt((
-            map_res(
-                preceded(alt((tag("0x"), tag("0X"))), hex_digit1),
-                |s| u32::from_str_radix(s, 16)
-            ),
-            map_res(digit1, |s: &str| s.parse::<u32>()),
-        ))
-    )(input)
-}
-
-/// Parse a hex u64 (0x123456789abcdef0)
-fn hex_u64(input: &str) -> ParseResult<u64> {
-    preceded(
-        ws,
-        alt((
-            map_res(
-                preceded(alt((tag("0x"), tag("0X"))), hex_digit1),
-                |s| u64::from_str_radix(s, 16)
-            ),
-            map_res(digit1, |s: &str| s.parse::<u64>()),
-        ))
-    )(input)
+impl TextError {
+    /// An error at byte `offset` of `data`
+    fn at(data: &str, offset: usize, expected: impl Into<String>) -> Self {
+        let before = &data[..offset];
+        // A byte order mark takes no column
+        let line_start = before.rfind('\n').map_or(if before.starts_with('\u{feff}') { 3 } else { 0 }, |i| i + 1);
+        let found = token_at(&data[offset..]);
+        TextError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            expected: expected.into(),
+            found: found.to_string(),
+            span: Span { start: offset, end: offset + found.len() },
+        }
+    }
 }
 
-/// Parse a boolean
-fn parse_bool(input: &str) -> ParseResult<bool> {
-    preceded(
-        ws,
-        alt((
-            value(true, tag("true")),
-            value(false, tag("false")),
-        ))
-    )(input)
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: expected {}, found ", self.line, self.column, self.expected)?;
+        if self.found.is_empty() {
+            write!(f, "end of input")
+        } else {
+            write!(f, "`{}`", self.found)
+        }
+    }
 }
 
-/// Parse a number of any type
-fn parse_number<T: std::str::FromStr>(input: &str) -> ParseResult<T> {
-    map_res(word, |s| s.parse::<T>())(input)
+impl std::error::Error for TextError {}
+
+/// The token starting `input`: a quoted string, a run of name characters or one other character
+fn token_at(input: &str) -> &str {
+    let Some(first) = input.chars().next() else { return "" };
+    let end = match first {
+        '"' | '\'' => input[1..].find(first).map_or(input.len(), |i| i + 2),
+        c if c.is_alphanumeric() || "_+-.".contains(c) => {
+            input.find(|c: char| !(c.is_alphanumeric() || "_+-.".contains(c))).unwrap_or(input.len())
+        },
+        c => c.len_utf8(),
+    };
+    let token = &input[..end];
+    token.split(['\r', '\n']).next().unwrap_or(token)
 }
 
 // ============================================================================
-// Type Parsers
+// Public API
 // ============================================================================
 
-/// Parse a type name
-fn parse_type_name(input: &str) -> ParseResult<BinType> {
-    map_res(word, |s| s.parse::<BinType>())(input)
-}
-
-/// Parse container type: list[type], map[key,value], option[type]
-fn parse_container_type(input: &str) -> ParseResult<(BinType, Option<BinType>)> {
-    preceded(
-        ws,
-        delimited(
-            char('['),
-            alt((
-                // map[key,value]
-                map(
-                    tuple((
-                        parse_type_name,
-                        preceded(tuple((ws, char(','), ws)), parse_type_name),
-                    )),
-                    |(k, v)| (k, Some(v))
-                ),
-                // list[type] or option[type]
-                map(parse_type_name, |t| (t, None)),
-            )),
-            preceded(ws, char(']'))
-        )
-    )(input)
+/// Container type parameters: the item (or key) type and, for maps, the value type
+type TypeInfo = (BinType, Option<BinType>);
+
+/// Known field types used to resolve `key = value` fields without annotations.
+///
+/// Build one from an existing bin with [`TextSchema::from_bin`] or fill it by
+/// hand with [`TextSchema::insert`].
+#[derive(Debug, Default, Clone)]
+pub struct TextSchema {
+    fields: HashMap<u32, (BinType, Option<TypeInfo>)>,
 }
 
-// ============================================================================
-// Value Parsers
-// ============================================================================
+impl TextSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-/// Parse a vec2: { x, y }
-fn parse_vec2(input: &str) -> ParseResult<[f32; 2]> {
-    delimited(
-        preceded(ws, char('{')),
-        map(
-            tuple((
-                parse_number::<f32>,
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-            )),
-            |(x, y)| [x, y]
-        ),
-        preceded(ws, char('}'))
-    )(input)
-}
-
-/// Parse a vec3: { x, y, z }
-fn parse_vec3(input: &str) -> ParseResult<[f32; 3]> {
-    delimited(
-        preceded(ws, char('{')),
-        map(
-            tuple((
-                parse_number::<f32>,
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-            )),
-            |(x, y, z)| [x, y, z]
-        ),
-        preceded(ws, char('}'))
-    )(input)
-}
-
-/// Parse a vec4: { x, y, z, w }
-fn parse_vec4(input: &str) -> ParseResult<[f32; 4]> {
-    delimited(
-        preceded(ws, char('{')),
-        map(
-            tuple((
-                parse_number::<f32>,
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-                preceded(tuple((ws, char(','), ws)), parse_number::<f32>),
-            )),
-            |(x, y, z, w)| [x, y, z, w]
-        ),
-        preceded(ws, char('}'))
-    )(input)
-}
-
-/// Parse a mtx44: { 16 floats }
-fn parse_mtx44(input: &str) -> ParseResult<[f32; 16]> {
-    delimited(
-        preceded(ws, char('{')),
-        map(
-            tuple((
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                parse_number::<f32>,
-                preceded(tuple((ws, opt(char(',')), ws)), parse_number::<f32>),
-            )),
-            |(m0, m1, m2, m3, m4, m5, m6, m7, m8, m9, m10, m11, m12, m13, m14, m15)| {
-                [m0, m1, m2, m3, m4, m5, m6, m7, m8, m9, m10, m11, m12, m13, m14, m15]
-            }
-        ),
-        preceded(ws, char('}'))
-    )(input)
-}
-
-/// Parse rgba: { r, g, b, a }
-fn parse_rgba(input: &str) -> ParseResult<[u8; 4]> {
-    delimited(
-        preceded(ws, char('{')),
-        map(
-            tuple((
-                parse_number::<u8>,
-                preceded(tuple((ws, char(','), ws)), parse_number::<u8>),
-                preceded(tuple((ws, char(','), ws)), parse_number::<u8>),
-                preceded(tuple((ws, char(','), ws)), parse_number::<u8>),
-            )),
-            |(r, g, b, a)| [r, g, b, a]
-        ),
-        preceded(ws, char('}'))
-    )(input)
-}
-
-/// Parse a hash (hex or quoted string)
-fn parse_hash(input: &str) -> ParseResult<BinValue> {
-    preceded(
-        ws,
-        alt((
-            map(quoted_string, |s| {
-                let h = crate::hash::fnv1a(&s);
-                BinValue::Hash { value: h, name: Some(s) }
-            }),
-            map(hex_u32, |h| BinValue::Hash { value: h, name: None }),
-        ))
-    )(input)
-}
-
-/// Parse a file hash (hex or quoted string)
-fn parse_file(input: &str) -> ParseResult<BinValue> {
-    preceded(
-        ws,
-        alt((
-            map(quoted_string, |s| {
-                let h = crate::hash::Xxh64::new(&s).0;
-                BinValue::File { value: h, name: Some(s) }
-            }),
-            map(hex_u64, |h| BinValue::File { value: h, name: None }),
-        ))
-    )(input)
-}
-
-/// Parse a link hash (hex or quoted string)
-fn parse_link(input: &str) -> ParseResult<BinValue> {
-    preceded(
-        ws,
-        alt((
-            map(quoted_string, |s| {
-                let h = crate::hash::fnv1a(&s);
-                BinValue::Link { value: h, name: Some(s) }
-            }),
-            map(hex_u32, |h| BinValue::Link { value: h, name: None }),
-        ))
-    )(input)
-}
-
-/// Parse a list: { item1, item2, ... }
-fn parse_list(input: &str, value_type: BinType, is_list2: bool) -> ParseResult<BinValue> {
-    let (input, items) = delimited(
-        preceded(ws, char('{')),
-        map(
-            opt(terminated(
-                separated_list0(
-                    preceded(ws, char(',')),
-                    |i| parse_value(i, value_type, None)
-                ),
-                opt(preceded(ws, char(',')))
-            )),
-            |opt_items| opt_items.unwrap_or_default()
-        ),
-        preceded(ws, char('}'))
-    )(input)?;
-
-    if is_list2 {
-        Ok((input, BinValue::List2 { value_type, items }))
-    } else {
-        Ok((input, BinValue::List { value_type, items }))
-    }
-}
-
-/// Parse an option: {} or { value }
-fn parse_option(input: &str, value_type: BinType) -> ParseResult<BinValue> {
-    let (input, item) = delimited(
-        preceded(ws, char('{')),
-        opt(|i| parse_value(i, value_type, None)),
-        preceded(ws, char('}'))
-    )(input)?;
-
-    Ok((input, BinValue::Option {
-        value_type,
-        item: item.map(Box::new)
-    }))
-}
-
-/// Parse a map: { key1 = val1, key2 = val2, ... }
-fn parse_map(input: &str, key_type: BinType, value_type: BinType) -> ParseResult<BinValue> {
-    let (input, items) = delimited(
-        preceded(ws, char('{')),
-        map(
-            opt(terminated(
-                separated_list0(
-                    preceded(ws, char(',')),
-                    tuple((
-                        |i| parse_value(i, key_type, None),
-                        preceded(tuple((ws, char('='), ws)), |i| parse_value(i, value_type, None)),
-                    ))
-                ),
-                opt(preceded(ws, char(',')))
-            )),
-            |opt_items| opt_items.unwrap_or_default()
-        ),
-        preceded(ws, char('}'))
-    )(input)?;
-
-    Ok((input, BinValue::Map { key_type, value_type, items }))
-}
-
-/// Parse a field: key: type = value
-fn parse_field(input: &str) -> ParseResult<crate::model::Field> {
-    let (input, key_str) = word(input)?;
-    let (key, key_str_opt) = if key_str.starts_with("0x") || key_str.starts_with("0X") {
-        (u32::from_str_radix(&key_str[2..], 16).unwrap_or(0), None)
-    } else {
-        (crate::hash::fnv1a(key_str), Some(key_str.to_string()))
-    };
+    /// Record the type of a field by name
+    pub fn insert(&mut self, field: &str, bin_type: BinType, type_info: Option<(BinType, Option<BinType>)>) {
+        self.fields.insert(crate::hash::fnv1a(field), (bin_type, type_info));
+    }
 
-    let (input, _) = preceded(ws, char(':'))(input)?;
-    let (input, field_type) = parse_type_name(input)?;
-    
-    // Handle container types
-    let (input, type_info) = if field_type.is_container() {
-        let (input, ti) = parse_container_type(input)?;
-        (input, Some(ti))
-    } else {
-        (input, None)
-    };
+    /// Look up a field type by field hash
+    pub fn get(&self, key: u32) -> Option<(BinType, Option<TypeInfo>)> {
+        self.fields.get(&key).copied()
+    }
 
-    let (input, _) = preceded(ws, char('='))(input)?;
-    let (input, value) = parse_value(input, field_type, type_info)?;
+    /// Learn field types from every structure in `bin`
+    ///
+    /// The first type seen for a field wins.
+    pub fn from_bin(bin: &Bin) -> Self {
+        let mut schema = Self::new();
+        for value in bin.sections.values() {
+            schema.learn(value);
+        }
+        schema
+    }
 
-    Ok((input, crate::model::Field { key, key_str: key_str_opt, value }))
+    fn learn(&mut self, value: &BinValue) {
+        match value {
+            BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+                for field in items {
+                    self.fields.entry(field.key).or_insert_with(|| type_of(&field.value));
+                    self.learn(&field.value);
+                }
+            },
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for item in items {
+                    self.learn(item);
+                }
+            },
+            BinValue::Option { item: Some(inner), .. } => self.learn(inner),
+            BinValue::Map { items, .. } => {
+                for (_, v) in items {
+                    self.learn(v);
+                }
+            },
+            _ => {},
+        }
+    }
 }
 
-/// Parse an embed: name { field1: type = value, ... }
-fn parse_embed(input: &str) -> ParseResult<BinValue> {
-    let (input, name_str) = word(input)?;
-    let (name, name_opt) = if name_str.starts_with("0x") || name_str.starts_with("0X") {
-        (u32::from_str_radix(&name_str[2..], 16).unwrap_or(0), None)
-    } else {
-        (crate::hash::fnv1a(name_str), Some(name_str.to_string()))
+fn type_of(value: &BinValue) -> (BinType, Option<TypeInfo>) {
+    let info = match value {
+        BinValue::List { value_type, .. } | BinValue::List2 { value_type, .. } | BinValue::Option { value_type, .. } => {
+            Some((*value_type, None))
+        },
+        BinValue::Map { key_type, value_type, .. } => Some((*key_type, Some(*value_type))),
+        _ => None,
     };
+    (crate::binary::get_value_type(value), info)
+}
 
-    let (input, items) = delimited(
-        preceded(ws, char('{')),
-        map(
-            opt(terminated(
-                separated_list0(
-                    opt(preceded(ws, char(','))),
-                    parse_field
-                ),
-                opt(preceded(ws, char(',')))
-            )),
-            |opt_items| opt_items.unwrap_or_default()
-        ),
-        preceded(ws, char('}'))
-    )(input)?;
-
-    Ok((input, BinValue::Embed { name, name_str: name_opt, items }))
-}
-
-/// Parse a pointer: name { field1: type = value, ... } or null
-fn parse_pointer(input: &str) -> ParseResult<BinValue> {
-    preceded(
-        ws,
-        alt((
-            value(BinValue::Pointer { name: 0, name_str: None, items: vec![] }, tag("null")),
-            |input| {
-                let (input, name_str) = word(input)?;
-                let (name, name_opt) = if name_str == "null" {
-                    (0, None)
-                } else if name_str.starts_with("0x") || name_str.starts_with("0X") {
-                    (u32::from_str_radix(&name_str[2..], 16).unwrap_or(0), None)
-                } else {
-                    (crate::hash::fnv1a(name_str), Some(name_str.to_string()))
-                };
+/// Options for [`read_text_with_options`].
+#[derive(Debug, Clone, Copy)]
+pub struct TextReadOptions<'a> {
+    /// Accept `key = value` without a `: type` annotation (relaxed dialect)
+    ///
+    /// The type comes from `schema` when it knows the field, otherwise from
+    /// the shape of the literal (see [`read_text_with_options`]).
+    pub infer_types: bool,
+    /// Field types consulted before falling back to literal-shape inference
+    pub schema: Option<&'a TextSchema>,
+    /// Parse with the rules of this revision; `None` takes it from the header
+    pub revision: Option<TextRevision>,
+    /// Also accept the quirks of files written by the C++ ritobin: `path`,
+    /// `struct`/`ptr` and `bitbool` for `file`, `pointer` and `flag`, and
+    /// vector and color components without commas
+    ///
+    /// Everything else C++ ritobin writes, such as `0x` hashes of any width
+    /// and list items on their own lines, reads without it.
+    pub ritobin_compat: bool,
+    /// Maximum nesting of containers, pointers and embeds inside a section
+    ///
+    /// Nested values don't use the call stack, so this only bounds the work
+    /// and memory a crafted file can ask for.
+    pub max_depth: usize,
+    /// What to do with a section whose name was already used
+    pub duplicate_sections: DuplicateSections,
+}
 
-                let (input, items) = if name == 0 {
-                    (input, vec![])
-                } else {
-                    delimited(
-                        preceded(ws, char('{')),
-                        map(
-                            opt(terminated(
-                                separated_list0(
-                                    opt(preceded(ws, char(','))),
-                                    parse_field
-                                ),
-                                opt(preceded(ws, char(',')))
-                            )),
-                            |opt_items| opt_items.unwrap_or_default()
-                        ),
-                        preceded(ws, char('}'))
-                    )(input)?
-                };
-
-                Ok((input, BinValue::Pointer { name, name_str: name_opt, items }))
-            }
-        ))
-    )(input)
-}
-
-/// Main value parser
-fn parse_value<'a>(input: &'a str, bin_type: BinType, type_info: Option<(BinType, Option<BinType>)>) -> ParseResult<'a, BinValue> {
-    match bin_type {
-        BinType::None => map(preceded(ws, tag("null")), |_| BinValue::None)(input),
-        BinType::Bool => map(parse_bool, BinValue::Bool)(input),
-        BinType::I8 => map(parse_number, BinValue::I8)(input),
-        BinType::U8 => map(parse_number, BinValue::U8)(input),
-        BinType::I16 => map(parse_number, BinValue::I16)(input),
-        BinType::U16 => map(parse_number, BinValue::U16)(input),
-        BinType::I32 => map(parse_number, BinValue::I32)(input),
-        BinType::U32 => map(hex_u32, BinValue::U32)(input),
-        BinType::I64 => map(parse_number, BinValue::I64)(input),
-        BinType::U64 => map(hex_u64, BinValue::U64)(input),
-        BinType::F32 => map(parse_number, BinValue::F32)(input),
-        BinType::Vec2 => map(parse_vec2, BinValue::Vec2)(input),
-        BinType::Vec3 => map(parse_vec3, BinValue::Vec3)(input),
-        BinType::Vec4 => map(parse_vec4, BinValue::Vec4)(input),
-        BinType::Mtx44 => map(parse_mtx44, BinValue::Mtx44)(input),
-        BinType::Rgba => map(parse_rgba, BinValue::Rgba)(input),
-        BinType::String => map(quoted_string, BinValue::String)(input),
-        BinType::Hash => parse_hash(input),
-        BinType::File => parse_file(input),
-        BinType::Link => parse_link(input),
-        BinType::Flag => map(parse_bool, BinValue::Flag)(input),
-        BinType::List => {
-            let (inner_type, _) = type_info.ok_or_else(|| {
-                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
-            })?;
-            parse_list(input, inner_type, false)
-        },
-        BinType::List2 => {
-            let (inner_type, _) = type_info.ok_or_else(|| {
-                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
-            })?;
-            parse_list(input, inner_type, true)
-        },
-        BinType::Option => {
-            let (inner_type, _) = type_info.ok_or_else(|| {
-                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
-            })?;
-            parse_option(input, inner_type)
-        },
-        BinType::Map => {
-            let (key_type, value_type) = type_info.ok_or_else(|| {
-                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
-            })?;
-            let value_type = value_type.ok_or_else(|| {
-                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
-            })?;
-            parse_map(input, key_type, value_type)
-        },
-        BinType::Pointer => parse_pointer(input),
-        BinType::Embed => parse_embed(input),
+/// How a section name used more than once is read, see
+/// [`TextReadOptions::duplicate_sections`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateSections {
+    /// Keep the last value, at the position of the first (default)
+    #[default]
+    KeepLast,
+    /// Keep the first value and ignore the rest
+    KeepFirst,
+    /// Fail at the repeated section, or skip it with [`read_text_recover`]
+    Error,
+}
+
+/// A section whose name was already used, reported by [`read_text_with_duplicates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateSection {
+    /// The section name
+    pub key: String,
+    /// Line the repeated section starts on, from 1
+    pub line: usize,
+    /// The whole repeated section, from its name to the end of its value
+    pub span: Span,
+}
+
+impl Default for TextReadOptions<'_> {
+    fn default() -> Self {
+        Self {
+            infer_types: false,
+            schema: None,
+            revision: None,
+            ritobin_compat: false,
+            max_depth: 256,
+            duplicate_sections: DuplicateSections::KeepLast,
+        }
     }
 }
 
-// ============================================================================
-// Top-Level Parsers
-// ============================================================================
+pub fn read_text(data: &str) -> Result<Bin, TextError> {
+    read_text_with_options(data, &TextReadOptions::default())
+}
 
-/// Parse a section: key: type = value
-fn parse_section(input: &str) -> ParseResult<(String, BinValue)> {
-    preceded(
-        ws,
-        |input| {
-            let (input, key) = identifier(input)?;
-            let (input, _) = preceded(ws, char(':'))(input)?;
-            let (input, bin_type) = parse_type_name(input)?;
+/// Read a text file with explicit parser options.
+///
+/// With `infer_types` enabled, unannotated values are typed as follows when
+/// the schema doesn't know the field: quoted text is `string`, `0x...` is
+/// `hash`, `true`/`false` is `bool`, integers are `i32` (or `u32`/`i64` when
+/// out of range), other numbers are `f32`, and `Name { ... }` is an `embed`.
+///
+/// Type names are read in any case, and common aliases such as `uint32`,
+/// `float` and `boolean` are accepted; the writer always uses the canonical
+/// lowercase names.
+///
+/// Files are parsed by the rules of their [`TextRevision`]; a revision newer
+/// than [`TextRevision::CURRENT`] is an error rather than a misread.
+///
+/// A leading UTF-8 byte order mark is skipped and CRLF line endings read as
+/// LF, including inside strings.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::text::{read_text_with_options, TextReadOptions};
+///
+/// let err = read_text_with_options("version: u32 = 3\nflags: u8 = 300\n", &TextReadOptions::default()).unwrap_err();
+/// assert_eq!((err.line, err.column), (2, 13));
+/// assert_eq!(err.to_string(), "line 2, column 13: expected u8, found `300`");
+/// ```
+pub fn read_text_with_options(data: &str, opts: &TextReadOptions) -> Result<Bin, TextError> {
+    read_text_impl(data, opts, None, None, None)
+}
 
-            // Handle container types
-            let (input, type_info) = if bin_type.is_container() {
-                let (input, ti) = parse_container_type(input)?;
-                (input, Some(ti))
-            } else {
-                (input, None)
-            };
+/// Result of [`read_text_recover`].
+#[derive(Debug)]
+pub struct RecoveredText {
+    /// Every section that parsed
+    pub bin: Bin,
+    /// Why each left-out section failed, in file order
+    pub errors: Vec<TextError>,
+}
 
-            let (input, _) = preceded(ws, char('='))(input)?;
-            let (input, value) = parse_value(input, bin_type, type_info)?;
+/// Read a text file, skipping sections that fail to parse instead of failing entirely.
+///
+/// After a malformed section, parsing resumes at the next unindented line
+/// of the form `name: ...` or `name = ...` (or with a quoted name), which is
+/// how the writer lays out sections. An unsupported revision header is still fatal.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::text::{read_text_recover, TextReadOptions};
+///
+/// let text = "version: u32 = 3\nlinked: list[string] = {\n  \"a.bin\"\n  oops\n}\nflag: bool = true\n";
+/// let recovered = read_text_recover(text, &TextReadOptions::default())?;
+/// assert_eq!(recovered.bin.sections.len(), 2);
+/// assert_eq!(recovered.errors[0].line, 4);
+/// # Ok::<(), ritobin_rust::text::TextError>(())
+/// ```
+pub fn read_text_recover(data: &str, opts: &TextReadOptions) -> Result<RecoveredText, TextError> {
+    let mut errors = Vec::new();
+    let bin = read_text_impl(data, opts, Some(&mut errors), None, None)?;
+    Ok(RecoveredText { bin, errors })
+}
 
-            Ok((input, (key.to_string(), value)))
-        }
-    )(input)
+/// Read a text file along with the byte span of every value in it.
+///
+/// The spans are in pre-order: each section's value, then what it holds,
+/// with map keys before their values and fields in order. A `raw` field's
+/// span covers its hex string.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::text::{read_text_with_spans, TextReadOptions};
+///
+/// let text = "a: u8 = 1\nb: list[u8] = { 2, 3 }\n";
+/// let (bin, spans) = read_text_with_spans(text, &TextReadOptions::default())?;
+/// assert_eq!(bin.sections.len(), 2);
+/// let literals: Vec<&str> = spans.iter().map(|s| &text[s.start..s.end]).collect();
+/// assert_eq!(literals, ["1", "{ 2, 3 }", "2", "3"]);
+/// # Ok::<(), ritobin_rust::text::TextError>(())
+/// ```
+pub fn read_text_with_spans(data: &str, opts: &TextReadOptions) -> Result<(Bin, Vec<Span>), TextError> {
+    let mut spans = Vec::new();
+    let bin = read_text_impl(data, opts, None, Some(&mut spans), None)?;
+    Ok((bin, spans))
 }
 
-/// Parse the entire bin file
-fn parse_bin(input: &str) -> ParseResult<Bin> {
-    let (input, _) = ws(input)?;
-    let (input, sections) = many0(parse_section)(input)?;
-    let (input, _) = ws(input)?;
+/// Read a text file, also listing every section whose name was already used.
+///
+/// Which value is kept is up to `opts.duplicate_sections`; with the default
+/// the last one wins and the repeats are only reported.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::BinValue;
+/// use ritobin_rust::text::{read_text_with_duplicates, DuplicateSections, TextReadOptions};
+///
+/// let text = "version: u32 = 3\nflag: bool = true\nversion: u32 = 4\n";
+/// let opts = TextReadOptions { duplicate_sections: DuplicateSections::KeepFirst, ..Default::default() };
+/// let (bin, duplicates) = read_text_with_duplicates(text, &opts)?;
+/// assert_eq!(bin.sections.get("version"), Some(&BinValue::U32(3)));
+/// assert_eq!((duplicates[0].key.as_str(), duplicates[0].line), ("version", 3));
+/// # Ok::<(), ritobin_rust::text::TextError>(())
+/// ```
+pub fn read_text_with_duplicates(data: &str, opts: &TextReadOptions) -> Result<(Bin, Vec<DuplicateSection>), TextError> {
+    let mut duplicates = Vec::new();
+    let bin = read_text_impl(data, opts, None, None, Some(&mut duplicates))?;
+    Ok((bin, duplicates))
+}
+
+/// Where a value is in a text file: its bytes and the lines they're on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRange {
+    pub span: Span,
+    /// First line, from 1
+    pub start_line: usize,
+    /// Last line, inclusive
+    pub end_line: usize,
+}
+
+/// Where each value of a parsed text file came from, by value path.
+///
+/// Paths use the notation of [`edit::ValuePath`](crate::edit::ValuePath),
+/// with fields by name (or `0x` hash) and map items by key as written by
+/// [`compare`](crate::compare), e.g. `entries[0x1234abcd].mSpell[2]`. A map
+/// item's range starts at its key, so it takes in the whole `key = value`.
+#[derive(Debug, Clone, Default)]
+pub struct TextSourceMap {
+    values: indexmap::IndexMap<String, SourceRange>,
+}
 
-    let mut bin = Bin::new();
-    for (key, value) in sections {
-        bin.sections.insert(key, value);
+impl TextSourceMap {
+    /// Map the values of `bin` to `spans` of `data`, as given by [`read_text_with_spans`]
+    pub fn new(data: &str, bin: &Bin, spans: &[Span]) -> Self {
+        let newlines: Vec<usize> = data.match_indices('\n').map(|(i, _)| i).collect();
+        let mut map = SourceMapBuilder { newlines, spans: spans.iter(), map: Self::default() };
+        for (key, value) in &bin.sections {
+            map.visit(key.clone(), value, None);
+        }
+        map.map
+    }
+
+    /// Where the value at `path` is, if it came from the text
+    pub fn get(&self, path: &str) -> Option<&SourceRange> {
+        self.values.get(path)
+    }
+
+    /// Every value path and its range, in file order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SourceRange)> {
+        self.values.iter().map(|(path, range)| (path.as_str(), range))
     }
 
-    Ok((input, bin))
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
 }
 
-// ============================================================================
-// Public API
-// ============================================================================
+struct SourceMapBuilder<'a> {
+    /// Byte offset of every `\n`
+    newlines: Vec<usize>,
+    spans: std::slice::Iter<'a, Span>,
+    map: TextSourceMap,
+}
 
-pub fn read_text(data: &str) -> Result<Bin, String> {
-    match parse_bin(data) {
-        Ok((remaining, bin)) => {
-            let trimmed = remaining.trim();
-            if !trimmed.is_empty() {
-                Err(format!("Unexpected content after parsing: {}", trimmed))
-            } else {
-                Ok(bin)
-            }
-        }
-        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
-            Err(format!("Parse error at position: {:?}", e))
+impl SourceMapBuilder<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.newlines.partition_point(|&nl| nl < offset) + 1
+    }
+
+    /// Record `value` and everything in it, taking their spans in pre-order;
+    /// `from` is where the value's map key starts
+    fn visit(&mut self, path: String, value: &BinValue, from: Option<usize>) {
+        let Some(&span) = self.spans.next() else { return };
+        let span = Span { start: from.unwrap_or(span.start), end: span.end };
+        let range = SourceRange { span, start_line: self.line(span.start), end_line: self.line(span.end.max(span.start + 1) - 1) };
+        self.map.values.entry(path.clone()).or_insert(range);
+        match value {
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for (i, item) in items.iter().enumerate() {
+                    self.visit(format!("{}[{}]", path, i), item, None);
+                }
+            },
+            BinValue::Option { item: Some(inner), .. } => self.visit(format!("{}[0]", path), inner, None),
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    let key_start = self.spans.next().map(|s| s.start);
+                    self.visit(format!("{}[{}]", path, crate::compare::key_label(k)), v, key_start);
+                }
+            },
+            BinValue::Pointer { items, .. } | BinValue::Embed { items, .. } => {
+                for field in items {
+                    self.visit(format!("{}.{}", path, crate::compare::field_label(field)), &field.value, None);
+                }
+            },
+            _ => {},
         }
-        Err(nom::Err::Incomplete(_)) => {
-            Err("Incomplete input".to_string())
+    }
+}
+
+/// Read a text file along with where each of its values came from.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::text::{read_text_with_source_map, TextReadOptions};
+///
+/// let text = "version: u32 = 3\nlinked: list[string] = {\n  \"a.bin\"\n  \"b.bin\"\n}\n";
+/// let (_, map) = read_text_with_source_map(text, &TextReadOptions::default())?;
+/// assert_eq!(map.get("linked").map(|r| (r.start_line, r.end_line)), Some((2, 5)));
+/// assert_eq!(map.get("linked[1]").unwrap().start_line, 4);
+/// # Ok::<(), ritobin_rust::text::TextError>(())
+/// ```
+pub fn read_text_with_source_map(data: &str, opts: &TextReadOptions) -> Result<(Bin, TextSourceMap), TextError> {
+    let (bin, spans) = read_text_with_spans(data, opts)?;
+    let map = TextSourceMap::new(data, &bin, &spans);
+    Ok((bin, map))
+}
+
+/// Read only the `type`, `version` and `linked` sections at the start of a
+/// text file.
+///
+/// Parsing stops at the first other section, usually `entries`, without
+/// reading it, so this stays fast on files of any size. Strings moved into a
+/// `__strings` table are left as their `@str:N` references.
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::model::BinValue;
+/// use ritobin_rust::text::read_header;
+///
+/// let text = "#PROP_text\ntype: string = \"PROP\"\nversion: u32 = 3\nentries: map[hash,embed] = { not even valid\n";
+/// let header = read_header(text)?;
+/// assert_eq!(header.sections.get("version"), Some(&BinValue::U32(3)));
+/// assert!(!header.sections.contains_key("entries"));
+/// # Ok::<(), ritobin_rust::text::TextError>(())
+/// ```
+pub fn read_header(data: &str) -> Result<Bin, TextError> {
+    let opts = &TextReadOptions { revision: Some(detect_revision(data, &TextReadOptions::default())?), ..Default::default() };
+    let mut parser = parser::Parser::new(data, bom_len(data), opts, false);
+    parser.header_only = true;
+    parser.bin(None).map_err(|e| e.into_error(data))
+}
+
+/// The revision to parse `data` by: the one in `opts`, else the one in its header
+fn detect_revision(data: &str, opts: &TextReadOptions) -> Result<TextRevision, TextError> {
+    match opts.revision {
+        Some(revision) => Ok(revision),
+        None => TextRevision::detect(data).map_err(|_| {
+            let header = data.find("#PROP_text").map_or(0, |i| i + "#PROP_text".len());
+            let offset = header + data[header..].len() - data[header..].trim_start_matches([' ', '\t']).len();
+            TextError::at(data, offset, format!("a text format revision up to v{}", TextRevision::CURRENT.number()))
+        }),
+    }
+}
+
+/// Length of the byte order mark at the start of `data`, if any
+fn bom_len(data: &str) -> usize {
+    if data.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 }
+}
+
+fn read_text_impl(
+    data: &str,
+    opts: &TextReadOptions,
+    errors: Option<&mut Vec<TextError>>,
+    spans: Option<&mut Vec<Span>>,
+    duplicates: Option<&mut Vec<DuplicateSection>>,
+) -> Result<Bin, TextError> {
+    let opts = &TextReadOptions { revision: Some(detect_revision(data, opts)?), ..*opts };
+    let mut parser = parser::Parser::new(data, bom_len(data), opts, spans.is_some());
+    let mut skipped = Vec::new();
+    let mut bin = parser.bin(errors.is_some().then_some(&mut skipped)).map_err(|e| e.into_error(data))?;
+    let expanded = crate::string_table::expand_strings(&mut bin).map_err(|message| {
+        let section = crate::string_table::STRING_TABLE_SECTION;
+        let at = data.match_indices(section).map(|(i, _)| i).find(|&i| i == 0 || data.as_bytes()[i - 1] == b'\n');
+        TextError::at(data, at.unwrap_or(0), message)
+    });
+    match errors {
+        Some(errors) => {
+            errors.extend(skipped.into_iter().map(|e| e.into_error(data)));
+            errors.extend(expanded.err());
+        },
+        None => expanded?,
+    }
+    if let Some(spans) = spans {
+        for key in bin.sections.keys() {
+            spans.extend(parser.section_spans.get(key).into_iter().flatten());
         }
     }
+    if let Some(duplicates) = duplicates {
+        duplicates.extend(parser.duplicates.into_iter().map(|(key, span)| DuplicateSection {
+            key,
+            line: data[..span.start].matches('\n').count() + 1,
+            span,
+        }));
+    }
+    Ok(bin)
 }
 
 fn get_bin_type_name(t: BinType) -> &'static str {
@@ -922,6 +1242,7 @@ fn get_type_name(v: &BinValue) -> &'static str {
         BinValue::Option { .. } => "option",
         BinValue::Map { .. } => "map",
         BinValue::Flag(_) => "flag",
+        BinValue::Raw { .. } => "raw",
     }
 }
 
@@ -942,6 +1263,23 @@ mod tests {
         assert!(text.contains("version: u32 = 1"));
     }
 
+    #[test]
+    fn test_write_text_folded() {
+        let mut bin = Bin::new();
+        bin.sections.insert("items".to_string(), BinValue::List {
+            value_type: BinType::U32,
+            items: (0..5).map(BinValue::U32).collect(),
+        });
+        let opts = TextWriteOptions { max_items_per_container: Some(2), ..Default::default() };
+        let text = write_text_with_options(&bin, &opts).unwrap();
+        assert!(text.contains("  1\n  # ... 3 more items\n}"));
+        assert!(!text.contains("  2\n"));
+
+        let Some(BinValue::List { items, .. }) = read_text(&text).unwrap().sections.get("items").cloned() else { panic!() };
+        assert_eq!(items.len(), 2);
+        assert_eq!(write_text_with_options(&bin, &TextWriteOptions::default()).unwrap(), write_text(&bin).unwrap());
+    }
+
     #[test]
     fn test_read_text_basic() {
         let text = r#"
@@ -953,4 +1291,444 @@ version: u32 = 1
         assert_eq!(bin.sections.get("type"), Some(&BinValue::String("PROP".to_string())));
         assert_eq!(bin.sections.get("version"), Some(&BinValue::U32(1)));
     }
+
+    #[test]
+    fn test_text_revision_header() {
+        let mut bin = Bin::new();
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        let text = write_text(&bin).unwrap();
+        assert!(text.starts_with("#PROP_text v2\n"));
+        assert_eq!(TextRevision::detect(&text), Ok(TextRevision::CURRENT));
+        assert_eq!(read_text(&text).unwrap(), bin);
+
+        let opts = TextWriteOptions { revision: TextRevision::V1, ..Default::default() };
+        let legacy = write_text_with_options(&bin, &opts).unwrap();
+        assert!(legacy.starts_with("#PROP_text\n"));
+        assert_eq!(TextRevision::detect(&legacy), Ok(TextRevision::V1));
+        assert_eq!(TextRevision::detect("version: u32 = 3"), Ok(TextRevision::V1));
+        assert_eq!(read_text(&legacy).unwrap(), bin);
+
+        assert_eq!(read_text("#PROP_text v9\nversion: u32 = 3").unwrap_err().found, "v9");
+        assert!(read_text("#PROP_text beta\nversion: u32 = 3").is_err());
+        let forced = TextReadOptions { revision: Some(TextRevision::V1), ..Default::default() };
+        assert_eq!(read_text_with_options("#PROP_text v9\nversion: u32 = 3", &forced).unwrap(), bin);
+    }
+
+    #[test]
+    fn test_text_round_trip_nested() {
+        let text = r#"
+#PROP_text
+type: string = "PROP"
+version: u32 = 3
+entries: map[hash,embed] = {
+  "Characters/Ahri/CharacterRecords/Root" = CharacterRecord {
+    spellNames: list[string] = {
+      "AhriQ"
+      "AhriW"
+    }
+    mPtr: pointer = SomeClass {
+      x: u32 = 5
+      y: f32 = 1.5
+    }
+  }
+  0x12345678 = 0xdeadbeef {}
+}
+"#;
+        let bin = read_text(text).unwrap();
+        let written = write_text(&bin).unwrap();
+        assert_eq!(read_text(&written).unwrap(), bin);
+        let with_commas = r#"a: list[u8] = { 1, 2, 3, }"#;
+        assert!(read_text(with_commas).is_ok());
+    }
+
+    #[test]
+    fn test_text_error_location() {
+        let err = |text: &str| {
+            let e = read_text(text).unwrap_err();
+            (e.line, e.column, e.expected, e.found)
+        };
+        assert_eq!(err("version: u32 = 3\nflags: u8 = 300\n"), (2, 13, "u8".to_string(), "300".to_string()));
+        let nested = "entries: map[hash,embed] = {\n  \"Root\" = Record {\n    inner: embed = Child {\n      x: f32 = 1.5\n      y: vec2 = { 1, oops }\n    }\n  }\n}\n";
+        assert_eq!(err(nested), (5, 22, "a number".to_string(), "oops".to_string()));
+        assert_eq!(err("list: list[u8] = {\n  1\n  2\n"), (4, 1, "'}'".to_string(), String::new()));
+        assert_eq!(err("a: u9 = 1"), (1, 4, "a type name".to_string(), "u9".to_string()));
+        assert_eq!(err("a = 1"), (1, 3, "': type'".to_string(), "=".to_string()));
+        assert_eq!(read_text("a: u8 = x").unwrap_err().to_string(), "line 1, column 9: expected u8, found `x`");
+    }
+
+    #[test]
+    fn test_read_text_recover() {
+        let text = "\
+#PROP_text
+version: u32 = 3
+first: list[u8] = {
+  1
+  x
+}
+second: u8 = 2
+third: embed = Thing {
+  a: u9 = 1
+}
+fourth: u8 = 4
+fifth: list[u8] = {
+  5
+";
+        assert!(read_text(text).is_err());
+        let recovered = read_text_recover(text, &TextReadOptions::default()).unwrap();
+        let keys: Vec<&str> = recovered.bin.sections.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["version", "second", "fourth"]);
+        let errors: Vec<(usize, usize)> = recovered.errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(errors, [(5, 3), (9, 6), (14, 1)]);
+        assert_eq!(recovered.bin.sections.get("fourth"), Some(&BinValue::U8(4)));
+
+        let clean = read_text_recover("a: u8 = 1\n", &TextReadOptions::default()).unwrap();
+        assert!(clean.errors.is_empty());
+    }
+
+    #[test]
+    fn test_write_text_style() {
+        let text = r#"
+entries: map[hash,embed] = {
+  0x12AB = 0xdeadbeef {
+    spellNames: list[string] = {
+      "AhriQ"
+      "AhriW"
+    }
+    mPtr: pointer = SomeClass {
+      0xABC: list[u8] = { 1, 2, 3 }
+    }
+  }
+}
+"#;
+        let bin = read_text(text).unwrap();
+        let opts = TextWriteOptions {
+            indent_tabs: true,
+            trailing_commas: true,
+            uppercase_hex: true,
+            max_inline_items: 2,
+            revision: TextRevision::V1,
+            ..Default::default()
+        };
+        let written = write_text_with_options(&bin, &opts).unwrap();
+        assert_eq!(written, "#PROP_text\nentries: map[hash,embed] = {\n\t0x12AB = 0xDEADBEEF {\n\t\tspellNames: list[string] = { \"AhriQ\", \"AhriW\" },\n\t\tmPtr: pointer = SomeClass {\n\t\t\t0xABC: list[u8] = {\n\t\t\t\t1,\n\t\t\t\t2,\n\t\t\t\t3,\n\t\t\t},\n\t\t},\n\t},\n}\n");
+        assert_eq!(read_text(&written).unwrap(), bin);
+
+        let four = write_text_with_options(&bin, &TextWriteOptions { indent_width: 4, ..Default::default() }).unwrap();
+        assert!(four.contains("\n    0x12ab = 0xdeadbeef {\n        spellNames"));
+        assert_eq!(read_text(&four).unwrap(), bin);
+    }
+
+    #[test]
+    fn test_write_text_hash_comments() {
+        let named = |name: &str| BinValue::Hash { value: crate::hash::fnv1a(name), name: Some(name.to_string()) };
+        let mut bin = Bin::new();
+        bin.sections.insert("h".to_string(), named("Root"));
+        bin.sections.insert("m".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::File,
+            items: vec![(named("Key"), BinValue::File { value: crate::hash::Xxh64::new("a.dds").0, name: Some("a.dds".to_string()) })],
+        });
+        let root = crate::hash::fnv1a("Root");
+        let file = crate::hash::Xxh64::new("a.dds").0;
+
+        let opts = TextWriteOptions { hash_comments: HashComments::Name, trailing_commas: true, ..Default::default() };
+        let written = write_text_with_options(&bin, &opts).unwrap();
+        assert!(written.contains(&format!("h: hash = {:#x} # Root\n", root)));
+        assert!(written.contains(&format!("  \"Key\" = {:#x}, # a.dds\n", file)));
+        let read = read_text(&written).unwrap();
+        assert_eq!(read.sections.get("h"), Some(&BinValue::Hash { value: root, name: None }));
+
+        let opts = TextWriteOptions { hash_comments: HashComments::Hex, ..Default::default() };
+        let written = write_text_with_options(&bin, &opts).unwrap();
+        assert!(written.contains(&format!("h: hash = \"Root\" # {:#x}\n", root)));
+        assert_eq!(read_text(&written).unwrap(), bin);
+    }
+
+    #[test]
+    fn test_text_string_escapes() {
+        let tricky = "quote \" back \\ tab \t nl \n bell \u{7} nel \u{85} é 日本 😀 'single'";
+        let mut bin = Bin::new();
+        bin.sections.insert("s".to_string(), BinValue::String(tricky.to_string()));
+        bin.sections.insert("h".to_string(), BinValue::Hash { value: crate::hash::fnv1a("a\u{1}b"), name: Some("a\u{1}b".to_string()) });
+        let written = write_text(&bin).unwrap();
+        assert!(written.contains(r#""quote \" back \\ tab \t nl \n bell \x07 nel \u0085 é 日本 😀 'single'""#));
+        assert_eq!(read_text(&written).unwrap(), bin);
+
+        let escaped = read_text(r#"s: string = "\x41\u00e9\ud83d\ude00\u{1F600}" t: string = 'it\'s'"#).unwrap();
+        assert_eq!(escaped.sections.get("s"), Some(&BinValue::String("Aé😀😀".to_string())));
+        assert_eq!(escaped.sections.get("t"), Some(&BinValue::String("it's".to_string())));
+        let err = read_text(r#"s: string = "bad \q""#).unwrap_err();
+        assert_eq!((err.column, err.found.as_str()), (19, "q"));
+        assert!(read_text(r#"s: string = "\ud83d alone""#).is_err());
+    }
+
+    #[test]
+    fn test_text_special_floats() {
+        let floats = [
+            f32::INFINITY, f32::NEG_INFINITY, f32::NAN, f32::from_bits(0xffc0_0000), f32::from_bits(0x7f80_0001),
+            f32::MAX, f32::MIN_POSITIVE, 1e-45, -0.0, 1e30, 0.1,
+        ];
+        let mut bin = Bin::new();
+        for (i, v) in floats.iter().enumerate() {
+            bin.sections.insert(format!("f{}", i), BinValue::F32(*v));
+        }
+        bin.sections.insert("v".to_string(), BinValue::Vec4([f32::NAN, f32::from_bits(0xffc0_0000), 1e-3, f32::NEG_INFINITY]));
+        let mut m = [1e38; 16];
+        m[5] = f32::from_bits(0x7fc0_1234);
+        bin.sections.insert("m".to_string(), BinValue::Mtx44(m));
+
+        let written = write_text(&bin).unwrap();
+        assert!(written.contains("f3: f32 = nan(0xffc00000)\n"));
+        assert!(written.contains("v: vec4 = { NaN, nan(0xffc00000), 0.001, -inf }"));
+        let read = read_text(&written).unwrap();
+        let bits = |bin: &Bin| -> Vec<u32> {
+            bin.sections.values().flat_map(|v| match v {
+                BinValue::F32(f) => vec![f.to_bits()],
+                BinValue::Vec4(f) => f.iter().map(|f| f.to_bits()).collect(),
+                BinValue::Mtx44(f) => f.iter().map(|f| f.to_bits()).collect(),
+                _ => unreachable!(),
+            }).collect()
+        };
+        assert_eq!(bits(&read), bits(&bin));
+
+        let parsed = read_text("a: f32 = 1.5E-3 b: f32 = -Infinity c: vec2 = { 2e5, nan } d: f32 = +.5e1").unwrap();
+        assert_eq!(parsed.sections.get("a"), Some(&BinValue::F32(0.0015)));
+        assert_eq!(parsed.sections.get("b"), Some(&BinValue::F32(f32::NEG_INFINITY)));
+        assert!(matches!(parsed.sections.get("c"), Some(BinValue::Vec2([x, y])) if *x == 2e5 && y.is_nan()));
+        assert_eq!(parsed.sections.get("d"), Some(&BinValue::F32(5.0)));
+        assert_eq!(read_text("a: f32 = 1e39").unwrap_err().expected, "f32");
+        assert!(read_text("a: f32 = nan(0x3f800000)").is_err());
+    }
+
+    #[test]
+    fn test_read_text_bom_and_crlf() {
+        let unix = "#PROP_text v2\n# comment\nname: string = \"two\nlines\"\nlist: list[u8] = {\n  1\n  2\n}\n";
+        let windows = format!("\u{feff}{}", unix.replace('\n', "\r\n"));
+        assert_eq!(read_text(&windows).unwrap(), read_text(unix).unwrap());
+
+        let err = read_text("\u{feff}a: u8 = 1\r\nb: u8 = x\r\n").unwrap_err();
+        assert_eq!((err.line, err.column, err.found.as_str()), (2, 9, "x"));
+        let newer = read_text("\u{feff}#PROP_text v9\r\na: u8 = 1").unwrap_err();
+        assert_eq!((newer.line, newer.found.as_str()), (1, "v9"));
+    }
+
+    #[test]
+    fn test_read_text_spans() {
+        let text = "\u{feff}#PROP_text v2\r\nentries: map[hash,embed] = {\r\n  0x10 = Thing {\r\n    name: string = \"a\"\r\n    p: pointer = null\r\n    0x20: raw[0x80] = \"ff00\"\r\n  }\r\n}\r\n";
+        let (bin, spans) = read_text_with_spans(text, &TextReadOptions::default()).unwrap();
+        assert_eq!(bin, read_text(text).unwrap());
+        let literals: Vec<&str> = spans.iter().map(|s| &text[s.start..s.end]).collect();
+        assert_eq!(literals[1..], ["0x10", &text[text.find("Thing").unwrap()..text.rfind("  }").unwrap() + 3], "\"a\"", "null", "\"ff00\""]);
+        assert_eq!(literals[0], text[text.find('{').unwrap()..].trim_end());
+
+        let err = read_text("a: u8 = 1\nb: vec2 = { 1, oops }").unwrap_err();
+        assert_eq!(&"a: u8 = 1\nb: vec2 = { 1, oops }"[err.span.start..err.span.end], "oops");
+        assert_eq!(read_text("\u{feff}a: u8 = x").unwrap_err().column, 9);
+    }
+
+    #[test]
+    fn test_read_text_duplicate_sections() {
+        let text = "entries: map[hash,embed] = {\n  0x1 = A {}\n}\nversion: u32 = 3\nentries: map[hash,embed] = {\n  0x2 = B {}\n}\n";
+        let read = |policy| read_text_with_duplicates(text, &TextReadOptions { duplicate_sections: policy, ..Default::default() });
+        let entry = |key: u32, class: &str| BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![(
+                BinValue::Hash { value: key, name: None },
+                BinValue::Embed { name: crate::hash::fnv1a(class), name_str: Some(class.to_string()), items: vec![] },
+            )],
+        };
+
+        let (last, duplicates) = read(DuplicateSections::KeepLast).unwrap();
+        assert_eq!(last.sections.keys().collect::<Vec<_>>(), ["entries", "version"]);
+        assert_eq!(last.sections["entries"], entry(2, "B"));
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].key.as_str(), duplicates[0].line), ("entries", 5));
+        assert!(text[duplicates[0].span.start..duplicates[0].span.end].starts_with("entries") && text[..duplicates[0].span.end].ends_with('}'));
+        assert_eq!(read(DuplicateSections::KeepFirst).unwrap().0.sections["entries"], entry(1, "A"));
+
+        let opts = TextReadOptions { duplicate_sections: DuplicateSections::Error, ..Default::default() };
+        let err = read_text_with_options(text, &opts).unwrap_err();
+        assert_eq!((err.line, err.column, err.found.as_str()), (5, 1, "entries"));
+        let recovered = read_text_recover(text, &opts).unwrap();
+        assert_eq!(recovered.bin.sections["entries"], entry(1, "A"));
+        assert_eq!(recovered.bin.sections["version"], BinValue::U32(3));
+        assert_eq!(recovered.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_read_text_max_depth() {
+        let nested = |depth: usize| format!("a: embed = {}A {{}}{}\n", "A { x: embed = ".repeat(depth), " }".repeat(depth));
+        let err = read_text(&nested(300)).unwrap_err();
+        assert_eq!(err.expected, "at most 256 levels of nesting");
+        assert_eq!(err.column, 12 + 256 * 15);
+        assert!(read_text(&nested(255)).is_ok());
+
+        // Far deeper than recursion could go on a test thread's stack
+        let deep = TextReadOptions { max_depth: usize::MAX, ..Default::default() };
+        let bin = read_text_with_options(&nested(5000), &deep).unwrap();
+        let mut value = &bin.sections["a"];
+        let mut depth = 0;
+        while let BinValue::Embed { items, .. } = value {
+            let Some(field) = items.first() else { break };
+            value = &field.value;
+            depth += 1;
+        }
+        assert_eq!(depth, 5000);
+    }
+
+    #[test]
+    fn test_read_text_source_map() {
+        let text = "\
+#PROP_text v2
+entries: map[hash,embed] = {
+  \"Root\" = Record {
+    name: string = \"Ahri\"
+    spells: list[string] = {
+      \"Q\"
+      \"W\"
+    }
+    extra: option[u8] = { 5 }
+  }
+}
+";
+        let (bin, map) = read_text_with_source_map(text, &TextReadOptions::default()).unwrap();
+        let lines = |path: &str| map.get(path).map(|r| (r.start_line, r.end_line));
+        let root = format!("entries[{:#010x}]", crate::hash::fnv1a("Root"));
+        assert_eq!(lines("entries"), Some((2, 11)));
+        assert_eq!(lines(&root), Some((3, 10)));
+        assert_eq!(lines(&format!("{}.name", root)), Some((4, 4)));
+        assert_eq!(lines(&format!("{}.spells", root)), Some((5, 8)));
+        assert_eq!(lines(&format!("{}.spells[1]", root)), Some((7, 7)));
+        assert_eq!(lines(&format!("{}.extra[0]", root)), Some((9, 9)));
+        assert_eq!(map.len(), 8);
+
+        let spells: crate::edit::ValuePath = format!("{}.spells[1]", root).parse().unwrap();
+        assert_eq!(spells.get(&bin), Some(&BinValue::String("W".to_string())));
+        let range = map.get(&spells.to_string()).unwrap();
+        assert_eq!(&text[range.span.start..range.span.end], "\"W\"");
+    }
+
+    #[test]
+    fn test_read_text_ritobin_compat() {
+        // Laid out the way the C++ ritobin writes, with its older type names
+        let text = "\
+#PROP_text
+type: string = \"PROP\"
+version: u32 = 3
+linked: list[string] = {
+    \"DATA/Characters/Ahri/Ahri.bin\"
+}
+entries: map[hash,embed] = {
+    0x0a1b2c3d = 0xdeadbeef {
+        0x1234abcd: path = 0x0123456789abcdef
+        mPtr: struct = SomeClass {
+            enabled: bitbool = true
+        }
+        color: rgba = { 255 128 0 255 }
+        pos: vec3 = { 1 2.5 -3 }
+        mat: mtx44 = {
+            1, 0, 0, 0
+            0, 1, 0, 0
+            0, 0, 1, 0
+            0, 0, 0, 1
+        }
+        empty: pointer = null
+    }
+}
+";
+        assert!(read_text(text).is_err());
+        let opts = TextReadOptions { ritobin_compat: true, ..Default::default() };
+        let bin = read_text_with_options(text, &opts).unwrap();
+        let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") else { panic!("Expected map") };
+        assert_eq!(items[0].0, BinValue::Hash { value: 0x0a1b2c3d, name: None });
+        let BinValue::Embed { name: 0xdeadbeef, items: fields, .. } = &items[0].1 else { panic!("Expected embed") };
+        assert_eq!(fields[0].value, BinValue::File { value: 0x0123456789abcdef, name: None });
+        assert!(matches!(&fields[1].value, BinValue::Pointer { items, .. } if items[0].value == BinValue::Flag(true)));
+        assert_eq!(fields[2].value, BinValue::Rgba([255, 128, 0, 255]));
+        assert_eq!(fields[3].value, BinValue::Vec3([1.0, 2.5, -3.0]));
+        assert_eq!(read_text_with_options(&write_text(&bin).unwrap(), &opts).unwrap(), bin);
+    }
+
+    #[test]
+    fn test_read_text_type_aliases() {
+        let text = "a: Bool = true\nb: boolean = false\nc: UINT32 = 7\nd: float = 1.5\ne: List[Int8] = { -1 }\nf: map[String,uint64] = { \"x\" = 2 }\n";
+        let bin = read_text(text).unwrap();
+        assert_eq!(bin.sections.get("a"), Some(&BinValue::Bool(true)));
+        assert_eq!(bin.sections.get("b"), Some(&BinValue::Bool(false)));
+        assert_eq!(bin.sections.get("c"), Some(&BinValue::U32(7)));
+        assert_eq!(bin.sections.get("d"), Some(&BinValue::F32(1.5)));
+        assert_eq!(bin.sections.get("e"), Some(&BinValue::List { value_type: BinType::I8, items: vec![BinValue::I8(-1)] }));
+        assert!(write_text(&bin).unwrap().contains("f: map[string,u64] = {"));
+        assert_eq!(read_text("a: integer = 1").unwrap_err().expected, "a type name");
+    }
+
+    #[test]
+    fn test_read_text_quoted_section_keys() {
+        let text = "\"0x1234abcd\": embed = Thing { a: u8 = 1 }\n'my section': u8 = 2\n0xdeadbeef: u8 = 3\n";
+        let bin = read_text(text).unwrap();
+        let keys: Vec<&str> = bin.sections.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["0x1234abcd", "my section", "0xdeadbeef"]);
+        let written = write_text(&bin).unwrap();
+        assert!(written.contains("\n\"my section\": u8 = 2\n"));
+        assert!(written.contains("\n0x1234abcd: embed = Thing {") && written.contains("\n0xdeadbeef: u8 = 3\n"));
+        assert_eq!(read_text(&written).unwrap(), bin);
+
+        let recovered = read_text_recover("\"a b\": u8 = x\n\"c d\": u8 = 4\n", &TextReadOptions::default()).unwrap();
+        assert_eq!(recovered.bin.sections.get("c d"), Some(&BinValue::U8(4)));
+    }
+
+    #[test]
+    fn test_read_header() {
+        let text = "\u{feff}#PROP_text v2\r\ntype: string = \"PROP\"\r\nversion: u32 = 3\r\nlinked: list[string] = {\r\n  \"a.bin\"\r\n}\r\nentries: map[hash,embed] = {\r\n  0x1 = Broken { x: u8 = 300 }\r\n}\r\n";
+        let header = read_header(text).unwrap();
+        let keys: Vec<&str> = header.sections.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["type", "version", "linked"]);
+        assert!(read_text(text).is_err());
+        assert_eq!(read_header("#PROP_text v9\nversion: u32 = 3").unwrap_err().found, "v9");
+        assert_eq!(read_header("version: u32 = x").unwrap_err().found, "x");
+    }
+
+    #[test]
+    fn test_read_text_inferred_types() {
+        let text = r#"
+version = 3
+entries = {
+  "Root" = Record {
+    name = "Ahri"
+    hp = 590.5
+    level = -2
+    big = 3000000000
+    spell = 0x1234abcd
+    enabled = true
+    inner = Child { x = 1 }
+  }
+}
+"#;
+        assert!(read_text(text).is_err());
+
+        let opts = TextReadOptions { infer_types: true, ..Default::default() };
+        let bin = read_text_with_options(text, &opts).unwrap();
+        assert_eq!(bin.sections.get("version"), Some(&BinValue::U32(3)));
+        let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") else { panic!("Expected map") };
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!("Expected embed") };
+        let values: Vec<&BinValue> = fields.iter().map(|f| &f.value).collect();
+        assert_eq!(values[0], &BinValue::String("Ahri".to_string()));
+        assert_eq!(values[1], &BinValue::F32(590.5));
+        assert_eq!(values[2], &BinValue::I32(-2));
+        assert_eq!(values[3], &BinValue::U32(3000000000));
+        assert_eq!(values[4], &BinValue::Hash { value: 0x1234abcd, name: None });
+        assert_eq!(values[5], &BinValue::Bool(true));
+        assert!(matches!(values[6], BinValue::Embed { .. }));
+
+        // The schema wins over the literal shape
+        let mut schema = TextSchema::new();
+        schema.insert("level", BinType::I8, None);
+        schema.insert("hp", BinType::F32, None);
+        let opts = TextReadOptions { infer_types: true, schema: Some(&schema), ..Default::default() };
+        let bin = read_text_with_options(text, &opts).unwrap();
+        let Some(BinValue::Map { items, .. }) = bin.sections.get("entries") else { panic!("Expected map") };
+        let BinValue::Embed { items: fields, .. } = &items[0].1 else { panic!("Expected embed") };
+        assert_eq!(fields[2].value, BinValue::I8(-2));
+    }
 }
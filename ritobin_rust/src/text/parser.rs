@@ -0,0 +1,814 @@
+//! The text format parser: recursive descent straight over the input bytes,
+//! with nested values kept on an explicit stack.
+//!
+//! Names and numbers are read as slices of the input, so the only
+//! allocations are the strings and names that end up in the bin. Every
+//! value's byte span can be recorded on the way, in pre-order.
+
+use super::{get_bin_type_name, DuplicateSections, Span, TextError, TextReadOptions, TypeInfo};
+use crate::model::{Bin, BinType, BinValue, Field};
+use indexmap::IndexMap;
+use std::num::ParseIntError;
+
+type Result<T> = std::result::Result<T, Failure>;
+
+const ESCAPE: &str = "an escape such as \\n, \\xNN or \\uNNNN";
+
+/// What the parser was looking for where it failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expected {
+    Char(char),
+    What(&'static str),
+    /// Nesting past [`TextReadOptions::max_depth`]
+    TooDeep(usize),
+}
+
+/// Where parsing stopped, as a byte offset, and what it wanted there
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Failure {
+    pos: usize,
+    expected: Expected,
+}
+
+impl Failure {
+    fn new(pos: usize, what: &'static str) -> Self {
+        Failure { pos, expected: Expected::What(what) }
+    }
+
+    fn char(pos: usize, c: char) -> Self {
+        Failure { pos, expected: Expected::Char(c) }
+    }
+
+    pub(super) fn into_error(self, data: &str) -> TextError {
+        let expected = match self.expected {
+            Expected::Char(c) => format!("'{}'", c),
+            Expected::What(what) => what.to_string(),
+            Expected::TooDeep(max_depth) => format!("at most {} levels of nesting", max_depth),
+        };
+        TextError::at(data, self.pos, expected)
+    }
+}
+
+/// Characters of names and literals
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '+' | '-' | '.')
+}
+
+/// `0x` hex or plain decimal digits
+fn parse_hex<T>(s: &str, from_str_radix: fn(&str, u32) -> std::result::Result<T, ParseIntError>) -> Option<T> {
+    let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => (hex, 16),
+        None => (s, 10),
+    };
+    let valid = !digits.is_empty() && digits.bytes().all(|b| if radix == 16 { b.is_ascii_hexdigit() } else { b.is_ascii_digit() });
+    valid.then(|| from_str_radix(digits, radix).ok()).flatten()
+}
+
+/// The hash of a field or class name, and the name unless it's written as `0x` hex
+fn name_hash(name: &str) -> (u32, Option<String>) {
+    match name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+        Some(hex) => (u32::from_str_radix(hex, 16).unwrap_or(0), None),
+        None => (crate::hash::fnv1a(name), Some(name.to_string())),
+    }
+}
+
+/// Other spellings of type names used by tutorials and other tools, in lowercase
+fn type_alias(name: &str) -> Option<BinType> {
+    match name {
+        "boolean" => Some(BinType::Bool),
+        "int8" | "sbyte" => Some(BinType::I8),
+        "uint8" | "byte" => Some(BinType::U8),
+        "int16" | "short" => Some(BinType::I16),
+        "uint16" | "ushort" => Some(BinType::U16),
+        "int32" | "int" => Some(BinType::I32),
+        "uint32" | "uint" => Some(BinType::U32),
+        "int64" | "long" => Some(BinType::I64),
+        "uint64" | "ulong" => Some(BinType::U64),
+        "float" | "float32" => Some(BinType::F32),
+        "vector2" => Some(BinType::Vec2),
+        "vector3" => Some(BinType::Vec3),
+        "vector4" => Some(BinType::Vec4),
+        "matrix44" => Some(BinType::Mtx44),
+        "color" => Some(BinType::Rgba),
+        "str" => Some(BinType::String),
+        _ => None,
+    }
+}
+
+/// Names older ritobin builds and other League tools give some types,
+/// accepted in [`TextReadOptions::ritobin_compat`] mode
+fn ritobin_type_alias(name: &str) -> Option<BinType> {
+    match name {
+        "path" => Some(BinType::File),
+        "struct" | "ptr" => Some(BinType::Pointer),
+        "bitbool" => Some(BinType::Flag),
+        _ => None,
+    }
+}
+
+/// A type name in any case, or one of its common aliases, or with `compat`
+/// one of the names older ritobin builds use
+pub(crate) fn type_from_name(s: &str, compat: bool) -> Option<BinType> {
+    s.parse().ok().or_else(|| {
+        let name = s.to_ascii_lowercase();
+        name.parse().ok()
+            .or_else(|| type_alias(&name))
+            .or_else(|| compat.then(|| ritobin_type_alias(&name)).flatten())
+    })
+}
+
+/// Types of the standard top-level sections
+fn section_type(key: &str) -> Option<(BinType, Option<TypeInfo>)> {
+    match key {
+        "type" => Some((BinType::String, None)),
+        "version" => Some((BinType::U32, None)),
+        "ptch_header" => Some((BinType::U64, None)),
+        "linked" => Some((BinType::List, Some((BinType::String, None)))),
+        "entries" | "patches" => Some((BinType::Map, Some((BinType::Hash, Some(BinType::Embed))))),
+        _ => None,
+    }
+}
+
+/// A container, pointer or embed whose block [`Parser::value`] is reading
+enum Frame {
+    List { list2: bool, value_type: BinType, items: Vec<BinValue> },
+    Option { value_type: BinType, item: Option<Box<BinValue>> },
+    /// `key` holds a key whose value is being read
+    Map { key_type: BinType, value_type: BinType, items: Vec<(BinValue, BinValue)>, key: Option<BinValue> },
+    /// `field` holds the name of the field whose value is being read
+    Struct { pointer: bool, name: u32, name_str: Option<String>, items: Vec<Field>, field: Option<(u32, Option<String>)> },
+}
+
+impl Frame {
+    /// Add a finished item, map key or field value
+    fn push(&mut self, value: BinValue) {
+        match self {
+            Frame::List { items, .. } => items.push(value),
+            Frame::Option { item, .. } => *item = Some(Box::new(value)),
+            Frame::Map { items, key, .. } => match key.take() {
+                Some(key) => items.push((key, value)),
+                None => *key = Some(value),
+            },
+            Frame::Struct { items, field, .. } => {
+                let (key, key_str) = field.take().unwrap_or_default();
+                items.push(Field { key, key_str, value });
+            },
+        }
+    }
+
+    /// The value read, once its block has ended
+    fn finish(self) -> BinValue {
+        match self {
+            Frame::List { list2: false, value_type, items } => BinValue::List { value_type, items },
+            Frame::List { list2: true, value_type, items } => BinValue::List2 { value_type, items },
+            Frame::Option { value_type, item } => BinValue::Option { value_type, item },
+            Frame::Map { key_type, value_type, items, .. } => BinValue::Map { key_type, value_type, items },
+            Frame::Struct { pointer: true, name, name_str, items, .. } => BinValue::Pointer { name, name_str, items },
+            Frame::Struct { pointer: false, name, name_str, items, .. } => BinValue::Embed { name, name_str, items },
+        }
+    }
+}
+
+/// How a value started: read whole, or as a frame whose items follow, with
+/// the index of its span
+enum Begun {
+    Value(BinValue),
+    Frame(Frame, Option<usize>),
+}
+
+pub(super) struct Parser<'a, 'o> {
+    data: &'a str,
+    /// Byte offset of the next character
+    pos: usize,
+    opts: &'o TextReadOptions<'o>,
+    /// Spans of the values of the section being read, when recording
+    spans: Option<Vec<Span>>,
+    /// Spans of each section read, kept like the sections of the bin
+    pub(super) section_spans: IndexMap<String, Vec<Span>>,
+    /// Stop at the first section that isn't `type`, `version` or `linked`
+    pub(super) header_only: bool,
+    /// Name and span of each section whose name was already used
+    pub(super) duplicates: Vec<(String, Span)>,
+}
+
+impl<'a, 'o> Parser<'a, 'o> {
+    /// A parser starting at byte `pos` of `data`, recording spans if `spans` is set
+    pub(super) fn new(data: &'a str, pos: usize, opts: &'o TextReadOptions<'o>, spans: bool) -> Self {
+        Parser { data, pos, opts, spans: spans.then(Vec::new), section_spans: IndexMap::new(), header_only: false, duplicates: Vec::new() }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.data[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.as_bytes().get(self.pos).copied()
+    }
+
+    fn fail(&self, what: &'static str) -> Failure {
+        Failure::new(self.pos, what)
+    }
+
+    /// Move past whitespace and `#` comments
+    fn skip_ws(&mut self) {
+        let bytes = self.data.as_bytes();
+        while let Some(&b) = bytes.get(self.pos) {
+            match b {
+                b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c => self.pos += 1,
+                b'#' => self.pos = bytes[self.pos..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| self.pos + i),
+                0x80.. => match self.rest().chars().next() {
+                    Some(c) if c.is_whitespace() => self.pos += c.len_utf8(),
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+    }
+
+    /// The run of characters from here matching `f`, possibly empty
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        let bytes = self.data.as_bytes();
+        while let Some(&b) = bytes.get(self.pos) {
+            let c = if b < 0x80 { b as char } else { self.rest().chars().next().unwrap_or_default() };
+            if !f(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.data[start..self.pos]
+    }
+
+    /// A name or literal after any whitespace, if there is one
+    fn word(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        Some(self.take_while(is_word_char)).filter(|w| !w.is_empty())
+    }
+
+    /// Whether `c` is next after any whitespace, consuming it if so
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_ws();
+        let found = self.peek() == Some(c);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if self.eat(c) { Ok(()) } else { Err(Failure::char(self.pos, c as char)) }
+    }
+
+    /// A word read by `parse`, failing at its start as `what`
+    fn token<T>(&mut self, what: &'static str, parse: impl FnOnce(&'a str) -> Option<T>) -> Result<T> {
+        self.skip_ws();
+        let start = self.pos;
+        self.word().and_then(parse).ok_or(Failure::new(start, what))
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T> {
+        self.token("a number", |s| s.parse().ok())
+    }
+
+    /// An f32: a decimal or scientific literal, `inf`, `-inf`, `NaN`, or
+    /// `nan(0x7fc00001)` giving the bits of a NaN
+    ///
+    /// A literal too large for f32 is an error rather than infinity.
+    fn f32(&mut self) -> Result<f32> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.rest().get(..6).is_some_and(|p| p.eq_ignore_ascii_case("nan(0x")) {
+            self.pos += 6;
+            let bits = self.hex_digits(1, 8);
+            let closed = self.peek() == Some(b')');
+            self.pos += closed as usize;
+            return bits.map(f32::from_bits).filter(|v| v.is_nan() && closed).ok_or(Failure::new(start, "a number"));
+        }
+        self.token("a number", |s| {
+            let v = s.parse::<f32>().ok()?;
+            // `inf` and `NaN` are spelled without digits
+            (v.is_finite() || !s.contains(|c: char| c.is_ascii_digit())).then_some(v)
+        })
+    }
+
+    /// `min` to `max` hex digits right here, as a number
+    fn hex_digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        let len = self.data.as_bytes()[self.pos..].iter().take(max).take_while(|b| b.is_ascii_hexdigit()).count();
+        let digits = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        if len < min {
+            return None;
+        }
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    /// `{ a, b, ... }` holding exactly `N` items, with commas between them
+    /// unless `commas` is false, in which case they're optional
+    fn array<T: Copy + Default, const N: usize>(&mut self, commas: bool, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<[T; N]> {
+        self.expect(b'{')?;
+        let mut out = [T::default(); N];
+        for (i, v) in out.iter_mut().enumerate() {
+            if i > 0 && !self.eat(b',') && commas {
+                return Err(Failure::char(self.pos, ','));
+            }
+            *v = item(self)?;
+        }
+        self.expect(b'}')?;
+        Ok(out)
+    }
+
+    /// A string in double or single quotes, with backslash escapes; CRLF
+    /// inside the quotes reads as LF
+    fn string(&mut self) -> Result<String> {
+        self.skip_ws();
+        let quote = match self.peek() {
+            Some(q @ (b'"' | b'\'')) => q,
+            _ => return Err(self.fail("a string")),
+        };
+        self.pos += 1;
+        let bytes = self.data.as_bytes();
+        let mut out = String::new();
+        loop {
+            // Everything else is copied a run at a time; these are all ASCII,
+            // so the runs end on character boundaries
+            let run = bytes[self.pos..].iter().position(|&b| b == quote || b == b'\\' || b == b'\r');
+            let Some(len) = run else { return Err(Failure::char(bytes.len(), quote as char)) };
+            out.push_str(&self.data[self.pos..self.pos + len]);
+            self.pos += len + 1;
+            match bytes[self.pos - 1] {
+                b'\\' => out.push(self.escape()?),
+                b'\r' if self.peek() == Some(b'\n') => {},
+                b'\r' => out.push('\r'),
+                _ => return Ok(out),
+            }
+        }
+    }
+
+    /// The character a backslash escape stands for: `\n`, `\r`, `\t`, `\\`,
+    /// `\"`, `\'`, `\xNN`, `\uNNNN` (UTF-16, with surrogate pairs) or `\u{N...}`
+    fn escape(&mut self) -> Result<char> {
+        let start = self.pos;
+        let bad = Failure::new(start, ESCAPE);
+        let c = self.rest().chars().next().ok_or(bad)?;
+        self.pos += c.len_utf8();
+        match c {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            '\\' | '"' | '\'' => Ok(c),
+            'x' => self.hex_digits(2, 2).and_then(char::from_u32).ok_or(bad),
+            'u' if self.peek() == Some(b'{') => {
+                self.pos += 1;
+                let code = self.hex_digits(1, 6);
+                let closed = self.peek() == Some(b'}');
+                self.pos += closed as usize;
+                code.filter(|_| closed).and_then(char::from_u32).ok_or(bad)
+            },
+            'u' => {
+                let high = self.hex_digits(4, 4).ok_or(bad)?;
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    let low_start = self.pos;
+                    let low = match self.rest().starts_with("\\u") {
+                        true => {
+                            self.pos += 2;
+                            self.hex_digits(4, 4)
+                        },
+                        false => None,
+                    };
+                    match low {
+                        Some(low) if (0xDC00..0xE000).contains(&low) => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                        _ => return Err(Failure::new(low_start, "the low half of a surrogate pair")),
+                    }
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or(Failure::new(start, "a Unicode scalar value"))
+            },
+            _ => Err(bad),
+        }
+    }
+
+    /// A type name in any case, or one of its common aliases
+    fn type_name(&mut self) -> Result<BinType> {
+        let compat = self.opts.ritobin_compat;
+        self.token("a type name", |s| type_from_name(s, compat))
+    }
+
+    /// A type annotation: `type`, `list[type]` or `map[key,value]`
+    fn type_annotation(&mut self) -> Result<(BinType, Option<TypeInfo>)> {
+        let bin_type = self.type_name()?;
+        if !bin_type.is_container() {
+            return Ok((bin_type, None));
+        }
+        self.expect(b'[')?;
+        let inner = self.type_name()?;
+        let value = if self.eat(b',') { Some(self.type_name()?) } else { None };
+        self.expect(b']')?;
+        Ok((bin_type, Some((inner, value))))
+    }
+
+    /// `: type =` or, when inference is enabled, a bare `=`
+    ///
+    /// Returns the declared or inferred type of the value that follows. `known`
+    /// is the type from the schema (or the standard section table) and takes
+    /// priority over guessing from the literal.
+    fn assignment(&mut self, known: Option<(BinType, Option<TypeInfo>)>) -> Result<(BinType, Option<TypeInfo>)> {
+        let annotation = if self.eat(b':') { Some(self.type_annotation()?) } else { None };
+        if annotation.is_none() && !self.opts.infer_types {
+            return Err(self.fail("': type'"));
+        }
+        self.expect(b'=')?;
+        match annotation.or(known).or_else(|| self.infer_literal_type()) {
+            Some(t) => Ok(t),
+            None => Err(self.fail("a value whose type can be inferred")),
+        }
+    }
+
+    /// Guess the type of the literal that's next from its shape
+    ///
+    /// Quoted text is a string, `0x...` a hash, `true`/`false` a bool, integers
+    /// are i32 (widening to u32/i64 when they don't fit), other numbers are f32,
+    /// and `Name { ... }` is an embed.
+    fn infer_literal_type(&mut self) -> Option<(BinType, Option<TypeInfo>)> {
+        self.skip_ws();
+        let start = self.pos;
+        let rest = self.rest();
+        if rest.starts_with(['"', '\'']) {
+            return Some((BinType::String, None));
+        }
+        if rest.starts_with("0x") || rest.starts_with("0X") {
+            return Some((BinType::Hash, None));
+        }
+        let token = self.word()?;
+        let block = self.eat(b'{');
+        self.pos = start;
+        let bin_type = match token {
+            "true" | "false" => BinType::Bool,
+            "null" => BinType::Pointer,
+            _ if token.parse::<i32>().is_ok() => BinType::I32,
+            _ if token.parse::<u32>().is_ok() => BinType::U32,
+            _ if token.parse::<i64>().is_ok() => BinType::I64,
+            _ if token.parse::<f32>().is_ok() => BinType::F32,
+            _ if block => BinType::Embed,
+            _ => return None,
+        };
+        Some((bin_type, None))
+    }
+
+    /// Whether the `{ ... }` block being read ends next, consuming the `}` if so
+    ///
+    /// Items are separated by commas and/or newlines. An item that fails is an
+    /// error even if the block could have ended there, so the error points
+    /// into the item.
+    fn block_end(&mut self) -> Result<bool> {
+        self.skip_ws();
+        match self.peek() {
+            Some(b'}') => {
+                self.pos += 1;
+                Ok(true)
+            },
+            None => Err(Failure::char(self.pos, '}')),
+            Some(_) => Ok(false),
+        }
+    }
+
+    /// Consume `: raw[` if it's next
+    fn raw_header(&mut self) -> bool {
+        let start = self.pos;
+        if self.eat(b':') && self.word() == Some("raw") && self.eat(b'[') {
+            return true;
+        }
+        self.pos = start;
+        false
+    }
+
+    /// The rest of a preserved unknown value: `0xNN] = "hex"` or `0xNN,absorbed] = "hex"`
+    fn raw(&mut self) -> Result<BinValue> {
+        let type_byte = self.token("a type byte", |s| parse_hex(s, u8::from_str_radix))?;
+        let absorbed_fields = if self.eat(b',') { self.token("a field count", |s| parse_hex(s, u16::from_str_radix))? } else { 0 };
+        self.expect(b']')?;
+        self.expect(b'=')?;
+        self.spanned(|p| {
+            let start = p.pos;
+            let bytes = crate::model::hex_decode(&p.string()?).map_err(|_| Failure::new(start, "hex bytes"))?;
+            Ok(BinValue::Raw { type_byte, bytes, absorbed_fields })
+        })
+    }
+
+    /// Start recording the span of the value that starts here, returning its index
+    fn open_span(&mut self) -> Option<usize> {
+        let start = self.pos;
+        self.spans.as_mut().map(|spans| {
+            spans.push(Span { start, end: start });
+            spans.len() - 1
+        })
+    }
+
+    /// End the span opened as `index` here
+    fn close_span(&mut self, index: Option<usize>) {
+        if let (Some(spans), Some(index)) = (&mut self.spans, index) {
+            spans[index].end = self.pos;
+        }
+    }
+
+    /// Run `parse` on the value that's next, recording its span
+    fn spanned(&mut self, parse: impl FnOnce(&mut Self) -> Result<BinValue>) -> Result<BinValue> {
+        self.skip_ws();
+        let index = self.open_span();
+        let value = parse(self)?;
+        self.close_span(index);
+        Ok(value)
+    }
+
+    /// A value of `bin_type`; a value that fails where it starts is reported
+    /// as expecting the type
+    ///
+    /// Containers, pointers and embeds are read with an explicit stack of
+    /// [`Frame`]s rather than by recursion, so no input can overflow the call
+    /// stack; nesting deeper than [`TextReadOptions::max_depth`] is an error.
+    fn value(&mut self, mut bin_type: BinType, mut type_info: Option<TypeInfo>) -> Result<BinValue> {
+        // Each open frame, with the index of its span
+        let mut stack: Vec<(Frame, Option<usize>)> = Vec::new();
+        loop {
+            let mut done = match self.begin_value(bin_type, type_info, stack.len())? {
+                Begun::Value(value) => Some(value),
+                Begun::Frame(frame, span) => {
+                    stack.push((frame, span));
+                    None
+                },
+            };
+            // Hand finished values to their frames until one wants another item
+            loop {
+                if let Some(value) = done.take() {
+                    match stack.last_mut() {
+                        Some((frame, _)) => frame.push(value),
+                        None => return Ok(value),
+                    }
+                }
+                let Some((frame, _)) = stack.last_mut() else { unreachable!("a frame is open until the value is done") };
+                match self.next_item(frame)? {
+                    Some(next) => {
+                        (bin_type, type_info) = next;
+                        break;
+                    },
+                    None => {
+                        let (frame, span) = stack.pop().unwrap();
+                        self.close_span(span);
+                        done = Some(frame.finish());
+                    },
+                }
+            }
+        }
+    }
+
+    /// Read a plain value whole, or open the block of a nested one; `depth`
+    /// is the number of frames already open
+    fn begin_value(&mut self, bin_type: BinType, type_info: Option<TypeInfo>, depth: usize) -> Result<Begun> {
+        if bin_type.is_container() && type_info.is_none_or(|(_, value)| bin_type == BinType::Map && value.is_none()) {
+            self.skip_ws();
+            return Err(self.fail("a container type such as list[u32]"));
+        }
+        self.skip_ws();
+        let start = self.pos;
+        let nested = bin_type.is_container() || matches!(bin_type, BinType::Pointer | BinType::Embed);
+        if nested && depth >= self.opts.max_depth {
+            return Err(Failure { pos: start, expected: Expected::TooDeep(self.opts.max_depth) });
+        }
+        let span = self.open_span();
+        let begun = self.begin_value_of_type(bin_type, type_info).map_err(|mut e| {
+            if e.pos == start {
+                e.expected = Expected::What(get_bin_type_name(bin_type));
+            }
+            e
+        })?;
+        Ok(match begun {
+            Begun::Value(value) => {
+                self.close_span(span);
+                Begun::Value(value)
+            },
+            Begun::Frame(frame, _) => Begun::Frame(frame, span),
+        })
+    }
+
+    fn begin_value_of_type(&mut self, bin_type: BinType, type_info: Option<TypeInfo>) -> Result<Begun> {
+        let hash = |p: &mut Self| -> Result<(u32, Option<String>)> {
+            if matches!(p.peek(), Some(b'"' | b'\'')) {
+                let name = p.string()?;
+                Ok((crate::hash::fnv1a(&name), Some(name)))
+            } else {
+                Ok((p.token("a number", |s| parse_hex(s, u32::from_str_radix))?, None))
+            }
+        };
+        let (inner, value_type) = type_info.unwrap_or((bin_type, None));
+        // The C++ reader takes commas between components as optional, like mtx44's
+        let commas = !self.opts.ritobin_compat;
+        let frame = match bin_type {
+            BinType::List | BinType::List2 => Frame::List { list2: bin_type == BinType::List2, value_type: inner, items: Vec::new() },
+            BinType::Option => Frame::Option { value_type: inner, item: None },
+            BinType::Map => Frame::Map { key_type: inner, value_type: value_type.unwrap_or(inner), items: Vec::new(), key: None },
+            BinType::Pointer | BinType::Embed => {
+                let pointer = bin_type == BinType::Pointer;
+                let name = self.word().ok_or(self.fail("a class name"))?;
+                if pointer && name == "null" {
+                    return Ok(Begun::Value(BinValue::Pointer { name: 0, name_str: None, items: vec![] }));
+                }
+                let (name, name_str) = name_hash(name);
+                Frame::Struct { pointer, name, name_str, items: Vec::new(), field: None }
+            },
+            _ => {
+                return Ok(Begun::Value(match bin_type {
+                    BinType::None => self.token("null", |s| (s == "null").then_some(BinValue::None))?,
+                    BinType::Bool => BinValue::Bool(self.bool()?),
+                    BinType::Flag => BinValue::Flag(self.bool()?),
+                    BinType::I8 => BinValue::I8(self.number()?),
+                    BinType::U8 => BinValue::U8(self.number()?),
+                    BinType::I16 => BinValue::I16(self.number()?),
+                    BinType::U16 => BinValue::U16(self.number()?),
+                    BinType::I32 => BinValue::I32(self.number()?),
+                    BinType::U32 => BinValue::U32(self.token("a number", |s| parse_hex(s, u32::from_str_radix))?),
+                    BinType::I64 => BinValue::I64(self.number()?),
+                    BinType::U64 => BinValue::U64(self.token("a number", |s| parse_hex(s, u64::from_str_radix))?),
+                    BinType::F32 => BinValue::F32(self.f32()?),
+                    BinType::Vec2 => BinValue::Vec2(self.array(commas, Self::f32)?),
+                    BinType::Vec3 => BinValue::Vec3(self.array(commas, Self::f32)?),
+                    BinType::Vec4 => BinValue::Vec4(self.array(commas, Self::f32)?),
+                    BinType::Mtx44 => BinValue::Mtx44(self.array(false, Self::f32)?),
+                    BinType::Rgba => BinValue::Rgba(self.array(commas, Self::number)?),
+                    BinType::String => BinValue::String(self.string()?),
+                    BinType::Hash => {
+                        let (value, name) = hash(self)?;
+                        BinValue::Hash { value, name }
+                    },
+                    BinType::Link => {
+                        let (value, name) = hash(self)?;
+                        BinValue::Link { value, name }
+                    },
+                    BinType::File => {
+                        if matches!(self.peek(), Some(b'"' | b'\'')) {
+                            let name = self.string()?;
+                            BinValue::File { value: crate::hash::Xxh64::new(&name).0, name: Some(name) }
+                        } else {
+                            BinValue::File { value: self.token("a number", |s| parse_hex(s, u64::from_str_radix))?, name: None }
+                        }
+                    },
+                    _ => unreachable!("nested types open a frame"),
+                }));
+            },
+        };
+        self.expect(b'{')?;
+        Ok(Begun::Frame(frame, None))
+    }
+
+    /// Move on to the next item of `frame`: returns the type of the value to
+    /// read next, or `None` once the frame's block has ended
+    fn next_item(&mut self, frame: &mut Frame) -> Result<Option<(BinType, Option<TypeInfo>)>> {
+        match frame {
+            Frame::List { value_type, items, .. } => {
+                if !items.is_empty() {
+                    self.eat(b',');
+                }
+                Ok((!self.block_end()?).then_some((*value_type, None)))
+            },
+            Frame::Option { value_type, item } => {
+                if item.is_some() {
+                    self.expect(b'}')?;
+                    return Ok(None);
+                }
+                Ok((!self.eat(b'}')).then_some((*value_type, None)))
+            },
+            Frame::Map { key_type, value_type, items, key } => {
+                if key.is_some() {
+                    self.expect(b'=')?;
+                    return Ok(Some((*value_type, None)));
+                }
+                if !items.is_empty() {
+                    self.eat(b',');
+                }
+                Ok((!self.block_end()?).then_some((*key_type, None)))
+            },
+            // A field: `key: type = value`
+            Frame::Struct { items, field, .. } => loop {
+                if !items.is_empty() {
+                    self.eat(b',');
+                }
+                if self.block_end()? {
+                    return Ok(None);
+                }
+                let name = self.word().ok_or(self.fail("a field name"))?;
+                let (key, key_str) = name_hash(name);
+                if self.raw_header() {
+                    items.push(Field { key, key_str, value: self.raw()? });
+                    continue;
+                }
+                let known = self.opts.schema.and_then(|schema| schema.get(key));
+                let next = self.assignment(known)?;
+                *field = Some((key, key_str));
+                return Ok(Some(next));
+            },
+        }
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        self.token("a bool", |s| match s {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        })
+    }
+
+    /// A section: `key: type = value`, where the key may also be quoted
+    fn section(&mut self) -> Result<(String, BinValue)> {
+        self.skip_ws();
+        let key = if matches!(self.peek(), Some(b'"' | b'\'')) {
+            self.string()?
+        } else {
+            let key = self.take_while(|c| c.is_alphanumeric() || c == '_');
+            if key.is_empty() {
+                return Err(self.fail("a section name"));
+            }
+            key.to_string()
+        };
+        let (bin_type, type_info) = self.assignment(section_type(&key))?;
+        let value = self.value(bin_type, type_info)?;
+        Ok((key, value))
+    }
+
+    /// Whether the section that's next is one of the header sections
+    fn at_header_section(&mut self) -> bool {
+        let start = self.pos;
+        let key = self.take_while(|c| c.is_alphanumeric() || c == '_');
+        self.pos = start;
+        matches!(key, "type" | "version" | "linked")
+    }
+
+    /// Parse sections to the end of the input
+    ///
+    /// With `skipped`, a section that fails is recorded there and parsing
+    /// resumes at the next line that looks like the start of a section.
+    pub(super) fn bin(&mut self, mut skipped: Option<&mut Vec<Failure>>) -> Result<Bin> {
+        let mut bin = Bin::new();
+        loop {
+            self.skip_ws();
+            if self.pos == self.data.len() || (self.header_only && !self.at_header_section()) {
+                return Ok(bin);
+            }
+            let start = self.pos;
+            let (key, value) = match (self.section(), skipped.as_deref_mut()) {
+                (Ok(section), _) => section,
+                (Err(e), Some(skipped)) => {
+                    self.pos = next_section(self.data, start, e.pos);
+                    if let Some(spans) = &mut self.spans {
+                        spans.clear();
+                    }
+                    skipped.push(e);
+                    continue;
+                },
+                (Err(e), None) => return Err(e),
+            };
+            let spans = self.spans.as_mut().map(std::mem::take);
+            if bin.sections.contains_key(&key) {
+                self.duplicates.push((key.clone(), Span { start, end: self.pos }));
+                match self.opts.duplicate_sections {
+                    DuplicateSections::KeepLast => {},
+                    DuplicateSections::KeepFirst => continue,
+                    DuplicateSections::Error => {
+                        let e = Failure::new(start, "a section name not used before");
+                        match skipped.as_deref_mut() {
+                            Some(skipped) => {
+                                skipped.push(e);
+                                continue;
+                            },
+                            None => return Err(e),
+                        }
+                    },
+                }
+            }
+            if let Some(spans) = spans {
+                self.section_spans.insert(key.clone(), spans);
+            }
+            bin.sections.insert(key, value);
+        }
+    }
+}
+
+/// Where parsing can resume after a section starting at byte `start` failed
+/// at `failed`: the first line from there that starts with `name:` or
+/// `name =` unindented, with the name plain or quoted, or the end of the input
+fn next_section(data: &str, start: usize, failed: usize) -> usize {
+    let looks_like_section = |line: &str| {
+        let name = match line.chars().next() {
+            Some(quote @ ('"' | '\'')) => line[1..].find(quote).map_or("", |i| &line[i + 2..]),
+            _ => line.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_'),
+        };
+        name.len() < line.len() && matches!(name.trim_start_matches([' ', '\t']).chars().next(), Some(':' | '='))
+    };
+    let next_line = |pos: usize| data[pos..].find('\n').map_or(data.len(), |i| pos + i + 1);
+    // The failing line itself only counts if the failure is at its start
+    let at_line_start = failed > start && failed < data.len() && data.as_bytes()[failed - 1] == b'\n';
+    let mut pos = if at_line_start { failed } else { next_line(failed) };
+    while pos < data.len() {
+        if pos > start && looks_like_section(&data[pos..]) {
+            return pos;
+        }
+        pos = next_line(pos);
+    }
+    data.len()
+}
@@ -1,13 +1,162 @@
-use crate::model::{Bin, BinValue};
+use crate::model::{Bin, BinValue, Field};
 use crate::hash_binary::{BinaryHashReader, BinaryHashWriter};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use crate::paths::long_path;
+use crate::progress::{Event, Progress};
 
 pub struct BinUnhasher {
     fnv1a: HashMap<u32, String>,
     xxh64: HashMap<u64, String>,
+    session: SessionOverlay,
+    name_case: NameCase,
+}
+
+/// How name casing is handled when names are written into a bin.
+///
+/// FNV1a and XXH64 both lowercase their input, so `Characters/Ahri` and
+/// `characters/ahri` resolve to the same hash. Hash files keep the casing
+/// they were written with; this controls which spelling ends up in exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameCase {
+    /// Keep names already present in the bin as typed, fill the rest from the tables (default)
+    #[default]
+    Preserve,
+    /// Prefer the hash-table casing, re-casing names that only differ by case
+    Table,
+    /// Lowercase every name
+    Lower,
+}
+
+/// Names discovered or entered during the current session.
+///
+/// The overlay is consulted before the loaded hash tables and is never merged
+/// into them, so it can be exported on its own when the session ends.
+#[derive(Debug, Default, Clone)]
+pub struct SessionOverlay {
+    pub fnv1a: HashMap<u32, String>,
+    pub xxh64: HashMap<u64, String>,
+}
+
+impl SessionOverlay {
+    /// Total number of names held by the overlay
+    pub fn len(&self) -> usize {
+        self.fnv1a.len() + self.xxh64.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fnv1a.is_empty() && self.xxh64.is_empty()
+    }
+}
+
+impl Default for BinUnhasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A name typed into a bin that the hash tables don't know and that looks
+/// mistyped, see [`BinUnhasher::suspicious_names`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuspiciousName {
+    pub name: String,
+    /// Why it looks mistyped, e.g. "contains a backslash"
+    pub reason: &'static str,
+}
+
+/// Why `name` looks like a mistyped path, if it does
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::unhash::typo_reason;
+///
+/// assert_eq!(typo_reason("Characters/Ahri/Skins/Skin0"), None);
+/// assert_eq!(typo_reason("Characters\\Ahri"), Some("contains a backslash"));
+/// assert_eq!(typo_reason("Characters//Ahri"), Some("has an empty path segment"));
+/// ```
+pub fn typo_reason(name: &str) -> Option<&'static str> {
+    if name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+        Some("starts or ends with whitespace")
+    } else if name.chars().any(char::is_control) {
+        Some("contains control characters")
+    } else if name.contains('\\') {
+        Some("contains a backslash")
+    } else if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        Some("has an empty path segment")
+    } else if !name.is_ascii() {
+        Some("contains non-ASCII characters")
+    } else {
+        None
+    }
+}
+
+/// Count of resolved and unresolved hash names in a bin
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NameStats {
+    pub resolved: usize,
+    pub unresolved: usize,
+}
+
+impl NameStats {
+    /// Walk `bin` and count every hash (entries, classes, fields, hashes, links, files)
+    pub fn collect(bin: &Bin) -> Self {
+        let mut stats = Self::default();
+        for value in bin.sections.values() {
+            stats.visit(value);
+        }
+        stats
+    }
+
+    fn count(&mut self, name: &Option<String>) {
+        if name.is_some() {
+            self.resolved += 1;
+        } else {
+            self.unresolved += 1;
+        }
+    }
+
+    fn visit(&mut self, value: &BinValue) {
+        match value {
+            BinValue::Hash { name, .. } | BinValue::Link { name, .. } | BinValue::File { name, .. } => {
+                self.count(name);
+            },
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for item in items {
+                    self.visit(item);
+                }
+            },
+            BinValue::Option { item: Some(inner), .. } => {
+                self.visit(inner);
+            },
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    self.visit(k);
+                    self.visit(v);
+                }
+            },
+            BinValue::Pointer { name, name_str, items } => {
+                // Null pointers carry no class hash
+                if *name != 0 {
+                    self.count(name_str);
+                }
+                for field in items {
+                    self.count(&field.key_str);
+                    self.visit(&field.value);
+                }
+            },
+            BinValue::Embed { name_str, items, .. } => {
+                self.count(name_str);
+                for field in items {
+                    self.count(&field.key_str);
+                    self.visit(&field.value);
+                }
+            },
+            _ => {}
+        }
+    }
 }
 
 impl BinUnhasher {
@@ -15,43 +164,111 @@ impl BinUnhasher {
         Self {
             fnv1a: HashMap::new(),
             xxh64: HashMap::new(),
+            session: SessionOverlay::default(),
+            name_case: NameCase::default(),
         }
     }
 
+    /// Choose how name casing is handled by `unhash_bin`
+    pub fn set_name_case(&mut self, name_case: NameCase) {
+        self.name_case = name_case;
+    }
+
+    pub fn name_case(&self) -> NameCase {
+        self.name_case
+    }
+
     /// Load hashes automatically - tries binary format first, falls back to text
     /// 
     /// This is the recommended way to load hashes as it will use the fastest
-    /// available format.
-    pub fn load_auto(&mut self, path: &str) -> std::io::Result<()> {
+    /// available format. Nothing is printed;
+    /// [`load_hash_dir_with_progress`](crate::discovery::load_hash_dir_with_progress)
+    /// reports each file of a hash directory as it is loaded.
+    ///
+    /// Like every file API in this crate it takes anything path-like, so
+    /// `&str`, `String`, `&Path` and `PathBuf` all work:
+    ///
+    /// ```no_run
+    /// use ritobin_rust::unhash::BinUnhasher;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut unhasher = BinUnhasher::new();
+    /// unhasher.load_auto("hashes/hashes.game.txt")?;
+    /// unhasher.load_auto(PathBuf::from("hashes").join("hashes.binfields.txt"))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load_auto<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
         // Try binary first (much faster)
-        let bin_path = if path.ends_with(".txt") {
-            path.replace(".txt", ".bin")
+        let bin_path = if path.extension().is_some_and(|e| e == "txt") {
+            path.with_extension("bin")
         } else {
-            format!("{}.bin", path)
+            with_suffix(path, ".bin")
         };
 
-        if Path::new(&bin_path).exists() {
-            eprintln!("Loading binary hash file: {}", bin_path);
-            return self.load_binary_file(&bin_path);
+        if bin_path.exists() {
+            self.load_binary_file(&bin_path)?;
+        } else {
+            // Fallback to text format
+            let name = file_name_lossy(path);
+            if name.contains("hashes.game.txt") || name.contains("fnv1a") {
+                self.load_fnv1a_cdtb(path);
+            } else if name.contains("xxh64") {
+                self.load_xxh64_cdtb(path);
+            } else {
+                // Try to detect format
+                self.load_fnv1a_cdtb(path);
+            }
+        }
+
+        // Newer names shipped as a delta on top of the full list
+        let delta_path = Self::delta_path(path);
+        if delta_path.exists() {
+            self.load_binary_file(&delta_path)?;
         }
 
-        // Fallback to text format
-        eprintln!("Loading text hash file: {}", path);
-        if path.contains("hashes.game.txt") || path.contains("fnv1a") {
-            self.load_fnv1a_cdtb(path);
-        } else if path.contains("xxh64") {
-            self.load_xxh64_cdtb(path);
+        Ok(())
+    }
+
+    /// Where [`load_auto`](Self::load_auto) looks for a delta of a hash file:
+    /// `hashes.game.txt` -> `hashes.game.delta.bin`
+    pub fn delta_path(path: &Path) -> PathBuf {
+        if path.extension().is_some_and(|e| e == "txt" || e == "bin") {
+            path.with_extension("delta.bin")
         } else {
-            // Try to detect format
-            self.load_fnv1a_cdtb(path);
+            with_suffix(path, ".delta.bin")
         }
-        
-        Ok(())
+    }
+
+    /// Hashes that are new or renamed compared to `base`.
+    ///
+    /// Saved with [`save_binary_file`](Self::save_binary_file), the result is
+    /// a delta: loading it on top of `base` gives the same names as `self`.
+    pub fn delta_from(&self, base: &BinUnhasher) -> BinUnhasher {
+        let mut delta = BinUnhasher::new();
+        delta.fnv1a = self.fnv1a.iter()
+            .filter(|(hash, name)| base.fnv1a.get(hash) != Some(name))
+            .map(|(hash, name)| (*hash, name.clone()))
+            .collect();
+        delta.xxh64 = self.xxh64.iter()
+            .filter(|(hash, name)| base.xxh64.get(hash) != Some(name))
+            .map(|(hash, name)| (*hash, name.clone()))
+            .collect();
+        delta
+    }
+
+    /// Number of names in the loaded tables, not counting the session overlay
+    pub fn len(&self) -> usize {
+        self.fnv1a.len() + self.xxh64.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fnv1a.is_empty() && self.xxh64.is_empty()
     }
 
     /// Load from binary format file
-    pub fn load_binary_file(&mut self, path: &str) -> std::io::Result<()> {
-        let file = File::open(path)?;
+    pub fn load_binary_file<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let file = File::open(long_path(path.as_ref()))?;
         self.load_binary(file)
     }
 
@@ -68,8 +285,8 @@ impl BinUnhasher {
     }
 
     /// Save to binary format file
-    pub fn save_binary_file(&self, path: &str) -> std::io::Result<()> {
-        let file = File::create(path)?;
+    pub fn save_binary_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = File::create(long_path(path.as_ref()))?;
         self.save_binary(file)
     }
 
@@ -82,173 +299,348 @@ impl BinUnhasher {
     /// Convert text hash file to binary format
     /// 
     /// Returns the number of hashes converted
-    pub fn convert_text_to_binary(input_path: &str, output_path: &str) -> std::io::Result<usize> {
-        let mut unhasher = BinUnhasher::new();
+    pub fn convert_text_to_binary<P: AsRef<Path>, Q: AsRef<Path>>(input_path: P, output_path: Q) -> std::io::Result<usize> {
+        let unhasher = Self::load_text_for_conversion(input_path.as_ref());
+        let total = unhasher.len();
+        
+        // Save to binary
+        unhasher.save_binary(File::create(long_path(output_path.as_ref()))?)?;
         
-        // Load from text
-        if input_path.contains("fnv1a") || input_path.contains("hashes.game") {
+        Ok(total)
+    }
+
+    /// Convert a text hash file to a binary delta holding only the names
+    /// missing from (or renamed in) the binary hash file `base_path`
+    ///
+    /// Returns the number of hashes in the delta
+    pub fn convert_text_to_delta<P: AsRef<Path>, B: AsRef<Path>, Q: AsRef<Path>>(
+        input_path: P,
+        base_path: B,
+        output_path: Q,
+    ) -> std::io::Result<usize> {
+        let unhasher = Self::load_text_for_conversion(input_path.as_ref());
+        let mut base = BinUnhasher::new();
+        base.load_binary_file(base_path)?;
+
+        let delta = unhasher.delta_from(&base);
+        delta.save_binary(File::create(long_path(output_path.as_ref()))?)?;
+        Ok(delta.len())
+    }
+
+    fn load_text_for_conversion(input_path: &Path) -> BinUnhasher {
+        let mut unhasher = BinUnhasher::new();
+        let name = file_name_lossy(input_path);
+        if name.contains("fnv1a") || name.contains("hashes.game") {
             unhasher.load_fnv1a_cdtb(input_path);
-        } else if input_path.contains("xxh64") {
+        } else if name.contains("xxh64") {
             unhasher.load_xxh64_cdtb(input_path);
         } else {
             // Try both
             unhasher.load_fnv1a_cdtb(input_path);
             unhasher.load_xxh64_cdtb(input_path);
         }
-        
-        let total = unhasher.fnv1a.len() + unhasher.xxh64.len();
-        
-        // Save to binary
-        unhasher.save_binary_file(output_path)?;
-        
-        Ok(total)
+        unhasher
     }
 
-    pub fn load_fnv1a_cdtb(&mut self, path: &str) -> bool {
-        if let Ok(file) = File::open(path) {
-            self.load_fnv1a_from_reader(BufReader::new(file))
-        } else {
-            // Try with suffix .0, .1, etc.
-            let mut i = 0;
-            let mut loaded_any = false;
-            loop {
-                let p = format!("{}.{}", path, i);
-                if let Ok(file) = File::open(&p) {
-                    if self.load_fnv1a_from_reader(BufReader::new(file)) {
-                        loaded_any = true;
-                    }
-                } else {
-                    break;
-                }
-                i += 1;
-            }
-            loaded_any
-        }
+    pub fn load_fnv1a_cdtb<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        load_split(path.as_ref(), |reader| self.load_fnv1a_from_reader(reader))
     }
 
     fn load_fnv1a_from_reader<R: BufRead>(&mut self, reader: R) -> bool {
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if line.is_empty() { continue; }
-                if let Some(idx) = line.find(' ') {
-                    if let Ok(hash) = u32::from_str_radix(&line[..idx], 16) {
-                        let name = line[idx+1..].to_string();
-                        self.fnv1a.insert(hash, name);
-                    }
+        for line in reader.lines().map_while(Result::ok) {
+            if line.is_empty() { continue; }
+            if let Some(idx) = line.find(' ') {
+                if let Ok(hash) = u32::from_str_radix(&line[..idx], 16) {
+                    let name = line[idx+1..].to_string();
+                    self.fnv1a.insert(hash, name);
                 }
             }
         }
         true
     }
 
-    pub fn load_xxh64_cdtb(&mut self, path: &str) -> bool {
-        if let Ok(file) = File::open(path) {
-            self.load_xxh64_from_reader(BufReader::new(file))
-        } else {
-            let mut i = 0;
-            let mut loaded_any = false;
-            loop {
-                let p = format!("{}.{}", path, i);
-                if let Ok(file) = File::open(&p) {
-                    if self.load_xxh64_from_reader(BufReader::new(file)) {
-                        loaded_any = true;
-                    }
-                } else {
-                    break;
-                }
-                i += 1;
-            }
-            loaded_any
-        }
+    pub fn load_xxh64_cdtb<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        load_split(path.as_ref(), |reader| self.load_xxh64_from_reader(reader))
     }
 
     fn load_xxh64_from_reader<R: BufRead>(&mut self, reader: R) -> bool {
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if line.is_empty() { continue; }
-                if let Some(idx) = line.find(' ') {
-                    if let Ok(hash) = u64::from_str_radix(&line[..idx], 16) {
-                        let name = line[idx+1..].to_string();
-                        self.xxh64.insert(hash, name);
-                    }
+        for line in reader.lines().map_while(Result::ok) {
+            if line.is_empty() { continue; }
+            if let Some(idx) = line.find(' ') {
+                if let Ok(hash) = u64::from_str_radix(&line[..idx], 16) {
+                    let name = line[idx+1..].to_string();
+                    self.xxh64.insert(hash, name);
                 }
             }
         }
         true
     }
 
-    pub fn unhash_bin(&self, bin: &mut Bin) {
-        for value in bin.sections.values_mut() {
-            self.unhash_value(value);
+    /// Look up an FNV1a hash, preferring names from the session overlay
+    pub fn resolve_fnv1a(&self, hash: u32) -> Option<&str> {
+        self.session.fnv1a.get(&hash)
+            .or_else(|| self.fnv1a.get(&hash))
+            .map(String::as_str)
+    }
+
+    /// Look up an XXH64 hash, preferring names from the session overlay
+    pub fn resolve_xxh64(&self, hash: u64) -> Option<&str> {
+        self.session.xxh64.get(&hash)
+            .or_else(|| self.xxh64.get(&hash))
+            .map(String::as_str)
+    }
+
+    /// Record a name for an FNV1a hash (fields, classes, entries, links) in the session overlay
+    ///
+    /// Returns the hash of the name.
+    pub fn add_session_fnv1a(&mut self, name: &str) -> u32 {
+        let hash = crate::hash::fnv1a(name);
+        self.session.fnv1a.insert(hash, name.to_string());
+        hash
+    }
+
+    /// Record a name for an XXH64 hash (file paths) in the session overlay
+    ///
+    /// Returns the hash of the name.
+    pub fn add_session_xxh64(&mut self, name: &str) -> u64 {
+        let hash = crate::hash::Xxh64::new(name).0;
+        self.session.xxh64.insert(hash, name.to_string());
+        hash
+    }
+
+    /// Names collected during this session
+    pub fn session(&self) -> &SessionOverlay {
+        &self.session
+    }
+
+    /// Drop every session name, leaving the base tables untouched
+    pub fn clear_session(&mut self) {
+        self.session = SessionOverlay::default();
+    }
+
+    /// Harvest names present in `bin` that the base tables don't know yet
+    ///
+    /// Only names whose hash matches the stored value are recorded, so stale
+    /// or hand-edited names that no longer hash correctly are ignored.
+    /// Returns the number of new names added to the overlay.
+    pub fn learn_from_bin(&mut self, bin: &Bin) -> usize {
+        let before = self.session.len();
+        for value in bin.sections.values() {
+            self.learn_value(value);
         }
+        self.session.len() - before
     }
 
-    fn unhash_value(&self, value: &mut BinValue) {
+    fn learn_fnv1a(&mut self, hash: u32, name: &Option<String>) {
+        if let Some(name) = name {
+            if !self.fnv1a.contains_key(&hash) && crate::hash::fnv1a(name) == hash {
+                self.session.fnv1a.entry(hash).or_insert_with(|| name.clone());
+            }
+        }
+    }
+
+    fn learn_xxh64(&mut self, hash: u64, name: &Option<String>) {
+        if let Some(name) = name {
+            if !self.xxh64.contains_key(&hash) && crate::hash::Xxh64::new(name).0 == hash {
+                self.session.xxh64.entry(hash).or_insert_with(|| name.clone());
+            }
+        }
+    }
+
+    fn learn_value(&mut self, value: &BinValue) {
         match value {
-            BinValue::Hash { value: h, name } => {
-                if name.is_none() {
-                    if let Some(s) = self.fnv1a.get(h) {
-                        *name = Some(s.clone());
-                    }
-                }
+            BinValue::Hash { value: h, name } | BinValue::Link { value: h, name } => {
+                self.learn_fnv1a(*h, name);
             },
             BinValue::File { value: h, name } => {
-                if name.is_none() {
-                    if let Some(s) = self.xxh64.get(h) {
-                        *name = Some(s.clone());
-                    }
+                self.learn_xxh64(*h, name);
+            },
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for item in items {
+                    self.learn_value(item);
                 }
             },
-            BinValue::Link { value: h, name } => {
-                if name.is_none() {
-                    if let Some(s) = self.fnv1a.get(h) {
-                        *name = Some(s.clone());
-                    }
+            BinValue::Option { item: Some(inner), .. } => {
+                self.learn_value(inner);
+            },
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    self.learn_value(k);
+                    self.learn_value(v);
+                }
+            },
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                self.learn_fnv1a(*name, name_str);
+                for Field { key, key_str, value } in items {
+                    self.learn_fnv1a(*key, key_str);
+                    self.learn_value(value);
                 }
             },
+            _ => {}
+        }
+    }
+
+    /// Save only the session names to a binary hash file
+    pub fn save_session_binary_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = File::create(long_path(path.as_ref()))?;
+        let mut hash_writer = BinaryHashWriter::new(file);
+        hash_writer.write_hashes(&self.session.fnv1a, &self.session.xxh64)
+    }
+
+    /// Write the session FNV1a names in CDTB text format (`hash name` per line)
+    pub fn write_session_fnv1a_text<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut entries: Vec<_> = self.session.fnv1a.iter().collect();
+        entries.sort();
+        for (hash, name) in entries {
+            writeln!(writer, "{:08x} {}", hash, name)?;
+        }
+        Ok(())
+    }
+
+    /// Write the session XXH64 names in CDTB text format (`hash name` per line)
+    pub fn write_session_xxh64_text<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut entries: Vec<_> = self.session.xxh64.iter().collect();
+        entries.sort();
+        for (hash, name) in entries {
+            writeln!(writer, "{:016x} {}", hash, name)?;
+        }
+        Ok(())
+    }
+
+    /// Names in `bin` the tables don't know that look mistyped, each listed once
+    ///
+    /// Names typed into text or JSON are hashed as they are, so a mistyped
+    /// one silently becomes a hash the game won't look up. Names the tables
+    /// (or the session) know are never reported, whatever they look like.
+    pub fn suspicious_names(&self, bin: &Bin) -> Vec<SuspiciousName> {
+        let mut found = Vec::new();
+        for value in bin.sections.values() {
+            self.find_suspicious(value, &mut found);
+        }
+        found
+    }
+
+    fn check_name(known: bool, name: &Option<String>, found: &mut Vec<SuspiciousName>) {
+        let Some(name) = name.as_deref().filter(|_| !known) else { return };
+        if let Some(reason) = typo_reason(name) {
+            if !found.iter().any(|s| s.name == name) {
+                found.push(SuspiciousName { name: name.to_string(), reason });
+            }
+        }
+    }
+
+    fn find_suspicious(&self, value: &BinValue, found: &mut Vec<SuspiciousName>) {
+        match value {
+            BinValue::Hash { value: h, name } | BinValue::Link { value: h, name } => {
+                Self::check_name(self.resolve_fnv1a(*h).is_some(), name, found);
+            },
+            BinValue::File { value: h, name } => {
+                Self::check_name(self.resolve_xxh64(*h).is_some(), name, found);
+            },
             BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
                 for item in items {
-                    self.unhash_value(item);
+                    self.find_suspicious(item, found);
                 }
             },
-            BinValue::Option { item, .. } => {
-                if let Some(inner) = item {
-                    self.unhash_value(inner);
-                }
+            BinValue::Option { item: Some(inner), .. } => {
+                self.find_suspicious(inner, found);
             },
             BinValue::Map { items, .. } => {
                 for (k, v) in items {
-                    self.unhash_value(k);
-                    self.unhash_value(v);
+                    self.find_suspicious(k, found);
+                    self.find_suspicious(v, found);
                 }
             },
-            BinValue::Pointer { name, name_str, items } => {
-                if name_str.is_none() {
-                    if let Some(s) = self.fnv1a.get(name) {
-                        *name_str = Some(s.clone());
-                    }
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                Self::check_name(self.resolve_fnv1a(*name).is_some(), name_str, found);
+                for Field { key, key_str, value } in items {
+                    Self::check_name(self.resolve_fnv1a(*key).is_some(), key_str, found);
+                    self.find_suspicious(value, found);
                 }
-                for field in items {
-                    if field.key_str.is_none() {
-                        if let Some(s) = self.fnv1a.get(&field.key) {
-                            field.key_str = Some(s.clone());
+            },
+            _ => {}
+        }
+    }
+
+    pub fn unhash_bin(&self, bin: &mut Bin) {
+        for value in bin.sections.values_mut() {
+            self.unhash_value(value);
+        }
+    }
+
+    /// [`unhash_bin`](Self::unhash_bin), then report the resolved and unresolved names
+    pub fn unhash_bin_with_progress(&self, bin: &mut Bin, progress: &mut dyn Progress) {
+        self.unhash_bin(bin);
+        progress.event(Event::HashesResolved(NameStats::collect(bin)));
+    }
+
+    /// Fill or re-case an FNV1a name according to the current `NameCase`
+    fn apply_fnv1a(&self, hash: u32, name: &mut Option<String>) {
+        let resolved = self.resolve_fnv1a(hash);
+        let verified = |s: &str| crate::hash::fnv1a(s) == hash;
+        self.apply_name(resolved, name, verified);
+    }
+
+    /// Fill or re-case an XXH64 name according to the current `NameCase`
+    fn apply_xxh64(&self, hash: u64, name: &mut Option<String>) {
+        let resolved = self.resolve_xxh64(hash);
+        let verified = |s: &str| crate::hash::Xxh64::new(s).0 == hash;
+        self.apply_name(resolved, name, verified);
+    }
+
+    fn apply_name(&self, resolved: Option<&str>, name: &mut Option<String>, verified: impl Fn(&str) -> bool) {
+        match name {
+            None => {
+                if let Some(s) = resolved {
+                    *name = Some(match self.name_case {
+                        NameCase::Lower => s.to_ascii_lowercase(),
+                        NameCase::Preserve | NameCase::Table => s.to_string(),
+                    });
+                }
+            },
+            Some(existing) => match self.name_case {
+                NameCase::Preserve => {},
+                NameCase::Table => {
+                    // Only re-case when the table name really is the same name
+                    if let Some(s) = resolved {
+                        if s != existing && s.eq_ignore_ascii_case(existing) && verified(s) {
+                            *existing = s.to_string();
                         }
                     }
-                    self.unhash_value(&mut field.value);
+                },
+                NameCase::Lower => {
+                    existing.make_ascii_lowercase();
+                },
+            },
+        }
+    }
+
+    fn unhash_value(&self, value: &mut BinValue) {
+        match value {
+            BinValue::Hash { value: h, name } | BinValue::Link { value: h, name } => {
+                self.apply_fnv1a(*h, name);
+            },
+            BinValue::File { value: h, name } => {
+                self.apply_xxh64(*h, name);
+            },
+            BinValue::List { items, .. } | BinValue::List2 { items, .. } => {
+                for item in items {
+                    self.unhash_value(item);
                 }
             },
-            BinValue::Embed { name, name_str, items } => {
-                if name_str.is_none() {
-                    if let Some(s) = self.fnv1a.get(name) {
-                        *name_str = Some(s.clone());
-                    }
+            BinValue::Option { item: Some(inner), .. } => {
+                self.unhash_value(inner);
+            },
+            BinValue::Map { items, .. } => {
+                for (k, v) in items {
+                    self.unhash_value(k);
+                    self.unhash_value(v);
                 }
+            },
+            BinValue::Pointer { name, name_str, items } | BinValue::Embed { name, name_str, items } => {
+                self.apply_fnv1a(*name, name_str);
                 for field in items {
-                    if field.key_str.is_none() {
-                        if let Some(s) = self.fnv1a.get(&field.key) {
-                            field.key_str = Some(s.clone());
-                        }
-                    }
+                    self.apply_fnv1a(field.key, &mut field.key_str);
                     self.unhash_value(&mut field.value);
                 }
             },
@@ -257,6 +649,32 @@ impl BinUnhasher {
     }
 }
 
+/// Load `path`, or if it doesn't exist its split parts `path.0`, `path.1`, ...
+fn load_split(path: &Path, mut load: impl FnMut(BufReader<File>) -> bool) -> bool {
+    if let Ok(file) = File::open(long_path(path)) {
+        return load(BufReader::new(file));
+    }
+    let mut loaded_any = false;
+    for i in 0.. {
+        let Ok(file) = File::open(long_path(&with_suffix(path, &format!(".{}", i)))) else { break };
+        if load(BufReader::new(file)) {
+            loaded_any = true;
+        }
+    }
+    loaded_any
+}
+
+/// `path` with `suffix` appended to its last component
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(suffix);
+    s.into()
+}
+
+fn file_name_lossy(path: &Path) -> std::borrow::Cow<'_, str> {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,4 +703,131 @@ mod tests {
         
         std::fs::remove_file("test_hashes.txt").unwrap();
     }
+
+    #[test]
+    fn test_hash_delta() {
+        let dir = std::env::temp_dir().join(format!("ritobin_delta_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("hashes.game.txt");
+        std::fs::write(&text, "00000001 old\n00000002 renamed_old\n").unwrap();
+        BinUnhasher::convert_text_to_binary(&text, dir.join("hashes.game.bin")).unwrap();
+
+        std::fs::write(&text, "00000001 old\n00000002 renamed_new\n00000003 added\n").unwrap();
+        let count = BinUnhasher::convert_text_to_delta(&text, dir.join("hashes.game.bin"), BinUnhasher::delta_path(&text)).unwrap();
+        assert_eq!(count, 2);
+        std::fs::remove_file(&text).unwrap();
+
+        let mut unhasher = BinUnhasher::new();
+        unhasher.load_auto(&text).unwrap();
+        assert_eq!(unhasher.len(), 3);
+        assert_eq!(unhasher.resolve_fnv1a(2), Some("renamed_new"));
+        assert_eq!(unhasher.resolve_fnv1a(3), Some("added"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_overlay() {
+        let mut unhasher = BinUnhasher::new();
+        unhasher.fnv1a.insert(crate::hash::fnv1a("mSpell"), "base_name".to_string());
+        let hash = unhasher.add_session_fnv1a("mSpell");
+
+        // Session names win over the base tables, which stay untouched
+        assert_eq!(unhasher.resolve_fnv1a(hash), Some("mSpell"));
+        assert_eq!(unhasher.fnv1a.get(&hash).map(String::as_str), Some("base_name"));
+
+        let mut bin = Bin::new();
+        bin.sections.insert("link".to_string(), BinValue::Link {
+            value: crate::hash::fnv1a("Characters/Ahri"),
+            name: Some("Characters/Ahri".to_string()),
+        });
+        bin.sections.insert("stale".to_string(), BinValue::Hash {
+            value: 0x1234,
+            name: Some("not_matching".to_string()),
+        });
+        assert_eq!(unhasher.learn_from_bin(&bin), 1);
+
+        let mut out = Vec::new();
+        unhasher.write_session_fnv1a_text(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("mSpell"));
+        assert!(text.contains("Characters/Ahri"));
+
+        unhasher.clear_session();
+        assert!(unhasher.session().is_empty());
+        assert_eq!(unhasher.resolve_fnv1a(hash), Some("base_name"));
+    }
+
+    #[test]
+    fn test_suspicious_names() {
+        let mut unhasher = BinUnhasher::new();
+        unhasher.fnv1a.insert(crate::hash::fnv1a("Known//Path"), "Known//Path".to_string());
+        let named = |name: &str| BinValue::Link { value: crate::hash::fnv1a(name), name: Some(name.to_string()) };
+        let mut bin = Bin::new();
+        bin.sections.insert("entries".to_string(), BinValue::List {
+            value_type: crate::model::BinType::Link,
+            items: vec![named("Known//Path"), named("Characters/Ahri "), named("Characters/Ahri"), named("Characters/Ahri ")],
+        });
+        bin.sections.insert("file".to_string(), BinValue::File { value: 1, name: Some("ASSETS\\ahri.dds".to_string()) });
+
+        let found = unhasher.suspicious_names(&bin);
+        assert_eq!(found, vec![
+            SuspiciousName { name: "Characters/Ahri ".to_string(), reason: "starts or ends with whitespace" },
+            SuspiciousName { name: "ASSETS\\ahri.dds".to_string(), reason: "contains a backslash" },
+        ]);
+    }
+
+    #[test]
+    fn test_name_case() {
+        let mut unhasher = BinUnhasher::new();
+        let hash = crate::hash::fnv1a("Characters/Ahri");
+        unhasher.fnv1a.insert(hash, "Characters/Ahri".to_string());
+
+        let make_bin = || {
+            let mut bin = Bin::new();
+            bin.sections.insert("typed".to_string(), BinValue::Link {
+                value: hash,
+                name: Some("characters/ahri".to_string()),
+            });
+            bin.sections.insert("missing".to_string(), BinValue::Hash { value: hash, name: None });
+            bin
+        };
+        let names = |bin: &Bin| -> Vec<String> {
+            bin.sections.values().map(|v| match v {
+                BinValue::Hash { name, .. } | BinValue::Link { name, .. } => name.clone().unwrap(),
+                _ => unreachable!(),
+            }).collect()
+        };
+
+        let mut bin = make_bin();
+        unhasher.unhash_bin(&mut bin);
+        assert_eq!(names(&bin), ["characters/ahri", "Characters/Ahri"]);
+
+        unhasher.set_name_case(NameCase::Table);
+        let mut bin = make_bin();
+        unhasher.unhash_bin(&mut bin);
+        assert_eq!(names(&bin), ["Characters/Ahri", "Characters/Ahri"]);
+
+        unhasher.set_name_case(NameCase::Lower);
+        let mut bin = make_bin();
+        unhasher.unhash_bin(&mut bin);
+        assert_eq!(names(&bin), ["characters/ahri", "characters/ahri"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_non_utf8_split_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join(std::ffi::OsStr::from_bytes(b"ritobin_\xff_hashes"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hashes.binfields.txt");
+        std::fs::write(with_suffix(&path, ".0"), "0000000a alpha\n").unwrap();
+        std::fs::write(with_suffix(&path, ".1"), "0000000b beta\n").unwrap();
+
+        let mut unhasher = BinUnhasher::new();
+        unhasher.load_auto(&path).unwrap();
+        assert_eq!(unhasher.resolve_fnv1a(0xa), Some("alpha"));
+        assert_eq!(unhasher.resolve_fnv1a(0xb), Some("beta"));
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
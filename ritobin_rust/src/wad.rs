@@ -0,0 +1,689 @@
+//! Bins inside `.wad.client` archives, with the `wad` feature.
+//!
+//! League ships its bins packed into WAD archives. [`Wad::parse`] reads the
+//! table of contents of an archive held in memory, read or
+//! [mapped](crate::mmap), and [`Wad::read_chunk`] decompresses one chunk;
+//! zstd and gzip are decoded without extra dependencies. Chunks are named by
+//! the XXH64 hash of their lowercased path, see [`path_hash`].
+//!
+//! [`Wad::edit`] writes a copy of an archive with chunks replaced or added,
+//! e.g. an edited bin put back in place.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ritobin_rust::wad::Wad;
+//!
+//! let data = std::fs::read("Ahri.wad.client")?;
+//! let wad = Wad::parse(&data)?;
+//! if let Some(chunk) = wad.chunk_by_path("data/characters/ahri/ahri.bin") {
+//!     let bin = wad.read_bin(chunk)?;
+//!     println!("{} sections", bin.sections.len());
+//! }
+//! for (chunk, bin) in wad.bins() {
+//!     println!("{:016x}: {}", chunk.path_hash, if bin.is_ok() { "ok" } else { "damaged" });
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Putting an edited bin back:
+//!
+//! ```no_run
+//! use ritobin_rust::wad::Wad;
+//!
+//! let data = std::fs::read("Ahri.wad.client")?;
+//! let wad = Wad::parse(&data)?;
+//! let chunk = wad.chunk_by_path("data/characters/ahri/ahri.bin").ok_or("no such chunk")?;
+//! let mut bin = wad.read_bin(chunk)?;
+//! bin.sections.shift_remove("linked");
+//!
+//! let mut edit = wad.edit();
+//! edit.set_bin("data/characters/ahri/ahri.bin", &bin)?;
+//! std::fs::write("Ahri.wad.client", edit.write()?)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::binary::{read_bin, write_bin, BinError, BinaryReader};
+use crate::model::Bin;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+mod inflate;
+mod zstd;
+
+#[derive(Error, Debug)]
+pub enum WadError {
+    #[error("Invalid magic bytes")]
+    InvalidMagic,
+    #[error("Unsupported WAD version {0}.{1}")]
+    UnsupportedVersion(u8, u8),
+    /// The table of contents runs past the end of the data
+    #[error("WAD table of contents is cut off")]
+    Truncated,
+    #[error("Chunk {path_hash:016x} has unknown compression type {compression}")]
+    UnknownCompression { path_hash: u64, compression: u8 },
+    #[error("Chunk {0:016x} lies outside the archive")]
+    OutOfBounds(u64),
+    /// A chunk whose data is in another archive
+    #[error("Chunk {0:016x} is a satellite chunk, stored in another archive")]
+    Satellite(u64),
+    #[error("Chunk {path_hash:016x} could not be decompressed: {message}")]
+    Decompress { path_hash: u64, message: &'static str },
+    #[error("Chunk {path_hash:016x} decompressed to {actual} bytes instead of {expected}")]
+    SizeMismatch { path_hash: u64, expected: u32, actual: usize },
+    /// An archive written by [`WadEdit::write`] would pass the 4 GiB offsets can address
+    #[error("WAD archive would be larger than 4 GiB")]
+    TooLarge,
+    #[error(transparent)]
+    Bin(#[from] BinError),
+}
+
+/// How a chunk is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WadCompression {
+    None,
+    Gzip,
+    /// A reference to data in another archive
+    Satellite,
+    Zstd,
+    /// Zstd frames mixed with uncompressed runs
+    ZstdMulti,
+}
+
+impl WadCompression {
+    fn to_u8(self) -> u8 {
+        match self {
+            WadCompression::None => 0,
+            WadCompression::Gzip => 1,
+            WadCompression::Satellite => 2,
+            WadCompression::Zstd => 3,
+            WadCompression::ZstdMulti => 4,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(WadCompression::None),
+            1 => Some(WadCompression::Gzip),
+            2 => Some(WadCompression::Satellite),
+            3 => Some(WadCompression::Zstd),
+            4 => Some(WadCompression::ZstdMulti),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a WAD's table of contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WadChunk {
+    /// XXH64 of the lowercased path, see [`path_hash`]
+    pub path_hash: u64,
+    /// Offset of the stored data from the start of the archive
+    pub offset: u32,
+    /// Stored size
+    pub compressed_size: u32,
+    /// Size once decompressed
+    pub size: u32,
+    pub compression: WadCompression,
+    pub subchunk_count: u8,
+    /// Whether the data is shared with another chunk
+    pub duplicate: bool,
+    pub first_subchunk: u16,
+    /// Checksum of the stored data: XXH3 from version 3.1, the first 8 bytes
+    /// of its SHA-256 in versions 2 and 3.0, and 0 in version 1 archives
+    pub checksum: u64,
+}
+
+/// The hash naming the chunk for `path`
+///
+/// # Examples
+///
+/// ```
+/// use ritobin_rust::wad::path_hash;
+///
+/// assert_eq!(path_hash("DATA/Characters/Ahri/Ahri.bin"), path_hash("data/characters/ahri/ahri.bin"));
+/// ```
+pub fn path_hash(path: &str) -> u64 {
+    crate::hash::xxh64(path.to_lowercase().as_bytes(), 0)
+}
+
+/// A WAD archive held in memory, see the [module docs](self).
+#[derive(Debug)]
+pub struct Wad<'a> {
+    data: &'a [u8],
+    version: (u8, u8),
+    /// Where the table of contents starts and the size of its entries
+    toc_offset: u64,
+    entry_size: u64,
+    chunks: Vec<WadChunk>,
+    by_hash: HashMap<u64, usize>,
+}
+
+impl<'a> Wad<'a> {
+    /// Read the header and table of contents of `data`
+    ///
+    /// Versions 1 to 3 are supported. Chunk data is not touched until read.
+    pub fn parse(data: &'a [u8]) -> Result<Self, WadError> {
+        let mut reader = BinaryReader::new(data);
+        let truncated = |_| WadError::Truncated;
+        if reader.read_bytes(2).map_err(truncated)? != b"RW" {
+            return Err(WadError::InvalidMagic);
+        }
+        let version = (reader.read_u8().map_err(truncated)?, reader.read_u8().map_err(truncated)?);
+
+        let (toc_offset, entry_size, count) = match version.0 {
+            1 | 2 => {
+                if version.0 == 2 {
+                    // Signature length, signature and checksum
+                    reader.read_bytes(1 + 83 + 8).map_err(truncated)?;
+                }
+                let toc_offset = reader.read_u16().map_err(truncated)? as u64;
+                let entry_size = reader.read_u16().map_err(truncated)? as u64;
+                (toc_offset, entry_size, reader.read_u32().map_err(truncated)?)
+            }
+            3 => {
+                // Signature and checksum
+                reader.read_bytes(256 + 8).map_err(truncated)?;
+                let count = reader.read_u32().map_err(truncated)?;
+                (reader.position(), 32, count)
+            }
+            _ => return Err(WadError::UnsupportedVersion(version.0, version.1)),
+        };
+        let min_entry_size = if version.0 == 1 { 24 } else { 32 };
+        if entry_size < min_entry_size || toc_offset + count as u64 * entry_size > data.len() as u64 {
+            return Err(WadError::Truncated);
+        }
+
+        let mut chunks = Vec::with_capacity(count as usize);
+        for i in 0..count as u64 {
+            reader.set_position(toc_offset + i * entry_size);
+            let path_hash = reader.read_u64()?;
+            let offset = reader.read_u32()?;
+            let compressed_size = reader.read_u32()?;
+            let size = reader.read_u32()?;
+            let kind = reader.read_u8()?;
+            let compression = WadCompression::from_u8(kind & 0x0f)
+                .ok_or(WadError::UnknownCompression { path_hash, compression: kind & 0x0f })?;
+            let duplicate = reader.read_bool()?;
+            let first_subchunk = reader.read_u16()?;
+            let checksum = if version.0 >= 2 { reader.read_u64()? } else { 0 };
+            chunks.push(WadChunk {
+                path_hash,
+                offset,
+                compressed_size,
+                size,
+                compression,
+                subchunk_count: kind >> 4,
+                duplicate,
+                first_subchunk,
+                checksum,
+            });
+        }
+        let by_hash = chunks.iter().enumerate().map(|(i, chunk)| (chunk.path_hash, i)).collect();
+        Ok(Self { data, version, toc_offset, entry_size, chunks, by_hash })
+    }
+
+    /// Major and minor version of the archive
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    /// Every chunk, in table of contents order
+    pub fn chunks(&self) -> &[WadChunk] {
+        &self.chunks
+    }
+
+    /// The chunk with path hash `hash`
+    pub fn chunk(&self, hash: u64) -> Option<&WadChunk> {
+        self.by_hash.get(&hash).map(|&i| &self.chunks[i])
+    }
+
+    /// The chunk for `path`, matched case-insensitively
+    pub fn chunk_by_path(&self, path: &str) -> Option<&WadChunk> {
+        self.chunk(path_hash(path))
+    }
+
+    /// The data of `chunk` as stored, without decompressing it
+    pub fn raw_chunk(&self, chunk: &WadChunk) -> Result<&'a [u8], WadError> {
+        let start = chunk.offset as usize;
+        self.data.get(start..start + chunk.compressed_size as usize).ok_or(WadError::OutOfBounds(chunk.path_hash))
+    }
+
+    /// The decompressed data of `chunk`
+    pub fn read_chunk(&self, chunk: &WadChunk) -> Result<Vec<u8>, WadError> {
+        let data = self.decompress(chunk, usize::MAX)?;
+        if data.len() != chunk.size as usize {
+            return Err(WadError::SizeMismatch { path_hash: chunk.path_hash, expected: chunk.size, actual: data.len() });
+        }
+        Ok(data)
+    }
+
+    /// Decompress and read `chunk` as a bin
+    pub fn read_bin(&self, chunk: &WadChunk) -> Result<Bin, WadError> {
+        Ok(read_bin(&self.read_chunk(chunk)?)?)
+    }
+
+    /// Whether `chunk` holds a bin, going by its first bytes
+    ///
+    /// Only the start of the chunk is decompressed.
+    pub fn is_bin(&self, chunk: &WadChunk) -> Result<bool, WadError> {
+        if chunk.compression == WadCompression::Satellite {
+            return Ok(false);
+        }
+        let start = self.decompress(chunk, 4)?;
+        Ok(start.starts_with(b"PROP") || start.starts_with(b"PTCH"))
+    }
+
+    /// Every chunk holding a bin, read
+    ///
+    /// Chunks are recognized by their first bytes, so bins are found without
+    /// knowing their paths; a chunk that can't be decompressed far enough to
+    /// tell is returned with its error.
+    pub fn bins(&self) -> impl Iterator<Item = (&WadChunk, Result<Bin, WadError>)> + '_ {
+        self.chunks.iter().filter_map(move |chunk| match self.is_bin(chunk) {
+            Ok(true) => Some((chunk, self.read_bin(chunk))),
+            Ok(false) => None,
+            Err(e) => Some((chunk, Err(e))),
+        })
+    }
+
+    /// Start a copy of the archive with chunks replaced or added
+    pub fn edit(&self) -> WadEdit<'_, 'a> {
+        WadEdit { wad: self, changed: BTreeMap::new() }
+    }
+
+    /// Decompress `chunk`, stopping early once `limit` bytes are out
+    fn decompress(&self, chunk: &WadChunk, limit: usize) -> Result<Vec<u8>, WadError> {
+        let raw = self.raw_chunk(chunk)?;
+        let failed = |message| WadError::Decompress { path_hash: chunk.path_hash, message };
+        match chunk.compression {
+            WadCompression::None => Ok(raw[..raw.len().min(limit)].to_vec()),
+            WadCompression::Gzip => inflate::gunzip(raw, limit).map_err(failed),
+            WadCompression::Satellite => Err(WadError::Satellite(chunk.path_hash)),
+            WadCompression::Zstd => zstd::decompress(raw, limit).map_err(failed),
+            WadCompression::ZstdMulti => {
+                let mut out = Vec::new();
+                let mut pos = 0;
+                while pos < raw.len() && out.len() < limit {
+                    if zstd::is_frame(&raw[pos..]) {
+                        pos += zstd::decode_frame(&raw[pos..], &mut out, limit).map_err(failed)?;
+                    } else {
+                        let end = (pos + 1..raw.len()).find(|&i| zstd::is_frame(&raw[i..])).unwrap_or(raw.len());
+                        out.extend_from_slice(&raw[pos..end]);
+                        pos = end;
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Changes to a [`Wad`], written out as a new archive by [`WadEdit::write`].
+///
+/// New data is stored uncompressed in the replaced chunk's format (zstd for
+/// zstd and new chunks, gzip for gzip, as is for uncompressed chunks), which
+/// the game reads like any other chunk. Only decoders are implemented, so
+/// edited archives grow by the difference between the compressed and plain
+/// sizes of the changed chunks. Checksums of changed chunks are recomputed in
+/// the archive's own algorithm, see [`WadChunk::checksum`]. The header,
+/// signature included, is copied.
+#[derive(Debug)]
+pub struct WadEdit<'w, 'a> {
+    wad: &'w Wad<'a>,
+    /// New decompressed data by path hash
+    changed: BTreeMap<u64, Vec<u8>>,
+}
+
+impl WadEdit<'_, '_> {
+    /// Store `data` as the chunk with path hash `hash`, adding a chunk if the archive has none
+    pub fn set_chunk(&mut self, hash: u64, data: Vec<u8>) {
+        self.changed.insert(hash, data);
+    }
+
+    /// Store `data` as the chunk for `path`
+    pub fn set_path(&mut self, path: &str, data: Vec<u8>) {
+        self.set_chunk(path_hash(path), data);
+    }
+
+    /// Encode `bin` and store it as the chunk for `path`
+    ///
+    /// The bin is written uncompressed, in stored zstd or gzip blocks when the
+    /// chunk it replaces was compressed.
+    pub fn set_bin(&mut self, path: &str, bin: &Bin) -> Result<(), WadError> {
+        self.set_path(path, write_bin(bin)?);
+        Ok(())
+    }
+
+    /// The archive with every change made
+    ///
+    /// Chunks keep table of contents order, sorted by path hash, and data
+    /// shared by several unchanged chunks stays shared.
+    pub fn write(&self) -> Result<Vec<u8>, WadError> {
+        let wad = self.wad;
+        let mut chunks = wad.chunks.clone();
+        for &hash in self.changed.keys().filter(|hash| !wad.by_hash.contains_key(hash)) {
+            chunks.push(WadChunk {
+                path_hash: hash,
+                offset: 0,
+                compressed_size: 0,
+                size: 0,
+                compression: WadCompression::Zstd,
+                subchunk_count: 0,
+                duplicate: false,
+                first_subchunk: 0,
+                checksum: 0,
+            });
+        }
+        chunks.sort_by_key(|chunk| chunk.path_hash);
+
+        let toc_offset = wad.toc_offset as usize;
+        let entry_size = wad.entry_size as usize;
+        let mut out = wad.data[..toc_offset].to_vec();
+        let count_at = match wad.version.0 {
+            1 => 8,
+            2 => 100,
+            _ => toc_offset - 4,
+        };
+        out[count_at..count_at + 4].copy_from_slice(&(chunks.len() as u32).to_le_bytes());
+        out.resize(toc_offset + chunks.len() * entry_size, 0);
+
+        // Where unchanged data was copied to, by its old offset and size
+        let mut copied: HashMap<(u32, u32), u32> = HashMap::new();
+        for (i, chunk) in chunks.iter_mut().enumerate() {
+            if let Some(data) = self.changed.get(&chunk.path_hash) {
+                let (compression, stored) = match chunk.compression {
+                    WadCompression::None => (WadCompression::None, data.clone()),
+                    WadCompression::Gzip => (WadCompression::Gzip, inflate::gzip_stored(data)),
+                    _ => (WadCompression::Zstd, zstd::store(data)),
+                };
+                chunk.offset = u32::try_from(out.len()).map_err(|_| WadError::TooLarge)?;
+                chunk.compressed_size = u32::try_from(stored.len()).map_err(|_| WadError::TooLarge)?;
+                chunk.size = u32::try_from(data.len()).map_err(|_| WadError::TooLarge)?;
+                chunk.compression = compression;
+                chunk.subchunk_count = 0;
+                chunk.first_subchunk = 0;
+                chunk.duplicate = false;
+                chunk.checksum = checksum(wad.version, &stored);
+                out.extend_from_slice(&stored);
+            } else if chunk.compression != WadCompression::Satellite {
+                let key = (chunk.offset, chunk.compressed_size);
+                chunk.offset = match copied.get(&key) {
+                    Some(&offset) => offset,
+                    None => {
+                        let offset = u32::try_from(out.len()).map_err(|_| WadError::TooLarge)?;
+                        out.extend_from_slice(wad.raw_chunk(chunk)?);
+                        copied.insert(key, offset);
+                        offset
+                    }
+                };
+            }
+
+            let entry = &mut out[toc_offset + i * entry_size..];
+            entry[..8].copy_from_slice(&chunk.path_hash.to_le_bytes());
+            entry[8..12].copy_from_slice(&chunk.offset.to_le_bytes());
+            entry[12..16].copy_from_slice(&chunk.compressed_size.to_le_bytes());
+            entry[16..20].copy_from_slice(&chunk.size.to_le_bytes());
+            entry[20] = chunk.subchunk_count << 4 | chunk.compression.to_u8();
+            entry[21] = chunk.duplicate as u8;
+            entry[22..24].copy_from_slice(&chunk.first_subchunk.to_le_bytes());
+            if wad.version.0 >= 2 {
+                entry[24..32].copy_from_slice(&chunk.checksum.to_le_bytes());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// The checksum of `stored` chunk data in an archive of `version`
+fn checksum(version: (u8, u8), stored: &[u8]) -> u64 {
+    match version {
+        (1, _) => 0,
+        (2, _) | (3, 0) => u64::from_le_bytes(crate::hash::sha256(stored)[..8].try_into().unwrap()),
+        _ => crate::hash::xxh3_64(stored),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write_bin;
+    use crate::model::{BinType, BinValue};
+
+    /// `data` in a zstd frame of one uncompressed block
+    fn zstd_stored(data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x50];
+        frame.extend_from_slice(&(((data.len() as u32) << 3) | 1).to_le_bytes()[..3]);
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    /// A version 2 or 3 archive holding `chunks` of (path, compression, stored data, size)
+    fn build_wad(version: (u8, u8), chunks: &[(&str, u8, Vec<u8>, usize)]) -> Vec<u8> {
+        let mut data = vec![b'R', b'W', version.0, version.1];
+        if version.0 == 2 {
+            data.resize(4 + 1 + 83 + 8, 0);
+            data.extend_from_slice(&104u16.to_le_bytes());
+            data.extend_from_slice(&32u16.to_le_bytes());
+        } else {
+            data.resize(4 + 256 + 8, 0);
+        }
+        data.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        let mut offset = data.len() + chunks.len() * 32;
+        for (path, compression, stored, size) in chunks {
+            data.extend_from_slice(&path_hash(path).to_le_bytes());
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            data.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(*size as u32).to_le_bytes());
+            data.extend_from_slice(&[*compression, 0, 0, 0]);
+            data.extend_from_slice(&0u64.to_le_bytes());
+            offset += stored.len();
+        }
+        for (_, _, stored, _) in chunks {
+            data.extend_from_slice(stored);
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_bins_from_wad() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+        bin.sections.insert("entries".to_string(), BinValue::Map {
+            key_type: BinType::Hash,
+            value_type: BinType::Embed,
+            items: vec![],
+        });
+        let bytes = write_bin(&bin).unwrap();
+        let mut multi = b"head".to_vec();
+        multi.extend(zstd_stored(&bytes[..10]));
+        multi.extend(zstd_stored(&bytes[10..]));
+        let data = build_wad((3, 4), &[
+            ("data/ahri.bin", 3, zstd_stored(&bytes), bytes.len()),
+            ("assets/icon.dds", 0, b"DDS texture".to_vec(), 11),
+            ("data/multi.bin", 4, multi, bytes.len() + 4),
+            ("data/raw.bin", 0, bytes.clone(), bytes.len()),
+        ]);
+
+        let wad = Wad::parse(&data).unwrap();
+        assert_eq!(wad.version(), (3, 4));
+        assert_eq!(wad.chunks().len(), 4);
+        let chunk = wad.chunk_by_path("DATA/Ahri.bin").unwrap();
+        assert_eq!(wad.read_bin(chunk).unwrap(), bin);
+        assert_eq!(wad.read_chunk(wad.chunk_by_path("assets/icon.dds").unwrap()).unwrap(), b"DDS texture");
+
+        let multi = wad.chunk_by_path("data/multi.bin").unwrap();
+        let mut expected = b"head".to_vec();
+        expected.extend_from_slice(&bytes);
+        assert_eq!(wad.read_chunk(multi).unwrap(), expected);
+        assert!(!wad.is_bin(multi).unwrap());
+
+        let found: Vec<_> = wad.bins().map(|(chunk, bin)| (chunk.path_hash, bin.unwrap())).collect();
+        assert_eq!(found, [(path_hash("data/ahri.bin"), bin.clone()), (path_hash("data/raw.bin"), bin)]);
+
+        assert!(matches!(Wad::parse(b"RW\x03\x04"), Err(WadError::Truncated)));
+        assert!(matches!(Wad::parse(b"PROP"), Err(WadError::InvalidMagic)));
+    }
+
+    #[test]
+    fn test_edit_wad() {
+        let mut bin = Bin::new();
+        bin.sections.insert("type".to_string(), BinValue::String("PROP".to_string()));
+        bin.sections.insert("version".to_string(), BinValue::U32(3));
+        bin.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![] });
+        bin.sections.insert("entries".to_string(), BinValue::Map { key_type: BinType::Hash, value_type: BinType::Embed, items: vec![] });
+        let bytes = write_bin(&bin).unwrap();
+        let multi = [b"head".as_slice(), &zstd_stored(&bytes)].concat();
+        let data = build_wad((3, 4), &[
+            ("data/ahri.bin", 3, zstd_stored(&bytes), bytes.len()),
+            ("assets/icon.dds", 0, b"DDS texture".to_vec(), 11),
+            ("data/multi.bin", 4, multi, bytes.len() + 4),
+        ]);
+        let wad = Wad::parse(&data).unwrap();
+
+        let mut edited = bin.clone();
+        edited.sections.insert("linked".to_string(), BinValue::List { value_type: BinType::String, items: vec![BinValue::String("a.bin".to_string())] });
+        let large: Vec<u8> = (0..300_000u32).map(|i| (i.wrapping_mul(i) >> 7) as u8).collect();
+        let mut edit = wad.edit();
+        edit.set_bin("DATA/Ahri.bin", &edited).unwrap();
+        edit.set_path("assets/icon.dds", b"DDS new texture".to_vec());
+        edit.set_path("assets/large.dds", large.clone());
+        edit.set_path("assets/empty.dds", Vec::new());
+        let written = edit.write().unwrap();
+
+        let wad = Wad::parse(&written).unwrap();
+        assert_eq!(wad.version(), (3, 4));
+        let hashes: Vec<_> = wad.chunks().iter().map(|chunk| chunk.path_hash).collect();
+        assert!(hashes.is_sorted() && hashes.len() == 5);
+        let ahri = wad.chunk_by_path("data/ahri.bin").unwrap();
+        assert_eq!(ahri.compression, WadCompression::Zstd);
+        assert_eq!(wad.read_bin(ahri).unwrap(), edited);
+        assert_eq!(ahri.checksum, crate::hash::xxh3_64(wad.raw_chunk(ahri).unwrap()));
+        let icon = wad.chunk_by_path("assets/icon.dds").unwrap();
+        assert_eq!((icon.compression, wad.read_chunk(icon).unwrap()), (WadCompression::None, b"DDS new texture".to_vec()));
+        assert_eq!(wad.read_chunk(wad.chunk_by_path("assets/large.dds").unwrap()).unwrap(), large);
+        assert!(wad.read_chunk(wad.chunk_by_path("assets/empty.dds").unwrap()).unwrap().is_empty());
+        let multi = wad.chunk_by_path("data/multi.bin").unwrap();
+        assert_eq!(wad.read_chunk(multi).unwrap(), [b"head".as_slice(), &bytes].concat());
+        let original = Wad::parse(&data).unwrap();
+        assert_eq!(wad.raw_chunk(multi).unwrap(), original.raw_chunk(original.chunk(multi.path_hash).unwrap()).unwrap());
+
+        let large = &large[..70_000];
+        assert_eq!(inflate::gunzip(&inflate::gzip_stored(large), usize::MAX).unwrap(), large);
+        assert_eq!(crate::hash::xxh3_64(b""), 0x2d06800538d394c2);
+        let input: Vec<u8> = (0..1025u32).map(|i| (i * 7 + 3) as u8).collect();
+        assert_eq!(crate::hash::xxh3_64(&input), 0x806c2072ed713576);
+    }
+
+    #[test]
+    fn test_edit_old_wad_checksums() {
+        for version in [(2, 0), (3, 0)] {
+            let data = build_wad(version, &[
+                ("assets/icon.dds", 0, b"DDS texture".to_vec(), 11),
+                ("assets/map.dds", 3, zstd_stored(b"DDS map"), 7),
+            ]);
+            let wad = Wad::parse(&data).unwrap();
+            let mut edit = wad.edit();
+            edit.set_path("assets/icon.dds", b"DDS new texture".to_vec());
+            edit.set_path("assets/new.dds", b"DDS added".to_vec());
+            let written = edit.write().unwrap();
+
+            let wad = Wad::parse(&written).unwrap();
+            assert_eq!(wad.version(), version);
+            for path in ["assets/icon.dds", "assets/new.dds"] {
+                let chunk = wad.chunk_by_path(path).unwrap();
+                let digest = crate::hash::sha256(wad.raw_chunk(chunk).unwrap());
+                assert_eq!(chunk.checksum.to_le_bytes(), digest[..8]);
+            }
+            let map = wad.chunk_by_path("assets/map.dds").unwrap();
+            assert_eq!((map.checksum, wad.read_chunk(map).unwrap()), (0, b"DDS map".to_vec()));
+        }
+
+        let digest = |data: &[u8]| crate::hash::sha256(data).iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        assert_eq!(digest(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(digest(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        );
+        assert_eq!(digest(&[b'a'; 1000]), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+    }
+
+    #[test]
+    fn test_decompress_reference_output() {
+        // Produced by the zstd and gzip command line tools at their highest levels
+        let expected: String = (0..200u32).map(|i| format!("Characters/Champ{}/Skins/Skin{}: {}\n", i % 13, i % 7, i * i % 97)).collect();
+        assert_eq!(zstd::decompress(ZSTD_REFERENCE, usize::MAX).unwrap(), expected.as_bytes());
+        assert_eq!(inflate::gunzip(GZIP_REFERENCE, usize::MAX).unwrap(), expected.as_bytes());
+        assert!(zstd::decompress(&ZSTD_REFERENCE[..ZSTD_REFERENCE.len() - 3], usize::MAX).is_err());
+        assert!(inflate::gunzip(&GZIP_REFERENCE[..GZIP_REFERENCE.len() - 3], usize::MAX).is_err());
+    }
+
+    const ZSTD_REFERENCE: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x64, 0x9b, 0x19, 0x8d, 0x11, 0x00, 0x06, 0x1e, 0x40, 0x18, 0x90, 0x29, 0x19, 0x0e, 0xfd, 0xb2, 0x50, 0x7a, 0x04, 0xfb,
+        0x2b, 0x45, 0x45, 0xa4, 0x94, 0x49, 0xa6, 0x94, 0x12, 0xcc, 0x9a, 0x35, 0x51, 0x17, 0x45, 0x00, 0x34, 0x00, 0x33, 0x00, 0x73, 0x85, 0xb9, 0x6d,
+        0x76, 0xec, 0x55, 0x15, 0xda, 0xc4, 0xbc, 0x15, 0x29, 0xf3, 0x5d, 0xc9, 0x71, 0x62, 0xcc, 0x99, 0x5b, 0x63, 0xa3, 0xf2, 0xa9, 0x3c, 0xef, 0x50,
+        0xf8, 0x85, 0x43, 0x41, 0xe4, 0x1b, 0x0a, 0x76, 0x0d, 0x85, 0xd6, 0x43, 0x81, 0xdc, 0x50, 0xd8, 0x0c, 0x62, 0x28, 0x84, 0x02, 0x04, 0x05, 0x0c,
+        0x04, 0x38, 0x88, 0x04, 0x07, 0x0e, 0x14, 0x24, 0x0c, 0x58, 0x20, 0x00, 0x21, 0x01, 0x81, 0x00, 0x80, 0x22, 0xe4, 0x77, 0x48, 0x9a, 0x25, 0x48,
+        0xae, 0x68, 0xdb, 0x63, 0xd2, 0xda, 0xe8, 0xf3, 0x9f, 0x2c, 0xf1, 0xb3, 0xb5, 0xa0, 0xcc, 0xd6, 0x89, 0x75, 0x4a, 0x86, 0x6a, 0x46, 0xde, 0x92,
+        0x9b, 0x56, 0x96, 0xe4, 0xfd, 0x71, 0x6e, 0xdb, 0x1a, 0xdb, 0xab, 0x88, 0xc8, 0xb1, 0x9c, 0x48, 0x86, 0x96, 0x97, 0x18, 0x12, 0x43, 0xa1, 0x90,
+        0xaa, 0x57, 0xf6, 0xab, 0x5c, 0x4c, 0x2b, 0xc5, 0xaf, 0xe4, 0xd8, 0x2c, 0xf9, 0xe5, 0x57, 0xbe, 0x5a, 0xcc, 0x1f, 0x85, 0x7b, 0xa3, 0xcc, 0xf9,
+        0xdb, 0x28, 0xd6, 0x32, 0x69, 0x3c, 0x73, 0xca, 0x6a, 0xbe, 0xc2, 0x2e, 0x26, 0x38, 0x4e, 0xb6, 0x57, 0xcc, 0xae, 0x15, 0xd5, 0xe3, 0x7a, 0x04,
+        0x06, 0xf2, 0xd5, 0x62, 0xfe, 0x84, 0xe5, 0x8f, 0xea, 0x31, 0x87, 0x2b, 0x2f, 0xd5, 0x06, 0x57, 0x79, 0xd9, 0xab, 0x3a, 0x82, 0xe4, 0x43, 0x22,
+        0x21, 0x6b, 0xd2, 0xed, 0xf4, 0x93, 0x59, 0xda, 0x5b, 0x42, 0x35, 0x32, 0x9d, 0x8e, 0x4c, 0x8d, 0x50, 0xeb, 0x34, 0x96, 0xfc, 0x9f, 0x36, 0xe9,
+        0x5e, 0x44, 0x5a, 0x88, 0x02, 0x81, 0x20, 0xa8, 0x21, 0x74, 0x7b, 0xc8, 0x34, 0x06, 0x81, 0xaa, 0x40, 0x02, 0x09, 0x50, 0x92, 0x07, 0x22, 0x08,
+        0x20, 0x08, 0x1c, 0x21, 0x68, 0x44, 0x08, 0x23, 0x09, 0x89, 0xf0, 0x08, 0x21, 0xb8, 0x6e, 0xcf, 0x7a, 0x1f, 0x54, 0xac, 0x64, 0x21, 0x24, 0x8a,
+        0xf0, 0xff, 0x08, 0x09, 0x48, 0xf8, 0xfb, 0x5f, 0x42, 0x1d, 0x8c, 0x2e, 0x84, 0xed, 0x9e, 0x7e, 0x79, 0xb3, 0x31, 0x9f, 0x21, 0x9c, 0x59, 0x8b,
+        0xe5, 0xcb, 0xce, 0x94, 0xb3, 0xb8, 0xc0, 0x70, 0x4e, 0x47, 0x0f, 0x10, 0xc8, 0xaa, 0xa3, 0xa1, 0xbd, 0x5d, 0x6f, 0xa3, 0x8c, 0xa4, 0x38, 0xbd,
+        0x2c, 0xe9, 0xe1, 0x9a, 0xbb, 0x38, 0xea, 0x6a, 0x12, 0x42, 0x9b, 0x21, 0x47, 0xa5, 0x38, 0x98, 0x93, 0xbb, 0x28, 0x56, 0x25, 0xcf, 0x17, 0xd7,
+        0x6f, 0xb4, 0x7c, 0x90, 0xbf, 0x7a, 0x85, 0x50, 0x5d, 0x84, 0xf4, 0x11, 0x7e, 0x90, 0x90, 0xb6, 0x5d, 0xc2, 0xad, 0xcd, 0x32, 0x47, 0x69, 0x36,
+        0xf2, 0x4c, 0xe3, 0x68, 0x65, 0x90, 0x9f, 0x5a, 0x87, 0x86, 0x47, 0x24, 0x4b, 0x79, 0xa3, 0x39, 0x8d, 0x10, 0x14, 0x77, 0xbc, 0x80, 0x8e, 0xbb,
+        0xd1, 0x75, 0x93, 0x83, 0xcf, 0x8c, 0x03, 0xf8, 0xc6, 0x71, 0xb5, 0x5d, 0x70, 0x13, 0xe1, 0x2f, 0x15, 0x87, 0x6e, 0x71, 0xee, 0xa2, 0x5c, 0x83,
+        0x66, 0x37, 0xf9, 0x13, 0x6e, 0x42, 0x5c, 0xfa, 0x14, 0x00, 0x84, 0x87, 0xbe, 0xf7, 0x8e, 0xa3, 0x63, 0x04, 0xd6, 0xc3, 0xff, 0x4e, 0x05, 0x09,
+        0x85, 0x46, 0xb8, 0x10, 0x38, 0xd8, 0xf1, 0xe7, 0x6a, 0x13, 0xe0, 0xcf, 0x16, 0x99, 0xe8, 0x62, 0xcd, 0xc7, 0xd4, 0x9a, 0xcc, 0x32, 0xd9, 0x86,
+        0x8b, 0x14, 0x57, 0x7d, 0x39, 0x00, 0x97, 0x8a, 0x9a, 0x43, 0xc7, 0x62, 0xe7, 0x66, 0x3f, 0x62, 0xbe, 0x52, 0x0a, 0xc3, 0xa7, 0x08, 0x00, 0x32,
+        0xca, 0xab, 0x5e, 0x06, 0x71, 0x7e, 0xa1, 0x9c, 0x89, 0xb8, 0xc2, 0x7e, 0xbb, 0x26, 0x37, 0x03, 0xa5, 0x9d, 0x81, 0x3c, 0xa2, 0x3a, 0x21, 0x51,
+        0xea, 0x43, 0x00, 0xb8, 0xd5, 0xd8, 0x00, 0x34, 0xd3, 0x46, 0x76, 0xcf, 0x1f, 0x68, 0xaa, 0x1a, 0x00, 0x55, 0x01, 0x22, 0x36, 0xd5, 0x87,
+    ];
+
+    const GZIP_REFERENCE: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xa5, 0x99, 0x41, 0x6e, 0x1c, 0x31, 0x0c, 0x04, 0xef, 0x7e, 0xc5, 0xfe, 0xc0, 0x22,
+        0x29, 0x52, 0xd2, 0x5e, 0xfd, 0x84, 0xbc, 0xc0, 0x08, 0x02, 0x38, 0x08, 0x12, 0x04, 0x76, 0xfe, 0x8f, 0x68, 0x7c, 0xda, 0xe1, 0x34, 0xd8, 0x07,
+        0x5f, 0x7c, 0x22, 0x3c, 0xd3, 0xdb, 0x52, 0xb3, 0x6b, 0xf7, 0xe5, 0xed, 0xf5, 0xfd, 0xf5, 0xfb, 0xbf, 0x1f, 0xef, 0x1f, 0xcf, 0x2f, 0x6f, 0xaf,
+        0xbf, 0xff, 0xb6, 0xe7, 0x6f, 0xbf, 0x7e, 0xfe, 0xf9, 0xf8, 0xfc, 0xdb, 0xee, 0xb7, 0xf6, 0xf4, 0x92, 0x06, 0xe4, 0x61, 0x40, 0xee, 0x37, 0xb9,
+        0x0c, 0xe8, 0xc3, 0x80, 0xde, 0x6f, 0xfd, 0x32, 0x60, 0x0f, 0x03, 0x76, 0xbf, 0xad, 0xcb, 0x40, 0x7f, 0x18, 0xe8, 0xfb, 0x11, 0x71, 0x99, 0xf0,
+        0x87, 0x09, 0xbf, 0xdf, 0xd4, 0x2f, 0x13, 0xf1, 0x30, 0x11, 0xf7, 0x9b, 0x5d, 0xff, 0xc7, 0x38, 0x2b, 0xed, 0xd7, 0xf7, 0x98, 0x67, 0xa9, 0x71,
+        0x95, 0xb2, 0xce, 0x5a, 0xe7, 0xf5, 0xd3, 0x90, 0x76, 0x56, 0x6b, 0xd7, 0x09, 0x39, 0xcb, 0xd5, 0xeb, 0x63, 0x44, 0xcf, 0x7a, 0xfb, 0xb8, 0x8c,
+        0xb4, 0xb3, 0xde, 0xa1, 0xa5, 0x71, 0x5b, 0xaf, 0x96, 0xc6, 0x6d, 0xb9, 0x26, 0xa5, 0x73, 0x5b, 0x6e, 0x68, 0x69, 0xdd, 0xe1, 0xad, 0x97, 0xd6,
+        0x6d, 0xb5, 0x66, 0xa5, 0x75, 0x5b, 0xec, 0x68, 0xa5, 0x75, 0x5b, 0xac, 0x68, 0x69, 0xdd, 0x16, 0xeb, 0x56, 0x5a, 0xb7, 0xd5, 0xae, 0xa8, 0xad,
+        0x3b, 0x4e, 0x72, 0xaf, 0xbd, 0x3b, 0xf4, 0x4a, 0xed, 0xdd, 0x16, 0xdc, 0xad, 0xf4, 0x6e, 0x0b, 0x5e, 0xbd, 0xf4, 0x6e, 0x0b, 0xf6, 0x56, 0x9a,
+        0xb7, 0x05, 0xcf, 0xd2, 0xbb, 0xe3, 0x30, 0x7b, 0xe9, 0xdd, 0x96, 0xab, 0xa3, 0xf4, 0x6e, 0xab, 0x9d, 0xb3, 0xf4, 0x6e, 0x8b, 0xf5, 0x5e, 0x7a,
+        0x77, 0x5c, 0xdd, 0xda, 0xbb, 0x2d, 0x76, 0xae, 0xd2, 0xbb, 0x2d, 0x36, 0xc8, 0xb5, 0x3b, 0x0e, 0xb3, 0xd7, 0xde, 0x6d, 0xbd, 0x42, 0xbc, 0x3b,
+        0x04, 0x47, 0xe9, 0xdd, 0x16, 0x1c, 0x51, 0x7a, 0x77, 0xdc, 0xdd, 0x59, 0x7a, 0x77, 0x64, 0x95, 0x96, 0xe6, 0x6d, 0xc1, 0x32, 0x4b, 0xf3, 0x0e,
+        0x7b, 0x4b, 0xef, 0xb6, 0xda, 0x55, 0xdf, 0xbb, 0x43, 0xac, 0x97, 0xde, 0x6d, 0xb1, 0xa3, 0x8e, 0xcc, 0xe3, 0xee, 0x7a, 0xe9, 0xdd, 0x11, 0x54,
+        0x56, 0x7b, 0xd7, 0xf0, 0x48, 0xda, 0x42, 0xe0, 0x39, 0x92, 0xf6, 0x10, 0x78, 0xd9, 0x46, 0x05, 0xa7, 0x68, 0x06, 0x1f, 0x5a, 0x4a, 0xe6, 0x28,
+        0xad, 0x0b, 0x6a, 0x5d, 0x83, 0xf6, 0xfb, 0x59, 0x6d, 0xaf, 0xef, 0x9d, 0xc2, 0x63, 0x38, 0xe8, 0x51, 0x9e, 0x69, 0xed, 0x4a, 0xe9, 0x9d, 0xe3,
+        0x4b, 0x95, 0xf6, 0x10, 0xba, 0x9a, 0x69, 0x11, 0x81, 0xfb, 0x2d, 0x69, 0x15, 0x81, 0x90, 0x48, 0xd9, 0xec, 0x75, 0x66, 0xe2, 0xb0, 0x4a, 0xc9,
+        0x0c, 0x02, 0xcf, 0x92, 0xbb, 0x75, 0x68, 0x06, 0xca, 0x5d, 0x4f, 0x9b, 0xa8, 0x95, 0xde, 0x09, 0x8c, 0xff, 0x91, 0x16, 0x91, 0x95, 0xde, 0xe1,
+        0x3d, 0xb4, 0xd2, 0x1a, 0xea, 0xb5, 0x77, 0x8e, 0x57, 0x62, 0x5e, 0x44, 0x56, 0x7b, 0xd7, 0xe0, 0x72, 0x4e, 0xd9, 0x3c, 0xea, 0x92, 0xa9, 0xb0,
+        0x24, 0x28, 0x2d, 0x1a, 0x96, 0x92, 0xb9, 0x2e, 0x2b, 0x0e, 0x0b, 0x8f, 0x9f, 0xe5, 0x6a, 0xe9, 0x5d, 0x83, 0xb5, 0x6b, 0xa4, 0xbb, 0x3b, 0x4a,
+        0xef, 0x14, 0xf6, 0xbf, 0x45, 0x3b, 0x64, 0x5a, 0x43, 0x93, 0x5c, 0x3b, 0x87, 0x6d, 0x56, 0xd2, 0x22, 0xea, 0x75, 0x64, 0x36, 0x58, 0xab, 0x53,
+        0x32, 0x83, 0x6a, 0x9e, 0x82, 0x59, 0xe2, 0xcb, 0x84, 0xd0, 0x19, 0x20, 0x08, 0xe3, 0x83, 0xc6, 0xf0, 0x40, 0x18, 0x1d, 0x50, 0x38, 0x58, 0x94,
+        0x0d, 0x24, 0x38, 0x1c, 0x38, 0x85, 0x03, 0x0b, 0x06, 0x07, 0xc0, 0xd7, 0x94, 0xc9, 0xe0, 0x70, 0xa4, 0x48, 0x9e, 0x94, 0x0e, 0x8c, 0xc1, 0x81,
+        0x76, 0x06, 0x07, 0xe0, 0xb2, 0x64, 0x38, 0x50, 0x06, 0x07, 0x94, 0x0d, 0x4c, 0x18, 0x1b, 0x80, 0xe8, 0xc8, 0x6c, 0xb0, 0x9c, 0xb2, 0x81, 0x19,
+        0x65, 0x03, 0x90, 0x84, 0x29, 0x93, 0x45, 0x29, 0x1b, 0x18, 0x63, 0x83, 0x15, 0x0c, 0x0e, 0xc0, 0x72, 0x48, 0x70, 0xb0, 0x84, 0xc1, 0x41, 0x37,
+        0x06, 0x07, 0x8b, 0xc2, 0x01, 0x58, 0x96, 0x93, 0xed, 0xdb, 0xcc, 0x06, 0x4e, 0xd9, 0x00, 0xec, 0xfd, 0xcc, 0x06, 0xa0, 0x3c, 0x64, 0x36, 0x00,
+        0x0d, 0x24, 0x85, 0xb2, 0x2a, 0x63, 0x03, 0x50, 0x85, 0x94, 0xf6, 0xa9, 0xc4, 0x06, 0xe6, 0x8c, 0x0d, 0x44, 0x18, 0x1c, 0x80, 0x72, 0x98, 0xe0,
+        0x80, 0x14, 0xcc, 0x0e, 0x4b, 0x6a, 0x82, 0x03, 0x50, 0x74, 0x17, 0x2d, 0xcb, 0x19, 0x0e, 0x82, 0xb2, 0xc1, 0x32, 0xca, 0x06, 0xa0, 0xf9, 0xa7,
+        0x68, 0x1e, 0x8b, 0xb1, 0xc1, 0x70, 0xc6, 0x06, 0x80, 0x63, 0x8c, 0xc2, 0x50, 0x82, 0x83, 0xe1, 0x0c, 0x0e, 0xc0, 0x9b, 0x06, 0x55, 0x9b, 0xe0,
+        0x60, 0x19, 0x83, 0x83, 0x60, 0x6c, 0xc0, 0xac, 0xc3, 0x9c, 0x9b, 0xd9, 0xa0, 0x4f, 0xca, 0x06, 0x11, 0x8c, 0x0d, 0x66, 0x30, 0x36, 0x10, 0x61,
+        0x6c, 0x60, 0x4e, 0xd9, 0x40, 0x28, 0x1b, 0x2c, 0x06, 0x07, 0xaa, 0x0c, 0x0e, 0x9c, 0xc2, 0xc1, 0x9c, 0x0c, 0x0e, 0x40, 0xde, 0x25, 0x38, 0x60,
+        0xa1, 0xe9, 0x28, 0x78, 0x85, 0x7f, 0x49, 0x95, 0xd9, 0x60, 0x75, 0xc6, 0x06, 0x60, 0x8b, 0x08, 0xdd, 0x44, 0x29, 0x99, 0x7b, 0x67, 0x6c, 0x00,
+        0x36, 0x62, 0x62, 0x03, 0xb0, 0x55, 0x9d, 0x7e, 0x09, 0x99, 0xe1, 0xa0, 0x31, 0x38, 0x30, 0x63, 0x70, 0xb0, 0x9c, 0xc1, 0x01, 0x2b, 0x2b, 0x1d,
+        0x36, 0x9e, 0x4c, 0x07, 0x4a, 0xe1, 0x60, 0x28, 0x83, 0x03, 0xd0, 0xde, 0x32, 0x1c, 0x74, 0x06, 0x07, 0xc6, 0xd8, 0x60, 0x0a, 0x83, 0x83, 0xa0,
+        0x74, 0xd0, 0xd7, 0xd7, 0x7f, 0x3e, 0x00, 0xc5, 0x3c, 0x01, 0x82, 0xc4, 0xd7, 0x09, 0xa1, 0x53, 0x40, 0x10, 0xca, 0x07, 0x8d, 0xe1, 0x81, 0x30,
+        0x3a, 0xa0, 0x70, 0xb0, 0x18, 0x1b, 0x48, 0x50, 0x38, 0xf0, 0xa7, 0xff, 0x12, 0x59, 0x76, 0x09, 0x9b, 0x1a, 0x00, 0x00,
+    ];
+}
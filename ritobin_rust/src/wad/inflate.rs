@@ -0,0 +1,378 @@
+//! Deflate (RFC 1951) and gzip (RFC 1952) decoding for WAD chunks.
+
+type Result<T> = std::result::Result<T, &'static str>;
+
+const TRUNCATED: &str = "deflate data ended early";
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decode a gzip member, checking its CRC and length unless stopped early
+/// after `limit` bytes
+pub(crate) fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        return Err("not a gzip stream");
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 0x04 != 0 {
+        let extra = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        pos += 2 + extra;
+    }
+    // File name and comment, zero-terminated
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let len = data.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or(TRUNCATED)?;
+            pos += len + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+
+    let (out, used, finished) = inflate(data.get(pos..).ok_or(TRUNCATED)?, limit)?;
+    if finished {
+        let trailer = data.get(pos + used..pos + used + 8).ok_or(TRUNCATED)?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        if crc != crc32(&out) || len != out.len() as u32 {
+            return Err("gzip checksum mismatch");
+        }
+    }
+    Ok(out)
+}
+
+/// `data` as a gzip member of stored deflate blocks, read like any other
+/// gzip stream but no smaller
+pub(crate) fn gzip_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 0xffff * 5 + 23);
+    // No flags, no time, unknown OS
+    out.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+    let count = data.len().div_ceil(0xffff).max(1);
+    for (i, block) in data.chunks(0xffff).chain(data.is_empty().then_some(data)).enumerate() {
+        // Final bit and stored block type, padded to the byte
+        out.push((i + 1 == count) as u8);
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Decode a raw deflate stream, stopping after the block that takes the
+/// output to `limit` bytes
+///
+/// Returns the output, the bytes read and whether the final block was reached.
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize, bool)> {
+    let mut bits = Bits { data, pos: 0, buffer: 0, count: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let header = bits.take(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("corrupt stored deflate block");
+                }
+                out.extend_from_slice(bits.take(len as usize)?);
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("reserved deflate block type"),
+        }
+        if last {
+            return Ok((out, bits.pos, true));
+        }
+        if out.len() >= limit {
+            return Ok((out, bits.pos, false));
+        }
+    }
+}
+
+/// Reads bits least significant first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn read(&mut self, len: u32) -> Result<u32> {
+        while self.count < len {
+            let byte = *self.data.get(self.pos).ok_or(TRUNCATED)?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << len) - 1);
+        self.buffer >>= len;
+        self.count -= len;
+        Ok(value)
+    }
+
+    /// Drop the bits left in the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or(TRUNCATED)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 16],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err("over-subscribed deflate code");
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid deflate code")
+    }
+}
+
+/// Read the code length tables of a dynamic block
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literal_count = bits.read(5)? as usize + 257;
+    let distance_count = bits.read(5)? as usize + 1;
+    let code_count = bits.read(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err("corrupt deflate block header");
+    }
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[i] = bits.read(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or("corrupt deflate block header")?, 3 + bits.read(2)?),
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err("corrupt deflate block header");
+        }
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return Err("deflate block has no end code");
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+/// Decode the literals and matches of a compressed block
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let index = symbol - 257;
+        let (&base, &extra) = LENGTH_BASE.get(index).zip(LENGTH_EXTRA.get(index)).ok_or("invalid deflate length")?;
+        let len = base as usize + bits.read(extra as u32)? as usize;
+        let index = distances.decode(bits)? as usize;
+        let (&base, &extra) = DISTANCE_BASE.get(index).zip(DISTANCE_EXTRA.get(index)).ok_or("invalid deflate distance")?;
+        let distance = base as usize + bits.read(extra as u32)? as usize;
+        if distance > out.len() {
+            return Err("deflate distance too far back");
+        }
+        let from = out.len() - distance;
+        for k in 0..len {
+            out.push(out[from + k]);
+        }
+    }
+}
+
+/// CRC-32 as used by gzip
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs deflate bits least significant first
+    #[derive(Default)]
+    struct BitWriter {
+        out: Vec<u8>,
+        count: u32,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, len: u32) {
+            for i in 0..len {
+                if self.count.is_multiple_of(8) {
+                    self.out.push(0);
+                }
+                *self.out.last_mut().unwrap() |= ((value >> i & 1) as u8) << (self.count % 8);
+                self.count += 1;
+            }
+        }
+
+        /// Huffman codes are packed most significant bit first
+        fn code(&mut self, code: u32, len: u32) {
+            for i in (0..len).rev() {
+                self.bits(code >> i & 1, 1);
+            }
+        }
+    }
+
+    /// A final block of fixed codes: `a`, then a 3-byte match `distance_code` back
+    fn fixed_block(distance_code: u32) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(1, 1);
+        w.bits(1, 2);
+        w.code(0x30 + b'a' as u32, 8);
+        w.code(257 - 256, 7);
+        w.code(distance_code, 5);
+        w.code(0, 7);
+        w.out
+    }
+
+    /// A final dynamic block header with code length code lengths `lengths`,
+    /// in storage order
+    fn dynamic_header(lengths: &[u32]) -> BitWriter {
+        let mut w = BitWriter::default();
+        w.bits(1, 1);
+        w.bits(2, 2);
+        w.bits(0, 5);
+        w.bits(0, 5);
+        w.bits(lengths.len() as u32 - 4, 4);
+        for &len in lengths {
+            w.bits(len, 3);
+        }
+        w
+    }
+
+    #[test]
+    fn test_inflate_blocks() {
+        // One back is the `a` just written, two back is before the start
+        assert_eq!(inflate(&fixed_block(0), usize::MAX).unwrap().0, b"aaaa");
+        assert_eq!(inflate(&fixed_block(1), usize::MAX).unwrap_err(), "deflate distance too far back");
+
+        assert_eq!(inflate(&[0x01, 3, 0, 0xfc, 0xff, b'a', b'b', b'c'], usize::MAX).unwrap(), (b"abc".to_vec(), 8, true));
+        assert_eq!(inflate(&[0x01, 3, 0, 0xfc, 0xfe, b'a', b'b', b'c'], usize::MAX).unwrap_err(), "corrupt stored deflate block");
+        assert_eq!(inflate(&[0x01, 3, 0, 0xfc, 0xff, b'a'], usize::MAX).unwrap_err(), TRUNCATED);
+        assert_eq!(inflate(&[0x07], usize::MAX).unwrap_err(), "reserved deflate block type");
+
+        // Four 1-bit codes
+        let w = dynamic_header(&[1, 1, 1, 1]);
+        assert_eq!(inflate(&w.out, usize::MAX).unwrap_err(), "over-subscribed deflate code");
+        // Only codes 0 and 18 (a run of zeros), used for all 258 lengths
+        let mut w = dynamic_header(&[0, 0, 1, 1]);
+        w.code(1, 1);
+        w.bits(138 - 11, 7);
+        w.code(1, 1);
+        w.bits(120 - 11, 7);
+        assert_eq!(inflate(&w.out, usize::MAX).unwrap_err(), "deflate block has no end code");
+        // A run that goes past the last length
+        let mut w = dynamic_header(&[0, 0, 1, 1]);
+        for _ in 0..2 {
+            w.code(1, 1);
+            w.bits(127, 7);
+        }
+        assert_eq!(inflate(&w.out, usize::MAX).unwrap_err(), "corrupt deflate block header");
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let blocks = [0x00, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x01, 3, 0, 0xfc, 0xff, b'd', b'e', b'f'];
+        assert_eq!(inflate(&blocks, 2).unwrap(), (b"abc".to_vec(), 8, false));
+        assert_eq!(inflate(&blocks, 6).unwrap(), (b"abcdef".to_vec(), 16, true));
+
+        // Stopping early skips the checksum, reading to the end doesn't
+        let data: Vec<u8> = (0..70_000u32).map(|i| (i % 251) as u8).collect();
+        let mut gzip = gzip_stored(&data);
+        let crc = gzip.len() - 8;
+        gzip[crc] ^= 1;
+        assert_eq!(gunzip(&gzip, 10).unwrap(), &data[..0xffff]);
+        assert_eq!(gunzip(&gzip, usize::MAX).unwrap_err(), "gzip checksum mismatch");
+        gzip[crc] ^= 1;
+        assert_eq!(gunzip(&gzip, usize::MAX).unwrap(), data);
+        assert_eq!(gunzip(&gzip[..gzip.len() - 4], usize::MAX).unwrap_err(), TRUNCATED);
+        assert_eq!(gunzip(&gzip[1..], usize::MAX).unwrap_err(), "not a gzip stream");
+    }
+}
@@ -0,0 +1,754 @@
+//! Zstandard decoding (RFC 8878) for WAD chunks.
+//!
+//! Decodes everything the format allows except dictionaries, which League
+//! archives don't use. The whole frame output is kept, so the window size is
+//! never a limit. Data is written back with [`store`], uncompressed.
+
+use crate::hash::xxh64_exact;
+
+type Result<T> = std::result::Result<T, &'static str>;
+
+const MAGIC: u32 = 0xFD2F_B528;
+const TRUNCATED: &str = "zstd data ended early";
+/// Largest block a frame may hold
+const MAX_BLOCK: usize = 128 * 1024;
+
+/// Baseline and extra bits of each literals length code
+const LL_CODES: [(u32, u32); 36] = [
+    (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0),
+    (8, 0), (9, 0), (10, 0), (11, 0), (12, 0), (13, 0), (14, 0), (15, 0),
+    (16, 1), (18, 1), (20, 1), (22, 1), (24, 2), (28, 2), (32, 3), (40, 3),
+    (48, 4), (64, 6), (128, 7), (256, 8), (512, 9), (1024, 10), (2048, 11), (4096, 12),
+    (8192, 13), (16384, 14), (32768, 15), (65536, 16),
+];
+
+/// Baseline and extra bits of each match length code
+const ML_CODES: [(u32, u32); 53] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+    (11, 0), (12, 0), (13, 0), (14, 0), (15, 0), (16, 0), (17, 0), (18, 0),
+    (19, 0), (20, 0), (21, 0), (22, 0), (23, 0), (24, 0), (25, 0), (26, 0),
+    (27, 0), (28, 0), (29, 0), (30, 0), (31, 0), (32, 0), (33, 0), (34, 0),
+    (35, 1), (37, 1), (39, 1), (41, 1), (43, 2), (47, 2), (51, 3), (59, 3),
+    (67, 4), (83, 4), (99, 5), (131, 7), (259, 8), (515, 9), (1027, 10), (2051, 11),
+    (4099, 12), (8195, 13), (16387, 14), (32771, 15), (65539, 16),
+];
+
+const LL_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+const ML_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const OF_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+/// Whether `data` starts with a Zstandard frame
+pub(crate) fn is_frame(data: &[u8]) -> bool {
+    data.get(..4).is_some_and(|magic| u32::from_le_bytes(magic.try_into().unwrap()) == MAGIC)
+}
+
+/// Decode every frame in `data`, stopping after the block that takes the
+/// output to `limit` bytes
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() && out.len() < limit {
+        pos += decode_frame(&data[pos..], &mut out, limit)?;
+    }
+    Ok(out)
+}
+
+/// Decode the frame at the start of `data` onto `out`, returning its length
+///
+/// Skippable frames are skipped. Once `out` holds `limit` bytes decoding
+/// stops after the current block and the length returned is meaningless.
+pub(crate) fn decode_frame(data: &[u8], out: &mut Vec<u8>, limit: usize) -> Result<usize> {
+    let mut r = Bytes { data, pos: 0 };
+    let magic = r.uint(4)? as u32;
+    if magic & 0xFFFF_FFF0 == 0x184D_2A50 {
+        let size = r.uint(4)? as usize;
+        r.take(size)?;
+        return Ok(r.pos);
+    }
+    if magic != MAGIC {
+        return Err("not a zstd frame");
+    }
+
+    let descriptor = r.uint(1)? as u8;
+    if descriptor & 0x08 != 0 {
+        return Err("reserved bit set in zstd frame header");
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    if !single_segment {
+        // Window descriptor; the whole output is kept anyway
+        r.uint(1)?;
+    }
+    let dictionary = r.uint([0, 1, 2, 4][(descriptor & 3) as usize])?;
+    if dictionary != 0 {
+        return Err("zstd dictionaries are not supported");
+    }
+    let content_size = match descriptor >> 6 {
+        0 if single_segment => r.uint(1)?,
+        0 => 0,
+        1 => r.uint(2)? + 256,
+        2 => r.uint(4)?,
+        _ => r.uint(8)?,
+    };
+    out.reserve((content_size as usize).min(limit).min(1 << 26));
+
+    let start = out.len();
+    let mut frame = Frame::default();
+    loop {
+        let header = r.uint(3)? as usize;
+        let size = header >> 3;
+        match (header >> 1) & 3 {
+            0 => out.extend_from_slice(r.take(size)?),
+            1 => {
+                let byte = r.uint(1)? as u8;
+                out.resize(out.len() + size, byte);
+            }
+            2 => frame.decode_block(r.take(size)?, out, start)?,
+            _ => return Err("reserved zstd block type"),
+        }
+        if header & 1 != 0 {
+            break;
+        }
+        if out.len() >= limit {
+            return Ok(r.pos);
+        }
+    }
+    if descriptor & 0x04 != 0 {
+        let checksum = r.uint(4)? as u32;
+        if xxh64_exact(&out[start..], 0) as u32 != checksum {
+            return Err("zstd checksum mismatch");
+        }
+    }
+    Ok(r.pos)
+}
+
+/// `data` as a frame of uncompressed blocks with a content checksum, read
+/// like any other frame but no smaller
+pub(crate) fn store(data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 3 + 20);
+    frame.extend_from_slice(&MAGIC.to_le_bytes());
+    // Single segment with an 8-byte content size and a checksum
+    frame.push(0xE4);
+    frame.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    let count = data.len().div_ceil(MAX_BLOCK).max(1);
+    for (i, block) in data.chunks(MAX_BLOCK).chain(data.is_empty().then_some(data)).enumerate() {
+        let header = (block.len() as u32) << 3 | (i + 1 == count) as u32;
+        frame.extend_from_slice(&header.to_le_bytes()[..3]);
+        frame.extend_from_slice(block);
+    }
+    frame.extend_from_slice(&(xxh64_exact(data, 0) as u32).to_le_bytes());
+    frame
+}
+
+/// Little-endian reads from a byte slice
+struct Bytes<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.saturating_add(len)).ok_or(TRUNCATED)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64> {
+        Ok(self.take(len)?.iter().rev().fold(0, |v, &b| (v << 8) | b as u64))
+    }
+}
+
+/// Tables and repeat offsets carried from block to block
+struct Frame {
+    huffman: Option<HuffmanTable>,
+    literal_lengths: Option<FseTable>,
+    offsets: Option<FseTable>,
+    match_lengths: Option<FseTable>,
+    repeats: [usize; 3],
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self { huffman: None, literal_lengths: None, offsets: None, match_lengths: None, repeats: [1, 4, 8] }
+    }
+}
+
+impl Frame {
+    fn decode_block(&mut self, block: &[u8], out: &mut Vec<u8>, frame_start: usize) -> Result<()> {
+        let (literals, used) = self.decode_literals(block)?;
+        self.decode_sequences(&block[used..], &literals, out, frame_start)
+    }
+
+    /// The literals section, and how many bytes of `block` it takes
+    fn decode_literals(&mut self, block: &[u8]) -> Result<(Vec<u8>, usize)> {
+        let byte = |i: usize| block.get(i).map(|&b| b as usize).ok_or(TRUNCATED);
+        let b0 = byte(0)?;
+        let size_format = (b0 >> 2) & 3;
+        if b0 & 3 < 2 {
+            let (size, header) = match size_format {
+                0 | 2 => (b0 >> 3, 1),
+                1 => ((b0 >> 4) + (byte(1)? << 4), 2),
+                _ => ((b0 >> 4) + (byte(1)? << 4) + (byte(2)? << 12), 3),
+            };
+            return if b0 & 3 == 0 {
+                let literals = block.get(header..header + size).ok_or(TRUNCATED)?;
+                Ok((literals.to_vec(), header + size))
+            } else {
+                Ok((vec![byte(header)? as u8; size], header + 1))
+            };
+        }
+
+        let (header, bits) = match size_format {
+            0 | 1 => (3, 10),
+            2 => (4, 14),
+            _ => (5, 18),
+        };
+        let sizes = (0..header).try_fold(0u64, |v, i| Ok::<_, &str>(v | (byte(i)? as u64) << (8 * i)))? >> 4;
+        let regenerated = (sizes & ((1 << bits) - 1)) as usize;
+        let compressed = (sizes >> bits) as usize;
+        let mut data = block.get(header..header + compressed).ok_or(TRUNCATED)?;
+        if b0 & 3 == 2 {
+            let (table, used) = HuffmanTable::read(data)?;
+            self.huffman = Some(table);
+            data = &data[used..];
+        }
+        let table = self.huffman.as_ref().ok_or("zstd literals reuse a Huffman table that was never sent")?;
+
+        let mut literals = Vec::with_capacity(regenerated);
+        if size_format == 0 {
+            table.decode_stream(data, regenerated, &mut literals)?;
+        } else {
+            let jump = |i: usize| data.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize).ok_or(TRUNCATED);
+            let lens = [jump(0)?, jump(2)?, jump(4)?];
+            let per_stream = regenerated.div_ceil(4);
+            let last = regenerated.checked_sub(3 * per_stream).ok_or("corrupt zstd literals")?;
+            let mut streams = &data[6..];
+            for (len, count) in lens.into_iter().zip([per_stream; 3]) {
+                let stream = streams.get(..len).ok_or(TRUNCATED)?;
+                table.decode_stream(stream, count, &mut literals)?;
+                streams = &streams[len..];
+            }
+            table.decode_stream(streams, last, &mut literals)?;
+        }
+        Ok((literals, header + compressed))
+    }
+
+    fn decode_sequences(&mut self, data: &[u8], literals: &[u8], out: &mut Vec<u8>, frame_start: usize) -> Result<()> {
+        let byte = |i: usize| data.get(i).map(|&b| b as usize).ok_or(TRUNCATED);
+        let b0 = byte(0)?;
+        let (count, mut pos) = match b0 {
+            0 => {
+                out.extend_from_slice(literals);
+                return Ok(());
+            }
+            1..=127 => (b0, 1),
+            128..=254 => (((b0 - 128) << 8) + byte(1)?, 2),
+            _ => (byte(1)? + (byte(2)? << 8) + 0x7F00, 3),
+        };
+        let modes = byte(pos)?;
+        pos += 1;
+        select_table(&mut self.literal_lengths, modes >> 6, data, &mut pos, (&LL_DEFAULT, 6), 9)?;
+        select_table(&mut self.offsets, (modes >> 4) & 3, data, &mut pos, (&OF_DEFAULT, 5), 8)?;
+        select_table(&mut self.match_lengths, (modes >> 2) & 3, data, &mut pos, (&ML_DEFAULT, 6), 9)?;
+        let (Some(ll), Some(of), Some(ml)) = (&self.literal_lengths, &self.offsets, &self.match_lengths) else {
+            unreachable!("tables were just selected")
+        };
+
+        let mut bits = BackwardBits::new(data.get(pos..).ok_or(TRUNCATED)?)?;
+        let mut ll_state = bits.read(ll.log) as usize;
+        let mut of_state = bits.read(of.log) as usize;
+        let mut ml_state = bits.read(ml.log) as usize;
+        let mut literal_pos = 0;
+        for i in 0..count {
+            let (ll_entry, of_entry, ml_entry) = (ll.states[ll_state], of.states[of_state], ml.states[ml_state]);
+            let (ll_base, ll_bits) = *LL_CODES.get(ll_entry.symbol as usize).ok_or("corrupt zstd sequences")?;
+            let (ml_base, ml_bits) = *ML_CODES.get(ml_entry.symbol as usize).ok_or("corrupt zstd sequences")?;
+            if of_entry.symbol > 31 {
+                return Err("corrupt zstd sequences");
+            }
+            let offset_value = (1u64 << of_entry.symbol) + bits.read(of_entry.symbol as u32);
+            let match_len = (ml_base as u64 + bits.read(ml_bits)) as usize;
+            let literal_len = (ll_base as u64 + bits.read(ll_bits)) as usize;
+
+            let offset = if offset_value > 3 {
+                let offset = offset_value as usize - 3;
+                self.repeats = [offset, self.repeats[0], self.repeats[1]];
+                offset
+            } else {
+                let index = offset_value as usize - 1 + (literal_len == 0) as usize;
+                let [r0, r1, r2] = self.repeats;
+                let (offset, repeats) = match index {
+                    0 => (r0, [r0, r1, r2]),
+                    1 => (r1, [r1, r0, r2]),
+                    2 => (r2, [r2, r0, r1]),
+                    _ => (r0.wrapping_sub(1), [r0.wrapping_sub(1), r0, r1]),
+                };
+                self.repeats = repeats;
+                offset
+            };
+
+            if i + 1 < count {
+                ll_state = ll_entry.base as usize + bits.read(ll_entry.bits as u32) as usize;
+                ml_state = ml_entry.base as usize + bits.read(ml_entry.bits as u32) as usize;
+                of_state = of_entry.base as usize + bits.read(of_entry.bits as u32) as usize;
+            }
+
+            let copied = literals.get(literal_pos..literal_pos + literal_len).ok_or("corrupt zstd sequences")?;
+            out.extend_from_slice(copied);
+            literal_pos += literal_len;
+            if offset == 0 || offset > out.len() - frame_start {
+                return Err("zstd match offset out of range");
+            }
+            let from = out.len() - offset;
+            if offset >= match_len {
+                out.extend_from_within(from..from + match_len);
+            } else {
+                for k in 0..match_len {
+                    out.push(out[from + k]);
+                }
+            }
+        }
+        if bits.overflowed() {
+            return Err("corrupt zstd sequences");
+        }
+        out.extend_from_slice(&literals[literal_pos..]);
+        Ok(())
+    }
+}
+
+/// Replace `table` as `mode` says, reading a table description at `pos` if needed
+fn select_table(
+    table: &mut Option<FseTable>,
+    mode: usize,
+    data: &[u8],
+    pos: &mut usize,
+    (default, default_log): (&[i16], u32),
+    max_log: u32,
+) -> Result<()> {
+    match mode {
+        0 => *table = Some(FseTable::from_counts(default, default_log)?),
+        1 => {
+            let symbol = *data.get(*pos).ok_or(TRUNCATED)?;
+            *pos += 1;
+            *table = Some(FseTable { log: 0, states: vec![FseEntry { symbol, bits: 0, base: 0 }] });
+        }
+        2 => {
+            let (read, used) = FseTable::read(data.get(*pos..).ok_or(TRUNCATED)?, max_log, 52)?;
+            *pos += used;
+            *table = Some(read);
+        }
+        _ if table.is_some() => {}
+        _ => return Err("zstd sequences reuse a table that was never sent"),
+    }
+    Ok(())
+}
+
+/// Reads a bitstream from its end, as Huffman and FSE streams are stored.
+///
+/// The last byte holds a marker bit above the first bits to read. Reading
+/// past the start gives zeros and marks the stream as overflowed.
+struct BackwardBits<'a> {
+    data: &'a [u8],
+    /// Bits left to read, below this position
+    pos: isize,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let last = *data.last().ok_or(TRUNCATED)?;
+        if last == 0 {
+            return Err("zstd bitstream has no end marker");
+        }
+        Ok(Self { data, pos: (data.len() * 8) as isize - 1 - last.leading_zeros() as isize })
+    }
+
+    /// `len` bits starting at bit `start`, which must be in the data
+    fn extract(&self, start: usize, len: u32) -> u64 {
+        let mut word = [0u8; 8];
+        let from = start / 8;
+        let available = self.data.len().saturating_sub(from).min(8);
+        word[..available].copy_from_slice(&self.data[from..from + available]);
+        (u64::from_le_bytes(word) >> (start % 8)) & ((1u64 << len) - 1)
+    }
+
+    fn peek(&self, len: u32) -> u64 {
+        if len == 0 || self.pos <= 0 {
+            return 0;
+        }
+        let start = self.pos - len as isize;
+        if start >= 0 {
+            self.extract(start as usize, len)
+        } else {
+            self.extract(0, self.pos as u32) << -start
+        }
+    }
+
+    fn consume(&mut self, len: u32) {
+        self.pos -= len as isize;
+    }
+
+    fn read(&mut self, len: u32) -> u64 {
+        let value = self.peek(len);
+        self.consume(len);
+        value
+    }
+
+    fn overflowed(&self) -> bool {
+        self.pos < 0
+    }
+}
+
+/// Reads a bitstream from its start, least significant bits first
+struct ForwardBits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl ForwardBits<'_> {
+    fn peek(&self, len: u32) -> u32 {
+        (0..len).fold(0, |v, i| {
+            let bit = self.pos + i as usize;
+            let set = self.data.get(bit / 8).is_some_and(|b| b >> (bit % 8) & 1 != 0);
+            v | (set as u32) << i
+        })
+    }
+
+    fn consume(&mut self, len: u32) -> Result<()> {
+        self.pos += len as usize;
+        if self.pos > self.data.len() * 8 {
+            return Err(TRUNCATED);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, len: u32) -> Result<u32> {
+        let value = self.peek(len);
+        self.consume(len)?;
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FseEntry {
+    symbol: u8,
+    bits: u8,
+    base: u16,
+}
+
+/// A finite state entropy decoding table
+struct FseTable {
+    log: u32,
+    states: Vec<FseEntry>,
+}
+
+impl FseTable {
+    /// Read a table description; returns the table and the bytes it took
+    fn read(data: &[u8], max_log: u32, max_symbol: usize) -> Result<(Self, usize)> {
+        let mut bits = ForwardBits { data, pos: 0 };
+        let log = bits.read(4)? + 5;
+        if log > max_log {
+            return Err("zstd FSE table too large");
+        }
+        let mut remaining = (1i32 << log) + 1;
+        let mut threshold = 1i32 << log;
+        let mut len = log + 1;
+        let mut counts = Vec::new();
+        while remaining > 1 {
+            if counts.len() > max_symbol {
+                return Err("corrupt zstd FSE table");
+            }
+            let max = 2 * threshold - 1 - remaining;
+            let low = bits.peek(len - 1) as i32;
+            let value = if low < max {
+                bits.consume(len - 1)?;
+                low
+            } else {
+                let value = bits.read(len)? as i32;
+                if value >= threshold { value - max } else { value }
+            };
+            let count = value - 1;
+            remaining -= count.abs();
+            counts.push(count as i16);
+            if count == 0 {
+                loop {
+                    let repeat = bits.read(2)?;
+                    counts.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+            if remaining < 1 {
+                return Err("corrupt zstd FSE table");
+            }
+            while remaining < threshold {
+                len -= 1;
+                threshold >>= 1;
+            }
+        }
+        if counts.len() > max_symbol + 1 {
+            return Err("corrupt zstd FSE table");
+        }
+        Ok((Self::from_counts(&counts, log)?, bits.pos.div_ceil(8)))
+    }
+
+    /// Build the decoding table for normalized symbol counts
+    fn from_counts(counts: &[i16], log: u32) -> Result<Self> {
+        let size = 1usize << log;
+        let mut states = vec![FseEntry::default(); size];
+        let mut next = vec![0u32; counts.len()];
+        // "Less than one" symbols take the last cells
+        let mut high = size;
+        for (symbol, &count) in counts.iter().enumerate() {
+            if count == -1 {
+                high = high.checked_sub(1).ok_or("corrupt zstd FSE table")?;
+                states[high].symbol = symbol as u8;
+                next[symbol] = 1;
+            } else {
+                next[symbol] = count.max(0) as u32;
+            }
+        }
+        if counts.iter().map(|&c| c.max(0) as usize).sum::<usize>() != high {
+            return Err("corrupt zstd FSE table");
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut pos = 0;
+        for (symbol, &count) in counts.iter().enumerate() {
+            for _ in 0..count.max(0) {
+                states[pos].symbol = symbol as u8;
+                pos = (pos + step) & (size - 1);
+                while pos >= high {
+                    pos = (pos + step) & (size - 1);
+                }
+            }
+        }
+        if pos != 0 {
+            return Err("corrupt zstd FSE table");
+        }
+        for state in &mut states {
+            let n = next[state.symbol as usize];
+            next[state.symbol as usize] += 1;
+            let bits = log - (31 - n.leading_zeros());
+            state.bits = bits as u8;
+            state.base = ((n << bits) as usize - size) as u16;
+        }
+        Ok(Self { log, states })
+    }
+}
+
+/// A Huffman decoding table for literals, indexed by the next `max_bits` bits
+struct HuffmanTable {
+    max_bits: u32,
+    /// Symbol and code length
+    entries: Vec<(u8, u8)>,
+}
+
+impl HuffmanTable {
+    /// Read a tree description; returns the table and the bytes it took
+    fn read(data: &[u8]) -> Result<(Self, usize)> {
+        let header = *data.first().ok_or(TRUNCATED)? as usize;
+        let (mut weights, used) = if header < 128 {
+            let compressed = data.get(1..1 + header).ok_or(TRUNCATED)?;
+            (decode_weights(compressed)?, 1 + header)
+        } else {
+            let count = header - 127;
+            let packed = data.get(1..1 + count.div_ceil(2)).ok_or(TRUNCATED)?;
+            let weights = (0..count).map(|i| if i % 2 == 0 { packed[i / 2] >> 4 } else { packed[i / 2] & 15 }).collect();
+            (weights, 1 + count.div_ceil(2))
+        };
+        if weights.len() > 255 || weights.iter().any(|&w| w > 11) {
+            return Err("corrupt zstd Huffman table");
+        }
+        let total: u32 = weights.iter().filter(|&&w| w > 0).map(|&w| 1 << (w - 1)).sum();
+        if total == 0 {
+            return Err("corrupt zstd Huffman table");
+        }
+        // The last weight makes the total a power of two
+        let max_bits = 32 - total.leading_zeros();
+        let rest = (1 << max_bits) - total;
+        if max_bits > 11 || !rest.is_power_of_two() {
+            return Err("corrupt zstd Huffman table");
+        }
+        weights.push(rest.trailing_zeros() as u8 + 1);
+
+        let mut rank_start = [0usize; 13];
+        let mut pos = 0;
+        for (weight, start) in rank_start.iter_mut().enumerate().take(max_bits as usize + 1).skip(1) {
+            *start = pos;
+            pos += weights.iter().filter(|&&w| w as usize == weight).count() << (weight - 1);
+        }
+        let mut entries = vec![(0, 0); 1 << max_bits];
+        for (symbol, &weight) in weights.iter().enumerate().filter(|(_, &w)| w > 0) {
+            let len = 1 << (weight - 1);
+            let start = rank_start[weight as usize];
+            entries[start..start + len].fill((symbol as u8, (max_bits + 1 - weight as u32) as u8));
+            rank_start[weight as usize] += len;
+        }
+        Ok((Self { max_bits, entries }, used))
+    }
+
+    fn decode_stream(&self, data: &[u8], count: usize, out: &mut Vec<u8>) -> Result<()> {
+        let mut bits = BackwardBits::new(data)?;
+        for _ in 0..count {
+            let (symbol, len) = self.entries[bits.peek(self.max_bits) as usize];
+            bits.consume(len as u32);
+            out.push(symbol);
+        }
+        if bits.overflowed() {
+            return Err("corrupt zstd literals");
+        }
+        Ok(())
+    }
+}
+
+/// Decode FSE-compressed Huffman weights, two interleaved states sharing one stream
+fn decode_weights(data: &[u8]) -> Result<Vec<u8>> {
+    let (table, used) = FseTable::read(data, 6, 255)?;
+    let mut bits = BackwardBits::new(&data[used..])?;
+    let mut states = [bits.read(table.log) as usize, bits.read(table.log) as usize];
+    let mut weights = Vec::new();
+    for turn in (0..2).cycle() {
+        if weights.len() > 255 {
+            return Err("corrupt zstd Huffman table");
+        }
+        let entry = table.states[states[turn]];
+        weights.push(entry.symbol);
+        states[turn] = entry.base as usize + bits.read(entry.bits as u32) as usize;
+        if bits.overflowed() {
+            weights.push(table.states[states[1 - turn]].symbol);
+            break;
+        }
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A single-segment frame of `(block type, size, content)` blocks, the last marked final
+    fn frame(blocks: &[(u32, usize, &[u8])]) -> Vec<u8> {
+        let mut frame = MAGIC.to_le_bytes().to_vec();
+        frame.extend([0x20, 0]);
+        for (i, &(kind, size, content)) in blocks.iter().enumerate() {
+            let header = (size as u32) << 3 | kind << 1 | (i + 1 == blocks.len()) as u32;
+            frame.extend_from_slice(&header.to_le_bytes()[..3]);
+            frame.extend_from_slice(content);
+        }
+        frame
+    }
+
+    /// A compressed block holding a literals section and its sequences
+    fn compressed(literals: &[u8], sequences: &[u8]) -> Vec<u8> {
+        let block = [literals, sequences].concat();
+        frame(&[(2, block.len(), &block)])
+    }
+
+    /// A single-stream Huffman literals section of `regenerated` bytes
+    fn huffman_literals(regenerated: usize, data: &[u8]) -> Vec<u8> {
+        let header = 2 | (regenerated as u32) << 4 | (data.len() as u32) << 14;
+        [&header.to_le_bytes()[..3], data].concat()
+    }
+
+    /// One sequence from single-symbol tables: 2 literals, then a 3-byte
+    /// match whose offset code is `offset_symbol` with no extra bits set
+    fn one_sequence(offset_symbol: u8) -> Vec<u8> {
+        vec![1, 0x54, 2, offset_symbol, 0, 1 << offset_symbol]
+    }
+
+    #[test]
+    fn test_frames() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let mut stored = store(&data);
+        assert!(is_frame(&stored));
+        assert_eq!(decompress(&stored, usize::MAX).unwrap(), data);
+
+        // Stopping early skips the checksum, reading to the end doesn't
+        *stored.last_mut().unwrap() ^= 1;
+        assert_eq!(decompress(&stored, 10).unwrap(), &data[..MAX_BLOCK]);
+        assert_eq!(decompress(&stored, MAX_BLOCK + 1).unwrap(), &data[..2 * MAX_BLOCK]);
+        assert_eq!(decompress(&stored, usize::MAX).unwrap_err(), "zstd checksum mismatch");
+        assert_eq!(decompress(&stored[..stored.len() - 2], usize::MAX).unwrap_err(), TRUNCATED);
+
+        let skippable = [&0x184D_2A53u32.to_le_bytes()[..], &3u32.to_le_bytes(), b"xyz"].concat();
+        let rle_raw = frame(&[(1, 3, b"a"), (0, 2, b"bc")]);
+        assert_eq!(decompress(&[skippable, rle_raw.clone()].concat(), usize::MAX).unwrap(), b"aaabc");
+        // The limit also stops before the next frame
+        assert_eq!(decompress(&[rle_raw.clone(), rle_raw.clone()].concat(), 3).unwrap(), b"aaa");
+        assert_eq!(decompress(&[rle_raw.clone(), rle_raw].concat(), 4).unwrap(), b"aaabc");
+
+        assert_eq!(decompress(b"\x28\xb5\x2f\xfe\x20\0", usize::MAX).unwrap_err(), "not a zstd frame");
+        let mut reserved = frame(&[(3, 0, b"")]);
+        assert_eq!(decompress(&reserved, usize::MAX).unwrap_err(), "reserved zstd block type");
+        reserved[4] |= 0x08;
+        assert_eq!(decompress(&reserved, usize::MAX).unwrap_err(), "reserved bit set in zstd frame header");
+        let dictionary = [&MAGIC.to_le_bytes()[..], &[0x21, 7, 0]].concat();
+        assert_eq!(decompress(&dictionary, usize::MAX).unwrap_err(), "zstd dictionaries are not supported");
+    }
+
+    #[test]
+    fn test_huffman_literals() {
+        // One weight of 1 leaves room for a second: symbols 0 and 1, a bit each
+        let literals = huffman_literals(3, &[128, 0x10, 0b1101]);
+        assert_eq!(decompress(&compressed(&literals, &[0]), usize::MAX).unwrap(), [1, 0, 1]);
+        // Asking for more symbols than the stream holds
+        let literals = huffman_literals(4, &[128, 0x10, 0b1101]);
+        assert_eq!(decompress(&compressed(&literals, &[0]), usize::MAX).unwrap_err(), "corrupt zstd literals");
+
+        // Weights of 3 and 1 can't be made a power of two by one more weight
+        let literals = huffman_literals(1, &[129, 0x31]);
+        assert_eq!(decompress(&compressed(&literals, &[0]), usize::MAX).unwrap_err(), "corrupt zstd Huffman table");
+        let literals = huffman_literals(1, &[128, 0xC0]);
+        assert_eq!(decompress(&compressed(&literals, &[0]), usize::MAX).unwrap_err(), "corrupt zstd Huffman table");
+        let literals = huffman_literals(1, &[129, 0x00]);
+        assert_eq!(decompress(&compressed(&literals, &[0]), usize::MAX).unwrap_err(), "corrupt zstd Huffman table");
+
+        // A treeless section has nothing to reuse in the first block
+        let mut literals = huffman_literals(1, &[0x80]);
+        literals[0] |= 3;
+        assert_eq!(
+            decompress(&compressed(&literals, &[0]), usize::MAX).unwrap_err(),
+            "zstd literals reuse a Huffman table that was never sent"
+        );
+    }
+
+    #[test]
+    fn test_sequences() {
+        // Offset code 2 with no extra bits is 4, one byte back
+        assert_eq!(decompress(&compressed(&[2 << 3, b'a', b'b'], &one_sequence(2)), usize::MAX).unwrap(), b"abbbb");
+        // Offset code 5 is 29 bytes back, before the start of the output
+        assert_eq!(
+            decompress(&compressed(&[2 << 3, b'a', b'b'], &one_sequence(5)), usize::MAX).unwrap_err(),
+            "zstd match offset out of range"
+        );
+        // Or before the start of the frame, even with earlier output
+        let earlier = frame(&[(0, 40, &[b'x'; 40])]);
+        let block = compressed(&[2 << 3, b'a', b'b'], &one_sequence(5));
+        assert_eq!(decompress(&[earlier, block].concat(), usize::MAX).unwrap_err(), "zstd match offset out of range");
+
+        let mut repeat = one_sequence(2);
+        repeat[1] = 0xFC;
+        assert_eq!(
+            decompress(&compressed(&[2 << 3, b'a', b'b'], &repeat), usize::MAX).unwrap_err(),
+            "zstd sequences reuse a table that was never sent"
+        );
+        // An accuracy log of 5 + 15 is over the literal lengths limit of 9
+        assert_eq!(
+            decompress(&compressed(&[0], &[1, 0x80, 0x0F]), usize::MAX).unwrap_err(),
+            "zstd FSE table too large"
+        );
+        let mut unmarked = one_sequence(2);
+        *unmarked.last_mut().unwrap() = 0;
+        assert_eq!(
+            decompress(&compressed(&[2 << 3, b'a', b'b'], &unmarked), usize::MAX).unwrap_err(),
+            "zstd bitstream has no end marker"
+        );
+    }
+}